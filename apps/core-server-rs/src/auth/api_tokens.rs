@@ -2,10 +2,9 @@ use chrono::Utc;
 use sha2::{Digest, Sha256};
 use sqlx::types::Json as SqlJson;
 use sqlx::PgPool;
-use std::collections::HashSet;
 use uuid::Uuid;
 
-use crate::auth::AuthenticatedUser;
+use crate::auth::{scopes, AuthenticatedUser};
 use crate::error::{internal_error, AppError, AppResult};
 
 #[derive(sqlx::FromRow)]
@@ -63,12 +62,15 @@ pub(crate) async fn resolve_api_token(
         .name
         .clone()
         .unwrap_or_else(|| format!("api_token_{}", row.id));
-    let capabilities: HashSet<String> = row.capabilities.0.into_iter().collect();
+    let scopes = scopes::load(db, scopes::Principal::ApiToken(row.id)).await?;
+    let capabilities = super::global_capabilities(row.capabilities.0, &scopes);
     Ok(Some(AuthenticatedUser {
         id: format!("api_token:{}", row.id),
         email: display,
         role: "api_token".to_string(),
         capabilities,
         source: "api_token".to_string(),
+        session_id: None,
+        scopes,
     }))
 }
@@ -0,0 +1,372 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::security_events::{self, SecurityEvent};
+use super::SessionClient;
+use crate::error::{internal_error, AppError, AppResult};
+
+const SCOPE_ACCOUNT: &str = "account";
+const SCOPE_IP: &str = "ip";
+
+/// How failed logins for one key turn into delays and locks.
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    /// Failures allowed before any delay applies.
+    pub backoff_after: i32,
+    /// Failures at which the key is locked for `lock_duration`.
+    pub lock_after: i32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub lock_duration: Duration,
+    /// Quiet period after which the failure count starts over.
+    pub reset_after: Duration,
+}
+
+pub const ACCOUNT_POLICY: LockoutPolicy = LockoutPolicy {
+    backoff_after: 3,
+    lock_after: 10,
+    base_delay: Duration::seconds(1),
+    max_delay: Duration::minutes(5),
+    lock_duration: Duration::minutes(15),
+    reset_after: Duration::hours(1),
+};
+
+/// Looser than the account policy since several users can share an address.
+pub const IP_POLICY: LockoutPolicy = LockoutPolicy {
+    backoff_after: 10,
+    lock_after: 50,
+    base_delay: Duration::seconds(1),
+    max_delay: Duration::minutes(5),
+    lock_duration: Duration::minutes(15),
+    reset_after: Duration::hours(1),
+};
+
+#[derive(Debug, Clone, PartialEq)]
+struct CounterState {
+    failures: i32,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AccountLockout {
+    pub failures: i32,
+    pub last_failure_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+fn db_error(err: sqlx::Error) -> AppError {
+    let (status, message) = internal_error(err);
+    AppError::new(status, message)
+}
+
+fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Counter after one more failure, given the stored count and last failure time.
+fn next_failure(
+    policy: &LockoutPolicy,
+    previous: Option<(i32, DateTime<Utc>)>,
+    now: DateTime<Utc>,
+) -> CounterState {
+    let failures = match previous {
+        Some((failures, last_failure_at)) if now - last_failure_at < policy.reset_after => {
+            failures.saturating_add(1)
+        }
+        _ => 1,
+    };
+    let locked_until = if failures >= policy.lock_after {
+        Some(now + policy.lock_duration)
+    } else if failures > policy.backoff_after {
+        let exponent = (failures - policy.backoff_after - 1).min(20) as u32;
+        let delay = policy
+            .base_delay
+            .checked_mul(2_i32.pow(exponent))
+            .unwrap_or(policy.max_delay)
+            .min(policy.max_delay);
+        Some(now + delay)
+    } else {
+        None
+    };
+    CounterState {
+        failures,
+        locked_until,
+    }
+}
+
+/// Rejects the attempt while the account or client IP is backing off or locked.
+pub async fn check(db: &PgPool, email: &str, client: &SessionClient) -> AppResult<()> {
+    let locked_until: Option<DateTime<Utc>> = sqlx::query_scalar(
+        r#"
+        SELECT MAX(locked_until)
+        FROM login_attempt_counters
+        WHERE locked_until > NOW()
+          AND ((scope = $1 AND key = $2) OR (scope = $3 AND key = $4))
+        "#,
+    )
+    .bind(SCOPE_ACCOUNT)
+    .bind(account_key(email))
+    .bind(SCOPE_IP)
+    .bind(client.lockout_ip().unwrap_or(""))
+    .fetch_one(db)
+    .await
+    .map_err(db_error)?;
+    match locked_until {
+        Some(until) => {
+            let seconds = (until - Utc::now()).num_seconds().max(1);
+            Err(AppError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("Too many failed login attempts; try again in {seconds} seconds"),
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+async fn bump(
+    db: &PgPool,
+    scope: &str,
+    key: &str,
+    policy: &LockoutPolicy,
+) -> AppResult<CounterState> {
+    let now = Utc::now();
+    let mut tx = db.begin().await.map_err(db_error)?;
+    let previous: Option<(i32, DateTime<Utc>)> = sqlx::query_as(
+        r#"
+        SELECT failures, last_failure_at
+        FROM login_attempt_counters
+        WHERE scope = $1 AND key = $2
+        FOR UPDATE
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    let next = next_failure(policy, previous, now);
+    sqlx::query(
+        r#"
+        INSERT INTO login_attempt_counters (scope, key, failures, first_failure_at, last_failure_at, locked_until)
+        VALUES ($1, $2, $3, $4, $4, $5)
+        ON CONFLICT (scope, key) DO UPDATE SET
+            failures = EXCLUDED.failures,
+            first_failure_at = CASE
+                WHEN EXCLUDED.failures = 1 THEN EXCLUDED.first_failure_at
+                ELSE login_attempt_counters.first_failure_at
+            END,
+            last_failure_at = EXCLUDED.last_failure_at,
+            locked_until = EXCLUDED.locked_until
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(next.failures)
+    .bind(now)
+    .bind(next.locked_until)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+    Ok(next)
+}
+
+/// Counts a failed login against the account and the client IP and records the
+/// security events, including a lock event when a key crosses its lock threshold.
+pub async fn record_failure(
+    db: &PgPool,
+    email: &str,
+    user_id: Option<Uuid>,
+    client: &SessionClient,
+    reason: &str,
+) {
+    let key = account_key(email);
+    let account = match bump(db, SCOPE_ACCOUNT, &key, &ACCOUNT_POLICY).await {
+        Ok(state) => Some(state),
+        Err(err) => {
+            tracing::warn!(error = %err.message, "failed to count login failure");
+            None
+        }
+    };
+    let ip = match client.lockout_ip() {
+        Some(ip) => match bump(db, SCOPE_IP, ip, &IP_POLICY).await {
+            Ok(state) => Some(state),
+            Err(err) => {
+                tracing::warn!(error = %err.message, "failed to count login failure");
+                None
+            }
+        },
+        None => None,
+    };
+
+    SecurityEvent::new(security_events::LOGIN_FAILED)
+        .user(user_id, &key)
+        .client(client)
+        .details(json!({
+            "reason": reason,
+            "account_failures": account.as_ref().map(|state| state.failures),
+            "ip_failures": ip.as_ref().map(|state| state.failures),
+        }))
+        .record(db)
+        .await;
+
+    if let Some(state) = account
+        .as_ref()
+        .filter(|state| state.failures == ACCOUNT_POLICY.lock_after)
+    {
+        tracing::warn!(email = %key, "account locked after repeated failed logins");
+        SecurityEvent::new(security_events::ACCOUNT_LOCKED)
+            .user(user_id, &key)
+            .client(client)
+            .details(json!({
+                "failures": state.failures,
+                "locked_until": state.locked_until,
+            }))
+            .record(db)
+            .await;
+    }
+    if let Some(state) = ip
+        .as_ref()
+        .filter(|state| state.failures == IP_POLICY.lock_after)
+    {
+        tracing::warn!(ip = ?client.ip, "client locked after repeated failed logins");
+        SecurityEvent::new(security_events::IP_LOCKED)
+            .client(client)
+            .details(json!({
+                "failures": state.failures,
+                "locked_until": state.locked_until,
+            }))
+            .record(db)
+            .await;
+    }
+}
+
+/// Clears the account counter after a successful login. The IP counter is left
+/// alone so one valid account cannot be used to reset it.
+pub async fn record_success(db: &PgPool, email: &str) {
+    let result = sqlx::query("DELETE FROM login_attempt_counters WHERE scope = $1 AND key = $2")
+        .bind(SCOPE_ACCOUNT)
+        .bind(account_key(email))
+        .execute(db)
+        .await;
+    if let Err(err) = result {
+        tracing::warn!(error = %err, "failed to clear login failures");
+    }
+}
+
+pub async fn account_lockout(db: &PgPool, email: &str) -> AppResult<Option<AccountLockout>> {
+    sqlx::query_as(
+        r#"
+        SELECT failures, last_failure_at, locked_until
+        FROM login_attempt_counters
+        WHERE scope = $1 AND key = $2
+        "#,
+    )
+    .bind(SCOPE_ACCOUNT)
+    .bind(account_key(email))
+    .fetch_optional(db)
+    .await
+    .map_err(db_error)
+}
+
+/// Admin unlock: forgets the account's failures. Returns whether anything was cleared.
+pub async fn unlock_account(db: &PgPool, email: &str) -> AppResult<bool> {
+    let result = sqlx::query("DELETE FROM login_attempt_counters WHERE scope = $1 AND key = $2")
+        .bind(SCOPE_ACCOUNT)
+        .bind(account_key(email))
+        .execute(db)
+        .await
+        .map_err(db_error)?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Drops counters that have been quiet longer than any policy remembers them.
+pub async fn prune(db: &PgPool) -> AppResult<u64> {
+    let keep = ACCOUNT_POLICY.reset_after.max(IP_POLICY.reset_after);
+    let result = sqlx::query(
+        r#"
+        DELETE FROM login_attempt_counters
+        WHERE last_failure_at < $1
+          AND (locked_until IS NULL OR locked_until < NOW())
+        "#,
+    )
+    .bind(Utc::now() - keep)
+    .execute(db)
+    .await
+    .map_err(db_error)?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn after(policy: &LockoutPolicy, failures: i32, now: DateTime<Utc>) -> CounterState {
+        let mut previous = None;
+        let mut state = CounterState {
+            failures: 0,
+            locked_until: None,
+        };
+        for _ in 0..failures {
+            state = next_failure(policy, previous, now);
+            previous = Some((state.failures, now));
+        }
+        state
+    }
+
+    #[test]
+    fn failures_back_off_exponentially_then_lock() {
+        let now = Utc::now();
+        assert_eq!(after(&ACCOUNT_POLICY, 3, now).locked_until, None);
+        assert_eq!(
+            after(&ACCOUNT_POLICY, 4, now).locked_until,
+            Some(now + Duration::seconds(1))
+        );
+        assert_eq!(
+            after(&ACCOUNT_POLICY, 6, now).locked_until,
+            Some(now + Duration::seconds(4))
+        );
+        assert_eq!(
+            after(&ACCOUNT_POLICY, 10, now).locked_until,
+            Some(now + Duration::minutes(15))
+        );
+        // Past the lock threshold every failure re-locks.
+        assert_eq!(
+            after(&ACCOUNT_POLICY, 11, now).locked_until,
+            Some(now + Duration::minutes(15))
+        );
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = LockoutPolicy {
+            lock_after: 1_000,
+            ..ACCOUNT_POLICY
+        };
+        let now = Utc::now();
+        assert_eq!(
+            after(&policy, 40, now).locked_until,
+            Some(now + policy.max_delay)
+        );
+    }
+
+    #[test]
+    fn quiet_period_resets_the_count() {
+        let now = Utc::now();
+        let stale = now - ACCOUNT_POLICY.reset_after - Duration::seconds(1);
+        assert_eq!(
+            next_failure(&ACCOUNT_POLICY, Some((9, stale)), now),
+            CounterState {
+                failures: 1,
+                locked_until: None
+            }
+        );
+        assert_eq!(
+            next_failure(&ACCOUNT_POLICY, Some((9, now)), now).failures,
+            10
+        );
+    }
+}
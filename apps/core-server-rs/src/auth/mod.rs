@@ -1,25 +1,53 @@
 pub(crate) mod api_tokens;
+pub(crate) mod lockout;
+pub(crate) mod oidc;
 mod password;
+pub(crate) mod scopes;
+pub(crate) mod security_events;
+pub(crate) mod sessions;
+pub(crate) mod totp;
 
 use axum::extract::{FromRef, FromRequestParts};
 use axum::http::header::AUTHORIZATION;
 use axum::http::request::Parts;
-use base64::engine::general_purpose::URL_SAFE_NO_PAD;
-use base64::Engine;
-use chrono::{DateTime, Duration as ChronoDuration, Utc};
-use rand::rngs::OsRng;
-use rand::RngCore;
+use chrono::Duration as ChronoDuration;
 use sqlx::types::Json as SqlJson;
 use sqlx::PgPool;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
 
 pub use password::{hash_password, verify_password};
+pub use scopes::NodeScope;
+pub use sessions::{IssuedSession, ResolvedSession, SessionClient};
+
+const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+/// Every capability a route checks; anything else in a grant would never match.
+pub const KNOWN_CAPABILITIES: &[&str] = &[
+    "alerts.ack",
+    "alerts.view",
+    "analysis.run",
+    "analysis.view",
+    "analytics.view",
+    "audit.view",
+    "backups.view",
+    "config.view",
+    "config.write",
+    "metrics.ingest",
+    "metrics.view",
+    "nodes.view",
+    "outputs.command",
+    "outputs.view",
+    "schedules.view",
+    "schedules.write",
+    "sensors.view",
+    "setup.credentials.view",
+    "users.manage",
+];
 
 pub fn canonicalize_role(role: &str) -> String {
     let trimmed = role.trim().to_lowercase();
@@ -38,79 +66,84 @@ pub struct AuthenticatedUser {
     pub role: String,
     pub capabilities: HashSet<String>,
     pub source: String,
+    /// Login session behind the request; `None` for API tokens and service users.
+    pub session_id: Option<Uuid>,
+    /// Capabilities bound to specific nodes, node groups or map layers.
+    pub scopes: scopes::ResourceScopes,
 }
 
 impl AuthenticatedUser {
     pub fn user_id(&self) -> Option<Uuid> {
         Uuid::parse_str(&self.id).ok()
     }
+
+    /// Whether `capability` is held, globally or through a grant on some resource.
+    pub fn holds(&self, capability: &str) -> bool {
+        self.capabilities.contains(capability) || self.scopes.is_scoped(capability)
+    }
+
+    /// Nodes reachable through whichever of `options` the user holds.
+    pub fn node_scope(&self, options: &[&str]) -> NodeScope {
+        self.scopes
+            .node_scope(options.iter().copied().filter(|cap| self.holds(cap)))
+    }
 }
 
-#[derive(Debug)]
-struct SessionEntry {
-    user_id: Uuid,
-    source: String,
-    expires_at: DateTime<Utc>,
+/// Capabilities held without a resource scope. A scoped grant only confers its
+/// capability on the covered nodes, so it never lands here and a role
+/// capability that is also granted on resources is narrowed to them.
+pub(crate) fn global_capabilities(
+    granted: Vec<String>,
+    scopes: &scopes::ResourceScopes,
+) -> HashSet<String> {
+    granted
+        .into_iter()
+        .filter(|cap| !scopes.is_scoped(cap))
+        .collect()
 }
 
+/// Login sessions live in `auth_sessions` so they survive restarts and can be
+/// listed and revoked; this holds the token lifetimes.
 #[derive(Debug)]
 pub struct AuthManager {
-    sessions: RwLock<HashMap<String, SessionEntry>>,
     ttl: ChronoDuration,
+    refresh_ttl: ChronoDuration,
 }
 
 impl AuthManager {
     pub fn new(token_ttl_hours: i64) -> Self {
         Self {
-            sessions: RwLock::new(HashMap::new()),
             ttl: ChronoDuration::hours(token_ttl_hours),
+            refresh_ttl: ChronoDuration::days(REFRESH_TOKEN_TTL_DAYS),
         }
     }
 
-    pub async fn issue_for_user(&self, user_id: Uuid, source: String) -> String {
-        let mut buf = [0u8; 32];
-        OsRng.fill_bytes(&mut buf);
-        let token = URL_SAFE_NO_PAD.encode(buf);
-        let expires_at = Utc::now() + self.ttl;
-        let mut sessions = self.sessions.write().await;
-        sessions.insert(
-            token.clone(),
-            SessionEntry {
-                user_id,
-                source,
-                expires_at,
-            },
-        );
-        token
+    pub async fn issue_for_user(
+        &self,
+        db: &PgPool,
+        user_id: Uuid,
+        source: &str,
+        client: &SessionClient,
+    ) -> AppResult<IssuedSession> {
+        sessions::insert_session(db, user_id, source, client, self.ttl, self.refresh_ttl).await
     }
 
-    pub async fn resolve(&self, token: &str) -> Option<(Uuid, String)> {
-        let mut sessions = self.sessions.write().await;
-        let entry = sessions.get(token)?;
-        if entry.expires_at <= Utc::now() {
-            sessions.remove(token);
-            return None;
-        }
-        Some((entry.user_id, entry.source.clone()))
+    pub async fn resolve(&self, db: &PgPool, token: &str) -> AppResult<Option<ResolvedSession>> {
+        sessions::resolve_session(db, token).await
     }
 
-    pub async fn prune_expired(&self) -> usize {
-        let mut sessions = self.sessions.write().await;
-        let now = Utc::now();
-        let expired: Vec<String> = sessions
-            .iter()
-            .filter_map(|(token, entry)| {
-                if entry.expires_at <= now {
-                    Some(token.clone())
-                } else {
-                    None
-                }
-            })
-            .collect();
-        for token in &expired {
-            sessions.remove(token);
-        }
-        expired.len()
+    /// Rotates a refresh token. Returns the new tokens and the session's user.
+    pub async fn refresh(
+        &self,
+        db: &PgPool,
+        refresh_token: &str,
+        client: &SessionClient,
+    ) -> AppResult<Option<(IssuedSession, Uuid)>> {
+        sessions::rotate_session(db, refresh_token, client, self.ttl, self.refresh_ttl).await
+    }
+
+    pub async fn prune_expired(&self, db: &PgPool) -> AppResult<u64> {
+        sessions::prune_sessions(db).await
     }
 }
 
@@ -143,8 +176,8 @@ where
 
         async move {
             let token = token_result?;
-            let user = if let Some((user_id, source)) = manager.resolve(&token).await {
-                resolve_user_from_db(&db, user_id, &source).await?
+            let user = if let Some(session) = manager.resolve(&db, &token).await? {
+                resolve_user_from_db(&db, &session).await?
             } else {
                 api_tokens::resolve_api_token(&db, &token)
                     .await?
@@ -190,8 +223,8 @@ where
             let Some(token) = token_result? else {
                 return Ok(OptionalAuthUser(None));
             };
-            let user = if let Some((user_id, source)) = manager.resolve(&token).await {
-                resolve_user_from_db(&db, user_id, &source).await?
+            let user = if let Some(session) = manager.resolve(&db, &token).await? {
+                resolve_user_from_db(&db, &session).await?
             } else {
                 api_tokens::resolve_api_token(&db, &token)
                     .await?
@@ -208,22 +241,29 @@ struct UserAuthRow {
     email: String,
     role: String,
     capabilities: SqlJson<Vec<String>>,
+    totp_pending_enrollment: bool,
 }
 
 async fn resolve_user_from_db(
     db: &PgPool,
-    user_id: Uuid,
-    source: &str,
+    session: &ResolvedSession,
 ) -> AppResult<AuthenticatedUser> {
     let row: Option<UserAuthRow> = sqlx::query_as(
         r#"
-        SELECT id, email, role, capabilities
-        FROM users
-        WHERE id = $1
+        SELECT
+            u.id,
+            u.email,
+            u.role,
+            u.capabilities,
+            (COALESCE(p.require_totp, FALSE) AND u.totp_enabled_at IS NULL)
+                AS totp_pending_enrollment
+        FROM users u
+        LEFT JOIN auth_role_policies p ON p.role = u.role
+        WHERE u.id = $1
         LIMIT 1
         "#,
     )
-    .bind(user_id)
+    .bind(session.user_id)
     .fetch_optional(db)
     .await
     .map_err(|err| {
@@ -233,33 +273,51 @@ async fn resolve_user_from_db(
 
     let row = row.ok_or_else(|| AppError::unauthorized("Missing or invalid token"))?;
 
-    let mut capabilities: HashSet<String> = row.capabilities.0.into_iter().collect();
-    if capabilities.contains("config.write") {
+    let mut scopes = scopes::load(db, scopes::Principal::User(row.id)).await?;
+    let mut capabilities = global_capabilities(row.capabilities.0, &scopes);
+    if capabilities.contains("config.write") && !scopes.is_scoped("config.view") {
         capabilities.insert("config.view".to_string());
     }
+    // Until a user whose role requires 2FA enrolls, the session is only good for
+    // /api/auth/me and the TOTP enrollment endpoints. SSO logins leave the second
+    // factor to the identity provider.
+    if row.totp_pending_enrollment && session.source != oidc::SESSION_SOURCE {
+        capabilities.clear();
+        scopes = scopes::ResourceScopes::default();
+    }
 
     Ok(AuthenticatedUser {
         id: row.id.to_string(),
         email: row.email,
         role: canonicalize_role(&row.role),
         capabilities,
-        source: source.to_string(),
+        source: session.source.clone(),
+        session_id: Some(session.session_id),
+        scopes,
     })
 }
 
+/// Capabilities held for every node. Resource-scoped grants do not satisfy this;
+/// node-bound actions go through [`require_node_capabilities`].
 pub fn require_capabilities(user: &AuthenticatedUser, required: &[&str]) -> AppResult<()> {
-    let missing: Vec<&str> = required
+    let (scoped, missing): (Vec<&str>, Vec<&str>) = required
         .iter()
         .copied()
-        .filter(|cap| !user.capabilities.contains(*cap))
-        .collect();
-    if missing.is_empty() {
-        return Ok(());
+        .filter(|cap| !user.capabilities.contains(*cap) || user.scopes.is_scoped(cap))
+        .partition(|cap| user.scopes.is_scoped(cap));
+    if !missing.is_empty() {
+        return Err(AppError::forbidden(format!(
+            "Missing capabilities: {}",
+            missing.join(", ")
+        )));
     }
-    Err(AppError::forbidden(format!(
-        "Missing capabilities: {}",
-        missing.join(", ")
-    )))
+    if !scoped.is_empty() {
+        return Err(AppError::forbidden(format!(
+            "Capabilities limited to specific nodes: {}",
+            scoped.join(", ")
+        )));
+    }
+    Ok(())
 }
 
 pub fn require_any_capabilities(user: &AuthenticatedUser, options: &[&str]) -> AppResult<()> {
@@ -267,7 +325,7 @@ pub fn require_any_capabilities(user: &AuthenticatedUser, options: &[&str]) -> A
         return Ok(());
     }
     for cap in options {
-        if user.capabilities.contains(*cap) {
+        if user.capabilities.contains(*cap) && !user.scopes.is_scoped(cap) {
             return Ok(());
         }
     }
@@ -277,6 +335,50 @@ pub fn require_any_capabilities(user: &AuthenticatedUser, options: &[&str]) -> A
     )))
 }
 
+/// For endpoints that filter their results with [`AuthenticatedUser::node_scope`]:
+/// one of `options` must be held, globally or for some nodes.
+pub fn require_any_node_capabilities(user: &AuthenticatedUser, options: &[&str]) -> AppResult<()> {
+    if options.is_empty() || options.iter().any(|cap| user.holds(cap)) {
+        return Ok(());
+    }
+    Err(AppError::forbidden(format!(
+        "Missing capabilities: one of {}",
+        options.join(", ")
+    )))
+}
+
+/// `require_capabilities` for an action on one node: every capability must be
+/// held globally or granted for that node.
+pub fn require_node_capabilities(
+    user: &AuthenticatedUser,
+    required: &[&str],
+    node_id: Uuid,
+) -> AppResult<()> {
+    let missing: Vec<&str> = required
+        .iter()
+        .copied()
+        .filter(|cap| !user.holds(cap))
+        .collect();
+    if !missing.is_empty() {
+        return Err(AppError::forbidden(format!(
+            "Missing capabilities: {}",
+            missing.join(", ")
+        )));
+    }
+    let denied: Vec<&str> = required
+        .iter()
+        .copied()
+        .filter(|cap| !user.node_scope(&[*cap]).allows(node_id))
+        .collect();
+    if denied.is_empty() {
+        return Ok(());
+    }
+    Err(AppError::forbidden(format!(
+        "Capabilities not granted for node {node_id}: {}",
+        denied.join(", ")
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -290,6 +392,8 @@ mod tests {
             role: "view".to_string(),
             capabilities: HashSet::new(),
             source: "test".to_string(),
+            session_id: None,
+            scopes: Default::default(),
         };
 
         let err = require_capabilities(&user, &["analysis.run"]).unwrap_err();
@@ -307,6 +411,8 @@ mod tests {
             role: "operator".to_string(),
             capabilities: caps,
             source: "test".to_string(),
+            session_id: None,
+            scopes: Default::default(),
         };
 
         assert!(require_capabilities(&user, &["analysis.run"]).is_ok());
@@ -320,6 +426,8 @@ mod tests {
             role: "operator".to_string(),
             capabilities: HashSet::new(),
             source: "test".to_string(),
+            session_id: None,
+            scopes: Default::default(),
         };
 
         assert!(user.user_id().is_some());
@@ -333,6 +441,8 @@ mod tests {
             role: "api_token".to_string(),
             capabilities: HashSet::new(),
             source: "api_token".to_string(),
+            session_id: None,
+            scopes: Default::default(),
         };
 
         assert!(user.user_id().is_none());
@@ -348,6 +458,8 @@ mod tests {
             role: "view".to_string(),
             capabilities: caps,
             source: "test".to_string(),
+            session_id: None,
+            scopes: Default::default(),
         };
 
         assert!(require_any_capabilities(&user, &["b", "a"]).is_ok());
@@ -361,10 +473,80 @@ mod tests {
             role: "view".to_string(),
             capabilities: HashSet::new(),
             source: "test".to_string(),
+            session_id: None,
+            scopes: Default::default(),
         };
 
         let err = require_any_capabilities(&user, &["metrics.view", "config.write"]).unwrap_err();
         assert_eq!(err.status, axum::http::StatusCode::FORBIDDEN);
         assert!(err.message.contains("metrics.view"));
     }
+
+    #[test]
+    fn require_node_capabilities_honors_scoped_grants() {
+        let greenhouse = Uuid::new_v4();
+        let well = Uuid::new_v4();
+        let scopes = scopes::ResourceScopes::from_grants([(
+            "outputs.command".to_string(),
+            Some(greenhouse),
+        )]);
+        let user = AuthenticatedUser {
+            id: "user-1".to_string(),
+            email: "contractor@example.com".to_string(),
+            role: "view".to_string(),
+            capabilities: global_capabilities(
+                vec!["outputs.view".to_string(), "outputs.command".to_string()],
+                &scopes,
+            ),
+            source: "test".to_string(),
+            session_id: None,
+            scopes,
+        };
+
+        assert!(require_node_capabilities(&user, &["outputs.command"], greenhouse).is_ok());
+        let err = require_node_capabilities(&user, &["outputs.command"], well).unwrap_err();
+        assert_eq!(err.status, axum::http::StatusCode::FORBIDDEN);
+        assert!(err.message.contains("outputs.command"));
+        assert!(require_node_capabilities(&user, &["outputs.view"], well).is_ok());
+        assert!(require_capabilities(&user, &["outputs.view"]).is_ok());
+        assert!(require_capabilities(&user, &["outputs.command"]).is_err());
+        assert!(require_any_capabilities(&user, &["outputs.command"]).is_err());
+        assert!(require_any_node_capabilities(&user, &["outputs.command"]).is_ok());
+    }
+
+    #[test]
+    fn role_defaults_are_known_capabilities() {
+        for role in ["admin", "operator", "view"] {
+            for capability in crate::routes::users::default_capabilities_for_role(role) {
+                assert!(
+                    KNOWN_CAPABILITIES.contains(&capability.as_str()),
+                    "{role} default {capability} is not a known capability"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn single_node_config_write_grant_is_refused_on_global_endpoints() {
+        let barn = Uuid::new_v4();
+        let scopes =
+            scopes::ResourceScopes::from_grants([("config.write".to_string(), Some(barn))]);
+        let user = AuthenticatedUser {
+            id: "user-1".to_string(),
+            email: "contractor@example.com".to_string(),
+            role: "view".to_string(),
+            capabilities: global_capabilities(vec!["nodes.view".to_string()], &scopes),
+            source: "test".to_string(),
+            session_id: None,
+            scopes,
+        };
+
+        assert!(!user.capabilities.contains("config.write"));
+        assert!(!user.capabilities.contains("config.view"));
+        let err = require_capabilities(&user, &["config.write"]).unwrap_err();
+        assert_eq!(err.status, axum::http::StatusCode::FORBIDDEN);
+        assert!(require_any_capabilities(&user, &["config.write", "users.manage"]).is_err());
+        assert!(require_node_capabilities(&user, &["config.write"], barn).is_ok());
+        assert!(require_node_capabilities(&user, &["config.write"], Uuid::new_v4()).is_err());
+    }
 }
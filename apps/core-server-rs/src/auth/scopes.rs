@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::{internal_error, AppError, AppResult};
+
+pub const RESOURCE_NODE: &str = "node";
+pub const RESOURCE_NODE_GROUP: &str = "node_group";
+pub const RESOURCE_MAP_LAYER: &str = "map_layer";
+
+/// Whose grants to load.
+#[derive(Debug, Clone, Copy)]
+pub enum Principal {
+    User(Uuid),
+    ApiToken(Uuid),
+}
+
+/// Capabilities that are bound to resources, resolved to the node ids their
+/// grants cover. A capability missing from the map is unrestricted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceScopes {
+    nodes: HashMap<String, HashSet<Uuid>>,
+}
+
+/// The nodes a request may touch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeScope {
+    All,
+    Only(HashSet<Uuid>),
+}
+
+impl NodeScope {
+    pub fn allows(&self, node_id: Uuid) -> bool {
+        match self {
+            NodeScope::All => true,
+            NodeScope::Only(nodes) => nodes.contains(&node_id),
+        }
+    }
+
+    /// Same as `allows` for ids kept as strings in responses; an unparsable id
+    /// is only allowed when the scope is unrestricted.
+    pub fn allows_str(&self, node_id: &str) -> bool {
+        match self {
+            NodeScope::All => true,
+            NodeScope::Only(_) => Uuid::parse_str(node_id.trim()).is_ok_and(|id| self.allows(id)),
+        }
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        matches!(self, NodeScope::Only(_))
+    }
+
+    /// Node ids for an `= ANY($n)` filter; `None` when unrestricted.
+    pub fn node_ids(&self) -> Option<Vec<Uuid>> {
+        match self {
+            NodeScope::All => None,
+            NodeScope::Only(nodes) => Some(nodes.iter().copied().collect()),
+        }
+    }
+}
+
+impl ResourceScopes {
+    pub fn from_grants(grants: impl IntoIterator<Item = (String, Option<Uuid>)>) -> Self {
+        let mut nodes: HashMap<String, HashSet<Uuid>> = HashMap::new();
+        for (capability, node_id) in grants {
+            // A grant whose group or layer is empty still restricts the capability.
+            let entry = nodes.entry(capability).or_default();
+            if let Some(node_id) = node_id {
+                entry.insert(node_id);
+            }
+        }
+        Self { nodes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn is_scoped(&self, capability: &str) -> bool {
+        self.nodes.contains_key(capability)
+    }
+
+    pub fn scoped_capabilities(&self) -> impl Iterator<Item = &str> {
+        self.nodes.keys().map(String::as_str)
+    }
+
+    /// Nodes reachable through any of `held`, which the caller has already
+    /// narrowed to capabilities the principal has.
+    pub fn node_scope<'a>(&self, held: impl IntoIterator<Item = &'a str>) -> NodeScope {
+        let mut union = HashSet::new();
+        for capability in held {
+            match self.nodes.get(capability) {
+                None => return NodeScope::All,
+                Some(nodes) => union.extend(nodes.iter().copied()),
+            }
+        }
+        NodeScope::Only(union)
+    }
+}
+
+fn db_error(err: sqlx::Error) -> AppError {
+    let (status, message) = internal_error(err);
+    AppError::new(status, message)
+}
+
+/// Loads a principal's grants with node groups and map layers expanded to nodes.
+pub async fn load(db: &PgPool, principal: Principal) -> AppResult<ResourceScopes> {
+    let (user_id, api_token_id) = match principal {
+        Principal::User(id) => (Some(id), None),
+        Principal::ApiToken(id) => (None, Some(id)),
+    };
+    let rows: Vec<(String, Option<Uuid>)> = sqlx::query_as(
+        r#"
+        SELECT g.capability, covered.node_id
+        FROM capability_grants g
+        LEFT JOIN LATERAL (
+            SELECT n.id AS node_id
+            FROM nodes n
+            WHERE g.resource_type = 'node' AND n.id::text = g.resource_id
+            UNION
+            SELECT m.node_id
+            FROM node_group_members m
+            WHERE g.resource_type = 'node_group' AND m.group_id::text = g.resource_id
+            UNION
+            SELECT l.node_id
+            FROM map_layer_nodes l
+            WHERE g.resource_type = 'map_layer' AND l.layer_id::text = g.resource_id
+        ) covered ON TRUE
+        WHERE ($1::uuid IS NOT NULL AND g.user_id = $1)
+           OR ($2::uuid IS NOT NULL AND g.api_token_id = $2)
+        "#,
+    )
+    .bind(user_id)
+    .bind(api_token_id)
+    .fetch_all(db)
+    .await
+    .map_err(db_error)?;
+    Ok(ResourceScopes::from_grants(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unscoped_capability_reaches_every_node() {
+        let node = Uuid::new_v4();
+        let scopes = ResourceScopes::from_grants([("outputs.command".to_string(), Some(node))]);
+        assert_eq!(scopes.node_scope(["outputs.view"]), NodeScope::All);
+        assert_eq!(
+            scopes.node_scope(["outputs.command", "outputs.view"]),
+            NodeScope::All
+        );
+    }
+
+    #[test]
+    fn scoped_capabilities_union_their_nodes() {
+        let greenhouse = Uuid::new_v4();
+        let barn = Uuid::new_v4();
+        let well = Uuid::new_v4();
+        let scopes = ResourceScopes::from_grants([
+            ("outputs.command".to_string(), Some(greenhouse)),
+            ("config.write".to_string(), Some(barn)),
+        ]);
+        let scope = scopes.node_scope(["outputs.command", "config.write"]);
+        assert!(scope.allows(greenhouse));
+        assert!(scope.allows(barn));
+        assert!(!scope.allows(well));
+        assert!(scope.allows_str(&greenhouse.to_string()));
+        assert!(!scope.allows_str("not-a-uuid"));
+    }
+
+    #[test]
+    fn empty_group_grant_still_restricts() {
+        let scopes = ResourceScopes::from_grants([("sensors.view".to_string(), None)]);
+        assert!(scopes.is_scoped("sensors.view"));
+        assert_eq!(
+            scopes.node_scope(["sensors.view"]),
+            NodeScope::Only(HashSet::new())
+        );
+    }
+
+    #[test]
+    fn holding_nothing_reaches_nothing() {
+        let scopes = ResourceScopes::default();
+        assert_eq!(
+            scopes.node_scope(std::iter::empty()),
+            NodeScope::Only(HashSet::new())
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use serde_json::Value;
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{AuthenticatedUser, SessionClient};
+
+pub const LOGIN_FAILED: &str = "login_failed";
+pub const ACCOUNT_LOCKED: &str = "account_locked";
+pub const IP_LOCKED: &str = "ip_locked";
+pub const ACCOUNT_UNLOCKED: &str = "account_unlocked";
+pub const PASSWORD_CHANGED: &str = "password_changed";
+pub const TOTP_ENABLED: &str = "totp_enabled";
+pub const TOTP_DISABLED: &str = "totp_disabled";
+pub const RECOVERY_CODES_REGENERATED: &str = "recovery_codes_regenerated";
+
+/// One row for `security_events`. Recording is best-effort: a failed insert is
+/// logged and never fails the request that triggered it.
+#[derive(Debug, Clone)]
+pub struct SecurityEvent<'a> {
+    event_type: &'static str,
+    user_id: Option<Uuid>,
+    email: Option<&'a str>,
+    client: Option<&'a SessionClient>,
+    actor: Option<&'a AuthenticatedUser>,
+    details: Value,
+}
+
+impl<'a> SecurityEvent<'a> {
+    pub fn new(event_type: &'static str) -> Self {
+        Self {
+            event_type,
+            user_id: None,
+            email: None,
+            client: None,
+            actor: None,
+            details: Value::Object(Default::default()),
+        }
+    }
+
+    pub fn user(mut self, user_id: Option<Uuid>, email: &'a str) -> Self {
+        self.user_id = user_id;
+        self.email = Some(email);
+        self
+    }
+
+    pub fn client(mut self, client: &'a SessionClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn actor(mut self, actor: &'a AuthenticatedUser) -> Self {
+        self.actor = Some(actor);
+        self
+    }
+
+    pub fn details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    pub async fn record(self, db: &PgPool) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO security_events (
+                event_type, user_id, email, client_ip, user_agent, actor_id, actor_email, details
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(self.event_type)
+        .bind(self.user_id)
+        .bind(self.email)
+        .bind(self.client.and_then(|client| client.ip.as_deref()))
+        .bind(self.client.and_then(|client| client.user_agent.as_deref()))
+        .bind(self.actor.map(|actor| actor.id.as_str()))
+        .bind(self.actor.map(|actor| actor.email.as_str()))
+        .bind(SqlJson(&self.details))
+        .execute(db)
+        .await;
+        if let Err(err) = result {
+            tracing::warn!(error = %err, event_type = self.event_type, "failed to record security event");
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use axum::http::header::USER_AGENT;
+use axum::http::HeaderMap;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sqlx::PgPool;
+use std::net::{IpAddr, SocketAddr};
+use uuid::Uuid;
+
+use super::api_tokens::api_token_hash;
+use crate::error::{internal_error, AppError, AppResult};
+
+const MAX_USER_AGENT_LEN: usize = 512;
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+/// `last_used_at` is only written when it is older than this, so authenticated
+/// requests do not each turn into a row update.
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+/// Sessions are kept this long past refresh expiry so recent logins stay visible.
+const PRUNE_GRACE_DAYS: i64 = 7;
+
+pub const REVOKE_LOGOUT: &str = "logout";
+pub const REVOKE_USER: &str = "revoked_by_user";
+pub const REVOKE_ADMIN: &str = "revoked_by_admin";
+pub const REVOKE_PERMISSIONS_CHANGED: &str = "permissions_changed";
+pub const REVOKE_PASSWORD_CHANGED: &str = "password_changed";
+pub const REVOKE_REFRESH_REUSE: &str = "refresh_token_reuse";
+pub const REVOKE_SECOND_FACTOR_RESET: &str = "second_factor_reset";
+
+/// Where a session was created from, recorded for the session list.
+#[derive(Debug, Clone, Default)]
+pub struct SessionClient {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// `ip` is a trusted proxy that forwarded no client address, so it is shared by
+    /// every user behind it and must not be locked out.
+    pub shared_ip: bool,
+}
+
+impl SessionClient {
+    /// Takes the client IP from `X-Forwarded-For` when `peer` is one of `trusted_proxies`:
+    /// the rightmost address that is not itself a trusted proxy.
+    pub fn from_request(peer: SocketAddr, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> Self {
+        let user_agent = headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LEN).collect());
+        let peer_ip = peer.ip().to_canonical();
+        if !trusted_proxies.contains(&peer_ip) {
+            return Self {
+                ip: Some(peer_ip.to_string()),
+                user_agent,
+                shared_ip: false,
+            };
+        }
+        let forwarded = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|hop| hop.trim().parse::<IpAddr>().map(|ip| ip.to_canonical()))
+            .collect::<Vec<_>>();
+        // Stop at the first unparsable hop: anything left of it was written by the client.
+        let client_ip = forwarded
+            .into_iter()
+            .rev()
+            .map_while(Result::ok)
+            .find(|ip| !trusted_proxies.contains(ip));
+        match client_ip {
+            Some(ip) => Self {
+                ip: Some(ip.to_string()),
+                user_agent,
+                shared_ip: false,
+            },
+            None => Self {
+                ip: Some(peer_ip.to_string()),
+                user_agent,
+                shared_ip: true,
+            },
+        }
+    }
+
+    /// Key for the per-IP login counter; `None` when the IP is a shared proxy address.
+    pub fn lockout_ip(&self) -> Option<&str> {
+        if self.shared_ip {
+            None
+        } else {
+            self.ip.as_deref()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IssuedSession {
+    pub session_id: Uuid,
+    pub token: String,
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+    pub refresh_expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedSession {
+    pub session_id: Uuid,
+    pub user_id: Uuid,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub(crate) struct SessionRow {
+    pub id: Uuid,
+    pub source: String,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub refresh_expires_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ResolveRow {
+    id: Uuid,
+    user_id: Uuid,
+    source: String,
+    last_used_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct RefreshRow {
+    id: Uuid,
+    user_id: Uuid,
+    refresh_token_hash: String,
+    revoked_at: Option<DateTime<Utc>>,
+    refresh_expires_at: DateTime<Utc>,
+}
+
+fn db_error(err: sqlx::Error) -> AppError {
+    let (status, message) = internal_error(err);
+    AppError::new(status, message)
+}
+
+pub(crate) fn generate_token() -> String {
+    let mut buf = [0u8; 32];
+    OsRng.fill_bytes(&mut buf);
+    URL_SAFE_NO_PAD.encode(buf)
+}
+
+pub(super) async fn insert_session(
+    db: &PgPool,
+    user_id: Uuid,
+    source: &str,
+    client: &SessionClient,
+    ttl: ChronoDuration,
+    refresh_ttl: ChronoDuration,
+) -> AppResult<IssuedSession> {
+    let token = generate_token();
+    let refresh_token = generate_token();
+    let now = Utc::now();
+    let expires_at = now + ttl;
+    let refresh_expires_at = now + refresh_ttl;
+    let session_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO auth_sessions (
+            user_id,
+            source,
+            token_hash,
+            refresh_token_hash,
+            client_ip,
+            user_agent,
+            created_at,
+            last_used_at,
+            expires_at,
+            refresh_expires_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $8, $9)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(source)
+    .bind(api_token_hash(&token))
+    .bind(api_token_hash(&refresh_token))
+    .bind(client.ip.as_deref())
+    .bind(client.user_agent.as_deref())
+    .bind(now)
+    .bind(expires_at)
+    .bind(refresh_expires_at)
+    .fetch_one(db)
+    .await
+    .map_err(db_error)?;
+
+    Ok(IssuedSession {
+        session_id,
+        token,
+        refresh_token,
+        expires_at,
+        refresh_expires_at,
+    })
+}
+
+pub(super) async fn resolve_session(
+    db: &PgPool,
+    token: &str,
+) -> AppResult<Option<ResolvedSession>> {
+    let row: Option<ResolveRow> = sqlx::query_as(
+        r#"
+        SELECT id, user_id, source, last_used_at
+        FROM auth_sessions
+        WHERE token_hash = $1
+          AND revoked_at IS NULL
+          AND expires_at > NOW()
+        LIMIT 1
+        "#,
+    )
+    .bind(api_token_hash(token))
+    .fetch_optional(db)
+    .await
+    .map_err(db_error)?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    if Utc::now() - row.last_used_at > ChronoDuration::seconds(LAST_USED_RESOLUTION_SECONDS) {
+        let _ = sqlx::query("UPDATE auth_sessions SET last_used_at = NOW() WHERE id = $1")
+            .bind(row.id)
+            .execute(db)
+            .await;
+    }
+    Ok(Some(ResolvedSession {
+        session_id: row.id,
+        user_id: row.user_id,
+        source: row.source,
+    }))
+}
+
+/// Exchanges a refresh token for a new access/refresh pair on the same session.
+/// Presenting a refresh token that was already rotated away revokes the session,
+/// since only a copied token can be used twice.
+pub(super) async fn rotate_session(
+    db: &PgPool,
+    refresh_token: &str,
+    client: &SessionClient,
+    ttl: ChronoDuration,
+    refresh_ttl: ChronoDuration,
+) -> AppResult<Option<(IssuedSession, Uuid)>> {
+    let presented_hash = api_token_hash(refresh_token);
+    let mut tx = db.begin().await.map_err(db_error)?;
+    let row: Option<RefreshRow> = sqlx::query_as(
+        r#"
+        SELECT id, user_id, refresh_token_hash, revoked_at, refresh_expires_at
+        FROM auth_sessions
+        WHERE refresh_token_hash = $1
+           OR previous_refresh_token_hash = $1
+        LIMIT 1
+        FOR UPDATE
+        "#,
+    )
+    .bind(&presented_hash)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    if row.revoked_at.is_some() || row.refresh_expires_at <= Utc::now() {
+        return Ok(None);
+    }
+    if row.refresh_token_hash != presented_hash {
+        tracing::warn!(
+            session_id = %row.id,
+            user_id = %row.user_id,
+            "rotated refresh token was reused; revoking session"
+        );
+        revoke_where(&mut *tx, "id = $1", row.id, REVOKE_REFRESH_REUSE).await?;
+        tx.commit().await.map_err(db_error)?;
+        return Ok(None);
+    }
+
+    let token = generate_token();
+    let next_refresh_token = generate_token();
+    let now = Utc::now();
+    let expires_at = now + ttl;
+    let refresh_expires_at = now + refresh_ttl;
+    sqlx::query(
+        r#"
+        UPDATE auth_sessions
+        SET token_hash = $2,
+            refresh_token_hash = $3,
+            previous_refresh_token_hash = refresh_token_hash,
+            client_ip = COALESCE($4, client_ip),
+            user_agent = COALESCE($5, user_agent),
+            last_used_at = $6,
+            expires_at = $7,
+            refresh_expires_at = $8
+        WHERE id = $1
+        "#,
+    )
+    .bind(row.id)
+    .bind(api_token_hash(&token))
+    .bind(api_token_hash(&next_refresh_token))
+    .bind(client.ip.as_deref())
+    .bind(client.user_agent.as_deref())
+    .bind(now)
+    .bind(expires_at)
+    .bind(refresh_expires_at)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(Some((
+        IssuedSession {
+            session_id: row.id,
+            token,
+            refresh_token: next_refresh_token,
+            expires_at,
+            refresh_expires_at,
+        },
+        row.user_id,
+    )))
+}
+
+async fn revoke_where<'e, E>(executor: E, predicate: &str, id: Uuid, reason: &str) -> AppResult<u64>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let sql = format!(
+        "UPDATE auth_sessions SET revoked_at = NOW(), revoked_reason = $2 \
+         WHERE {predicate} AND revoked_at IS NULL"
+    );
+    let result = sqlx::query(&sql)
+        .bind(id)
+        .bind(reason)
+        .execute(executor)
+        .await
+        .map_err(db_error)?;
+    Ok(result.rows_affected())
+}
+
+/// Revokes one session. When `owner` is set the session must belong to that user,
+/// so people can only end their own sessions through the self-service API.
+pub async fn revoke_session(
+    db: &PgPool,
+    session_id: Uuid,
+    owner: Option<Uuid>,
+    reason: &str,
+) -> AppResult<bool> {
+    let revoked = match owner {
+        Some(owner) => sqlx::query(
+            r#"
+                UPDATE auth_sessions
+                SET revoked_at = NOW(), revoked_reason = $3
+                WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+                "#,
+        )
+        .bind(session_id)
+        .bind(owner)
+        .bind(reason)
+        .execute(db)
+        .await
+        .map_err(db_error)?
+        .rows_affected(),
+        None => revoke_where(db, "id = $1", session_id, reason).await?,
+    };
+    Ok(revoked > 0)
+}
+
+pub async fn revoke_user_sessions<'e, E>(executor: E, user_id: Uuid, reason: &str) -> AppResult<u64>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    revoke_where(executor, "user_id = $1", user_id, reason).await
+}
+
+/// Active sessions (refresh still possible) for a user, newest activity first.
+pub(crate) async fn list_user_sessions(db: &PgPool, user_id: Uuid) -> AppResult<Vec<SessionRow>> {
+    sqlx::query_as(
+        r#"
+        SELECT
+            id,
+            source,
+            client_ip,
+            user_agent,
+            created_at,
+            last_used_at,
+            expires_at,
+            refresh_expires_at
+        FROM auth_sessions
+        WHERE user_id = $1
+          AND revoked_at IS NULL
+          AND refresh_expires_at > NOW()
+        ORDER BY last_used_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+    .map_err(db_error)
+}
+
+pub(super) async fn prune_sessions(db: &PgPool) -> AppResult<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM auth_sessions
+        WHERE refresh_expires_at < NOW() - make_interval(days => $1)
+           OR revoked_at < NOW() - make_interval(days => $1)
+        "#,
+    )
+    .bind(PRUNE_GRACE_DAYS as i32)
+    .execute(db)
+    .await
+    .map_err(db_error)?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn generated_tokens_are_unique_and_url_safe() {
+        let first = generate_token();
+        let second = generate_token();
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 43);
+        assert!(first
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_'));
+    }
+
+    #[test]
+    fn session_client_records_peer_and_truncated_user_agent() {
+        let mut headers = HeaderMap::new();
+        let agent = "x".repeat(MAX_USER_AGENT_LEN + 10);
+        headers.insert(USER_AGENT, HeaderValue::from_str(&agent).unwrap());
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("203.0.113.9"));
+        let client = SessionClient::from_request("10.0.0.5:51000".parse().unwrap(), &headers, &[]);
+        assert_eq!(client.ip.as_deref(), Some("10.0.0.5"));
+        assert_eq!(client.lockout_ip(), Some("10.0.0.5"));
+        assert_eq!(
+            client.user_agent.map(|ua| ua.len()),
+            Some(MAX_USER_AGENT_LEN)
+        );
+
+        let client =
+            SessionClient::from_request("[::1]:8000".parse().unwrap(), &HeaderMap::new(), &[]);
+        assert_eq!(client.ip.as_deref(), Some("::1"));
+        assert!(client.user_agent.is_none());
+    }
+
+    #[test]
+    fn session_client_reads_forwarded_ip_only_from_trusted_proxies() {
+        let trusted: Vec<IpAddr> = vec!["127.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()];
+        let peer: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("198.51.100.1, 203.0.113.9, 10.0.0.2"),
+        );
+        let client = SessionClient::from_request(peer, &headers, &trusted);
+        assert_eq!(client.lockout_ip(), Some("203.0.113.9"));
+
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("203.0.113.9, not-an-ip"),
+        );
+        let client = SessionClient::from_request(peer, &headers, &trusted);
+        assert_eq!(client.ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(client.lockout_ip(), None);
+
+        let client = SessionClient::from_request(peer, &HeaderMap::new(), &trusted);
+        assert_eq!(client.ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(client.lockout_ip(), None);
+    }
+}
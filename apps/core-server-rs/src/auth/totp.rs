@@ -0,0 +1,312 @@
+use chrono::{DateTime, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha1::Sha1;
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+use url::form_urlencoded::byte_serialize;
+use uuid::Uuid;
+
+use super::password::{hash_password, verify_password};
+use crate::error::{internal_error, AppError, AppResult};
+
+pub const ISSUER: &str = "FarmDashboard";
+const SECRET_BYTES: usize = 20;
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Accept the previous and next 30s step to absorb phone clock drift.
+const SKEW_STEPS: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+#[derive(sqlx::FromRow)]
+struct TotpRow {
+    totp_secret: Option<String>,
+    totp_last_step: Option<i64>,
+    totp_recovery_codes: SqlJson<Vec<String>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub required: bool,
+    pub recovery_codes_remaining: i32,
+}
+
+/// What a login presented as its second factor.
+pub enum SecondFactor<'a> {
+    Code(&'a str),
+    RecoveryCode(&'a str),
+}
+
+fn db_error(err: sqlx::Error) -> AppError {
+    let (status, message) = internal_error(err);
+    AppError::new(status, message)
+}
+
+pub fn generate_secret() -> String {
+    let mut buf = [0u8; SECRET_BYTES];
+    OsRng.fill_bytes(&mut buf);
+    BASE32_NOPAD.encode(&buf)
+}
+
+/// `otpauth://` URI understood by authenticator apps; clients render it as a QR code.
+pub fn provisioning_uri(account: &str, secret: &str) -> String {
+    let label: String = byte_serialize(format!("{ISSUER}:{account}").as_bytes()).collect();
+    let issuer: String = byte_serialize(ISSUER.as_bytes()).collect();
+    format!(
+        "otpauth://totp/{label}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}"
+    )
+}
+
+fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    let normalized = secret
+        .trim()
+        .trim_end_matches('=')
+        .to_ascii_uppercase()
+        .replace(' ', "");
+    BASE32_NOPAD.decode(normalized.as_bytes()).ok()
+}
+
+/// RFC 4226 HOTP value for one counter step (RFC 6238 uses the time step as counter).
+fn hotp(key: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(digits)
+}
+
+/// Returns the matched time step so callers can reject replays of the same code.
+pub fn verify_code(
+    secret: &str,
+    code: &str,
+    now: DateTime<Utc>,
+    last_step: Option<i64>,
+) -> Option<i64> {
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    let key = decode_secret(secret)?;
+    let current = now.timestamp().div_euclid(STEP_SECONDS);
+    for step in (current - SKEW_STEPS)..=(current + SKEW_STEPS) {
+        if step < 0 || last_step.is_some_and(|last| step <= last) {
+            continue;
+        }
+        let expected = format!(
+            "{:0width$}",
+            hotp(&key, step as u64, DIGITS),
+            width = DIGITS as usize
+        );
+        if bool::from(expected.as_bytes().ct_eq(code.as_bytes())) {
+            return Some(step);
+        }
+    }
+    None
+}
+
+/// Plaintext recovery codes (shown once) and their password-style hashes.
+pub fn generate_recovery_codes() -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    let mut hashes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    for _ in 0..RECOVERY_CODE_COUNT {
+        let mut buf = [0u8; 10];
+        OsRng.fill_bytes(&mut buf);
+        let chars: String = buf
+            .iter()
+            .map(|byte| {
+                RECOVERY_CODE_ALPHABET[*byte as usize % RECOVERY_CODE_ALPHABET.len()] as char
+            })
+            .collect();
+        let code = format!("{}-{}", &chars[..5], &chars[5..]);
+        hashes.push(hash_password(&code)?);
+        codes.push(code);
+    }
+    Ok((codes, hashes))
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    let compact: String = code
+        .trim()
+        .to_ascii_lowercase()
+        .chars()
+        .filter(|ch| ch.is_ascii_alphanumeric())
+        .collect();
+    if compact.len() == 10 {
+        format!("{}-{}", &compact[..5], &compact[5..])
+    } else {
+        compact
+    }
+}
+
+pub async fn status(db: &PgPool, user_id: Uuid) -> AppResult<TwoFactorStatus> {
+    sqlx::query_as(
+        r#"
+        SELECT
+            u.totp_enabled_at IS NOT NULL AS enabled,
+            COALESCE(p.require_totp, FALSE) AS required,
+            jsonb_array_length(u.totp_recovery_codes) AS recovery_codes_remaining
+        FROM users u
+        LEFT JOIN auth_role_policies p ON p.role = u.role
+        WHERE u.id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| AppError::unauthorized("Missing or invalid token"))
+}
+
+/// Checks the second factor for a user with TOTP enabled. A matched code advances
+/// `totp_last_step`; a matched recovery code is removed so it only works once.
+pub async fn verify_second_factor(
+    db: &PgPool,
+    user_id: Uuid,
+    factor: SecondFactor<'_>,
+) -> AppResult<bool> {
+    let mut tx = db.begin().await.map_err(db_error)?;
+    let row: Option<TotpRow> = sqlx::query_as(
+        r#"
+        SELECT totp_secret, totp_last_step, totp_recovery_codes
+        FROM users
+        WHERE id = $1 AND totp_enabled_at IS NOT NULL
+        FOR UPDATE
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    let Some(row) = row else {
+        return Ok(false);
+    };
+
+    match factor {
+        SecondFactor::Code(code) => {
+            let Some(secret) = row.totp_secret.as_deref() else {
+                return Ok(false);
+            };
+            let Some(step) = verify_code(secret, code, Utc::now(), row.totp_last_step) else {
+                return Ok(false);
+            };
+            sqlx::query("UPDATE users SET totp_last_step = $2 WHERE id = $1")
+                .bind(user_id)
+                .bind(step)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        SecondFactor::RecoveryCode(code) => {
+            let code = normalize_recovery_code(code);
+            let mut remaining = row.totp_recovery_codes.0;
+            let Some(index) = remaining
+                .iter()
+                .position(|hash| verify_password(&code, hash))
+            else {
+                return Ok(false);
+            };
+            remaining.remove(index);
+            sqlx::query("UPDATE users SET totp_recovery_codes = $2 WHERE id = $1")
+                .bind(user_id)
+                .bind(SqlJson(&remaining))
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            tracing::info!(user_id = %user_id, remaining = remaining.len(), "recovery code used");
+        }
+    }
+    tx.commit().await.map_err(db_error)?;
+    Ok(true)
+}
+
+/// Clears every second-factor field; used by self-service disable and admin reset.
+pub async fn clear(db: &PgPool, user_id: Uuid) -> AppResult<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET totp_secret = NULL,
+            totp_pending_secret = NULL,
+            totp_enabled_at = NULL,
+            totp_last_step = NULL,
+            totp_recovery_codes = '[]'::jsonb
+        WHERE id = $1
+        "#,
+    )
+    .bind(user_id)
+    .execute(db)
+    .await
+    .map_err(db_error)?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // RFC 6238 appendix B uses the ASCII key "12345678901234567890" for SHA-1.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn hotp_matches_rfc6238_vectors() {
+        let key = decode_secret(RFC_SECRET).unwrap();
+        assert_eq!(key, b"12345678901234567890");
+        assert_eq!(hotp(&key, 59 / 30, 8), 94_287_082);
+        assert_eq!(hotp(&key, 1_111_111_109 / 30, 8), 7_081_804);
+        assert_eq!(hotp(&key, 1_234_567_890 / 30, 8), 89_005_924);
+        assert_eq!(hotp(&key, 20_000_000_000 / 30, 8), 65_353_130);
+    }
+
+    #[test]
+    fn verify_code_accepts_skew_and_rejects_replays() {
+        let now = Utc.timestamp_opt(1_111_111_109, 0).unwrap();
+        let step = 1_111_111_109 / 30;
+        assert_eq!(verify_code(RFC_SECRET, "081804", now, None), Some(step));
+        assert_eq!(verify_code(RFC_SECRET, "081 804", now, None), Some(step));
+        // Same code again after it was accepted.
+        assert_eq!(verify_code(RFC_SECRET, "081804", now, Some(step)), None);
+        // Code from the previous step is still accepted within the skew window.
+        let later = Utc.timestamp_opt(1_111_111_109 + 30, 0).unwrap();
+        assert_eq!(verify_code(RFC_SECRET, "081804", later, None), Some(step));
+        let much_later = Utc.timestamp_opt(1_111_111_109 + 90, 0).unwrap();
+        assert_eq!(verify_code(RFC_SECRET, "081804", much_later, None), None);
+        assert_eq!(verify_code(RFC_SECRET, "08180", now, None), None);
+        assert_eq!(verify_code(RFC_SECRET, "abcdef", now, None), None);
+    }
+
+    #[test]
+    fn provisioning_uri_escapes_label() {
+        let uri = provisioning_uri("ops+farm@example.com", "ABC234");
+        assert!(uri.starts_with("otpauth://totp/FarmDashboard%3Aops%2Bfarm%40example.com?"));
+        assert!(uri.contains("secret=ABC234"));
+        assert!(uri.contains("issuer=FarmDashboard"));
+        assert!(uri.contains("digits=6"));
+        assert!(uri.contains("period=30"));
+    }
+
+    #[test]
+    fn generated_secret_round_trips() {
+        let secret = generate_secret();
+        assert_eq!(
+            decode_secret(&secret).map(|key| key.len()),
+            Some(SECRET_BYTES)
+        );
+    }
+
+    #[test]
+    fn recovery_codes_normalize_before_matching() {
+        assert_eq!(normalize_recovery_code(" ABCDE FGHJK "), "abcde-fghjk");
+        assert_eq!(normalize_recovery_code("abcde-fghjk"), "abcde-fghjk");
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::{Component, Path, PathBuf};
 
 const DEFAULT_SETUP_CONFIG_PATH: &str = "/Users/Shared/InfrastructureDashboard/setup/config.json";
@@ -236,6 +237,101 @@ pub struct CoreConfig {
     pub analysis_profile_enabled: bool,
     pub analysis_profile_output_path: PathBuf,
     pub qdrant_url: String,
+    pub oidc: Option<OidcConfig>,
+    /// Passphrase for offsite backup encryption; a key file takes precedence.
+    pub backup_encryption_passphrase: Option<String>,
+    /// File holding a base64 32-byte key for offsite backup encryption.
+    pub backup_encryption_key_file: Option<PathBuf>,
+    /// Hours between scheduled backup test-restores; 0 disables the schedule.
+    pub backup_verify_interval_hours: u64,
+    /// Raise a per-node alarm when node-forwarder reports lost data.
+    pub data_loss_alarm_enabled: bool,
+    /// Peers whose `X-Forwarded-For` is trusted for the client IP (login lockout, session list).
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// OpenID Connect login, enabled when an issuer and client id are configured.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    /// Dashboard page the provider redirects back to; it posts `code` and `state`
+    /// to `/api/auth/oidc/callback`.
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub display_name: String,
+    /// Claim holding group or role names (string or array).
+    pub role_claim: String,
+    /// Claim value to dashboard role, checked in order; from `value=role,...`.
+    pub role_map: Vec<(String, String)>,
+    pub default_role: Option<String>,
+    /// Optional claim whose values are added to the role's default capabilities.
+    pub capability_claim: Option<String>,
+    pub auto_provision: bool,
+    /// Re-apply the mapped role and capabilities on every SSO login.
+    pub sync_roles: bool,
+    /// Let SSO logins skip the user's TOTP check; only for providers that enforce
+    /// their own second factor.
+    pub skip_totp: bool,
+}
+
+impl OidcConfig {
+    fn from_env() -> Option<Self> {
+        let issuer_url = env_optional_string("CORE_OIDC_ISSUER_URL")?;
+        let client_id = env_optional_string("CORE_OIDC_CLIENT_ID")?;
+        let redirect_uri = env_optional_string("CORE_OIDC_REDIRECT_URI")?;
+        let scopes = env_optional_string("CORE_OIDC_SCOPES")
+            .map(|value| {
+                value
+                    .split([' ', ','])
+                    .map(str::trim)
+                    .filter(|scope| !scope.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|scopes| scopes.iter().any(|scope| scope == "openid"))
+            .unwrap_or_else(|| {
+                vec![
+                    "openid".to_string(),
+                    "email".to_string(),
+                    "profile".to_string(),
+                ]
+            });
+        Some(Self {
+            issuer_url: issuer_url.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret: env_optional_string("CORE_OIDC_CLIENT_SECRET"),
+            redirect_uri,
+            scopes,
+            display_name: env_string("CORE_OIDC_DISPLAY_NAME", "Single sign-on"),
+            role_claim: env_string("CORE_OIDC_ROLE_CLAIM", "groups"),
+            role_map: parse_role_map(
+                env_optional_string("CORE_OIDC_ROLE_MAP")
+                    .as_deref()
+                    .unwrap_or(""),
+            ),
+            default_role: env_optional_string("CORE_OIDC_DEFAULT_ROLE"),
+            capability_claim: env_optional_string("CORE_OIDC_CAPABILITY_CLAIM"),
+            auto_provision: env_bool("CORE_OIDC_AUTO_PROVISION", true),
+            sync_roles: env_bool("CORE_OIDC_SYNC_ROLES", true),
+            skip_totp: env_bool("CORE_OIDC_SKIP_TOTP", false),
+        })
+    }
+}
+
+fn parse_role_map(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (claim, role) = entry.split_once('=')?;
+            let (claim, role) = (claim.trim(), role.trim());
+            if claim.is_empty() || role.is_empty() {
+                return None;
+            }
+            Some((claim.to_string(), role.to_string()))
+        })
+        .collect()
 }
 
 impl CoreConfig {
@@ -361,6 +457,19 @@ impl CoreConfig {
             &analysis_profile_default.to_string_lossy(),
         )?;
         let qdrant_url = env_string("CORE_QDRANT_URL", "http://127.0.0.1:6333");
+        let oidc = OidcConfig::from_env();
+        let backup_encryption_passphrase = env_optional_string("CORE_BACKUP_ENCRYPTION_PASSPHRASE");
+        let backup_encryption_key_file = env_optional_path("CORE_BACKUP_ENCRYPTION_KEY_FILE");
+        let backup_verify_interval_hours = env_u64("CORE_BACKUP_VERIFY_INTERVAL_HOURS", 168);
+        let data_loss_alarm_enabled = env_bool("CORE_DATA_LOSS_ALARM_ENABLED", false);
+        // The dashboard proxies `/api` from the same host, so loopback is trusted by default.
+        let trusted_proxies = env_ip_list(
+            "CORE_TRUSTED_PROXIES",
+            &[
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ],
+        )?;
 
         let mut config = Self {
             database_url,
@@ -409,6 +518,12 @@ impl CoreConfig {
             analysis_profile_enabled,
             analysis_profile_output_path,
             qdrant_url,
+            oidc,
+            backup_encryption_passphrase,
+            backup_encryption_key_file,
+            backup_verify_interval_hours,
+            data_loss_alarm_enabled,
+            trusted_proxies,
         };
 
         if let Some(overrides) = setup_overrides.as_ref() {
@@ -467,6 +582,21 @@ fn env_optional_string(key: &str) -> Option<String> {
         .filter(|value| !value.is_empty())
 }
 
+fn env_ip_list(key: &str, default: &[IpAddr]) -> Result<Vec<IpAddr>> {
+    let Some(value) = env_optional_string(key) else {
+        return Ok(default.to_vec());
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.parse::<IpAddr>()
+                .with_context(|| format!("{key} contains an invalid IP address: {item}"))
+        })
+        .collect()
+}
+
 fn env_bool(key: &str, default: bool) -> bool {
     match std::env::var(key)
         .ok()
@@ -616,10 +746,27 @@ mod tests {
             analysis_profile_enabled: false,
             analysis_profile_output_path: data_root.join("storage/analysis/tmp/profiles"),
             qdrant_url: "http://127.0.0.1:6333".to_string(),
+            oidc: None,
+            backup_encryption_passphrase: None,
+            backup_encryption_key_file: None,
+            backup_verify_interval_hours: 0,
+            data_loss_alarm_enabled: false,
+            trusted_proxies: Vec::new(),
             data_root,
         }
     }
 
+    #[test]
+    fn parses_oidc_role_map() {
+        assert_eq!(
+            parse_role_map("farm-admins=admin, crew = operator,,broken,=view"),
+            vec![
+                ("farm-admins".to_string(), "admin".to_string()),
+                ("crew".to_string(), "operator".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn rejects_relative_or_parent_paths() {
         let err = validate_and_canonicalize_path(PathBuf::from("relative/path"), None, "TEST");
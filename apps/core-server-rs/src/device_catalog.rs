@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::sync::OnceLock;
+use serde_json::Value as JsonValue;
+use std::sync::{OnceLock, RwLock};
 
 static CATALOG: OnceLock<DeviceCatalog> = OnceLock::new();
+static USER_VENDORS: OnceLock<RwLock<Vec<DeviceVendor>>> = OnceLock::new();
+static POINT_SCHEMA: OnceLock<jsonschema::Validator> = OnceLock::new();
 
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct DeviceCatalog {
@@ -34,6 +37,10 @@ pub struct DevicePoint {
     pub protocol: String,
     #[serde(default)]
     pub register: Option<u32>,
+    /// Modbus table: `coil`, `discrete_input`, `input` or `holding`. When unset the
+    /// table is inferred from the 3xxxx/4xxxx register prefix.
+    #[serde(default)]
+    pub register_type: Option<String>,
     #[serde(default)]
     pub data_type: Option<String>,
     #[serde(default)]
@@ -58,11 +65,146 @@ pub fn catalog() -> &'static DeviceCatalog {
     })
 }
 
+/// The built-in catalog with user-defined profiles (the `device_profiles` table) merged
+/// in. User models for a built-in vendor id are listed under that vendor.
+pub fn merged_catalog() -> DeviceCatalog {
+    let mut merged = catalog().clone();
+    for vendor in user_vendors()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+    {
+        match merged
+            .vendors
+            .iter_mut()
+            .find(|entry| entry.id == vendor.id)
+        {
+            Some(existing) => existing.models.extend(vendor.models.iter().cloned()),
+            None => merged.vendors.push(vendor.clone()),
+        }
+    }
+    merged
+}
+
+/// Replaces the cached user-defined profiles; called whenever the table changes.
+pub fn set_user_vendors(vendors: Vec<DeviceVendor>) {
+    *user_vendors().write().unwrap_or_else(|e| e.into_inner()) = vendors;
+}
+
+fn user_vendors() -> &'static RwLock<Vec<DeviceVendor>> {
+    USER_VENDORS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+pub fn is_builtin_model(vendor_id: &str, model_id: &str) -> bool {
+    find_in(&catalog().vendors, vendor_id, model_id).is_some()
+}
+
 pub fn find_model(vendor_id: &str, model_id: &str) -> Option<DeviceModel> {
-    catalog()
-        .vendors
+    find_in(&catalog().vendors, vendor_id, model_id).or_else(|| {
+        find_in(
+            &user_vendors().read().unwrap_or_else(|e| e.into_inner()),
+            vendor_id,
+            model_id,
+        )
+    })
+}
+
+fn find_in(vendors: &[DeviceVendor], vendor_id: &str, model_id: &str) -> Option<DeviceModel> {
+    vendors
         .iter()
         .find(|vendor| vendor.id == vendor_id)
         .and_then(|vendor| vendor.models.iter().find(|model| model.id == model_id))
         .cloned()
 }
+
+/// Checks a raw point against `shared/device_profiles/device_point.schema.json` and
+/// returns one message per violation, prefixed with the offending JSON pointer.
+pub fn validate_point_json(point: &JsonValue) -> Vec<String> {
+    let validator = POINT_SCHEMA.get_or_init(|| {
+        let raw = include_str!("../../../shared/device_profiles/device_point.schema.json");
+        let schema: JsonValue = serde_json::from_str(raw).expect("device point schema is JSON");
+        jsonschema::validator_for(&schema).expect("device point schema compiles")
+    });
+    validator
+        .iter_errors(point)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{path}: {error}")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_catalog_points_match_schema() {
+        for vendor in &catalog().vendors {
+            for model in &vendor.models {
+                for point in &model.points {
+                    let value = serde_json::to_value(point).unwrap();
+                    let errors = validate_point_json(&value);
+                    assert!(
+                        errors.is_empty(),
+                        "{}/{}: {errors:?}",
+                        model.id,
+                        point.metric
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn schema_rejects_incomplete_points() {
+        let errors = validate_point_json(&serde_json::json!({
+            "name": "Voltage",
+            "metric": "Voltage L1",
+            "sensor_type": "voltage",
+            "unit": "V",
+            "protocol": "modbus_tcp",
+        }));
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors.iter().any(|error| error.starts_with("/metric")));
+        assert!(errors.iter().any(|error| error.contains("register")));
+
+        let errors = validate_point_json(&serde_json::json!({
+            "name": "Uptime",
+            "metric": "uptime",
+            "sensor_type": "generic",
+            "unit": "s",
+            "protocol": "snmp",
+            "oid": "1.3.6.1.2.1.1.3.0",
+            "colour": "red",
+        }));
+        assert_eq!(errors.len(), 1, "{errors:?}");
+    }
+
+    #[test]
+    fn user_models_merge_under_existing_vendors() {
+        let builtin_vendor = catalog().vendors[0].id.clone();
+        let model = DeviceModel {
+            id: "custom_meter_test".to_string(),
+            name: "Custom meter".to_string(),
+            since_year: None,
+            protocols: vec!["modbus_tcp".to_string()],
+            points: Vec::new(),
+        };
+        set_user_vendors(vec![DeviceVendor {
+            id: builtin_vendor.clone(),
+            name: "ignored".to_string(),
+            models: vec![model],
+        }]);
+
+        let merged = merged_catalog();
+        assert_eq!(merged.vendors.len(), catalog().vendors.len());
+        assert!(find_model(&builtin_vendor, "custom_meter_test").is_some());
+        assert!(!is_builtin_model(&builtin_vendor, "custom_meter_test"));
+        set_user_vendors(Vec::new());
+    }
+}
@@ -93,6 +93,10 @@ async fn main() -> Result<()> {
         tracing::warn!("failed to ensure core node exists: {err:#}");
     }
 
+    if let Err(err) = services::device_profiles::reload_catalog(&state.db).await {
+        tracing::warn!("failed to load user device profiles: {err:#}");
+    }
+
     if let Err(err) = services::map_offline::resume_installing_packs(state.clone()).await {
         tracing::warn!("failed to resume offline map installs: {err:#}");
     }
@@ -118,8 +122,15 @@ async fn main() -> Result<()> {
     services::schedule_engine::ScheduleEngine::new(pool, mqtt, config.clone())
         .start(cancel.clone());
     services::alarm_engine::AlarmEngineService::new(state.db.clone(), 10).start(cancel.clone());
+    services::edge_rules::EdgeRulesPublisher::new(state.db.clone(), state.mqtt.clone(), 60)
+        .start(cancel.clone());
+    services::forwarder_config::ForwarderConfigService::new(state.clone(), 60).start(cancel.clone());
+    services::forwarder_losses::ForwarderLossService::new(state.clone()).start(cancel.clone());
     services::mqtt_status_ingest::MqttStatusIngestService::new(state.clone()).start(cancel.clone());
     services::restore_worker::RestoreWorkerService::new(state.clone()).start(cancel.clone());
+    services::offsite_backup::OffsiteBackupService::new(state.clone()).start(cancel.clone());
+    services::backup_verification::BackupVerificationService::new(state.clone())
+        .start(cancel.clone());
     if config.enable_analytics_feeds {
         let feeds = services::analytics_feeds::AnalyticsFeedService::new(
             state.clone(),
@@ -34,12 +34,45 @@ impl Modify for SecurityAddon {
         crate::routes::auth::login,
         crate::routes::auth::me,
         crate::routes::auth::bootstrap,
+        crate::routes::auth::refresh,
+        crate::routes::auth::logout,
+        crate::routes::auth::list_sessions,
+        crate::routes::auth::revoke_session,
+        crate::routes::oidc::get_provider,
+        crate::routes::oidc::authorize,
+        crate::routes::oidc::callback,
+        crate::routes::two_factor::get_totp_status,
+        crate::routes::two_factor::enroll_totp,
+        crate::routes::two_factor::confirm_totp,
+        crate::routes::two_factor::regenerate_recovery_codes,
+        crate::routes::two_factor::disable_totp,
+        crate::routes::two_factor::list_role_policies,
+        crate::routes::two_factor::update_role_policy,
         crate::routes::api_tokens::list_api_tokens,
         crate::routes::api_tokens::revoke_api_token,
+        crate::routes::resource_scopes::get_api_token_grants,
+        crate::routes::resource_scopes::put_api_token_grants,
+        crate::routes::resource_scopes::get_user_grants,
+        crate::routes::resource_scopes::put_user_grants,
+        crate::routes::resource_scopes::list_node_groups,
+        crate::routes::resource_scopes::create_node_group,
+        crate::routes::resource_scopes::update_node_group,
+        crate::routes::resource_scopes::delete_node_group,
+        crate::routes::resource_scopes::get_map_layer_nodes,
+        crate::routes::resource_scopes::put_map_layer_nodes,
         crate::routes::users::list_users,
         crate::routes::users::create_user,
         crate::routes::users::update_user,
         crate::routes::users::delete_user,
+        crate::routes::users::list_user_sessions,
+        crate::routes::users::force_logout_user,
+        crate::routes::users::reset_user_totp,
+        crate::routes::users::get_user_lockout,
+        crate::routes::users::unlock_user,
+        crate::routes::security_events::list_security_events,
+        crate::routes::audit::list_audit_entries,
+        crate::routes::audit::export_audit_entries,
+        crate::routes::audit::verify_audit_chain,
         crate::routes::nodes::list_nodes,
         crate::routes::nodes::create_node,
         crate::routes::nodes::get_node,
@@ -77,6 +110,9 @@ impl Modify for SecurityAddon {
         crate::routes::alarm_rules::disable_alarm_rule,
         crate::routes::alarm_rules::preview_alarm_rule,
         crate::routes::alarm_rules::alarm_rule_stats,
+        crate::routes::alarm_rules::get_alarm_rule_edge,
+        crate::routes::alarm_rules::put_alarm_rule_edge,
+        crate::routes::alarm_rules::delete_alarm_rule_edge,
         crate::routes::alarms::list_alarms,
         crate::routes::alarms::alarm_history,
         crate::routes::alarms::acknowledge_event,
@@ -91,6 +127,15 @@ impl Modify for SecurityAddon {
         crate::routes::action_logs::list_action_logs,
         crate::routes::metrics::query_metrics,
         crate::routes::metrics::ingest_metrics,
+        crate::routes::metrics_export::export_metrics,
+        crate::routes::metrics_import::upload_import_file,
+        crate::routes::metrics_import::create_import,
+        crate::routes::spool_bundles::import_spool_bundle,
+        crate::routes::forwarder_config::list_forwarder_configs,
+        crate::routes::forwarder_config::get_forwarder_config,
+        crate::routes::forwarder_config::put_forwarder_config,
+        crate::routes::forwarder_config::delete_forwarder_config,
+        crate::routes::forwarder_losses::list_data_losses,
         crate::routes::annotations::list_annotations,
         crate::routes::annotations::create_annotation,
         crate::routes::annotations::update_annotation,
@@ -127,6 +172,19 @@ impl Modify for SecurityAddon {
         crate::routes::backups::get_retention,
         crate::routes::backups::update_retention,
         crate::routes::backups::recent_restores,
+        crate::routes::backup_targets::list_targets,
+        crate::routes::backup_targets::create_target,
+        crate::routes::backup_targets::update_target,
+        crate::routes::backup_targets::delete_target,
+        crate::routes::backup_targets::test_target,
+        crate::routes::backup_targets::run_target,
+        crate::routes::backup_targets::list_snapshots,
+        crate::routes::backup_targets::verify_snapshot,
+        crate::routes::backup_targets::restore_snapshot,
+        crate::routes::backup_targets::list_restores,
+        crate::routes::backup_verification::list_verifications,
+        crate::routes::backup_verification::get_verification,
+        crate::routes::backup_verification::run_verification,
         crate::routes::backups_exports::export_app_settings,
         crate::routes::backups_exports::import_app_settings,
         crate::routes::backups_exports::export_database,
@@ -163,6 +221,15 @@ impl Modify for SecurityAddon {
         crate::routes::external_devices::get_device_catalog,
         crate::routes::external_devices::list_devices,
         crate::routes::external_devices::sweep_devices,
+        crate::routes::external_devices::discover_opcua_device_endpoints,
+        crate::routes::external_devices::list_mqtt_discovered_devices,
+        crate::routes::device_profiles::list_device_profiles,
+        crate::routes::device_profiles::create_device_profile,
+        crate::routes::device_profiles::update_device_profile,
+        crate::routes::device_profiles::delete_device_profile,
+        crate::routes::device_profiles::export_device_profiles,
+        crate::routes::device_profiles::import_device_profiles,
+        crate::routes::device_profiles::test_read_device_profile,
         crate::routes::external_devices::create_device,
         crate::routes::external_devices::sync_device,
         crate::routes::external_devices::delete_device,
@@ -233,6 +300,10 @@ impl Modify for SecurityAddon {
         crate::routes::alarm_rules::AlarmRuleStatsBands,
         crate::routes::alarm_rules::AlarmRuleStatsBandSet,
         crate::routes::alarm_rules::AlarmRuleDeleteResponse,
+        crate::routes::alarm_rules::AlarmRuleEdgeRequest,
+        crate::routes::alarm_rules::AlarmRuleEdgeResponse,
+        crate::services::alarm_engine::edge::EdgeAction,
+        crate::services::alarm_engine::edge::EdgeTargetPlan,
         crate::services::alarm_engine::PreviewTargetResult,
         crate::routes::alarms::AlarmResponse,
         crate::routes::alarms::AlarmEventResponse,
@@ -261,13 +332,37 @@ impl Modify for SecurityAddon {
         crate::routes::analytics::AnalyticsWater,
         crate::routes::analytics::AnalyticsStatus,
         crate::routes::api_tokens::ApiTokenInfo,
+        crate::routes::resource_scopes::CapabilityGrant,
+        crate::routes::resource_scopes::CapabilityGrantsPayload,
+        crate::routes::resource_scopes::NodeGroupResponse,
+        crate::routes::resource_scopes::NodeGroupUpsertRequest,
+        crate::routes::resource_scopes::NodeMembershipPayload,
         crate::routes::auth::LoginRequest,
         crate::routes::auth::LoginResponse,
         crate::routes::auth::AuthMeResponse,
+        crate::routes::oidc::OidcProviderResponse,
+        crate::routes::oidc::OidcAuthorizeResponse,
+        crate::routes::oidc::OidcCallbackRequest,
+        crate::routes::two_factor::TotpStatusResponse,
+        crate::routes::two_factor::TotpEnrollResponse,
+        crate::routes::two_factor::TotpCodeRequest,
+        crate::routes::two_factor::TotpRecoveryCodesResponse,
+        crate::routes::two_factor::AuthRolePolicy,
         crate::routes::auth::AuthBootstrapResponse,
+        crate::routes::auth::RefreshRequest,
+        crate::routes::auth::AuthSessionResponse,
         crate::routes::annotations::AnnotationResponse,
         crate::routes::annotations::CreateAnnotationRequest,
         crate::routes::annotations::UpdateAnnotationRequest,
+        crate::routes::backup_targets::BackupTargetResponse,
+        crate::routes::backup_targets::BackupTargetsResponse,
+        crate::routes::backup_targets::BackupTargetUpsertRequest,
+        crate::routes::backup_targets::BackupTargetTestResponse,
+        crate::routes::backup_targets::OffsiteBackupResponse,
+        crate::routes::backup_targets::OffsiteRestoreRequest,
+        crate::routes::backup_targets::OffsiteRestoreJobResponse,
+        crate::routes::backup_verification::BackupVerificationRunResponse,
+        crate::services::backup_verification::VerificationCheck,
         crate::routes::backups::BackupFileInfo,
         crate::routes::backups::BackupNodeMetadata,
         crate::routes::backups::BackupSummary,
@@ -330,6 +425,19 @@ impl Modify for SecurityAddon {
         crate::routes::external_devices::ExternalDeviceSweepResponse,
         crate::routes::external_devices::ExternalDeviceCreateRequest,
         crate::services::external_devices::ExternalDeviceSweepCandidate,
+        crate::routes::external_devices::OpcuaEndpointDiscoveryRequest,
+        crate::routes::external_devices::OpcuaEndpointDiscoveryResponse,
+        crate::services::external_devices::OpcuaEndpointSummary,
+        crate::routes::external_devices::MqttDiscoveryResponse,
+        crate::services::external_devices::MqttDiscoveredDevice,
+        crate::services::external_devices::MqttDiscoveredEntity,
+        crate::services::external_devices::MqttOutputConfig,
+        crate::services::device_profiles::DeviceProfileInput,
+        crate::routes::device_profiles::DeviceProfileResponse,
+        crate::routes::device_profiles::DeviceProfileImportResponse,
+        crate::routes::device_profiles::DeviceProfileTestReadRequest,
+        crate::routes::device_profiles::DeviceProfileTestReadResponse,
+        crate::services::external_devices::PointTestReadResult,
         crate::device_catalog::DeviceCatalog,
         crate::device_catalog::DeviceVendor,
         crate::device_catalog::DeviceModel,
@@ -341,6 +449,19 @@ impl Modify for SecurityAddon {
         crate::routes::metrics::MetricIngestItem,
         crate::routes::metrics::MetricIngestRequest,
         crate::routes::metrics::MetricIngestResponse,
+        crate::routes::metrics_import::MetricsImportUploadResponse,
+        crate::services::metrics_import::ImportFormat,
+        crate::services::metrics_import::ImportLayout,
+        crate::services::metrics_import::ImportMapping,
+        crate::services::metrics_import::MetricsImportParams,
+        crate::services::spool_bundle::SpoolBundleImportReport,
+        crate::routes::forwarder_config::ForwarderConfigUpdateRequest,
+        crate::routes::forwarder_config::ForwarderConfigResponse,
+        crate::services::forwarder_config::ForwarderSettings,
+        crate::routes::forwarder_losses::DataLossEventResponse,
+        crate::services::spool_bundle::SegmentCheck,
+        crate::services::spool_bundle::SeqRange,
+        crate::services::spool_bundle::BundleLoss,
         crate::services::analysis::jobs::AnalysisJobStatus,
         crate::services::analysis::jobs::AnalysisJobProgress,
         crate::services::analysis::jobs::AnalysisJobError,
@@ -438,6 +559,10 @@ impl Modify for SecurityAddon {
         crate::routes::users::UserCreateRequest,
         crate::routes::users::UserUpdateRequest,
         crate::routes::users::UserResponse,
+        crate::routes::users::UserLockoutResponse,
+        crate::routes::security_events::SecurityEventResponse,
+        crate::routes::audit::AuditEntryResponse,
+        crate::routes::audit::AuditVerifyResponse,
         crate::routes::weather_stations::Ws2902Protocol,
         crate::routes::weather_stations::Ws2902CreateRequest,
         crate::routes::weather_stations::Ws2902CreatedSensor,
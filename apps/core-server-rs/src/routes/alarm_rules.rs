@@ -7,7 +7,8 @@ use serde_json::Value as JsonValue;
 use sqlx::types::Json as SqlJson;
 
 use crate::auth::AuthUser;
-use crate::error::map_db_error;
+use crate::error::{internal_error, map_db_error};
+use crate::services::alarm_engine::edge::{self, EdgeAction, EdgeTargetPlan};
 use crate::services::alarm_engine::types::TargetSelector;
 use crate::services::analysis::bucket_reader::{
     read_bucket_series_for_sensors_with_aggregation_and_options, BucketAggregationPreference,
@@ -15,6 +16,7 @@ use crate::services::analysis::bucket_reader::{
 use crate::services::analysis::parquet_duckdb::{
     bucket_coverage_pct, expected_bucket_count, MetricsBucketReadOptions, MetricsQualityFilter,
 };
+use crate::services::audit_log;
 use crate::state::AppState;
 
 const CAP_ALERTS_VIEW: &str = "alerts.view";
@@ -483,6 +485,16 @@ pub(crate) async fn create_alarm_rule(
         .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch created alarm rule".to_string()))?;
 
     let detail = fetch_rule_detail(&state, row).await?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "alarm_rule.create",
+        "alarm_rule",
+        Some(&inserted.0.to_string()),
+        None,
+        audit_log::snapshot(&detail),
+    )
+    .await;
     Ok((StatusCode::CREATED, Json(detail)))
 }
 
@@ -513,6 +525,7 @@ pub(crate) async fn update_alarm_rule(
     if existing.deleted_at.is_some() {
         return Err((StatusCode::NOT_FOUND, "Alarm rule not found".to_string()));
     }
+    let before = audit_log::snapshot(&fetch_rule_detail(&state, existing.clone()).await?);
 
     let name = payload.name.unwrap_or(existing.name);
     if name.trim().is_empty() {
@@ -565,7 +578,18 @@ pub(crate) async fn update_alarm_rule(
     let row = fetch_rule_row(&state, rule_id)
         .await?
         .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch updated alarm rule".to_string()))?;
-    Ok(Json(fetch_rule_detail(&state, row).await?))
+    let detail = fetch_rule_detail(&state, row).await?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "alarm_rule.update",
+        "alarm_rule",
+        Some(&rule_id.to_string()),
+        before,
+        audit_log::snapshot(&detail),
+    )
+    .await;
+    Ok(Json(detail))
 }
 
 #[utoipa::path(
@@ -599,6 +623,16 @@ pub(crate) async fn delete_alarm_rule(
     if result.rows_affected() == 0 {
         return Err((StatusCode::NOT_FOUND, "Alarm rule not found".to_string()));
     }
+    audit_log::record(
+        &state.db,
+        &user,
+        "alarm_rule.delete",
+        "alarm_rule",
+        Some(&rule_id.to_string()),
+        None,
+        None,
+    )
+    .await;
 
     Ok(Json(AlarmRuleDeleteResponse {
         status: "deleted".to_string(),
@@ -640,7 +674,18 @@ pub(crate) async fn enable_alarm_rule(
     let row = fetch_rule_row(&state, rule_id)
         .await?
         .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch enabled alarm rule".to_string()))?;
-    Ok(Json(fetch_rule_detail(&state, row).await?))
+    let detail = fetch_rule_detail(&state, row).await?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "alarm_rule.enable",
+        "alarm_rule",
+        Some(&rule_id.to_string()),
+        None,
+        audit_log::snapshot(&detail),
+    )
+    .await;
+    Ok(Json(detail))
 }
 
 #[utoipa::path(
@@ -678,7 +723,18 @@ pub(crate) async fn disable_alarm_rule(
     let row = fetch_rule_row(&state, rule_id)
         .await?
         .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch disabled alarm rule".to_string()))?;
-    Ok(Json(fetch_rule_detail(&state, row).await?))
+    let detail = fetch_rule_detail(&state, row).await?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "alarm_rule.disable",
+        "alarm_rule",
+        Some(&rule_id.to_string()),
+        None,
+        audit_log::snapshot(&detail),
+    )
+    .await;
+    Ok(Json(detail))
 }
 
 #[utoipa::path(
@@ -1054,6 +1110,259 @@ pub(crate) async fn alarm_rule_stats(
     }))
 }
 
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct AlarmRuleEdgeRequest {
+    /// Defaults to true.
+    enabled: Option<bool>,
+    fire_action: Option<EdgeAction>,
+    clear_action: Option<EdgeAction>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct AlarmRuleEdgeResponse {
+    rule_id: i64,
+    /// False until edge evaluation has been configured for the rule.
+    configured: bool,
+    enabled: bool,
+    fire_action: Option<EdgeAction>,
+    clear_action: Option<EdgeAction>,
+    updated_at: Option<String>,
+    /// Why the rule's condition cannot be evaluated on a node; `None` when it can.
+    unsupported_reason: Option<String>,
+    /// Targets and whether each one is pushed to its node.
+    targets: Vec<EdgeTargetPlan>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct AlarmRuleEdgeRow {
+    enabled: bool,
+    fire_action: Option<SqlJson<EdgeAction>>,
+    clear_action: Option<SqlJson<EdgeAction>>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn normalize_edge_action(action: EdgeAction) -> Result<EdgeAction, (StatusCode, String)> {
+    let action = EdgeAction {
+        output_id: action.output_id.trim().to_string(),
+        state: action.state.trim().to_string(),
+    };
+    if action.output_id.is_empty() || action.state.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "edge actions require output_id and state".to_string()));
+    }
+    Ok(action)
+}
+
+async fn fetch_live_rule_row(state: &AppState, rule_id: i64) -> Result<AlarmRuleRow, (StatusCode, String)> {
+    match fetch_rule_row(state, rule_id).await? {
+        Some(row) if row.deleted_at.is_none() => Ok(row),
+        _ => Err((StatusCode::NOT_FOUND, "Alarm rule not found".to_string())),
+    }
+}
+
+async fn fetch_edge_detail(state: &AppState, row: &AlarmRuleRow) -> Result<AlarmRuleEdgeResponse, (StatusCode, String)> {
+    let edge_row: Option<AlarmRuleEdgeRow> = sqlx::query_as(
+        r#"
+        SELECT enabled, fire_action, clear_action, updated_at
+        FROM alarm_rule_edge
+        WHERE rule_id = $1
+        "#,
+    )
+    .bind(row.id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    let fire_action = edge_row.as_ref().and_then(|edge| edge.fire_action.as_ref()).map(|action| action.0.clone());
+    let clear_action = edge_row.as_ref().and_then(|edge| edge.clear_action.as_ref()).map(|action| action.0.clone());
+
+    let (unsupported_reason, targets) = match crate::services::alarm_engine::types::parse_rule_envelope(&row.target_selector.0, &row.condition_ast.0, &row.timing.0)
+        .and_then(|envelope| edge::check_edge_condition(&envelope.condition).map(|()| envelope))
+    {
+        Ok(envelope) => {
+            let compilation = edge::compile_rule(
+                &state.db,
+                row.id,
+                &row.name,
+                &row.severity,
+                &envelope,
+                fire_action.as_ref(),
+                clear_action.as_ref(),
+            )
+            .await
+            .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+            (None, compilation.plans)
+        }
+        Err(err) => (Some(err), Vec::new()),
+    };
+
+    Ok(AlarmRuleEdgeResponse {
+        rule_id: row.id,
+        configured: edge_row.is_some(),
+        enabled: edge_row.as_ref().is_some_and(|edge| edge.enabled),
+        fire_action,
+        clear_action,
+        updated_at: edge_row.map(|edge| edge.updated_at.to_rfc3339()),
+        unsupported_reason,
+        targets,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/alarm-rules/{rule_id}/edge",
+    tag = "alarm_rules",
+    params(("rule_id" = i64, Path, description = "Alarm rule id")),
+    responses(
+        (status = 200, description = "Edge evaluation settings for the rule", body = AlarmRuleEdgeResponse),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn get_alarm_rule_edge(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(rule_id): Path<i64>,
+) -> Result<Json<AlarmRuleEdgeResponse>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &[CAP_ALERTS_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let row = fetch_live_rule_row(&state, rule_id).await?;
+    Ok(Json(fetch_edge_detail(&state, &row).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/alarm-rules/{rule_id}/edge",
+    tag = "alarm_rules",
+    params(("rule_id" = i64, Path, description = "Alarm rule id")),
+    request_body = AlarmRuleEdgeRequest,
+    responses(
+        (status = 200, description = "Updated edge evaluation settings", body = AlarmRuleEdgeResponse),
+        (status = 400, description = "Rule cannot be evaluated on a node, or invalid action"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn put_alarm_rule_edge(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(rule_id): Path<i64>,
+    Json(payload): Json<AlarmRuleEdgeRequest>,
+) -> Result<Json<AlarmRuleEdgeResponse>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let row = fetch_live_rule_row(&state, rule_id).await?;
+    let envelope = crate::services::alarm_engine::types::parse_rule_envelope(&row.target_selector.0, &row.condition_ast.0, &row.timing.0)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    edge::check_edge_condition(&envelope.condition).map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+
+    let fire_action = payload.fire_action.map(normalize_edge_action).transpose()?;
+    let clear_action = payload.clear_action.map(normalize_edge_action).transpose()?;
+    let target_nodes = if fire_action.is_some() || clear_action.is_some() {
+        edge::target_nodes(&state.db, &envelope.target_selector).await.map_err(internal_error)?
+    } else {
+        Vec::new()
+    };
+    for action in fire_action.iter().chain(clear_action.iter()) {
+        let output_node: Option<uuid::Uuid> = sqlx::query_scalar("SELECT node_id FROM outputs WHERE id = $1")
+            .bind(&action.output_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(map_db_error)?;
+        let Some(output_node) = output_node else {
+            return Err((StatusCode::BAD_REQUEST, format!("unknown output {}", action.output_id)));
+        };
+        // The node drives the output itself, so this is a command on that node.
+        crate::auth::require_node_capabilities(&user, &["outputs.command"], output_node)
+            .map_err(|err| (err.status, err.message))?;
+        if target_nodes.is_empty() || target_nodes.iter().any(|node| *node != Some(output_node)) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("output {} is not on the node the rule targets", action.output_id),
+            ));
+        }
+    }
+
+    let before = fetch_edge_detail(&state, &row).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO alarm_rule_edge (rule_id, enabled, fire_action, clear_action, updated_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (rule_id)
+        DO UPDATE SET
+            enabled = EXCLUDED.enabled,
+            fire_action = EXCLUDED.fire_action,
+            clear_action = EXCLUDED.clear_action,
+            updated_at = EXCLUDED.updated_at
+        "#,
+    )
+    .bind(rule_id)
+    .bind(payload.enabled.unwrap_or(true))
+    .bind(fire_action.map(SqlJson))
+    .bind(clear_action.map(SqlJson))
+    .execute(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    let detail = fetch_edge_detail(&state, &row).await?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "alarm_rule.edge.update",
+        "alarm_rule",
+        Some(&rule_id.to_string()),
+        before.configured.then(|| audit_log::snapshot(&before)).flatten(),
+        audit_log::snapshot(&detail),
+    )
+    .await;
+    Ok(Json(detail))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/alarm-rules/{rule_id}/edge",
+    tag = "alarm_rules",
+    params(("rule_id" = i64, Path, description = "Alarm rule id")),
+    responses(
+        (status = 200, description = "Edge evaluation removed", body = AlarmRuleDeleteResponse),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn delete_alarm_rule_edge(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(rule_id): Path<i64>,
+) -> Result<Json<AlarmRuleDeleteResponse>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let result = sqlx::query("DELETE FROM alarm_rule_edge WHERE rule_id = $1")
+        .bind(rule_id)
+        .execute(&state.db)
+        .await
+        .map_err(map_db_error)?;
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Edge evaluation not configured".to_string()));
+    }
+    audit_log::record(
+        &state.db,
+        &user,
+        "alarm_rule.edge.delete",
+        "alarm_rule",
+        Some(&rule_id.to_string()),
+        None,
+        None,
+    )
+    .await;
+
+    Ok(Json(AlarmRuleDeleteResponse {
+        status: "deleted".to_string(),
+    }))
+}
+
 pub(crate) fn router() -> Router<AppState> {
     Router::new()
         .route("/alarm-rules", get(list_alarm_rules).post(create_alarm_rule))
@@ -1067,6 +1376,12 @@ pub(crate) fn router() -> Router<AppState> {
         )
         .route("/alarm-rules/{rule_id}/enable", post(enable_alarm_rule))
         .route("/alarm-rules/{rule_id}/disable", post(disable_alarm_rule))
+        .route(
+            "/alarm-rules/{rule_id}/edge",
+            get(get_alarm_rule_edge)
+                .put(put_alarm_rule_edge)
+                .delete(delete_alarm_rule_edge),
+        )
 }
 
 #[cfg(test)]
@@ -6,12 +6,16 @@ use serde_json::Value as JsonValue;
 use sqlx::types::Json as SqlJson;
 use uuid::Uuid;
 
-use crate::auth::AuthUser;
+use crate::auth::{AuthUser, NodeScope};
 use crate::error::map_db_error;
 use crate::state::AppState;
 
 const CAP_ALERTS_VIEW: &str = "alerts.view";
 
+/// An alarm or event without its own node belongs to its sensor's node.
+const SCOPE_NODE_SQL: &str =
+    "COALESCE(node_id, (SELECT s.node_id FROM sensors s WHERE s.sensor_id = scoped.sensor_id))";
+
 #[derive(sqlx::FromRow)]
 pub(crate) struct AlarmRow {
     id: i64,
@@ -138,14 +142,19 @@ pub(crate) struct BulkAcknowledgeResponse {
     pub acknowledged: u64,
 }
 
-pub(crate) async fn fetch_alarms(db: &sqlx::PgPool) -> Result<Vec<AlarmResponse>, sqlx::Error> {
-    let rows: Vec<AlarmRow> = sqlx::query_as(
+pub(crate) async fn fetch_alarms(
+    db: &sqlx::PgPool,
+    scope: &NodeScope,
+) -> Result<Vec<AlarmResponse>, sqlx::Error> {
+    let rows: Vec<AlarmRow> = sqlx::query_as(&format!(
         r#"
         SELECT id, name, rule, status, sensor_id, node_id, origin, anomaly_score, last_fired, rule_id, target_key, resolved_at
-        FROM alarms
+        FROM alarms scoped
+        WHERE ($1::uuid[] IS NULL OR {SCOPE_NODE_SQL} = ANY($1))
         ORDER BY id ASC
-        "#,
-    )
+        "#
+    ))
+    .bind(scope.node_ids())
     .fetch_all(db)
     .await?;
 
@@ -155,18 +164,21 @@ pub(crate) async fn fetch_alarms(db: &sqlx::PgPool) -> Result<Vec<AlarmResponse>
 pub(crate) async fn fetch_alarm_events(
     db: &sqlx::PgPool,
     limit: i64,
+    scope: &NodeScope,
 ) -> Result<Vec<AlarmEventResponse>, sqlx::Error> {
     let limit = limit.clamp(1, 250);
-    let rows: Vec<AlarmEventRow> = sqlx::query_as(
+    let rows: Vec<AlarmEventRow> = sqlx::query_as(&format!(
         r#"
         SELECT id, alarm_id, sensor_id, node_id, status, message, created_at, origin, anomaly_score, rule_id, transition, incident_id, target_key
-        FROM alarm_events
+        FROM alarm_events scoped
         WHERE alarm_id IS NOT NULL
+          AND ($2::uuid[] IS NULL OR {SCOPE_NODE_SQL} = ANY($2))
         ORDER BY created_at DESC
         LIMIT $1
-        "#,
-    )
+        "#
+    ))
     .bind(limit)
+    .bind(scope.node_ids())
     .fetch_all(db)
     .await?;
     Ok(rows.into_iter().map(AlarmEventResponse::from).collect())
@@ -187,9 +199,14 @@ pub(crate) async fn list_alarms(
     axum::extract::State(state): axum::extract::State<AppState>,
     AuthUser(user): AuthUser,
 ) -> Result<Json<Vec<AlarmResponse>>, (StatusCode, String)> {
-    crate::auth::require_any_capabilities(&user, &[CAP_ALERTS_VIEW, "config.write"])
+    crate::auth::require_any_node_capabilities(&user, &[CAP_ALERTS_VIEW, "config.write"])
         .map_err(|err| (err.status, err.message))?;
-    Ok(Json(fetch_alarms(&state.db).await.map_err(map_db_error)?))
+    let scope = user.node_scope(&[CAP_ALERTS_VIEW, "config.write"]);
+    Ok(Json(
+        fetch_alarms(&state.db, &scope)
+            .await
+            .map_err(map_db_error)?,
+    ))
 }
 
 #[utoipa::path(
@@ -209,11 +226,12 @@ pub(crate) async fn alarm_history(
     AuthUser(user): AuthUser,
     Query(query): Query<HistoryQuery>,
 ) -> Result<Json<Vec<AlarmEventResponse>>, (StatusCode, String)> {
-    crate::auth::require_any_capabilities(&user, &[CAP_ALERTS_VIEW, "config.write"])
+    crate::auth::require_any_node_capabilities(&user, &[CAP_ALERTS_VIEW, "config.write"])
         .map_err(|err| (err.status, err.message))?;
     let limit = query.limit.unwrap_or(100).clamp(1, 250) as i64;
+    let scope = user.node_scope(&[CAP_ALERTS_VIEW, "config.write"]);
     Ok(Json(
-        fetch_alarm_events(&state.db, limit)
+        fetch_alarm_events(&state.db, limit, &scope)
             .await
             .map_err(map_db_error)?,
     ))
@@ -236,13 +254,27 @@ pub(crate) async fn acknowledge_event(
     AuthUser(user): AuthUser,
     Path(event_id): Path<String>,
 ) -> Result<Json<AlarmEventResponse>, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["alerts.ack"])
+    crate::auth::require_any_node_capabilities(&user, &["alerts.ack"])
         .map_err(|err| (err.status, err.message))?;
 
     let event_id: i64 = event_id
         .trim()
         .parse()
         .map_err(|_| (StatusCode::NOT_FOUND, "Alarm event not found".to_string()))?;
+    let scope = user.node_scope(&["alerts.ack"]);
+    if let Some(node_ids) = scope.node_ids() {
+        let in_scope: bool = sqlx::query_scalar(&format!(
+            "SELECT EXISTS(SELECT 1 FROM alarm_events scoped WHERE id = $1 AND {SCOPE_NODE_SQL} = ANY($2))"
+        ))
+        .bind(event_id)
+        .bind(node_ids)
+        .fetch_one(&state.db)
+        .await
+        .map_err(map_db_error)?;
+        if !in_scope {
+            return Err((StatusCode::NOT_FOUND, "Alarm event not found".to_string()));
+        }
+    }
     let result = sqlx::query(
         "UPDATE alarm_events SET status = 'acknowledged' WHERE id = $1 AND status <> 'ok'",
     )
@@ -290,7 +322,7 @@ pub(crate) async fn acknowledge_events_bulk(
     AuthUser(user): AuthUser,
     Json(payload): Json<BulkAcknowledgeRequest>,
 ) -> Result<Json<BulkAcknowledgeResponse>, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["alerts.ack"])
+    crate::auth::require_any_node_capabilities(&user, &["alerts.ack"])
         .map_err(|err| (err.status, err.message))?;
 
     if payload.event_ids.is_empty() {
@@ -315,16 +347,20 @@ pub(crate) async fn acknowledge_events_bulk(
         ids.push(parsed);
     }
 
-    let result = sqlx::query(
+    // Events outside the caller's nodes are skipped rather than rejected.
+    let scope = user.node_scope(&["alerts.ack"]);
+    let result = sqlx::query(&format!(
         r#"
-        UPDATE alarm_events
+        UPDATE alarm_events scoped
         SET status = 'acknowledged'
         WHERE id = ANY($1)
           AND status <> 'acknowledged'
           AND status <> 'ok'
-        "#,
-    )
+          AND ($2::uuid[] IS NULL OR {SCOPE_NODE_SQL} = ANY($2))
+        "#
+    ))
     .bind(&ids)
+    .bind(scope.node_ids())
     .execute(&state.db)
     .await
     .map_err(map_db_error)?;
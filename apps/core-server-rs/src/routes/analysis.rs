@@ -22,12 +22,13 @@ use crate::services::analysis::tsse::types::{
     EventPolarityV1, PreviewEventOverlaysV1, TsseEpisodeV1, TssePreviewRequestV1,
     TssePreviewResponseV1, TssePreviewSeriesPointV1, TssePreviewSeriesV1,
 };
+use crate::services::metrics_import::METRICS_IMPORT_JOB_TYPE;
 use crate::state::AppState;
 
 const CAP_ANALYSIS_RUN: &[&str] = &["analysis.run"];
 const CAP_ANALYSIS_VIEW: &[&str] = &["analysis.view"];
 
-fn enforce_max_active_jobs(max_jobs: usize, active_jobs: i64) -> Result<(), (StatusCode, String)> {
+pub(crate) fn enforce_max_active_jobs(max_jobs: usize, active_jobs: i64) -> Result<(), (StatusCode, String)> {
     let max_jobs = max_jobs.max(1);
     if active_jobs as usize >= max_jobs {
         return Err((
@@ -87,6 +88,13 @@ pub async fn create_job(
     }
 
     let job_type = request.job_type.trim();
+    if job_type == METRICS_IMPORT_JOB_TYPE {
+        // Imports write metrics, so they go through the ingest-gated endpoint.
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Start metrics imports with POST /api/metrics/imports".to_string(),
+        ));
+    }
     let user_id = user.user_id();
     if user_id.is_none() && user.source != "api_token" {
         return Err((
@@ -732,6 +740,8 @@ mod tests {
             role: "view".to_string(),
             capabilities: set,
             source: "test".to_string(),
+            session_id: None,
+            scopes: Default::default(),
         })
     }
 
@@ -797,6 +807,12 @@ mod tests {
             analysis_profile_enabled: false,
             analysis_profile_output_path: analysis_tmp_path.join("profiles"),
             qdrant_url: "http://127.0.0.1:6333".to_string(),
+            oidc: None,
+            backup_encryption_passphrase: None,
+            backup_encryption_key_file: None,
+            backup_verify_interval_hours: 0,
+            data_loss_alarm_enabled: false,
+            trusted_proxies: Vec::new(),
         };
 
         let db = PgPoolOptions::new()
@@ -857,6 +873,27 @@ mod tests {
         assert!(err.1.contains("analysis.run"));
     }
 
+    #[tokio::test]
+    async fn create_job_rejects_metrics_imports() {
+        let state = make_min_state().await;
+        let request = AnalysisJobCreateRequest {
+            job_type: METRICS_IMPORT_JOB_TYPE.to_string(),
+            params: serde_json::json!({}),
+            job_key: None,
+            dedupe: false,
+        };
+
+        let err = create_job(
+            State(state),
+            make_user_with_caps(&["analysis.run"]),
+            Json(request),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        assert!(err.1.contains("/api/metrics/imports"));
+    }
+
     #[tokio::test]
     async fn preview_requires_analysis_view() {
         let state = make_min_state().await;
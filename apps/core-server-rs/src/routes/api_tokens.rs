@@ -8,6 +8,7 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::error::map_db_error;
+use crate::services::audit_log;
 use crate::state::AppState;
 
 #[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
@@ -111,6 +112,16 @@ pub(crate) async fn revoke_api_token(
     if updated.rows_affected() == 0 {
         return Err((StatusCode::NOT_FOUND, "Token not found".to_string()));
     }
+    audit_log::record(
+        &state.db,
+        &user,
+        "api_token.revoke",
+        "api_token",
+        Some(token_id),
+        None,
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
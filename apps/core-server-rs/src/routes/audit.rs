@@ -0,0 +1,361 @@
+use axum::body::Body;
+use axum::extract::Query;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::Response;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+
+use crate::auth::AuthUser;
+use crate::error::{internal_error, map_db_error};
+use crate::services::audit_log::{self, AuditRecord};
+use crate::state::AppState;
+
+const AUDIT_CAPABILITIES: [&str; 2] = ["audit.view", "users.manage"];
+const MAX_EXPORT_ROWS: i64 = 50_000;
+const VERIFY_BATCH: i64 = 5_000;
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::IntoParams)]
+pub(crate) struct AuditQuery {
+    /// Actor user id or email.
+    actor: Option<String>,
+    /// Exact action, e.g. `sensor.update`, or a prefix ending in `.` such as `user.`.
+    action: Option<String>,
+    target_type: Option<String>,
+    target_id: Option<String>,
+    /// Start timestamp (RFC3339).
+    from: Option<String>,
+    /// End timestamp (RFC3339).
+    to: Option<String>,
+    /// Only entries with an id below this one (for paging back).
+    before_id: Option<i64>,
+    #[param(minimum = 1, maximum = 500)]
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::IntoParams)]
+pub(crate) struct AuditExportQuery {
+    /// `csv` (default) or `ndjson`.
+    format: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct AuditEntryResponse {
+    id: i64,
+    occurred_at: String,
+    actor_id: String,
+    actor_email: String,
+    actor_source: String,
+    action: String,
+    target_type: String,
+    target_id: Option<String>,
+    before: Option<JsonValue>,
+    after: Option<JsonValue>,
+    /// Changed fields as `{field: {before, after}}`.
+    changes: JsonValue,
+    prev_hash: String,
+    hash: String,
+}
+
+impl From<AuditRecord> for AuditEntryResponse {
+    fn from(row: AuditRecord) -> Self {
+        Self {
+            id: row.id,
+            occurred_at: row.occurred_at.to_rfc3339(),
+            actor_id: row.actor_id,
+            actor_email: row.actor_email,
+            actor_source: row.actor_source,
+            action: row.action,
+            target_type: row.target_type,
+            target_id: row.target_id,
+            before: row.before.map(|value| value.0),
+            after: row.after.map(|value| value.0),
+            changes: row.changes.0,
+            prev_hash: row.prev_hash,
+            hash: row.hash,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct AuditVerifyResponse {
+    ok: bool,
+    checked: u64,
+    /// First entry whose hash chain does not match, if any.
+    broken_at: Option<i64>,
+}
+
+fn trimmed(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn parse_rfc3339_optional(
+    raw: Option<&str>,
+    field: &str,
+) -> Result<Option<DateTime<Utc>>, (StatusCode, String)> {
+    raw.map(|value| {
+        DateTime::parse_from_rfc3339(value)
+            .map(|parsed| parsed.with_timezone(&Utc))
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("{field} must be RFC3339")))
+    })
+    .transpose()
+}
+
+async fn fetch_entries(
+    state: &AppState,
+    query: &AuditQuery,
+    limit: i64,
+) -> Result<Vec<AuditRecord>, (StatusCode, String)> {
+    let from_ts = parse_rfc3339_optional(trimmed(&query.from), "from")?;
+    let to_ts = parse_rfc3339_optional(trimmed(&query.to), "to")?;
+    let action = trimmed(&query.action);
+    let (action_exact, action_prefix) = match action {
+        Some(prefix) if prefix.ends_with('.') => (None, Some(format!("{prefix}%"))),
+        other => (other, None),
+    };
+
+    sqlx::query_as(
+        r#"
+        SELECT
+            id,
+            occurred_at,
+            actor_id,
+            actor_email,
+            actor_source,
+            action,
+            target_type,
+            target_id,
+            before,
+            after,
+            changes,
+            prev_hash,
+            hash
+        FROM audit_log
+        WHERE ($1::text IS NULL OR actor_id = $1 OR lower(actor_email) = lower($1))
+          AND ($2::text IS NULL OR action = $2)
+          AND ($3::text IS NULL OR action LIKE $3)
+          AND ($4::text IS NULL OR target_type = $4)
+          AND ($5::text IS NULL OR target_id = $5)
+          AND ($6::timestamptz IS NULL OR occurred_at >= $6)
+          AND ($7::timestamptz IS NULL OR occurred_at <= $7)
+          AND ($8::bigint IS NULL OR id < $8)
+        ORDER BY id DESC
+        LIMIT $9
+        "#,
+    )
+    .bind(trimmed(&query.actor))
+    .bind(action_exact)
+    .bind(action_prefix)
+    .bind(trimmed(&query.target_type))
+    .bind(trimmed(&query.target_id))
+    .bind(from_ts)
+    .bind(to_ts)
+    .bind(query.before_id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/audit",
+    tag = "audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Audit entries, newest first", body = Vec<AuditEntryResponse>),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_audit_entries(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntryResponse>>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &AUDIT_CAPABILITIES)
+        .map_err(|err| (err.status, err.message))?;
+    let limit = query.limit.unwrap_or(100).clamp(1, 500) as i64;
+    let rows = fetch_entries(&state, &query, limit).await?;
+    Ok(Json(
+        rows.into_iter().map(AuditEntryResponse::from).collect(),
+    ))
+}
+
+fn export_csv(rows: Vec<AuditRecord>) -> Result<Vec<u8>, (StatusCode, String)> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record([
+            "id",
+            "occurred_at",
+            "actor_id",
+            "actor_email",
+            "actor_source",
+            "action",
+            "target_type",
+            "target_id",
+            "changes",
+            "before",
+            "after",
+            "prev_hash",
+            "hash",
+        ])
+        .map_err(internal_error)?;
+    let json_cell =
+        |value: Option<&JsonValue>| value.map(|value| value.to_string()).unwrap_or_default();
+    for row in rows {
+        writer
+            .write_record([
+                row.id.to_string(),
+                row.occurred_at.to_rfc3339(),
+                row.actor_id,
+                row.actor_email,
+                row.actor_source,
+                row.action,
+                row.target_type,
+                row.target_id.unwrap_or_default(),
+                json_cell(Some(&row.changes.0)),
+                json_cell(row.before.as_ref().map(|value| &value.0)),
+                json_cell(row.after.as_ref().map(|value| &value.0)),
+                row.prev_hash,
+                row.hash,
+            ])
+            .map_err(internal_error)?;
+    }
+    writer.into_inner().map_err(internal_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/audit/export",
+    tag = "audit",
+    params(AuditQuery, AuditExportQuery),
+    responses(
+        (status = 200, description = "Audit entries as CSV or NDJSON, newest first", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn export_audit_entries(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Query(filter): Query<AuditQuery>,
+    Query(query): Query<AuditExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &AUDIT_CAPABILITIES)
+        .map_err(|err| (err.status, err.message))?;
+    let format = trimmed(&query.format).unwrap_or("csv").to_lowercase();
+    let limit = filter
+        .limit
+        .map(i64::from)
+        .unwrap_or(MAX_EXPORT_ROWS)
+        .clamp(1, MAX_EXPORT_ROWS);
+    let rows = fetch_entries(&state, &filter, limit).await?;
+
+    let (body, content_type, extension) = match format.as_str() {
+        "csv" => (export_csv(rows)?, "text/csv; charset=utf-8", "csv"),
+        "ndjson" | "jsonl" => {
+            let mut out = Vec::new();
+            for row in rows {
+                serde_json::to_writer(&mut out, &AuditEntryResponse::from(row))
+                    .map_err(internal_error)?;
+                out.push(b'\n');
+            }
+            (out, "application/x-ndjson", "ndjson")
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "format must be csv or ndjson".to_string(),
+            ))
+        }
+    };
+
+    let filename = format!("audit-{}.{extension}", Utc::now().format("%Y%m%dT%H%M%SZ"));
+    let mut response = Response::new(Body::from(body));
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{filename}\""))
+            .map_err(internal_error)?,
+    );
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/audit/verify",
+    tag = "audit",
+    responses(
+        (status = 200, description = "Hash chain check over the whole log", body = AuditVerifyResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn verify_audit_chain(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<AuditVerifyResponse>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &AUDIT_CAPABILITIES)
+        .map_err(|err| (err.status, err.message))?;
+
+    let mut last_hash = audit_log::GENESIS_HASH.to_string();
+    let mut last_id = 0_i64;
+    let mut checked = 0_u64;
+    loop {
+        let rows: Vec<AuditRecord> = sqlx::query_as(
+            r#"
+            SELECT
+                id, occurred_at, actor_id, actor_email, actor_source, action, target_type,
+                target_id, before, after, changes, prev_hash, hash
+            FROM audit_log
+            WHERE id > $1
+            ORDER BY id ASC
+            LIMIT $2
+            "#,
+        )
+        .bind(last_id)
+        .bind(VERIFY_BATCH)
+        .fetch_all(&state.db)
+        .await
+        .map_err(map_db_error)?;
+        let Some(last_row) = rows.last() else {
+            break;
+        };
+        last_id = last_row.id;
+        let check = audit_log::verify_chain(&rows, last_hash);
+        checked += check.checked;
+        if let Some(broken_at) = check.broken_at {
+            tracing::error!(broken_at, "audit log hash chain broken");
+            return Ok(Json(AuditVerifyResponse {
+                ok: false,
+                checked,
+                broken_at: Some(broken_at),
+            }));
+        }
+        last_hash = check.last_hash;
+    }
+    Ok(Json(AuditVerifyResponse {
+        ok: true,
+        checked,
+        broken_at: None,
+    }))
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/audit", get(list_audit_entries))
+        .route("/audit/export", get(export_audit_entries))
+        .route("/audit/verify", get(verify_audit_chain))
+}
@@ -1,9 +1,15 @@
-use axum::http::StatusCode;
-use axum::routing::{get, post};
+use axum::extract::{ConnectInfo, Path};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{delete, get, post};
 use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use std::net::SocketAddr;
 use uuid::Uuid;
 
-use crate::auth::AuthUser;
+use crate::auth::lockout;
+use crate::auth::sessions::{self, SessionRow};
+use crate::auth::totp::{self, SecondFactor};
+use crate::auth::{AuthUser, IssuedSession, SessionClient};
 use crate::error::map_db_error;
 use crate::state::AppState;
 
@@ -11,11 +17,68 @@ use crate::state::AppState;
 pub(crate) struct LoginRequest {
     email: String,
     password: String,
+    /// Current authenticator code; required once the user has enrolled in TOTP.
+    #[serde(default)]
+    totp_code: Option<String>,
+    /// One-time recovery code, accepted instead of `totp_code`.
+    #[serde(default)]
+    recovery_code: Option<String>,
 }
 
+pub(crate) const TOTP_REQUIRED_MESSAGE: &str = "Two-factor code required";
+
 #[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
 pub(crate) struct LoginResponse {
     token: String,
+    refresh_token: String,
+    session_id: String,
+    expires_at: DateTime<Utc>,
+    refresh_expires_at: DateTime<Utc>,
+}
+
+impl From<IssuedSession> for LoginResponse {
+    fn from(session: IssuedSession) -> Self {
+        Self {
+            token: session.token,
+            refresh_token: session.refresh_token,
+            session_id: session.session_id.to_string(),
+            expires_at: session.expires_at,
+            refresh_expires_at: session.refresh_expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct AuthSessionResponse {
+    id: String,
+    source: String,
+    client_ip: Option<String>,
+    user_agent: Option<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    refresh_expires_at: DateTime<Utc>,
+    /// True for the session that made this request.
+    current: bool,
+}
+
+pub(crate) fn session_response(row: SessionRow, current: Option<Uuid>) -> AuthSessionResponse {
+    AuthSessionResponse {
+        id: row.id.to_string(),
+        source: row.source,
+        client_ip: row.client_ip,
+        user_agent: row.user_agent,
+        created_at: row.created_at,
+        last_used_at: row.last_used_at,
+        expires_at: row.expires_at,
+        refresh_expires_at: row.refresh_expires_at,
+        current: current == Some(row.id),
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
@@ -24,7 +87,12 @@ pub(crate) struct AuthMeResponse {
     email: String,
     role: String,
     source: String,
+    /// Every capability held, including those granted only on specific nodes.
     capabilities: Vec<String>,
+    totp_enabled: bool,
+    /// The user's role requires TOTP; while `totp_enabled` is false the session has
+    /// no capabilities until enrollment is finished.
+    totp_required: bool,
 }
 
 #[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
@@ -36,6 +104,42 @@ pub(crate) struct AuthBootstrapResponse {
 struct AuthUserRow {
     id: Uuid,
     password_hash: Option<String>,
+    totp_enabled: bool,
+    password_login_disabled: bool,
+}
+
+/// Checks the second factor of a user enrolled in TOTP; a wrong code counts
+/// against the login lockout like a wrong password.
+pub(crate) async fn verify_login_factor(
+    state: &AppState,
+    email: &str,
+    user_id: Uuid,
+    client: &SessionClient,
+    totp_code: Option<&str>,
+    recovery_code: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let totp_code = totp_code.map(str::trim).filter(|value| !value.is_empty());
+    let recovery_code = recovery_code
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+    let factor = match (totp_code, recovery_code) {
+        (Some(code), _) => SecondFactor::Code(code),
+        (None, Some(code)) => SecondFactor::RecoveryCode(code),
+        (None, None) => {
+            return Err((StatusCode::UNAUTHORIZED, TOTP_REQUIRED_MESSAGE.to_string()));
+        }
+    };
+    let verified = totp::verify_second_factor(&state.db, user_id, factor)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    if !verified {
+        lockout::record_failure(&state.db, email, Some(user_id), client, "bad_second_factor").await;
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Invalid two-factor code".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 #[utoipa::path(
@@ -45,11 +149,15 @@ struct AuthUserRow {
     responses(
         (status = 200, description = "Auth token", body = LoginResponse),
         (status = 400, description = "Missing email/password"),
-        (status = 401, description = "Invalid credentials")
+        (status = 401, description = "Invalid credentials, or a two-factor code is required or invalid"),
+        (status = 403, description = "Password login disabled for this user"),
+        (status = 429, description = "Too many failed attempts for this account or client")
     )
 )]
 pub(crate) async fn login(
     axum::extract::State(state): axum::extract::State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, (StatusCode, String)> {
     let email = payload.email.trim().to_lowercase();
@@ -59,10 +167,18 @@ pub(crate) async fn login(
             "Email and password are required".to_string(),
         ));
     }
+    let client = SessionClient::from_request(peer, &headers, &state.config.trusted_proxies);
+    lockout::check(&state.db, &email, &client)
+        .await
+        .map_err(|err| (err.status, err.message))?;
 
     let row: Option<AuthUserRow> = sqlx::query_as(
         r#"
-        SELECT id, password_hash
+        SELECT
+            id,
+            password_hash,
+            totp_enabled_at IS NOT NULL AS totp_enabled,
+            password_login_disabled
         FROM users
         WHERE email = $1
         LIMIT 1
@@ -74,22 +190,175 @@ pub(crate) async fn login(
     .map_err(map_db_error)?;
 
     let Some(row) = row else {
+        lockout::record_failure(&state.db, &email, None, &client, "unknown_account").await;
         return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
     };
     let Some(hash) = row.password_hash.as_deref() else {
+        lockout::record_failure(&state.db, &email, Some(row.id), &client, "no_password").await;
         return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
     };
     if !crate::auth::verify_password(&payload.password, hash) {
+        lockout::record_failure(&state.db, &email, Some(row.id), &client, "bad_password").await;
         return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
     }
+    // Checked after the password so the response does not reveal which accounts use SSO.
+    if row.password_login_disabled {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Password login is disabled for this account; use single sign-on".to_string(),
+        ));
+    }
+    if row.totp_enabled {
+        verify_login_factor(
+            &state,
+            &email,
+            row.id,
+            &client,
+            payload.totp_code.as_deref(),
+            payload.recovery_code.as_deref(),
+        )
+        .await?;
+    }
+    lockout::record_success(&state.db, &email).await;
 
     let _ = sqlx::query("UPDATE users SET last_login = NOW() WHERE id = $1")
         .bind(row.id)
         .execute(&state.db)
         .await;
 
-    let token = state.auth.issue_for_user(row.id, "db".to_string()).await;
-    Ok(Json(LoginResponse { token }))
+    if let Err(err) = state.auth.prune_expired(&state.db).await {
+        tracing::warn!(error = %err.message, "failed to prune expired sessions");
+    }
+    if let Err(err) = lockout::prune(&state.db).await {
+        tracing::warn!(error = %err.message, "failed to prune login counters");
+    }
+    let session = state
+        .auth
+        .issue_for_user(&state.db, row.id, "db", &client)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    Ok(Json(LoginResponse::from(session)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "Rotated access and refresh tokens", body = LoginResponse),
+        (status = 400, description = "Missing refresh token"),
+        (status = 401, description = "Invalid, expired or revoked refresh token")
+    )
+)]
+pub(crate) async fn refresh(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<LoginResponse>, (StatusCode, String)> {
+    let refresh_token = payload.refresh_token.trim();
+    if refresh_token.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "refresh_token is required".to_string(),
+        ));
+    }
+    let client = SessionClient::from_request(peer, &headers, &state.config.trusted_proxies);
+    let rotated = state
+        .auth
+        .refresh(&state.db, refresh_token, &client)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    let Some((session, _user_id)) = rotated else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Invalid refresh token".to_string(),
+        ));
+    };
+    Ok(Json(LoginResponse::from(session)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Current session revoked"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn logout(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<StatusCode, (StatusCode, String)> {
+    // API tokens have no session; they are revoked through /api/api-tokens instead.
+    if let Some(session_id) = user.session_id {
+        sessions::revoke_session(&state.db, session_id, None, sessions::REVOKE_LOGOUT)
+            .await
+            .map_err(|err| (err.status, err.message))?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/sessions",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Active sessions of the current user", body = Vec<AuthSessionResponse>),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_sessions(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<Vec<AuthSessionResponse>>, (StatusCode, String)> {
+    let Some(user_id) = user.user_id() else {
+        return Ok(Json(Vec::new()));
+    };
+    let rows = sessions::list_user_sessions(&state.db, user_id)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| session_response(row, user.session_id))
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/auth/sessions/{session_id}",
+    tag = "auth",
+    params(("session_id" = String, Path, description = "Session id")),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Session not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn revoke_session(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let session_id = Uuid::parse_str(session_id.trim())
+        .map_err(|_| (StatusCode::NOT_FOUND, "Session not found".to_string()))?;
+    let Some(user_id) = user.user_id() else {
+        return Err((StatusCode::NOT_FOUND, "Session not found".to_string()));
+    };
+    let revoked =
+        sessions::revoke_session(&state.db, session_id, Some(user_id), sessions::REVOKE_USER)
+            .await
+            .map_err(|err| (err.status, err.message))?;
+    if !revoked {
+        return Err((StatusCode::NOT_FOUND, "Session not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[utoipa::path(
@@ -99,16 +368,37 @@ pub(crate) async fn login(
     responses((status = 200, description = "Current user", body = AuthMeResponse)),
     security(("HTTPBearer" = []))
 )]
-pub(crate) async fn me(AuthUser(user): AuthUser) -> Json<AuthMeResponse> {
-    let mut capabilities: Vec<String> = user.capabilities.into_iter().collect();
+pub(crate) async fn me(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<AuthMeResponse>, (StatusCode, String)> {
+    let (totp_enabled, totp_required) = match user.user_id() {
+        Some(user_id) => {
+            let status = totp::status(&state.db, user_id)
+                .await
+                .map_err(|err| (err.status, err.message))?;
+            (status.enabled, status.required)
+        }
+        None => (false, false),
+    };
+    let mut capabilities: Vec<String> = user
+        .capabilities
+        .iter()
+        .map(String::as_str)
+        .chain(user.scopes.scoped_capabilities())
+        .map(str::to_string)
+        .collect();
     capabilities.sort();
-    Json(AuthMeResponse {
+    capabilities.dedup();
+    Ok(Json(AuthMeResponse {
         id: user.id,
         email: user.email,
         role: user.role,
         source: user.source,
         capabilities,
-    })
+        totp_enabled,
+        totp_required,
+    }))
 }
 
 #[utoipa::path(
@@ -133,6 +423,10 @@ pub(crate) async fn bootstrap(
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/auth/login", post(login))
+        .route("/auth/refresh", post(refresh))
+        .route("/auth/logout", post(logout))
+        .route("/auth/sessions", get(list_sessions))
+        .route("/auth/sessions/{session_id}", delete(revoke_session))
         .route("/auth/me", get(me))
         .route("/auth/bootstrap", get(bootstrap))
 }
@@ -0,0 +1,921 @@
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde_json::Value as JsonValue;
+use sqlx::types::Json as SqlJson;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::map_db_error;
+use crate::services::audit_log;
+use crate::services::offsite_backup::archive::{ALL_COMPONENTS, COMPONENT_MAP};
+use crate::services::offsite_backup::crypto::BackupKey;
+use crate::services::offsite_backup::targets::BackupStore;
+use crate::services::offsite_backup::{
+    self, BackupTargetRow, OffsiteBackupRow, BACKUP_COLUMNS, TARGET_COLUMNS,
+};
+use crate::state::AppState;
+
+const CAP_BACKUPS_VIEW: &str = "backups.view";
+const MAX_SNAPSHOTS_LISTED: i64 = 200;
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct BackupTargetResponse {
+    id: String,
+    name: String,
+    /// `s3`, `sftp` or `path`.
+    kind: String,
+    config: JsonValue,
+    /// Names of the stored secrets; values are never returned.
+    secrets_set: Vec<String>,
+    enabled: bool,
+    interval_hours: i32,
+    include_map_packs: bool,
+    keep_daily: i32,
+    keep_weekly: i32,
+    keep_monthly: i32,
+    last_run_at: Option<String>,
+    last_status: Option<String>,
+    last_error: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+impl From<BackupTargetRow> for BackupTargetResponse {
+    fn from(row: BackupTargetRow) -> Self {
+        let secrets_set = row
+            .secrets
+            .0
+            .as_object()
+            .map(|secrets| secrets.keys().cloned().collect())
+            .unwrap_or_default();
+        Self {
+            id: row.id.to_string(),
+            name: row.name,
+            kind: row.kind,
+            config: row.config.0,
+            secrets_set,
+            enabled: row.enabled,
+            interval_hours: row.interval_hours,
+            include_map_packs: row.include_map_packs,
+            keep_daily: row.keep_daily,
+            keep_weekly: row.keep_weekly,
+            keep_monthly: row.keep_monthly,
+            last_run_at: row.last_run_at.map(|ts| ts.to_rfc3339()),
+            last_status: row.last_status,
+            last_error: row.last_error,
+            created_at: row.created_at.to_rfc3339(),
+            updated_at: row.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct BackupTargetsResponse {
+    /// Whether CORE_BACKUP_ENCRYPTION_KEY_FILE or CORE_BACKUP_ENCRYPTION_PASSPHRASE is set.
+    encryption_configured: bool,
+    targets: Vec<BackupTargetResponse>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct BackupTargetUpsertRequest {
+    name: String,
+    kind: String,
+    #[serde(default)]
+    config: Option<JsonValue>,
+    /// Secrets to set. On update, omitted names keep their stored value and an
+    /// empty string removes one.
+    #[serde(default)]
+    secrets: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    interval_hours: Option<i32>,
+    #[serde(default)]
+    include_map_packs: Option<bool>,
+    #[serde(default)]
+    keep_daily: Option<i32>,
+    #[serde(default)]
+    keep_weekly: Option<i32>,
+    #[serde(default)]
+    keep_monthly: Option<i32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct BackupTargetTestResponse {
+    ok: bool,
+    message: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct OffsiteBackupResponse {
+    id: String,
+    target_id: String,
+    object_key: String,
+    /// `running`, `ok`, `error` or `pruned`.
+    status: String,
+    trigger: String,
+    archive_sha256: Option<String>,
+    object_sha256: Option<String>,
+    size_bytes: Option<i64>,
+    components: Vec<String>,
+    message: Option<String>,
+    verified_at: Option<String>,
+    verify_status: Option<String>,
+    verify_message: Option<String>,
+    started_at: String,
+    finished_at: Option<String>,
+    pruned_at: Option<String>,
+}
+
+impl From<OffsiteBackupRow> for OffsiteBackupResponse {
+    fn from(row: OffsiteBackupRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            target_id: row.target_id.to_string(),
+            object_key: row.object_key,
+            status: row.status,
+            trigger: row.trigger,
+            archive_sha256: row.archive_sha256,
+            object_sha256: row.object_sha256,
+            size_bytes: row.size_bytes,
+            components: row.components.0,
+            message: row.message,
+            verified_at: row.verified_at.map(|ts| ts.to_rfc3339()),
+            verify_status: row.verify_status,
+            verify_message: row.verify_message,
+            started_at: row.started_at.to_rfc3339(),
+            finished_at: row.finished_at.map(|ts| ts.to_rfc3339()),
+            pruned_at: row.pruned_at.map(|ts| ts.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct OffsiteRestoreRequest {
+    /// Any of `database`, `setup_config`, `lake_state`, `map`. Defaults to
+    /// everything in the snapshot except map packs.
+    #[serde(default)]
+    components: Option<Vec<String>>,
+}
+
+#[derive(sqlx::FromRow)]
+struct OffsiteRestoreJobRow {
+    id: Uuid,
+    offsite_backup_id: Uuid,
+    components: SqlJson<Vec<String>>,
+    status: String,
+    message: Option<String>,
+    actor_email: Option<String>,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+    finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct OffsiteRestoreJobResponse {
+    id: String,
+    offsite_backup_id: String,
+    components: Vec<String>,
+    /// `queued`, `running`, `ok` or `error`.
+    status: String,
+    message: Option<String>,
+    actor_email: Option<String>,
+    started_at: Option<String>,
+    finished_at: Option<String>,
+    created_at: String,
+}
+
+impl From<OffsiteRestoreJobRow> for OffsiteRestoreJobResponse {
+    fn from(row: OffsiteRestoreJobRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            offsite_backup_id: row.offsite_backup_id.to_string(),
+            components: row.components.0,
+            status: row.status,
+            message: row.message,
+            actor_email: row.actor_email,
+            started_at: row.started_at.map(|ts| ts.to_rfc3339()),
+            finished_at: row.finished_at.map(|ts| ts.to_rfc3339()),
+            created_at: row.created_at.to_rfc3339(),
+        }
+    }
+}
+
+const RESTORE_JOB_COLUMNS: &str = r#"
+    id, offsite_backup_id, components, status, message, actor_email,
+    started_at, finished_at, created_at
+"#;
+
+fn parse_id(raw: &str, what: &str) -> Result<Uuid, (StatusCode, String)> {
+    Uuid::parse_str(raw.trim()).map_err(|_| (StatusCode::NOT_FOUND, format!("{what} not found")))
+}
+
+async fn fetch_target(
+    state: &AppState,
+    target_id: &str,
+) -> Result<BackupTargetRow, (StatusCode, String)> {
+    let id = parse_id(target_id, "Backup target")?;
+    offsite_backup::load_target(&state.db, id)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Backup target not found".to_string()))
+}
+
+async fn fetch_snapshot(
+    state: &AppState,
+    backup_id: &str,
+) -> Result<OffsiteBackupRow, (StatusCode, String)> {
+    let id = parse_id(backup_id, "Snapshot")?;
+    offsite_backup::load_backup(&state.db, id)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Snapshot not found".to_string()))
+}
+
+/// Applies an upsert payload over `existing` and validates the result.
+fn merge_target(
+    payload: &BackupTargetUpsertRequest,
+    existing: Option<&BackupTargetRow>,
+) -> Result<(JsonValue, JsonValue), (StatusCode, String)> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name is required".to_string()));
+    }
+    let config = payload
+        .config
+        .clone()
+        .or_else(|| existing.map(|row| row.config.0.clone()))
+        .unwrap_or_else(|| serde_json::json!({}));
+    let mut secrets = existing
+        .and_then(|row| row.secrets.0.as_object().cloned())
+        .unwrap_or_default();
+    for (key, value) in payload.secrets.iter().flatten() {
+        if value.is_empty() {
+            secrets.remove(key);
+        } else {
+            secrets.insert(key.clone(), JsonValue::String(value.clone()));
+        }
+    }
+    let secrets = JsonValue::Object(secrets);
+    BackupStore::parse(payload.kind.trim(), &config, &secrets)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    if payload.interval_hours.is_some_and(|hours| hours <= 0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "interval_hours must be positive".to_string(),
+        ));
+    }
+    for (field, value) in [
+        ("keep_daily", payload.keep_daily),
+        ("keep_weekly", payload.keep_weekly),
+        ("keep_monthly", payload.keep_monthly),
+    ] {
+        if value.is_some_and(|value| value < 0) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("{field} must be zero or more"),
+            ));
+        }
+    }
+    Ok((config, secrets))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/backups/targets",
+    tag = "backups",
+    responses(
+        (status = 200, description = "Offsite backup targets", body = BackupTargetsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_targets(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<BackupTargetsResponse>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &[CAP_BACKUPS_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let rows: Vec<BackupTargetRow> = sqlx::query_as(&format!(
+        "SELECT {TARGET_COLUMNS} FROM backup_targets ORDER BY lower(name) ASC"
+    ))
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    let encryption_configured = matches!(BackupKey::from_config(&state.config), Ok(Some(_)));
+    Ok(Json(BackupTargetsResponse {
+        encryption_configured,
+        targets: rows.into_iter().map(BackupTargetResponse::from).collect(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/backups/targets",
+    tag = "backups",
+    request_body = BackupTargetUpsertRequest,
+    responses(
+        (status = 201, description = "Created backup target", body = BackupTargetResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "Name already in use")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn create_target(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<BackupTargetUpsertRequest>,
+) -> Result<(StatusCode, Json<BackupTargetResponse>), (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let (config, secrets) = merge_target(&payload, None)?;
+    let row: BackupTargetRow = sqlx::query_as(&format!(
+        r#"
+        INSERT INTO backup_targets (
+            name, kind, config, secrets, enabled, interval_hours, include_map_packs,
+            keep_daily, keep_weekly, keep_monthly
+        )
+        VALUES (
+            $1, $2, $3, $4, COALESCE($5, TRUE), COALESCE($6, 24), COALESCE($7, FALSE),
+            COALESCE($8, 7), COALESCE($9, 4), COALESCE($10, 12)
+        )
+        RETURNING {TARGET_COLUMNS}
+        "#
+    ))
+    .bind(payload.name.trim())
+    .bind(payload.kind.trim())
+    .bind(SqlJson(&config))
+    .bind(SqlJson(&secrets))
+    .bind(payload.enabled)
+    .bind(payload.interval_hours)
+    .bind(payload.include_map_packs)
+    .bind(payload.keep_daily)
+    .bind(payload.keep_weekly)
+    .bind(payload.keep_monthly)
+    .fetch_one(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    let response = BackupTargetResponse::from(row);
+    audit_log::record(
+        &state.db,
+        &user,
+        "backup_target.create",
+        "backup_target",
+        Some(&response.id),
+        None,
+        audit_log::snapshot(&response),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/backups/targets/{target_id}",
+    tag = "backups",
+    request_body = BackupTargetUpsertRequest,
+    params(("target_id" = String, Path, description = "Backup target id")),
+    responses(
+        (status = 200, description = "Updated backup target", body = BackupTargetResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found"),
+        (status = 409, description = "Name already in use")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn update_target(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(target_id): Path<String>,
+    Json(payload): Json<BackupTargetUpsertRequest>,
+) -> Result<Json<BackupTargetResponse>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let existing = fetch_target(&state, &target_id).await?;
+    let (config, secrets) = merge_target(&payload, Some(&existing))?;
+    let row: BackupTargetRow = sqlx::query_as(&format!(
+        r#"
+        UPDATE backup_targets
+        SET
+            name = $2,
+            kind = $3,
+            config = $4,
+            secrets = $5,
+            enabled = COALESCE($6, enabled),
+            interval_hours = COALESCE($7, interval_hours),
+            include_map_packs = COALESCE($8, include_map_packs),
+            keep_daily = COALESCE($9, keep_daily),
+            keep_weekly = COALESCE($10, keep_weekly),
+            keep_monthly = COALESCE($11, keep_monthly),
+            updated_at = now()
+        WHERE id = $1
+        RETURNING {TARGET_COLUMNS}
+        "#
+    ))
+    .bind(existing.id)
+    .bind(payload.name.trim())
+    .bind(payload.kind.trim())
+    .bind(SqlJson(&config))
+    .bind(SqlJson(&secrets))
+    .bind(payload.enabled)
+    .bind(payload.interval_hours)
+    .bind(payload.include_map_packs)
+    .bind(payload.keep_daily)
+    .bind(payload.keep_weekly)
+    .bind(payload.keep_monthly)
+    .fetch_one(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    let before = BackupTargetResponse::from(existing);
+    let response = BackupTargetResponse::from(row);
+    audit_log::record(
+        &state.db,
+        &user,
+        "backup_target.update",
+        "backup_target",
+        Some(&response.id),
+        audit_log::snapshot(&before),
+        audit_log::snapshot(&response),
+    )
+    .await;
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/backups/targets/{target_id}",
+    tag = "backups",
+    params(("target_id" = String, Path, description = "Backup target id")),
+    responses(
+        (status = 204, description = "Backup target deleted; stored snapshots are left in place"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn delete_target(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(target_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let existing = fetch_target(&state, &target_id).await?;
+    sqlx::query("DELETE FROM backup_targets WHERE id = $1")
+        .bind(existing.id)
+        .execute(&state.db)
+        .await
+        .map_err(map_db_error)?;
+
+    let before = BackupTargetResponse::from(existing);
+    audit_log::record(
+        &state.db,
+        &user,
+        "backup_target.delete",
+        "backup_target",
+        Some(&before.id),
+        audit_log::snapshot(&before),
+        None,
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/backups/targets/{target_id}/test",
+    tag = "backups",
+    params(("target_id" = String, Path, description = "Backup target id")),
+    responses(
+        (status = 200, description = "Connectivity check result", body = BackupTargetTestResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn test_target(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(target_id): Path<String>,
+) -> Result<Json<BackupTargetTestResponse>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let target = fetch_target(&state, &target_id).await?;
+    let outcome = match target.store() {
+        Ok(store) => store.check().await,
+        Err(err) => Err(err),
+    };
+    Ok(Json(match outcome {
+        Ok(()) => BackupTargetTestResponse {
+            ok: true,
+            message: "Target is reachable and writable.".to_string(),
+        },
+        Err(err) => BackupTargetTestResponse {
+            ok: false,
+            message: format!("{err:#}"),
+        },
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/backups/targets/{target_id}/run",
+    tag = "backups",
+    params(("target_id" = String, Path, description = "Backup target id")),
+    responses(
+        (status = 202, description = "Snapshot started", body = OffsiteBackupResponse),
+        (status = 400, description = "No encryption key configured"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found"),
+        (status = 409, description = "A snapshot is already running for this target")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn run_target(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(target_id): Path<String>,
+) -> Result<(StatusCode, Json<OffsiteBackupResponse>), (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let target = fetch_target(&state, &target_id).await?;
+    if !matches!(BackupKey::from_config(&state.config), Ok(Some(_))) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Configure a backup encryption key before running offsite backups".to_string(),
+        ));
+    }
+    let backup_id = offsite_backup::start_backup(&state.db, &target, "manual")
+        .await
+        .map_err(map_db_error)?
+        .ok_or((
+            StatusCode::CONFLICT,
+            "A snapshot is already running for this target".to_string(),
+        ))?;
+    let row = offsite_backup::load_backup(&state.db, backup_id)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Snapshot not found".to_string()))?;
+
+    audit_log::record(
+        &state.db,
+        &user,
+        "backup_target.run",
+        "backup_target",
+        Some(&target.id.to_string()),
+        None,
+        Some(serde_json::json!({ "offsite_backup_id": backup_id.to_string() })),
+    )
+    .await;
+
+    let worker_state = state.clone();
+    tokio::spawn(async move {
+        offsite_backup::execute_backup(&worker_state, &target, backup_id).await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(row.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/backups/targets/{target_id}/snapshots",
+    tag = "backups",
+    params(("target_id" = String, Path, description = "Backup target id")),
+    responses(
+        (status = 200, description = "Snapshots for the target, newest first", body = Vec<OffsiteBackupResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_snapshots(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(target_id): Path<String>,
+) -> Result<Json<Vec<OffsiteBackupResponse>>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &[CAP_BACKUPS_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let target = fetch_target(&state, &target_id).await?;
+    let rows: Vec<OffsiteBackupRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT {BACKUP_COLUMNS}
+        FROM offsite_backups
+        WHERE target_id = $1
+        ORDER BY started_at DESC
+        LIMIT $2
+        "#
+    ))
+    .bind(target.id)
+    .bind(MAX_SNAPSHOTS_LISTED)
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    Ok(Json(
+        rows.into_iter().map(OffsiteBackupResponse::from).collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/backups/offsite/{backup_id}/verify",
+    tag = "backups",
+    params(("backup_id" = String, Path, description = "Offsite snapshot id")),
+    responses(
+        (status = 202, description = "Verification started; poll the snapshot for verify_status", body = OffsiteBackupResponse),
+        (status = 400, description = "Snapshot is not complete"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn verify_snapshot(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(backup_id): Path<String>,
+) -> Result<(StatusCode, Json<OffsiteBackupResponse>), (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let snapshot = fetch_snapshot(&state, &backup_id).await?;
+    if snapshot.status != "ok" {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Snapshot is {}; only completed snapshots can be verified",
+                snapshot.status
+            ),
+        ));
+    }
+
+    audit_log::record(
+        &state.db,
+        &user,
+        "offsite_backup.verify",
+        "offsite_backup",
+        Some(&snapshot.id.to_string()),
+        None,
+        None,
+    )
+    .await;
+
+    let worker_state = state.clone();
+    let id = snapshot.id;
+    tokio::spawn(async move {
+        if let Err(err) = offsite_backup::verify_backup(&worker_state, id).await {
+            tracing::warn!(offsite_backup_id = %id, "offsite snapshot verification failed: {err:#}");
+        }
+    });
+    Ok((StatusCode::ACCEPTED, Json(snapshot.into())))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/backups/offsite/{backup_id}/restore",
+    tag = "backups",
+    request_body = OffsiteRestoreRequest,
+    params(("backup_id" = String, Path, description = "Offsite snapshot id")),
+    responses(
+        (status = 202, description = "Restore queued", body = OffsiteRestoreJobResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn restore_snapshot(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(backup_id): Path<String>,
+    Json(payload): Json<OffsiteRestoreRequest>,
+) -> Result<(StatusCode, Json<OffsiteRestoreJobResponse>), (StatusCode, String)> {
+    // Restoring replaces users and credentials too, so it needs both.
+    crate::auth::require_capabilities(&user, &["config.write", "users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let snapshot = fetch_snapshot(&state, &backup_id).await?;
+    if snapshot.status != "ok" {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Snapshot is {}; only completed snapshots can be restored",
+                snapshot.status
+            ),
+        ));
+    }
+    let components: Vec<String> = match payload.components {
+        Some(components) => {
+            if components.is_empty() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "components must not be empty".to_string(),
+                ));
+            }
+            for component in &components {
+                if !ALL_COMPONENTS.contains(&component.as_str()) {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("Unknown component {component}"),
+                    ));
+                }
+                if !snapshot.components.0.contains(component) {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("Snapshot does not contain {component}"),
+                    ));
+                }
+            }
+            components
+        }
+        None => snapshot
+            .components
+            .0
+            .iter()
+            .filter(|component| component.as_str() != COMPONENT_MAP)
+            .cloned()
+            .collect(),
+    };
+
+    let row: OffsiteRestoreJobRow = sqlx::query_as(&format!(
+        r#"
+        INSERT INTO offsite_restore_jobs (
+            offsite_backup_id, components, status, actor_user_id, actor_email
+        )
+        VALUES ($1, $2, 'queued', $3, $4)
+        RETURNING {RESTORE_JOB_COLUMNS}
+        "#
+    ))
+    .bind(snapshot.id)
+    .bind(SqlJson(&components))
+    .bind(Uuid::parse_str(&user.id).ok())
+    .bind(&user.email)
+    .fetch_one(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    let response = OffsiteRestoreJobResponse::from(row);
+    audit_log::record(
+        &state.db,
+        &user,
+        "offsite_backup.restore",
+        "offsite_backup",
+        Some(&snapshot.id.to_string()),
+        None,
+        audit_log::snapshot(&response),
+    )
+    .await;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/backups/offsite/restores",
+    tag = "backups",
+    responses(
+        (status = 200, description = "Recent controller restores from offsite snapshots", body = Vec<OffsiteRestoreJobResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_restores(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<Vec<OffsiteRestoreJobResponse>>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &[CAP_BACKUPS_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let rows: Vec<OffsiteRestoreJobRow> = sqlx::query_as(&format!(
+        "SELECT {RESTORE_JOB_COLUMNS} FROM offsite_restore_jobs ORDER BY created_at DESC LIMIT 50"
+    ))
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    Ok(Json(
+        rows.into_iter()
+            .map(OffsiteRestoreJobResponse::from)
+            .collect(),
+    ))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/backups/targets", get(list_targets).post(create_target))
+        .route(
+            "/backups/targets/{target_id}",
+            put(update_target).delete(delete_target),
+        )
+        .route("/backups/targets/{target_id}/test", post(test_target))
+        .route("/backups/targets/{target_id}/run", post(run_target))
+        .route(
+            "/backups/targets/{target_id}/snapshots",
+            get(list_snapshots),
+        )
+        .route("/backups/offsite/restores", get(list_restores))
+        .route("/backups/offsite/{backup_id}/verify", post(verify_snapshot))
+        .route(
+            "/backups/offsite/{backup_id}/restore",
+            post(restore_snapshot),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn existing_s3() -> BackupTargetRow {
+        let now = chrono::Utc::now();
+        BackupTargetRow {
+            id: Uuid::new_v4(),
+            name: "minio".to_string(),
+            kind: "s3".to_string(),
+            config: SqlJson(serde_json::json!({"bucket": "farm"})),
+            secrets: SqlJson(serde_json::json!({
+                "access_key_id": "old-key",
+                "secret_access_key": "old-secret"
+            })),
+            enabled: true,
+            interval_hours: 24,
+            include_map_packs: false,
+            keep_daily: 7,
+            keep_weekly: 4,
+            keep_monthly: 12,
+            last_run_at: None,
+            last_status: None,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn payload(secrets: Option<BTreeMap<String, String>>) -> BackupTargetUpsertRequest {
+        BackupTargetUpsertRequest {
+            name: "minio".to_string(),
+            kind: "s3".to_string(),
+            config: None,
+            secrets,
+            enabled: None,
+            interval_hours: None,
+            include_map_packs: None,
+            keep_daily: None,
+            keep_weekly: None,
+            keep_monthly: None,
+        }
+    }
+
+    #[test]
+    fn update_keeps_omitted_secrets_and_hides_values() {
+        let existing = existing_s3();
+        let update = payload(Some(BTreeMap::from([(
+            "secret_access_key".to_string(),
+            "rotated".to_string(),
+        )])));
+        let (_, secrets) = merge_target(&update, Some(&existing)).unwrap();
+        assert_eq!(secrets["access_key_id"], "old-key");
+        assert_eq!(secrets["secret_access_key"], "rotated");
+
+        let response = BackupTargetResponse::from(existing);
+        let body = serde_json::to_string(&response).unwrap();
+        assert!(!body.contains("old-secret"));
+        assert_eq!(
+            response.secrets_set,
+            vec!["access_key_id", "secret_access_key"]
+        );
+    }
+
+    #[test]
+    fn clearing_a_required_secret_is_rejected() {
+        let update = payload(Some(BTreeMap::from([(
+            "access_key_id".to_string(),
+            String::new(),
+        )])));
+        let err = merge_target(&update, Some(&existing_s3())).unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+
+        let mut bad_interval = payload(None);
+        bad_interval.interval_hours = Some(0);
+        assert!(merge_target(&bad_interval, Some(&existing_s3())).is_err());
+    }
+}
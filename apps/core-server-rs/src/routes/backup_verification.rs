@@ -0,0 +1,198 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use sqlx::types::Json as SqlJson;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::map_db_error;
+use crate::services::audit_log;
+use crate::services::backup_verification::{self, VerificationCheck};
+use crate::state::AppState;
+
+const CAP_BACKUPS_VIEW: &str = "backups.view";
+const MAX_RUNS_LISTED: i64 = 50;
+
+const RUN_COLUMNS: &str = r#"
+    id, trigger, status, dump_bytes, checks, summary, started_at, finished_at
+"#;
+
+#[derive(sqlx::FromRow)]
+struct VerificationRunRow {
+    id: Uuid,
+    trigger: String,
+    status: String,
+    dump_bytes: Option<i64>,
+    checks: SqlJson<Vec<VerificationCheck>>,
+    summary: Option<String>,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct BackupVerificationRunResponse {
+    id: String,
+    /// `schedule` or `manual`.
+    trigger: String,
+    /// `running`, `pass`, `fail` or `error`.
+    status: String,
+    dump_bytes: Option<i64>,
+    checks: Vec<VerificationCheck>,
+    summary: Option<String>,
+    started_at: String,
+    finished_at: Option<String>,
+}
+
+impl From<VerificationRunRow> for BackupVerificationRunResponse {
+    fn from(row: VerificationRunRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            trigger: row.trigger,
+            status: row.status,
+            dump_bytes: row.dump_bytes,
+            checks: row.checks.0,
+            summary: row.summary,
+            started_at: row.started_at.to_rfc3339(),
+            finished_at: row.finished_at.map(|ts| ts.to_rfc3339()),
+        }
+    }
+}
+
+async fn fetch_run(
+    state: &AppState,
+    run_id: Uuid,
+) -> Result<BackupVerificationRunResponse, (StatusCode, String)> {
+    let row: Option<VerificationRunRow> = sqlx::query_as(&format!(
+        "SELECT {RUN_COLUMNS} FROM backup_verification_runs WHERE id = $1"
+    ))
+    .bind(run_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    row.map(BackupVerificationRunResponse::from).ok_or((
+        StatusCode::NOT_FOUND,
+        "Verification run not found".to_string(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/backups/verifications",
+    tag = "backups",
+    responses(
+        (status = 200, description = "Recent backup verification runs, newest first", body = Vec<BackupVerificationRunResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_verifications(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<Vec<BackupVerificationRunResponse>>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &[CAP_BACKUPS_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let rows: Vec<VerificationRunRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT {RUN_COLUMNS}
+        FROM backup_verification_runs
+        ORDER BY started_at DESC
+        LIMIT $1
+        "#
+    ))
+    .bind(MAX_RUNS_LISTED)
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    Ok(Json(
+        rows.into_iter()
+            .map(BackupVerificationRunResponse::from)
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/backups/verifications/{run_id}",
+    tag = "backups",
+    params(("run_id" = String, Path, description = "Verification run id")),
+    responses(
+        (status = 200, description = "Verification run with per-check results", body = BackupVerificationRunResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn get_verification(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(run_id): Path<String>,
+) -> Result<Json<BackupVerificationRunResponse>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &[CAP_BACKUPS_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let run_id = Uuid::parse_str(run_id.trim()).map_err(|_| {
+        (
+            StatusCode::NOT_FOUND,
+            "Verification run not found".to_string(),
+        )
+    })?;
+    Ok(Json(fetch_run(&state, run_id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/backups/verifications/run",
+    tag = "backups",
+    responses(
+        (status = 202, description = "Verification started; poll the run for results", body = BackupVerificationRunResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "A verification is already running")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn run_verification(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<(StatusCode, Json<BackupVerificationRunResponse>), (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let run_id = backup_verification::start_run(&state.db, "manual")
+        .await
+        .map_err(map_db_error)?
+        .ok_or((
+            StatusCode::CONFLICT,
+            "A backup verification is already running".to_string(),
+        ))?;
+    let run = fetch_run(&state, run_id).await?;
+
+    audit_log::record(
+        &state.db,
+        &user,
+        "backup_verification.run",
+        "backup_verification",
+        Some(&run_id.to_string()),
+        None,
+        None,
+    )
+    .await;
+
+    let worker_state = state.clone();
+    tokio::spawn(async move {
+        backup_verification::execute_run(&worker_state, run_id).await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/backups/verifications", get(list_verifications))
+        .route("/backups/verifications/run", post(run_verification))
+        .route("/backups/verifications/{run_id}", get(get_verification))
+}
@@ -16,6 +16,7 @@ use crate::auth::AuthUser;
 use crate::backup_bundle::{BackupOutputSnapshot, NodeBackupBundle, NODE_BACKUP_SCHEMA_VERSION};
 use crate::error::{internal_error, map_db_error};
 use crate::routes::node_sensors::{NodeAds1263SettingsDraft, NodeSensorDraft};
+use crate::services::audit_log;
 use crate::state::AppState;
 
 const CAP_BACKUPS_VIEW: &str = "backups.view";
@@ -165,6 +166,20 @@ pub(crate) async fn run_backups(
         }
     }
 
+    audit_log::record(
+        &state.db,
+        &user,
+        "backup.run",
+        "backup",
+        None,
+        None,
+        Some(serde_json::json!({
+            "nodes": total_nodes,
+            "failed": failures.len(),
+        })),
+    )
+    .await;
+
     Ok(Json(BackupRunResponse {
         status: if failures.is_empty() {
             "ok".to_string()
@@ -636,6 +651,20 @@ pub(crate) async fn restore_backup(
     .execute(&state.db)
     .await
     .map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "backup.restore",
+        "node",
+        Some(&target_node_id.to_string()),
+        None,
+        Some(serde_json::json!({
+            "backup_node_id": backup_node_id,
+            "backup_date": payload.date.trim(),
+            "target_node_id": target_node_id,
+        })),
+    )
+    .await;
 
     Ok(Json(RestoreResponse {
         status: "queued".to_string(),
@@ -729,6 +758,14 @@ pub(crate) async fn update_retention(
     crate::auth::require_capabilities(&user, &["config.write"])
         .map_err(|err| (err.status, err.message))?;
 
+    let previous_keep_days =
+        fetch_default_keep_days(&state.db, state.config.backup_retention_days as i32).await;
+    let before = audit_log::snapshot(
+        &fetch_retention(&state.db, previous_keep_days)
+            .await
+            .map_err(map_db_error)?,
+    );
+
     if let Some(default_keep_days) = payload.default_keep_days {
         if default_keep_days <= 0 {
             return Err((
@@ -779,7 +816,22 @@ pub(crate) async fn update_retention(
         }
     }
 
-    get_retention(axum::extract::State(state), AuthUser(user)).await
+    let default_keep_days =
+        fetch_default_keep_days(&state.db, state.config.backup_retention_days as i32).await;
+    let retention = fetch_retention(&state.db, default_keep_days)
+        .await
+        .map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "backup.retention_update",
+        "backup_retention",
+        None,
+        before,
+        audit_log::snapshot(&retention),
+    )
+    .await;
+    Ok(Json(retention))
 }
 
 #[utoipa::path(
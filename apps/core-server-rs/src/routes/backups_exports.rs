@@ -16,6 +16,7 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::error::{internal_error, map_db_error};
+use crate::services::audit_log;
 use crate::state::AppState;
 
 const POSTGRES_BIN_FALLBACK: &str = "/usr/local/farm-dashboard/native/postgres/bin";
@@ -181,12 +182,12 @@ pub(crate) struct DatabaseExportQuery {
 }
 
 #[derive(Debug, Clone)]
-struct PgConnection {
-    host: Option<String>,
-    port: Option<u16>,
-    user: Option<String>,
-    password: Option<String>,
-    dbname: String,
+pub(crate) struct PgConnection {
+    pub(crate) host: Option<String>,
+    pub(crate) port: Option<u16>,
+    pub(crate) user: Option<String>,
+    pub(crate) password: Option<String>,
+    pub(crate) dbname: String,
 }
 
 async fn fetch_default_keep_days(db: &sqlx::PgPool, fallback: i32) -> i32 {
@@ -232,7 +233,7 @@ async fn existing_public_tables(
     .await
 }
 
-fn find_postgres_tool(tool: &str) -> PathBuf {
+pub(crate) fn find_postgres_tool(tool: &str) -> PathBuf {
     if let Some(path_var) = std::env::var_os("PATH") {
         for path in std::env::split_paths(&path_var) {
             let candidate = path.join(tool);
@@ -248,7 +249,27 @@ fn find_postgres_tool(tool: &str) -> PathBuf {
     PathBuf::from(tool)
 }
 
-fn parse_pg_connection(database_url: &str) -> Result<PgConnection, (StatusCode, String)> {
+/// `tool` with connection flags and password for `pg_conn`; the caller adds `--dbname`.
+pub(crate) fn postgres_command(tool: &str, pg_conn: &PgConnection) -> Command {
+    let mut cmd = Command::new(find_postgres_tool(tool));
+    if let Some(host) = pg_conn.host.as_deref() {
+        cmd.arg("--host").arg(host);
+    }
+    if let Some(port) = pg_conn.port {
+        cmd.arg("--port").arg(port.to_string());
+    }
+    if let Some(user) = pg_conn.user.as_deref() {
+        cmd.arg("--username").arg(user);
+    }
+    if let Some(password) = pg_conn.password.as_deref() {
+        cmd.env("PGPASSWORD", password);
+    }
+    cmd
+}
+
+pub(crate) fn parse_pg_connection(
+    database_url: &str,
+) -> Result<PgConnection, (StatusCode, String)> {
     let url = Url::parse(database_url).map_err(|_| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -936,7 +957,7 @@ pub(crate) async fn import_app_settings(
     }
     write_json_file_atomic(&local_path, &bundle.setup_config.value).await?;
 
-    Ok(Json(AppSettingsImportResponse {
+    let response = AppSettingsImportResponse {
         status: "ok".to_string(),
         applied: AppSettingsImportApplied {
             setup_credentials: bundle.setup_credentials.len(),
@@ -947,10 +968,21 @@ pub(crate) async fn import_app_settings(
             setup_config_written: true,
         },
         warnings,
-    }))
+    };
+    audit_log::record(
+        &state.db,
+        &user,
+        "settings.import",
+        "app_settings",
+        None,
+        None,
+        audit_log::snapshot(&response.applied),
+    )
+    .await;
+    Ok(Json(response))
 }
 
-async fn run_pg_dump_to_file(
+pub(crate) async fn run_pg_dump_to_file(
     pg_conn: &PgConnection,
     tables: Option<&[String]>,
     format_args: &[&str],
@@ -5,6 +5,7 @@ use axum::routing::{delete, get, post};
 use axum::{Json, Router};
 
 use crate::auth::AuthUser;
+use crate::services::audit_log;
 use crate::services::cloud_sync::{
     self, CloudAccessSettingsPatch, CloudIngestResult, CloudRole, CloudSyncPayload,
 };
@@ -210,6 +211,12 @@ pub(crate) async fn update_cloud_access(
         ));
     }
 
+    let before = cloud_sync::load_local_cloud_access_state(&state.db)
+        .await
+        .ok()
+        .and_then(|local| {
+            audit_log::snapshot(&to_cloud_access_response(CloudRole::Local, Some(local), 0))
+        });
     let patch = CloudAccessSettingsPatch {
         cloud_server_base_url: payload.cloud_server_base_url,
         sync_interval_seconds: payload.sync_interval_seconds,
@@ -222,11 +229,18 @@ pub(crate) async fn update_cloud_access(
     let site_count = cloud_sync::count_registered_sites(&state.db)
         .await
         .map_err(map_internal_error)?;
-    Ok(Json(to_cloud_access_response(
-        CloudRole::Local,
-        Some(local),
-        site_count,
-    )))
+    let response = to_cloud_access_response(CloudRole::Local, Some(local), site_count);
+    audit_log::record(
+        &state.db,
+        &user,
+        "cloud_access.update",
+        "cloud_access",
+        None,
+        before,
+        audit_log::snapshot(&response),
+    )
+    .await;
+    Ok(Json(response))
 }
 
 #[utoipa::path(
@@ -258,6 +272,16 @@ pub(crate) async fn rotate_cloud_key(
     cloud_sync::rotate_local_site_key(&state.db)
         .await
         .map_err(map_internal_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "cloud_access.rotate_key",
+        "cloud_access",
+        None,
+        None,
+        None,
+    )
+    .await;
     let local = cloud_sync::load_local_cloud_access_state(&state.db)
         .await
         .map_err(map_internal_error)?;
@@ -323,7 +347,18 @@ pub(crate) async fn register_cloud_site(
     let site = cloud_sync::register_cloud_site(&state.db, &payload.site_name, &payload.site_key)
         .await
         .map_err(map_bad_request)?;
-    Ok(Json(site.into()))
+    let site = CloudSiteResponse::from(site);
+    audit_log::record(
+        &state.db,
+        &user,
+        "cloud_site.register",
+        "cloud_site",
+        Some(&site.site_id),
+        None,
+        audit_log::snapshot(&site),
+    )
+    .await;
+    Ok(Json(site))
 }
 
 #[utoipa::path(
@@ -351,6 +386,16 @@ pub(crate) async fn remove_cloud_site(
     cloud_sync::delete_cloud_site(&state.db, &site_id)
         .await
         .map_err(map_bad_request)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "cloud_site.remove",
+        "cloud_site",
+        Some(&site_id),
+        None,
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -48,7 +48,12 @@ pub(crate) struct DashboardSnapshot {
 }
 
 fn has_any_capability(user: &crate::auth::AuthenticatedUser, options: &[&str]) -> bool {
-    options.iter().any(|cap| user.capabilities.contains(*cap))
+    crate::auth::require_any_capabilities(user, options).is_ok()
+}
+
+/// For sections filtered to the user's nodes, where a scoped grant is enough.
+fn has_any_node_capability(user: &crate::auth::AuthenticatedUser, options: &[&str]) -> bool {
+    crate::auth::require_any_node_capabilities(user, options).is_ok()
 }
 
 async fn build_snapshot(
@@ -56,36 +61,43 @@ async fn build_snapshot(
     headers: &HeaderMap,
     user: &crate::auth::AuthenticatedUser,
 ) -> Result<DashboardSnapshot, (StatusCode, String)> {
-    let can_nodes = has_any_capability(user, &["nodes.view", "config.write"]);
-    let can_sensors = has_any_capability(user, &["sensors.view", "config.write"]);
-    let can_outputs = has_any_capability(user, &["outputs.view", "config.write"]);
+    let can_nodes = has_any_node_capability(user, &["nodes.view", "config.write"]);
+    let can_sensors = has_any_node_capability(user, &["sensors.view", "config.write"]);
+    let can_outputs = has_any_node_capability(user, &["outputs.view", "config.write"]);
     let can_schedules =
         has_any_capability(user, &["schedules.view", "schedules.write", "config.write"]);
-    let can_alerts = has_any_capability(user, &["alerts.view", "config.write"]);
+    let can_alerts = has_any_node_capability(user, &["alerts.view", "config.write"]);
     let can_users = has_any_capability(user, &["users.manage"]);
     let can_analytics = has_any_capability(user, &["analytics.view", "config.write"]);
 
-    let nodes = if can_nodes {
+    let mut nodes = if can_nodes {
         crate::routes::nodes::fetch_nodes(&state.db)
             .await
             .map_err(map_db_error)?
     } else {
         vec![]
     };
-    let sensors = if can_sensors {
+    let node_scope = user.node_scope(&["nodes.view", "config.write"]);
+    nodes.retain(|node| node_scope.allows_str(&node.id));
+    let mut sensors = if can_sensors {
         crate::routes::sensors::fetch_sensors(&state.db, None, false)
             .await
             .map_err(map_db_error)?
     } else {
         vec![]
     };
-    let outputs = if can_outputs {
+    let sensor_scope = user.node_scope(&["sensors.view", "config.write"]);
+    sensors.retain(|sensor| sensor_scope.allows_str(&sensor.node_id));
+    let mut outputs = if can_outputs {
         crate::routes::outputs::fetch_outputs(&state.db, None)
             .await
             .map_err(map_db_error)?
     } else {
         vec![]
     };
+    let output_scope = user.node_scope(&["outputs.view", "config.write"]);
+    outputs.retain(|output| output_scope.allows_str(&output.node_id));
+    let alert_scope = user.node_scope(&["alerts.view", "config.write"]);
     let users = if can_users {
         crate::routes::users::fetch_users(&state.db)
             .await
@@ -101,14 +113,14 @@ async fn build_snapshot(
         vec![]
     };
     let alarms = if can_alerts {
-        crate::routes::alarms::fetch_alarms(&state.db)
+        crate::routes::alarms::fetch_alarms(&state.db, &alert_scope)
             .await
             .map_err(map_db_error)?
     } else {
         vec![]
     };
     let alarm_events = if can_alerts {
-        crate::routes::alarms::fetch_alarm_events(&state.db, 250)
+        crate::routes::alarms::fetch_alarm_events(&state.db, 250, &alert_scope)
             .await
             .map_err(map_db_error)?
     } else {
@@ -168,6 +180,8 @@ pub(crate) async fn build_cloud_sync_snapshot(
         role: "admin".to_string(),
         capabilities,
         source: "cloud-sync".to_string(),
+        session_id: None,
+        scopes: Default::default(),
     };
     let headers = HeaderMap::new();
     build_snapshot(state, &headers, &user).await
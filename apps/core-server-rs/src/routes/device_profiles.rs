@@ -0,0 +1,422 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+use crate::auth::{require_capabilities, AuthUser};
+use crate::device_catalog::{DeviceCatalog, DevicePoint};
+use crate::error::map_db_error;
+use crate::services::device_profiles::{
+    self, inputs_from_catalog_file, validate_profile, DeviceProfileInput, DeviceProfileRow,
+};
+use crate::services::external_devices::{
+    test_read_points, ExternalDeviceConfig, PointTestReadResult,
+};
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeviceProfileResponse {
+    pub id: String,
+    pub vendor_id: String,
+    pub vendor_name: String,
+    pub model_id: String,
+    pub model_name: String,
+    pub since_year: Option<i32>,
+    pub protocols: Vec<String>,
+    pub points: Vec<DevicePoint>,
+    pub created_by: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<DeviceProfileRow> for DeviceProfileResponse {
+    fn from(row: DeviceProfileRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            vendor_id: row.vendor_id,
+            vendor_name: row.vendor_name,
+            model_id: row.model_id,
+            model_name: row.model_name,
+            since_year: row.since_year,
+            protocols: row.protocols.0,
+            points: row.points.0,
+            created_by: row.created_by,
+            created_at: row.created_at.to_rfc3339(),
+            updated_at: row.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeviceProfileImportResponse {
+    pub imported: usize,
+    pub profiles: Vec<DeviceProfileResponse>,
+}
+
+#[derive(Debug, Clone, Deserialize, utoipa::ToSchema)]
+pub struct DeviceProfileTestReadRequest {
+    pub protocol: String,
+    /// Connection settings using the external device config field names (`host`,
+    /// `port`, `unit_id`, `serial_port`, `snmp_community`, `http_base_url`, ...).
+    #[schema(value_type = Object)]
+    pub connection: JsonValue,
+    #[schema(value_type = Vec<DevicePoint>)]
+    pub points: Vec<JsonValue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeviceProfileTestReadResponse {
+    pub protocol: String,
+    pub points: Vec<PointTestReadResult>,
+}
+
+fn validation_error(errors: Vec<String>) -> (StatusCode, String) {
+    (
+        StatusCode::BAD_REQUEST,
+        format!("Invalid device profile: {}", errors.join("; ")),
+    )
+}
+
+fn parse_profile_id(profile_id: &str) -> Result<Uuid, (StatusCode, String)> {
+    Uuid::parse_str(profile_id.trim())
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid profile id".to_string()))
+}
+
+async fn refresh_catalog(state: &AppState) -> Result<(), (StatusCode, String)> {
+    device_profiles::reload_catalog(&state.db)
+        .await
+        .map_err(map_db_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/integrations/devices/catalog/profiles",
+    tag = "integrations",
+    responses(
+        (status = 200, description = "User-defined device profiles", body = Vec<DeviceProfileResponse>)
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn list_device_profiles(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<DeviceProfileResponse>>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.view"]).map_err(|err| (err.status, err.message))?;
+    let rows = device_profiles::list_profiles(&state.db)
+        .await
+        .map_err(map_db_error)?;
+    Ok(Json(
+        rows.into_iter().map(DeviceProfileResponse::from).collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/integrations/devices/catalog/profiles",
+    tag = "integrations",
+    request_body = DeviceProfileInput,
+    responses(
+        (status = 200, description = "Device profile created", body = DeviceProfileResponse),
+        (status = 400, description = "Profile failed validation"),
+        (status = 409, description = "A profile with this vendor/model already exists")
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn create_device_profile(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+    Json(request): Json<DeviceProfileInput>,
+) -> Result<Json<DeviceProfileResponse>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.write"]).map_err(|err| (err.status, err.message))?;
+    let profile = validate_profile(&request).map_err(validation_error)?;
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM device_profiles WHERE vendor_id = $1 AND model_id = $2)",
+    )
+    .bind(&profile.vendor_id)
+    .bind(&profile.model.id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    if exists {
+        return Err((
+            StatusCode::CONFLICT,
+            "A profile with this vendor/model already exists".to_string(),
+        ));
+    }
+    let row = device_profiles::upsert_profile(&state.db, &profile, Some(&user.email))
+        .await
+        .map_err(map_db_error)?;
+    refresh_catalog(&state).await?;
+    Ok(Json(row.into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/integrations/devices/catalog/profiles/{profile_id}",
+    tag = "integrations",
+    request_body = DeviceProfileInput,
+    params(
+        ("profile_id" = String, Path, description = "Device profile id")
+    ),
+    responses(
+        (status = 200, description = "Device profile updated", body = DeviceProfileResponse),
+        (status = 400, description = "Profile failed validation"),
+        (status = 404, description = "Profile not found"),
+        (status = 409, description = "Vendor or model id changed on a profile used by configured devices")
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn update_device_profile(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+    Path(profile_id): Path<String>,
+    Json(request): Json<DeviceProfileInput>,
+) -> Result<Json<DeviceProfileResponse>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.write"]).map_err(|err| (err.status, err.message))?;
+    let profile_id = parse_profile_id(&profile_id)?;
+    let profile = validate_profile(&request).map_err(validation_error)?;
+    let (vendor_id, model_id) = device_profiles::profile_identity(&state.db, profile_id)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Profile not found".to_string()))?;
+    if vendor_id != profile.vendor_id || model_id != profile.model.id {
+        // Devices point at the catalog by vendor and model id, so renaming would orphan them.
+        let in_use = device_profiles::profile_usage(&state.db, profile_id)
+            .await
+            .map_err(map_db_error)?;
+        if in_use > 0 {
+            return Err((
+                StatusCode::CONFLICT,
+                format!(
+                    "Profile is used by {in_use} configured device(s); its vendor and model ids cannot change"
+                ),
+            ));
+        }
+    }
+    let row = device_profiles::update_profile(&state.db, profile_id, &profile)
+        .await
+        .map_err(map_db_error)?;
+    refresh_catalog(&state).await?;
+    Ok(Json(row.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/integrations/devices/catalog/profiles/{profile_id}",
+    tag = "integrations",
+    params(
+        ("profile_id" = String, Path, description = "Device profile id")
+    ),
+    responses(
+        (status = 200, description = "Device profile deleted"),
+        (status = 404, description = "Profile not found"),
+        (status = 409, description = "Profile is used by configured devices")
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn delete_device_profile(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+    Path(profile_id): Path<String>,
+) -> Result<Json<JsonValue>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.write"]).map_err(|err| (err.status, err.message))?;
+    let profile_id = parse_profile_id(&profile_id)?;
+    let in_use = device_profiles::profile_usage(&state.db, profile_id)
+        .await
+        .map_err(map_db_error)?;
+    if in_use > 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Profile is used by {in_use} configured device(s)"),
+        ));
+    }
+    if !device_profiles::delete_profile(&state.db, profile_id)
+        .await
+        .map_err(map_db_error)?
+    {
+        return Err((StatusCode::NOT_FOUND, "Profile not found".to_string()));
+    }
+    refresh_catalog(&state).await?;
+    Ok(Json(json!({ "status": "ok" })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/integrations/devices/catalog/export",
+    tag = "integrations",
+    responses(
+        (status = 200, description = "User-defined profiles in catalog file format", body = DeviceCatalog)
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn export_device_profiles(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<DeviceCatalog>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.view"]).map_err(|err| (err.status, err.message))?;
+    let rows = device_profiles::list_profiles(&state.db)
+        .await
+        .map_err(map_db_error)?;
+    Ok(Json(DeviceCatalog {
+        version: 1,
+        vendors: device_profiles::vendors_from_rows(&rows),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/integrations/devices/catalog/import",
+    tag = "integrations",
+    request_body = DeviceCatalog,
+    responses(
+        (status = 200, description = "Profiles imported", body = DeviceProfileImportResponse),
+        (status = 400, description = "File failed validation; nothing was imported")
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn import_device_profiles(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+    Json(file): Json<JsonValue>,
+) -> Result<Json<DeviceProfileImportResponse>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.write"]).map_err(|err| (err.status, err.message))?;
+    let inputs = inputs_from_catalog_file(&file).map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+
+    let mut profiles = Vec::new();
+    let mut errors = Vec::new();
+    for input in &inputs {
+        match validate_profile(input) {
+            Ok(profile) => profiles.push(profile),
+            Err(problems) => errors.extend(
+                problems
+                    .into_iter()
+                    .map(|problem| format!("{}/{}: {problem}", input.vendor_id, input.model_id)),
+            ),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(validation_error(errors));
+    }
+
+    let mut tx = state.db.begin().await.map_err(map_db_error)?;
+    let mut imported = Vec::new();
+    for profile in &profiles {
+        let row = device_profiles::upsert_profile(&mut *tx, profile, Some(&user.email))
+            .await
+            .map_err(map_db_error)?;
+        imported.push(DeviceProfileResponse::from(row));
+    }
+    tx.commit().await.map_err(map_db_error)?;
+    refresh_catalog(&state).await?;
+
+    Ok(Json(DeviceProfileImportResponse {
+        imported: imported.len(),
+        profiles: imported,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/integrations/devices/catalog/test-read",
+    tag = "integrations",
+    request_body = DeviceProfileTestReadRequest,
+    responses(
+        (status = 200, description = "Decoded values from the live device", body = DeviceProfileTestReadResponse),
+        (status = 400, description = "Invalid points or connection settings"),
+        (status = 502, description = "Device could not be reached")
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn test_read_device_profile(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+    Json(request): Json<DeviceProfileTestReadRequest>,
+) -> Result<Json<DeviceProfileTestReadResponse>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.write"]).map_err(|err| (err.status, err.message))?;
+    let candidate = DeviceProfileInput {
+        vendor_id: "test_read".to_string(),
+        vendor_name: "Test read".to_string(),
+        model_id: "candidate".to_string(),
+        model_name: "Candidate".to_string(),
+        since_year: None,
+        protocols: vec![request.protocol.clone()],
+        points: request.points,
+    };
+    let profile = validate_profile(&candidate).map_err(validation_error)?;
+    if profile.model.points.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "At least one point is required".to_string(),
+        ));
+    }
+
+    let mut connection = match request.connection {
+        JsonValue::Object(map) => map,
+        JsonValue::Null => serde_json::Map::new(),
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "connection must be a JSON object".to_string(),
+            ))
+        }
+    };
+    connection.insert("vendor_id".to_string(), json!(candidate.vendor_id));
+    connection.insert("model_id".to_string(), json!(candidate.model_id));
+    connection.insert("protocol".to_string(), json!(request.protocol));
+    let config: ExternalDeviceConfig = serde_json::from_value(JsonValue::Object(connection))
+        .map_err(|err| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid connection: {err}"),
+            )
+        })?;
+
+    let points = test_read_points(&state, &config, &profile.model.points)
+        .await
+        .map_err(|err| (StatusCode::BAD_GATEWAY, format!("{err:#}")))?;
+    Ok(Json(DeviceProfileTestReadResponse {
+        protocol: config.protocol,
+        points,
+    }))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/integrations/devices/catalog/profiles",
+            get(list_device_profiles).post(create_device_profile),
+        )
+        .route(
+            "/integrations/devices/catalog/profiles/{profile_id}",
+            put(update_device_profile).delete(delete_device_profile),
+        )
+        .route(
+            "/integrations/devices/catalog/export",
+            get(export_device_profiles),
+        )
+        .route(
+            "/integrations/devices/catalog/import",
+            post(import_device_profiles),
+        )
+        .route(
+            "/integrations/devices/catalog/test-read",
+            post(test_read_device_profile),
+        )
+}
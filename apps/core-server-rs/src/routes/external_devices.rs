@@ -13,7 +13,9 @@ use crate::device_catalog;
 use crate::device_catalog::DeviceVendor;
 use crate::error::map_db_error;
 use crate::services::external_devices::{
+    discover_opcua_endpoints, discovered_mqtt_devices, model_supports_protocol,
     sweep_external_network, ExternalDeviceConfig, ExternalDeviceSweepCandidate,
+    MqttDiscoveredDevice, MqttOutputConfig, OpcuaEndpointSummary,
 };
 use crate::state::AppState;
 
@@ -69,9 +71,48 @@ pub struct ExternalDeviceCreateRequest {
     pub bacnet_bbmd_host: Option<String>,
     pub bacnet_bbmd_port: Option<u16>,
     pub bacnet_foreign_ttl_seconds: Option<u16>,
+    pub modbus_request_delay_ms: Option<u64>,
+    pub modbus_max_block_registers: Option<u16>,
+    pub modbus_max_register_gap: Option<u16>,
+    pub serial_port: Option<String>,
+    pub serial_baud_rate: Option<u32>,
+    pub serial_parity: Option<String>,
+    pub serial_stop_bits: Option<u8>,
+    pub serial_data_bits: Option<u8>,
+    pub opcua_endpoint_url: Option<String>,
+    pub opcua_security_policy: Option<String>,
+    pub opcua_security_mode: Option<String>,
+    pub opcua_username: Option<String>,
+    pub opcua_password: Option<String>,
+    pub opcua_client_cert_pem: Option<String>,
+    pub opcua_client_key_pem: Option<String>,
+    pub opcua_trust_server_cert: Option<bool>,
+    pub opcua_publishing_interval_ms: Option<u64>,
+    pub opcua_browse_root: Option<String>,
+    pub mqtt_base_topic: Option<String>,
+    pub mqtt_device_identifier: Option<String>,
+    pub mqtt_outputs: Option<Vec<MqttOutputConfig>>,
     pub external_id: Option<String>,
 }
 
+#[derive(Debug, Clone, Deserialize, utoipa::ToSchema)]
+pub struct OpcuaEndpointDiscoveryRequest {
+    pub endpoint_url: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct OpcuaEndpointDiscoveryResponse {
+    pub endpoint_url: String,
+    pub endpoints: Vec<OpcuaEndpointSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct MqttDiscoveryResponse {
+    pub devices: Vec<MqttDiscoveredDevice>,
+}
+
 #[utoipa::path(
     get,
     path = "/api/integrations/devices/catalog",
@@ -87,10 +128,10 @@ pub async fn get_device_catalog(
     AuthUser(user): AuthUser,
 ) -> Result<Json<ExternalDeviceCatalogResponse>, (StatusCode, String)> {
     require_capabilities(&user, &["config.view"]).map_err(|err| (err.status, err.message))?;
-    let catalog = device_catalog::catalog();
+    let catalog = device_catalog::merged_catalog();
     Ok(Json(ExternalDeviceCatalogResponse {
         version: catalog.version,
-        vendors: catalog.vendors.clone(),
+        vendors: catalog.vendors,
     }))
 }
 
@@ -160,11 +201,7 @@ pub async fn create_device(
     require_capabilities(&user, &["config.write"]).map_err(|err| (err.status, err.message))?;
     let model = device_catalog::find_model(&request.vendor_id, &request.model_id)
         .ok_or_else(|| (StatusCode::BAD_REQUEST, "Unknown device model".to_string()))?;
-    if !model
-        .protocols
-        .iter()
-        .any(|entry| entry == &request.protocol)
-    {
+    if !model_supports_protocol(&model, &request.protocol) {
         return Err((
             StatusCode::BAD_REQUEST,
             "Protocol not supported for this model".to_string(),
@@ -209,6 +246,19 @@ pub async fn create_device(
         }
     }
 
+    let serial_port = request
+        .serial_port
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+    if request.protocol == "modbus_rtu" && serial_port.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "serial_port is required for Modbus RTU".to_string(),
+        ));
+    }
+
     let device_config = ExternalDeviceConfig {
         vendor_id: request.vendor_id.clone(),
         model_id: request.model_id.clone(),
@@ -233,19 +283,64 @@ pub async fn create_device(
         bacnet_bbmd_host: request.bacnet_bbmd_host.clone(),
         bacnet_bbmd_port: request.bacnet_bbmd_port,
         bacnet_foreign_ttl_seconds: request.bacnet_foreign_ttl_seconds,
+        modbus_request_delay_ms: request.modbus_request_delay_ms,
+        modbus_max_block_registers: request.modbus_max_block_registers,
+        modbus_max_register_gap: request.modbus_max_register_gap,
+        serial_port: serial_port.clone(),
+        serial_baud_rate: request.serial_baud_rate,
+        serial_parity: request.serial_parity.clone(),
+        serial_stop_bits: request.serial_stop_bits,
+        serial_data_bits: request.serial_data_bits,
+        opcua_endpoint_url: request.opcua_endpoint_url.clone(),
+        opcua_security_policy: request.opcua_security_policy.clone(),
+        opcua_security_mode: request.opcua_security_mode.clone(),
+        opcua_username: request.opcua_username.clone(),
+        opcua_password: request.opcua_password.clone(),
+        opcua_client_cert_pem: request.opcua_client_cert_pem.clone(),
+        opcua_client_key_pem: request.opcua_client_key_pem.clone(),
+        opcua_trust_server_cert: request.opcua_trust_server_cert,
+        opcua_publishing_interval_ms: request.opcua_publishing_interval_ms,
+        opcua_browse_root: request.opcua_browse_root.clone(),
+        mqtt_base_topic: trimmed(request.mqtt_base_topic.as_deref()),
+        mqtt_device_identifier: trimmed(request.mqtt_device_identifier.as_deref()),
+        mqtt_outputs: request.mqtt_outputs.clone(),
         discovered_points: None,
     };
+    if request.protocol == "opcua" {
+        crate::services::external_devices::opcua_endpoint_url(&device_config)
+            .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    }
+    if request.protocol == "mqtt" {
+        validate_mqtt_device(&model, &device_config)?;
+    }
     let external_id = request.external_id.clone().unwrap_or_else(|| {
         let host = host
             .clone()
             .or_else(|| http_base_url.clone())
+            .or_else(|| serial_port.clone())
+            .or_else(|| device_config.mqtt_device_identifier.clone())
+            .or_else(|| device_config.mqtt_base_topic.clone())
+            .or_else(|| {
+                request
+                    .opcua_endpoint_url
+                    .as_deref()
+                    .and_then(|value| Url::parse(value.trim()).ok())
+                    .and_then(|parsed| parsed.host_str().map(str::to_string))
+            })
             .unwrap_or_else(|| "unknown".to_string());
-        format!(
+        let base = format!(
             "{}:{}:{}",
             request.vendor_id.trim(),
             request.model_id.trim(),
             host.trim()
-        )
+        );
+        // Several RTU slaves share one bus address, so the unit id keeps them distinct.
+        match request.protocol.as_str() {
+            "modbus_rtu" | "modbus_rtu_over_tcp" => {
+                format!("{base}:{}", request.unit_id.unwrap_or(1))
+            }
+            _ => base,
+        }
     });
 
     let config = json!({
@@ -383,10 +478,137 @@ pub async fn sweep_devices(
     Ok(Json(ExternalDeviceSweepResponse { range, candidates }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/integrations/devices/opcua/endpoints",
+    tag = "integrations",
+    request_body = OpcuaEndpointDiscoveryRequest,
+    responses(
+        (status = 200, description = "Endpoints advertised by the OPC UA server", body = OpcuaEndpointDiscoveryResponse)
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn discover_opcua_device_endpoints(
+    AuthUser(user): AuthUser,
+    State(state): State<AppState>,
+    Json(request): Json<OpcuaEndpointDiscoveryRequest>,
+) -> Result<Json<OpcuaEndpointDiscoveryResponse>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.view"]).map_err(|err| (err.status, err.message))?;
+    let config: ExternalDeviceConfig = serde_json::from_value(json!({
+        "vendor_id": "opcua",
+        "model_id": "opcua_server",
+        "protocol": "opcua",
+        "host": request.host,
+        "port": request.port,
+        "opcua_endpoint_url": request.endpoint_url,
+    }))
+    .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    let endpoint_url = crate::services::external_devices::opcua_endpoint_url(&config)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    let endpoints = discover_opcua_endpoints(&state, &config)
+        .await
+        .map_err(|err| (StatusCode::BAD_GATEWAY, format!("{err:#}")))?;
+    Ok(Json(OpcuaEndpointDiscoveryResponse {
+        endpoint_url,
+        endpoints,
+    }))
+}
+
+fn trimmed(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// MQTT devices need somewhere to get topics from: the model's points, Home Assistant
+/// discovery, or explicitly configured command outputs.
+fn validate_mqtt_device(
+    model: &device_catalog::DeviceModel,
+    config: &ExternalDeviceConfig,
+) -> Result<(), (StatusCode, String)> {
+    let model_topics = model.points.iter().any(|point| point.protocol == "mqtt");
+    let outputs = config.mqtt_outputs.as_deref().unwrap_or_default();
+    if !model_topics && config.mqtt_device_identifier.is_none() && outputs.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "MQTT devices need catalog points, a Home Assistant device identifier, or outputs"
+                .to_string(),
+        ));
+    }
+    let uses_base_topic = model.points.iter().any(|point| {
+        point.protocol == "mqtt"
+            && point
+                .path
+                .as_deref()
+                .is_some_and(|path| path.contains("{base_topic}"))
+    }) || outputs
+        .iter()
+        .any(|output| output.command_topic.contains("{base_topic}"));
+    if uses_base_topic && config.mqtt_base_topic.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "mqtt_base_topic is required for this model".to_string(),
+        ));
+    }
+    let mut ids = std::collections::HashSet::new();
+    for output in outputs {
+        if output.id.trim().is_empty() || output.command_topic.trim().is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "MQTT outputs need an id and a command_topic".to_string(),
+            ));
+        }
+        if output.command_topic.contains(['+', '#']) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("command_topic for output {} cannot contain wildcards", output.id),
+            ));
+        }
+        if !ids.insert(output.id.trim()) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("duplicate MQTT output id {}", output.id),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/integrations/devices/mqtt/discovered",
+    tag = "integrations",
+    responses(
+        (status = 200, description = "Devices announced through Home Assistant MQTT discovery", body = MqttDiscoveryResponse)
+    ),
+    security(
+        ("HTTPBearer" = [])
+    )
+)]
+pub async fn list_mqtt_discovered_devices(
+    AuthUser(user): AuthUser,
+) -> Result<Json<MqttDiscoveryResponse>, (StatusCode, String)> {
+    require_capabilities(&user, &["config.view"]).map_err(|err| (err.status, err.message))?;
+    Ok(Json(MqttDiscoveryResponse {
+        devices: discovered_mqtt_devices(),
+    }))
+}
+
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/integrations/devices/catalog", get(get_device_catalog))
         .route("/integrations/devices/sweep", post(sweep_devices))
+        .route(
+            "/integrations/devices/opcua/endpoints",
+            post(discover_opcua_device_endpoints),
+        )
+        .route(
+            "/integrations/devices/mqtt/discovered",
+            get(list_mqtt_discovered_devices),
+        )
         .route(
             "/integrations/devices",
             get(list_devices).post(create_device),
@@ -0,0 +1,347 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use sqlx::types::Json as SqlJson;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::map_db_error;
+use crate::services::audit_log;
+use crate::services::forwarder_config::{
+    self, drift_status, ForwarderConfigDocument, ForwarderSettings,
+};
+use crate::state::AppState;
+
+const CAP_NODES_VIEW: &str = "nodes.view";
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct ForwarderConfigUpdateRequest {
+    settings: ForwarderSettings,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct ForwarderConfigResponse {
+    node_id: String,
+    node_name: String,
+    /// Id the node's forwarder publishes under; documents are only delivered once it is known.
+    node_mqtt_id: Option<String>,
+    /// False when no overrides are stored and the node runs on its environment values.
+    configured: bool,
+    settings: ForwarderSettings,
+    desired_version: String,
+    updated_at: Option<String>,
+    updated_by: Option<String>,
+    applied_version: Option<String>,
+    applied_at: Option<String>,
+    rejected_version: Option<String>,
+    error: Option<String>,
+    /// Values the forwarder reported it is running with.
+    effective: Option<JsonValue>,
+    reported_at: Option<String>,
+    /// `in_sync`, `pending`, `rejected` or `unreported`.
+    drift: String,
+    /// Desired settings that differ from the reported values.
+    drifted_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct ForwarderConfigRow {
+    node_id: Uuid,
+    node_name: String,
+    node_mqtt_id: Option<String>,
+    settings: Option<SqlJson<JsonValue>>,
+    updated_at: Option<DateTime<Utc>>,
+    updated_by: Option<String>,
+    applied_version: Option<String>,
+    applied_at: Option<DateTime<Utc>>,
+    rejected_version: Option<String>,
+    error: Option<String>,
+    effective: Option<SqlJson<JsonValue>>,
+    reported_at: Option<DateTime<Utc>>,
+}
+
+impl From<ForwarderConfigRow> for ForwarderConfigResponse {
+    fn from(row: ForwarderConfigRow) -> Self {
+        let configured = row.settings.is_some();
+        let settings: ForwarderSettings = row
+            .settings
+            .and_then(|raw| serde_json::from_value(raw.0).ok())
+            .unwrap_or_default();
+        let desired_version = settings.version();
+        let effective = row.effective.map(|raw| raw.0);
+        let drift = drift_status(
+            &desired_version,
+            row.applied_version.as_deref(),
+            row.rejected_version.as_deref(),
+            row.reported_at.is_some(),
+        );
+        let drifted_fields = match (&effective, drift) {
+            (Some(effective), "in_sync" | "pending") => settings.drifted_fields(effective),
+            _ => Vec::new(),
+        };
+        Self {
+            node_id: row.node_id.to_string(),
+            node_name: row.node_name,
+            node_mqtt_id: row.node_mqtt_id,
+            configured,
+            settings,
+            desired_version,
+            updated_at: row.updated_at.map(|v| v.to_rfc3339()),
+            updated_by: row.updated_by,
+            applied_version: row.applied_version,
+            applied_at: row.applied_at.map(|v| v.to_rfc3339()),
+            rejected_version: row.rejected_version,
+            error: row.error,
+            effective,
+            reported_at: row.reported_at.map(|v| v.to_rfc3339()),
+            drift: drift.to_string(),
+            drifted_fields,
+        }
+    }
+}
+
+const CONFIG_SELECT: &str = r#"
+    SELECT
+        n.id AS node_id,
+        n.name AS node_name,
+        NULLIF(TRIM(n.config->>'agent_node_id'), '') AS node_mqtt_id,
+        c.settings,
+        c.updated_at,
+        c.updated_by,
+        s.applied_version,
+        s.applied_at,
+        s.rejected_version,
+        s.error,
+        s.effective,
+        s.reported_at
+    FROM nodes n
+    LEFT JOIN node_forwarder_config c ON c.node_id = n.id
+    LEFT JOIN node_forwarder_config_state s
+        ON s.node_mqtt_id = NULLIF(TRIM(n.config->>'agent_node_id'), '')
+"#;
+
+async fn fetch_config(
+    db: &PgPool,
+    node_id: Uuid,
+) -> Result<Option<ForwarderConfigRow>, sqlx::Error> {
+    sqlx::query_as(&format!("{CONFIG_SELECT} WHERE n.id = $1"))
+        .bind(node_id)
+        .fetch_optional(db)
+        .await
+}
+
+fn parse_node_id(node_id: &str) -> Result<Uuid, (StatusCode, String)> {
+    Uuid::parse_str(node_id).map_err(|_| (StatusCode::BAD_REQUEST, "Invalid node id".to_string()))
+}
+
+/// Delivers the document right away instead of waiting for the publisher's next pass.
+async fn publish_now(state: &AppState, row: &ForwarderConfigRow, settings: &ForwarderSettings) {
+    let Some(node_mqtt_id) = row.node_mqtt_id.as_deref() else {
+        return;
+    };
+    let document = ForwarderConfigDocument::new(settings.clone());
+    if let Err(err) = forwarder_config::publish_document(&state.mqtt, node_mqtt_id, &document).await
+    {
+        tracing::warn!(node = %node_mqtt_id, error = %err, "failed to publish forwarder config");
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/nodes/forwarder-config",
+    tag = "nodes",
+    responses(
+        (status = 200, description = "Desired and applied forwarder config per node", body = Vec<ForwarderConfigResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_forwarder_configs(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<Vec<ForwarderConfigResponse>>, (StatusCode, String)> {
+    crate::auth::require_any_node_capabilities(&user, &[CAP_NODES_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+    let scope = user.node_scope(&[CAP_NODES_VIEW, "config.write"]);
+    let mut rows: Vec<ForwarderConfigRow> =
+        sqlx::query_as(&format!("{CONFIG_SELECT} ORDER BY n.name ASC, n.id ASC"))
+            .fetch_all(&state.db)
+            .await
+            .map_err(map_db_error)?;
+    rows.retain(|row| scope.allows(row.node_id));
+    Ok(Json(rows.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/nodes/{node_id}/forwarder-config",
+    tag = "nodes",
+    params(("node_id" = String, Path, description = "Node UUID")),
+    responses(
+        (status = 200, description = "Desired and applied forwarder config", body = ForwarderConfigResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Node not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn get_forwarder_config(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(node_id): Path<String>,
+) -> Result<Json<ForwarderConfigResponse>, (StatusCode, String)> {
+    crate::auth::require_any_node_capabilities(&user, &[CAP_NODES_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+    let node_uuid = parse_node_id(&node_id)?;
+    if !user
+        .node_scope(&[CAP_NODES_VIEW, "config.write"])
+        .allows(node_uuid)
+    {
+        return Err((StatusCode::NOT_FOUND, "Node not found".to_string()));
+    }
+    let row = fetch_config(&state.db, node_uuid)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Node not found".to_string()))?;
+    Ok(Json(row.into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/nodes/{node_id}/forwarder-config",
+    tag = "nodes",
+    params(("node_id" = String, Path, description = "Node UUID")),
+    request_body = ForwarderConfigUpdateRequest,
+    responses(
+        (status = 200, description = "Updated forwarder config", body = ForwarderConfigResponse),
+        (status = 400, description = "Invalid settings"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Node not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn put_forwarder_config(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(node_id): Path<String>,
+    Json(payload): Json<ForwarderConfigUpdateRequest>,
+) -> Result<Json<ForwarderConfigResponse>, (StatusCode, String)> {
+    let node_uuid = parse_node_id(&node_id)?;
+    crate::auth::require_node_capabilities(&user, &["config.write"], node_uuid)
+        .map_err(|err| (err.status, err.message))?;
+    let errors = payload.settings.validate();
+    if !errors.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, errors.join("; ")));
+    }
+
+    let before = fetch_config(&state.db, node_uuid)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Node not found".to_string()))?;
+    let settings_json = serde_json::to_value(&payload.settings)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    sqlx::query(
+        r#"
+        INSERT INTO node_forwarder_config (node_id, settings, updated_at, updated_by)
+        VALUES ($1, $2, now(), $3)
+        ON CONFLICT (node_id)
+        DO UPDATE SET
+            settings = EXCLUDED.settings,
+            updated_at = EXCLUDED.updated_at,
+            updated_by = EXCLUDED.updated_by
+        "#,
+    )
+    .bind(node_uuid)
+    .bind(SqlJson(&settings_json))
+    .bind(&user.id)
+    .execute(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    audit_log::record(
+        &state.db,
+        &user,
+        "node_forwarder.config.update",
+        "node",
+        Some(&node_id),
+        before.settings.as_ref().map(|raw| raw.0.clone()),
+        Some(settings_json),
+    )
+    .await;
+    publish_now(&state, &before, &payload.settings).await;
+
+    let row = fetch_config(&state.db, node_uuid)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Node not found".to_string()))?;
+    Ok(Json(row.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/nodes/{node_id}/forwarder-config",
+    tag = "nodes",
+    params(("node_id" = String, Path, description = "Node UUID")),
+    responses(
+        (status = 200, description = "Overrides removed; the node returns to its environment values", body = ForwarderConfigResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Node not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn delete_forwarder_config(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(node_id): Path<String>,
+) -> Result<Json<ForwarderConfigResponse>, (StatusCode, String)> {
+    let node_uuid = parse_node_id(&node_id)?;
+    crate::auth::require_node_capabilities(&user, &["config.write"], node_uuid)
+        .map_err(|err| (err.status, err.message))?;
+    let before = fetch_config(&state.db, node_uuid)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Node not found".to_string()))?;
+
+    if before.settings.is_some() {
+        sqlx::query("DELETE FROM node_forwarder_config WHERE node_id = $1")
+            .bind(node_uuid)
+            .execute(&state.db)
+            .await
+            .map_err(map_db_error)?;
+        audit_log::record(
+            &state.db,
+            &user,
+            "node_forwarder.config.delete",
+            "node",
+            Some(&node_id),
+            before.settings.as_ref().map(|raw| raw.0.clone()),
+            None,
+        )
+        .await;
+        publish_now(&state, &before, &ForwarderSettings::default()).await;
+    }
+
+    let row = fetch_config(&state.db, node_uuid)
+        .await
+        .map_err(map_db_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Node not found".to_string()))?;
+    Ok(Json(row.into()))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/nodes/forwarder-config", get(list_forwarder_configs))
+        .route(
+            "/nodes/{node_id}/forwarder-config",
+            get(get_forwarder_config)
+                .put(put_forwarder_config)
+                .delete(delete_forwarder_config),
+        )
+}
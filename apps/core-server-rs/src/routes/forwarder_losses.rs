@@ -0,0 +1,186 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::map_db_error;
+use crate::services::forwarder_losses::lost_samples;
+use crate::state::AppState;
+
+const CAP_NODES_VIEW: &str = "nodes.view";
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::IntoParams)]
+pub(crate) struct DataLossQuery {
+    /// Node UUID; events are matched through the node's forwarder id.
+    node_id: Option<String>,
+    /// Only events reported at or after this RFC3339 time.
+    from: Option<String>,
+    /// Only events reported before this RFC3339 time.
+    to: Option<String>,
+    #[param(minimum = 1, maximum = 1000)]
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct DataLossEventResponse {
+    id: String,
+    /// `None` until a node reports the forwarder id the loss was published under.
+    node_id: Option<String>,
+    node_name: Option<String>,
+    node_mqtt_id: String,
+    stream_id: String,
+    start_seq: i64,
+    end_seq: i64,
+    samples: i64,
+    /// `dropped`, `expired`, `corrupt`, ... as reported by the forwarder.
+    kind: String,
+    reason: Option<String>,
+    dropped_at: Option<String>,
+    /// Last sample before the gap.
+    time_start: Option<String>,
+    /// First sample after the gap; `None` until the node delivers one.
+    time_end: Option<String>,
+    received_at: String,
+    annotation_id: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct DataLossRow {
+    id: i64,
+    node_id: Option<Uuid>,
+    node_name: Option<String>,
+    node_mqtt_id: String,
+    stream_id: Uuid,
+    start_seq: i64,
+    end_seq: i64,
+    kind: String,
+    reason: Option<String>,
+    dropped_at: Option<DateTime<Utc>>,
+    time_start: Option<DateTime<Utc>>,
+    time_end: Option<DateTime<Utc>>,
+    received_at: DateTime<Utc>,
+    annotation_id: Option<Uuid>,
+}
+
+impl From<DataLossRow> for DataLossEventResponse {
+    fn from(row: DataLossRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            node_id: row.node_id.map(|value| value.to_string()),
+            node_name: row.node_name,
+            node_mqtt_id: row.node_mqtt_id,
+            stream_id: row.stream_id.to_string(),
+            start_seq: row.start_seq,
+            end_seq: row.end_seq,
+            samples: lost_samples(row.start_seq, row.end_seq),
+            kind: row.kind,
+            reason: row.reason,
+            dropped_at: row.dropped_at.map(|ts| ts.to_rfc3339()),
+            time_start: row.time_start.map(|ts| ts.to_rfc3339()),
+            time_end: row.time_end.map(|ts| ts.to_rfc3339()),
+            received_at: row.received_at.to_rfc3339(),
+            annotation_id: row.annotation_id.map(|value| value.to_string()),
+        }
+    }
+}
+
+fn parse_rfc3339_optional(
+    raw: Option<&str>,
+    field: &str,
+) -> Result<Option<DateTime<Utc>>, (StatusCode, String)> {
+    let Some(raw) = raw.map(str::trim).filter(|raw| !raw.is_empty()) else {
+        return Ok(None);
+    };
+    DateTime::parse_from_rfc3339(raw)
+        .map(|ts| Some(ts.with_timezone(&Utc)))
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("{field} must be RFC3339")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/data-losses",
+    tag = "nodes",
+    params(DataLossQuery),
+    responses(
+        (status = 200, description = "Data lost by node forwarders, newest first", body = Vec<DataLossEventResponse>),
+        (status = 400, description = "Invalid filter"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Node not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_data_losses(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Query(query): Query<DataLossQuery>,
+) -> Result<Json<Vec<DataLossEventResponse>>, (StatusCode, String)> {
+    crate::auth::require_any_node_capabilities(&user, &[CAP_NODES_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+    let scope = user.node_scope(&[CAP_NODES_VIEW, "config.write"]);
+    let node_id: Option<Uuid> = query
+        .node_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(Uuid::parse_str)
+        .transpose()
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                "node_id must be a UUID".to_string(),
+            )
+        })?;
+    if node_id.is_some_and(|node_id| !scope.allows(node_id)) {
+        return Err((StatusCode::NOT_FOUND, "Node not found".to_string()));
+    }
+    let from = parse_rfc3339_optional(query.from.as_deref(), "from")?;
+    let to = parse_rfc3339_optional(query.to.as_deref(), "to")?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let rows: Vec<DataLossRow> = sqlx::query_as(
+        r#"
+        SELECT
+            e.id,
+            n.id AS node_id,
+            n.name AS node_name,
+            e.node_mqtt_id,
+            e.stream_id,
+            e.start_seq,
+            e.end_seq,
+            e.kind,
+            e.reason,
+            e.dropped_at,
+            e.time_start,
+            e.time_end,
+            e.received_at,
+            e.annotation_id
+        FROM node_forwarder_loss_events e
+        LEFT JOIN nodes n ON NULLIF(TRIM(n.config->>'agent_node_id'), '') = e.node_mqtt_id
+        WHERE ($1::uuid IS NULL OR n.id = $1)
+          AND ($2::timestamptz IS NULL OR e.received_at >= $2)
+          AND ($3::timestamptz IS NULL OR e.received_at < $3)
+          AND ($5::uuid[] IS NULL OR n.id = ANY($5))
+        ORDER BY e.received_at DESC, e.id DESC
+        LIMIT $4
+        "#,
+    )
+    .bind(node_id)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .bind(scope.node_ids())
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    Ok(Json(rows.into_iter().map(Into::into).collect()))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/data-losses", get(list_data_losses))
+}
@@ -0,0 +1,728 @@
+use std::collections::HashMap;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{RawQuery, State};
+use axum::http::{header, HeaderName, HeaderValue, StatusCode};
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use serde_json::json;
+use tokio_util::io::ReaderStream;
+use url::form_urlencoded;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::{internal_error, map_db_error};
+use crate::services::analysis::parquet_duckdb::{BucketAggregationMode, MetricsExportRow};
+use crate::services::derived_sensors;
+use crate::services::metrics_export::{self, ExportChunk, ExportMode};
+use crate::state::AppState;
+
+const CAP_METRICS_VIEW: &str = "metrics.view";
+const MAX_EXPORT_SENSORS: usize = 100;
+const DEFAULT_CHUNK_DAYS: i64 = 31;
+const MAX_CHUNK_DAYS: i64 = 366;
+/// Rows encoded per streamed body frame.
+const ROWS_PER_FRAME: usize = 5_000;
+const NON_STORED_SOURCES: &[&str] = &[
+    derived_sensors::SENSOR_CONFIG_SOURCE_DERIVED,
+    "forecast_points",
+];
+
+const HEADER_CHUNK_START: &str = "x-export-chunk-start";
+const HEADER_CHUNK_END: &str = "x-export-chunk-end";
+const HEADER_NEXT_CURSOR: &str = "x-export-next-cursor";
+const HEADER_SOURCE: &str = "x-export-source";
+const HEADER_ROWS: &str = "x-export-rows";
+const HEADER_TIMEZONE: &str = "x-export-timezone";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Csv,
+    Ndjson,
+    Parquet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CsvLayout {
+    Long,
+    Wide,
+}
+
+#[derive(Debug, Clone)]
+struct ExportParams {
+    sensor_ids: Vec<String>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    cursor: DateTime<Utc>,
+    mode: ExportMode,
+    format: ExportFormat,
+    layout: CsvLayout,
+    tz: Tz,
+    chunk_span: Duration,
+    metadata: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, sqlx::FromRow)]
+struct ExportSensor {
+    sensor_id: String,
+    name: String,
+    #[serde(rename = "type")]
+    sensor_type: String,
+    unit: String,
+    interval_seconds: i32,
+    node_id: Uuid,
+    node_name: String,
+    #[serde(skip)]
+    source: Option<String>,
+}
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, message.into())
+}
+
+fn parse_ts(raw: &str, field: &str) -> Result<DateTime<Utc>, (StatusCode, String)> {
+    DateTime::parse_from_rfc3339(raw.trim())
+        .map(|ts| ts.with_timezone(&Utc))
+        .map_err(|_| bad_request(format!("Invalid {field} timestamp")))
+}
+
+fn parse_aggregation(raw: &str) -> Option<BucketAggregationMode> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "avg" | "mean" => Some(BucketAggregationMode::Avg),
+        "last" => Some(BucketAggregationMode::Last),
+        "sum" => Some(BucketAggregationMode::Sum),
+        "min" => Some(BucketAggregationMode::Min),
+        "max" => Some(BucketAggregationMode::Max),
+        _ => None,
+    }
+}
+
+fn aggregation_name(aggregation: BucketAggregationMode) -> &'static str {
+    match aggregation {
+        BucketAggregationMode::Avg => "avg",
+        BucketAggregationMode::Last => "last",
+        BucketAggregationMode::Sum => "sum",
+        BucketAggregationMode::Min => "min",
+        BucketAggregationMode::Max => "max",
+    }
+}
+
+fn parse_params(raw: Option<&str>) -> Result<ExportParams, (StatusCode, String)> {
+    let mut sensor_ids: Vec<String> = Vec::new();
+    let mut values: HashMap<String, String> = HashMap::new();
+    for (key, value) in form_urlencoded::parse(raw.unwrap_or("").as_bytes()) {
+        match key.as_ref() {
+            "sensor_ids[]" | "sensor_ids" => {
+                let value = value.trim();
+                if !value.is_empty() && !sensor_ids.iter().any(|id| id == value) {
+                    sensor_ids.push(value.to_string());
+                }
+            }
+            _ => {
+                values.insert(key.into_owned(), value.trim().to_string());
+            }
+        }
+    }
+    let get = |key: &str| {
+        values
+            .get(key)
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    };
+
+    if sensor_ids.is_empty() {
+        return Err(bad_request("At least one sensor_ids value is required"));
+    }
+    if sensor_ids.len() > MAX_EXPORT_SENSORS {
+        return Err(bad_request(format!(
+            "Too many sensor_ids (max {MAX_EXPORT_SENSORS})"
+        )));
+    }
+
+    let start = parse_ts(get("start").ok_or(bad_request("Missing start"))?, "start")?;
+    let end = parse_ts(get("end").ok_or(bad_request("Missing end"))?, "end")?;
+    if end <= start {
+        return Err(bad_request("end must be after start"));
+    }
+    let cursor = match get("cursor") {
+        Some(raw) => parse_ts(raw, "cursor")?,
+        None => start,
+    };
+    if cursor < start || cursor >= end {
+        return Err(bad_request("cursor must be within [start,end)"));
+    }
+
+    let interval = match get("interval") {
+        Some(raw) => raw
+            .parse::<i64>()
+            .map_err(|_| bad_request("interval must be a number of seconds"))?,
+        None => 0,
+    };
+    let mode = if interval > 0 {
+        let aggregation = match get("agg") {
+            Some(raw) => parse_aggregation(raw)
+                .ok_or(bad_request("agg must be avg, last, sum, min or max"))?,
+            None => BucketAggregationMode::Avg,
+        };
+        ExportMode::Buckets {
+            interval_seconds: interval,
+            aggregation,
+        }
+    } else {
+        ExportMode::Raw
+    };
+
+    let format = match get("format").map(str::to_ascii_lowercase).as_deref() {
+        None | Some("csv") => ExportFormat::Csv,
+        Some("ndjson") | Some("jsonl") => ExportFormat::Ndjson,
+        Some("parquet") => ExportFormat::Parquet,
+        Some(_) => return Err(bad_request("format must be csv, ndjson or parquet")),
+    };
+    let layout = match get("layout").map(str::to_ascii_lowercase).as_deref() {
+        None | Some("long") => CsvLayout::Long,
+        Some("wide") => CsvLayout::Wide,
+        Some(_) => return Err(bad_request("layout must be long or wide")),
+    };
+    if layout == CsvLayout::Wide && format != ExportFormat::Csv {
+        return Err(bad_request("The wide layout is only available for CSV"));
+    }
+    let tz = match get("tz") {
+        Some(raw) => raw
+            .parse::<Tz>()
+            .map_err(|_| bad_request(format!("Unknown time zone: {raw}")))?,
+        None => Tz::UTC,
+    };
+    let chunk_days = match get("chunk_days") {
+        Some(raw) => raw
+            .parse::<i64>()
+            .ok()
+            .filter(|days| (1..=MAX_CHUNK_DAYS).contains(days))
+            .ok_or(bad_request(format!(
+                "chunk_days must be between 1 and {MAX_CHUNK_DAYS}"
+            )))?,
+        None => DEFAULT_CHUNK_DAYS,
+    };
+    let metadata = !matches!(get("metadata"), Some("false") | Some("0"));
+
+    Ok(ExportParams {
+        sensor_ids,
+        start,
+        end,
+        cursor,
+        mode,
+        format,
+        layout,
+        tz,
+        chunk_span: Duration::days(chunk_days),
+        metadata,
+    })
+}
+
+fn format_ts(ts: DateTime<Utc>, tz: Tz) -> String {
+    ts.with_timezone(&tz)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn format_cursor(ts: DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn export_metadata(
+    params: &ExportParams,
+    sensors: &[ExportSensor],
+    chunk: &ExportChunk,
+) -> serde_json::Value {
+    let (mode, interval_seconds, aggregation) = match params.mode {
+        ExportMode::Raw => ("raw", None, None),
+        ExportMode::Buckets {
+            interval_seconds,
+            aggregation,
+        } => (
+            "buckets",
+            Some(interval_seconds),
+            Some(aggregation_name(aggregation)),
+        ),
+    };
+    json!({
+        "mode": mode,
+        "interval_seconds": interval_seconds,
+        "aggregation": aggregation,
+        "timezone": params.tz.name(),
+        "start": format_cursor(params.start),
+        "end": format_cursor(params.end),
+        "chunk_start": format_cursor(chunk.start),
+        "chunk_end": format_cursor(chunk.end),
+        "next_cursor": chunk.next_cursor.map(format_cursor),
+        "source": chunk.source.as_str(),
+        "sensors": sensors,
+    })
+}
+
+/// Sensor metadata as `#`-prefixed CSV lines ahead of the data header.
+fn csv_metadata_lines(sensors: &[ExportSensor], tz: Tz) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::WriterBuilder::new().from_writer(Vec::new());
+    writer.write_record([
+        "sensor_id",
+        "name",
+        "type",
+        "unit",
+        "interval_seconds",
+        "node_id",
+        "node_name",
+    ])?;
+    for sensor in sensors {
+        writer.write_record([
+            sensor.sensor_id.clone(),
+            sensor.name.clone(),
+            sensor.sensor_type.clone(),
+            sensor.unit.clone(),
+            sensor.interval_seconds.to_string(),
+            sensor.node_id.to_string(),
+            sensor.node_name.clone(),
+        ])?;
+    }
+    let body = writer
+        .into_inner()
+        .map_err(|err| csv::Error::from(err.into_error()))?;
+    let mut out = format!("# timezone: {}\n", tz.name()).into_bytes();
+    for line in String::from_utf8_lossy(&body).lines() {
+        out.extend_from_slice(b"# ");
+        out.extend_from_slice(line.as_bytes());
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+fn csv_record(fields: &[String]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    // Writing to a Vec cannot fail.
+    let _ = writer.write_record(fields);
+    writer.into_inner().unwrap_or_default()
+}
+
+/// Rows grouped by timestamp with one value slot per sensor, in `sensor_ids` order.
+fn pivot_wide(
+    rows: Vec<MetricsExportRow>,
+    sensor_ids: &[String],
+) -> Vec<(DateTime<Utc>, Vec<Option<f64>>)> {
+    let index: HashMap<&str, usize> = sensor_ids
+        .iter()
+        .enumerate()
+        .map(|(idx, id)| (id.as_str(), idx))
+        .collect();
+    let mut out: Vec<(DateTime<Utc>, Vec<Option<f64>>)> = Vec::new();
+    for row in rows {
+        let Some(&slot) = index.get(row.sensor_id.as_str()) else {
+            continue;
+        };
+        if out.last().map(|(ts, _)| *ts) != Some(row.ts) {
+            out.push((row.ts, vec![None; sensor_ids.len()]));
+        }
+        if let Some((_, values)) = out.last_mut() {
+            values[slot] = Some(row.value);
+        }
+    }
+    out
+}
+
+/// Lazily encodes `items` in frames of [`ROWS_PER_FRAME`].
+fn framed<T: Send + 'static>(
+    items: Vec<T>,
+    mut encode: impl FnMut(T, &mut Vec<u8>) + Send + 'static,
+) -> impl Iterator<Item = Vec<u8>> + Send + 'static {
+    let mut items = items.into_iter();
+    std::iter::from_fn(move || {
+        let mut frame = Vec::new();
+        for item in items.by_ref().take(ROWS_PER_FRAME) {
+            encode(item, &mut frame);
+        }
+        (!frame.is_empty()).then_some(frame)
+    })
+}
+
+fn text_body(head: Vec<u8>, frames: impl Iterator<Item = Vec<u8>> + Send + 'static) -> Body {
+    let frames = std::iter::once(head)
+        .chain(frames)
+        .filter(|frame| !frame.is_empty())
+        .map(|frame| Ok::<_, std::io::Error>(Bytes::from(frame)));
+    Body::from_stream(futures::stream::iter(frames))
+}
+
+fn encode_text(
+    params: &ExportParams,
+    sensors: &[ExportSensor],
+    chunk: ExportChunk,
+) -> Result<Body, (StatusCode, String)> {
+    let tz = params.tz;
+    let extra_column = params.mode.extra_column();
+    match (params.format, params.layout) {
+        (ExportFormat::Ndjson, _) => {
+            let mut head = Vec::new();
+            if params.metadata {
+                serde_json::to_writer(
+                    &mut head,
+                    &json!({ "metadata": export_metadata(params, sensors, &chunk) }),
+                )
+                .map_err(internal_error)?;
+                head.push(b'\n');
+            }
+            let frames = framed(chunk.rows, move |row, out| {
+                let line = json!({
+                    "timestamp": format_ts(row.ts, tz),
+                    "sensor_id": row.sensor_id,
+                    "value": row.value,
+                    extra_column: row.extra,
+                });
+                let _ = serde_json::to_writer(&mut *out, &line);
+                out.push(b'\n');
+            });
+            Ok(text_body(head, frames))
+        }
+        (_, CsvLayout::Long) => {
+            let mut head = Vec::new();
+            if params.metadata {
+                head = csv_metadata_lines(sensors, tz).map_err(internal_error)?;
+            }
+            head.extend(csv_record(&[
+                "timestamp".to_string(),
+                "sensor_id".to_string(),
+                "value".to_string(),
+                extra_column.to_string(),
+            ]));
+            let frames = framed(chunk.rows, move |row, out| {
+                out.extend(csv_record(&[
+                    format_ts(row.ts, tz),
+                    row.sensor_id,
+                    row.value.to_string(),
+                    row.extra.to_string(),
+                ]));
+            });
+            Ok(text_body(head, frames))
+        }
+        (_, CsvLayout::Wide) => {
+            let mut head = Vec::new();
+            if params.metadata {
+                head = csv_metadata_lines(sensors, tz).map_err(internal_error)?;
+            }
+            let mut columns = vec!["timestamp".to_string()];
+            columns.extend(params.sensor_ids.iter().cloned());
+            head.extend(csv_record(&columns));
+            let rows = pivot_wide(chunk.rows, &params.sensor_ids);
+            let frames = framed(rows, move |(ts, values), out| {
+                let mut fields = Vec::with_capacity(values.len() + 1);
+                fields.push(format_ts(ts, tz));
+                fields.extend(
+                    values
+                        .into_iter()
+                        .map(|value| value.map(|v| v.to_string()).unwrap_or_default()),
+                );
+                out.extend(csv_record(&fields));
+            });
+            Ok(text_body(head, frames))
+        }
+    }
+}
+
+async fn load_sensors(
+    state: &AppState,
+    user: &crate::auth::AuthenticatedUser,
+    sensor_ids: &[String],
+) -> Result<Vec<ExportSensor>, (StatusCode, String)> {
+    let rows: Vec<ExportSensor> = sqlx::query_as(
+        r#"
+        SELECT
+            s.sensor_id,
+            s.name,
+            s.type AS sensor_type,
+            s.unit,
+            s.interval_seconds,
+            s.node_id,
+            n.name AS node_name,
+            COALESCE(s.config, '{}'::jsonb)->>'source' AS source
+        FROM sensors s
+        JOIN nodes n ON n.id = s.node_id
+        WHERE s.sensor_id = ANY($1)
+        "#,
+    )
+    .bind(sensor_ids)
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    let scope = user.node_scope(&[CAP_METRICS_VIEW, "config.write"]);
+    let mut by_id: HashMap<String, ExportSensor> = rows
+        .into_iter()
+        .filter(|sensor| scope.allows(sensor.node_id))
+        .map(|sensor| (sensor.sensor_id.clone(), sensor))
+        .collect();
+    let missing: Vec<&str> = sensor_ids
+        .iter()
+        .filter(|id| !by_id.contains_key(*id))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Unknown sensors: {}", missing.join(", ")),
+        ));
+    }
+    let computed: Vec<&str> = sensor_ids
+        .iter()
+        .filter(|id| {
+            by_id[*id]
+                .source
+                .as_deref()
+                .is_some_and(|source| NON_STORED_SOURCES.contains(&source))
+        })
+        .map(String::as_str)
+        .collect();
+    if !computed.is_empty() {
+        return Err(bad_request(format!(
+            "Derived and forecast sensors have no stored series to export: {}",
+            computed.join(", ")
+        )));
+    }
+    Ok(sensor_ids
+        .iter()
+        .filter_map(|id| by_id.remove(id))
+        .collect())
+}
+
+fn set_header(
+    response: &mut Response,
+    name: &'static str,
+    value: &str,
+) -> Result<(), (StatusCode, String)> {
+    response.headers_mut().insert(
+        HeaderName::from_static(name),
+        HeaderValue::from_str(value).map_err(internal_error)?,
+    );
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/metrics/export",
+    tag = "metrics",
+    params(
+        ("sensor_ids" = Vec<String>, Query, description = "Sensor ids (repeatable, max 100)"),
+        ("start" = String, Query, description = "Start timestamp (RFC3339, inclusive)"),
+        ("end" = String, Query, description = "End timestamp (RFC3339, exclusive)"),
+        ("interval" = Option<i64>, Query, description = "Bucket interval in seconds; omit for raw points. Buckets are aligned to the Unix epoch."),
+        ("agg" = Option<String>, Query, description = "Bucket aggregation: avg (default), last, sum, min or max"),
+        ("format" = Option<String>, Query, description = "csv (default), ndjson or parquet"),
+        ("layout" = Option<String>, Query, description = "CSV layout: long (default, one row per sensor and timestamp) or wide (one column per sensor)"),
+        ("tz" = Option<String>, Query, description = "IANA time zone for CSV/NDJSON timestamps (default UTC); Parquet always stores UTC"),
+        ("cursor" = Option<String>, Query, description = "Resume at this chunk start, taken from x-export-next-cursor"),
+        ("chunk_days" = Option<i64>, Query, description = "Maximum days per chunk (1-366, default 31)"),
+        ("metadata" = Option<bool>, Query, description = "Include sensor metadata (CSV comment lines, NDJSON first line, Parquet footer); default true")
+    ),
+    responses(
+        (status = 200, description = "One chunk of the export. x-export-next-cursor is set while more chunks remain; x-export-chunk-start/-end, x-export-source and x-export-rows describe this chunk.", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Unknown sensors")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn export_metrics(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    RawQuery(raw): RawQuery,
+) -> Result<Response, (StatusCode, String)> {
+    crate::auth::require_any_node_capabilities(&user, &[CAP_METRICS_VIEW, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let params = parse_params(raw.as_deref())?;
+    let sensors = load_sensors(&state, &user, &params.sensor_ids).await?;
+
+    let chunk = metrics_export::read_chunk(
+        &state.db,
+        state.analysis_jobs.duckdb(),
+        state.analysis_jobs.lake_config(),
+        &params.sensor_ids,
+        params.mode,
+        params.cursor,
+        params.end,
+        params.chunk_span,
+    )
+    .await
+    .map_err(internal_error)?;
+
+    let (chunk_start, chunk_end, next_cursor, source, row_count) = (
+        chunk.start,
+        chunk.end,
+        chunk.next_cursor,
+        chunk.source,
+        chunk.rows.len(),
+    );
+    let (body, content_type, extension) = match params.format {
+        ExportFormat::Parquet => {
+            let tmp_dir = state.config.analysis_tmp_path.clone();
+            tokio::fs::create_dir_all(&tmp_dir)
+                .await
+                .map_err(internal_error)?;
+            let out = tempfile::Builder::new()
+                .prefix("metrics-export-")
+                .suffix(".parquet")
+                .tempfile_in(&tmp_dir)
+                .map_err(internal_error)?;
+            let kv_metadata = if params.metadata {
+                vec![(
+                    "farm_dashboard.export".to_string(),
+                    export_metadata(&params, &sensors, &chunk).to_string(),
+                )]
+            } else {
+                vec![]
+            };
+            state
+                .analysis_jobs
+                .duckdb()
+                .write_metrics_export_parquet(
+                    chunk.rows,
+                    params.mode.extra_column(),
+                    kv_metadata,
+                    out.path().to_path_buf(),
+                )
+                .await
+                .map_err(internal_error)?;
+            // The open handle keeps the data readable once the temp path is removed.
+            let file = tokio::fs::File::open(out.path())
+                .await
+                .map_err(internal_error)?;
+            drop(out);
+            (
+                Body::from_stream(ReaderStream::new(file)),
+                "application/vnd.apache.parquet",
+                "parquet",
+            )
+        }
+        ExportFormat::Ndjson => (
+            encode_text(&params, &sensors, chunk)?,
+            "application/x-ndjson",
+            "ndjson",
+        ),
+        ExportFormat::Csv => (
+            encode_text(&params, &sensors, chunk)?,
+            "text/csv; charset=utf-8",
+            "csv",
+        ),
+    };
+
+    let filename = format!(
+        "metrics-{}-{}.{extension}",
+        chunk_start.format("%Y%m%dT%H%M%SZ"),
+        chunk_end.format("%Y%m%dT%H%M%SZ")
+    );
+    let mut response = Response::new(body);
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{filename}\""))
+            .map_err(internal_error)?,
+    );
+    set_header(
+        &mut response,
+        HEADER_CHUNK_START,
+        &format_cursor(chunk_start),
+    )?;
+    set_header(&mut response, HEADER_CHUNK_END, &format_cursor(chunk_end))?;
+    if let Some(next_cursor) = next_cursor {
+        set_header(
+            &mut response,
+            HEADER_NEXT_CURSOR,
+            &format_cursor(next_cursor),
+        )?;
+    }
+    set_header(&mut response, HEADER_SOURCE, source.as_str())?;
+    set_header(&mut response, HEADER_ROWS, &row_count.to_string())?;
+    set_header(&mut response, HEADER_TIMEZONE, params.tz.name())?;
+    Ok(response)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/metrics/export", get(export_metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn row(sensor_id: &str, minute: u32, value: f64) -> MetricsExportRow {
+        MetricsExportRow {
+            sensor_id: sensor_id.to_string(),
+            ts: Utc.with_ymd_and_hms(2026, 3, 1, 0, minute, 0).unwrap(),
+            value,
+            extra: 0,
+        }
+    }
+
+    #[test]
+    fn params_default_to_raw_long_csv_in_utc() {
+        let params = parse_params(Some(
+            "sensor_ids=a&sensor_ids=b&sensor_ids=a&start=2026-01-01T00:00:00Z&end=2026-02-01T00:00:00Z",
+        ))
+        .unwrap();
+        assert_eq!(params.sensor_ids, vec!["a", "b"]);
+        assert_eq!(params.mode, ExportMode::Raw);
+        assert_eq!(params.format, ExportFormat::Csv);
+        assert_eq!(params.layout, CsvLayout::Long);
+        assert_eq!(params.tz, Tz::UTC);
+        assert_eq!(params.cursor, params.start);
+        assert!(params.metadata);
+    }
+
+    #[test]
+    fn params_reject_invalid_combinations() {
+        let base = "sensor_ids=a&start=2026-01-01T00:00:00Z&end=2026-02-01T00:00:00Z";
+        for extra in [
+            "&format=parquet&layout=wide",
+            "&tz=Mars/Olympus",
+            "&interval=60&agg=median",
+            "&cursor=2026-03-01T00:00:00Z",
+            "&chunk_days=0",
+        ] {
+            let err = parse_params(Some(format!("{base}{extra}").as_str())).unwrap_err();
+            assert_eq!(err.0, StatusCode::BAD_REQUEST, "{extra}");
+        }
+        let params = parse_params(Some(
+            format!("{base}&interval=3600&agg=last&tz=America/Chicago&format=ndjson").as_str(),
+        ))
+        .unwrap();
+        assert_eq!(
+            params.mode,
+            ExportMode::Buckets {
+                interval_seconds: 3600,
+                aggregation: BucketAggregationMode::Last
+            }
+        );
+        assert_eq!(params.tz, chrono_tz::America::Chicago);
+    }
+
+    #[test]
+    fn wide_pivot_aligns_sensors_by_timestamp() {
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let rows = vec![row("a", 0, 1.0), row("b", 0, 2.0), row("b", 1, 3.0)];
+        let wide = pivot_wide(rows, &ids);
+        assert_eq!(wide.len(), 2);
+        assert_eq!(wide[0].1, vec![Some(1.0), Some(2.0)]);
+        assert_eq!(wide[1].1, vec![None, Some(3.0)]);
+    }
+
+    #[test]
+    fn timestamps_render_in_the_requested_zone() {
+        let ts = Utc.with_ymd_and_hms(2026, 7, 1, 12, 0, 0).unwrap();
+        assert_eq!(format_ts(ts, Tz::UTC), "2026-07-01T12:00:00Z");
+        assert_eq!(
+            format_ts(ts, chrono_tz::America::Chicago),
+            "2026-07-01T07:00:00-05:00"
+        );
+    }
+}
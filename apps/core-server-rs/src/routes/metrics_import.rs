@@ -0,0 +1,392 @@
+use std::path::Path;
+
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::Utc;
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::{internal_error, map_db_error};
+use crate::services::analysis::jobs::{AnalysisJobCreateRequest, AnalysisJobCreateResponse};
+use crate::services::audit_log;
+use crate::services::metrics_import::{
+    create_upload_dir, prune_stale_uploads, read_csv_headers, read_upload, remove_upload,
+    upload_data_path, write_upload, ImportFormat, ImportUpload, MetricsImportParams,
+    IMPORT_UPLOAD_TTL_HOURS, MAX_IMPORT_UPLOAD_BYTES, METRICS_IMPORT_JOB_TYPE,
+};
+use crate::state::AppState;
+
+const CAP_METRICS_INGEST: &str = "metrics.ingest";
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ImportUploadQuery {
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    filename: Option<String>,
+    #[serde(default)]
+    delimiter: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct MetricsImportUploadResponse {
+    upload_id: String,
+    format: ImportFormat,
+    filename: Option<String>,
+    bytes: u64,
+    /// Column names to reference in the import mappings.
+    columns: Vec<String>,
+    expires_at: String,
+}
+
+fn parse_delimiter(raw: Option<&str>) -> Result<char, (StatusCode, String)> {
+    match raw {
+        None | Some("") => Ok(','),
+        Some("tab") | Some("\\t") | Some("\t") => Ok('\t'),
+        Some(raw) => {
+            let mut chars = raw.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) if ch.is_ascii() && !ch.is_ascii_alphanumeric() => Ok(ch),
+                _ => Err((
+                    StatusCode::BAD_REQUEST,
+                    "delimiter must be a single punctuation character or 'tab'".to_string(),
+                )),
+            }
+        }
+    }
+}
+
+fn detect_format(
+    query: &ImportUploadQuery,
+    headers: &HeaderMap,
+) -> Result<ImportFormat, (StatusCode, String)> {
+    query
+        .format
+        .as_deref()
+        .and_then(ImportFormat::parse)
+        .or_else(|| {
+            query
+                .filename
+                .as_deref()
+                .and_then(ImportFormat::from_filename)
+        })
+        .or_else(|| {
+            headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| ImportFormat::parse(value.split(';').next().unwrap_or_default()))
+        })
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "Set format=csv or format=parquet".to_string(),
+        ))
+}
+
+/// Streams a request body to `path`, refusing bodies over `max_bytes`.
+pub(crate) async fn write_body(
+    body: Body,
+    path: &Path,
+    max_bytes: u64,
+) -> Result<u64, (StatusCode, String)> {
+    let mut stream = body.into_data_stream();
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(internal_error)?;
+    let mut written: u64 = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|err| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Upload interrupted: {err}"),
+            )
+        })?;
+        written += chunk.len() as u64;
+        if written > max_bytes {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Upload exceeds {max_bytes} bytes"),
+            ));
+        }
+        file.write_all(&chunk).await.map_err(internal_error)?;
+    }
+    file.flush().await.map_err(internal_error)?;
+    Ok(written)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/metrics/imports/uploads",
+    tag = "metrics",
+    params(
+        ("format" = Option<String>, Query, description = "csv or parquet; inferred from filename or Content-Type when omitted"),
+        ("filename" = Option<String>, Query, description = "Original file name, kept for reference"),
+        ("delimiter" = Option<String>, Query, description = "CSV delimiter: one punctuation character or 'tab' (default ',')")
+    ),
+    request_body(content = String, description = "Raw CSV or Parquet file", content_type = "application/octet-stream"),
+    responses(
+        (status = 201, description = "Upload stored; reference upload_id when starting the import", body = MetricsImportUploadResponse),
+        (status = 400, description = "Invalid request or unreadable file"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 413, description = "Payload too large")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn upload_import_file(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Query(query): Query<ImportUploadQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, Json<MetricsImportUploadResponse>), (StatusCode, String)> {
+    crate::auth::require_any_node_capabilities(&user, &[CAP_METRICS_INGEST, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let format = detect_format(&query, &headers)?;
+    let delimiter = parse_delimiter(query.delimiter.as_deref())?;
+    let tmp_path = state.config.analysis_tmp_path.clone();
+    let now = Utc::now();
+    prune_stale_uploads(&tmp_path, now);
+
+    let upload_id = Uuid::new_v4();
+    create_upload_dir(&tmp_path, upload_id).map_err(internal_error)?;
+    let data_path = upload_data_path(&tmp_path, upload_id, format);
+    let stored: Result<(u64, Vec<String>), (StatusCode, String)> = async {
+        let bytes = write_body(body, &data_path, MAX_IMPORT_UPLOAD_BYTES).await?;
+        if bytes == 0 {
+            return Err((StatusCode::BAD_REQUEST, "Upload is empty".to_string()));
+        }
+        let columns = match format {
+            ImportFormat::Csv => read_csv_headers(&data_path, delimiter),
+            ImportFormat::Parquet => {
+                state
+                    .analysis_jobs
+                    .duckdb()
+                    .describe_parquet_columns(data_path.clone())
+                    .await
+            }
+        }
+        .map_err(|err| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Could not read the uploaded file: {err}"),
+            )
+        })?;
+        Ok((bytes, columns))
+    }
+    .await;
+    let (bytes, columns) = match stored {
+        Ok(stored) => stored,
+        Err(err) => {
+            remove_upload(&tmp_path, upload_id);
+            return Err(err);
+        }
+    };
+
+    let filename = query
+        .filename
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    let upload = ImportUpload {
+        upload_id,
+        format,
+        filename: filename.clone(),
+        bytes,
+        columns: columns.clone(),
+        delimiter,
+        created_by: user.id.clone(),
+        created_at: now,
+    };
+    write_upload(&tmp_path, &upload).map_err(internal_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(MetricsImportUploadResponse {
+            upload_id: upload_id.to_string(),
+            format,
+            filename,
+            bytes,
+            columns,
+            expires_at: (now + chrono::Duration::hours(IMPORT_UPLOAD_TTL_HOURS)).to_rfc3339(),
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/metrics/imports",
+    tag = "metrics",
+    request_body = MetricsImportParams,
+    responses(
+        (status = 200, description = "Import job created; follow it through /api/analysis/jobs/{id}", body = AnalysisJobCreateResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Unknown upload or sensors"),
+        (status = 429, description = "Too many active analysis jobs")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn create_import(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Json(params): Json<MetricsImportParams>,
+) -> Result<Json<AnalysisJobCreateResponse>, (StatusCode, String)> {
+    crate::auth::require_any_node_capabilities(&user, &[CAP_METRICS_INGEST, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    params
+        .validate()
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+    let upload = read_upload(&state.config.analysis_tmp_path, &params.upload_id)
+        .map_err(internal_error)?
+        .filter(|upload| upload.created_by == user.id)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "Upload not found or expired".to_string(),
+        ))?;
+    params
+        .check_columns(&upload.columns)
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+
+    let mut sensor_ids: Vec<String> = params
+        .mappings
+        .iter()
+        .map(|mapping| mapping.sensor_id.trim().to_string())
+        .collect();
+    sensor_ids.sort();
+    sensor_ids.dedup();
+    let rows: Vec<(String, Uuid)> = sqlx::query_as(
+        r#"
+        SELECT sensor_id, node_id
+        FROM sensors
+        WHERE sensor_id = ANY($1)
+          AND deleted_at IS NULL
+        "#,
+    )
+    .bind(&sensor_ids)
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    let scope = user.node_scope(&[CAP_METRICS_INGEST, "config.write"]);
+    let allowed: std::collections::HashSet<String> = rows
+        .into_iter()
+        .filter(|(_, node_id)| scope.allows(*node_id))
+        .map(|(sensor_id, _)| sensor_id)
+        .collect();
+    let missing: Vec<&str> = sensor_ids
+        .iter()
+        .filter(|id| !allowed.contains(*id))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Unknown sensors: {}", missing.join(", ")),
+        ));
+    }
+
+    let user_id = user.user_id();
+    if let Some(user_id) = user_id {
+        let active_jobs = state
+            .analysis_jobs
+            .count_active_jobs_for_user(user_id)
+            .await
+            .map_err(map_db_error)?;
+        crate::routes::analysis::enforce_max_active_jobs(
+            state.config.analysis_max_jobs_per_user,
+            active_jobs,
+        )?;
+    }
+    let request = AnalysisJobCreateRequest {
+        job_type: METRICS_IMPORT_JOB_TYPE.to_string(),
+        params: serde_json::to_value(&params).map_err(internal_error)?,
+        job_key: Some(format!("upload:{}", upload.upload_id)),
+        dedupe: false,
+    };
+    let (job, _created) = state
+        .analysis_jobs
+        .create_job(&request, user_id)
+        .await
+        .map_err(map_db_error)?;
+
+    if !params.dry_run {
+        audit_log::record(
+            &state.db,
+            &user,
+            "metrics.import",
+            "analysis_job",
+            Some(&job.id.to_string()),
+            None,
+            Some(serde_json::json!({
+                "upload_id": upload.upload_id.to_string(),
+                "filename": upload.filename,
+                "bytes": upload.bytes,
+                "sensor_ids": sensor_ids,
+                "lake_cutoff": params.lake_cutoff,
+            })),
+        )
+        .await;
+    }
+
+    Ok(Json(AnalysisJobCreateResponse {
+        job: job.to_public(),
+    }))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/metrics/imports", post(create_import))
+        .route("/metrics/imports/uploads", post(upload_import_file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn query(format: Option<&str>, filename: Option<&str>) -> ImportUploadQuery {
+        ImportUploadQuery {
+            format: format.map(str::to_string),
+            filename: filename.map(str::to_string),
+            delimiter: None,
+        }
+    }
+
+    #[test]
+    fn delimiters_are_single_punctuation_or_tab() {
+        assert_eq!(parse_delimiter(None).unwrap(), ',');
+        assert_eq!(parse_delimiter(Some(";")).unwrap(), ';');
+        assert_eq!(parse_delimiter(Some("tab")).unwrap(), '\t');
+        assert!(parse_delimiter(Some("x")).is_err());
+        assert!(parse_delimiter(Some(";;")).is_err());
+    }
+
+    #[test]
+    fn format_comes_from_query_then_filename_then_content_type() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/csv; charset=utf-8"),
+        );
+        assert_eq!(
+            detect_format(&query(Some("parquet"), Some("a.csv")), &headers).unwrap(),
+            ImportFormat::Parquet
+        );
+        assert_eq!(
+            detect_format(&query(None, Some("logger.PARQUET")), &headers).unwrap(),
+            ImportFormat::Parquet
+        );
+        assert_eq!(
+            detect_format(&query(None, None), &headers).unwrap(),
+            ImportFormat::Csv
+        );
+        assert!(detect_format(&query(None, Some("a.xlsx")), &HeaderMap::new()).is_err());
+    }
+}
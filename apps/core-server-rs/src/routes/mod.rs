@@ -5,7 +5,10 @@ pub mod analysis;
 pub mod analytics;
 pub mod annotations;
 pub mod api_tokens;
+pub mod audit;
 pub mod auth;
+pub mod backup_targets;
+pub mod backup_verification;
 pub mod backups;
 pub mod backups_exports;
 pub mod battery;
@@ -15,10 +18,13 @@ pub mod controller_config;
 pub mod dashboard;
 pub mod deployments;
 pub mod dev_activity;
+pub mod device_profiles;
 pub mod discovery;
 pub mod display_profiles;
 pub mod external_devices;
 pub mod forecast;
+pub mod forwarder_config;
+pub mod forwarder_losses;
 pub mod health;
 pub mod incidents;
 pub mod indicators;
@@ -26,18 +32,25 @@ pub mod map;
 pub mod map_assets;
 pub mod map_offline;
 pub mod metrics;
+pub mod metrics_export;
+pub mod metrics_import;
 pub mod node_sensors;
 pub mod nodes;
+pub mod oidc;
 pub mod outputs;
 pub mod power_runway;
 pub mod predictive;
 pub mod renogy;
 pub mod renogy_settings;
+pub mod resource_scopes;
 pub mod schedules;
+pub mod security_events;
 pub mod sensors;
 pub mod setup;
 pub mod setup_daemon;
+pub mod spool_bundles;
 pub mod templates;
+pub mod two_factor;
 pub mod users;
 pub mod weather_stations;
 
@@ -52,9 +65,14 @@ pub fn router(state: AppState) -> Router {
             "/api",
             Router::new()
                 .merge(auth::router())
+                .merge(two_factor::router())
+                .merge(oidc::router())
                 .merge(api_tokens::router())
                 .merge(users::router())
+                .merge(security_events::router())
+                .merge(audit::router())
                 .merge(nodes::router())
+                .merge(resource_scopes::router())
                 .merge(node_sensors::router())
                 .merge(display_profiles::router())
                 .merge(sensors::router())
@@ -67,11 +85,18 @@ pub fn router(state: AppState) -> Router {
                 .merge(analysis::router())
                 .merge(annotations::router())
                 .merge(metrics::router())
+                .merge(metrics_export::router())
+                .merge(metrics_import::router())
+                .merge(spool_bundles::router())
+                .merge(forwarder_config::router())
+                .merge(forwarder_losses::router())
                 .merge(map::router())
                 .merge(map_assets::router())
                 .merge(map_offline::router())
                 .merge(backups::router())
                 .merge(backups_exports::router())
+                .merge(backup_targets::router())
+                .merge(backup_verification::router())
                 .merge(connection::router())
                 .merge(cloud_access::router())
                 .merge(controller_config::router())
@@ -79,6 +104,7 @@ pub fn router(state: AppState) -> Router {
                 .merge(dev_activity::router())
                 .merge(forecast::router())
                 .merge(external_devices::router())
+                .merge(device_profiles::router())
                 .merge(analytics::router())
                 .merge(indicators::router())
                 .merge(templates::router())
@@ -213,6 +239,27 @@ mod auth_gaps_tests {
         assert_eq!(err.0, axum::http::StatusCode::FORBIDDEN);
     }
 
+    #[tokio::test]
+    async fn spool_bundle_import_forbidden_without_ingest_caps() {
+        let user = crate::test_support::test_user_with_caps(&["config.read"]);
+        let query = axum::extract::Query::try_from_uri(
+            &"/api/nodes/spool-bundles?dry_run=true".parse().unwrap(),
+        )
+        .unwrap();
+        let result = spool_bundles::import_spool_bundle(
+            axum::extract::State(state()),
+            crate::auth::AuthUser(user),
+            query,
+            Body::empty(),
+        )
+        .await;
+        let err = match result {
+            Ok(_) => panic!("expected forbidden"),
+            Err(err) => err,
+        };
+        assert_eq!(err.0, axum::http::StatusCode::FORBIDDEN);
+    }
+
     #[tokio::test]
     async fn nodes_list_forbidden_without_view_caps() {
         let user = crate::test_support::test_user_with_caps(&[]);
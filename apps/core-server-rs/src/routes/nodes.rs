@@ -198,10 +198,13 @@ pub(crate) async fn list_nodes(
     axum::extract::State(state): axum::extract::State<AppState>,
     AuthUser(user): AuthUser,
 ) -> Result<Json<Vec<NodeResponse>>, (StatusCode, String)> {
-    crate::auth::require_any_capabilities(&user, &[CAP_NODES_VIEW, "config.write"])
+    crate::auth::require_any_node_capabilities(&user, &[CAP_NODES_VIEW, "config.write"])
         .map_err(|err| (err.status, err.message))?;
 
-    Ok(Json(fetch_nodes(&state.db).await.map_err(map_db_error)?))
+    let scope = user.node_scope(&[CAP_NODES_VIEW, "config.write"]);
+    let mut nodes = fetch_nodes(&state.db).await.map_err(map_db_error)?;
+    nodes.retain(|node| scope.allows_str(&node.id));
+    Ok(Json(nodes))
 }
 
 #[utoipa::path(
@@ -454,11 +457,17 @@ pub(crate) async fn get_node(
     AuthUser(user): AuthUser,
     Path(node_id): Path<String>,
 ) -> Result<Json<NodeResponse>, (StatusCode, String)> {
-    crate::auth::require_any_capabilities(&user, &[CAP_NODES_VIEW, "config.write"])
+    crate::auth::require_any_node_capabilities(&user, &[CAP_NODES_VIEW, "config.write"])
         .map_err(|err| (err.status, err.message))?;
 
     let node_uuid = Uuid::parse_str(node_id.trim())
         .map_err(|_| (StatusCode::NOT_FOUND, "Node not found".to_string()))?;
+    if !user
+        .node_scope(&[CAP_NODES_VIEW, "config.write"])
+        .allows(node_uuid)
+    {
+        return Err((StatusCode::NOT_FOUND, "Node not found".to_string()));
+    }
     let row: Option<NodeRow> = sqlx::query_as(
         r#"
         SELECT
@@ -519,11 +528,10 @@ pub(crate) async fn update_node(
     Path(node_id): Path<String>,
     Json(payload): Json<NodeUpdateRequest>,
 ) -> Result<Json<NodeResponse>, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["config.write"])
-        .map_err(|err| (err.status, err.message))?;
-
     let node_uuid = Uuid::parse_str(node_id.trim())
         .map_err(|_| (StatusCode::NOT_FOUND, "Node not found".to_string()))?;
+    crate::auth::require_node_capabilities(&user, &["config.write"], node_uuid)
+        .map_err(|err| (err.status, err.message))?;
 
     let mut tx = state.db.begin().await.map_err(map_db_error)?;
 
@@ -654,11 +662,10 @@ pub(crate) async fn delete_node(
     Path(node_id): Path<String>,
     Query(query): Query<NodeDeleteQuery>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["config.write"])
-        .map_err(|err| (err.status, err.message))?;
-
     let node_uuid = Uuid::parse_str(node_id.trim())
         .map_err(|_| (StatusCode::NOT_FOUND, "Node not found".to_string()))?;
+    crate::auth::require_node_capabilities(&user, &["config.write"], node_uuid)
+        .map_err(|err| (err.status, err.message))?;
 
     if core_node::is_core_node_id(node_uuid) {
         return Err((
@@ -0,0 +1,330 @@
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use sqlx::types::Json as SqlJson;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use crate::auth::lockout;
+use crate::auth::oidc::{self, OidcIdentity};
+use crate::auth::sessions;
+use crate::auth::SessionClient;
+use crate::config::OidcConfig;
+use crate::error::{map_db_conflict, map_db_error};
+use crate::routes::auth::{verify_login_factor, LoginResponse};
+use crate::routes::users::{
+    default_capabilities_for_role, ensure_admin_defaults, normalize_capabilities,
+};
+use crate::state::AppState;
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct OidcProviderResponse {
+    enabled: bool,
+    /// Label for the login button.
+    display_name: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct OidcAuthorizeResponse {
+    /// Identity provider URL to send the browser to.
+    authorization_url: String,
+    state: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct OidcCallbackRequest {
+    code: String,
+    state: String,
+    /// Current authenticator code; required once the user has enrolled in TOTP unless
+    /// `CORE_OIDC_SKIP_TOTP` is set. The authorization code is single-use, so a
+    /// missing factor means restarting the sign-in.
+    #[serde(default)]
+    totp_code: Option<String>,
+    /// One-time recovery code, accepted instead of `totp_code`.
+    #[serde(default)]
+    recovery_code: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SecondFactorRow {
+    email: String,
+    totp_enabled: bool,
+}
+
+#[derive(sqlx::FromRow)]
+struct LinkedUserRow {
+    id: Uuid,
+    role: String,
+    capabilities: SqlJson<Vec<String>>,
+    oidc_subject: Option<String>,
+}
+
+fn oidc_config(state: &AppState) -> Result<&OidcConfig, (StatusCode, String)> {
+    state.config.oidc.as_ref().ok_or((
+        StatusCode::NOT_FOUND,
+        "Single sign-on is not configured".to_string(),
+    ))
+}
+
+fn sso_capabilities(role: &str, claim_capabilities: &[String]) -> Vec<String> {
+    let mut capabilities = default_capabilities_for_role(role);
+    capabilities.extend(claim_capabilities.iter().cloned());
+    let mut capabilities = normalize_capabilities(capabilities);
+    ensure_admin_defaults(role, &mut capabilities);
+    capabilities
+}
+
+/// Finds the dashboard user for an SSO identity, linking by email on first use when
+/// the provider verified the address and creating the user when just-in-time
+/// provisioning is on.
+async fn provision_user(
+    state: &AppState,
+    config: &OidcConfig,
+    identity: &OidcIdentity,
+) -> Result<Uuid, (StatusCode, String)> {
+    let linked: Option<LinkedUserRow> = sqlx::query_as(
+        r#"
+        SELECT id, role, capabilities, oidc_subject
+        FROM users
+        WHERE oidc_issuer = $1 AND oidc_subject = $2
+        "#,
+    )
+    .bind(&identity.issuer)
+    .bind(&identity.subject)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    let existing = match linked {
+        Some(row) => Some(row),
+        None => {
+            let by_email: Option<LinkedUserRow> = sqlx::query_as(
+                r#"
+                SELECT id, role, capabilities, oidc_subject
+                FROM users
+                WHERE email = $1
+                "#,
+            )
+            .bind(&identity.email)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(map_db_error)?;
+            if let Some(row) = by_email.as_ref() {
+                if row.oidc_subject.is_some() {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        "This account is linked to a different identity".to_string(),
+                    ));
+                }
+                if !identity.email_verified {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        "The identity provider has not verified this email address, so it cannot be linked to the existing account".to_string(),
+                    ));
+                }
+                sqlx::query("UPDATE users SET oidc_issuer = $2, oidc_subject = $3 WHERE id = $1")
+                    .bind(row.id)
+                    .bind(&identity.issuer)
+                    .bind(&identity.subject)
+                    .execute(&state.db)
+                    .await
+                    .map_err(map_db_error)?;
+                tracing::info!(user_id = %row.id, email = %identity.email, "linked sso identity");
+            }
+            by_email
+        }
+    };
+
+    let Some(existing) = existing else {
+        if !config.auto_provision {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "No dashboard account exists for this identity".to_string(),
+            ));
+        }
+        let role = identity
+            .mapped_role
+            .clone()
+            .or_else(|| {
+                config
+                    .default_role
+                    .as_deref()
+                    .map(crate::auth::canonicalize_role)
+            })
+            .ok_or((
+                StatusCode::FORBIDDEN,
+                "No dashboard role is mapped for this identity".to_string(),
+            ))?;
+        let capabilities = sso_capabilities(&role, &identity.claim_capabilities);
+        let user_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO users (
+                name, email, role, capabilities, password_hash,
+                oidc_issuer, oidc_subject, password_login_disabled
+            )
+            VALUES ($1, $2, $3, $4, NULL, $5, $6, TRUE)
+            RETURNING id
+            "#,
+        )
+        .bind(&identity.name)
+        .bind(&identity.email)
+        .bind(&role)
+        .bind(SqlJson(capabilities))
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|err| map_db_conflict(err, "Email already in use"))?;
+        tracing::info!(user_id = %user_id, email = %identity.email, role = %role, "provisioned sso user");
+        return Ok(user_id);
+    };
+
+    // Only an explicit claim match overrides what an admin set locally.
+    if let (true, Some(role)) = (config.sync_roles, identity.mapped_role.as_deref()) {
+        let capabilities = sso_capabilities(role, &identity.claim_capabilities);
+        let unchanged = crate::auth::canonicalize_role(&existing.role) == role
+            && capabilities.iter().collect::<HashSet<_>>()
+                == existing.capabilities.0.iter().collect::<HashSet<_>>();
+        if !unchanged {
+            sqlx::query(
+                "UPDATE users SET role = $2, capabilities = $3, updated_at = NOW() WHERE id = $1",
+            )
+            .bind(existing.id)
+            .bind(role)
+            .bind(SqlJson(capabilities))
+            .execute(&state.db)
+            .await
+            .map_err(map_db_error)?;
+            sessions::revoke_user_sessions(
+                &state.db,
+                existing.id,
+                sessions::REVOKE_PERMISSIONS_CHANGED,
+            )
+            .await
+            .map_err(|err| (err.status, err.message))?;
+            tracing::info!(user_id = %existing.id, role = %role, "synced sso role");
+        }
+    }
+    Ok(existing.id)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/oidc",
+    tag = "auth",
+    responses((status = 200, description = "Single sign-on availability", body = OidcProviderResponse))
+)]
+pub(crate) async fn get_provider(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Json<OidcProviderResponse> {
+    Json(OidcProviderResponse {
+        enabled: state.config.oidc.is_some(),
+        display_name: state
+            .config
+            .oidc
+            .as_ref()
+            .map(|config| config.display_name.clone()),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/oidc/authorize",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Authorization request", body = OidcAuthorizeResponse),
+        (status = 404, description = "Single sign-on not configured"),
+        (status = 502, description = "Identity provider unavailable")
+    )
+)]
+pub(crate) async fn authorize(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<OidcAuthorizeResponse>, (StatusCode, String)> {
+    let config = oidc_config(&state)?;
+    let request = oidc::begin_login(&state.http, config)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    Ok(Json(OidcAuthorizeResponse {
+        authorization_url: request.url,
+        state: request.state,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/oidc/callback",
+    tag = "auth",
+    request_body = OidcCallbackRequest,
+    responses(
+        (status = 200, description = "Auth token", body = LoginResponse),
+        (status = 401, description = "Sign-in expired or rejected, or a two-factor code is required or invalid"),
+        (status = 403, description = "Identity not allowed"),
+        (status = 404, description = "Single sign-on not configured"),
+        (status = 429, description = "Too many failed attempts for this account or client"),
+        (status = 502, description = "Identity provider unavailable")
+    )
+)]
+pub(crate) async fn callback(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<OidcCallbackRequest>,
+) -> Result<Json<LoginResponse>, (StatusCode, String)> {
+    let config = oidc_config(&state)?;
+    let identity = oidc::complete_login(
+        &state.http,
+        config,
+        payload.code.trim(),
+        payload.state.trim(),
+    )
+    .await
+    .map_err(|err| (err.status, err.message))?;
+    let user_id = provision_user(&state, config, &identity).await?;
+
+    let client = SessionClient::from_request(peer, &headers, &state.config.trusted_proxies);
+    if !config.skip_totp {
+        let row: SecondFactorRow = sqlx::query_as(
+            "SELECT email, totp_enabled_at IS NOT NULL AS totp_enabled FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(map_db_error)?;
+        if row.totp_enabled {
+            lockout::check(&state.db, &row.email, &client)
+                .await
+                .map_err(|err| (err.status, err.message))?;
+            verify_login_factor(
+                &state,
+                &row.email,
+                user_id,
+                &client,
+                payload.totp_code.as_deref(),
+                payload.recovery_code.as_deref(),
+            )
+            .await?;
+            lockout::record_success(&state.db, &row.email).await;
+        }
+    }
+
+    let _ = sqlx::query("UPDATE users SET last_login = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(&state.db)
+        .await;
+
+    let session = state
+        .auth
+        .issue_for_user(&state.db, user_id, oidc::SESSION_SOURCE, &client)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    Ok(Json(LoginResponse::from(session)))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/auth/oidc", get(get_provider))
+        .route("/auth/oidc/authorize", get(authorize))
+        .route("/auth/oidc/callback", post(callback))
+}
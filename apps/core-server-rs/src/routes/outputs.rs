@@ -9,11 +9,12 @@ use uuid::Uuid;
 use crate::auth::AuthUser;
 use crate::error::map_db_error;
 use crate::ids;
+use crate::services::audit_log;
 use crate::state::AppState;
 
 const CAP_OUTPUTS_VIEW: &str = "outputs.view";
 
-#[derive(sqlx::FromRow)]
+#[derive(Clone, sqlx::FromRow)]
 pub(crate) struct OutputRow {
     id: String,
     node_id: Uuid,
@@ -28,7 +29,7 @@ pub(crate) struct OutputRow {
 #[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
 pub(crate) struct OutputResponse {
     id: String,
-    node_id: String,
+    pub(crate) node_id: String,
     name: String,
     #[serde(rename = "type")]
     output_type: String,
@@ -148,14 +149,15 @@ pub(crate) async fn list_outputs(
     AuthUser(user): AuthUser,
     Query(query): Query<OutputsQuery>,
 ) -> Result<Json<Vec<OutputResponse>>, (StatusCode, String)> {
-    crate::auth::require_any_capabilities(&user, &[CAP_OUTPUTS_VIEW, "config.write"])
+    crate::auth::require_any_node_capabilities(&user, &[CAP_OUTPUTS_VIEW, "config.write"])
         .map_err(|err| (err.status, err.message))?;
 
-    Ok(Json(
-        fetch_outputs(&state.db, query.node_id)
-            .await
-            .map_err(map_db_error)?,
-    ))
+    let scope = user.node_scope(&[CAP_OUTPUTS_VIEW, "config.write"]);
+    let mut outputs = fetch_outputs(&state.db, query.node_id)
+        .await
+        .map_err(map_db_error)?;
+    outputs.retain(|output| scope.allows_str(&output.node_id));
+    Ok(Json(outputs))
 }
 
 #[utoipa::path(
@@ -176,7 +178,7 @@ pub(crate) async fn get_output(
     AuthUser(user): AuthUser,
     Path(output_id): Path<String>,
 ) -> Result<Json<OutputResponse>, (StatusCode, String)> {
-    crate::auth::require_any_capabilities(&user, &[CAP_OUTPUTS_VIEW, "config.write"])
+    crate::auth::require_any_node_capabilities(&user, &[CAP_OUTPUTS_VIEW, "config.write"])
         .map_err(|err| (err.status, err.message))?;
 
     let row: Option<OutputRow> = sqlx::query_as(
@@ -202,6 +204,12 @@ pub(crate) async fn get_output(
     let Some(row) = row else {
         return Err((StatusCode::NOT_FOUND, "Output not found".to_string()));
     };
+    if !user
+        .node_scope(&[CAP_OUTPUTS_VIEW, "config.write"])
+        .allows(row.node_id)
+    {
+        return Err((StatusCode::NOT_FOUND, "Output not found".to_string()));
+    }
 
     Ok(Json(OutputResponse::from(row)))
 }
@@ -387,7 +395,7 @@ pub(crate) async fn create_output(
     AuthUser(user): AuthUser,
     Json(payload): Json<OutputCreateRequest>,
 ) -> Result<(StatusCode, Json<OutputResponse>), (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["config.write"])
+    crate::auth::require_node_capabilities(&user, &["config.write"], payload.node_id)
         .map_err(|err| (err.status, err.message))?;
 
     if payload.name.trim().is_empty() || payload.output_type.trim().is_empty() {
@@ -498,6 +506,16 @@ pub(crate) async fn create_output(
         .map_err(map_db_error)?;
 
     tx.commit().await.map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "output.create",
+        "output",
+        Some(&output.id),
+        None,
+        audit_log::snapshot(&output),
+    )
+    .await;
     Ok((StatusCode::CREATED, Json(output)))
 }
 
@@ -522,7 +540,7 @@ pub(crate) async fn update_output(
     Path(output_id): Path<String>,
     Json(payload): Json<OutputUpdateRequest>,
 ) -> Result<Json<OutputResponse>, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["config.write"])
+    crate::auth::require_any_node_capabilities(&user, &["config.write"])
         .map_err(|err| (err.status, err.message))?;
 
     let mut tx = state.db.begin().await.map_err(map_db_error)?;
@@ -550,8 +568,13 @@ pub(crate) async fn update_output(
     let Some(mut existing) = existing else {
         return Err((StatusCode::NOT_FOUND, "Output not found".to_string()));
     };
+    crate::auth::require_node_capabilities(&user, &["config.write"], existing.node_id)
+        .map_err(|err| (err.status, err.message))?;
+    let before = audit_log::snapshot(&OutputResponse::from(existing.clone()));
 
     if let Some(node_id) = payload.node_id {
+        crate::auth::require_node_capabilities(&user, &["config.write"], node_id)
+            .map_err(|err| (err.status, err.message))?;
         let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM nodes WHERE id = $1")
             .bind(node_id)
             .fetch_optional(&mut *tx)
@@ -658,6 +681,16 @@ pub(crate) async fn update_output(
     }
 
     tx.commit().await.map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "output.update",
+        "output",
+        Some(&output.id),
+        before,
+        audit_log::snapshot(&output),
+    )
+    .await;
     Ok(Json(output))
 }
 
@@ -679,20 +712,37 @@ pub(crate) async fn delete_output(
     AuthUser(user): AuthUser,
     Path(output_id): Path<String>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["config.write"])
+    crate::auth::require_any_node_capabilities(&user, &["config.write"])
         .map_err(|err| (err.status, err.message))?;
 
     let mut tx = state.db.begin().await.map_err(map_db_error)?;
 
-    let existing: Option<(Uuid,)> = sqlx::query_as("SELECT node_id FROM outputs WHERE id = $1")
-        .bind(output_id.trim())
-        .fetch_optional(&mut *tx)
-        .await
-        .map_err(map_db_error)?;
+    let existing: Option<OutputRow> = sqlx::query_as(
+        r#"
+        SELECT
+            id,
+            node_id,
+            name,
+            type as output_type,
+            state,
+            last_command,
+            supported_states,
+            COALESCE(config, '{}'::jsonb) as config
+        FROM outputs
+        WHERE id = $1
+        "#,
+    )
+    .bind(output_id.trim())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(map_db_error)?;
 
-    let Some((node_id,)) = existing else {
+    let Some(existing) = existing else {
         return Err((StatusCode::NOT_FOUND, "Output not found".to_string()));
     };
+    let node_id = existing.node_id;
+    crate::auth::require_node_capabilities(&user, &["config.write"], node_id)
+        .map_err(|err| (err.status, err.message))?;
 
     let result = sqlx::query("DELETE FROM outputs WHERE id = $1")
         .bind(output_id.trim())
@@ -727,6 +777,16 @@ pub(crate) async fn delete_output(
     }
 
     tx.commit().await.map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "output.delete",
+        "output",
+        Some(output_id.trim()),
+        audit_log::snapshot(&OutputResponse::from(existing)),
+        None,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -750,7 +810,7 @@ pub(crate) async fn command_output(
     Path(output_id): Path<String>,
     Json(payload): Json<OutputCommandRequest>,
 ) -> Result<Json<OutputResponse>, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["outputs.command"])
+    crate::auth::require_any_node_capabilities(&user, &["outputs.command"])
         .map_err(|err| (err.status, err.message))?;
 
     let desired = payload.state.trim();
@@ -758,6 +818,17 @@ pub(crate) async fn command_output(
         return Err((StatusCode::BAD_REQUEST, "Missing state".to_string()));
     }
 
+    let target: Option<(Uuid,)> = sqlx::query_as("SELECT node_id FROM outputs WHERE id = $1")
+        .bind(output_id.trim())
+        .fetch_optional(&state.db)
+        .await
+        .map_err(map_db_error)?;
+    let Some((node_id,)) = target else {
+        return Err((StatusCode::NOT_FOUND, "Output not found".to_string()));
+    };
+    crate::auth::require_node_capabilities(&user, &["outputs.command"], node_id)
+        .map_err(|err| (err.status, err.message))?;
+
     let row: Option<OutputRow> = sqlx::query_as(
         r#"
         UPDATE outputs
@@ -792,8 +863,29 @@ pub(crate) async fn command_output(
         "reason": payload.reason.unwrap_or_else(|| "manual".to_string()),
     });
     let _ = state.mqtt.publish_json(&topic, &payload).await;
+    // Outputs of MQTT external devices are also driven on the device's own command topic.
+    if let Err(err) = crate::services::external_devices::publish_mqtt_output_command(
+        &state.mqtt,
+        &row.config.0,
+        desired,
+    )
+    .await
+    {
+        tracing::warn!(output_id = %row.id, "failed to publish mqtt device command: {err:#}");
+    }
 
-    Ok(Json(OutputResponse::from(row)))
+    let output = OutputResponse::from(row);
+    audit_log::record(
+        &state.db,
+        &user,
+        "output.command",
+        "output",
+        Some(&output.id),
+        None,
+        Some(payload),
+    )
+    .await;
+    Ok(Json(output))
 }
 
 pub fn router() -> Router<AppState> {
@@ -9,6 +9,7 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::error::map_db_error;
+use crate::services::audit_log;
 use crate::state::AppState;
 
 const DEVICE_TYPE: &str = "renogy_bt2";
@@ -572,6 +573,16 @@ pub(crate) async fn put_desired_settings(
     .execute(&state.db)
     .await
     .map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "renogy_settings.set_desired",
+        "node",
+        Some(&node_id),
+        Some(previous_desired),
+        Some(payload.desired.clone()),
+    )
+    .await;
 
     Ok(Json(RenogyDesiredSettingsResponse {
         node_id,
@@ -1178,6 +1189,19 @@ pub(crate) async fn apply_settings(
     .map_err(map_db_error)?;
 
     tx.commit().await.map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "renogy_settings.apply",
+        "node",
+        Some(&node_id),
+        None,
+        Some(serde_json::json!({
+            "status": &apply_status,
+            "desired": &desired.desired,
+        })),
+    )
+    .await;
 
     Ok(Json(RenogyApplyResponse {
         status: apply_status,
@@ -1356,6 +1380,19 @@ pub(crate) async fn rollback(
     .execute(&state.db)
     .await
     .map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "renogy_settings.rollback",
+        "node",
+        Some(&node_id),
+        None,
+        Some(serde_json::json!({
+            "event_id": payload.event_id,
+            "desired": &desired,
+        })),
+    )
+    .await;
 
     Ok(Json(RenogyDesiredSettingsResponse {
         node_id,
@@ -1454,6 +1491,16 @@ pub(crate) async fn set_maintenance_mode(
     .execute(&state.db)
     .await
     .map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "renogy_settings.maintenance",
+        "node",
+        Some(&node_id),
+        None,
+        Some(serde_json::json!({ "maintenance_mode": payload.enabled })),
+    )
+    .await;
 
     Ok(Json(RenogyDesiredSettingsResponse {
         node_id,
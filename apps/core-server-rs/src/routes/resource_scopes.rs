@@ -0,0 +1,859 @@
+use std::collections::BTreeSet;
+
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::auth::scopes::{RESOURCE_MAP_LAYER, RESOURCE_NODE, RESOURCE_NODE_GROUP};
+use crate::auth::AuthUser;
+use crate::error::map_db_error;
+use crate::services::audit_log;
+use crate::state::AppState;
+
+const MAX_GRANTS: usize = 500;
+
+#[derive(sqlx::FromRow)]
+struct NodeGroupRow {
+    id: Uuid,
+    name: String,
+    description: String,
+    node_ids: Vec<Uuid>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct NodeGroupResponse {
+    id: String,
+    name: String,
+    description: String,
+    node_ids: Vec<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+impl From<NodeGroupRow> for NodeGroupResponse {
+    fn from(row: NodeGroupRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            name: row.name,
+            description: row.description,
+            node_ids: row.node_ids.iter().map(Uuid::to_string).collect(),
+            created_at: row.created_at.to_rfc3339(),
+            updated_at: row.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct NodeGroupUpsertRequest {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    node_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct NodeMembershipPayload {
+    node_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct CapabilityGrant {
+    capability: String,
+    /// `node`, `node_group` or `map_layer`.
+    resource_type: String,
+    /// Node or node group uuid, or map layer id.
+    resource_id: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct CapabilityGrantsPayload {
+    grants: Vec<CapabilityGrant>,
+}
+
+async fn fetch_node_group(
+    db: &sqlx::PgPool,
+    group_id: Uuid,
+) -> Result<Option<NodeGroupResponse>, sqlx::Error> {
+    let row: Option<NodeGroupRow> = sqlx::query_as(
+        r#"
+        SELECT
+            g.id,
+            g.name,
+            g.description,
+            COALESCE(
+                (SELECT array_agg(m.node_id ORDER BY m.node_id) FROM node_group_members m WHERE m.group_id = g.id),
+                '{}'
+            ) as node_ids,
+            g.created_at,
+            g.updated_at
+        FROM node_groups g
+        WHERE g.id = $1
+        "#,
+    )
+    .bind(group_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(NodeGroupResponse::from))
+}
+
+/// Rejects node ids that do not exist so a typo cannot silently shrink a scope.
+async fn ensure_nodes_exist(
+    db: &sqlx::PgPool,
+    node_ids: &[Uuid],
+) -> Result<Vec<Uuid>, (StatusCode, String)> {
+    let unique: Vec<Uuid> = node_ids
+        .iter()
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let known: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM nodes WHERE id = ANY($1)")
+        .bind(&unique)
+        .fetch_all(db)
+        .await
+        .map_err(map_db_error)?;
+    if let Some(missing) = unique.iter().find(|id| !known.contains(id)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown node {missing}")));
+    }
+    Ok(unique)
+}
+
+async fn replace_group_members(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    group_id: Uuid,
+    node_ids: &[Uuid],
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query("DELETE FROM node_group_members WHERE group_id = $1")
+        .bind(group_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(map_db_error)?;
+    sqlx::query("INSERT INTO node_group_members (group_id, node_id) SELECT $1, unnest($2::uuid[])")
+        .bind(group_id)
+        .bind(node_ids)
+        .execute(&mut **tx)
+        .await
+        .map_err(map_db_error)?;
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/node-groups",
+    tag = "nodes",
+    responses(
+        (status = 200, description = "Node groups", body = Vec<NodeGroupResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_node_groups(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<Vec<NodeGroupResponse>>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &["nodes.view", "config.write", "users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let rows: Vec<NodeGroupRow> = sqlx::query_as(
+        r#"
+        SELECT
+            g.id,
+            g.name,
+            g.description,
+            COALESCE(
+                (SELECT array_agg(m.node_id ORDER BY m.node_id) FROM node_group_members m WHERE m.group_id = g.id),
+                '{}'
+            ) as node_ids,
+            g.created_at,
+            g.updated_at
+        FROM node_groups g
+        ORDER BY lower(g.name) ASC
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    Ok(Json(
+        rows.into_iter().map(NodeGroupResponse::from).collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/node-groups",
+    tag = "nodes",
+    request_body = NodeGroupUpsertRequest,
+    responses(
+        (status = 201, description = "Created node group", body = NodeGroupResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "Name already in use")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn create_node_group(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<NodeGroupUpsertRequest>,
+) -> Result<(StatusCode, Json<NodeGroupResponse>), (StatusCode, String)> {
+    // Group membership widens every grant on the group, so node-scoped writers
+    // may not edit groups.
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Missing name".to_string()));
+    }
+    let node_ids = ensure_nodes_exist(&state.db, &payload.node_ids).await?;
+
+    let mut tx = state.db.begin().await.map_err(map_db_error)?;
+    let group_id: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        INSERT INTO node_groups (name, description)
+        VALUES ($1, $2)
+        ON CONFLICT (name) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(name)
+    .bind(payload.description.as_deref().unwrap_or("").trim())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(map_db_error)?;
+    let Some(group_id) = group_id else {
+        return Err((
+            StatusCode::CONFLICT,
+            "Node group name already in use".to_string(),
+        ));
+    };
+    replace_group_members(&mut tx, group_id, &node_ids).await?;
+    tx.commit().await.map_err(map_db_error)?;
+
+    let group = fetch_node_group(&state.db, group_id)
+        .await
+        .map_err(map_db_error)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Node group not found".to_string()))?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "node_group.create",
+        "node_group",
+        Some(&group.id),
+        None,
+        audit_log::snapshot(&group),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(group)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/node-groups/{group_id}",
+    tag = "nodes",
+    request_body = NodeGroupUpsertRequest,
+    params(("group_id" = String, Path, description = "Node group id")),
+    responses(
+        (status = 200, description = "Updated node group", body = NodeGroupResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Node group not found"),
+        (status = 409, description = "Name already in use")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn update_node_group(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(group_id): Path<String>,
+    Json(payload): Json<NodeGroupUpsertRequest>,
+) -> Result<Json<NodeGroupResponse>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let group_id = Uuid::parse_str(group_id.trim())
+        .map_err(|_| (StatusCode::NOT_FOUND, "Node group not found".to_string()))?;
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Missing name".to_string()));
+    }
+    let Some(before) = fetch_node_group(&state.db, group_id)
+        .await
+        .map_err(map_db_error)?
+    else {
+        return Err((StatusCode::NOT_FOUND, "Node group not found".to_string()));
+    };
+    let node_ids = ensure_nodes_exist(&state.db, &payload.node_ids).await?;
+
+    let mut tx = state.db.begin().await.map_err(map_db_error)?;
+    let taken: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM node_groups WHERE name = $1 AND id <> $2)")
+            .bind(name)
+            .bind(group_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(map_db_error)?;
+    if taken {
+        return Err((
+            StatusCode::CONFLICT,
+            "Node group name already in use".to_string(),
+        ));
+    }
+    sqlx::query(
+        r#"
+        UPDATE node_groups
+        SET name = $2, description = COALESCE($3, description), updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(group_id)
+    .bind(name)
+    .bind(payload.description.as_deref().map(str::trim))
+    .execute(&mut *tx)
+    .await
+    .map_err(map_db_error)?;
+    replace_group_members(&mut tx, group_id, &node_ids).await?;
+    tx.commit().await.map_err(map_db_error)?;
+
+    let group = fetch_node_group(&state.db, group_id)
+        .await
+        .map_err(map_db_error)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Node group not found".to_string()))?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "node_group.update",
+        "node_group",
+        Some(&group.id),
+        audit_log::snapshot(&before),
+        audit_log::snapshot(&group),
+    )
+    .await;
+    Ok(Json(group))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/node-groups/{group_id}",
+    tag = "nodes",
+    params(("group_id" = String, Path, description = "Node group id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Node group not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn delete_node_group(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(group_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let group_id = Uuid::parse_str(group_id.trim())
+        .map_err(|_| (StatusCode::NOT_FOUND, "Node group not found".to_string()))?;
+    let Some(before) = fetch_node_group(&state.db, group_id)
+        .await
+        .map_err(map_db_error)?
+    else {
+        return Err((StatusCode::NOT_FOUND, "Node group not found".to_string()));
+    };
+
+    // Grants on the group go with it; they cannot reference a missing group.
+    let mut tx = state.db.begin().await.map_err(map_db_error)?;
+    sqlx::query("DELETE FROM capability_grants WHERE resource_type = $1 AND resource_id = $2")
+        .bind(RESOURCE_NODE_GROUP)
+        .bind(group_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(map_db_error)?;
+    sqlx::query("DELETE FROM node_groups WHERE id = $1")
+        .bind(group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_db_error)?;
+    tx.commit().await.map_err(map_db_error)?;
+
+    audit_log::record(
+        &state.db,
+        &user,
+        "node_group.delete",
+        "node_group",
+        Some(&before.id),
+        audit_log::snapshot(&before),
+        None,
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn fetch_layer_nodes(
+    db: &sqlx::PgPool,
+    layer_id: i64,
+) -> Result<Option<NodeMembershipPayload>, (StatusCode, String)> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM map_layers WHERE id = $1)")
+        .bind(layer_id)
+        .fetch_one(db)
+        .await
+        .map_err(map_db_error)?;
+    if !exists {
+        return Ok(None);
+    }
+    let node_ids: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT node_id FROM map_layer_nodes WHERE layer_id = $1 ORDER BY node_id",
+    )
+    .bind(layer_id)
+    .fetch_all(db)
+    .await
+    .map_err(map_db_error)?;
+    Ok(Some(NodeMembershipPayload { node_ids }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/map/layers/{id}/nodes",
+    tag = "map",
+    params(("id" = i64, Path, description = "Layer id")),
+    responses(
+        (status = 200, description = "Nodes covered by the layer", body = NodeMembershipPayload),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn get_map_layer_nodes(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(id): Path<i64>,
+) -> Result<Json<NodeMembershipPayload>, (StatusCode, String)> {
+    crate::auth::require_any_capabilities(&user, &["nodes.view", "config.write", "users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+    fetch_layer_nodes(&state.db, id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Layer not found".to_string()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/map/layers/{id}/nodes",
+    tag = "map",
+    request_body = NodeMembershipPayload,
+    params(("id" = i64, Path, description = "Layer id")),
+    responses(
+        (status = 200, description = "Nodes covered by the layer", body = NodeMembershipPayload),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn put_map_layer_nodes(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<NodeMembershipPayload>,
+) -> Result<Json<NodeMembershipPayload>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let Some(before) = fetch_layer_nodes(&state.db, id).await? else {
+        return Err((StatusCode::NOT_FOUND, "Layer not found".to_string()));
+    };
+    let node_ids = ensure_nodes_exist(&state.db, &payload.node_ids).await?;
+
+    let mut tx = state.db.begin().await.map_err(map_db_error)?;
+    sqlx::query("DELETE FROM map_layer_nodes WHERE layer_id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_db_error)?;
+    sqlx::query("INSERT INTO map_layer_nodes (layer_id, node_id) SELECT $1, unnest($2::uuid[])")
+        .bind(id)
+        .bind(&node_ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_db_error)?;
+    tx.commit().await.map_err(map_db_error)?;
+
+    let after = NodeMembershipPayload { node_ids };
+    audit_log::record(
+        &state.db,
+        &user,
+        "map_layer.nodes_update",
+        "map_layer",
+        Some(&id.to_string()),
+        audit_log::snapshot(&before),
+        audit_log::snapshot(&after),
+    )
+    .await;
+    Ok(Json(after))
+}
+
+/// Validates a grant and returns it with its resource id in canonical form.
+async fn normalize_grant(
+    db: &sqlx::PgPool,
+    grant: &CapabilityGrant,
+) -> Result<CapabilityGrant, (StatusCode, String)> {
+    let capability = grant.capability.trim();
+    if capability.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Missing capability".to_string()));
+    }
+    if !crate::auth::KNOWN_CAPABILITIES.contains(&capability) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown capability {capability}"),
+        ));
+    }
+    let resource_type = grant.resource_type.trim().to_lowercase();
+    let raw_id = grant.resource_id.trim();
+    let invalid = || {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Unknown {resource_type} {raw_id}"),
+        )
+    };
+    let (resource_id, exists) = match resource_type.as_str() {
+        RESOURCE_NODE | RESOURCE_NODE_GROUP => {
+            let id = Uuid::parse_str(raw_id).map_err(|_| invalid())?;
+            let table_sql = if resource_type == RESOURCE_NODE {
+                "SELECT EXISTS(SELECT 1 FROM nodes WHERE id = $1)"
+            } else {
+                "SELECT EXISTS(SELECT 1 FROM node_groups WHERE id = $1)"
+            };
+            let exists: bool = sqlx::query_scalar(table_sql)
+                .bind(id)
+                .fetch_one(db)
+                .await
+                .map_err(map_db_error)?;
+            (id.to_string(), exists)
+        }
+        RESOURCE_MAP_LAYER => {
+            let id: i64 = raw_id.parse().map_err(|_| invalid())?;
+            let exists: bool =
+                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM map_layers WHERE id = $1)")
+                    .bind(id)
+                    .fetch_one(db)
+                    .await
+                    .map_err(map_db_error)?;
+            (id.to_string(), exists)
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "resource_type must be node, node_group or map_layer".to_string(),
+            ))
+        }
+    };
+    if !exists {
+        return Err(invalid());
+    }
+    Ok(CapabilityGrant {
+        capability: capability.to_string(),
+        resource_type,
+        resource_id,
+    })
+}
+
+#[derive(Clone, Copy)]
+enum GrantPrincipal {
+    User(Uuid),
+    ApiToken(Uuid),
+}
+
+impl GrantPrincipal {
+    fn column(self) -> &'static str {
+        match self {
+            GrantPrincipal::User(_) => "user_id",
+            GrantPrincipal::ApiToken(_) => "api_token_id",
+        }
+    }
+
+    fn id(self) -> Uuid {
+        match self {
+            GrantPrincipal::User(id) | GrantPrincipal::ApiToken(id) => id,
+        }
+    }
+}
+
+async fn fetch_grants(
+    db: &sqlx::PgPool,
+    principal: GrantPrincipal,
+) -> Result<CapabilityGrantsPayload, (StatusCode, String)> {
+    let rows: Vec<(String, String, String)> = sqlx::query_as(&format!(
+        r#"
+        SELECT capability, resource_type, resource_id
+        FROM capability_grants
+        WHERE {} = $1
+        ORDER BY capability, resource_type, resource_id
+        "#,
+        principal.column()
+    ))
+    .bind(principal.id())
+    .fetch_all(db)
+    .await
+    .map_err(map_db_error)?;
+    Ok(CapabilityGrantsPayload {
+        grants: rows
+            .into_iter()
+            .map(|(capability, resource_type, resource_id)| CapabilityGrant {
+                capability,
+                resource_type,
+                resource_id,
+            })
+            .collect(),
+    })
+}
+
+async fn replace_grants(
+    state: &AppState,
+    user: &crate::auth::AuthenticatedUser,
+    principal: GrantPrincipal,
+    payload: CapabilityGrantsPayload,
+) -> Result<CapabilityGrantsPayload, (StatusCode, String)> {
+    if payload.grants.len() > MAX_GRANTS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("grants cannot exceed {MAX_GRANTS} entries"),
+        ));
+    }
+    let mut grants = Vec::with_capacity(payload.grants.len());
+    for grant in &payload.grants {
+        grants.push(normalize_grant(&state.db, grant).await?);
+    }
+    grants.sort_by(|a, b| {
+        (&a.capability, &a.resource_type, &a.resource_id).cmp(&(
+            &b.capability,
+            &b.resource_type,
+            &b.resource_id,
+        ))
+    });
+    grants.dedup_by(|a, b| {
+        a.capability == b.capability
+            && a.resource_type == b.resource_type
+            && a.resource_id == b.resource_id
+    });
+
+    let before = fetch_grants(&state.db, principal).await?;
+    let column = principal.column();
+    let mut tx = state.db.begin().await.map_err(map_db_error)?;
+    sqlx::query(&format!(
+        "DELETE FROM capability_grants WHERE {column} = $1"
+    ))
+    .bind(principal.id())
+    .execute(&mut *tx)
+    .await
+    .map_err(map_db_error)?;
+    for grant in &grants {
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO capability_grants ({column}, capability, resource_type, resource_id, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            "#
+        ))
+        .bind(principal.id())
+        .bind(&grant.capability)
+        .bind(&grant.resource_type)
+        .bind(&grant.resource_id)
+        .bind(&user.email)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_db_error)?;
+    }
+    tx.commit().await.map_err(map_db_error)?;
+
+    let after = CapabilityGrantsPayload { grants };
+    let (action, target_type) = match principal {
+        GrantPrincipal::User(_) => ("user.grants_update", "user"),
+        GrantPrincipal::ApiToken(_) => ("api_token.grants_update", "api_token"),
+    };
+    audit_log::record(
+        &state.db,
+        user,
+        action,
+        target_type,
+        Some(&principal.id().to_string()),
+        audit_log::snapshot(&before),
+        audit_log::snapshot(&after),
+    )
+    .await;
+    Ok(after)
+}
+
+async fn user_principal(
+    db: &sqlx::PgPool,
+    user_id: &str,
+) -> Result<GrantPrincipal, (StatusCode, String)> {
+    let not_found = || (StatusCode::NOT_FOUND, "User not found".to_string());
+    let id = Uuid::parse_str(user_id.trim()).map_err(|_| not_found())?;
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .map_err(map_db_error)?;
+    if !exists {
+        return Err(not_found());
+    }
+    Ok(GrantPrincipal::User(id))
+}
+
+async fn token_principal(
+    db: &sqlx::PgPool,
+    token_id: &str,
+) -> Result<GrantPrincipal, (StatusCode, String)> {
+    let not_found = || (StatusCode::NOT_FOUND, "Token not found".to_string());
+    let id = Uuid::parse_str(token_id.trim()).map_err(|_| not_found())?;
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM api_tokens WHERE id = $1)")
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .map_err(map_db_error)?;
+    if !exists {
+        return Err(not_found());
+    }
+    Ok(GrantPrincipal::ApiToken(id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/{user_id}/grants",
+    tag = "users",
+    params(("user_id" = String, Path, description = "User id")),
+    responses(
+        (status = 200, description = "Resource-scoped grants", body = CapabilityGrantsPayload),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "User not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn get_user_grants(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<String>,
+) -> Result<Json<CapabilityGrantsPayload>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+    let principal = user_principal(&state.db, &user_id).await?;
+    Ok(Json(fetch_grants(&state.db, principal).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/users/{user_id}/grants",
+    tag = "users",
+    request_body = CapabilityGrantsPayload,
+    params(("user_id" = String, Path, description = "User id")),
+    responses(
+        (status = 200, description = "Resource-scoped grants", body = CapabilityGrantsPayload),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "User not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn put_user_grants(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<String>,
+    Json(payload): Json<CapabilityGrantsPayload>,
+) -> Result<Json<CapabilityGrantsPayload>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+    let principal = user_principal(&state.db, &user_id).await?;
+    Ok(Json(
+        replace_grants(&state, &user, principal, payload).await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/api-tokens/{token_id}/grants",
+    tag = "auth",
+    params(("token_id" = String, Path, description = "API token id")),
+    responses(
+        (status = 200, description = "Resource-scoped grants", body = CapabilityGrantsPayload),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Token not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn get_api_token_grants(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(token_id): Path<String>,
+) -> Result<Json<CapabilityGrantsPayload>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+    let principal = token_principal(&state.db, &token_id).await?;
+    Ok(Json(fetch_grants(&state.db, principal).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/api-tokens/{token_id}/grants",
+    tag = "auth",
+    request_body = CapabilityGrantsPayload,
+    params(("token_id" = String, Path, description = "API token id")),
+    responses(
+        (status = 200, description = "Resource-scoped grants", body = CapabilityGrantsPayload),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Token not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn put_api_token_grants(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(token_id): Path<String>,
+    Json(payload): Json<CapabilityGrantsPayload>,
+) -> Result<Json<CapabilityGrantsPayload>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+    let principal = token_principal(&state.db, &token_id).await?;
+    Ok(Json(
+        replace_grants(&state, &user, principal, payload).await?,
+    ))
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/node-groups",
+            get(list_node_groups).post(create_node_group),
+        )
+        .route(
+            "/node-groups/{group_id}",
+            put(update_node_group).delete(delete_node_group),
+        )
+        .route(
+            "/map/layers/{id}/nodes",
+            get(get_map_layer_nodes).put(put_map_layer_nodes),
+        )
+        .route(
+            "/users/{user_id}/grants",
+            get(get_user_grants).put(put_user_grants),
+        )
+        .route(
+            "/api-tokens/{token_id}/grants",
+            get(get_api_token_grants).put(put_api_token_grants),
+        )
+}
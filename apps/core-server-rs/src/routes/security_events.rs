@@ -0,0 +1,167 @@
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::map_db_error;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::IntoParams)]
+pub(crate) struct SecurityEventsQuery {
+    /// Only events for this user id (UUID).
+    user_id: Option<String>,
+    /// Only this event type, e.g. `login_failed` or `account_locked`.
+    event_type: Option<String>,
+    /// Start timestamp (RFC3339).
+    from: Option<String>,
+    /// End timestamp (RFC3339).
+    to: Option<String>,
+    #[param(minimum = 1, maximum = 500)]
+    limit: Option<u32>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SecurityEventRow {
+    id: i64,
+    occurred_at: DateTime<Utc>,
+    event_type: String,
+    user_id: Option<Uuid>,
+    email: Option<String>,
+    client_ip: Option<String>,
+    user_agent: Option<String>,
+    actor_id: Option<String>,
+    actor_email: Option<String>,
+    details: sqlx::types::Json<JsonValue>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct SecurityEventResponse {
+    id: String,
+    occurred_at: String,
+    event_type: String,
+    user_id: Option<String>,
+    email: Option<String>,
+    client_ip: Option<String>,
+    user_agent: Option<String>,
+    actor_id: Option<String>,
+    actor_email: Option<String>,
+    details: JsonValue,
+}
+
+impl From<SecurityEventRow> for SecurityEventResponse {
+    fn from(row: SecurityEventRow) -> Self {
+        Self {
+            id: row.id.to_string(),
+            occurred_at: row.occurred_at.to_rfc3339(),
+            event_type: row.event_type,
+            user_id: row.user_id.map(|value| value.to_string()),
+            email: row.email,
+            client_ip: row.client_ip,
+            user_agent: row.user_agent,
+            actor_id: row.actor_id,
+            actor_email: row.actor_email,
+            details: row.details.0,
+        }
+    }
+}
+
+fn parse_rfc3339_optional(
+    raw: Option<&str>,
+    field: &str,
+) -> Result<Option<DateTime<Utc>>, (StatusCode, String)> {
+    raw.map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|parsed| parsed.with_timezone(&Utc))
+                .map_err(|_| (StatusCode::BAD_REQUEST, format!("{field} must be RFC3339")))
+        })
+        .transpose()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/security-events",
+    tag = "users",
+    params(SecurityEventsQuery),
+    responses(
+        (status = 200, description = "Security events, newest first", body = Vec<SecurityEventResponse>),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_security_events(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Query(query): Query<SecurityEventsQuery>,
+) -> Result<Json<Vec<SecurityEventResponse>>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let user_id: Option<Uuid> = query
+        .user_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(Uuid::parse_str)
+        .transpose()
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                "user_id must be a UUID".to_string(),
+            )
+        })?;
+    let event_type = query
+        .event_type
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+    let from_ts = parse_rfc3339_optional(query.from.as_deref(), "from")?;
+    let to_ts = parse_rfc3339_optional(query.to.as_deref(), "to")?;
+    let limit = query.limit.unwrap_or(100).clamp(1, 500) as i64;
+
+    let rows: Vec<SecurityEventRow> = sqlx::query_as(
+        r#"
+        SELECT
+            id,
+            occurred_at,
+            event_type,
+            user_id,
+            email,
+            client_ip,
+            user_agent,
+            actor_id,
+            actor_email,
+            details
+        FROM security_events
+        WHERE ($1::uuid IS NULL OR user_id = $1)
+          AND ($2::text IS NULL OR event_type = $2)
+          AND ($3::timestamptz IS NULL OR occurred_at >= $3)
+          AND ($4::timestamptz IS NULL OR occurred_at <= $4)
+        ORDER BY occurred_at DESC, id DESC
+        LIMIT $5
+        "#,
+    )
+    .bind(user_id)
+    .bind(event_type)
+    .bind(from_ts)
+    .bind(to_ts)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    Ok(Json(
+        rows.into_iter().map(SecurityEventResponse::from).collect(),
+    ))
+}
+
+pub(crate) fn router() -> Router<AppState> {
+    Router::new().route("/security-events", get(list_security_events))
+}
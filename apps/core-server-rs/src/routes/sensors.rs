@@ -9,6 +9,7 @@ use uuid::Uuid;
 use crate::auth::{AuthUser, OptionalAuthUser};
 use crate::error::map_db_error;
 use crate::ids;
+use crate::services::audit_log;
 use crate::services::derived_sensors;
 use crate::services::sensor_visibility;
 use crate::services::sensor_visibility::SensorVisibilityInfo;
@@ -19,7 +20,7 @@ const SENSOR_CONFIG_SOURCE_DERIVED: &str = derived_sensors::SENSOR_CONFIG_SOURCE
 /// Must match `apps/core-server-rs/src/services/analysis/bucket_reader.rs`.
 const MAX_DERIVED_SENSOR_DEPTH: usize = 10;
 
-#[derive(sqlx::FromRow)]
+#[derive(Clone, sqlx::FromRow)]
 pub(crate) struct SensorRow {
     sensor_id: String,
     node_id: Uuid,
@@ -38,7 +39,7 @@ pub(crate) struct SensorRow {
 #[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
 pub(crate) struct SensorResponse {
     sensor_id: String,
-    node_id: String,
+    pub(crate) node_id: String,
     name: String,
     #[serde(rename = "type")]
     sensor_type: String,
@@ -228,6 +229,9 @@ struct SensorListRow {
     node_config: SqlJson<JsonValue>,
 }
 
+/// Capabilities whose node scope limits which sensors a signed-in caller sees.
+const SENSOR_VIEW_CAPABILITIES: [&str; 2] = ["sensors.view", "config.write"];
+
 pub(crate) async fn fetch_sensors(
     db: &sqlx::PgPool,
     node_id: Option<Uuid>,
@@ -855,16 +859,20 @@ pub(crate) async fn list_sensors(
     Query(query): Query<SensorsQuery>,
 ) -> Result<Json<Vec<SensorResponse>>, (StatusCode, String)> {
     if query.include_hidden {
-        let Some(user) = user else {
+        let Some(user) = user.as_ref() else {
             return Err((StatusCode::UNAUTHORIZED, "Unauthorized".to_string()));
         };
-        crate::auth::require_capabilities(&user, &["config.write"])
+        crate::auth::require_capabilities(user, &["config.write"])
             .map_err(|err| (err.status, err.message))?;
     }
 
     let mut sensors = fetch_sensors(&state.db, query.node_id, query.include_hidden)
         .await
         .map_err(map_db_error)?;
+    if let Some(user) = user.as_ref() {
+        let scope = user.node_scope(&SENSOR_VIEW_CAPABILITIES);
+        sensors.retain(|sensor| scope.allows_str(&sensor.node_id));
+    }
     apply_derived_latest_values(&state.db, &mut sensors)
         .await
         .map_err(map_db_error)?;
@@ -949,6 +957,14 @@ pub(crate) async fn get_sensor(
     let Some(row) = row else {
         return Err((StatusCode::NOT_FOUND, "Sensor not found".to_string()));
     };
+    if let Some(user) = user.as_ref() {
+        if !user
+            .node_scope(&SENSOR_VIEW_CAPABILITIES)
+            .allows(row.node_id)
+        {
+            return Err((StatusCode::NOT_FOUND, "Sensor not found".to_string()));
+        }
+    }
 
     if query.include_hidden {
         let Some(user) = user else {
@@ -1017,7 +1033,7 @@ pub(crate) async fn create_sensor(
     AuthUser(user): AuthUser,
     Json(payload): Json<SensorCreateRequest>,
 ) -> Result<(StatusCode, Json<SensorResponse>), (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["config.write"])
+    crate::auth::require_node_capabilities(&user, &["config.write"], payload.node_id)
         .map_err(|err| (err.status, err.message))?;
 
     if payload.name.trim().is_empty()
@@ -1166,6 +1182,16 @@ pub(crate) async fn create_sensor(
         .map_err(map_db_error)?;
 
     tx.commit().await.map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "sensor.create",
+        "sensor",
+        Some(&sensor.sensor_id),
+        None,
+        audit_log::snapshot(&sensor),
+    )
+    .await;
     Ok((StatusCode::CREATED, Json(sensor)))
 }
 
@@ -1190,7 +1216,7 @@ pub(crate) async fn update_sensor(
     Path(sensor_id): Path<String>,
     Json(payload): Json<SensorUpdateRequest>,
 ) -> Result<Json<SensorResponse>, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["config.write"])
+    crate::auth::require_any_node_capabilities(&user, &["config.write"])
         .map_err(|err| (err.status, err.message))?;
 
     let mut tx = state.db.begin().await.map_err(map_db_error)?;
@@ -1222,6 +1248,9 @@ pub(crate) async fn update_sensor(
     let Some(mut existing) = existing else {
         return Err((StatusCode::NOT_FOUND, "Sensor not found".to_string()));
     };
+    crate::auth::require_node_capabilities(&user, &["config.write"], existing.node_id)
+        .map_err(|err| (err.status, err.message))?;
+    let before = audit_log::snapshot(&SensorResponse::from(existing.clone()));
 
     let existing_source = existing
         .config
@@ -1488,6 +1517,16 @@ pub(crate) async fn update_sensor(
     }
 
     tx.commit().await.map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "sensor.update",
+        "sensor",
+        Some(&sensor.sensor_id),
+        before,
+        audit_log::snapshot(&sensor),
+    )
+    .await;
     Ok(Json(sensor))
 }
 
@@ -1510,7 +1549,7 @@ pub(crate) async fn delete_sensor(
     Path(sensor_id): Path<String>,
     Query(query): Query<SensorDeleteQuery>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    crate::auth::require_capabilities(&user, &["config.write"])
+    crate::auth::require_any_node_capabilities(&user, &["config.write"])
         .map_err(|err| (err.status, err.message))?;
 
     let mut tx = state.db.begin().await.map_err(map_db_error)?;
@@ -1542,6 +1581,9 @@ pub(crate) async fn delete_sensor(
     let Some(existing) = existing else {
         return Err((StatusCode::NOT_FOUND, "Sensor not found".to_string()));
     };
+    crate::auth::require_node_capabilities(&user, &["config.write"], existing.node_id)
+        .map_err(|err| (err.status, err.message))?;
+    let before = audit_log::snapshot(&SensorResponse::from(existing.clone()));
 
     let node: Option<NodeIdentityRow> = sqlx::query_as(
         r#"
@@ -1586,6 +1628,16 @@ pub(crate) async fn delete_sensor(
         }
 
         tx.commit().await.map_err(map_db_error)?;
+        audit_log::record(
+            &state.db,
+            &user,
+            "sensor.purge",
+            "sensor",
+            Some(sensor_id.trim()),
+            before,
+            None,
+        )
+        .await;
         return Ok(StatusCode::NO_CONTENT);
     }
 
@@ -1625,6 +1677,7 @@ pub(crate) async fn delete_sensor(
     .await
     .map_err(map_db_error)?;
 
+    let after = audit_log::snapshot(&SensorResponse::from(row.clone()));
     if let Some(mut node) = node {
         let sensor = SensorResponse::from(row);
         update_sensor_manifest(&mut node.config.0, &sensor);
@@ -1637,6 +1690,16 @@ pub(crate) async fn delete_sensor(
     }
 
     tx.commit().await.map_err(map_db_error)?;
+    audit_log::record(
+        &state.db,
+        &user,
+        "sensor.delete",
+        "sensor",
+        Some(sensor_id.trim()),
+        before,
+        after,
+    )
+    .await;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -0,0 +1,121 @@
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::internal_error;
+use crate::routes::metrics_import::write_body;
+use crate::services::audit_log;
+use crate::services::spool_bundle::{
+    import_bundle, BundleImportError, BundleImportOptions, SpoolBundleImportReport,
+    MAX_BUNDLE_BYTES,
+};
+use crate::state::AppState;
+
+const CAP_METRICS_INGEST: &str = "metrics.ingest";
+const BUNDLE_DIR: &str = "spool-bundles";
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SpoolBundleQuery {
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    adopt_stream: bool,
+}
+
+fn map_import_error(err: BundleImportError) -> (StatusCode, String) {
+    match err {
+        BundleImportError::Invalid(message) => (StatusCode::BAD_REQUEST, message),
+        BundleImportError::Forbidden(message) => (StatusCode::FORBIDDEN, message),
+        BundleImportError::Internal(err) => internal_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/nodes/spool-bundles",
+    tag = "nodes",
+    params(
+        ("dry_run" = Option<bool>, Query, description = "Decode and count without writing metrics or the ACK"),
+        ("adopt_stream" = Option<bool>, Query, description = "Replace the node's ACK state when the controller tracks a different stream")
+    ),
+    request_body(content = String, description = "Bundle written by `node-forwarder spool export`", content_type = "application/x-tar"),
+    responses(
+        (status = 200, description = "Import report", body = SpoolBundleImportReport),
+        (status = 400, description = "Not a readable spool bundle"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 413, description = "Payload too large")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn import_spool_bundle(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Query(query): Query<SpoolBundleQuery>,
+    body: Body,
+) -> Result<Json<SpoolBundleImportReport>, (StatusCode, String)> {
+    crate::auth::require_any_node_capabilities(&user, &[CAP_METRICS_INGEST, "config.write"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let dir = state.config.analysis_tmp_path.join(BUNDLE_DIR);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(internal_error)?;
+    let path = dir.join(format!("{}.tar", Uuid::new_v4()));
+    let options = BundleImportOptions {
+        dry_run: query.dry_run,
+        adopt_stream: query.adopt_stream,
+    };
+    let result = async {
+        let bytes = write_body(body, &path, MAX_BUNDLE_BYTES).await?;
+        if bytes == 0 {
+            return Err((StatusCode::BAD_REQUEST, "Upload is empty".to_string()));
+        }
+        let authorize = |node_id| {
+            crate::auth::require_node_capabilities(&user, &[CAP_METRICS_INGEST], node_id).or_else(
+                |_| crate::auth::require_node_capabilities(&user, &["config.write"], node_id),
+            )
+        };
+        import_bundle(&state.db, &path, authorize, options)
+            .await
+            .map_err(map_import_error)
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&path).await;
+    let report = result?;
+
+    if !report.dry_run {
+        audit_log::record(
+            &state.db,
+            &user,
+            "node_forwarder.spool_bundle.import",
+            "node",
+            Some(&report.node_id),
+            None,
+            Some(serde_json::json!({
+                "node_mqtt_id": report.node_mqtt_id,
+                "stream_id": report.stream_id,
+                "segments": report.segments.len(),
+                "accepted": report.accepted,
+                "duplicates": report.duplicates,
+                "held": report.held,
+                "deferred": report.deferred,
+                "acked_seq_before": report.acked_seq_before,
+                "acked_seq_after": report.acked_seq_after,
+                "adopt_stream": query.adopt_stream,
+            })),
+        )
+        .await;
+    }
+
+    Ok(Json(report))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/nodes/spool-bundles", post(import_spool_bundle))
+}
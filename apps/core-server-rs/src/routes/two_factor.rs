@@ -0,0 +1,523 @@
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::types::Json as SqlJson;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use crate::auth::lockout;
+use crate::auth::security_events::{self, SecurityEvent};
+use crate::auth::totp::{self, SecondFactor};
+use crate::auth::{AuthUser, AuthenticatedUser, SessionClient};
+use crate::error::{internal_error, map_db_error};
+use crate::services::audit_log;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct TotpStatusResponse {
+    enabled: bool,
+    required: bool,
+    recovery_codes_remaining: i32,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct TotpEnrollResponse {
+    /// Base32 secret for manual entry.
+    secret: String,
+    /// `otpauth://` URI to render as a QR code.
+    provisioning_uri: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct TotpCodeRequest {
+    code: Option<String>,
+    /// Accepted instead of `code` where noted.
+    recovery_code: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct TotpRecoveryCodesResponse {
+    /// Shown once; only hashes are stored.
+    recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, sqlx::FromRow, utoipa::ToSchema)]
+pub(crate) struct AuthRolePolicy {
+    role: String,
+    require_totp: bool,
+}
+
+#[derive(sqlx::FromRow)]
+struct EnrollmentRow {
+    email: String,
+    totp_enabled: bool,
+    totp_pending_secret: Option<String>,
+}
+
+fn session_user_id(user: &AuthenticatedUser) -> Result<Uuid, (StatusCode, String)> {
+    user.user_id().ok_or_else(|| {
+        (
+            StatusCode::FORBIDDEN,
+            "Two-factor settings belong to user accounts, not API tokens".to_string(),
+        )
+    })
+}
+
+async fn load_enrollment(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<EnrollmentRow, (StatusCode, String)> {
+    sqlx::query_as(
+        r#"
+        SELECT email, totp_enabled_at IS NOT NULL AS totp_enabled, totp_pending_secret
+        FROM users
+        WHERE id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(map_db_error)?
+    .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))
+}
+
+/// Re-checks a second factor before a sensitive change; wrong codes count against
+/// the same lockout as login.
+async fn require_current_factor(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    user_id: Uuid,
+    client: &SessionClient,
+    payload: &TotpCodeRequest,
+    allow_recovery: bool,
+) -> Result<(), (StatusCode, String)> {
+    lockout::check(&state.db, &user.email, client)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    let code = payload
+        .code
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+    let recovery = payload
+        .recovery_code
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .filter(|_| allow_recovery);
+    let factor = match (code, recovery) {
+        (Some(code), _) => SecondFactor::Code(code),
+        (None, Some(code)) => SecondFactor::RecoveryCode(code),
+        (None, None) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                crate::routes::auth::TOTP_REQUIRED_MESSAGE.to_string(),
+            ))
+        }
+    };
+    let verified = totp::verify_second_factor(&state.db, user_id, factor)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    if !verified {
+        lockout::record_failure(
+            &state.db,
+            &user.email,
+            Some(user_id),
+            client,
+            "bad_second_factor",
+        )
+        .await;
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Invalid two-factor code".to_string(),
+        ));
+    }
+    lockout::record_success(&state.db, &user.email).await;
+    Ok(())
+}
+
+async fn store_recovery_codes(
+    state: &AppState,
+    user_id: Uuid,
+    hashes: Vec<String>,
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query("UPDATE users SET totp_recovery_codes = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(SqlJson(hashes))
+        .execute(&state.db)
+        .await
+        .map_err(map_db_error)?;
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/totp",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Two-factor status of the current user", body = TotpStatusResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not a user session")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn get_totp_status(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<TotpStatusResponse>, (StatusCode, String)> {
+    let user_id = session_user_id(&user)?;
+    let status = totp::status(&state.db, user_id)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    Ok(Json(TotpStatusResponse {
+        enabled: status.enabled,
+        required: status.required,
+        recovery_codes_remaining: status.recovery_codes_remaining,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/totp/enroll",
+    tag = "auth",
+    responses(
+        (status = 200, description = "New pending TOTP secret", body = TotpEnrollResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not a user session"),
+        (status = 409, description = "TOTP already enabled")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn enroll_totp(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<TotpEnrollResponse>, (StatusCode, String)> {
+    let user_id = session_user_id(&user)?;
+    let enrollment = load_enrollment(&state, user_id).await?;
+    if enrollment.totp_enabled {
+        return Err((
+            StatusCode::CONFLICT,
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    // Restarting enrollment replaces any secret that was never confirmed.
+    let secret = totp::generate_secret();
+    sqlx::query("UPDATE users SET totp_pending_secret = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(&secret)
+        .execute(&state.db)
+        .await
+        .map_err(map_db_error)?;
+    Ok(Json(TotpEnrollResponse {
+        provisioning_uri: totp::provisioning_uri(&enrollment.email, &secret),
+        secret,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/totp/confirm",
+    tag = "auth",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "TOTP enabled; recovery codes are returned once", body = TotpRecoveryCodesResponse),
+        (status = 400, description = "No pending enrollment or missing code"),
+        (status = 401, description = "Invalid code"),
+        (status = 403, description = "Not a user session"),
+        (status = 409, description = "TOTP already enabled")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn confirm_totp(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<TotpRecoveryCodesResponse>, (StatusCode, String)> {
+    let user_id = session_user_id(&user)?;
+    let enrollment = load_enrollment(&state, user_id).await?;
+    if enrollment.totp_enabled {
+        return Err((
+            StatusCode::CONFLICT,
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    let Some(secret) = enrollment.totp_pending_secret else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Start enrollment first".to_string(),
+        ));
+    };
+    let code = payload.code.as_deref().unwrap_or("").trim();
+    let Some(step) = totp::verify_code(&secret, code, Utc::now(), None) else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Invalid two-factor code".to_string(),
+        ));
+    };
+    let (codes, hashes) = totp::generate_recovery_codes().map_err(internal_error)?;
+    let updated = sqlx::query(
+        r#"
+        UPDATE users
+        SET totp_secret = totp_pending_secret,
+            totp_pending_secret = NULL,
+            totp_enabled_at = NOW(),
+            totp_last_step = $3,
+            totp_recovery_codes = $4
+        WHERE id = $1 AND totp_pending_secret = $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(&secret)
+    .bind(step)
+    .bind(SqlJson(hashes))
+    .execute(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    if updated.rows_affected() == 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            "Enrollment changed; start again".to_string(),
+        ));
+    }
+    tracing::info!(user_id = %user_id, "totp enabled");
+    let client = SessionClient::from_request(peer, &headers, &state.config.trusted_proxies);
+    SecurityEvent::new(security_events::TOTP_ENABLED)
+        .user(Some(user_id), &enrollment.email)
+        .actor(&user)
+        .client(&client)
+        .record(&state.db)
+        .await;
+    audit_log::record(
+        &state.db,
+        &user,
+        "user.totp_enable",
+        "user",
+        Some(&user_id.to_string()),
+        Some(json!({ "totp_enabled": false })),
+        Some(json!({ "totp_enabled": true, "recovery_codes_remaining": codes.len() })),
+    )
+    .await;
+    Ok(Json(TotpRecoveryCodesResponse {
+        recovery_codes: codes,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/totp/recovery-codes",
+    tag = "auth",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "New recovery codes; previous ones stop working", body = TotpRecoveryCodesResponse),
+        (status = 400, description = "Missing code"),
+        (status = 401, description = "Invalid code"),
+        (status = 403, description = "Not a user session"),
+        (status = 429, description = "Too many failed attempts for this account or client")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn regenerate_recovery_codes(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<TotpRecoveryCodesResponse>, (StatusCode, String)> {
+    let user_id = session_user_id(&user)?;
+    let client = SessionClient::from_request(peer, &headers, &state.config.trusted_proxies);
+    require_current_factor(&state, &user, user_id, &client, &payload, false).await?;
+    let status = totp::status(&state.db, user_id)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    let (codes, hashes) = totp::generate_recovery_codes().map_err(internal_error)?;
+    store_recovery_codes(&state, user_id, hashes).await?;
+    SecurityEvent::new(security_events::RECOVERY_CODES_REGENERATED)
+        .user(Some(user_id), &user.email)
+        .actor(&user)
+        .client(&client)
+        .record(&state.db)
+        .await;
+    audit_log::record(
+        &state.db,
+        &user,
+        "user.totp_recovery_codes_regenerate",
+        "user",
+        Some(&user_id.to_string()),
+        Some(json!({ "recovery_codes_remaining": status.recovery_codes_remaining })),
+        Some(json!({ "recovery_codes_remaining": codes.len() })),
+    )
+    .await;
+    Ok(Json(TotpRecoveryCodesResponse {
+        recovery_codes: codes,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/totp/disable",
+    tag = "auth",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 204, description = "TOTP disabled"),
+        (status = 400, description = "Missing code"),
+        (status = 401, description = "Invalid code"),
+        (status = 403, description = "Required by role policy or not a user session"),
+        (status = 429, description = "Too many failed attempts for this account or client")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn disable_totp(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = session_user_id(&user)?;
+    let status = totp::status(&state.db, user_id)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    if status.required {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Two-factor authentication is required for your role".to_string(),
+        ));
+    }
+    if !status.enabled {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+    let client = SessionClient::from_request(peer, &headers, &state.config.trusted_proxies);
+    require_current_factor(&state, &user, user_id, &client, &payload, true).await?;
+    totp::clear(&state.db, user_id)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    tracing::info!(user_id = %user_id, "totp disabled");
+    SecurityEvent::new(security_events::TOTP_DISABLED)
+        .user(Some(user_id), &user.email)
+        .actor(&user)
+        .client(&client)
+        .record(&state.db)
+        .await;
+    audit_log::record(
+        &state.db,
+        &user,
+        "user.totp_disable",
+        "user",
+        Some(&user_id.to_string()),
+        Some(json!({
+            "totp_enabled": true,
+            "recovery_codes_remaining": status.recovery_codes_remaining,
+        })),
+        Some(json!({ "totp_enabled": false, "recovery_codes_remaining": 0 })),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/totp/policies",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Per-role two-factor policy", body = Vec<AuthRolePolicy>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_role_policies(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<Vec<AuthRolePolicy>>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+    let rows: Vec<AuthRolePolicy> = sqlx::query_as(
+        r#"
+        SELECT roles.role, COALESCE(p.require_totp, FALSE) AS require_totp
+        FROM (
+            SELECT unnest(ARRAY['admin', 'operator', 'view']) AS role
+            UNION
+            SELECT role FROM users
+            UNION
+            SELECT role FROM auth_role_policies
+        ) roles
+        LEFT JOIN auth_role_policies p ON p.role = roles.role
+        ORDER BY roles.role
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    Ok(Json(rows))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/auth/totp/policies",
+    tag = "auth",
+    request_body = AuthRolePolicy,
+    responses(
+        (status = 200, description = "Updated policy", body = AuthRolePolicy),
+        (status = 400, description = "Invalid role"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn update_role_policy(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<AuthRolePolicy>,
+) -> Result<Json<AuthRolePolicy>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+    let role = crate::auth::canonicalize_role(&payload.role);
+    if role.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Role is required".to_string()));
+    }
+    let row: AuthRolePolicy = sqlx::query_as(
+        r#"
+        INSERT INTO auth_role_policies (role, require_totp, updated_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (role) DO UPDATE SET
+            require_totp = EXCLUDED.require_totp,
+            updated_at = EXCLUDED.updated_at
+        RETURNING role, require_totp
+        "#,
+    )
+    .bind(&role)
+    .bind(payload.require_totp)
+    .fetch_one(&state.db)
+    .await
+    .map_err(map_db_error)?;
+    tracing::info!(role = %row.role, require_totp = row.require_totp, actor = %user.email, "2fa policy updated");
+    audit_log::record(
+        &state.db,
+        &user,
+        "auth_policy.update",
+        "role",
+        Some(&row.role),
+        None,
+        audit_log::snapshot(&row),
+    )
+    .await;
+    Ok(Json(row))
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/auth/totp", get(get_totp_status))
+        .route("/auth/totp/enroll", post(enroll_totp))
+        .route("/auth/totp/confirm", post(confirm_totp))
+        .route("/auth/totp/recovery-codes", post(regenerate_recovery_codes))
+        .route("/auth/totp/disable", post(disable_totp))
+        .route(
+            "/auth/totp/policies",
+            get(list_role_policies).put(update_role_policy),
+        )
+}
@@ -1,17 +1,22 @@
 use axum::extract::{ConnectInfo, Path};
 use axum::http::StatusCode;
-use axum::routing::{get, put};
+use axum::routing::{delete, get, post, put};
 use axum::{Json, Router};
+use serde_json::Value as JsonValue;
 use sqlx::types::Json as SqlJson;
 use sqlx::PgPool;
 use std::net::SocketAddr;
 use uuid::Uuid;
 
+use crate::auth::security_events::{self, SecurityEvent};
+use crate::auth::{lockout, sessions};
 use crate::auth::{AuthUser, OptionalAuthUser};
 use crate::error::{internal_error, map_db_conflict, map_db_error};
+use crate::routes::auth::{session_response, AuthSessionResponse};
+use crate::services::audit_log;
 use crate::state::AppState;
 
-fn normalize_capabilities(capabilities: Vec<String>) -> Vec<String> {
+pub(crate) fn normalize_capabilities(capabilities: Vec<String>) -> Vec<String> {
     let mut out = capabilities
         .into_iter()
         .map(|value| value.trim().to_string())
@@ -22,7 +27,7 @@ fn normalize_capabilities(capabilities: Vec<String>) -> Vec<String> {
     out
 }
 
-fn ensure_admin_defaults(role: &str, capabilities: &mut Vec<String>) {
+pub(crate) fn ensure_admin_defaults(role: &str, capabilities: &mut Vec<String>) {
     if !role.trim().eq_ignore_ascii_case("admin") {
         return;
     }
@@ -59,6 +64,9 @@ fn ensure_admin_defaults(role: &str, capabilities: &mut Vec<String>) {
     if !capabilities.iter().any(|cap| cap == "users.manage") {
         capabilities.push("users.manage".to_string());
     }
+    if !capabilities.iter().any(|cap| cap == "audit.view") {
+        capabilities.push("audit.view".to_string());
+    }
     if !capabilities.iter().any(|cap| cap == "analysis.view") {
         capabilities.push("analysis.view".to_string());
     }
@@ -85,7 +93,7 @@ fn bootstrap_user_create_allowed(peer_ip: std::net::IpAddr) -> bool {
     allow && peer_ip.is_loopback()
 }
 
-fn default_capabilities_for_role(role: &str) -> Vec<String> {
+pub(crate) fn default_capabilities_for_role(role: &str) -> Vec<String> {
     match role.trim().to_lowercase().as_str() {
         "admin" => vec![
             "config.view",
@@ -98,6 +106,7 @@ fn default_capabilities_for_role(role: &str) -> Vec<String> {
             "setup.credentials.view",
             "config.write",
             "users.manage",
+            "audit.view",
             "schedules.write",
             "outputs.command",
             "alerts.view",
@@ -156,6 +165,8 @@ pub(crate) struct UserUpdateRequest {
     role: Option<String>,
     capabilities: Option<Vec<String>>,
     password: Option<String>,
+    /// Only allow single sign-on for this user.
+    password_login_disabled: Option<bool>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
@@ -166,9 +177,20 @@ pub(crate) struct UserResponse {
     role: String,
     capabilities: Vec<String>,
     last_login: Option<String>,
+    password_login_disabled: bool,
+    /// Linked to an identity-provider account.
+    sso_linked: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub(crate) struct UserLockoutResponse {
+    locked: bool,
+    failures: i32,
+    last_failure_at: Option<String>,
+    locked_until: Option<String>,
 }
 
-#[derive(sqlx::FromRow)]
+#[derive(Clone, sqlx::FromRow)]
 pub(crate) struct UserRow {
     id: Uuid,
     name: String,
@@ -176,6 +198,8 @@ pub(crate) struct UserRow {
     role: String,
     capabilities: SqlJson<Vec<String>>,
     last_login: Option<chrono::DateTime<chrono::Utc>>,
+    password_login_disabled: bool,
+    sso_linked: bool,
 }
 
 fn user_row_to_response(row: UserRow) -> UserResponse {
@@ -186,13 +210,16 @@ fn user_row_to_response(row: UserRow) -> UserResponse {
         role: crate::auth::canonicalize_role(&row.role),
         capabilities: row.capabilities.0,
         last_login: row.last_login.map(|ts| ts.to_rfc3339()),
+        password_login_disabled: row.password_login_disabled,
+        sso_linked: row.sso_linked,
     }
 }
 
 pub(crate) async fn fetch_users(db: &PgPool) -> Result<Vec<UserResponse>, sqlx::Error> {
     let rows: Vec<UserRow> = sqlx::query_as(
         r#"
-        SELECT id, name, email, role, capabilities, last_login
+        SELECT id, name, email, role, capabilities, last_login, password_login_disabled,
+               oidc_subject IS NOT NULL AS sso_linked
         FROM users
         ORDER BY name ASC
         "#,
@@ -247,13 +274,14 @@ pub(crate) async fn create_user(
         .await
         .map_err(map_db_error)?;
 
-    if users_exist {
+    let actor = if users_exist {
         let user = maybe_user.ok_or((
             StatusCode::UNAUTHORIZED,
             "Missing or invalid token".to_string(),
         ))?;
         crate::auth::require_capabilities(&user, &["users.manage"])
             .map_err(|err| (err.status, err.message))?;
+        Some(user)
     } else {
         // Fresh install bootstrap protection:
         // Never allow unauthenticated user creation from the LAN just because the DB is empty.
@@ -266,7 +294,8 @@ pub(crate) async fn create_user(
                 "Bootstrap user creation is disabled. Complete setup via the installer/Setup Center.".to_string(),
             ));
         }
-    }
+        None
+    };
 
     if payload.password.trim().is_empty() {
         return Err((StatusCode::BAD_REQUEST, "Password is required".to_string()));
@@ -284,7 +313,8 @@ pub(crate) async fn create_user(
         r#"
         INSERT INTO users (name, email, role, capabilities, password_hash)
         VALUES ($1, $2, $3, $4, $5)
-        RETURNING id, name, email, role, capabilities, last_login
+        RETURNING id, name, email, role, capabilities, last_login, password_login_disabled,
+                  oidc_subject IS NOT NULL AS sso_linked
         "#,
     )
     .bind(payload.name.trim())
@@ -296,7 +326,20 @@ pub(crate) async fn create_user(
     .await
     .map_err(|err| map_db_conflict(err, "Email already in use"))?;
 
-    Ok((StatusCode::CREATED, Json(user_row_to_response(row))))
+    let created = user_row_to_response(row);
+    if let Some(user) = actor.as_ref() {
+        audit_log::record(
+            &state.db,
+            user,
+            "user.create",
+            "user",
+            Some(&created.id),
+            None,
+            audit_log::snapshot(&created),
+        )
+        .await;
+    }
+    Ok((StatusCode::CREATED, Json(created)))
 }
 
 #[utoipa::path(
@@ -329,7 +372,8 @@ pub(crate) async fn update_user(
 
     let existing: Option<UserRow> = sqlx::query_as(
         r#"
-        SELECT id, name, email, role, capabilities, last_login
+        SELECT id, name, email, role, capabilities, last_login, password_login_disabled,
+               oidc_subject IS NOT NULL AS sso_linked
         FROM users
         WHERE id = $1
         "#,
@@ -342,12 +386,18 @@ pub(crate) async fn update_user(
     let Some(existing) = existing else {
         return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
     };
+    let before = audit_log::snapshot(&user_row_to_response(existing.clone()));
 
+    let previous_role = crate::auth::canonicalize_role(&existing.role);
+    let previous_capabilities: std::collections::HashSet<String> =
+        existing.capabilities.0.iter().cloned().collect();
     let mut name = existing.name;
     let mut email = existing.email;
     let mut role = existing.role;
     let mut capabilities = existing.capabilities.0;
     let mut password_hash: Option<String> = None;
+    let password_changed = payload.password.is_some();
+    let mut password_login_disabled = existing.password_login_disabled;
 
     if let Some(updated) = payload.name {
         if !updated.trim().is_empty() {
@@ -379,6 +429,10 @@ pub(crate) async fn update_user(
         password_hash = Some(crate::auth::hash_password(&updated).map_err(internal_error)?);
     }
 
+    if let Some(updated) = payload.password_login_disabled {
+        password_login_disabled = updated;
+    }
+
     ensure_admin_defaults(&role, &mut capabilities);
 
     let row: UserRow = sqlx::query_as(
@@ -389,9 +443,11 @@ pub(crate) async fn update_user(
             role = $4,
             capabilities = $5,
             password_hash = COALESCE($6, password_hash),
+            password_login_disabled = $7,
             updated_at = NOW()
         WHERE id = $1
-        RETURNING id, name, email, role, capabilities, last_login
+        RETURNING id, name, email, role, capabilities, last_login, password_login_disabled,
+                  oidc_subject IS NOT NULL AS sso_linked
         "#,
     )
     .bind(user_uuid)
@@ -400,11 +456,59 @@ pub(crate) async fn update_user(
     .bind(&role)
     .bind(SqlJson(capabilities))
     .bind(password_hash)
+    .bind(password_login_disabled)
     .fetch_one(&state.db)
     .await
     .map_err(|err| map_db_conflict(err, "Email already in use"))?;
 
-    Ok(Json(user_row_to_response(row)))
+    // Existing sessions carry the old permissions in the client's view of the user,
+    // so changing them forces a fresh login. A password reset also ends every session,
+    // including any opened with the old password.
+    let permissions_changed = crate::auth::canonicalize_role(&row.role) != previous_role
+        || row
+            .capabilities
+            .0
+            .iter()
+            .cloned()
+            .collect::<std::collections::HashSet<_>>()
+            != previous_capabilities;
+    let revoke_reason = if permissions_changed {
+        Some(sessions::REVOKE_PERMISSIONS_CHANGED)
+    } else if password_changed {
+        Some(sessions::REVOKE_PASSWORD_CHANGED)
+    } else {
+        None
+    };
+    if let Some(reason) = revoke_reason {
+        sessions::revoke_user_sessions(&state.db, user_uuid, reason)
+            .await
+            .map_err(|err| (err.status, err.message))?;
+    }
+    if password_changed {
+        SecurityEvent::new(security_events::PASSWORD_CHANGED)
+            .user(Some(user_uuid), &row.email)
+            .actor(&user)
+            .record(&state.db)
+            .await;
+    }
+
+    let updated = user_row_to_response(row);
+    let mut after = audit_log::snapshot(&updated);
+    if let (true, Some(JsonValue::Object(fields))) = (password_changed, after.as_mut()) {
+        // The hash itself never reaches the log, only the fact that it changed.
+        fields.insert("password_changed".to_string(), JsonValue::Bool(true));
+    }
+    audit_log::record(
+        &state.db,
+        &user,
+        "user.update",
+        "user",
+        Some(&updated.id),
+        before,
+        after,
+    )
+    .await;
+    Ok(Json(updated))
 }
 
 #[utoipa::path(
@@ -431,15 +535,268 @@ pub(crate) async fn delete_user(
     let user_uuid = Uuid::parse_str(user_id.trim())
         .map_err(|_| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
 
-    let result = sqlx::query("DELETE FROM users WHERE id = $1")
+    let deleted: Option<UserRow> = sqlx::query_as(
+        r#"
+        DELETE FROM users
+        WHERE id = $1
+        RETURNING id, name, email, role, capabilities, last_login, password_login_disabled,
+                  oidc_subject IS NOT NULL AS sso_linked
+        "#,
+    )
+    .bind(user_uuid)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(map_db_error)?;
+
+    let Some(deleted) = deleted else {
+        return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
+    };
+    audit_log::record(
+        &state.db,
+        &user,
+        "user.delete",
+        "user",
+        Some(user_id.trim()),
+        audit_log::snapshot(&user_row_to_response(deleted)),
+        None,
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/{user_id}/sessions",
+    tag = "users",
+    params(("user_id" = String, Path, description = "User id")),
+    responses(
+        (status = 200, description = "Active sessions of the user", body = Vec<AuthSessionResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "User not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn list_user_sessions(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<AuthSessionResponse>>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let user_uuid = Uuid::parse_str(user_id.trim())
+        .map_err(|_| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    let rows = sessions::list_user_sessions(&state.db, user_uuid)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| session_response(row, user.session_id))
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/{user_id}/logout",
+    tag = "users",
+    params(("user_id" = String, Path, description = "User id")),
+    responses(
+        (status = 204, description = "All sessions of the user revoked"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "User not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn force_logout_user(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let user_uuid = Uuid::parse_str(user_id.trim())
+        .map_err(|_| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
         .bind(user_uuid)
-        .execute(&state.db)
+        .fetch_one(&state.db)
         .await
         .map_err(map_db_error)?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
+    }
+    let revoked = sessions::revoke_user_sessions(&state.db, user_uuid, sessions::REVOKE_ADMIN)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    tracing::info!(
+        user_id = %user_uuid,
+        revoked,
+        actor = %user.email,
+        "forced logout"
+    );
+    audit_log::record(
+        &state.db,
+        &user,
+        "user.force_logout",
+        "user",
+        Some(user_id.trim()),
+        None,
+        Some(serde_json::json!({ "revoked_sessions": revoked })),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/users/{user_id}/totp",
+    tag = "users",
+    params(("user_id" = String, Path, description = "User id")),
+    responses(
+        (status = 204, description = "Second factor removed and sessions revoked"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "User not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn reset_user_totp(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
 
-    if result.rows_affected() == 0 {
+    let user_uuid = Uuid::parse_str(user_id.trim())
+        .map_err(|_| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    let cleared = crate::auth::totp::clear(&state.db, user_uuid)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    if !cleared {
         return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
     }
+    // Sessions established with the old factor should not outlive the reset.
+    sessions::revoke_user_sessions(&state.db, user_uuid, sessions::REVOKE_SECOND_FACTOR_RESET)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    tracing::info!(user_id = %user_uuid, actor = %user.email, "second factor reset");
+    audit_log::record(
+        &state.db,
+        &user,
+        "user.totp_reset",
+        "user",
+        Some(user_id.trim()),
+        None,
+        None,
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn fetch_user_email(
+    state: &AppState,
+    user_id: &str,
+) -> Result<(Uuid, String), (StatusCode, String)> {
+    let user_uuid = Uuid::parse_str(user_id.trim())
+        .map_err(|_| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    let email: Option<String> = sqlx::query_scalar("SELECT email FROM users WHERE id = $1")
+        .bind(user_uuid)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(map_db_error)?;
+    let email = email.ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    Ok((user_uuid, email))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/{user_id}/lockout",
+    tag = "users",
+    params(("user_id" = String, Path, description = "User id")),
+    responses(
+        (status = 200, description = "Failed-login state of the account", body = UserLockoutResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "User not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn get_user_lockout(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<String>,
+) -> Result<Json<UserLockoutResponse>, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let (_, email) = fetch_user_email(&state, &user_id).await?;
+    let lockout = lockout::account_lockout(&state.db, &email)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    let now = chrono::Utc::now();
+    Ok(Json(match lockout {
+        Some(lockout) => UserLockoutResponse {
+            locked: lockout.locked_until.is_some_and(|until| until > now),
+            failures: lockout.failures,
+            last_failure_at: Some(lockout.last_failure_at.to_rfc3339()),
+            locked_until: lockout.locked_until.map(|ts| ts.to_rfc3339()),
+        },
+        None => UserLockoutResponse {
+            locked: false,
+            failures: 0,
+            last_failure_at: None,
+            locked_until: None,
+        },
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/{user_id}/unlock",
+    tag = "users",
+    params(("user_id" = String, Path, description = "User id")),
+    responses(
+        (status = 204, description = "Failed-login counter cleared"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "User not found")
+    ),
+    security(("HTTPBearer" = []))
+)]
+pub(crate) async fn unlock_user(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    crate::auth::require_capabilities(&user, &["users.manage"])
+        .map_err(|err| (err.status, err.message))?;
+
+    let (user_uuid, email) = fetch_user_email(&state, &user_id).await?;
+    let cleared = lockout::unlock_account(&state.db, &email)
+        .await
+        .map_err(|err| (err.status, err.message))?;
+    if cleared {
+        SecurityEvent::new(security_events::ACCOUNT_UNLOCKED)
+            .user(Some(user_uuid), &email)
+            .actor(&user)
+            .record(&state.db)
+            .await;
+        tracing::info!(user_id = %user_uuid, actor = %user.email, "account unlocked");
+        audit_log::record(
+            &state.db,
+            &user,
+            "user.unlock",
+            "user",
+            Some(user_id.trim()),
+            None,
+            None,
+        )
+        .await;
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -447,6 +804,11 @@ pub fn router() -> Router<AppState> {
     Router::new()
         .route("/users", get(list_users).post(create_user))
         .route("/users/{user_id}", put(update_user).delete(delete_user))
+        .route("/users/{user_id}/sessions", get(list_user_sessions))
+        .route("/users/{user_id}/logout", post(force_logout_user))
+        .route("/users/{user_id}/totp", delete(reset_user_totp))
+        .route("/users/{user_id}/lockout", get(get_user_lockout))
+        .route("/users/{user_id}/unlock", post(unlock_user))
 }
 
 #[cfg(test)]
@@ -464,6 +826,7 @@ mod tests {
         assert!(capabilities.contains(&"config.view".to_string()));
         assert!(capabilities.contains(&"config.write".to_string()));
         assert!(capabilities.contains(&"users.manage".to_string()));
+        assert!(capabilities.contains(&"audit.view".to_string()));
     }
 
     #[test]
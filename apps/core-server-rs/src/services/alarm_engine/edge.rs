@@ -0,0 +1,554 @@
+//! Alarm rules evaluated on nodes by node-forwarder while the controller is unreachable.
+//!
+//! Opted-in rules (`alarm_rule_edge`) are compiled into one bundle per node. A node only acts on
+//! its bundle after losing every MQTT upstream; the transitions it decided come back through its
+//! spool as `edge-alarm.*` samples, which telemetry-sidecar parks in `edge_alarm_events` and
+//! [`reconcile_edge_events`] folds into `alarm_events` and incidents.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use sqlx::types::Json as SqlJson;
+use sqlx::{FromRow, PgPool};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use uuid::Uuid;
+
+use super::eval::{self, ResolvedTarget};
+use super::types::{self, ConditionNode, MatchMode, RuleEnvelope, TargetSelector, TimingConfig};
+use super::{AlarmRuleRow, AlarmRuleStateRow};
+
+pub const EDGE_ALARM_SENSOR_PREFIX: &str = "edge-alarm.";
+const RECONCILE_BATCH: i64 = 500;
+
+/// Output command applied on the node when an edge rule fires or clears.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct EdgeAction {
+    pub output_id: String,
+    pub state: String,
+}
+
+/// One rule target as node-forwarder evaluates it.
+#[derive(Debug, Clone, Serialize)]
+pub struct EdgeRule {
+    pub rule_id: i64,
+    pub name: String,
+    pub severity: String,
+    pub target_key: String,
+    pub event_sensor_id: String,
+    pub sensor_ids: Vec<String>,
+    pub match_mode: MatchMode,
+    pub condition: ConditionNode,
+    pub timing: TimingConfig,
+    pub fire_action: Option<EdgeAction>,
+    pub clear_action: Option<EdgeAction>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EdgeRuleBundle {
+    /// sha256 of the rules; unchanged rules keep the same version.
+    pub version: String,
+    pub generated_at: String,
+    pub rules: Vec<EdgeRule>,
+}
+
+impl EdgeRuleBundle {
+    fn new(mut rules: Vec<EdgeRule>) -> Self {
+        rules.sort_by(|a, b| (a.rule_id, &a.target_key).cmp(&(b.rule_id, &b.target_key)));
+        let encoded = serde_json::to_vec(&rules).unwrap_or_default();
+        Self {
+            version: format!("{:x}", Sha256::digest(&encoded)),
+            generated_at: Utc::now().to_rfc3339(),
+            rules,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct EdgeTargetPlan {
+    pub target_key: String,
+    pub node_id: Option<String>,
+    pub node_mqtt_id: Option<String>,
+    pub event_sensor_id: String,
+    /// Why the target is evaluated only on the controller; `None` when it is pushed to the node.
+    pub skipped_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EdgeRuleCompilation {
+    pub plans: Vec<EdgeTargetPlan>,
+    /// Compiled rules keyed by node MQTT id.
+    pub rules: Vec<(String, EdgeRule)>,
+}
+
+/// Sensor id carrying a target's transitions through the node spool.
+pub fn event_sensor_id(rule_id: i64, target_key: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(target_key.as_bytes()));
+    format!("{EDGE_ALARM_SENSOR_PREFIX}{rule_id}.{}", &digest[..12])
+}
+
+/// Nodes evaluate threshold, range, rolling window and consecutive period conditions only.
+pub fn check_edge_condition(node: &ConditionNode) -> Result<(), String> {
+    match node {
+        ConditionNode::Threshold { .. }
+        | ConditionNode::Range { .. }
+        | ConditionNode::RollingWindow { .. } => Ok(()),
+        ConditionNode::ConsecutivePeriods { child, .. } => check_edge_condition(child),
+        ConditionNode::Offline { .. } => Err("offline conditions cannot run on a node".to_string()),
+        ConditionNode::Deviation { .. } => {
+            Err("deviation conditions cannot run on a node".to_string())
+        }
+        ConditionNode::All { .. } | ConditionNode::Any { .. } | ConditionNode::Not { .. } => {
+            Err("all/any/not conditions cannot run on a node".to_string())
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct OutputNodeRow {
+    id: String,
+    node_id: Uuid,
+}
+
+/// Resolves a rule's targets and keeps those a single node can evaluate and act on by itself.
+pub async fn compile_rule(
+    pool: &PgPool,
+    rule_id: i64,
+    name: &str,
+    severity: &str,
+    envelope: &RuleEnvelope,
+    fire_action: Option<&EdgeAction>,
+    clear_action: Option<&EdgeAction>,
+) -> Result<EdgeRuleCompilation> {
+    let mut compilation = EdgeRuleCompilation::default();
+    let targets = eval::resolve_targets(pool, &envelope.target_selector).await?;
+    if targets.is_empty() {
+        return Ok(compilation);
+    }
+
+    let output_ids: Vec<String> = fire_action
+        .into_iter()
+        .chain(clear_action)
+        .map(|action| action.output_id.clone())
+        .collect();
+    let output_nodes: HashMap<String, Uuid> = if output_ids.is_empty() {
+        HashMap::new()
+    } else {
+        let rows: Vec<OutputNodeRow> =
+            sqlx::query_as("SELECT id, node_id FROM outputs WHERE id = ANY($1)")
+                .bind(&output_ids)
+                .fetch_all(pool)
+                .await?;
+        rows.into_iter().map(|row| (row.id, row.node_id)).collect()
+    };
+
+    let mut node_mqtt_ids: HashMap<Uuid, Option<String>> = HashMap::new();
+    for target in targets {
+        let event_sensor_id = event_sensor_id(rule_id, &target.target_key);
+        let mut plan = EdgeTargetPlan {
+            target_key: target.target_key.clone(),
+            node_id: target.node_id.map(|id| id.to_string()),
+            node_mqtt_id: None,
+            event_sensor_id: event_sensor_id.clone(),
+            skipped_reason: None,
+        };
+
+        let Some(node_id) = target.node_id else {
+            plan.skipped_reason = Some("sensors span more than one node".to_string());
+            compilation.plans.push(plan);
+            continue;
+        };
+        if let Entry::Vacant(entry) = node_mqtt_ids.entry(node_id) {
+            entry.insert(fetch_node_mqtt_id(pool, node_id).await?);
+        }
+        let Some(node_mqtt_id) = node_mqtt_ids.get(&node_id).cloned().flatten() else {
+            plan.skipped_reason = Some("node has not reported a node-forwarder id".to_string());
+            compilation.plans.push(plan);
+            continue;
+        };
+        plan.node_mqtt_id = Some(node_mqtt_id.clone());
+
+        if let Some(output_id) = output_ids
+            .iter()
+            .find(|output_id| output_nodes.get(*output_id) != Some(&node_id))
+        {
+            plan.skipped_reason = Some(format!("output {output_id} is not on this node"));
+            compilation.plans.push(plan);
+            continue;
+        }
+
+        compilation.rules.push((
+            node_mqtt_id,
+            edge_rule(
+                rule_id,
+                name,
+                severity,
+                envelope,
+                target,
+                event_sensor_id,
+                fire_action,
+                clear_action,
+            ),
+        ));
+        compilation.plans.push(plan);
+    }
+
+    Ok(compilation)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn edge_rule(
+    rule_id: i64,
+    name: &str,
+    severity: &str,
+    envelope: &RuleEnvelope,
+    target: ResolvedTarget,
+    event_sensor_id: String,
+    fire_action: Option<&EdgeAction>,
+    clear_action: Option<&EdgeAction>,
+) -> EdgeRule {
+    EdgeRule {
+        rule_id,
+        name: name.to_string(),
+        severity: severity.to_string(),
+        target_key: target.target_key,
+        event_sensor_id,
+        sensor_ids: target.sensor_ids,
+        match_mode: target.match_mode,
+        condition: envelope.condition.clone(),
+        timing: envelope.timing.clone(),
+        fire_action: fire_action.cloned(),
+        clear_action: clear_action.cloned(),
+    }
+}
+
+/// Node of each target the selector resolves to; `None` for a target whose sensors span nodes.
+pub async fn target_nodes(pool: &PgPool, selector: &TargetSelector) -> Result<Vec<Option<Uuid>>> {
+    let targets = eval::resolve_targets(pool, selector).await?;
+    Ok(targets.into_iter().map(|target| target.node_id).collect())
+}
+
+async fn fetch_node_mqtt_id(pool: &PgPool, node_id: Uuid) -> Result<Option<String>> {
+    let row: Option<(Option<String>,)> = sqlx::query_as(
+        r#"
+        SELECT NULLIF(TRIM(config->>'agent_node_id'), '')
+        FROM nodes
+        WHERE id = $1
+        "#,
+    )
+    .bind(node_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.and_then(|row| row.0))
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct EdgeRuleSourceRow {
+    id: i64,
+    name: String,
+    severity: String,
+    target_selector: SqlJson<JsonValue>,
+    condition_ast: SqlJson<JsonValue>,
+    timing: SqlJson<JsonValue>,
+    fire_action: Option<SqlJson<EdgeAction>>,
+    clear_action: Option<SqlJson<EdgeAction>>,
+}
+
+/// One bundle for every node with a node-forwarder id, empty when no edge rule targets it, so a
+/// node drops rules that were removed while the controller was down.
+pub async fn compile_bundles(pool: &PgPool) -> Result<BTreeMap<String, EdgeRuleBundle>> {
+    let node_ids: Vec<(String,)> = sqlx::query_as(
+        r#"
+        SELECT DISTINCT TRIM(config->>'agent_node_id')
+        FROM nodes
+        WHERE NULLIF(TRIM(config->>'agent_node_id'), '') IS NOT NULL
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut per_node: BTreeMap<String, Vec<EdgeRule>> = node_ids
+        .into_iter()
+        .map(|(node_mqtt_id,)| (node_mqtt_id, Vec::new()))
+        .collect();
+
+    let rows: Vec<EdgeRuleSourceRow> = sqlx::query_as(
+        r#"
+        SELECT
+            r.id,
+            r.name,
+            r.severity,
+            r.target_selector,
+            r.condition_ast,
+            r.timing,
+            e.fire_action,
+            e.clear_action
+        FROM alarm_rule_edge e
+        JOIN alarm_rules r ON r.id = e.rule_id
+        WHERE e.enabled = TRUE
+          AND r.enabled = TRUE
+          AND r.deleted_at IS NULL
+        ORDER BY r.id ASC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in rows {
+        let envelope = match types::parse_rule_envelope(
+            &row.target_selector.0,
+            &row.condition_ast.0,
+            &row.timing.0,
+        ) {
+            Ok(envelope) => envelope,
+            Err(err) => {
+                tracing::debug!(rule_id = row.id, error = %err, "skipping invalid edge rule");
+                continue;
+            }
+        };
+        if let Err(err) = check_edge_condition(&envelope.condition) {
+            tracing::debug!(rule_id = row.id, error = %err, "skipping unsupported edge rule");
+            continue;
+        }
+        let compilation = compile_rule(
+            pool,
+            row.id,
+            &row.name,
+            &row.severity,
+            &envelope,
+            row.fire_action.as_ref().map(|action| &action.0),
+            row.clear_action.as_ref().map(|action| &action.0),
+        )
+        .await?;
+        for (node_mqtt_id, rule) in compilation.rules {
+            per_node.entry(node_mqtt_id).or_default().push(rule);
+        }
+    }
+
+    Ok(per_node
+        .into_iter()
+        .map(|(node_mqtt_id, rules)| (node_mqtt_id, EdgeRuleBundle::new(rules)))
+        .collect())
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct EdgeAlarmEventRow {
+    id: i64,
+    node_mqtt_id: String,
+    event_sensor_id: String,
+    rule_id: i64,
+    transition: String,
+    observed_value: Option<f64>,
+    occurred_at: DateTime<Utc>,
+}
+
+/// Applies transitions nodes decided while offline, oldest first. A transition is skipped when
+/// the controller has already moved the alarm past it (`superseded`) or the alarm is already in
+/// that state (`duplicate`). Transitions reported by a node the target does not live on are
+/// dropped as `unknown_target`.
+pub async fn reconcile_edge_events(pool: &PgPool) -> Result<usize> {
+    let events: Vec<EdgeAlarmEventRow> = sqlx::query_as(
+        r#"
+        SELECT id, node_mqtt_id, event_sensor_id, rule_id, transition, observed_value, occurred_at
+        FROM edge_alarm_events
+        WHERE reconciled_at IS NULL
+        ORDER BY occurred_at ASC, id ASC
+        LIMIT $1
+        "#,
+    )
+    .bind(RECONCILE_BATCH)
+    .fetch_all(pool)
+    .await?;
+
+    let mut applied = 0usize;
+    for event in events {
+        let outcome = reconcile_event(pool, &event).await?;
+        if outcome == "applied" {
+            applied += 1;
+        }
+        sqlx::query(
+            r#"
+            UPDATE edge_alarm_events
+            SET reconciled_at = NOW(), outcome = $2
+            WHERE id = $1
+            "#,
+        )
+        .bind(event.id)
+        .bind(outcome)
+        .execute(pool)
+        .await?;
+    }
+    Ok(applied)
+}
+
+async fn reconcile_event(pool: &PgPool, event: &EdgeAlarmEventRow) -> Result<&'static str> {
+    let rule: Option<AlarmRuleRow> = sqlx::query_as(
+        r#"
+        SELECT
+            id,
+            name,
+            severity,
+            origin,
+            target_selector,
+            condition_ast,
+            timing,
+            message_template
+        FROM alarm_rules
+        WHERE id = $1
+          AND deleted_at IS NULL
+        "#,
+    )
+    .bind(event.rule_id)
+    .fetch_optional(pool)
+    .await?;
+    let Some(rule) = rule else {
+        return Ok("unknown_target");
+    };
+    let Ok(envelope) = types::parse_rule_envelope(
+        &rule.target_selector.0,
+        &rule.condition_ast.0,
+        &rule.timing.0,
+    ) else {
+        return Ok("unknown_target");
+    };
+    let target = eval::resolve_targets(pool, &envelope.target_selector)
+        .await?
+        .into_iter()
+        .find(|target| event_sensor_id(rule.id, &target.target_key) == event.event_sensor_id);
+    let Some(target) = target else {
+        return Ok("unknown_target");
+    };
+    // Only the node the target lives on may report transitions for it.
+    let Some(node_id) = target.node_id else {
+        return Ok("unknown_target");
+    };
+    if fetch_node_mqtt_id(pool, node_id).await?.as_deref() != Some(event.node_mqtt_id.as_str()) {
+        return Ok("unknown_target");
+    }
+
+    let state_row: Option<AlarmRuleStateRow> = sqlx::query_as(
+        r#"
+        SELECT currently_firing, window_state, last_eval_at, last_transition_at
+        FROM alarm_rule_state
+        WHERE rule_id = $1 AND target_key = $2
+        "#,
+    )
+    .bind(rule.id)
+    .bind(&target.target_key)
+    .fetch_optional(pool)
+    .await?;
+    if state_row
+        .as_ref()
+        .and_then(|row| row.last_transition_at)
+        .is_some_and(|at| at > event.occurred_at)
+    {
+        return Ok("superseded");
+    }
+    let currently_firing = state_row.is_some_and(|row| row.currently_firing);
+
+    let firing = match event.transition.as_str() {
+        "fired" if !currently_firing => {
+            super::transition_to_firing(
+                pool,
+                rule.id,
+                &rule,
+                &envelope,
+                &target,
+                event.observed_value,
+                event.occurred_at,
+                "edge",
+            )
+            .await?;
+            true
+        }
+        "resolved" if currently_firing => {
+            super::transition_to_ok(
+                pool,
+                rule.id,
+                &rule,
+                &target,
+                event.observed_value,
+                event.occurred_at,
+                "edge",
+            )
+            .await?;
+            false
+        }
+        _ => return Ok("duplicate"),
+    };
+
+    // Debounce/hysteresis progress belongs to the old state; the next controller evaluation
+    // starts its timers from here.
+    sqlx::query(
+        r#"
+        INSERT INTO alarm_rule_state (
+            rule_id,
+            target_key,
+            currently_firing,
+            consecutive_hits,
+            window_state,
+            last_value,
+            last_transition_at,
+            error
+        )
+        VALUES ($1, $2, $3, 0, '{}'::jsonb, $4, $5, NULL)
+        ON CONFLICT (rule_id, target_key)
+        DO UPDATE SET
+            currently_firing = EXCLUDED.currently_firing,
+            window_state = (alarm_rule_state.window_state - 'first_true_at') - 'first_false_at',
+            last_transition_at = EXCLUDED.last_transition_at
+        "#,
+    )
+    .bind(rule.id)
+    .bind(&target.target_key)
+    .bind(firing)
+    .bind(event.observed_value)
+    .bind(event.occurred_at)
+    .execute(pool)
+    .await?;
+
+    Ok("applied")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::alarm_engine::types::{CompareOp, ConsecutivePeriod};
+
+    #[test]
+    fn event_sensor_ids_are_stable_and_topic_safe() {
+        let id = event_sensor_id(42, "sensor:tank-level-1");
+        assert_eq!(id, event_sensor_id(42, "sensor:tank-level-1"));
+        assert_ne!(id, event_sensor_id(42, "sensor:tank-level-2"));
+        assert!(id.starts_with("edge-alarm.42."));
+        assert_eq!(id.len(), "edge-alarm.42.".len() + 12);
+        assert!(!id.contains('/') && !id.contains('+') && !id.contains('#'));
+    }
+
+    #[test]
+    fn only_the_edge_subset_of_conditions_is_accepted() {
+        let threshold = ConditionNode::Threshold {
+            op: CompareOp::Gt,
+            value: 1.0,
+        };
+        assert!(check_edge_condition(&threshold).is_ok());
+        assert!(check_edge_condition(&ConditionNode::ConsecutivePeriods {
+            period: ConsecutivePeriod::Eval,
+            count: 3,
+            child: Box::new(threshold.clone()),
+        })
+        .is_ok());
+        assert!(check_edge_condition(&ConditionNode::Offline {
+            missing_for_seconds: 60
+        })
+        .is_err());
+        assert!(check_edge_condition(&ConditionNode::ConsecutivePeriods {
+            period: ConsecutivePeriod::Hour,
+            count: 2,
+            child: Box::new(ConditionNode::Not {
+                child: Box::new(threshold),
+            }),
+        })
+        .is_err());
+    }
+}
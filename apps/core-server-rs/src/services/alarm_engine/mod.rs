@@ -7,6 +7,7 @@ use std::collections::HashSet;
 use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 
+pub mod edge;
 mod eval;
 pub mod types;
 
@@ -33,6 +34,11 @@ impl AlarmEngineService {
                 tokio::select! {
                     _ = cancel.cancelled() => break,
                     _ = interval.tick() => {
+                        // Transitions nodes decided while offline go first so this tick
+                        // evaluates against the reconciled state.
+                        if let Err(err) = edge::reconcile_edge_events(&self.pool).await {
+                            tracing::warn!(error = %err, "edge alarm reconciliation failed");
+                        }
                         if let Err(err) = evaluate_rules_now(&self.pool, None).await {
                             tracing::warn!(error = %err, "alarm engine tick failed");
                         }
@@ -233,6 +239,7 @@ pub async fn evaluate_rules_now(pool: &PgPool, only_sensor_ids: Option<&[String]
                     &target,
                     evaluation.observed_value,
                     now,
+                    &rule.origin,
                 )
                 .await?;
                 true
@@ -244,6 +251,7 @@ pub async fn evaluate_rules_now(pool: &PgPool, only_sensor_ids: Option<&[String]
                     &target,
                     evaluation.observed_value,
                     now,
+                    &rule.origin,
                 )
                 .await?;
                 true
@@ -372,6 +380,9 @@ async fn upsert_rule_target_state_error(
     Ok(())
 }
 
+/// `event_origin` is recorded on the `alarm_events` row: the rule's origin for controller
+/// evaluations, `edge` for transitions reconciled from a node.
+#[allow(clippy::too_many_arguments)]
 async fn transition_to_firing(
     pool: &PgPool,
     rule_id: i64,
@@ -380,6 +391,7 @@ async fn transition_to_firing(
     target: &eval::ResolvedTarget,
     observed_value: Option<f64>,
     now: chrono::DateTime<chrono::Utc>,
+    event_origin: &str,
 ) -> Result<()> {
     let mut tx = pool.begin().await?;
 
@@ -481,9 +493,10 @@ async fn transition_to_firing(
             anomaly_score,
             transition,
             incident_id,
-            target_key
+            target_key,
+            created_at
         )
-        VALUES ($1, $2, $3, $4, 'firing', $5, $6, $7, 'fired', $8, $9)
+        VALUES ($1, $2, $3, $4, 'firing', $5, $6, $7, 'fired', $8, $9, $10)
         "#,
     )
     .bind(alarm_id)
@@ -491,7 +504,7 @@ async fn transition_to_firing(
     .bind(sensor_id)
     .bind(node_id)
     .bind(message)
-    .bind(&rule.origin)
+    .bind(event_origin)
     .bind(observed_value)
     .bind(
         crate::services::incidents::get_or_create_incident(
@@ -508,6 +521,7 @@ async fn transition_to_firing(
         .await?,
     )
     .bind(&target.target_key)
+    .bind(now)
     .execute(&mut *tx)
     .await?;
 
@@ -522,6 +536,7 @@ async fn transition_to_ok(
     target: &eval::ResolvedTarget,
     observed_value: Option<f64>,
     now: chrono::DateTime<chrono::Utc>,
+    event_origin: &str,
 ) -> Result<()> {
     let mut tx = pool.begin().await?;
 
@@ -570,9 +585,10 @@ async fn transition_to_ok(
             anomaly_score,
             transition,
             incident_id,
-            target_key
+            target_key,
+            created_at
         )
-        VALUES ($1, $2, $3, $4, 'ok', $5, $6, $7, 'resolved', $8, $9)
+        VALUES ($1, $2, $3, $4, 'ok', $5, $6, $7, 'resolved', $8, $9, $10)
         "#,
     )
     .bind(existing.id)
@@ -580,7 +596,7 @@ async fn transition_to_ok(
     .bind(target.primary_sensor_id.as_deref())
     .bind(target.node_id)
     .bind(format!("{} resolved", rule.name))
-    .bind(&rule.origin)
+    .bind(event_origin)
     .bind(observed_value)
     .bind(
         crate::services::incidents::get_or_create_incident(
@@ -597,6 +613,7 @@ async fn transition_to_ok(
         .await?,
     )
     .bind(&target.target_key)
+    .bind(now)
     .execute(&mut *tx)
     .await?;
 
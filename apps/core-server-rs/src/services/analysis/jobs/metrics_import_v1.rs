@@ -0,0 +1,473 @@
+use super::runner::JobFailure;
+use super::store;
+use super::types::{AnalysisJobError, AnalysisJobProgress, AnalysisJobRow};
+use crate::services::analysis::lake::{
+    count_parquet_files_in_partition, list_parquet_files_for_range, read_manifest,
+    resolve_partition_location, write_manifest, AnalysisLakeConfig, PartitionLocation,
+    METRICS_DATASET_V1,
+};
+use crate::services::analysis::parquet_duckdb::{DuckDbQueryService, LakeImportCounts};
+use crate::services::analysis::replication::move_parquet_file;
+use crate::services::metrics_export::lake_watermark;
+use crate::services::metrics_import::{
+    read_upload, remove_upload, resolve_conversion, upload_data_path, upload_dir, ImportFormat,
+    ImportPoint, ImportReport, MetricsImportParams, RowMapper, METRICS_IMPORT_JOB_TYPE,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Points written to Postgres per `INSERT`.
+const INSERT_BATCH_POINTS: usize = 5_000;
+const NON_IMPORT_SOURCES: &[&str] = &["derived", "forecast_points"];
+
+#[derive(sqlx::FromRow)]
+struct ImportSensorRow {
+    sensor_id: String,
+    unit: Option<String>,
+    source: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+struct WriteTotals {
+    points: u64,
+    /// Points written, or that a dry run would write.
+    written: u64,
+    duplicates: u64,
+}
+
+pub async fn execute(
+    db: &PgPool,
+    duckdb: &DuckDbQueryService,
+    lake: &AnalysisLakeConfig,
+    job: &AnalysisJobRow,
+    cancel: CancellationToken,
+) -> std::result::Result<serde_json::Value, JobFailure> {
+    let params: MetricsImportParams = serde_json::from_value(job.params.0.clone())
+        .map_err(|err| failed("invalid_params", err.to_string(), None))?;
+    params
+        .validate()
+        .map_err(|message| failed("invalid_params", message, None))?;
+    let upload = read_upload(&lake.tmp_path, &params.upload_id)
+        .map_err(|err| failed("upload_read_failed", err.to_string(), None))?
+        .ok_or_else(|| {
+            failed(
+                "upload_not_found",
+                "Upload not found or expired".to_string(),
+                None,
+            )
+        })?;
+
+    // Resolve every mapping to a sensor and a unit conversion before reading data.
+    let sensor_ids: Vec<String> = params
+        .mappings
+        .iter()
+        .map(|mapping| mapping.sensor_id.trim().to_string())
+        .collect();
+    let rows: Vec<ImportSensorRow> = sqlx::query_as(
+        r#"
+        SELECT
+            sensor_id,
+            unit,
+            NULLIF(TRIM(COALESCE(config, '{}'::jsonb)->>'source'), '') AS source
+        FROM sensors
+        WHERE sensor_id = ANY($1)
+          AND deleted_at IS NULL
+        "#,
+    )
+    .bind(&sensor_ids)
+    .fetch_all(db)
+    .await
+    .map_err(|err| failed("sensor_lookup_failed", err.to_string(), None))?;
+    let sensors: HashMap<String, ImportSensorRow> = rows
+        .into_iter()
+        .map(|row| (row.sensor_id.clone(), row))
+        .collect();
+
+    let mut unknown: Vec<&str> = Vec::new();
+    let mut unsupported: Vec<&str> = Vec::new();
+    let mut conversions = Vec::with_capacity(params.mappings.len());
+    for (mapping, sensor_id) in params.mappings.iter().zip(&sensor_ids) {
+        let Some(sensor) = sensors.get(sensor_id) else {
+            unknown.push(sensor_id);
+            continue;
+        };
+        if sensor
+            .source
+            .as_deref()
+            .is_some_and(|source| NON_IMPORT_SOURCES.contains(&source))
+        {
+            unsupported.push(sensor_id);
+            continue;
+        }
+        conversions.push(
+            resolve_conversion(mapping, sensor.unit.as_deref())
+                .map_err(|message| failed("invalid_params", message, None))?,
+        );
+    }
+    if !unknown.is_empty() {
+        return Err(failed(
+            "unknown_sensors",
+            format!("Unknown sensors: {}", unknown.join(", ")),
+            Some(serde_json::json!({ "sensor_ids": unknown })),
+        ));
+    }
+    if !unsupported.is_empty() {
+        return Err(failed(
+            "unsupported_sensors",
+            format!(
+                "Cannot import into derived or forecast sensors: {}",
+                unsupported.join(", ")
+            ),
+            Some(serde_json::json!({ "sensor_ids": unsupported })),
+        ));
+    }
+
+    // Lake writes only cover what the lake already serves; newer points go
+    // to Postgres and reach the lake through replication.
+    let lake_cutoff = match params
+        .lake_cutoff()
+        .map_err(|message| failed("invalid_params", message, None))?
+    {
+        Some(requested) => {
+            let watermark = lake_watermark(lake).ok_or_else(|| {
+                failed(
+                    "lake_unavailable",
+                    "lake_cutoff needs a replicated analysis lake".to_string(),
+                    None,
+                )
+            })?;
+            Some(requested.min(watermark))
+        }
+        None => None,
+    };
+    let shards: Vec<String> = sensor_ids
+        .iter()
+        .map(|sensor_id| format!("{:02}", lake.shard_for_sensor_id(sensor_id)))
+        .collect();
+
+    let run_dir = upload_dir(&lake.tmp_path, upload.upload_id).join(format!("job-{}", job.id));
+    std::fs::create_dir_all(&run_dir)
+        .map_err(|err| failed("internal_error", err.to_string(), None))?;
+    let mut progress = AnalysisJobProgress {
+        phase: "metrics_import".to_string(),
+        completed: 0,
+        total: None,
+        message: Some("Preparing import".to_string()),
+    };
+    let _ = store::update_progress(db, job.id, &progress).await;
+
+    let data_path = upload_data_path(&lake.tmp_path, upload.upload_id, upload.format);
+    let (csv_path, delimiter) = match upload.format {
+        ImportFormat::Csv => (data_path, upload.delimiter),
+        ImportFormat::Parquet => {
+            progress.message = Some("Converting Parquet upload".to_string());
+            let _ = store::update_progress(db, job.id, &progress).await;
+            let converted = run_dir.join("converted.csv");
+            duckdb
+                .convert_parquet_to_csv(data_path, converted.clone())
+                .await
+                .map_err(|err| failed("duckdb_read_failed", err.to_string(), None))?;
+            (converted, ',')
+        }
+    };
+    let total_bytes = std::fs::metadata(&csv_path)
+        .map(|meta| meta.len())
+        .unwrap_or(0);
+    progress.total = Some(total_bytes);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .flexible(true)
+        .from_path(&csv_path)
+        .map_err(|err| failed("invalid_csv", err.to_string(), None))?;
+    let headers = reader
+        .headers()
+        .map_err(|err| failed("invalid_csv", err.to_string(), None))?
+        .clone();
+    let mapper = RowMapper::new(&params, &headers, conversions)
+        .map_err(|message| failed("invalid_params", message, None))?;
+
+    let staged_path = run_dir.join("lake-staged.csv");
+    let mut lake_writer = match lake_cutoff {
+        Some(_) => {
+            let mut writer = csv::Writer::from_path(&staged_path)
+                .map_err(|err| failed("internal_error", err.to_string(), None))?;
+            writer
+                .write_record(["sensor_id", "ts_micros", "value", "quality", "shard"])
+                .map_err(|err| failed("internal_error", err.to_string(), None))?;
+            Some(writer)
+        }
+        None => None,
+    };
+    let mut lake_range: Option<(DateTime<Utc>, DateTime<Utc>)> = None;
+    let mut lake_shards: BTreeSet<u32> = BTreeSet::new();
+    let mut lake_points: u64 = 0;
+
+    let mut report = ImportReport::new(&params.mappings);
+    let mut postgres = WriteTotals::default();
+    let mut record = csv::StringRecord::new();
+    let mut batch: Vec<ImportPoint> = Vec::with_capacity(INSERT_BATCH_POINTS);
+    loop {
+        if cancel.is_cancelled() {
+            return Err(JobFailure::Canceled);
+        }
+        let more = reader.read_record(&mut record).map_err(|err| {
+            failed(
+                "invalid_csv",
+                err.to_string(),
+                Some(serde_json::json!({ "rows_read": report.rows_read })),
+            )
+        })?;
+        if more {
+            let line = record.position().map(|pos| pos.line()).unwrap_or_default();
+            mapper.map_record(&record, line, &mut batch, &mut report);
+            if batch.len() < INSERT_BATCH_POINTS {
+                continue;
+            }
+        }
+
+        let mut for_postgres: Vec<ImportPoint> = Vec::with_capacity(batch.len());
+        for point in batch.drain(..) {
+            match (&mut lake_writer, lake_cutoff) {
+                (Some(writer), Some(cutoff)) if point.ts < cutoff => {
+                    writer
+                        .write_record([
+                            sensor_ids[point.mapping].as_str(),
+                            &point.ts.timestamp_micros().to_string(),
+                            &point.value.to_string(),
+                            &point.quality.to_string(),
+                            shards[point.mapping].as_str(),
+                        ])
+                        .map_err(|err| failed("internal_error", err.to_string(), None))?;
+                    lake_points += 1;
+                    lake_shards.insert(lake.shard_for_sensor_id(&sensor_ids[point.mapping]));
+                    lake_range = Some(match lake_range {
+                        Some((first, last)) => (first.min(point.ts), last.max(point.ts)),
+                        None => (point.ts, point.ts),
+                    });
+                }
+                _ => for_postgres.push(point),
+            }
+        }
+        if !for_postgres.is_empty() {
+            let written = write_postgres_batch(db, &sensor_ids, &for_postgres, params.dry_run)
+                .await
+                .map_err(|err| failed("db_query_failed", err.to_string(), None))?;
+            postgres.points += for_postgres.len() as u64;
+            postgres.written += written;
+            postgres.duplicates += for_postgres.len() as u64 - written;
+        }
+
+        progress.completed = reader.position().byte().min(total_bytes);
+        progress.message = Some(format!(
+            "{} {} points from {} rows",
+            if params.dry_run {
+                "Validated"
+            } else {
+                "Imported"
+            },
+            report.points,
+            report.rows_read
+        ));
+        let _ = store::update_progress(db, job.id, &progress).await;
+        if !more {
+            break;
+        }
+    }
+
+    let mut lake_totals = WriteTotals {
+        points: lake_points,
+        ..WriteTotals::default()
+    };
+    let mut touched_dates: BTreeSet<NaiveDate> = BTreeSet::new();
+    if let (Some(mut writer), Some((first, last))) = (lake_writer, lake_range) {
+        writer
+            .flush()
+            .map_err(|err| failed("internal_error", err.to_string(), None))?;
+        drop(writer);
+        progress.message = Some(format!("Writing {lake_points} points to the analysis lake"));
+        let _ = store::update_progress(db, job.id, &progress).await;
+
+        let existing_files = list_parquet_files_for_range(
+            lake,
+            METRICS_DATASET_V1,
+            first,
+            last + chrono::Duration::seconds(1),
+            &lake_shards,
+        )
+        .map_err(|err| failed("internal_error", err.to_string(), None))?;
+        let out_dir = run_dir.join("_out");
+        let LakeImportCounts { staged, fresh } = duckdb
+            .partition_import_points_for_lake(
+                staged_path,
+                existing_files,
+                (!params.dry_run).then(|| out_dir.clone()),
+                Utc::now(),
+            )
+            .await
+            .map_err(|err| failed("duckdb_write_failed", err.to_string(), None))?;
+        lake_totals.written = fresh;
+        lake_totals.duplicates = staged.saturating_sub(fresh);
+        if !params.dry_run && out_dir.exists() {
+            touched_dates = move_into_lake(lake, &out_dir, job.id)
+                .map_err(|err| failed("internal_error", err.to_string(), None))?;
+        }
+    }
+
+    if params.dry_run {
+        let _ = std::fs::remove_dir_all(&run_dir);
+    } else {
+        remove_upload(&lake.tmp_path, upload.upload_id);
+    }
+
+    Ok(serde_json::json!({
+        "job_type": METRICS_IMPORT_JOB_TYPE,
+        "upload_id": upload.upload_id.to_string(),
+        "format": upload.format,
+        "dry_run": params.dry_run,
+        "report": report,
+        "postgres": postgres,
+        "lake": {
+            "cutoff": lake_cutoff.map(|ts| ts.to_rfc3339()),
+            "points": lake_totals.points,
+            "written": lake_totals.written,
+            "duplicates": lake_totals.duplicates,
+            "touched_dates": touched_dates
+                .iter()
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .collect::<Vec<_>>(),
+        },
+    }))
+}
+
+fn failed(code: &str, message: String, details: Option<serde_json::Value>) -> JobFailure {
+    JobFailure::Failed(AnalysisJobError {
+        code: code.to_string(),
+        message,
+        details,
+    })
+}
+
+/// Inserts a batch, skipping points already stored. Returns how many were
+/// (or, for a dry run, would be) written.
+async fn write_postgres_batch(
+    db: &PgPool,
+    sensor_ids: &[String],
+    points: &[ImportPoint],
+    dry_run: bool,
+) -> Result<u64, sqlx::Error> {
+    let ids: Vec<String> = points
+        .iter()
+        .map(|point| sensor_ids[point.mapping].clone())
+        .collect();
+    let ts: Vec<DateTime<Utc>> = points.iter().map(|point| point.ts).collect();
+    if dry_run {
+        let existing: i64 = sqlx::query_scalar(
+            r#"
+            SELECT count(*)
+            FROM (
+                SELECT DISTINCT u.sensor_id, u.ts
+                FROM UNNEST($1::text[], $2::timestamptz[]) AS u(sensor_id, ts)
+                JOIN metrics m ON m.sensor_id = u.sensor_id AND m.ts = u.ts
+            ) matched
+            "#,
+        )
+        .bind(&ids)
+        .bind(&ts)
+        .fetch_one(db)
+        .await?;
+        return Ok((points.len() as u64).saturating_sub(existing.max(0) as u64));
+    }
+
+    let values: Vec<f64> = points.iter().map(|point| point.value).collect();
+    let qualities: Vec<i16> = points.iter().map(|point| point.quality).collect();
+    let result = sqlx::query(
+        r#"
+        INSERT INTO metrics (sensor_id, ts, value, quality, inserted_at)
+        SELECT sensor_id, ts, value, quality, now()
+        FROM UNNEST($1::text[], $2::timestamptz[], $3::float8[], $4::int2[])
+            AS u(sensor_id, ts, value, quality)
+        ON CONFLICT (sensor_id, ts) DO NOTHING
+        "#,
+    )
+    .bind(&ids)
+    .bind(&ts)
+    .bind(&values)
+    .bind(&qualities)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Moves `date=/shard=` output from DuckDB into the lake and records the
+/// partitions in the manifest, as `lake_backfill_v1` does.
+fn move_into_lake(
+    lake: &AnalysisLakeConfig,
+    out_dir: &Path,
+    job_id: Uuid,
+) -> anyhow::Result<BTreeSet<NaiveDate>> {
+    let now = Utc::now();
+    let mut manifest = read_manifest(lake).unwrap_or_default();
+    let mut touched: BTreeSet<NaiveDate> = BTreeSet::new();
+    let mut index: usize = 0;
+    for date_entry in std::fs::read_dir(out_dir)? {
+        let date_dir = date_entry?.path();
+        let Some(date) = date_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("date="))
+            .and_then(|raw| NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok())
+        else {
+            continue;
+        };
+        let location = resolve_partition_location(lake, &manifest, METRICS_DATASET_V1, date, now);
+        for shard_entry in std::fs::read_dir(&date_dir)? {
+            let shard_dir = shard_entry?.path();
+            let Some(shard) = shard_dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("shard="))
+                .and_then(|raw| raw.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let target_dir = match location {
+                PartitionLocation::Hot => lake.partition_dir_hot(METRICS_DATASET_V1, date, shard),
+                PartitionLocation::Cold => lake
+                    .partition_dir_cold(METRICS_DATASET_V1, date, shard)
+                    .unwrap_or_else(|| lake.partition_dir_hot(METRICS_DATASET_V1, date, shard)),
+            };
+            std::fs::create_dir_all(&target_dir)?;
+            for entry in std::fs::read_dir(&shard_dir)? {
+                let source = entry?.path();
+                if source.extension().and_then(|v| v.to_str()) != Some("parquet") {
+                    continue;
+                }
+                let final_parquet = target_dir.join(format!("import-{job_id}-{index}.parquet"));
+                let tmp_parquet = target_dir.join(format!("import-{job_id}-{index}.parquet.tmp"));
+                move_parquet_file(&source, &tmp_parquet, &final_parquet)?;
+                index += 1;
+            }
+            touched.insert(date);
+        }
+        manifest.set_partition_location(METRICS_DATASET_V1, date, location.as_str());
+        let partition_root = match location {
+            PartitionLocation::Hot => lake.dataset_root_hot(METRICS_DATASET_V1),
+            PartitionLocation::Cold => lake
+                .dataset_root_cold(METRICS_DATASET_V1)
+                .unwrap_or_else(|| lake.dataset_root_hot(METRICS_DATASET_V1)),
+        };
+        let partition_dir = partition_root.join(format!("date={}", date.format("%Y-%m-%d")));
+        if let Ok(file_count) = count_parquet_files_in_partition(&partition_dir) {
+            manifest.set_partition_file_count(METRICS_DATASET_V1, date, file_count);
+        }
+    }
+    if !touched.is_empty() {
+        write_manifest(lake, &manifest)?;
+    }
+    Ok(touched)
+}
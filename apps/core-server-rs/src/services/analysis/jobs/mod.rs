@@ -10,6 +10,7 @@ mod lake_inspect_v1;
 mod lake_parity_check_v1;
 mod lake_replication_tick_v1;
 mod matrix_profile_v1;
+mod metrics_import_v1;
 mod related_sensors_unified_v2;
 mod related_sensors_v1;
 mod runner;
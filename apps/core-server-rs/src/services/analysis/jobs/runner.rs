@@ -409,6 +409,10 @@ impl AnalysisJobService {
             "forecast_materialize_v1" => {
                 super::forecast_materialize_v1::execute(&self.db, job, cancel).await
             }
+            "metrics_import_v1" => {
+                super::metrics_import_v1::execute(&self.db, &self.duckdb, &self.lake, job, cancel)
+                    .await
+            }
             other => Err(JobFailure::Failed(AnalysisJobError {
                 code: "unsupported_job_type".to_string(),
                 message: format!("Unsupported job type: {other}"),
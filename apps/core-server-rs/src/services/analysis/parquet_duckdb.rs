@@ -32,6 +32,24 @@ pub struct MetricsBucketRow {
     pub samples: i64,
 }
 
+/// One exported value: a raw point (`extra` = quality) or a bucket (`extra` = samples).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsExportRow {
+    pub sensor_id: String,
+    pub ts: DateTime<Utc>,
+    pub value: f64,
+    pub extra: i64,
+}
+
+/// Row counts from staging imported points for the lake.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LakeImportCounts {
+    /// Rows in the staged CSV.
+    pub staged: u64,
+    /// Rows neither already in the lake nor repeated within the import.
+    pub fresh: u64,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MetricsQualityFilter {
     All,
@@ -201,6 +219,386 @@ impl DuckDbQueryService {
             .await
     }
 
+    /// Raw points ordered by `(ts, sensor_id)` with an exclusive `end`, capped
+    /// at `limit` rows so callers can page through dense ranges.
+    pub async fn read_metrics_points_page_from_lake(
+        &self,
+        lake: &AnalysisLakeConfig,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        sensor_ids: Vec<String>,
+        limit: usize,
+    ) -> Result<Vec<MetricsPointRow>> {
+        let shard_set = shard_set_for_sensor_ids(lake, &sensor_ids);
+        let parquet_files = list_parquet_files_for_range(
+            lake,
+            crate::services::analysis::lake::METRICS_DATASET_V1,
+            start,
+            end,
+            &shard_set,
+        )?;
+        let _permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .context("duckdb concurrency gate closed")?;
+
+        let tmp_path = self.tmp_path.clone();
+        tokio::task::spawn_blocking(move || -> Result<Vec<MetricsPointRow>> {
+            let conn = Connection::open_in_memory()?;
+            let tmp_dir = tmp_path.join("duckdb");
+            security::ensure_dir_mode(&tmp_dir, 0o700).ok();
+
+            let _ = conn.execute("PRAGMA threads=2", []);
+            let _ = conn.execute("PRAGMA enable_progress_bar=false", []);
+            let _ = conn.execute(
+                &format!(
+                    "SET temp_directory='{}'",
+                    escape_single_quotes(tmp_dir.display().to_string())
+                ),
+                [],
+            );
+
+            if parquet_files.is_empty() || sensor_ids.is_empty() || limit == 0 {
+                return Ok(vec![]);
+            }
+
+            let files_sql = parquet_files
+                .iter()
+                .map(|p| format!("'{}'", escape_single_quotes(p.display().to_string())))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let sensors_sql = sensor_ids
+                .iter()
+                .map(|s| format!("'{}'", escape_single_quotes(s.trim().to_string())))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let start_sql = start.to_rfc3339();
+            let end_sql = end.to_rfc3339();
+
+            let sql = format!(
+                r#"
+                SELECT sensor_id, ts, value, COALESCE(quality, 0)::INTEGER AS quality
+                FROM read_parquet([{files_sql}], hive_partitioning=1, union_by_name=1)
+                WHERE sensor_id IN ({sensors_sql})
+                  AND ts >= '{start_sql}'::TIMESTAMP
+                  AND ts < '{end_sql}'::TIMESTAMP
+                ORDER BY ts, sensor_id
+                LIMIT {limit}
+                "#
+            );
+
+            let mut stmt = conn.prepare(&sql)?;
+            let mut rows = stmt.query([])?;
+            let mut out = Vec::new();
+            while let Some(row) = rows.next()? {
+                let sensor_id: String = row.get(0)?;
+                let ts: NaiveDateTime = row.get(1)?;
+                let value: f64 = row.get(2)?;
+                let quality: i32 = row.get(3)?;
+                out.push(MetricsPointRow {
+                    sensor_id,
+                    ts: DateTime::<Utc>::from_naive_utc_and_offset(ts, Utc),
+                    value,
+                    quality,
+                });
+            }
+            Ok(out)
+        })
+        .await?
+    }
+
+    /// Writes export rows to a ZSTD Parquet file with a UTC `ts` column.
+    /// `extra_column` names the `extra` field (`quality` or `samples`);
+    /// `kv_metadata` lands in the file footer.
+    pub async fn write_metrics_export_parquet(
+        &self,
+        rows: Vec<MetricsExportRow>,
+        extra_column: &'static str,
+        kv_metadata: Vec<(String, String)>,
+        out_path: PathBuf,
+    ) -> Result<()> {
+        let _permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .context("duckdb concurrency gate closed")?;
+
+        let tmp_path = self.tmp_path.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let tmp_dir = tmp_path.join("duckdb");
+            std::fs::create_dir_all(&tmp_dir).ok();
+            security::ensure_dir_mode(&tmp_dir, 0o700).ok();
+
+            // Stage rows as CSV and let DuckDB convert, as replication does.
+            let staged = tempfile::Builder::new()
+                .prefix("export-")
+                .suffix(".csv")
+                .tempfile_in(&tmp_dir)?;
+            {
+                let mut writer = csv::Writer::from_writer(std::io::BufWriter::new(
+                    staged.as_file().try_clone()?,
+                ));
+                writer.write_record(["sensor_id", "ts_micros", "value", "extra"])?;
+                for row in &rows {
+                    writer.write_record([
+                        row.sensor_id.clone(),
+                        row.ts.timestamp_micros().to_string(),
+                        row.value.to_string(),
+                        row.extra.to_string(),
+                    ])?;
+                }
+                writer.flush()?;
+            }
+
+            let conn = Connection::open_in_memory()?;
+            let _ = conn.execute("PRAGMA threads=2", []);
+            let _ = conn.execute("PRAGMA enable_progress_bar=false", []);
+            let _ = conn.execute(
+                &format!(
+                    "SET temp_directory='{}'",
+                    escape_single_quotes(tmp_dir.display().to_string())
+                ),
+                [],
+            );
+
+            let csv_sql = escape_single_quotes(staged.path().display().to_string());
+            let out_sql = escape_single_quotes(out_path.display().to_string());
+            let kv_sql = kv_metadata
+                .into_iter()
+                .map(|(key, value)| {
+                    format!(
+                        "'{}': '{}'",
+                        escape_single_quotes(key),
+                        escape_single_quotes(value)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let kv_option = if kv_sql.is_empty() {
+                String::new()
+            } else {
+                format!(", KV_METADATA {{{kv_sql}}}")
+            };
+            let sql = format!(
+                r#"
+                COPY (
+                    SELECT
+                        sensor_id,
+                        to_timestamp(ts_micros / 1000000.0) AS ts,
+                        value,
+                        extra AS {extra_column}
+                    FROM read_csv(
+                        '{csv_sql}',
+                        columns={{'sensor_id':'VARCHAR','ts_micros':'BIGINT','value':'DOUBLE','extra':'BIGINT'}},
+                        header=true
+                    )
+                    ORDER BY ts, sensor_id
+                ) TO '{out_sql}' (FORMAT PARQUET, COMPRESSION ZSTD{kv_option});
+                "#
+            );
+            conn.execute(&sql, [])?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Column names of an uploaded Parquet file.
+    pub async fn describe_parquet_columns(&self, path: PathBuf) -> Result<Vec<String>> {
+        let _permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .context("duckdb concurrency gate closed")?;
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+            let conn = Connection::open_in_memory()?;
+            let path_sql = escape_single_quotes(path.display().to_string());
+            let mut stmt = conn.prepare(&format!(
+                "DESCRIBE SELECT * FROM read_parquet('{path_sql}')"
+            ))?;
+            let mut rows = stmt.query([])?;
+            let mut out = Vec::new();
+            while let Some(row) = rows.next()? {
+                out.push(row.get::<_, String>(0)?);
+            }
+            Ok(out)
+        })
+        .await?
+    }
+
+    /// Rewrites a Parquet file as a headed CSV so imports parse one format.
+    /// Timestamp columns come out as `YYYY-MM-DD HH:MM:SS[.f][+00]`.
+    pub async fn convert_parquet_to_csv(&self, source: PathBuf, dest: PathBuf) -> Result<()> {
+        let _permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .context("duckdb concurrency gate closed")?;
+
+        let tmp_path = self.tmp_path.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = Connection::open_in_memory()?;
+            let tmp_dir = tmp_path.join("duckdb");
+            security::ensure_dir_mode(&tmp_dir, 0o700).ok();
+
+            let _ = conn.execute("PRAGMA threads=2", []);
+            let _ = conn.execute("PRAGMA enable_progress_bar=false", []);
+            let _ = conn.execute("SET TimeZone='UTC'", []);
+            let _ = conn.execute(
+                &format!(
+                    "SET temp_directory='{}'",
+                    escape_single_quotes(tmp_dir.display().to_string())
+                ),
+                [],
+            );
+
+            let source_sql = escape_single_quotes(source.display().to_string());
+            let dest_sql = escape_single_quotes(dest.display().to_string());
+            conn.execute(
+                &format!(
+                    "COPY (SELECT * FROM read_parquet('{source_sql}')) TO '{dest_sql}' (FORMAT CSV, HEADER)"
+                ),
+                [],
+            )?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Drops staged import points the lake already holds, then writes the rest
+    /// as `date=/shard=` partitions under `out_dir`. With `out_dir` unset only
+    /// the counts are computed (dry run).
+    ///
+    /// The staged CSV has `sensor_id,ts_micros,value,quality,shard` columns;
+    /// `existing_files` are the lake files covering the same dates and shards.
+    pub async fn partition_import_points_for_lake(
+        &self,
+        staged_csv: PathBuf,
+        existing_files: Vec<PathBuf>,
+        out_dir: Option<PathBuf>,
+        inserted_at: DateTime<Utc>,
+    ) -> Result<LakeImportCounts> {
+        let _permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .context("duckdb concurrency gate closed")?;
+
+        let tmp_path = self.tmp_path.clone();
+        tokio::task::spawn_blocking(move || -> Result<LakeImportCounts> {
+            let conn = Connection::open_in_memory()?;
+            let tmp_dir = tmp_path.join("duckdb");
+            security::ensure_dir_mode(&tmp_dir, 0o700).ok();
+
+            let _ = conn.execute("PRAGMA threads=2", []);
+            let _ = conn.execute("PRAGMA enable_progress_bar=false", []);
+            let _ = conn.execute(
+                &format!(
+                    "SET temp_directory='{}'",
+                    escape_single_quotes(tmp_dir.display().to_string())
+                ),
+                [],
+            );
+
+            let csv_sql = escape_single_quotes(staged_csv.display().to_string());
+            let read_staged = format!(
+                "read_csv('{csv_sql}', columns={{'sensor_id':'VARCHAR','ts_micros':'BIGINT','value':'DOUBLE','quality':'INTEGER','shard':'VARCHAR'}}, header=true)"
+            );
+            let staged: i64 =
+                conn.query_row(&format!("SELECT count(*) FROM {read_staged}"), [], |row| {
+                    row.get(0)
+                })?;
+            conn.execute_batch(&format!(
+                r#"
+                CREATE TEMP TABLE staged AS
+                SELECT
+                    sensor_id,
+                    ts_micros,
+                    any_value(value) AS value,
+                    any_value(quality) AS quality,
+                    any_value(shard) AS shard
+                FROM {read_staged}
+                GROUP BY sensor_id, ts_micros;
+                "#
+            ))?;
+
+            if existing_files.is_empty() {
+                conn.execute_batch(
+                    "CREATE TEMP TABLE existing (sensor_id VARCHAR, ts_micros BIGINT);",
+                )?;
+            } else {
+                let files_sql = existing_files
+                    .iter()
+                    .map(|p| format!("'{}'", escape_single_quotes(p.display().to_string())))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                conn.execute_batch(&format!(
+                    r#"
+                    CREATE TEMP TABLE existing AS
+                    SELECT DISTINCT sensor_id, epoch_us(ts) AS ts_micros
+                    FROM read_parquet([{files_sql}], hive_partitioning=1, union_by_name=1)
+                    WHERE sensor_id IN (SELECT DISTINCT sensor_id FROM staged);
+                    "#
+                ))?;
+            }
+
+            // Replication writes `ts` through a float division, so allow a
+            // microsecond of rounding when matching existing points.
+            conn.execute_batch(
+                r#"
+                CREATE TEMP TABLE fresh AS
+                SELECT s.*
+                FROM staged s
+                WHERE NOT EXISTS (
+                    SELECT 1
+                    FROM existing e
+                    WHERE e.sensor_id = s.sensor_id
+                      AND e.ts_micros BETWEEN s.ts_micros - 1 AND s.ts_micros + 1
+                );
+                "#,
+            )?;
+            let fresh: i64 = conn.query_row("SELECT count(*) FROM fresh", [], |row| row.get(0))?;
+
+            if let Some(out_dir) = out_dir.filter(|_| fresh > 0) {
+                let out_sql = escape_single_quotes(out_dir.display().to_string());
+                let inserted_at_micros = inserted_at.timestamp_micros();
+                conn.execute(
+                    &format!(
+                        r#"
+                        COPY (
+                            SELECT
+                                sensor_id,
+                                make_timestamp(ts_micros) AS ts,
+                                value,
+                                COALESCE(quality, 0)::INTEGER AS quality,
+                                make_timestamp({inserted_at_micros}) AS inserted_at,
+                                shard,
+                                CAST(make_timestamp(ts_micros) AS DATE) AS date
+                            FROM fresh
+                            ORDER BY sensor_id, ts
+                        ) TO '{out_sql}' (FORMAT PARQUET, PARTITION_BY (date, shard), COMPRESSION ZSTD);
+                        "#
+                    ),
+                    [],
+                )?;
+            }
+
+            Ok(LakeImportCounts {
+                staged: staged.max(0) as u64,
+                fresh: fresh.max(0) as u64,
+            })
+        })
+        .await?
+    }
+
     pub async fn read_metrics_buckets_from_lake(
         &self,
         lake: &AnalysisLakeConfig,
@@ -1366,4 +1764,113 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn export_parquet_round_trips_rows_and_footer_metadata() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let svc = DuckDbQueryService::new(temp.path().to_path_buf(), 1);
+        let out = temp.path().join("export.parquet");
+        let ts = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let rows = vec![
+            MetricsExportRow {
+                sensor_id: "sensor-b".to_string(),
+                ts,
+                value: 2.5,
+                extra: 3,
+            },
+            MetricsExportRow {
+                sensor_id: "sensor-a".to_string(),
+                ts,
+                value: 1.5,
+                extra: 4,
+            },
+        ];
+        svc.write_metrics_export_parquet(
+            rows,
+            "samples",
+            vec![(
+                "farm_dashboard.export".to_string(),
+                "{\"mode\":\"buckets\"}".to_string(),
+            )],
+            out.clone(),
+        )
+        .await?;
+
+        let conn = Connection::open_in_memory()?;
+        let path = escape_single_quotes(out.display().to_string());
+        let (sensor_id, value, samples): (String, f64, i64) = conn.query_row(
+            &format!("SELECT sensor_id, value, samples FROM read_parquet('{path}') LIMIT 1"),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        assert_eq!((sensor_id.as_str(), value, samples), ("sensor-a", 1.5, 4));
+        let metadata: String = conn.query_row(
+            &format!(
+                "SELECT decode(value) FROM parquet_kv_metadata('{path}') \
+                 WHERE decode(key) = 'farm_dashboard.export'"
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(metadata, "{\"mode\":\"buckets\"}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_staging_skips_points_already_in_the_lake() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let existing = temp.path().join("existing.parquet");
+        write_parquet_rows(
+            existing.clone(),
+            vec![("sensor-a", "2020-06-01 00:00:00", 1.0, 0)],
+        )
+        .await?;
+
+        let staged = temp.path().join("staged.csv");
+        let ts = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+        let next = ts + chrono::Duration::minutes(1);
+        std::fs::write(
+            &staged,
+            format!(
+                "sensor_id,ts_micros,value,quality,shard\n\
+                 sensor-a,{ts},9.0,0,01\n\
+                 sensor-a,{next},2.0,0,01\n\
+                 sensor-a,{next},2.0,0,01\n",
+                ts = ts.timestamp_micros(),
+                next = next.timestamp_micros(),
+            ),
+        )?;
+
+        let svc = DuckDbQueryService::new(temp.path().to_path_buf(), 1);
+        let dry = svc
+            .partition_import_points_for_lake(staged.clone(), vec![existing.clone()], None, ts)
+            .await?;
+        assert_eq!(
+            dry,
+            LakeImportCounts {
+                staged: 3,
+                fresh: 1
+            }
+        );
+
+        let out = temp.path().join("out");
+        let counts = svc
+            .partition_import_points_for_lake(staged, vec![existing], Some(out.clone()), ts)
+            .await?;
+        assert_eq!(counts.fresh, 1);
+
+        let partition = out.join("date=2020-06-01").join("shard=01");
+        let files: Vec<_> = std::fs::read_dir(&partition)?.collect();
+        assert_eq!(files.len(), 1);
+        let conn = Connection::open_in_memory()?;
+        let path = escape_single_quotes(partition.join("*.parquet").display().to_string());
+        let (value, ts_out): (f64, NaiveDateTime) = conn.query_row(
+            &format!("SELECT value, ts FROM read_parquet('{path}')"),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(value, 2.0);
+        assert_eq!(ts_out, next.naive_utc());
+        Ok(())
+    }
 }
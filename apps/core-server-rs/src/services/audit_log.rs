@@ -0,0 +1,369 @@
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use serde_json::{Map, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+
+use crate::auth::AuthenticatedUser;
+
+/// `prev_hash` of the first row in the chain.
+pub(crate) const GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+/// Serializes writers so each row chains onto the one before it.
+const CHAIN_LOCK_KEY: i64 = 0x6175_6469_745f_6c67;
+const REDACTED: &str = "[redacted]";
+const SECRET_KEY_PARTS: [&str; 7] = [
+    "password",
+    "secret",
+    "token",
+    "api_key",
+    "private_key",
+    "site_key",
+    "credential",
+];
+
+/// Fields covered by a row's hash, in the form they are stored.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub(crate) struct AuditRecord {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor_id: String,
+    pub actor_email: String,
+    pub actor_source: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub before: Option<SqlJson<JsonValue>>,
+    pub after: Option<SqlJson<JsonValue>>,
+    pub changes: SqlJson<JsonValue>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+/// Replaces values under secret-looking keys so credentials never reach the log.
+pub(crate) fn redact(value: &JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Object(map) => JsonValue::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    // Flags such as `password_set` stay readable.
+                    let value = if is_secret_key(key) && !(value.is_null() || value.is_boolean()) {
+                        JsonValue::String(REDACTED.to_string())
+                    } else {
+                        redact(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        JsonValue::Array(items) => JsonValue::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Top-level fields that differ between `before` and `after`, as
+/// `{field: {"before": .., "after": ..}}`. Non-object values diff as a whole.
+pub(crate) fn diff(before: Option<&JsonValue>, after: Option<&JsonValue>) -> JsonValue {
+    let empty = Map::new();
+    match (before, after) {
+        (None | Some(JsonValue::Object(_)), None | Some(JsonValue::Object(_))) => {
+            let before = before.and_then(JsonValue::as_object).unwrap_or(&empty);
+            let after = after.and_then(JsonValue::as_object).unwrap_or(&empty);
+            let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            let mut changes = Map::new();
+            for key in keys {
+                let old = before.get(key).unwrap_or(&JsonValue::Null);
+                let new = after.get(key).unwrap_or(&JsonValue::Null);
+                if old != new {
+                    changes.insert(
+                        key.clone(),
+                        serde_json::json!({ "before": old, "after": new }),
+                    );
+                }
+            }
+            JsonValue::Object(changes)
+        }
+        (old, new) if old != new => serde_json::json!({
+            "value": {
+                "before": old.cloned().unwrap_or(JsonValue::Null),
+                "after": new.cloned().unwrap_or(JsonValue::Null),
+            }
+        }),
+        _ => JsonValue::Object(Map::new()),
+    }
+}
+
+/// JSON with object keys sorted at every level, so the hash does not depend on
+/// how Postgres or serde ordered them.
+fn write_canonical(value: &JsonValue, out: &mut String) {
+    match value {
+        JsonValue::Object(map) => {
+            let mut entries: Vec<(&String, &JsonValue)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&JsonValue::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        JsonValue::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+pub(crate) fn compute_hash(record: &AuditRecord) -> String {
+    let content = serde_json::json!({
+        "prev_hash": record.prev_hash,
+        "occurred_at": record.occurred_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+        "actor_id": record.actor_id,
+        "actor_email": record.actor_email,
+        "actor_source": record.actor_source,
+        "action": record.action,
+        "target_type": record.target_type,
+        "target_id": record.target_id,
+        "before": record.before.as_ref().map(|value| &value.0),
+        "after": record.after.as_ref().map(|value| &value.0),
+        "changes": record.changes.0,
+    });
+    let mut canonical = String::new();
+    write_canonical(&content, &mut canonical);
+    format!("{:x}", Sha256::digest(canonical.as_bytes()))
+}
+
+async fn append(db: &PgPool, mut record: AuditRecord) -> Result<i64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(CHAIN_LOCK_KEY)
+        .execute(&mut *tx)
+        .await?;
+    let prev_hash: Option<String> =
+        sqlx::query_scalar("SELECT hash FROM audit_log ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&mut *tx)
+            .await?;
+    record.prev_hash = prev_hash.unwrap_or_else(|| GENESIS_HASH.to_string());
+    record.hash = compute_hash(&record);
+    let id: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO audit_log (
+            occurred_at, actor_id, actor_email, actor_source, action, target_type, target_id,
+            before, after, changes, prev_hash, hash
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING id
+        "#,
+    )
+    .bind(record.occurred_at)
+    .bind(&record.actor_id)
+    .bind(&record.actor_email)
+    .bind(&record.actor_source)
+    .bind(&record.action)
+    .bind(&record.target_type)
+    .bind(&record.target_id)
+    .bind(&record.before)
+    .bind(&record.after)
+    .bind(&record.changes)
+    .bind(&record.prev_hash)
+    .bind(&record.hash)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Appends an audit entry for a change made by `actor`. `before` / `after` are the
+/// target's state around the change (`None` for create / delete); secrets are
+/// redacted and the field-level diff is stored alongside. Recording is
+/// best-effort: the change already happened, so a failed insert is logged rather
+/// than failing the request.
+pub(crate) async fn record(
+    db: &PgPool,
+    actor: &AuthenticatedUser,
+    action: &str,
+    target_type: &str,
+    target_id: Option<&str>,
+    before: Option<JsonValue>,
+    after: Option<JsonValue>,
+) {
+    let before = before.as_ref().map(redact);
+    let after = after.as_ref().map(redact);
+    let changes = diff(before.as_ref(), after.as_ref());
+    let record = AuditRecord {
+        id: 0,
+        // Postgres keeps microseconds; truncating first keeps the hash reproducible.
+        occurred_at: Utc::now().trunc_subsecs(6),
+        actor_id: actor.id.clone(),
+        actor_email: actor.email.clone(),
+        actor_source: actor.source.clone(),
+        action: action.to_string(),
+        target_type: target_type.to_string(),
+        target_id: target_id.map(str::to_string),
+        before: before.map(SqlJson),
+        after: after.map(SqlJson),
+        changes: SqlJson(changes),
+        prev_hash: String::new(),
+        hash: String::new(),
+    };
+    if let Err(err) = append(db, record).await {
+        tracing::error!(error = %err, action, target_type, target_id, "failed to write audit log");
+    }
+}
+
+/// Serializes a response or row for `before` / `after`.
+pub(crate) fn snapshot<T: serde::Serialize>(value: &T) -> Option<JsonValue> {
+    serde_json::to_value(value).ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ChainCheck {
+    pub checked: u64,
+    pub last_hash: String,
+    /// First row whose `prev_hash` or `hash` does not match.
+    pub broken_at: Option<i64>,
+}
+
+/// Walks rows in id order, continuing from `last_hash`, and stops at the first
+/// row that does not chain.
+pub(crate) fn verify_chain<'a>(
+    rows: impl IntoIterator<Item = &'a AuditRecord>,
+    mut last_hash: String,
+) -> ChainCheck {
+    let mut checked = 0;
+    for row in rows {
+        if row.prev_hash != last_hash || compute_hash(row) != row.hash {
+            return ChainCheck {
+                checked,
+                last_hash,
+                broken_at: Some(row.id),
+            };
+        }
+        checked += 1;
+        last_hash = row.hash.clone();
+    }
+    ChainCheck {
+        checked,
+        last_hash,
+        broken_at: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(id: i64, prev_hash: &str, after: JsonValue) -> AuditRecord {
+        let mut record = AuditRecord {
+            id,
+            occurred_at: Utc::now().trunc_subsecs(6),
+            actor_id: "user-1".to_string(),
+            actor_email: "ops@example.com".to_string(),
+            actor_source: "db".to_string(),
+            action: "sensor.update".to_string(),
+            target_type: "sensor".to_string(),
+            target_id: Some("s1".to_string()),
+            before: None,
+            after: Some(SqlJson(after)),
+            changes: SqlJson(json!({})),
+            prev_hash: prev_hash.to_string(),
+            hash: String::new(),
+        };
+        record.hash = compute_hash(&record);
+        record
+    }
+
+    #[test]
+    fn diff_lists_changed_fields_only() {
+        let before = json!({"name": "Pump", "interval": 30, "unit": "kW"});
+        let after = json!({"name": "Pump", "interval": 60, "enabled": true});
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            json!({
+                "enabled": {"before": null, "after": true},
+                "interval": {"before": 30, "after": 60},
+                "unit": {"before": "kW", "after": null},
+            })
+        );
+        assert_eq!(diff(Some(&before), Some(&before)), json!({}));
+        assert_eq!(
+            diff(Some(&json!("on")), Some(&json!("off"))),
+            json!({"value": {"before": "on", "after": "off"}})
+        );
+    }
+
+    #[test]
+    fn redacts_nested_secrets() {
+        let value = json!({
+            "name": "site",
+            "password": "hunter2",
+            "config": {"mqtt_password": "x", "api_key": null, "host": "h"},
+            "items": [{"access_token": "t"}],
+            "password_set": true,
+        });
+        assert_eq!(
+            redact(&value),
+            json!({
+                "name": "site",
+                "password": "[redacted]",
+                "config": {"mqtt_password": "[redacted]", "api_key": null, "host": "h"},
+                "items": [{"access_token": "[redacted]"}],
+                "password_set": true,
+            })
+        );
+    }
+
+    #[test]
+    fn hash_ignores_key_order() {
+        let a = entry(1, GENESIS_HASH, json!({"a": 1, "b": {"c": 2, "d": 3}}));
+        let mut b = entry(1, GENESIS_HASH, json!({"b": {"d": 3, "c": 2}, "a": 1}));
+        b.occurred_at = a.occurred_at;
+        b.hash = compute_hash(&b);
+        assert_eq!(a.hash, b.hash);
+    }
+
+    #[test]
+    fn verify_chain_detects_tampering() {
+        let first = entry(1, GENESIS_HASH, json!({"interval": 30}));
+        let second = entry(2, &first.hash, json!({"interval": 60}));
+        let third = entry(3, &second.hash, json!({"interval": 90}));
+        let rows = vec![first.clone(), second.clone(), third.clone()];
+
+        let check = verify_chain(&rows, GENESIS_HASH.to_string());
+        assert_eq!(check.checked, 3);
+        assert_eq!(check.broken_at, None);
+        assert_eq!(check.last_hash, third.hash);
+
+        let mut edited = rows.clone();
+        edited[1].after = Some(SqlJson(json!({"interval": 5})));
+        assert_eq!(
+            verify_chain(&edited, GENESIS_HASH.to_string()).broken_at,
+            Some(2)
+        );
+
+        let deleted = vec![first, third];
+        assert_eq!(
+            verify_chain(&deleted, GENESIS_HASH.to_string()).broken_at,
+            Some(3)
+        );
+    }
+}
@@ -0,0 +1,762 @@
+//! Scheduled test-restores of controller backups.
+//!
+//! A run takes the same full custom-format export `export_database` serves,
+//! restores it into a throwaway database, applies the latest node backup
+//! bundles there, and compares the result with the live database as of the
+//! export snapshot. Any failure fires the "Backup verification failed" alarm;
+//! the next passing run resolves it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::types::Json as SqlJson;
+use sqlx::{PgConnection, PgPool};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::routes::backups::scan_backup_root;
+use crate::routes::backups_exports::{parse_pg_connection, postgres_command, run_pg_dump_to_file};
+use crate::services::restore_worker::{apply_bundle_in_place, parse_bundle};
+use crate::state::AppState;
+
+const SCHEDULE_TICK: Duration = Duration::from_secs(600);
+const STALE_RUN_HOURS: i32 = 6;
+const TMP_DIR: &str = ".verify-tmp";
+const ALARM_NAME: &str = "Backup verification failed";
+const ALARM_ORIGIN: &str = "backup_verify";
+const ALARM_TARGET_KEY: &str = "backup_verification";
+/// Metrics rows in this window before the export must all come back.
+const METRICS_WINDOW_DAYS: i64 = 7;
+/// Tables whose row counts must match the export snapshot exactly.
+const COUNTED_TABLES: &[&str] = &[
+    "nodes",
+    "sensors",
+    "outputs",
+    "users",
+    "schedules",
+    "alarm_rules",
+    "alarms",
+    "node_groups",
+    "map_layers",
+    "map_features",
+    "setup_credentials",
+];
+/// Sensors fed by the controller itself; metric rows for them mean ingest
+/// crossed series (same audit as `ops_audit_sensor_series_integrity`).
+const NON_INGEST_SOURCES: &[&str] = &["derived", "forecast_points"];
+const MAX_LISTED: usize = 10;
+
+pub(crate) const CHECK_PASS: &str = "pass";
+pub(crate) const CHECK_FAIL: &str = "fail";
+pub(crate) const CHECK_SKIP: &str = "skip";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub(crate) struct VerificationCheck {
+    pub name: String,
+    /// `pass`, `fail` or `skip`.
+    pub status: String,
+    pub detail: String,
+}
+
+fn check(name: &str, passed: bool, detail: impl Into<String>) -> VerificationCheck {
+    VerificationCheck {
+        name: name.to_string(),
+        status: if passed { CHECK_PASS } else { CHECK_FAIL }.to_string(),
+        detail: detail.into(),
+    }
+}
+
+fn listed<T: std::fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+    let items: Vec<String> = items.into_iter().map(|item| item.to_string()).collect();
+    let mut out = items
+        .iter()
+        .take(MAX_LISTED)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if items.len() > MAX_LISTED {
+        out.push_str(&format!(" (+{} more)", items.len() - MAX_LISTED));
+    }
+    out
+}
+
+pub struct BackupVerificationService {
+    state: AppState,
+}
+
+impl BackupVerificationService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    pub fn start(self, cancel: CancellationToken) {
+        let interval_hours = self.state.config.backup_verify_interval_hours;
+        if interval_hours == 0 {
+            return;
+        }
+        let state = self.state;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(
+                tokio::time::Instant::now() + SCHEDULE_TICK,
+                SCHEDULE_TICK,
+            );
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if let Err(err) = run_if_due(&state, interval_hours).await {
+                            tracing::warn!("backup verification scheduler failed: {err:#}");
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn run_if_due(state: &AppState, interval_hours: u64) -> Result<()> {
+    let last: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT max(started_at) FROM backup_verification_runs")
+            .fetch_one(&state.db)
+            .await?;
+    let due =
+        last.is_none_or(|last| Utc::now() - last >= chrono::Duration::hours(interval_hours as i64));
+    if !due {
+        return Ok(());
+    }
+    if let Some(run_id) = start_run(&state.db, "schedule").await? {
+        execute_run(state, run_id).await;
+    }
+    Ok(())
+}
+
+/// Records a `running` row; `None` while another run is in flight.
+pub(crate) async fn start_run(db: &PgPool, trigger: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO backup_verification_runs (trigger, status)
+        SELECT $1, 'running'
+        WHERE NOT EXISTS (
+            SELECT 1 FROM backup_verification_runs
+            WHERE status = 'running'
+              AND started_at > now() - make_interval(hours => $2)
+        )
+        RETURNING id
+        "#,
+    )
+    .bind(trigger)
+    .bind(STALE_RUN_HOURS)
+    .fetch_optional(db)
+    .await
+}
+
+/// Runs the verification started with [`start_run`] and records the outcome.
+pub(crate) async fn execute_run(state: &AppState, run_id: Uuid) {
+    let mut checks = Vec::new();
+    let outcome = verify(state, &mut checks).await;
+    let failed: Vec<&str> = checks
+        .iter()
+        .filter(|check| check.status == CHECK_FAIL)
+        .map(|check| check.name.as_str())
+        .collect();
+    let (status, summary, dump_bytes) = match &outcome {
+        Err(err) => ("error", format!("{err:#}"), None),
+        Ok(bytes) if !failed.is_empty() => (
+            "fail",
+            format!("Failed checks: {}", failed.join(", ")),
+            Some(*bytes),
+        ),
+        Ok(bytes) => (
+            "pass",
+            format!("{} checks passed", checks.len()),
+            Some(*bytes),
+        ),
+    };
+
+    if let Err(err) = sqlx::query(
+        r#"
+        UPDATE backup_verification_runs
+        SET status = $2, summary = $3, dump_bytes = $4, checks = $5, finished_at = now()
+        WHERE id = $1
+        "#,
+    )
+    .bind(run_id)
+    .bind(status)
+    .bind(&summary)
+    .bind(dump_bytes)
+    .bind(SqlJson(&checks))
+    .execute(&state.db)
+    .await
+    {
+        tracing::warn!("failed to record backup verification result: {err:#}");
+    }
+
+    let alarm = if status == "pass" {
+        resolve_alarm(&state.db).await
+    } else {
+        tracing::warn!(run_id = %run_id, "backup verification {status}: {summary}");
+        raise_alarm(
+            &state.db,
+            &format!("Backup verification {status}: {summary}"),
+        )
+        .await
+    };
+    if let Err(err) = alarm {
+        tracing::warn!("failed to update backup verification alarm: {err:#}");
+    }
+}
+
+/// Live state at the export snapshot.
+struct Baseline {
+    columns: BTreeSet<(String, String)>,
+    counts: BTreeMap<String, i64>,
+    metrics_window: (DateTime<Utc>, DateTime<Utc>),
+    metrics_rows: i64,
+    timescaledb: bool,
+}
+
+async fn public_columns(conn: &mut PgConnection) -> Result<BTreeSet<(String, String)>> {
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT table_name::text, column_name::text
+        FROM information_schema.columns
+        WHERE table_schema = 'public'
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+async fn table_counts(conn: &mut PgConnection, tables: &[String]) -> Result<BTreeMap<String, i64>> {
+    let mut counts = BTreeMap::new();
+    for table in tables {
+        let count: i64 = sqlx::query_scalar(&format!("SELECT count(*) FROM public.\"{table}\""))
+            .fetch_one(&mut *conn)
+            .await?;
+        counts.insert(table.clone(), count);
+    }
+    Ok(counts)
+}
+
+async fn metrics_rows(
+    conn: &mut PgConnection,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+) -> Result<i64> {
+    Ok(
+        sqlx::query_scalar("SELECT count(*) FROM metrics WHERE ts >= $1 AND ts < $2")
+            .bind(start)
+            .bind(end)
+            .fetch_one(&mut *conn)
+            .await?,
+    )
+}
+
+/// Exports the database and captures the baseline from the same snapshot, so
+/// comparisons are exact even while the controller keeps writing.
+async fn export_with_baseline(state: &AppState, dump: &std::path::Path) -> Result<Baseline> {
+    let pg_conn =
+        parse_pg_connection(&state.config.database_url).map_err(|(_, msg)| anyhow!(msg))?;
+    let mut tx = state.db.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let snapshot: String = sqlx::query_scalar("SELECT pg_export_snapshot()")
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let columns = public_columns(&mut tx).await?;
+    let present: BTreeSet<&str> = columns.iter().map(|(table, _)| table.as_str()).collect();
+    let tables: Vec<String> = COUNTED_TABLES
+        .iter()
+        .filter(|table| present.contains(**table))
+        .map(|table| table.to_string())
+        .collect();
+    let counts = table_counts(&mut tx, &tables).await?;
+    let end: DateTime<Utc> = sqlx::query_scalar("SELECT now()")
+        .fetch_one(&mut *tx)
+        .await?;
+    let metrics_window = (end - chrono::Duration::days(METRICS_WINDOW_DAYS), end);
+    let metrics_rows = metrics_rows(&mut tx, metrics_window).await?;
+    let timescaledb: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let snapshot_arg = format!("--snapshot={snapshot}");
+    run_pg_dump_to_file(&pg_conn, None, &["--format=custom", &snapshot_arg], dump)
+        .await
+        .map_err(|(_, msg)| anyhow!(msg))?;
+    tx.rollback().await?;
+
+    Ok(Baseline {
+        columns,
+        counts,
+        metrics_window,
+        metrics_rows,
+        timescaledb,
+    })
+}
+
+fn scratch_database_name(live: &str) -> String {
+    let suffix = format!("_verify_{}", &Uuid::new_v4().simple().to_string()[..8]);
+    let keep = 63usize.saturating_sub(suffix.len());
+    let base: String = live
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .take(keep)
+        .collect();
+    format!("{base}{suffix}")
+}
+
+/// Runs every check, appending to `checks`; returns the export size. Errors
+/// mean the verification itself could not complete.
+async fn verify(state: &AppState, checks: &mut Vec<VerificationCheck>) -> Result<i64> {
+    let root = state.config.backup_storage_path.join(TMP_DIR);
+    tokio::fs::create_dir_all(&root)
+        .await
+        .with_context(|| format!("failed to create {}", root.display()))?;
+    let scratch_dir = tempfile::Builder::new()
+        .prefix("verify-")
+        .tempdir_in(&root)?;
+    let dump = scratch_dir.path().join("database.dump");
+
+    let baseline = export_with_baseline(state, &dump).await?;
+    let dump_bytes = tokio::fs::metadata(&dump).await?.len() as i64;
+    checks.push(check(
+        "database_export",
+        dump_bytes > 0,
+        format!("pg_dump custom export, {dump_bytes} bytes"),
+    ));
+
+    let pg_conn =
+        parse_pg_connection(&state.config.database_url).map_err(|(_, msg)| anyhow!(msg))?;
+    let scratch_name = scratch_database_name(&pg_conn.dbname);
+    sqlx::query(&format!("CREATE DATABASE \"{scratch_name}\""))
+        .execute(&state.db)
+        .await
+        .context("failed to create scratch database (the controller role needs CREATEDB)")?;
+
+    let outcome = async {
+        let options =
+            PgConnectOptions::from_str(&state.config.database_url)?.database(&scratch_name);
+        let scratch = PgPoolOptions::new()
+            .max_connections(2)
+            .connect_with(options)
+            .await
+            .context("failed to connect to scratch database")?;
+        let result = check_restore(state, &baseline, &scratch, &scratch_name, &dump, checks).await;
+        scratch.close().await;
+        result
+    }
+    .await;
+
+    if let Err(err) = sqlx::query(&format!(
+        "DROP DATABASE IF EXISTS \"{scratch_name}\" WITH (FORCE)"
+    ))
+    .execute(&state.db)
+    .await
+    {
+        tracing::warn!(database = %scratch_name, "failed to drop scratch database: {err:#}");
+    }
+    outcome.map(|_| dump_bytes)
+}
+
+async fn check_restore(
+    state: &AppState,
+    baseline: &Baseline,
+    scratch: &PgPool,
+    scratch_name: &str,
+    dump: &std::path::Path,
+    checks: &mut Vec<VerificationCheck>,
+) -> Result<()> {
+    let pg_conn =
+        parse_pg_connection(&state.config.database_url).map_err(|(_, msg)| anyhow!(msg))?;
+    if baseline.timescaledb {
+        sqlx::query("CREATE EXTENSION IF NOT EXISTS timescaledb")
+            .execute(scratch)
+            .await?;
+        sqlx::query("SELECT timescaledb_pre_restore()")
+            .execute(scratch)
+            .await?;
+    }
+    let output = postgres_command("pg_restore", &pg_conn)
+        .args(["--no-owner", "--no-acl", "--dbname", scratch_name])
+        .arg(dump)
+        .output()
+        .await
+        .context("failed to run pg_restore")?;
+    if baseline.timescaledb {
+        sqlx::query("SELECT timescaledb_post_restore()")
+            .execute(scratch)
+            .await?;
+    }
+    let restored = output.status.success();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    checks.push(check(
+        "database_restore",
+        restored,
+        if restored {
+            format!("restored into scratch database {scratch_name}")
+        } else {
+            format!(
+                "pg_restore failed: {}",
+                stderr.lines().last().unwrap_or("unknown error")
+            )
+        },
+    ));
+    if !restored {
+        return Ok(());
+    }
+
+    let mut conn = scratch.acquire().await?;
+
+    let columns = public_columns(&mut conn).await?;
+    let missing: Vec<String> = baseline
+        .columns
+        .difference(&columns)
+        .map(|(table, column)| format!("{table}.{column}"))
+        .collect();
+    let tables = baseline
+        .columns
+        .iter()
+        .map(|(table, _)| table)
+        .collect::<BTreeSet<_>>()
+        .len();
+    checks.push(check(
+        "schema",
+        missing.is_empty(),
+        if missing.is_empty() {
+            format!(
+                "{tables} tables and {} columns match",
+                baseline.columns.len()
+            )
+        } else {
+            format!("missing after restore: {}", listed(missing))
+        },
+    ));
+
+    let tables: Vec<String> = baseline.counts.keys().cloned().collect();
+    let counts = table_counts(&mut conn, &tables).await?;
+    let mismatched: Vec<String> = baseline
+        .counts
+        .iter()
+        .filter(|(table, expected)| counts.get(*table) != Some(expected))
+        .map(|(table, expected)| {
+            format!(
+                "{table} {} of {expected}",
+                counts.get(table).copied().unwrap_or(0)
+            )
+        })
+        .collect();
+    checks.push(check(
+        "row_counts",
+        mismatched.is_empty(),
+        if mismatched.is_empty() {
+            format!("{} tables match: {}", tables.len(), listed(&tables))
+        } else {
+            format!("row count mismatch: {}", listed(mismatched))
+        },
+    ));
+
+    let restored_rows = metrics_rows(&mut conn, baseline.metrics_window).await?;
+    checks.push(check(
+        "metrics_window",
+        restored_rows == baseline.metrics_rows,
+        format!(
+            "{restored_rows} of {} metric rows from the last {METRICS_WINDOW_DAYS} days",
+            baseline.metrics_rows
+        ),
+    ));
+
+    let orphan_sensors: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT s.sensor_id::text
+        FROM sensors s
+        WHERE NOT EXISTS (SELECT 1 FROM nodes n WHERE n.id = s.node_id)
+        ORDER BY s.sensor_id
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let orphan_series: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT m.sensor_id::text
+        FROM metrics m
+        WHERE NOT EXISTS (SELECT 1 FROM sensors s WHERE s.sensor_id = m.sensor_id)
+        ORDER BY 1
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    checks.push(check(
+        "sensor_references",
+        orphan_sensors.is_empty() && orphan_series.is_empty(),
+        if orphan_sensors.is_empty() && orphan_series.is_empty() {
+            "every sensor has a node and every metric series has a sensor".to_string()
+        } else {
+            format!(
+                "sensors without node: [{}]; metric series without sensor: [{}]",
+                listed(orphan_sensors),
+                listed(orphan_series)
+            )
+        },
+    ));
+
+    let crossed: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT s.sensor_id::text, COALESCE(s.config, '{}'::jsonb)->>'source'
+        FROM sensors s
+        WHERE COALESCE(s.config, '{}'::jsonb)->>'source' = ANY($1)
+          AND EXISTS (SELECT 1 FROM metrics m WHERE m.sensor_id = s.sensor_id)
+        ORDER BY 1
+        "#,
+    )
+    .bind(NON_INGEST_SOURCES)
+    .fetch_all(&mut *conn)
+    .await?;
+    checks.push(check(
+        "sensor_series_integrity",
+        crossed.is_empty(),
+        if crossed.is_empty() {
+            "no derived or forecast sensors carry ingested metrics".to_string()
+        } else {
+            format!(
+                "ingested metrics on non-ingest sensors: {}",
+                listed(
+                    crossed
+                        .iter()
+                        .map(|(id, source)| format!("{id} ({source})"))
+                )
+            )
+        },
+    ));
+    drop(conn);
+
+    checks.push(check_node_bundles(state, scratch).await?);
+    Ok(())
+}
+
+/// Applies the newest bundle of each node to the scratch copy, exactly as the
+/// restore worker would (minus pushing config to the node agent).
+async fn check_node_bundles(state: &AppState, scratch: &PgPool) -> Result<VerificationCheck> {
+    let root = state.config.backup_storage_path.clone();
+    let summaries = tokio::task::spawn_blocking(move || scan_backup_root(&root)).await??;
+    if summaries.is_empty() {
+        return Ok(VerificationCheck {
+            name: "node_bundles".to_string(),
+            status: CHECK_SKIP.to_string(),
+            detail: "no node backups on disk".to_string(),
+        });
+    }
+
+    let (mut restored, mut sensors, mut skipped) = (0usize, 0usize, 0usize);
+    let mut failures = Vec::new();
+    for summary in summaries {
+        let Some(latest) = summary.backups.first() else {
+            continue;
+        };
+        let Ok(node_id) = Uuid::parse_str(&summary.node_id) else {
+            skipped += 1;
+            continue;
+        };
+        let live_node: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM nodes
+                WHERE id = $1 AND NOT (COALESCE(config, '{}'::jsonb) @> '{"deleted": true}')
+            )
+            "#,
+        )
+        .bind(node_id)
+        .fetch_one(scratch)
+        .await?;
+        if !live_node {
+            skipped += 1;
+            continue;
+        }
+        let outcome = async {
+            let date = NaiveDate::parse_from_str(&latest.date, "%Y-%m-%d")?;
+            let bytes =
+                tokio::fs::read(state.config.backup_storage_path.join(&latest.path)).await?;
+            let bundle = parse_bundle(&bytes, node_id)?;
+            apply_bundle_in_place(scratch, node_id, date, &bundle).await
+        }
+        .await;
+        match outcome {
+            Ok(count) => {
+                restored += 1;
+                sensors += count;
+            }
+            Err(err) => failures.push(format!("{}: {err:#}", latest.path)),
+        }
+    }
+
+    let detail = format!(
+        "{restored} bundles restored ({sensors} sensors), {skipped} skipped for removed nodes"
+    );
+    Ok(if failures.is_empty() {
+        check("node_bundles", true, detail)
+    } else {
+        check(
+            "node_bundles",
+            false,
+            format!("{detail}; failed: {}", listed(failures)),
+        )
+    })
+}
+
+async fn raise_alarm(db: &PgPool, message: &str) -> Result<()> {
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+    let rule = serde_json::json!({ "type": "backup_verification", "severity": "critical" });
+    let existing: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM alarms
+        WHERE name = $1 AND origin = $2
+        LIMIT 1
+        "#,
+    )
+    .bind(ALARM_NAME)
+    .bind(ALARM_ORIGIN)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let alarm_id = if let Some(alarm_id) = existing {
+        sqlx::query(
+            r#"
+            UPDATE alarms SET status = 'firing', last_fired = NOW(), resolved_at = NULL
+            WHERE id = $1
+            "#,
+        )
+        .bind(alarm_id)
+        .execute(&mut *tx)
+        .await?;
+        alarm_id
+    } else {
+        sqlx::query_scalar(
+            r#"
+            INSERT INTO alarms (name, rule, status, origin, last_fired)
+            VALUES ($1, $2, 'firing', $3, NOW())
+            RETURNING id
+            "#,
+        )
+        .bind(ALARM_NAME)
+        .bind(&rule)
+        .bind(ALARM_ORIGIN)
+        .fetch_one(&mut *tx)
+        .await?
+    };
+    record_alarm_event(&mut tx, now, alarm_id, "firing", "fired", message).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn resolve_alarm(db: &PgPool) -> Result<()> {
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+    let firing: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM alarms
+        WHERE name = $1 AND origin = $2 AND status = 'firing'
+        LIMIT 1
+        "#,
+    )
+    .bind(ALARM_NAME)
+    .bind(ALARM_ORIGIN)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(alarm_id) = firing else {
+        tx.commit().await?;
+        return Ok(());
+    };
+    sqlx::query("UPDATE alarms SET status = 'ok', resolved_at = $2 WHERE id = $1")
+        .bind(alarm_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+    record_alarm_event(
+        &mut tx,
+        now,
+        alarm_id,
+        "ok",
+        "resolved",
+        "Backup verification passed",
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn record_alarm_event(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    now: DateTime<Utc>,
+    alarm_id: i64,
+    status: &str,
+    transition: &str,
+    message: &str,
+) -> Result<()> {
+    let incident_id = crate::services::incidents::get_or_create_incident(
+        tx,
+        now,
+        &crate::services::incidents::IncidentKey {
+            rule_id: None,
+            target_key: Some(ALARM_TARGET_KEY.to_string()),
+        },
+        "critical",
+        ALARM_NAME,
+        transition,
+    )
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO alarm_events (
+            alarm_id,
+            status,
+            message,
+            origin,
+            transition,
+            incident_id,
+            target_key
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(alarm_id)
+    .bind(status)
+    .bind(message)
+    .bind(ALARM_ORIGIN)
+    .bind(transition)
+    .bind(incident_id)
+    .bind(ALARM_TARGET_KEY)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scratch_names_fit_postgres_identifiers() {
+        let name = scratch_database_name(&"farm".repeat(30));
+        assert!(name.len() <= 63);
+        assert!(name.contains("_verify_"));
+        let name = scratch_database_name("farm-dashboard");
+        assert!(name.starts_with("farmdashboard_verify_"));
+    }
+
+    #[test]
+    fn long_lists_are_truncated() {
+        let items: Vec<u32> = (0..25).collect();
+        let text = listed(&items);
+        assert!(text.starts_with("0, 1, 2"));
+        assert!(text.ends_with("(+15 more)"));
+        assert_eq!(listed(["a", "b"]), "a, b");
+    }
+}
@@ -743,6 +743,8 @@ fn cloud_sync_service_user() -> AuthenticatedUser {
         role: "admin".to_string(),
         capabilities,
         source: "cloud-sync".to_string(),
+        session_id: None,
+        scopes: Default::default(),
     }
 }
 
@@ -0,0 +1,439 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::device_catalog::{self, DeviceModel, DevicePoint, DeviceVendor};
+use crate::services::external_devices::{
+    is_modbus_protocol, validate_point_semantics, SUPPORTED_PROTOCOLS,
+};
+
+#[derive(sqlx::FromRow)]
+pub struct DeviceProfileRow {
+    pub id: Uuid,
+    pub vendor_id: String,
+    pub vendor_name: String,
+    pub model_id: String,
+    pub model_name: String,
+    pub since_year: Option<i32>,
+    pub protocols: SqlJson<Vec<String>>,
+    pub points: SqlJson<Vec<DevicePoint>>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A profile as submitted by a user or read from an import file. Points stay raw JSON
+/// until they pass the schema so every problem can be reported at once.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeviceProfileInput {
+    pub vendor_id: String,
+    pub vendor_name: String,
+    pub model_id: String,
+    pub model_name: String,
+    pub since_year: Option<u32>,
+    pub protocols: Vec<String>,
+    #[schema(value_type = Vec<DevicePoint>)]
+    pub points: Vec<JsonValue>,
+}
+
+pub struct ValidatedProfile {
+    pub vendor_id: String,
+    pub vendor_name: String,
+    pub model: DeviceModel,
+}
+
+fn is_valid_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 64
+        && value
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_')
+}
+
+/// Validates ids, protocols and every point (JSON schema plus protocol-specific
+/// checks). Errors name the offending field, e.g. `points[2]/register: ...`.
+pub fn validate_profile(input: &DeviceProfileInput) -> Result<ValidatedProfile, Vec<String>> {
+    let mut errors = Vec::new();
+    let vendor_id = input.vendor_id.trim().to_string();
+    let model_id = input.model_id.trim().to_string();
+    if !is_valid_id(&vendor_id) {
+        errors.push("vendor_id must be 1-64 lowercase letters, digits or underscores".to_string());
+    }
+    if !is_valid_id(&model_id) {
+        errors.push("model_id must be 1-64 lowercase letters, digits or underscores".to_string());
+    }
+    if input.vendor_name.trim().is_empty() {
+        errors.push("vendor_name is required".to_string());
+    }
+    if input.model_name.trim().is_empty() {
+        errors.push("model_name is required".to_string());
+    }
+    if device_catalog::is_builtin_model(&vendor_id, &model_id) {
+        errors.push(format!(
+            "{vendor_id}/{model_id} is a built-in model and cannot be overridden"
+        ));
+    }
+    if input.protocols.is_empty() {
+        errors.push("at least one protocol is required".to_string());
+    }
+    for protocol in &input.protocols {
+        if !SUPPORTED_PROTOCOLS.contains(&protocol.as_str()) {
+            errors.push(format!("unsupported protocol {protocol}"));
+        }
+    }
+
+    let mut points = Vec::new();
+    let mut metrics = HashSet::new();
+    for (index, raw) in input.points.iter().enumerate() {
+        let schema_errors = device_catalog::validate_point_json(raw);
+        if !schema_errors.is_empty() {
+            errors.extend(schema_errors.into_iter().map(|error| {
+                if error.starts_with('/') {
+                    format!("points[{index}]{error}")
+                } else {
+                    format!("points[{index}]: {error}")
+                }
+            }));
+            continue;
+        }
+        let point: DevicePoint = match serde_json::from_value(raw.clone()) {
+            Ok(point) => point,
+            Err(err) => {
+                errors.push(format!("points[{index}]: {err}"));
+                continue;
+            }
+        };
+        let protocol_listed = input.protocols.iter().any(|protocol| {
+            protocol == &point.protocol
+                || (is_modbus_protocol(protocol) && is_modbus_protocol(&point.protocol))
+        });
+        if !protocol_listed {
+            errors.push(format!(
+                "points[{index}]/protocol: {} is not one of the model's protocols",
+                point.protocol
+            ));
+        }
+        if let Err(err) = validate_point_semantics(&point) {
+            errors.push(format!("points[{index}]: {err}"));
+        }
+        if !metrics.insert(point.metric.clone()) {
+            errors.push(format!(
+                "points[{index}]/metric: duplicate metric {}",
+                point.metric
+            ));
+        }
+        points.push(point);
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(ValidatedProfile {
+        vendor_id,
+        vendor_name: input.vendor_name.trim().to_string(),
+        model: DeviceModel {
+            id: model_id,
+            name: input.model_name.trim().to_string(),
+            since_year: input.since_year,
+            protocols: input.protocols.clone(),
+            points,
+        },
+    })
+}
+
+/// Flattens a catalog-shaped import file into profile inputs.
+pub fn inputs_from_catalog_file(file: &JsonValue) -> Result<Vec<DeviceProfileInput>, String> {
+    let vendors = file
+        .get("vendors")
+        .and_then(JsonValue::as_array)
+        .ok_or_else(|| "profile file must contain a vendors array".to_string())?;
+    let mut inputs = Vec::new();
+    for (vendor_index, vendor) in vendors.iter().enumerate() {
+        let text = |value: &JsonValue, key: &str| {
+            value
+                .get(key)
+                .and_then(JsonValue::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let models = vendor
+            .get("models")
+            .and_then(JsonValue::as_array)
+            .ok_or_else(|| format!("vendors[{vendor_index}] must contain a models array"))?;
+        for model in models {
+            inputs.push(DeviceProfileInput {
+                vendor_id: text(vendor, "id"),
+                vendor_name: text(vendor, "name"),
+                model_id: text(model, "id"),
+                model_name: text(model, "name"),
+                since_year: model
+                    .get("since_year")
+                    .and_then(JsonValue::as_u64)
+                    .map(|year| year as u32),
+                protocols: model
+                    .get("protocols")
+                    .and_then(JsonValue::as_array)
+                    .map(|protocols| {
+                        protocols
+                            .iter()
+                            .filter_map(|protocol| protocol.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                points: model
+                    .get("points")
+                    .and_then(JsonValue::as_array)
+                    .cloned()
+                    .unwrap_or_default(),
+            });
+        }
+    }
+    Ok(inputs)
+}
+
+pub fn vendors_from_rows(rows: &[DeviceProfileRow]) -> Vec<DeviceVendor> {
+    let mut vendors: Vec<DeviceVendor> = Vec::new();
+    for row in rows {
+        let model = DeviceModel {
+            id: row.model_id.clone(),
+            name: row.model_name.clone(),
+            since_year: row.since_year.map(|year| year.max(0) as u32),
+            protocols: row.protocols.0.clone(),
+            points: row.points.0.clone(),
+        };
+        match vendors.iter_mut().find(|vendor| vendor.id == row.vendor_id) {
+            Some(vendor) => vendor.models.push(model),
+            None => vendors.push(DeviceVendor {
+                id: row.vendor_id.clone(),
+                name: row.vendor_name.clone(),
+                models: vec![model],
+            }),
+        }
+    }
+    vendors
+}
+
+pub async fn list_profiles(db: &PgPool) -> Result<Vec<DeviceProfileRow>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT id, vendor_id, vendor_name, model_id, model_name, since_year,
+               protocols, points, created_by, created_at, updated_at
+        FROM device_profiles
+        ORDER BY vendor_id, model_id
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Refreshes the in-memory catalog from the `device_profiles` table.
+pub async fn reload_catalog(db: &PgPool) -> Result<(), sqlx::Error> {
+    let rows = list_profiles(db).await?;
+    device_catalog::set_user_vendors(vendors_from_rows(&rows));
+    Ok(())
+}
+
+pub async fn upsert_profile<'e, E>(
+    executor: E,
+    profile: &ValidatedProfile,
+    created_by: Option<&str>,
+) -> Result<DeviceProfileRow, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_as(
+        r#"
+        INSERT INTO device_profiles (
+            vendor_id, vendor_name, model_id, model_name, since_year, protocols, points, created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (vendor_id, model_id)
+        DO UPDATE SET
+            vendor_name = EXCLUDED.vendor_name,
+            model_name = EXCLUDED.model_name,
+            since_year = EXCLUDED.since_year,
+            protocols = EXCLUDED.protocols,
+            points = EXCLUDED.points,
+            updated_at = now()
+        RETURNING id, vendor_id, vendor_name, model_id, model_name, since_year,
+                  protocols, points, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(&profile.vendor_id)
+    .bind(&profile.vendor_name)
+    .bind(&profile.model.id)
+    .bind(&profile.model.name)
+    .bind(profile.model.since_year.map(|year| year as i32))
+    .bind(SqlJson(&profile.model.protocols))
+    .bind(SqlJson(&profile.model.points))
+    .bind(created_by)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn update_profile(
+    db: &PgPool,
+    id: Uuid,
+    profile: &ValidatedProfile,
+) -> Result<DeviceProfileRow, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        UPDATE device_profiles
+        SET vendor_id = $2,
+            vendor_name = $3,
+            model_id = $4,
+            model_name = $5,
+            since_year = $6,
+            protocols = $7,
+            points = $8,
+            updated_at = now()
+        WHERE id = $1
+        RETURNING id, vendor_id, vendor_name, model_id, model_name, since_year,
+                  protocols, points, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(id)
+    .bind(&profile.vendor_id)
+    .bind(&profile.vendor_name)
+    .bind(&profile.model.id)
+    .bind(&profile.model.name)
+    .bind(profile.model.since_year.map(|year| year as i32))
+    .bind(SqlJson(&profile.model.protocols))
+    .bind(SqlJson(&profile.model.points))
+    .fetch_one(db)
+    .await
+}
+
+/// Number of external devices configured with the profile's vendor/model.
+/// `(vendor_id, model_id)` of a stored profile; configured devices reference it by these.
+pub async fn profile_identity(
+    db: &PgPool,
+    id: Uuid,
+) -> Result<Option<(String, String)>, sqlx::Error> {
+    sqlx::query_as("SELECT vendor_id, model_id FROM device_profiles WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn profile_usage(db: &PgPool, id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM nodes n
+        JOIN device_profiles p ON p.id = $1
+        WHERE n.external_provider IS NOT NULL
+          AND n.config->'external_device'->>'vendor_id' = p.vendor_id
+          AND n.config->'external_device'->>'model_id' = p.model_id
+        "#,
+    )
+    .bind(id)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn delete_profile(db: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM device_profiles WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn input(points: Vec<JsonValue>) -> DeviceProfileInput {
+        DeviceProfileInput {
+            vendor_id: "acme".to_string(),
+            vendor_name: "Acme".to_string(),
+            model_id: "pm100".to_string(),
+            model_name: "PM100".to_string(),
+            since_year: Some(2021),
+            protocols: vec!["modbus_tcp".to_string()],
+            points,
+        }
+    }
+
+    #[test]
+    fn validate_profile_accepts_well_formed_points() {
+        let profile = validate_profile(&input(vec![json!({
+            "name": "Voltage L1",
+            "metric": "voltage_l1",
+            "sensor_type": "voltage",
+            "unit": "V",
+            "protocol": "modbus_rtu",
+            "register": 30001,
+            "data_type": "f32_ws",
+            "scale": 0.1,
+        })]))
+        .expect("valid profile");
+        assert_eq!(profile.model.points.len(), 1);
+        assert_eq!(profile.model.points[0].scale, Some(0.1));
+    }
+
+    #[test]
+    fn validate_profile_reports_every_problem() {
+        let mut bad = input(vec![
+            json!({
+                "name": "Voltage",
+                "metric": "voltage",
+                "sensor_type": "voltage",
+                "unit": "V",
+                "protocol": "modbus_tcp",
+                "register": 40001,
+                "data_type": "f48",
+            }),
+            json!({
+                "name": "Voltage again",
+                "metric": "voltage",
+                "sensor_type": "voltage",
+                "unit": "V",
+                "protocol": "modbus_tcp",
+                "register": 40003,
+            }),
+            json!({
+                "name": "Uptime",
+                "metric": "uptime",
+                "sensor_type": "generic",
+                "unit": "s",
+                "protocol": "snmp",
+            }),
+        ]);
+        bad.model_id = "PM 100".to_string();
+
+        let errors = validate_profile(&bad).err().expect("invalid profile");
+        assert!(errors.iter().any(|error| error.starts_with("model_id")));
+        assert!(errors.iter().any(|error| error.starts_with("points[0]:")));
+        assert!(errors
+            .iter()
+            .any(|error| error.starts_with("points[1]/metric: duplicate")));
+        assert!(errors.iter().any(|error| error.starts_with("points[2]")));
+    }
+
+    #[test]
+    fn catalog_files_flatten_into_inputs() {
+        let file = json!({
+            "version": 1,
+            "vendors": [{
+                "id": "acme",
+                "name": "Acme",
+                "models": [
+                    {"id": "pm100", "name": "PM100", "protocols": ["modbus_tcp"], "points": []},
+                    {"id": "pm200", "name": "PM200", "since_year": 2024, "protocols": ["snmp"], "points": []}
+                ]
+            }]
+        });
+        let inputs = inputs_from_catalog_file(&file).unwrap();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[1].vendor_id, "acme");
+        assert_eq!(inputs[1].since_year, Some(2024));
+        assert!(inputs_from_catalog_file(&json!({"models": []})).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use super::alarm_engine::edge;
+use super::mqtt::MqttPublisher;
+
+/// Publishes each node's edge alarm rule bundle, retained, on `iot/<node>/edge-rules`.
+/// node-forwarder evaluates the bundle only while it cannot reach any MQTT upstream.
+#[derive(Debug, Clone)]
+pub struct EdgeRulesPublisher {
+    pool: PgPool,
+    mqtt: Arc<MqttPublisher>,
+    poll_interval: Duration,
+}
+
+impl EdgeRulesPublisher {
+    pub fn new(pool: PgPool, mqtt: Arc<MqttPublisher>, poll_interval_seconds: u64) -> Self {
+        Self {
+            pool,
+            mqtt,
+            poll_interval: Duration::from_secs(poll_interval_seconds.max(10)),
+        }
+    }
+
+    pub fn start(self, cancel: CancellationToken) {
+        tokio::spawn(async move {
+            // Versions published by this process. Empty after a restart, so every node gets its
+            // bundle once again.
+            let mut published: HashMap<String, String> = HashMap::new();
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = interval.tick() => {
+                        if let Err(err) = self.tick(&mut published).await {
+                            tracing::warn!(error = %err, "edge rule publish failed");
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    async fn tick(&self, published: &mut HashMap<String, String>) -> Result<()> {
+        let bundles = edge::compile_bundles(&self.pool).await?;
+        for (node_mqtt_id, bundle) in bundles {
+            if published.get(&node_mqtt_id) == Some(&bundle.version) {
+                continue;
+            }
+            let topic = format!("iot/{node_mqtt_id}/edge-rules");
+            self.mqtt
+                .publish_retained_json(&topic, &serde_json::to_value(&bundle)?)
+                .await?;
+            tracing::info!(
+                node = %node_mqtt_id,
+                version = %bundle.version,
+                rules = bundle.rules.len(),
+                "published edge alarm rules"
+            );
+            published.insert(node_mqtt_id, bundle.version);
+        }
+        Ok(())
+    }
+}
@@ -11,12 +11,11 @@ use serde_json::{json, Value as JsonValue};
 use sqlx::types::Json as SqlJson;
 use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tokio::time::timeout;
-use tokio_modbus::prelude::Reader;
 use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
 use tokio_rustls::rustls::{ClientConfig, RootCertStore};
 use tokio_rustls::TlsConnector;
@@ -25,11 +24,26 @@ use tracing::warn;
 use url::Url;
 use uuid::Uuid;
 
+mod modbus;
+mod mqtt;
+mod opcua;
+
+pub use self::mqtt::{
+    discovered_devices as discovered_mqtt_devices,
+    publish_output_command as publish_mqtt_output_command, MqttDeviceIngestService,
+    MqttDiscoveredDevice, MqttDiscoveredEntity, MqttOutputConfig,
+};
+
+pub use self::opcua::{
+    discover_endpoints as discover_opcua_endpoints, endpoint_url as opcua_endpoint_url,
+    OpcuaEndpointSummary,
+};
+
 use crate::device_catalog::{find_model, DeviceModel, DevicePoint};
 use crate::ids;
 use crate::state::AppState;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct ExternalDeviceConfig {
     pub vendor_id: String,
     pub model_id: String,
@@ -68,6 +82,48 @@ pub struct ExternalDeviceConfig {
     pub bacnet_bbmd_port: Option<u16>,
     #[serde(default)]
     pub bacnet_foreign_ttl_seconds: Option<u16>,
+    #[serde(default)]
+    pub modbus_request_delay_ms: Option<u64>,
+    #[serde(default)]
+    pub modbus_max_block_registers: Option<u16>,
+    #[serde(default)]
+    pub modbus_max_register_gap: Option<u16>,
+    #[serde(default)]
+    pub serial_port: Option<String>,
+    #[serde(default)]
+    pub serial_baud_rate: Option<u32>,
+    #[serde(default)]
+    pub serial_parity: Option<String>,
+    #[serde(default)]
+    pub serial_stop_bits: Option<u8>,
+    #[serde(default)]
+    pub serial_data_bits: Option<u8>,
+    #[serde(default)]
+    pub opcua_endpoint_url: Option<String>,
+    #[serde(default)]
+    pub opcua_security_policy: Option<String>,
+    #[serde(default)]
+    pub opcua_security_mode: Option<String>,
+    #[serde(default)]
+    pub opcua_username: Option<String>,
+    #[serde(default)]
+    pub opcua_password: Option<String>,
+    #[serde(default)]
+    pub opcua_client_cert_pem: Option<String>,
+    #[serde(default)]
+    pub opcua_client_key_pem: Option<String>,
+    #[serde(default)]
+    pub opcua_trust_server_cert: Option<bool>,
+    #[serde(default)]
+    pub opcua_publishing_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub opcua_browse_root: Option<String>,
+    #[serde(default)]
+    pub mqtt_base_topic: Option<String>,
+    #[serde(default)]
+    pub mqtt_device_identifier: Option<String>,
+    #[serde(default)]
+    pub mqtt_outputs: Option<Vec<MqttOutputConfig>>,
 }
 
 #[derive(sqlx::FromRow)]
@@ -93,6 +149,7 @@ impl ExternalDeviceService {
     pub fn start(self, cancel: CancellationToken) {
         let state = self.state.clone();
         let interval = self.interval;
+        MqttDeviceIngestService::new(state.clone()).start(cancel.clone());
         tokio::spawn(async move {
             let mut ticker = tokio::time::interval(interval);
             loop {
@@ -120,21 +177,186 @@ pub async fn poll_all_devices(state: &AppState) -> Result<()> {
     .fetch_all(&state.db)
     .await
     .context("failed to query external devices")?;
+    if let Err(err) = crate::services::device_profiles::reload_catalog(&state.db).await {
+        warn!("failed to refresh user device profiles: {err:#}");
+    }
 
+    modbus::retain_sessions(
+        &devices
+            .iter()
+            .filter_map(|device| {
+                parse_external_device_config(&device.config.0)
+                    .filter(|config| is_modbus_protocol(&config.protocol))
+                    .map(|config| modbus::session_key(device.id, &config))
+            })
+            .collect(),
+    );
+    let device_ids = devices.iter().map(|device| device.id).collect();
+    opcua::retain_subscriptions(&device_ids);
+    mqtt::retain_devices(&device_ids);
+    let lanes = schedule_poll_lanes(devices);
+
+    // Modbus devices sharing a bus (serial line or RTU gateway) are polled one after
+    // another while separate buses run concurrently. Every other protocol keeps to a
+    // single sequential lane.
+    futures::stream::iter(lanes.into_values())
+        .for_each_concurrent(MAX_CONCURRENT_POLL_LANES, |lane| async move {
+            for device in lane {
+                if let Err(err) = poll_device(state, &device).await {
+                    warn!(
+                        node_id = %device.id,
+                        node_name = %device.name,
+                        error = %err,
+                        "external device poll failed"
+                    );
+                }
+            }
+        })
+        .await;
+
+    Ok(())
+}
+
+const MAX_CONCURRENT_POLL_LANES: usize = 8;
+const SEQUENTIAL_POLL_LANE: &str = "sequential";
+
+fn schedule_poll_lanes(devices: Vec<ExternalDeviceRow>) -> HashMap<String, Vec<ExternalDeviceRow>> {
+    let mut lanes: HashMap<String, Vec<ExternalDeviceRow>> = HashMap::new();
     for device in devices {
-        if let Err(err) = poll_device(state, &device).await {
-            warn!(
-                node_id = %device.id,
-                node_name = %device.name,
-                error = %err,
-                "external device poll failed"
-            );
-        }
+        let key = match parse_external_device_config(&device.config.0) {
+            Some(config) if is_modbus_protocol(&config.protocol) => {
+                modbus::session_key(device.id, &config)
+            }
+            _ => SEQUENTIAL_POLL_LANE.to_string(),
+        };
+        lanes.entry(key).or_default().push(device);
     }
+    lanes
+}
 
+pub fn is_modbus_protocol(protocol: &str) -> bool {
+    matches!(protocol, "modbus_tcp" | "modbus_rtu" | "modbus_rtu_over_tcp")
+}
+
+/// Modbus register maps are transport-agnostic, so a model that lists `modbus_tcp`
+/// can also be reached through an RTU line or gateway.
+pub fn model_supports_protocol(model: &DeviceModel, protocol: &str) -> bool {
+    model.protocols.iter().any(|entry| {
+        entry == protocol || (is_modbus_protocol(entry) && is_modbus_protocol(protocol))
+    })
+}
+
+pub const SUPPORTED_PROTOCOLS: &[&str] = &[
+    "modbus_tcp",
+    "modbus_rtu",
+    "modbus_rtu_over_tcp",
+    "bacnet_ip",
+    "snmp",
+    "http_json",
+    "lutron_lip",
+    "lutron_leap",
+    "opcua",
+    "mqtt",
+];
+
+/// Protocol-specific checks the JSON schema cannot express (Modbus addressing and
+/// data type names).
+pub fn validate_point_semantics(point: &DevicePoint) -> Result<()> {
+    if is_modbus_protocol(&point.protocol) {
+        modbus::validate_point(point)?;
+    }
+    if point.protocol == "snmp" {
+        parse_snmp_oid(point.oid.as_deref().unwrap_or(""))?;
+    }
+    if point.protocol == "bacnet_ip" {
+        parse_bacnet_object_identifier(point.bacnet_object.as_deref().unwrap_or(""))?;
+    }
     Ok(())
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PointTestReadResult {
+    pub metric: String,
+    pub name: String,
+    pub unit: String,
+    /// Decoded value before `scale` is applied.
+    pub raw_value: Option<f64>,
+    pub value: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// Reads `points` once from a live device without storing anything, so a candidate
+/// profile can be checked before it is saved.
+pub async fn test_read_points(
+    state: &AppState,
+    config: &ExternalDeviceConfig,
+    points: &[DevicePoint],
+) -> Result<Vec<PointTestReadResult>> {
+    let mut raw: HashMap<&str, Result<f64, String>> = HashMap::new();
+    match config.protocol.as_str() {
+        protocol if is_modbus_protocol(protocol) => {
+            let refs = points
+                .iter()
+                .filter(|point| is_modbus_protocol(&point.protocol))
+                .collect::<Vec<_>>();
+            let outcome = modbus::read_points_once(config, &refs).await?;
+            for (point, value) in outcome.values {
+                raw.insert(point.metric.as_str(), Ok(value));
+            }
+            for failure in outcome.failures {
+                raw.entry(failure.point.metric.as_str())
+                    .or_insert(Err(failure.error));
+            }
+            for point in &refs {
+                raw.entry(point.metric.as_str())
+                    .or_insert_with(|| Err("no value returned".to_string()));
+            }
+        }
+        "snmp" => {
+            let values = read_snmp_values(config, points).await?;
+            for point in points {
+                let value = values
+                    .iter()
+                    .find(|(metric, _)| metric == &point.metric)
+                    .map(|(_, value)| *value)
+                    .ok_or_else(|| "no value returned".to_string());
+                raw.insert(point.metric.as_str(), value);
+            }
+        }
+        "http_json" => {
+            for point in points.iter().filter(|p| p.protocol == "http_json") {
+                let value = match read_http_point(state, config, point).await {
+                    Ok(Some(value)) => Ok(value),
+                    Ok(None) => Err("JSON pointer did not resolve to a number".to_string()),
+                    Err(err) => Err(format!("{err:#}")),
+                };
+                raw.insert(point.metric.as_str(), value);
+            }
+        }
+        other => return Err(anyhow!("test read is not supported for protocol {other}")),
+    }
+
+    Ok(points
+        .iter()
+        .map(|point| {
+            let result = raw
+                .remove(point.metric.as_str())
+                .unwrap_or_else(|| Err("point protocol does not match the device".to_string()));
+            PointTestReadResult {
+                metric: point.metric.clone(),
+                name: point.name.clone(),
+                unit: point.unit.clone(),
+                raw_value: result.as_ref().ok().copied(),
+                value: result
+                    .as_ref()
+                    .ok()
+                    .map(|value| value * point.scale.unwrap_or(1.0)),
+                error: result.err(),
+            }
+        })
+        .collect())
+}
+
 pub async fn poll_device_by_id(state: &AppState, node_id: Uuid) -> Result<(String, usize)> {
     let device: ExternalDeviceRow = sqlx::query_as(
         r#"
@@ -161,6 +383,15 @@ pub async fn poll_device_by_id(state: &AppState, node_id: Uuid) -> Result<(Strin
     if let Some(vendor_id) = discovery.bacnet_vendor_id {
         config.bacnet_vendor_id = Some(vendor_id);
     }
+    // Discovered command topics only add outputs; entries the user already edited win.
+    if let Some(outputs) = discovery.mqtt_outputs {
+        let existing = config.mqtt_outputs.get_or_insert_with(Vec::new);
+        for output in outputs {
+            if !existing.iter().any(|entry| entry.id == output.id) {
+                existing.push(output);
+            }
+        }
+    }
     if config != original_config {
         update_device_config(state, device.id, &config).await?;
     }
@@ -210,12 +441,18 @@ async fn poll_device_with_config(
     ensure_device_sensors(state, device, &config, &points, poll_interval_seconds).await?;
 
     match config.protocol.as_str() {
-        "modbus_tcp" => poll_modbus_device(state, device, &config, &points, now).await?,
+        "modbus_tcp" | "modbus_rtu" | "modbus_rtu_over_tcp" => {
+            poll_modbus_device(state, device, &config, &points, now).await?
+        }
         "bacnet_ip" => poll_bacnet_device(state, device, &config, &points, now).await?,
         "snmp" => poll_snmp_device(state, device, &config, &points, now).await?,
         "http_json" => poll_http_device(state, device, &config, &points, now).await?,
         "lutron_lip" => poll_lutron_lip_device(state, device, &config, &points, now).await?,
         "lutron_leap" => poll_lutron_leap_device(state, device, &config, &points, now).await?,
+        "opcua" => poll_opcua_device(state, device, &config, &points).await?,
+        "mqtt" => {
+            poll_mqtt_device(state, device, &config, &points, poll_interval_seconds).await?
+        }
         _ => {
             warn!(
                 node_id = %device.id,
@@ -234,6 +471,7 @@ struct DeviceDiscoveryResult {
     points: Option<Vec<DevicePoint>>,
     bacnet_device_instance: Option<u32>,
     bacnet_vendor_id: Option<u16>,
+    mqtt_outputs: Option<Vec<MqttOutputConfig>>,
 }
 
 fn parse_external_device_config(config: &JsonValue) -> Option<ExternalDeviceConfig> {
@@ -382,7 +620,10 @@ fn points_for_device(config: &ExternalDeviceConfig, model: &DeviceModel) -> Vec<
     let mut points = model
         .points
         .iter()
-        .filter(|point| point.protocol == config.protocol)
+        .filter(|point| {
+            point.protocol == config.protocol
+                || (is_modbus_protocol(&point.protocol) && is_modbus_protocol(&config.protocol))
+        })
         .cloned()
         .collect::<Vec<_>>();
     if let Some(extra) = config.discovered_points.as_ref() {
@@ -500,36 +741,16 @@ async fn poll_modbus_device(
     points: &[DevicePoint],
     now: DateTime<Utc>,
 ) -> Result<()> {
-    let host = config.host.as_ref().context("modbus device missing host")?;
-    let port = config.port.unwrap_or(502);
-    let socket_addr: SocketAddr = format!("{}:{}", host, port)
-        .parse()
-        .context("invalid modbus socket address")?;
-    let mut ctx = if let Some(unit) = config.unit_id {
-        tokio_modbus::client::tcp::connect_slave(socket_addr, tokio_modbus::slave::Slave(unit))
-            .await
-            .context("modbus connect failed")?
-    } else {
-        tokio_modbus::client::tcp::connect(socket_addr)
-            .await
-            .context("modbus connect failed")?
-    };
+    let modbus_points = points
+        .iter()
+        .filter(|p| is_modbus_protocol(&p.protocol) && p.register.is_some())
+        .collect::<Vec<_>>();
+    if modbus_points.is_empty() {
+        return Err(anyhow!("modbus device had no readable points configured"));
+    }
 
-    let mut success_count = 0usize;
-    let mut failed_metrics = Vec::new();
-    for point in points.iter().filter(|p| p.protocol == "modbus_tcp") {
-        let register = match point.register {
-            Some(register) => register,
-            None => continue,
-        };
-        let data_type = point.data_type.as_deref().unwrap_or("u16");
-        let value = match read_modbus_value(&mut ctx, register, data_type).await {
-            Ok(value) => value,
-            Err(err) => {
-                failed_metrics.push(format!("{} ({err})", point.metric));
-                continue;
-            }
-        };
+    let outcome = modbus::read_points(device.id, config, &modbus_points).await?;
+    for (point, value) in &outcome.values {
         let scaled = value * point.scale.unwrap_or(1.0);
         insert_metric(
             state,
@@ -540,24 +761,20 @@ async fn poll_modbus_device(
             scaled,
         )
         .await?;
-        success_count += 1;
     }
 
-    if success_count == 0 {
-        if !failed_metrics.is_empty() {
-            return Err(anyhow!(
-                "modbus reads failed for all points: {}",
-                failed_metrics.join(", ")
-            ));
-        }
-        return Err(anyhow!("modbus device had no readable points configured"));
+    if outcome.values.is_empty() {
+        return Err(anyhow!(
+            "modbus reads failed for all points: {}",
+            modbus::describe_failures(&outcome.failures)
+        ));
     }
 
-    if !failed_metrics.is_empty() {
+    if !outcome.failures.is_empty() {
         warn!(
             node_id = %device.id,
-            host = config.host.as_deref().unwrap_or(""),
-            failed = %failed_metrics.join(", "),
+            host = config.host.as_deref().or(config.serial_port.as_deref()).unwrap_or(""),
+            failed = %modbus::describe_failures(&outcome.failures),
             "some modbus points failed to read"
         );
     }
@@ -565,61 +782,41 @@ async fn poll_modbus_device(
     Ok(())
 }
 
-async fn read_modbus_value(
-    ctx: &mut tokio_modbus::client::Context,
-    register: u32,
-    data_type: &str,
-) -> Result<f64> {
-    let (addr, use_input_registers) = if (30001..=39999).contains(&register) {
-        (register.saturating_sub(30001) as u16, true)
-    } else if (40001..=49999).contains(&register) {
-        (register.saturating_sub(40001) as u16, false)
-    } else {
-        (register.saturating_sub(1) as u16, false)
-    };
-    match data_type {
-        "u16" => {
-            let values = if use_input_registers {
-                ctx.read_input_registers(addr, 1).await?
-            } else {
-                ctx.read_holding_registers(addr, 1).await?
-            };
-            Ok(values.get(0).copied().unwrap_or(0) as f64)
-        }
-        "i16" => {
-            let values = if use_input_registers {
-                ctx.read_input_registers(addr, 1).await?
-            } else {
-                ctx.read_holding_registers(addr, 1).await?
-            };
-            let raw = values.get(0).copied().unwrap_or(0);
-            Ok(i16::from_be_bytes(raw.to_be_bytes()) as f64)
-        }
-        "u32" | "i32" | "f32_be" => {
-            let values = if use_input_registers {
-                ctx.read_input_registers(addr, 2).await?
-            } else {
-                ctx.read_holding_registers(addr, 2).await?
-            };
-            let hi = values.get(0).copied().unwrap_or(0);
-            let lo = values.get(1).copied().unwrap_or(0);
-            let combined = ((hi as u32) << 16) | (lo as u32);
-            match data_type {
-                "u32" => Ok(combined as f64),
-                "i32" => Ok(i32::from_be_bytes(combined.to_be_bytes()) as f64),
-                "f32_be" => Ok(f32::from_bits(combined).into()),
-                _ => Ok(combined as f64),
-            }
-        }
-        _ => {
-            let values = if use_input_registers {
-                ctx.read_input_registers(addr, 1).await?
-            } else {
-                ctx.read_holding_registers(addr, 1).await?
-            };
-            Ok(values.get(0).copied().unwrap_or(0) as f64)
-        }
-    }
+/// OPC UA values arrive through monitored items rather than reads, so a "poll" only
+/// (re)establishes the subscription when it is missing or its points changed.
+async fn poll_opcua_device(
+    state: &AppState,
+    device: &ExternalDeviceRow,
+    config: &ExternalDeviceConfig,
+    points: &[DevicePoint],
+) -> Result<()> {
+    let opcua_points = points
+        .iter()
+        .filter(|p| p.protocol == "opcua" && p.path.is_some())
+        .collect::<Vec<_>>();
+    opcua::ensure_subscription(state, device.id, config, &opcua_points).await
+}
+
+/// MQTT devices push their values; a "poll" refreshes the topic routes and output
+/// rows and fails when nothing has arrived on the device's topics for a while.
+async fn poll_mqtt_device(
+    state: &AppState,
+    device: &ExternalDeviceRow,
+    config: &ExternalDeviceConfig,
+    points: &[DevicePoint],
+    poll_interval_seconds: u64,
+) -> Result<()> {
+    let mqtt_points = points
+        .iter()
+        .filter(|p| p.protocol == "mqtt" && p.path.is_some())
+        .collect::<Vec<_>>();
+    mqtt::ensure_outputs(state, device.id, config).await?;
+    mqtt::register_device(
+        device.id,
+        config,
+        &mqtt_points,
+        std::time::Duration::from_secs(poll_interval_seconds.saturating_mul(3)),
+    )
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
@@ -930,6 +1127,27 @@ async fn probe_bacnet_identity(host: Ipv4Addr, port: u16) -> Result<Option<Bacne
         bacnet_bbmd_host: None,
         bacnet_bbmd_port: None,
         bacnet_foreign_ttl_seconds: None,
+        modbus_request_delay_ms: None,
+        modbus_max_block_registers: None,
+        modbus_max_register_gap: None,
+        serial_port: None,
+        serial_baud_rate: None,
+        serial_parity: None,
+        serial_stop_bits: None,
+        serial_data_bits: None,
+        opcua_endpoint_url: None,
+        opcua_security_policy: None,
+        opcua_security_mode: None,
+        opcua_username: None,
+        opcua_password: None,
+        opcua_client_cert_pem: None,
+        opcua_client_key_pem: None,
+        opcua_trust_server_cert: None,
+        opcua_publishing_interval_ms: None,
+        opcua_browse_root: None,
+        mqtt_base_topic: None,
+        mqtt_device_identifier: None,
+        mqtt_outputs: None,
     };
     let mut client = create_bacnet_client(&temp_config, host, port).await?;
     let result = discover_bacnet_identity(&client, host, port).await;
@@ -1052,6 +1270,7 @@ async fn discover_bacnet_points(
             unit,
             protocol: "bacnet_ip".to_string(),
             register: None,
+            register_type: None,
             data_type: None,
             scale: None,
             oid: None,
@@ -1067,6 +1286,7 @@ async fn discover_bacnet_points(
         points: Some(points),
         bacnet_device_instance: Some(identity.device_instance),
         bacnet_vendor_id: Some(identity.vendor_id),
+        ..DeviceDiscoveryResult::default()
     })
 }
 
@@ -1508,6 +1728,17 @@ async fn poll_snmp_device(
     points: &[DevicePoint],
     now: DateTime<Utc>,
 ) -> Result<()> {
+    let results = read_snmp_values(config, points).await?;
+    for (metric, value) in results {
+        insert_metric(state, now, device.id, &config.model_id, &metric, value).await?;
+    }
+    Ok(())
+}
+
+async fn read_snmp_values(
+    config: &ExternalDeviceConfig,
+    points: &[DevicePoint],
+) -> Result<Vec<(String, f64)>> {
     let host = config.host.as_ref().context("snmp device missing host")?;
     let port = config.port.unwrap_or(161);
     let community = config
@@ -1521,7 +1752,7 @@ async fn poll_snmp_device(
         .filter_map(|p| p.oid.as_ref().map(|oid| (p.metric.clone(), oid.clone())))
         .collect();
 
-    let results = tokio::task::spawn_blocking(move || -> Result<Vec<(String, f64)>> {
+    tokio::task::spawn_blocking(move || -> Result<Vec<(String, f64)>> {
         let timeout = std::time::Duration::from_secs(3);
         let mut session = snmp::SyncSession::new(
             (host.as_str(), port),
@@ -1543,12 +1774,7 @@ async fn poll_snmp_device(
         Ok(values)
     })
     .await
-    .context("snmp blocking task failed")??;
-
-    for (metric, value) in results {
-        insert_metric(state, now, device.id, &config.model_id, &metric, value).await?;
-    }
-    Ok(())
+    .context("snmp blocking task failed")?
 }
 
 fn snmp_value_to_f64(value: &snmp::Value<'_>) -> f64 {
@@ -1601,32 +1827,8 @@ async fn poll_http_device(
     points: &[DevicePoint],
     now: DateTime<Utc>,
 ) -> Result<()> {
-    let base_url = config
-        .http_base_url
-        .as_ref()
-        .context("http device missing base_url")?;
-    let client = state.http.clone();
     for point in points.iter().filter(|p| p.protocol == "http_json") {
-        let path = point.path.as_deref().unwrap_or("");
-        let url = format!("{}{}", base_url.trim_end_matches('/'), path);
-        let mut request = client.get(url);
-        if let (Some(user), Some(pass)) =
-            (config.http_username.as_ref(), config.http_password.as_ref())
-        {
-            request = request.basic_auth(user, Some(pass));
-        }
-        let response = request.send().await?;
-        let payload: JsonValue = response.json().await?;
-        let value = point
-            .json_pointer
-            .as_deref()
-            .and_then(|ptr| payload.pointer(ptr))
-            .and_then(|val| match val {
-                JsonValue::Number(num) => num.as_f64(),
-                JsonValue::String(text) => text.parse::<f64>().ok(),
-                _ => None,
-            })
-            .unwrap_or(0.0);
+        let value = read_http_point(state, config, point).await?.unwrap_or(0.0);
         insert_metric(
             state,
             now,
@@ -1640,6 +1842,35 @@ async fn poll_http_device(
     Ok(())
 }
 
+async fn read_http_point(
+    state: &AppState,
+    config: &ExternalDeviceConfig,
+    point: &DevicePoint,
+) -> Result<Option<f64>> {
+    let base_url = config
+        .http_base_url
+        .as_ref()
+        .context("http device missing base_url")?;
+    let path = point.path.as_deref().unwrap_or("");
+    let url = format!("{}{}", base_url.trim_end_matches('/'), path);
+    let mut request = state.http.get(url);
+    if let (Some(user), Some(pass)) = (config.http_username.as_ref(), config.http_password.as_ref())
+    {
+        request = request.basic_auth(user, Some(pass));
+    }
+    let response = request.send().await?;
+    let payload: JsonValue = response.json().await?;
+    Ok(point
+        .json_pointer
+        .as_deref()
+        .and_then(|ptr| payload.pointer(ptr))
+        .and_then(|val| match val {
+            JsonValue::Number(num) => num.as_f64(),
+            JsonValue::String(text) => text.parse::<f64>().ok(),
+            _ => None,
+        }))
+}
+
 async fn insert_metric(
     state: &AppState,
     ts: DateTime<Utc>,
@@ -1679,7 +1910,7 @@ async fn insert_metric(
 }
 
 async fn discover_device_points(
-    _state: &AppState,
+    state: &AppState,
     device: &ExternalDeviceRow,
     config: &ExternalDeviceConfig,
     model: &DeviceModel,
@@ -1703,6 +1934,7 @@ async fn discover_device_points(
                     unit: "%".to_string(),
                     protocol: "lutron_lip".to_string(),
                     register: None,
+                    register_type: None,
                     data_type: None,
                     scale: None,
                     oid: None,
@@ -1754,6 +1986,32 @@ async fn discover_device_points(
                 })
             }
         }
+        "opcua" => {
+            let points = opcua::discover_points(state, device.id, config)
+                .await
+                .with_context(|| {
+                    format!(
+                        "failed to discover OPC UA points for device {} ({})",
+                        device.name, model.id
+                    )
+                })?;
+            if points.is_empty() {
+                Ok(DeviceDiscoveryResult::default())
+            } else {
+                Ok(DeviceDiscoveryResult {
+                    points: Some(points),
+                    ..DeviceDiscoveryResult::default()
+                })
+            }
+        }
+        "mqtt" => {
+            let (points, outputs) = mqtt::discover_points(config);
+            Ok(DeviceDiscoveryResult {
+                points: (!points.is_empty()).then_some(points),
+                mqtt_outputs: (!outputs.is_empty()).then_some(outputs),
+                ..DeviceDiscoveryResult::default()
+            })
+        }
         _ => Ok(DeviceDiscoveryResult::default()),
     }
 }
@@ -2095,6 +2353,7 @@ async fn discover_lutron_leap_points(config: &ExternalDeviceConfig) -> Result<Ve
             unit: "%".to_string(),
             protocol: "lutron_leap".to_string(),
             register: None,
+            register_type: None,
             data_type: None,
             scale: None,
             oid: None,
@@ -2109,6 +2368,7 @@ async fn discover_lutron_leap_points(config: &ExternalDeviceConfig) -> Result<Ve
             unit: "".to_string(),
             protocol: "lutron_leap".to_string(),
             register: None,
+            register_type: None,
             data_type: None,
             scale: None,
             oid: None,
@@ -2123,6 +2383,7 @@ async fn discover_lutron_leap_points(config: &ExternalDeviceConfig) -> Result<Ve
             unit: "%".to_string(),
             protocol: "lutron_leap".to_string(),
             register: None,
+            register_type: None,
             data_type: None,
             scale: None,
             oid: None,
@@ -2137,6 +2398,7 @@ async fn discover_lutron_leap_points(config: &ExternalDeviceConfig) -> Result<Ve
             unit: "%".to_string(),
             protocol: "lutron_leap".to_string(),
             register: None,
+            register_type: None,
             data_type: None,
             scale: None,
             oid: None,
@@ -2151,6 +2413,7 @@ async fn discover_lutron_leap_points(config: &ExternalDeviceConfig) -> Result<Ve
             unit: "".to_string(),
             protocol: "lutron_leap".to_string(),
             register: None,
+            register_type: None,
             data_type: None,
             scale: None,
             oid: None,
@@ -2302,6 +2565,7 @@ mod tests {
             unit: String::new(),
             protocol: protocol.to_string(),
             register: None,
+            register_type: None,
             data_type: None,
             scale: None,
             oid: None,
@@ -2319,19 +2583,7 @@ mod tests {
             protocol: "bacnet_ip".to_string(),
             host: Some("192.168.75.40".to_string()),
             port: Some(47808),
-            unit_id: None,
             poll_interval_seconds: Some(30),
-            snmp_community: None,
-            http_base_url: None,
-            http_username: None,
-            http_password: None,
-            lip_username: None,
-            lip_password: None,
-            lip_integration_report: None,
-            leap_client_cert_pem: None,
-            leap_client_key_pem: None,
-            leap_ca_pem: None,
-            leap_verify_ca: None,
             discovered_points: Some(vec![sample_point(
                 "line_voltage_ab",
                 "bacnet_ip",
@@ -2339,9 +2591,7 @@ mod tests {
             )]),
             bacnet_device_instance: Some(1234),
             bacnet_vendor_id: Some(42),
-            bacnet_bbmd_host: None,
-            bacnet_bbmd_port: None,
-            bacnet_foreign_ttl_seconds: None,
+            ..Default::default()
         };
         let model = DeviceModel {
             id: "setra_power_meter_generic".to_string(),
@@ -2360,6 +2610,62 @@ mod tests {
         assert!(points.iter().any(|point| point.metric == "legacy_bacnet_metric"));
         assert!(points.iter().any(|point| point.metric == "line_voltage_ab"));
         assert!(!points.iter().any(|point| point.metric == "voltage_l1_n_v"));
+
+        let rtu_config = ExternalDeviceConfig {
+            protocol: "modbus_rtu".to_string(),
+            serial_port: Some("/dev/ttyUSB0".to_string()),
+            discovered_points: None,
+            ..config
+        };
+        let rtu_points = points_for_device(&rtu_config, &model);
+        assert_eq!(rtu_points.len(), 1);
+        assert_eq!(rtu_points[0].metric, "voltage_l1_n_v");
+        assert!(model_supports_protocol(&model, "modbus_rtu_over_tcp"));
+        assert!(!model_supports_protocol(&model, "snmp"));
+    }
+
+    #[test]
+    fn schedule_poll_lanes_only_splits_modbus_buses() {
+        let device = |config: ExternalDeviceConfig| ExternalDeviceRow {
+            id: Uuid::new_v4(),
+            name: config.protocol.clone(),
+            external_provider: Some(config.vendor_id.clone()),
+            external_id: None,
+            config: SqlJson(json!({ "external_device": config })),
+            created_at: Utc::now(),
+        };
+        let rtu = |serial_port: &str| ExternalDeviceConfig {
+            vendor_id: "generic".to_string(),
+            model_id: "generic_modbus_meter".to_string(),
+            protocol: "modbus_rtu".to_string(),
+            serial_port: Some(serial_port.to_string()),
+            ..Default::default()
+        };
+        let other = |protocol: &str| ExternalDeviceConfig {
+            vendor_id: "generic".to_string(),
+            model_id: "generic".to_string(),
+            protocol: protocol.to_string(),
+            host: Some("10.0.0.9".to_string()),
+            ..Default::default()
+        };
+
+        let lanes = schedule_poll_lanes(vec![
+            device(rtu("/dev/ttyUSB0")),
+            device(rtu("/dev/ttyUSB0")),
+            device(rtu("/dev/ttyUSB1")),
+            device(other("opcua")),
+            device(other("bacnet_ip")),
+            device(other("http_json")),
+        ]);
+
+        assert_eq!(lanes.len(), 3);
+        assert_eq!(lanes["serial:/dev/ttyUSB0"].len(), 2);
+        assert_eq!(lanes["serial:/dev/ttyUSB1"].len(), 1);
+        let sequential = lanes[SEQUENTIAL_POLL_LANE]
+            .iter()
+            .map(|device| device.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(sequential, vec!["opcua", "bacnet_ip", "http_json"]);
     }
 
     #[test]
@@ -2377,26 +2683,7 @@ mod tests {
             model_id: "metasys_server".to_string(),
             protocol: "http_json".to_string(),
             host: Some("https://192.168.75.18".to_string()),
-            port: None,
-            unit_id: None,
-            poll_interval_seconds: None,
-            snmp_community: None,
-            http_base_url: None,
-            http_username: None,
-            http_password: None,
-            lip_username: None,
-            lip_password: None,
-            lip_integration_report: None,
-            leap_client_cert_pem: None,
-            leap_client_key_pem: None,
-            leap_ca_pem: None,
-            leap_verify_ca: None,
-            discovered_points: None,
-            bacnet_device_instance: None,
-            bacnet_vendor_id: None,
-            bacnet_bbmd_host: None,
-            bacnet_bbmd_port: None,
-            bacnet_foreign_ttl_seconds: None,
+            ..Default::default()
         };
 
         normalize_http_device_config(&mut config);
@@ -2440,25 +2727,9 @@ mod tests {
             protocol: "bacnet_ip".to_string(),
             host: Some(host.to_string()),
             port: Some(47808),
-            unit_id: None,
             poll_interval_seconds: Some(30),
-            snmp_community: None,
-            http_base_url: None,
-            http_username: None,
-            http_password: None,
-            lip_username: None,
-            lip_password: None,
-            lip_integration_report: None,
-            leap_client_cert_pem: None,
-            leap_client_key_pem: None,
-            leap_ca_pem: None,
-            leap_verify_ca: None,
-            discovered_points: None,
-            bacnet_device_instance: None,
-            bacnet_vendor_id: None,
             bacnet_bbmd_host: std::env::var("ID_LIVE_BACNET_BBMD_HOST").ok(),
-            bacnet_bbmd_port: None,
-            bacnet_foreign_ttl_seconds: None,
+            ..Default::default()
         };
         discover_bacnet_points(&config).await
     }
@@ -2502,25 +2773,11 @@ mod tests {
             protocol: "bacnet_ip".to_string(),
             host: Some(target),
             port: Some(47808),
-            unit_id: None,
             poll_interval_seconds: Some(30),
-            snmp_community: None,
-            http_base_url: None,
-            http_username: None,
-            http_password: None,
-            lip_username: None,
-            lip_password: None,
-            lip_integration_report: None,
-            leap_client_cert_pem: None,
-            leap_client_key_pem: None,
-            leap_ca_pem: None,
-            leap_verify_ca: None,
-            discovered_points: None,
-            bacnet_device_instance: None,
-            bacnet_vendor_id: None,
             bacnet_bbmd_host: Some(gateway),
             bacnet_bbmd_port: Some(47808),
             bacnet_foreign_ttl_seconds: Some(300),
+            ..Default::default()
         };
         let mut client = create_bacnet_client(&config, host_ip, 47808).await.unwrap();
         let gateway_mac = bip_mac(gateway_ip, 47808);
@@ -2550,25 +2807,9 @@ mod tests {
             protocol: "bacnet_ip".to_string(),
             host: Some(host.clone()),
             port: Some(47808),
-            unit_id: None,
             poll_interval_seconds: Some(30),
-            snmp_community: None,
-            http_base_url: None,
-            http_username: None,
-            http_password: None,
-            lip_username: None,
-            lip_password: None,
-            lip_integration_report: None,
-            leap_client_cert_pem: None,
-            leap_client_key_pem: None,
-            leap_ca_pem: None,
-            leap_verify_ca: None,
-            discovered_points: None,
             bacnet_device_instance: Some(instance),
-            bacnet_vendor_id: None,
-            bacnet_bbmd_host: None,
-            bacnet_bbmd_port: None,
-            bacnet_foreign_ttl_seconds: None,
+            ..Default::default()
         };
         let host_ip = parse_ipv4_host(&host).unwrap();
         let mut client = create_bacnet_client(&config, host_ip, 47808).await.unwrap();
@@ -2597,30 +2838,14 @@ mod tests {
                     .unwrap_or_else(|_| "192.168.75.103".to_string()),
             ),
             port: Some(47808),
-            unit_id: None,
             poll_interval_seconds: Some(30),
-            snmp_community: None,
-            http_base_url: None,
-            http_username: None,
-            http_password: None,
-            lip_username: None,
-            lip_password: None,
-            lip_integration_report: None,
-            leap_client_cert_pem: None,
-            leap_client_key_pem: None,
-            leap_ca_pem: None,
-            leap_verify_ca: None,
-            discovered_points: None,
             bacnet_device_instance: Some(
                 std::env::var("ID_LIVE_BACNET_INSTANCE")
                     .ok()
                     .and_then(|value| value.parse::<u32>().ok())
                     .unwrap_or(394000),
             ),
-            bacnet_vendor_id: None,
-            bacnet_bbmd_host: None,
-            bacnet_bbmd_port: None,
-            bacnet_foreign_ttl_seconds: None,
+            ..Default::default()
         };
         let discovery = discover_bacnet_points(&config).await.unwrap();
         let points = discovery.points.unwrap_or_default();
@@ -2639,30 +2864,14 @@ mod tests {
                     .unwrap_or_else(|_| "192.168.75.80".to_string()),
             ),
             port: Some(47808),
-            unit_id: None,
             poll_interval_seconds: Some(30),
-            snmp_community: None,
-            http_base_url: None,
-            http_username: None,
-            http_password: None,
-            lip_username: None,
-            lip_password: None,
-            lip_integration_report: None,
-            leap_client_cert_pem: None,
-            leap_client_key_pem: None,
-            leap_ca_pem: None,
-            leap_verify_ca: None,
-            discovered_points: None,
             bacnet_device_instance: Some(
                 std::env::var("ID_LIVE_BACNET_INSTANCE")
                     .ok()
                     .and_then(|value| value.parse::<u32>().ok())
                     .unwrap_or(40),
             ),
-            bacnet_vendor_id: None,
-            bacnet_bbmd_host: None,
-            bacnet_bbmd_port: None,
-            bacnet_foreign_ttl_seconds: None,
+            ..Default::default()
         };
         let discovery = discover_bacnet_points(&config).await.unwrap();
         let points = discovery.points.unwrap_or_default();
@@ -0,0 +1,1270 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tokio_modbus::prelude::{Reader, SlaveContext};
+use tokio_modbus::slave::Slave;
+use uuid::Uuid;
+
+use super::ExternalDeviceConfig;
+use crate::device_catalog::DevicePoint;
+
+/// Protocol limits for FC01/FC02 (bits) and FC03/FC04 (registers).
+const MAX_BITS_PER_READ: u16 = 2000;
+const MAX_REGISTERS_PER_READ: u16 = 125;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+const RECONNECT_BACKOFF_BASE: Duration = Duration::from_secs(2);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(300);
+
+static SESSIONS: OnceLock<
+    std::sync::Mutex<HashMap<String, Arc<tokio::sync::Mutex<ModbusSession>>>>,
+> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(super) enum ModbusTable {
+    Coil,
+    DiscreteInput,
+    InputRegister,
+    HoldingRegister,
+}
+
+impl ModbusTable {
+    fn is_bit(self) -> bool {
+        matches!(self, ModbusTable::Coil | ModbusTable::DiscreteInput)
+    }
+
+    fn max_read(self) -> u16 {
+        if self.is_bit() {
+            MAX_BITS_PER_READ
+        } else {
+            MAX_REGISTERS_PER_READ
+        }
+    }
+}
+
+/// Register/byte ordering for multi-byte values, named after the byte sequence
+/// of a 32-bit value `0xAABBCCDD` as it appears on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteOrder {
+    /// ABCD: big-endian words, big-endian bytes (Modbus default).
+    BigEndian,
+    /// DCBA: fully little-endian.
+    LittleEndian,
+    /// CDAB: word-swapped, bytes big-endian within each word.
+    WordSwap,
+    /// BADC: word order kept, bytes swapped within each word.
+    ByteSwap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueKind {
+    Bool,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    U64,
+    I64,
+    F64,
+    String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ModbusDataType {
+    kind: ValueKind,
+    order: ByteOrder,
+    registers: u16,
+}
+
+impl ModbusDataType {
+    /// Parses catalog `data_type` strings such as `u16`, `f32_be`, `f32_le`,
+    /// `u32_ws`, `f64_bs`, `bool` or `string:8` (register count).
+    pub(super) fn parse(raw: &str) -> Result<Self> {
+        let normalized = raw.trim().to_ascii_lowercase();
+        if let Some(rest) = normalized.strip_prefix("string") {
+            let (count_text, order) = match rest.split_once('_') {
+                Some((count, suffix)) => (count, parse_byte_order(suffix)?),
+                None => (rest, ByteOrder::BigEndian),
+            };
+            // Strings are stored in character order; only byte swapping applies.
+            anyhow::ensure!(
+                matches!(order, ByteOrder::BigEndian | ByteOrder::ByteSwap),
+                "string data types only support the _be and _bs byte orders"
+            );
+            let registers = count_text
+                .trim_start_matches(':')
+                .parse::<u16>()
+                .map_err(|_| anyhow!("string data type needs a register count, e.g. string:8"))?;
+            anyhow::ensure!(
+                (1..=MAX_REGISTERS_PER_READ).contains(&registers),
+                "string register count must be between 1 and {MAX_REGISTERS_PER_READ}"
+            );
+            return Ok(Self {
+                kind: ValueKind::String,
+                order,
+                registers,
+            });
+        }
+
+        let (base, order) = match normalized.rsplit_once('_') {
+            Some((base, suffix)) => (base, parse_byte_order(suffix)?),
+            None => (normalized.as_str(), ByteOrder::BigEndian),
+        };
+        let (kind, registers) = match base {
+            "bool" | "bit" => (ValueKind::Bool, 1),
+            "u16" => (ValueKind::U16, 1),
+            "i16" => (ValueKind::I16, 1),
+            "u32" => (ValueKind::U32, 2),
+            "i32" => (ValueKind::I32, 2),
+            "f32" => (ValueKind::F32, 2),
+            "u64" => (ValueKind::U64, 4),
+            "i64" => (ValueKind::I64, 4),
+            "f64" => (ValueKind::F64, 4),
+            other => return Err(anyhow!("unsupported modbus data type {other}")),
+        };
+        Ok(Self {
+            kind,
+            order,
+            registers,
+        })
+    }
+
+    fn bool() -> Self {
+        Self {
+            kind: ValueKind::Bool,
+            order: ByteOrder::BigEndian,
+            registers: 1,
+        }
+    }
+
+    fn u16() -> Self {
+        Self {
+            kind: ValueKind::U16,
+            order: ByteOrder::BigEndian,
+            registers: 1,
+        }
+    }
+}
+
+fn parse_byte_order(suffix: &str) -> Result<ByteOrder> {
+    match suffix {
+        "be" => Ok(ByteOrder::BigEndian),
+        "le" => Ok(ByteOrder::LittleEndian),
+        "ws" => Ok(ByteOrder::WordSwap),
+        "bs" => Ok(ByteOrder::ByteSwap),
+        other => Err(anyhow!("unsupported modbus byte order suffix {other}")),
+    }
+}
+
+/// Resolves a catalog register number into a Modbus table + zero-based address.
+///
+/// Without an explicit `register_type` the legacy convention applies: 3xxxx is an
+/// input register, 4xxxx a holding register and anything else a 1-based holding
+/// register. With an explicit type, the conventional 0x/1x/3x/4x prefix is optional.
+pub(super) fn resolve_address(
+    register: u32,
+    register_type: Option<&str>,
+) -> Result<(ModbusTable, u16)> {
+    let explicit = register_type
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_ascii_lowercase);
+    let (table, prefixed_base) = match explicit.as_deref() {
+        None => {
+            if (30001..=39999).contains(&register) {
+                return Ok((ModbusTable::InputRegister, (register - 30001) as u16));
+            }
+            if (40001..=49999).contains(&register) {
+                return Ok((ModbusTable::HoldingRegister, (register - 40001) as u16));
+            }
+            return one_based(register).map(|addr| (ModbusTable::HoldingRegister, addr));
+        }
+        Some("coil" | "coils") => (ModbusTable::Coil, None),
+        Some("discrete_input" | "discrete_inputs") => (ModbusTable::DiscreteInput, Some(10001)),
+        Some("input" | "input_register" | "input_registers") => {
+            (ModbusTable::InputRegister, Some(30001))
+        }
+        Some("holding" | "holding_register" | "holding_registers") => {
+            (ModbusTable::HoldingRegister, Some(40001))
+        }
+        Some(other) => return Err(anyhow!("unsupported modbus register type {other}")),
+    };
+    if let Some(base) = prefixed_base {
+        if (base..=base + 9998).contains(&register) {
+            return Ok((table, (register - base) as u16));
+        }
+    }
+    one_based(register).map(|addr| (table, addr))
+}
+
+fn one_based(register: u32) -> Result<u16> {
+    u16::try_from(register.saturating_sub(1))
+        .map_err(|_| anyhow!("modbus register {register} is out of range"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct PlannedPoint {
+    pub(super) index: usize,
+    pub(super) address: u16,
+    pub(super) data_type: ModbusDataType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct ReadBlock {
+    pub(super) table: ModbusTable,
+    pub(super) start: u16,
+    pub(super) count: u16,
+    pub(super) members: Vec<PlannedPoint>,
+}
+
+pub(super) struct PlanResult {
+    pub(super) blocks: Vec<ReadBlock>,
+    pub(super) invalid: Vec<(usize, String)>,
+}
+
+/// Coalesces points into block reads. Points in the same table are merged while the
+/// gap to the previous point is at most `max_gap` registers and the block stays within
+/// `max_block` (clamped to the protocol limit for the table).
+pub(super) fn plan_reads(points: &[&DevicePoint], max_block: u16, max_gap: u16) -> PlanResult {
+    let mut planned: Vec<(ModbusTable, PlannedPoint)> = Vec::new();
+    let mut invalid = Vec::new();
+    for (index, point) in points.iter().enumerate() {
+        let Some(register) = point.register else {
+            continue;
+        };
+        let (table, address) = match resolve_address(register, point.register_type.as_deref()) {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                invalid.push((index, err.to_string()));
+                continue;
+            }
+        };
+        let data_type = if table.is_bit() {
+            match point.data_type.as_deref() {
+                None | Some("bool") | Some("bit") => ModbusDataType::bool(),
+                Some(other) => {
+                    invalid.push((index, format!("bit tables only support bool, got {other}")));
+                    continue;
+                }
+            }
+        } else {
+            match ModbusDataType::parse(point.data_type.as_deref().unwrap_or("u16")) {
+                Ok(data_type) => data_type,
+                Err(err) => {
+                    // Stored configs predate strict parsing and were read as u16; keep polling them.
+                    tracing::warn!(
+                        metric = %point.metric,
+                        data_type = point.data_type.as_deref().unwrap_or_default(),
+                        "{err}; reading as u16"
+                    );
+                    ModbusDataType::u16()
+                }
+            }
+        };
+        if address as u32 + data_type.registers as u32 > u16::MAX as u32 + 1 {
+            invalid.push((
+                index,
+                format!("register {register} runs past the address space"),
+            ));
+            continue;
+        }
+        planned.push((
+            table,
+            PlannedPoint {
+                index,
+                address,
+                data_type,
+            },
+        ));
+    }
+    planned.sort_by_key(|(table, point)| (*table, point.address, point.index));
+
+    let mut blocks: Vec<ReadBlock> = Vec::new();
+    for (table, point) in planned {
+        let limit = max_block.clamp(1, table.max_read()) as u32;
+        let point_end = point.address as u32 + point.data_type.registers as u32;
+        if let Some(block) = blocks.last_mut() {
+            let block_end = block.start as u32 + block.count as u32;
+            if block.table == table
+                && point.address as u32 <= block_end + max_gap as u32
+                && point_end.max(block_end) - block.start as u32 <= limit
+            {
+                block.count = (point_end.max(block_end) - block.start as u32) as u16;
+                block.members.push(point);
+                continue;
+            }
+        }
+        blocks.push(ReadBlock {
+            table,
+            start: point.address,
+            count: point.data_type.registers,
+            members: vec![point],
+        });
+    }
+    PlanResult { blocks, invalid }
+}
+
+/// Checks a point's addressing and data type without touching the bus.
+pub(super) fn validate_point(point: &DevicePoint) -> Result<()> {
+    let Some(register) = point.register else {
+        return Err(anyhow!("modbus points need a register"));
+    };
+    // Polling reads unknown register types as u16 for old configs; new points must parse.
+    let (table, _) = resolve_address(register, point.register_type.as_deref())?;
+    if !table.is_bit() {
+        ModbusDataType::parse(point.data_type.as_deref().unwrap_or("u16"))?;
+    }
+    match plan_reads(&[point], MAX_REGISTERS_PER_READ, 0)
+        .invalid
+        .pop()
+    {
+        Some((_, err)) => Err(anyhow!(err)),
+        None => Ok(()),
+    }
+}
+
+/// Decodes a register slice (already offset to the point's first register).
+pub(super) fn decode_registers(words: &[u16], data_type: &ModbusDataType) -> Result<f64> {
+    let needed = data_type.registers as usize;
+    anyhow::ensure!(
+        words.len() >= needed,
+        "expected {needed} registers, got {}",
+        words.len()
+    );
+    let words = &words[..needed];
+    if data_type.kind == ValueKind::Bool {
+        return Ok(if words[0] != 0 { 1.0 } else { 0.0 });
+    }
+    let bytes = ordered_bytes(words, data_type.order);
+    let value = match data_type.kind {
+        ValueKind::U16 => u16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+        ValueKind::I16 => i16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+        ValueKind::U32 => u32::from_be_bytes(array4(&bytes)) as f64,
+        ValueKind::I32 => i32::from_be_bytes(array4(&bytes)) as f64,
+        ValueKind::F32 => f32::from_be_bytes(array4(&bytes)) as f64,
+        ValueKind::U64 => u64::from_be_bytes(array8(&bytes)) as f64,
+        ValueKind::I64 => i64::from_be_bytes(array8(&bytes)) as f64,
+        ValueKind::F64 => f64::from_be_bytes(array8(&bytes)),
+        ValueKind::String => {
+            let text = String::from_utf8_lossy(&bytes);
+            let trimmed = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+            trimmed
+                .parse::<f64>()
+                .with_context(|| format!("string register value {trimmed:?} is not numeric"))?
+        }
+        ValueKind::Bool => unreachable!(),
+    };
+    anyhow::ensure!(value.is_finite(), "decoded value is not finite");
+    Ok(value)
+}
+
+fn ordered_bytes(words: &[u16], order: ByteOrder) -> Vec<u8> {
+    let ordered_words: Vec<u16> = match order {
+        ByteOrder::BigEndian | ByteOrder::ByteSwap => words.to_vec(),
+        ByteOrder::LittleEndian | ByteOrder::WordSwap => words.iter().rev().copied().collect(),
+    };
+    let swap_bytes = matches!(order, ByteOrder::LittleEndian | ByteOrder::ByteSwap);
+    ordered_words
+        .into_iter()
+        .flat_map(|word| {
+            if swap_bytes {
+                word.to_le_bytes()
+            } else {
+                word.to_be_bytes()
+            }
+        })
+        .collect()
+}
+
+fn array4(bytes: &[u8]) -> [u8; 4] {
+    [bytes[0], bytes[1], bytes[2], bytes[3]]
+}
+
+fn array8(bytes: &[u8]) -> [u8; 8] {
+    let mut out = [0u8; 8];
+    out.copy_from_slice(&bytes[..8]);
+    out
+}
+
+struct ModbusSession {
+    endpoint: String,
+    ctx: Option<tokio_modbus::client::Context>,
+    consecutive_failures: u32,
+    retry_at: Option<Instant>,
+    last_request_at: Option<Instant>,
+}
+
+impl ModbusSession {
+    fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            ctx: None,
+            consecutive_failures: 0,
+            retry_at: None,
+            last_request_at: None,
+        }
+    }
+
+    async fn ensure_connected(&mut self, config: &ExternalDeviceConfig) -> Result<()> {
+        if self.ctx.is_some() {
+            return Ok(());
+        }
+        if let Some(retry_at) = self.retry_at {
+            let now = Instant::now();
+            if retry_at > now {
+                return Err(anyhow!(
+                    "modbus reconnect backoff active after {} failed attempt(s); retrying in {}s",
+                    self.consecutive_failures,
+                    (retry_at - now).as_secs().max(1)
+                ));
+            }
+        }
+        match connect(config).await {
+            Ok(ctx) => {
+                self.ctx = Some(ctx);
+                self.consecutive_failures = 0;
+                self.retry_at = None;
+                Ok(())
+            }
+            Err(err) => {
+                self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+                self.retry_at = Some(Instant::now() + reconnect_backoff(self.consecutive_failures));
+                Err(err)
+            }
+        }
+    }
+
+    /// Time left until at least `delay` has passed since the previous request on this
+    /// connection; slow RS-485 bridges drop frames that arrive back-to-back.
+    fn pace(&self, delay: Duration) -> Option<Duration> {
+        let elapsed = self.last_request_at?.elapsed();
+        delay.checked_sub(elapsed).filter(|wait| !wait.is_zero())
+    }
+
+    async fn read_words(
+        &mut self,
+        table: ModbusTable,
+        start: u16,
+        count: u16,
+        delay: Duration,
+    ) -> std::result::Result<Vec<u16>, ReadError> {
+        if let Some(wait) = self.pace(delay) {
+            tokio::time::sleep(wait).await;
+        }
+        let ctx = self.ctx.as_mut().ok_or(ReadError::Disconnected)?;
+        let request = async {
+            match table {
+                ModbusTable::Coil => ctx
+                    .read_coils(start, count)
+                    .await
+                    .map(|bits| bits.into_iter().map(u16::from).collect::<Vec<u16>>()),
+                ModbusTable::DiscreteInput => ctx
+                    .read_discrete_inputs(start, count)
+                    .await
+                    .map(|bits| bits.into_iter().map(u16::from).collect::<Vec<u16>>()),
+                ModbusTable::InputRegister => ctx.read_input_registers(start, count).await,
+                ModbusTable::HoldingRegister => ctx.read_holding_registers(start, count).await,
+            }
+        };
+        let result = timeout(REQUEST_TIMEOUT, request).await;
+        self.last_request_at = Some(Instant::now());
+        match result {
+            Ok(Ok(words)) => Ok(words),
+            // tokio-modbus reports exception responses as `ErrorKind::Other`; the
+            // connection itself is still usable.
+            Ok(Err(err)) if err.kind() == std::io::ErrorKind::Other => {
+                Err(ReadError::Exception(err.to_string()))
+            }
+            Ok(Err(err)) => {
+                self.ctx = None;
+                Err(ReadError::Transport(err.to_string()))
+            }
+            Err(_) => {
+                // A late response would desync the transaction stream; start over.
+                self.ctx = None;
+                Err(ReadError::Transport("request timed out".to_string()))
+            }
+        }
+    }
+}
+
+enum ReadError {
+    Disconnected,
+    Exception(String),
+    Transport(String),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Disconnected => write!(f, "connection lost"),
+            ReadError::Exception(message) => write!(f, "modbus exception: {message}"),
+            ReadError::Transport(message) => write!(f, "transport error: {message}"),
+        }
+    }
+}
+
+fn reconnect_backoff(failures: u32) -> Duration {
+    let exponent = failures.saturating_sub(1).min(16);
+    RECONNECT_BACKOFF_BASE
+        .saturating_mul(1u32 << exponent)
+        .min(RECONNECT_BACKOFF_MAX)
+}
+
+/// Inter-request gap used on RTU links when the device config does not set one; gives
+/// RS-485 transceivers time to turn the line around between frames.
+const RTU_DEFAULT_REQUEST_DELAY: Duration = Duration::from_millis(20);
+
+fn is_rtu(config: &ExternalDeviceConfig) -> bool {
+    matches!(
+        config.protocol.as_str(),
+        "modbus_rtu" | "modbus_rtu_over_tcp"
+    )
+}
+
+/// Key of the pooled connection a device polls through. Modbus TCP devices get their own
+/// connection; every unit id on the same serial line or RTU-over-TCP gateway shares one
+/// so their frames never interleave on the bus.
+pub(super) fn session_key(node_id: Uuid, config: &ExternalDeviceConfig) -> String {
+    match config.protocol.as_str() {
+        "modbus_rtu" => format!(
+            "serial:{}",
+            config.serial_port.as_deref().unwrap_or("").trim()
+        ),
+        "modbus_rtu_over_tcp" => format!(
+            "rtu_tcp:{}:{}",
+            config.host.as_deref().unwrap_or("").trim(),
+            config.port.unwrap_or(502)
+        ),
+        _ => format!("node:{node_id}"),
+    }
+}
+
+/// Connection parameters; a change forces a reconnect of the pooled session.
+fn endpoint_key(config: &ExternalDeviceConfig) -> String {
+    match config.protocol.as_str() {
+        "modbus_rtu" => format!(
+            "{}://{}?baud={}&parity={}&data_bits={}&stop_bits={}",
+            config.protocol,
+            config.serial_port.as_deref().unwrap_or("").trim(),
+            config.serial_baud_rate.unwrap_or(9600),
+            config.serial_parity.as_deref().unwrap_or("none"),
+            config.serial_data_bits.unwrap_or(8),
+            config.serial_stop_bits.unwrap_or(1)
+        ),
+        "modbus_rtu_over_tcp" => format!(
+            "{}://{}:{}",
+            config.protocol,
+            config.host.as_deref().unwrap_or(""),
+            config.port.unwrap_or(502)
+        ),
+        _ => format!(
+            "{}://{}:{}/{}",
+            config.protocol,
+            config.host.as_deref().unwrap_or(""),
+            config.port.unwrap_or(502),
+            config
+                .unit_id
+                .map(|unit| unit.to_string())
+                .unwrap_or_default()
+        ),
+    }
+}
+
+async fn connect(config: &ExternalDeviceConfig) -> Result<tokio_modbus::client::Context> {
+    match config.protocol.as_str() {
+        "modbus_rtu" => connect_serial(config),
+        "modbus_rtu_over_tcp" => {
+            let socket_addr = tcp_socket_addr(config)?;
+            let stream = timeout(CONNECT_TIMEOUT, tokio::net::TcpStream::connect(socket_addr))
+                .await
+                .context("modbus RTU-over-TCP connect timed out")?
+                .context("modbus RTU-over-TCP connect failed")?;
+            Ok(tokio_modbus::client::rtu::attach_slave(
+                stream,
+                Slave(config.unit_id.unwrap_or(1)),
+            ))
+        }
+        _ => {
+            let socket_addr = tcp_socket_addr(config)?;
+            let connecting = async {
+                if let Some(unit) = config.unit_id {
+                    tokio_modbus::client::tcp::connect_slave(socket_addr, Slave(unit)).await
+                } else {
+                    tokio_modbus::client::tcp::connect(socket_addr).await
+                }
+            };
+            timeout(CONNECT_TIMEOUT, connecting)
+                .await
+                .context("modbus connect timed out")?
+                .context("modbus connect failed")
+        }
+    }
+}
+
+fn tcp_socket_addr(config: &ExternalDeviceConfig) -> Result<SocketAddr> {
+    let host = config.host.as_ref().context("modbus device missing host")?;
+    let port = config.port.unwrap_or(502);
+    format!("{}:{}", host, port)
+        .parse()
+        .context("invalid modbus socket address")
+}
+
+fn connect_serial(config: &ExternalDeviceConfig) -> Result<tokio_modbus::client::Context> {
+    let path = config
+        .serial_port
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .context("modbus RTU device missing serial_port")?;
+    let parity = match config
+        .serial_parity
+        .as_deref()
+        .map(|value| value.trim().to_ascii_lowercase())
+        .as_deref()
+    {
+        None | Some("") | Some("none") | Some("n") => tokio_serial::Parity::None,
+        Some("even") | Some("e") => tokio_serial::Parity::Even,
+        Some("odd") | Some("o") => tokio_serial::Parity::Odd,
+        Some(other) => return Err(anyhow!("unsupported serial parity {other}")),
+    };
+    let stop_bits = match config.serial_stop_bits.unwrap_or(1) {
+        1 => tokio_serial::StopBits::One,
+        2 => tokio_serial::StopBits::Two,
+        other => return Err(anyhow!("unsupported serial stop bits {other}")),
+    };
+    let data_bits = match config.serial_data_bits.unwrap_or(8) {
+        7 => tokio_serial::DataBits::Seven,
+        8 => tokio_serial::DataBits::Eight,
+        other => return Err(anyhow!("unsupported serial data bits {other}")),
+    };
+    let builder = tokio_serial::new(path, config.serial_baud_rate.unwrap_or(9600))
+        .parity(parity)
+        .stop_bits(stop_bits)
+        .data_bits(data_bits);
+    let port = tokio_serial::SerialStream::open(&builder)
+        .with_context(|| format!("failed to open serial port {path}"))?;
+    Ok(tokio_modbus::client::rtu::attach_slave(
+        port,
+        Slave(config.unit_id.unwrap_or(1)),
+    ))
+}
+
+fn session_for(key: &str, endpoint: &str) -> Arc<tokio::sync::Mutex<ModbusSession>> {
+    let sessions = SESSIONS.get_or_init(|| std::sync::Mutex::new(HashMap::new()));
+    let mut sessions = sessions
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    sessions
+        .entry(key.to_string())
+        .or_insert_with(|| {
+            Arc::new(tokio::sync::Mutex::new(ModbusSession::new(
+                endpoint.to_string(),
+            )))
+        })
+        .clone()
+}
+
+/// Drops pooled connections no active device polls through anymore.
+pub(super) fn retain_sessions(active: &HashSet<String>) {
+    if let Some(sessions) = SESSIONS.get() {
+        let mut sessions = sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        sessions.retain(|key, _| active.contains(key));
+    }
+}
+
+pub(super) struct ModbusReadOutcome<'a> {
+    pub(super) values: Vec<(&'a DevicePoint, f64)>,
+    pub(super) failures: Vec<PointFailure<'a>>,
+}
+
+/// A point that could not be read, and why.
+#[derive(Debug)]
+pub(super) struct PointFailure<'a> {
+    pub(super) point: &'a DevicePoint,
+    pub(super) error: String,
+}
+
+impl std::fmt::Display for PointFailure<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.point.metric, self.error)
+    }
+}
+
+/// Joins failures into one line for logs and poll errors.
+pub(super) fn describe_failures(failures: &[PointFailure<'_>]) -> String {
+    failures
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reads all modbus points for a device over its pooled connection using coalesced
+/// block reads. Returns raw (unscaled) values; per-point failures are collected
+/// rather than aborting the poll.
+pub(super) async fn read_points<'a>(
+    node_id: Uuid,
+    config: &ExternalDeviceConfig,
+    points: &[&'a DevicePoint],
+) -> Result<ModbusReadOutcome<'a>> {
+    let endpoint = endpoint_key(config);
+    let session = session_for(&session_key(node_id, config), &endpoint);
+    let mut session = session.lock().await;
+    if session.endpoint != endpoint {
+        *session = ModbusSession::new(endpoint);
+    }
+    read_with_session(&mut session, config, points).await
+}
+
+/// Like `read_points`, but over a connection opened for this call and closed after
+/// it, so test reads of an unsaved profile leave no pooled session and no reconnect
+/// backoff behind. RTU devices still go through their bus's pooled connection: a
+/// serial line or gateway takes one master at a time.
+pub(super) async fn read_points_once<'a>(
+    config: &ExternalDeviceConfig,
+    points: &[&'a DevicePoint],
+) -> Result<ModbusReadOutcome<'a>> {
+    if is_rtu(config) {
+        return read_points(Uuid::nil(), config, points).await;
+    }
+    let mut session = ModbusSession::new(endpoint_key(config));
+    read_with_session(&mut session, config, points).await
+}
+
+async fn read_with_session<'a>(
+    session: &mut ModbusSession,
+    config: &ExternalDeviceConfig,
+    points: &[&'a DevicePoint],
+) -> Result<ModbusReadOutcome<'a>> {
+    session.ensure_connected(config).await?;
+    if is_rtu(config) {
+        // Bus sessions are shared by several unit ids; address this device's unit.
+        if let Some(ctx) = session.ctx.as_mut() {
+            ctx.set_slave(Slave(config.unit_id.unwrap_or(1)));
+        }
+    }
+
+    let delay = match config.modbus_request_delay_ms {
+        Some(ms) => Duration::from_millis(ms),
+        None if is_rtu(config) => RTU_DEFAULT_REQUEST_DELAY,
+        None => Duration::ZERO,
+    };
+    let plan = plan_reads(
+        points,
+        config
+            .modbus_max_block_registers
+            .unwrap_or(MAX_REGISTERS_PER_READ),
+        config.modbus_max_register_gap.unwrap_or(0),
+    );
+    let mut outcome = ModbusReadOutcome {
+        values: Vec::new(),
+        failures: plan
+            .invalid
+            .into_iter()
+            .map(|(index, error)| PointFailure {
+                point: points[index],
+                error,
+            })
+            .collect(),
+    };
+    let fail = |point: &'a DevicePoint, error: &dyn std::fmt::Display| PointFailure {
+        point,
+        error: error.to_string(),
+    };
+
+    let mut blocks = plan.blocks.into_iter();
+    while let Some(block) = blocks.next() {
+        match session
+            .read_words(block.table, block.start, block.count, delay)
+            .await
+        {
+            Ok(words) => {
+                for member in &block.members {
+                    let offset = (member.address - block.start) as usize;
+                    let point = points[member.index];
+                    match decode_registers(words.get(offset..).unwrap_or(&[]), &member.data_type) {
+                        Ok(value) => outcome.values.push((point, value)),
+                        Err(err) => outcome.failures.push(fail(point, &err)),
+                    }
+                }
+            }
+            Err(ReadError::Exception(message)) if block.members.len() > 1 => {
+                // The span probably crosses an unmapped register; retry point by point.
+                tracing::debug!(
+                    endpoint = %session.endpoint,
+                    start = block.start,
+                    count = block.count,
+                    error = %message,
+                    "modbus block read rejected; falling back to per-point reads"
+                );
+                for member in &block.members {
+                    let point = points[member.index];
+                    match session
+                        .read_words(
+                            block.table,
+                            member.address,
+                            member.data_type.registers,
+                            delay,
+                        )
+                        .await
+                    {
+                        Ok(words) => match decode_registers(&words, &member.data_type) {
+                            Ok(value) => outcome.values.push((point, value)),
+                            Err(err) => outcome.failures.push(fail(point, &err)),
+                        },
+                        Err(err) => outcome.failures.push(fail(point, &err)),
+                    }
+                }
+            }
+            Err(err @ ReadError::Exception(_)) => {
+                for member in &block.members {
+                    outcome.failures.push(fail(points[member.index], &err));
+                }
+            }
+            Err(err) => {
+                // Transport failure: the connection was dropped, so skip the rest of
+                // this poll and reconnect on the next one.
+                let mut skipped = block.members;
+                for rest in blocks.by_ref() {
+                    skipped.extend(rest.members);
+                }
+                for member in &skipped {
+                    outcome.failures.push(fail(points[member.index], &err));
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modbus_point(metric: &str, register: u32, data_type: Option<&str>) -> DevicePoint {
+        DevicePoint {
+            name: metric.to_string(),
+            metric: metric.to_string(),
+            sensor_type: "generic".to_string(),
+            unit: String::new(),
+            protocol: "modbus_tcp".to_string(),
+            register: Some(register),
+            register_type: None,
+            data_type: data_type.map(str::to_string),
+            scale: None,
+            oid: None,
+            path: None,
+            json_pointer: None,
+            bacnet_object: None,
+        }
+    }
+
+    #[test]
+    fn plan_reads_coalesces_contiguous_registers() {
+        let points = [
+            modbus_point("voltage_a", 3028, Some("f32_be")),
+            modbus_point("voltage_b", 3030, Some("f32_be")),
+            modbus_point("voltage_c", 3032, Some("f32_be")),
+            modbus_point("frequency", 3109, Some("f32_be")),
+        ];
+        let refs = points.iter().collect::<Vec<_>>();
+
+        let plan = plan_reads(&refs, MAX_REGISTERS_PER_READ, 0);
+
+        assert!(plan.invalid.is_empty());
+        assert_eq!(plan.blocks.len(), 2);
+        assert_eq!(plan.blocks[0].start, 3027);
+        assert_eq!(plan.blocks[0].count, 6);
+        assert_eq!(plan.blocks[0].members.len(), 3);
+        assert_eq!(plan.blocks[1].start, 3108);
+        assert_eq!(plan.blocks[1].count, 2);
+    }
+
+    #[test]
+    fn plan_reads_bridges_gaps_and_respects_block_limit() {
+        let points = [
+            modbus_point("a", 40001, Some("u16")),
+            modbus_point("b", 40004, Some("u16")),
+            modbus_point("c", 40010, Some("u32")),
+        ];
+        let refs = points.iter().collect::<Vec<_>>();
+
+        let bridged = plan_reads(&refs, MAX_REGISTERS_PER_READ, 8);
+        assert_eq!(bridged.blocks.len(), 1);
+        assert_eq!(bridged.blocks[0].start, 0);
+        assert_eq!(bridged.blocks[0].count, 11);
+
+        let limited = plan_reads(&refs, 4, 8);
+        assert_eq!(limited.blocks.len(), 2);
+        assert_eq!(limited.blocks[0].count, 4);
+        assert_eq!(limited.blocks[1].start, 9);
+    }
+
+    #[test]
+    fn plan_reads_separates_tables_and_reads_unknown_types_as_u16() {
+        let mut coil = modbus_point("pump_running", 1, None);
+        coil.register_type = Some("coil".to_string());
+        let mut discrete = modbus_point("door_open", 10002, None);
+        discrete.register_type = Some("discrete_input".to_string());
+        let points = [
+            coil,
+            discrete,
+            modbus_point("input", 30001, Some("u16")),
+            modbus_point("holding", 40001, Some("u16")),
+            modbus_point("broken", 40002, Some("f128")),
+        ];
+        let refs = points.iter().collect::<Vec<_>>();
+
+        let plan = plan_reads(&refs, MAX_REGISTERS_PER_READ, 0);
+
+        let tables = plan
+            .blocks
+            .iter()
+            .map(|block| block.table)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tables,
+            vec![
+                ModbusTable::Coil,
+                ModbusTable::DiscreteInput,
+                ModbusTable::InputRegister,
+                ModbusTable::HoldingRegister,
+            ]
+        );
+        assert_eq!(plan.blocks[1].start, 1);
+        assert!(plan.invalid.is_empty());
+        let broken = &plan.blocks[3].members[1];
+        assert_eq!(broken.index, 4);
+        assert_eq!(broken.data_type, ModbusDataType::parse("u16").unwrap());
+    }
+
+    #[test]
+    fn resolve_address_keeps_legacy_convention() {
+        assert_eq!(
+            resolve_address(30104, None).unwrap(),
+            (ModbusTable::InputRegister, 103)
+        );
+        assert_eq!(
+            resolve_address(40001, None).unwrap(),
+            (ModbusTable::HoldingRegister, 0)
+        );
+        assert_eq!(
+            resolve_address(902, None).unwrap(),
+            (ModbusTable::HoldingRegister, 901)
+        );
+        assert_eq!(
+            resolve_address(902, Some("input")).unwrap(),
+            (ModbusTable::InputRegister, 901)
+        );
+        assert!(resolve_address(1, Some("analog")).is_err());
+    }
+
+    #[test]
+    fn decode_registers_handles_byte_orders() {
+        // 123.456f32 == 0x42F6E979
+        let be = [0x42F6, 0xE979];
+        let value = decode_registers(&be, &ModbusDataType::parse("f32_be").unwrap()).unwrap();
+        assert!((value - 123.456).abs() < 1e-3);
+        let ws = [0xE979, 0x42F6];
+        let value = decode_registers(&ws, &ModbusDataType::parse("f32_ws").unwrap()).unwrap();
+        assert!((value - 123.456).abs() < 1e-3);
+        let le = [0x79E9, 0xF642];
+        let value = decode_registers(&le, &ModbusDataType::parse("f32_le").unwrap()).unwrap();
+        assert!((value - 123.456).abs() < 1e-3);
+        let bs = [0xF642, 0x79E9];
+        let value = decode_registers(&bs, &ModbusDataType::parse("f32_bs").unwrap()).unwrap();
+        assert!((value - 123.456).abs() < 1e-3);
+    }
+
+    #[test]
+    fn decode_registers_handles_wide_and_string_types() {
+        let words = [0x0000, 0x0001, 0x0000, 0x0002];
+        let value = decode_registers(&words, &ModbusDataType::parse("u64").unwrap()).unwrap();
+        assert_eq!(value, 4_294_967_298.0);
+
+        let bits = 1234.5f64.to_bits();
+        let words = [
+            (bits >> 48) as u16,
+            (bits >> 32) as u16,
+            (bits >> 16) as u16,
+            bits as u16,
+        ];
+        let value = decode_registers(&words, &ModbusDataType::parse("f64").unwrap()).unwrap();
+        assert_eq!(value, 1234.5);
+
+        let words = [u16::from_be_bytes(*b"12"), u16::from_be_bytes(*b".5"), 0];
+        let value = decode_registers(&words, &ModbusDataType::parse("string:3").unwrap()).unwrap();
+        assert_eq!(value, 12.5);
+
+        assert_eq!(
+            decode_registers(&[0xFFFF], &ModbusDataType::parse("i16").unwrap()).unwrap(),
+            -1.0
+        );
+        assert!(ModbusDataType::parse("string").is_err());
+    }
+
+    #[test]
+    fn rtu_devices_share_a_session_per_bus() {
+        let mut meter_a = rtu_config("/dev/ttyUSB0", 1);
+        let meter_b = rtu_config("/dev/ttyUSB0", 2);
+        let other_bus = rtu_config("/dev/ttyUSB1", 1);
+        let node_a = Uuid::new_v4();
+        let node_b = Uuid::new_v4();
+
+        assert_eq!(session_key(node_a, &meter_a), session_key(node_b, &meter_b));
+        assert_ne!(
+            session_key(node_a, &meter_a),
+            session_key(node_a, &other_bus)
+        );
+        assert_eq!(endpoint_key(&meter_a), endpoint_key(&meter_b));
+
+        meter_a.protocol = "modbus_tcp".to_string();
+        meter_a.host = Some("192.168.1.20".to_string());
+        assert_ne!(session_key(node_a, &meter_a), session_key(node_b, &meter_a));
+    }
+
+    /// Modbus TCP server answering FC03 with each register's address as its value;
+    /// registers from 100 up raise "illegal data address".
+    async fn spawn_tcp_server() -> SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut request = [0u8; 12];
+                    while stream.read_exact(&mut request).await.is_ok() {
+                        let start = u16::from_be_bytes([request[8], request[9]]);
+                        let count = u16::from_be_bytes([request[10], request[11]]);
+                        let mut pdu = Vec::new();
+                        if start >= 100 {
+                            pdu.extend_from_slice(&[request[7] | 0x80, 0x02]);
+                        } else {
+                            pdu.extend_from_slice(&[request[7], (count * 2) as u8]);
+                            for register in start..start + count {
+                                pdu.extend_from_slice(&register.to_be_bytes());
+                            }
+                        }
+                        let mut response = request[..4].to_vec();
+                        response.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+                        response.push(request[6]);
+                        response.extend_from_slice(&pdu);
+                        if stream.write_all(&response).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn one_shot_read_reports_failures_per_point() {
+        let addr = spawn_tcp_server().await;
+        let config = ExternalDeviceConfig {
+            protocol: "modbus_tcp".to_string(),
+            host: Some(addr.ip().to_string()),
+            port: Some(addr.port()),
+            unit_id: Some(1),
+            ..Default::default()
+        };
+        let points = [
+            modbus_point("voltage", 40006, Some("u16")),
+            modbus_point("unmapped", 40201, Some("u16")),
+        ];
+        let refs = points.iter().collect::<Vec<_>>();
+
+        let outcome = read_points_once(&config, &refs).await.unwrap();
+        assert_eq!(outcome.values.len(), 1);
+        assert_eq!(outcome.values[0].0.metric, "voltage");
+        assert_eq!(outcome.values[0].1, 5.0);
+        assert_eq!(outcome.failures.len(), 1);
+        assert_eq!(outcome.failures[0].point.metric, "unmapped");
+        assert!(
+            outcome.failures[0].error.contains("exception"),
+            "{}",
+            outcome.failures[0]
+        );
+    }
+
+    fn rtu_config(serial_port: &str, unit_id: u8) -> ExternalDeviceConfig {
+        ExternalDeviceConfig {
+            vendor_id: "generic".to_string(),
+            model_id: "generic_modbus_meter".to_string(),
+            protocol: "modbus_rtu".to_string(),
+            unit_id: Some(unit_id),
+            modbus_request_delay_ms: Some(0),
+            serial_port: Some(serial_port.to_string()),
+            serial_baud_rate: Some(19200),
+            serial_parity: Some("even".to_string()),
+            serial_stop_bits: Some(1),
+            serial_data_bits: Some(8),
+            ..Default::default()
+        }
+    }
+
+    fn modbus_crc16(frame: &[u8]) -> u16 {
+        let mut crc = 0xFFFFu16;
+        for byte in frame {
+            crc ^= *byte as u16;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xA001
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        crc
+    }
+
+    /// Emulates RTU slaves behind the master side of a pseudo-terminal so the serial
+    /// transport can be exercised without hardware. Holding register `n` of unit `u`
+    /// reads as `u * 1000 + n`.
+    #[cfg(unix)]
+    struct PtyRtuBus {
+        path: String,
+        _slave: std::fs::File,
+        requests: Arc<std::sync::Mutex<Vec<(u8, u16, u16)>>>,
+    }
+
+    #[cfg(unix)]
+    impl PtyRtuBus {
+        fn spawn() -> Option<Self> {
+            use std::io::{Read, Write};
+            use std::os::fd::FromRawFd;
+
+            let mut master: libc::c_int = -1;
+            let mut slave: libc::c_int = -1;
+            // SAFETY: openpty writes two fds into the provided locations.
+            let rc = unsafe {
+                libc::openpty(
+                    &mut master,
+                    &mut slave,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+            };
+            if rc != 0 {
+                // Sandboxes without /dev/ptmx cannot run this harness.
+                return None;
+            }
+            // SAFETY: both fds are valid and owned from here on.
+            let (path, slave, mut master) = unsafe {
+                let mut termios: libc::termios = std::mem::zeroed();
+                libc::tcgetattr(slave, &mut termios);
+                libc::cfmakeraw(&mut termios);
+                libc::tcsetattr(slave, libc::TCSANOW, &termios);
+                let path = std::ffi::CStr::from_ptr(libc::ptsname(master))
+                    .to_string_lossy()
+                    .into_owned();
+                (
+                    path,
+                    std::fs::File::from_raw_fd(slave),
+                    std::fs::File::from_raw_fd(master),
+                )
+            };
+
+            let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let seen = requests.clone();
+            std::thread::spawn(move || {
+                let mut pending = Vec::new();
+                let mut buf = [0u8; 256];
+                loop {
+                    let read = match master.read(&mut buf) {
+                        Ok(0) | Err(_) => return,
+                        Ok(read) => read,
+                    };
+                    pending.extend_from_slice(&buf[..read]);
+                    while pending.len() >= 8 {
+                        let frame = pending.drain(..8).collect::<Vec<_>>();
+                        let crc = u16::from_le_bytes([frame[6], frame[7]]);
+                        if frame[1] != 0x03 || crc != modbus_crc16(&frame[..6]) {
+                            pending.clear();
+                            break;
+                        }
+                        let unit = frame[0];
+                        let start = u16::from_be_bytes([frame[2], frame[3]]);
+                        let count = u16::from_be_bytes([frame[4], frame[5]]);
+                        seen.lock().unwrap().push((unit, start, count));
+                        let mut response = vec![unit, 0x03, (count * 2) as u8];
+                        for address in start..start + count {
+                            let value = unit as u16 * 1000 + address;
+                            response.extend_from_slice(&value.to_be_bytes());
+                        }
+                        let crc = modbus_crc16(&response);
+                        response.extend_from_slice(&crc.to_le_bytes());
+                        if master.write_all(&response).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+
+            Some(Self {
+                path,
+                _slave: slave,
+                requests,
+            })
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rtu_polls_multiple_units_over_one_pty_bus() {
+        let Some(bus) = PtyRtuBus::spawn() else {
+            return;
+        };
+        let points = [
+            modbus_point("voltage", 40001, Some("u16")),
+            modbus_point("current", 40002, Some("u16")),
+            modbus_point("energy", 40010, Some("u32")),
+        ];
+        let refs = points.iter().collect::<Vec<_>>();
+
+        for unit in [1u8, 7u8] {
+            let config = rtu_config(&bus.path, unit);
+            let outcome = read_points(Uuid::new_v4(), &config, &refs)
+                .await
+                .expect("rtu poll over pty");
+            assert!(outcome.failures.is_empty(), "{:?}", outcome.failures);
+            let values = outcome
+                .values
+                .iter()
+                .map(|(point, value)| (point.metric.as_str(), *value))
+                .collect::<HashMap<_, _>>();
+            let base = unit as f64 * 1000.0;
+            assert_eq!(values["voltage"], base);
+            assert_eq!(values["current"], base + 1.0);
+            let hi = base + 9.0;
+            let lo = base + 10.0;
+            assert_eq!(values["energy"], hi * 65536.0 + lo);
+        }
+
+        let requests = bus.requests.lock().unwrap().clone();
+        assert_eq!(
+            requests,
+            vec![(1, 0, 2), (1, 9, 2), (7, 0, 2), (7, 9, 2)],
+            "each unit should be read with two coalesced block requests"
+        );
+    }
+
+    #[test]
+    fn reconnect_backoff_grows_and_caps() {
+        assert_eq!(reconnect_backoff(1), Duration::from_secs(2));
+        assert_eq!(reconnect_backoff(3), Duration::from_secs(8));
+        assert_eq!(reconnect_backoff(40), RECONNECT_BACKOFF_MAX);
+    }
+}
@@ -0,0 +1,901 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use sqlx::types::Json as SqlJson;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{insert_metric, slugify_metric, ExternalDeviceConfig};
+use crate::device_catalog::DevicePoint;
+use crate::ids;
+use crate::services::mqtt::MqttPublisher;
+use crate::state::AppState;
+
+/// Home Assistant publishes entity configs as `<prefix>/<component>/[<node_id>/]<object_id>/config`.
+const DISCOVERY_FILTERS: &[&str] = &["homeassistant/+/+/config", "homeassistant/+/+/+/config"];
+const BASE_TOPIC_PLACEHOLDER: &str = "{base_topic}";
+const SUBSCRIPTION_SYNC_INTERVAL: Duration = Duration::from_secs(5);
+/// Tasmota and Shelly report telemetry every few minutes by default, so a device is
+/// only treated as silent after a generous window.
+const MIN_STALE_AFTER: Duration = Duration::from_secs(600);
+
+static ROUTES: OnceLock<std::sync::Mutex<HashMap<Uuid, DeviceRoutes>>> = OnceLock::new();
+static DISCOVERY: OnceLock<std::sync::Mutex<HashMap<String, MqttDiscoveredEntity>>> =
+    OnceLock::new();
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+/// A command topic exposed as an output row. `{base_topic}` is substituted from the
+/// device config so catalog models can describe Tasmota/Shelly style topics.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct MqttOutputConfig {
+    pub id: String,
+    pub name: String,
+    pub command_topic: String,
+    #[serde(default)]
+    pub state_topic: Option<String>,
+    #[serde(default)]
+    pub state_json_pointer: Option<String>,
+    #[serde(default)]
+    pub payload_on: Option<String>,
+    #[serde(default)]
+    pub payload_off: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct MqttDiscoveredEntity {
+    pub discovery_topic: String,
+    pub component: String,
+    pub object_id: String,
+    pub unique_id: Option<String>,
+    pub name: Option<String>,
+    pub state_topic: Option<String>,
+    /// JSON pointer derived from a simple `value_template` such as
+    /// `{{ value_json.ENERGY.Power }}`; `None` means the raw payload is the value.
+    pub json_pointer: Option<String>,
+    pub unit: Option<String>,
+    pub device_class: Option<String>,
+    pub command_topic: Option<String>,
+    pub payload_on: Option<String>,
+    pub payload_off: Option<String>,
+    pub device_identifier: String,
+    pub device_name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct MqttDiscoveredDevice {
+    pub identifier: String,
+    pub name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub entities: Vec<MqttDiscoveredEntity>,
+}
+
+struct DeviceRoutes {
+    model_id: String,
+    routes: Vec<Route>,
+    registered_at: Instant,
+    last_message: Option<Instant>,
+}
+
+#[derive(Clone)]
+enum RouteTarget {
+    Metric {
+        metric: String,
+        scale: f64,
+    },
+    OutputState {
+        output_id: String,
+        payload_on: String,
+        payload_off: String,
+    },
+}
+
+#[derive(Clone)]
+struct Route {
+    topic: String,
+    json_pointer: Option<String>,
+    target: RouteTarget,
+}
+
+pub struct MqttDeviceIngestService {
+    state: AppState,
+}
+
+impl MqttDeviceIngestService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    pub fn start(self, cancel: CancellationToken) {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            loop {
+                if cancel.is_cancelled() {
+                    break;
+                }
+                if let Err(err) = run_once(&state, cancel.clone()).await {
+                    CONNECTED.store(false, Ordering::Relaxed);
+                    warn!("mqtt device ingest loop failed: {err:#}");
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                }
+            }
+        });
+    }
+}
+
+async fn run_once(state: &AppState, cancel: CancellationToken) -> Result<()> {
+    let mut options = MqttOptions::new(
+        "farmdashboard-core-device-ingest",
+        &state.config.mqtt_host,
+        state.config.mqtt_port,
+    );
+    options.set_keep_alive(Duration::from_secs(10));
+    if let (Some(username), Some(password)) = (
+        state.config.mqtt_username.as_deref(),
+        state.config.mqtt_password.as_deref(),
+    ) {
+        options.set_credentials(username, password);
+    }
+
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+    for filter in DISCOVERY_FILTERS {
+        client.subscribe(*filter, QoS::AtLeastOnce).await?;
+    }
+    let mut subscribed: HashSet<String> = HashSet::new();
+    let mut ticker = tokio::time::interval(SUBSCRIPTION_SYNC_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                break;
+            }
+            _ = ticker.tick() => {
+                sync_subscriptions(&client, &mut subscribed);
+            }
+            event = eventloop.poll() => {
+                match event {
+                    Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                        CONNECTED.store(true, Ordering::Relaxed);
+                    }
+                    Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                        handle_publish(state, publish.topic.as_str(), publish.payload.as_ref()).await;
+                    }
+                    Ok(Event::Incoming(Incoming::Disconnect)) => anyhow::bail!("mqtt disconnected"),
+                    Ok(_) => {}
+                    Err(err) => {
+                        anyhow::bail!(err);
+                    }
+                }
+            }
+        }
+    }
+
+    CONNECTED.store(false, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Subscribes to the state topics of registered devices. `try_*` keeps the select
+/// loop from blocking on a full request queue; anything skipped is retried next tick.
+fn sync_subscriptions(client: &AsyncClient, subscribed: &mut HashSet<String>) {
+    let wanted = routes()
+        .lock()
+        .map(|routes| {
+            routes
+                .values()
+                .flat_map(|device| device.routes.iter().map(|route| route.topic.clone()))
+                .collect::<HashSet<_>>()
+        })
+        .unwrap_or_default();
+    for topic in wanted.difference(&subscribed.clone()) {
+        if client
+            .try_subscribe(topic.as_str(), QoS::AtLeastOnce)
+            .is_ok()
+        {
+            subscribed.insert(topic.clone());
+        }
+    }
+    for topic in subscribed.clone().difference(&wanted) {
+        if client.try_unsubscribe(topic.as_str()).is_ok() {
+            subscribed.remove(topic);
+        }
+    }
+}
+
+async fn handle_publish(state: &AppState, topic: &str, payload: &[u8]) {
+    if DISCOVERY_FILTERS
+        .iter()
+        .any(|filter| topic_matches(filter, topic))
+    {
+        handle_discovery(topic, payload);
+        return;
+    }
+
+    let mut matched: Vec<(Uuid, String, RouteTarget, Option<f64>, String)> = Vec::new();
+    if let Ok(mut routes) = routes().lock() {
+        for (node_id, device) in routes.iter_mut() {
+            let mut hit = false;
+            for route in device
+                .routes
+                .iter()
+                .filter(|r| topic_matches(&r.topic, topic))
+            {
+                hit = true;
+                let raw = extract_raw(payload, route.json_pointer.as_deref());
+                let value = raw.as_deref().and_then(decode_value);
+                matched.push((
+                    *node_id,
+                    device.model_id.clone(),
+                    route.target.clone(),
+                    value,
+                    raw.unwrap_or_default(),
+                ));
+            }
+            if hit {
+                device.last_message = Some(Instant::now());
+            }
+        }
+    }
+
+    let now = Utc::now();
+    for (node_id, model_id, target, value, raw) in matched {
+        let result = match target {
+            RouteTarget::Metric { metric, scale } => match value {
+                Some(value) => {
+                    insert_metric(state, now, node_id, &model_id, &metric, value * scale).await
+                }
+                None => Ok(()),
+            },
+            RouteTarget::OutputState {
+                output_id,
+                payload_on,
+                payload_off,
+            } => {
+                let output_state = if raw.eq_ignore_ascii_case(&payload_on) {
+                    Some("on")
+                } else if raw.eq_ignore_ascii_case(&payload_off) {
+                    Some("off")
+                } else {
+                    value.map(|value| if value != 0.0 { "on" } else { "off" })
+                };
+                match output_state {
+                    Some(output_state) => {
+                        update_output_state(state, &output_id, output_state).await
+                    }
+                    None => Ok(()),
+                }
+            }
+        };
+        if let Err(err) = result {
+            warn!(node_id = %node_id, topic, "failed to store mqtt device value: {err:#}");
+        }
+    }
+}
+
+async fn update_output_state(state: &AppState, output_id: &str, output_state: &str) -> Result<()> {
+    sqlx::query("UPDATE outputs SET state = $2 WHERE id = $1 AND state IS DISTINCT FROM $2")
+        .bind(output_id)
+        .bind(output_state)
+        .execute(&state.db)
+        .await
+        .context("failed to update mqtt output state")?;
+    Ok(())
+}
+
+fn handle_discovery(topic: &str, payload: &[u8]) {
+    let Ok(mut cache) = discovery().lock() else {
+        return;
+    };
+    // An empty retained message is how Home Assistant removes an entity.
+    if payload.iter().all(u8::is_ascii_whitespace) {
+        cache.remove(topic);
+        return;
+    }
+    match parse_discovery_message(topic, payload) {
+        Some(entity) => {
+            cache.insert(topic.to_string(), entity);
+        }
+        None => {
+            tracing::debug!(
+                topic,
+                "ignoring unparseable Home Assistant discovery message"
+            );
+        }
+    }
+}
+
+fn routes() -> &'static std::sync::Mutex<HashMap<Uuid, DeviceRoutes>> {
+    ROUTES.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+fn discovery() -> &'static std::sync::Mutex<HashMap<String, MqttDiscoveredEntity>> {
+    DISCOVERY.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+pub fn resolve_topic(topic: &str, config: &ExternalDeviceConfig) -> String {
+    match config
+        .mqtt_base_topic
+        .as_deref()
+        .map(|value| value.trim().trim_matches('/'))
+        .filter(|value| !value.is_empty())
+    {
+        Some(base) => topic.trim().replace(BASE_TOPIC_PLACEHOLDER, base),
+        None => topic.trim().to_string(),
+    }
+}
+
+fn validate_topic(topic: &str) -> Result<()> {
+    if topic.is_empty() {
+        return Err(anyhow!("MQTT topic is empty"));
+    }
+    if topic.contains(BASE_TOPIC_PLACEHOLDER) {
+        return Err(anyhow!(
+            "topic {topic} uses {BASE_TOPIC_PLACEHOLDER} but mqtt_base_topic is not set"
+        ));
+    }
+    Ok(())
+}
+
+pub fn output_id(node_id: Uuid, output: &MqttOutputConfig) -> String {
+    ids::stable_hex_id("external_output", &format!("{node_id}:{}", output.id))
+}
+
+/// Records which topics feed which sensors and outputs for this device so the ingest
+/// loop subscribes to them, then reports whether the device has been heard from.
+pub(super) fn register_device(
+    node_id: Uuid,
+    config: &ExternalDeviceConfig,
+    points: &[&DevicePoint],
+    stale_after: Duration,
+) -> Result<()> {
+    let mut device_routes = Vec::new();
+    for point in points {
+        let topic = resolve_topic(point.path.as_deref().unwrap_or(""), config);
+        validate_topic(&topic).with_context(|| format!("point {}", point.metric))?;
+        device_routes.push(Route {
+            topic,
+            json_pointer: point.json_pointer.clone().filter(|p| !p.is_empty()),
+            target: RouteTarget::Metric {
+                metric: point.metric.clone(),
+                scale: point.scale.unwrap_or(1.0),
+            },
+        });
+    }
+    for output in config.mqtt_outputs.iter().flatten() {
+        let Some(state_topic) = output.state_topic.as_deref() else {
+            continue;
+        };
+        let topic = resolve_topic(state_topic, config);
+        validate_topic(&topic).with_context(|| format!("output {}", output.id))?;
+        device_routes.push(Route {
+            topic,
+            json_pointer: output.state_json_pointer.clone().filter(|p| !p.is_empty()),
+            target: RouteTarget::OutputState {
+                output_id: output_id(node_id, output),
+                payload_on: output
+                    .payload_on
+                    .clone()
+                    .unwrap_or_else(|| "ON".to_string()),
+                payload_off: output
+                    .payload_off
+                    .clone()
+                    .unwrap_or_else(|| "OFF".to_string()),
+            },
+        });
+    }
+    if device_routes.is_empty() {
+        return Err(anyhow!("MQTT device has no state topics configured"));
+    }
+
+    let mut routes = routes()
+        .lock()
+        .map_err(|_| anyhow!("mqtt route table poisoned"))?;
+    let entry = routes.entry(node_id).or_insert_with(|| DeviceRoutes {
+        model_id: config.model_id.clone(),
+        routes: Vec::new(),
+        registered_at: Instant::now(),
+        last_message: None,
+    });
+    entry.model_id = config.model_id.clone();
+    entry.routes = device_routes;
+
+    if !CONNECTED.load(Ordering::Relaxed) {
+        return Err(anyhow!("not connected to the MQTT broker"));
+    }
+    let last_heard = entry.last_message.unwrap_or(entry.registered_at);
+    let stale_after = stale_after.max(MIN_STALE_AFTER);
+    if last_heard.elapsed() > stale_after {
+        return Err(anyhow!(
+            "no MQTT messages received on the device topics for {}s",
+            last_heard.elapsed().as_secs()
+        ));
+    }
+    Ok(())
+}
+
+pub(super) fn retain_devices(active: &HashSet<Uuid>) {
+    if let Ok(mut routes) = routes().lock() {
+        routes.retain(|node_id, _| active.contains(node_id));
+    }
+}
+
+/// Upserts one output row per configured command topic so MQTT relays can be driven
+/// from the outputs API and schedules.
+pub(super) async fn ensure_outputs(
+    state: &AppState,
+    node_id: Uuid,
+    config: &ExternalDeviceConfig,
+) -> Result<()> {
+    for output in config.mqtt_outputs.iter().flatten() {
+        let command_topic = resolve_topic(&output.command_topic, config);
+        validate_topic(&command_topic).with_context(|| format!("output {}", output.id))?;
+        sqlx::query(
+            r#"
+            INSERT INTO outputs (id, node_id, name, type, state, supported_states, config)
+            VALUES ($1, $2, $3, 'relay', 'unknown', $4, $5)
+            ON CONFLICT (id) DO UPDATE SET
+                node_id = EXCLUDED.node_id,
+                name = EXCLUDED.name,
+                config = outputs.config || EXCLUDED.config
+            "#,
+        )
+        .bind(output_id(node_id, output))
+        .bind(node_id)
+        .bind(&output.name)
+        .bind(SqlJson(vec!["on".to_string(), "off".to_string()]))
+        .bind(SqlJson(json!({
+            "source": "external_device",
+            "protocol": "mqtt",
+            "command_topic": command_topic,
+            "command_payloads": {
+                "on": output.payload_on.as_deref().unwrap_or("ON"),
+                "off": output.payload_off.as_deref().unwrap_or("OFF"),
+            },
+        })))
+        .execute(&state.db)
+        .await
+        .with_context(|| format!("failed to upsert mqtt output {}", output.id))?;
+    }
+    Ok(())
+}
+
+/// Publishes an output command to the device's own command topic. Returns `false`
+/// for outputs that are not backed by an MQTT external device.
+pub async fn publish_output_command(
+    mqtt: &MqttPublisher,
+    output_config: &JsonValue,
+    desired: &str,
+) -> Result<bool> {
+    if output_config.get("protocol").and_then(JsonValue::as_str) != Some("mqtt") {
+        return Ok(false);
+    }
+    let Some(topic) = output_config
+        .get("command_topic")
+        .and_then(JsonValue::as_str)
+        .filter(|topic| !topic.is_empty())
+    else {
+        return Ok(false);
+    };
+    let payload = output_config
+        .get("command_payloads")
+        .and_then(|payloads| payloads.get(desired.to_ascii_lowercase()))
+        .and_then(JsonValue::as_str)
+        .unwrap_or(desired);
+    mqtt.publish_raw(topic, payload.as_bytes().to_vec()).await?;
+    Ok(true)
+}
+
+pub fn discovered_devices() -> Vec<MqttDiscoveredDevice> {
+    let entities = discovery()
+        .lock()
+        .map(|cache| cache.values().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut devices: BTreeMap<String, MqttDiscoveredDevice> = BTreeMap::new();
+    for entity in entities {
+        let device = devices
+            .entry(entity.device_identifier.clone())
+            .or_insert_with(|| MqttDiscoveredDevice {
+                identifier: entity.device_identifier.clone(),
+                name: entity.device_name.clone(),
+                manufacturer: entity.manufacturer.clone(),
+                model: entity.model.clone(),
+                entities: Vec::new(),
+            });
+        device.entities.push(entity);
+    }
+    let mut devices = devices.into_values().collect::<Vec<_>>();
+    for device in &mut devices {
+        device
+            .entities
+            .sort_by(|a, b| a.discovery_topic.cmp(&b.discovery_topic));
+    }
+    devices
+}
+
+/// Builds points and outputs from the Home Assistant entities announced for
+/// `mqtt_device_identifier`.
+pub(super) fn discover_points(
+    config: &ExternalDeviceConfig,
+) -> (Vec<DevicePoint>, Vec<MqttOutputConfig>) {
+    let Some(identifier) = config
+        .mqtt_device_identifier
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    else {
+        return (Vec::new(), Vec::new());
+    };
+    let entities = discovered_devices()
+        .into_iter()
+        .find(|device| device.identifier == identifier)
+        .map(|device| device.entities)
+        .unwrap_or_default();
+    entities_to_points(&entities)
+}
+
+fn entities_to_points(
+    entities: &[MqttDiscoveredEntity],
+) -> (Vec<DevicePoint>, Vec<MqttOutputConfig>) {
+    let mut points: Vec<DevicePoint> = Vec::new();
+    let mut outputs = Vec::new();
+    for entity in entities {
+        let label = entity
+            .name
+            .clone()
+            .unwrap_or_else(|| entity.object_id.clone());
+        let slug = slugify_metric(&format!("{}_{}", entity.component, entity.object_id));
+        let switchable = matches!(entity.component.as_str(), "switch" | "light" | "fan");
+        if let (true, Some(command_topic)) = (switchable, entity.command_topic.as_ref()) {
+            outputs.push(MqttOutputConfig {
+                id: slug.clone(),
+                name: label.clone(),
+                command_topic: command_topic.clone(),
+                state_topic: entity.state_topic.clone(),
+                state_json_pointer: entity.json_pointer.clone(),
+                payload_on: entity.payload_on.clone(),
+                payload_off: entity.payload_off.clone(),
+            });
+            continue;
+        }
+        let Some(state_topic) = entity.state_topic.as_ref() else {
+            continue;
+        };
+        if !matches!(entity.component.as_str(), "sensor" | "binary_sensor") {
+            continue;
+        }
+        if points.iter().any(|point| point.metric == slug) {
+            continue;
+        }
+        let (sensor_type, default_unit) = if entity.component == "binary_sensor" {
+            ("status", "")
+        } else {
+            sensor_type_for_device_class(entity.device_class.as_deref())
+        };
+        points.push(DevicePoint {
+            name: label,
+            metric: slug,
+            sensor_type: sensor_type.to_string(),
+            unit: entity
+                .unit
+                .clone()
+                .unwrap_or_else(|| default_unit.to_string()),
+            protocol: "mqtt".to_string(),
+            register: None,
+            register_type: None,
+            data_type: None,
+            scale: None,
+            oid: None,
+            path: Some(state_topic.clone()),
+            json_pointer: entity.json_pointer.clone(),
+            bacnet_object: None,
+        });
+    }
+    (points, outputs)
+}
+
+fn sensor_type_for_device_class(device_class: Option<&str>) -> (&'static str, &'static str) {
+    match device_class.unwrap_or("") {
+        "temperature" => ("temperature", "degC"),
+        "humidity" | "battery" | "moisture" => ("percentage", "%"),
+        "voltage" => ("voltage", "V"),
+        "current" => ("current", "A"),
+        "power" => ("power", "W"),
+        "energy" => ("energy", "kWh"),
+        "frequency" => ("frequency", "Hz"),
+        "power_factor" => ("power_factor", ""),
+        _ => ("generic", ""),
+    }
+}
+
+fn parse_discovery_message(topic: &str, payload: &[u8]) -> Option<MqttDiscoveredEntity> {
+    let segments = topic.split('/').collect::<Vec<_>>();
+    let (component, object_id) = match segments.as_slice() {
+        [_, component, object_id, "config"] => (*component, object_id.to_string()),
+        [_, component, node_id, object_id, "config"] => {
+            (*component, format!("{node_id}_{object_id}"))
+        }
+        _ => return None,
+    };
+    let value: JsonValue = serde_json::from_slice(payload).ok()?;
+    let object = value.as_object()?;
+    let base = string_field(object, &["~"]);
+    let topic_field = |keys: &[&str]| {
+        string_field(object, keys).map(|topic| expand_base_topic(&topic, base.as_deref()))
+    };
+
+    let device = object
+        .get("device")
+        .or_else(|| object.get("dev"))
+        .and_then(JsonValue::as_object);
+    let unique_id = string_field(object, &["unique_id", "uniq_id"]);
+    let device_identifier = device
+        .and_then(|device| device.get("identifiers").or_else(|| device.get("ids")))
+        .and_then(|ids| match ids {
+            JsonValue::Array(items) => items
+                .first()
+                .and_then(JsonValue::as_str)
+                .map(str::to_string),
+            JsonValue::String(id) => Some(id.clone()),
+            _ => None,
+        })
+        .or_else(|| unique_id.clone())
+        .unwrap_or_else(|| object_id.clone());
+
+    let json_pointer = match string_field(object, &["value_template", "val_tpl"]) {
+        Some(template) => Some(template_to_json_pointer(&template)?),
+        None => None,
+    };
+
+    Some(MqttDiscoveredEntity {
+        discovery_topic: topic.to_string(),
+        component: component.to_string(),
+        object_id,
+        unique_id,
+        name: string_field(object, &["name"]),
+        state_topic: topic_field(&["state_topic", "stat_t"]),
+        json_pointer,
+        unit: string_field(object, &["unit_of_measurement", "unit_of_meas"]),
+        device_class: string_field(object, &["device_class", "dev_cla"]),
+        command_topic: topic_field(&["command_topic", "cmd_t"]),
+        payload_on: string_field(object, &["payload_on", "pl_on"]),
+        payload_off: string_field(object, &["payload_off", "pl_off"]),
+        device_identifier,
+        device_name: device.and_then(|device| string_field(device, &["name"])),
+        manufacturer: device.and_then(|device| string_field(device, &["manufacturer", "mf"])),
+        model: device.and_then(|device| string_field(device, &["model", "mdl"])),
+    })
+}
+
+fn string_field(object: &JsonMap<String, JsonValue>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| match object.get(*key) {
+        Some(JsonValue::String(value)) if !value.trim().is_empty() => {
+            Some(value.trim().to_string())
+        }
+        Some(JsonValue::Number(value)) => Some(value.to_string()),
+        Some(JsonValue::Bool(value)) => Some(value.to_string()),
+        _ => None,
+    })
+}
+
+/// Home Assistant's `~` abbreviation stands for the base topic at either end.
+fn expand_base_topic(topic: &str, base: Option<&str>) -> String {
+    let Some(base) = base else {
+        return topic.to_string();
+    };
+    if let Some(rest) = topic.strip_prefix('~') {
+        format!("{base}{rest}")
+    } else if let Some(rest) = topic.strip_suffix('~') {
+        format!("{rest}{base}")
+    } else {
+        topic.to_string()
+    }
+}
+
+/// Converts the common `{{ value_json.a.b }}` / `{{ value_json['a'][0] }}` templates
+/// into a JSON pointer. `{{ value }}` maps to the raw payload; anything that needs
+/// real Jinja evaluation returns `None`.
+fn template_to_json_pointer(template: &str) -> Option<String> {
+    let inner = template
+        .trim()
+        .strip_prefix("{{")?
+        .strip_suffix("}}")?
+        .trim();
+    // Trailing filters like `| float` or `| round(1)` only coerce the number.
+    let expr = inner.split('|').next()?.trim();
+    if expr == "value" {
+        return Some(String::new());
+    }
+    let mut rest = expr.strip_prefix("value_json")?;
+    let mut pointer = String::new();
+    while !rest.is_empty() {
+        let segment;
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            segment = &after[..end];
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            segment = after[..end]
+                .trim()
+                .trim_matches(|ch: char| ch == '\'' || ch == '"');
+            rest = &after[end + 1..];
+        } else {
+            return None;
+        }
+        if segment.is_empty()
+            || !segment
+                .chars()
+                .all(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '-' | ' ' | ':'))
+        {
+            return None;
+        }
+        pointer.push('/');
+        pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+    }
+    Some(pointer)
+}
+
+/// MQTT topic filter matching with `+` (one level) and `#` (remaining levels).
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(expected), Some(actual)) if expected == actual => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Selects the value text from a payload: the JSON pointer target when one is set,
+/// otherwise the whole payload.
+fn extract_raw(payload: &[u8], json_pointer: Option<&str>) -> Option<String> {
+    let text = std::str::from_utf8(payload).ok()?.trim();
+    let Some(pointer) = json_pointer.filter(|pointer| !pointer.is_empty()) else {
+        return Some(text.to_string());
+    };
+    let value: JsonValue = serde_json::from_str(text).ok()?;
+    match value.pointer(pointer)? {
+        JsonValue::String(value) => Some(value.trim().to_string()),
+        JsonValue::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+fn decode_value(raw: &str) -> Option<f64> {
+    if let Ok(value) = raw.parse::<f64>() {
+        return value.is_finite().then_some(value);
+    }
+    match raw.to_ascii_lowercase().as_str() {
+        "on" | "true" | "open" | "online" | "detected" | "wet" => Some(1.0),
+        "off" | "false" | "closed" | "offline" | "clear" | "dry" => Some(0.0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topic_filters_match_wildcards() {
+        assert!(topic_matches(
+            "homeassistant/+/+/config",
+            "homeassistant/sensor/plug_1/config"
+        ));
+        assert!(!topic_matches(
+            "homeassistant/+/+/config",
+            "homeassistant/sensor/node/plug_1/config"
+        ));
+        assert!(topic_matches("tele/#", "tele/plug_1/SENSOR"));
+        assert!(topic_matches("tele/plug_1/SENSOR", "tele/plug_1/SENSOR"));
+        assert!(!topic_matches("tele/plug_1/SENSOR", "tele/plug_1/STATE"));
+    }
+
+    #[test]
+    fn value_templates_become_json_pointers() {
+        assert_eq!(
+            template_to_json_pointer("{{ value_json.ENERGY.Power }}").as_deref(),
+            Some("/ENERGY/Power")
+        );
+        assert_eq!(
+            template_to_json_pointer("{{ value_json['switch:0'].apower | float }}").as_deref(),
+            Some("/switch:0/apower")
+        );
+        assert_eq!(
+            template_to_json_pointer("{{value_json.sensors[1]}}").as_deref(),
+            Some("/sensors/1")
+        );
+        assert_eq!(template_to_json_pointer("{{ value }}").as_deref(), Some(""));
+        assert_eq!(
+            template_to_json_pointer("{% if value_json.on %}ON{% endif %}"),
+            None
+        );
+    }
+
+    #[test]
+    fn payloads_decode_numbers_and_states() {
+        assert_eq!(
+            extract_raw(b" 21.5 ", None)
+                .as_deref()
+                .and_then(decode_value),
+            Some(21.5)
+        );
+        assert_eq!(
+            extract_raw(b"ON", None).as_deref().and_then(decode_value),
+            Some(1.0)
+        );
+        let payload = br#"{"ENERGY":{"Power":42,"Today":"1.25"},"POWER":"OFF"}"#;
+        assert_eq!(
+            extract_raw(payload, Some("/ENERGY/Power"))
+                .as_deref()
+                .and_then(decode_value),
+            Some(42.0)
+        );
+        assert_eq!(
+            extract_raw(payload, Some("/ENERGY/Today"))
+                .as_deref()
+                .and_then(decode_value),
+            Some(1.25)
+        );
+        assert_eq!(
+            extract_raw(payload, Some("/POWER"))
+                .as_deref()
+                .and_then(decode_value),
+            Some(0.0)
+        );
+        assert_eq!(extract_raw(payload, Some("/missing")), None);
+        assert_eq!(decode_value("unavailable"), None);
+    }
+
+    #[test]
+    fn discovery_messages_expand_abbreviations() {
+        let payload = br#"{
+            "name": "Pump Power",
+            "~": "tele/pump_plug",
+            "stat_t": "~/SENSOR",
+            "val_tpl": "{{ value_json.ENERGY.Power }}",
+            "unit_of_meas": "W",
+            "dev_cla": "power",
+            "uniq_id": "pump_plug_power",
+            "dev": {"ids": ["A1B2C3"], "name": "Pump Plug", "mf": "Tasmota", "mdl": "Sonoff S31"}
+        }"#;
+        let entity =
+            parse_discovery_message("homeassistant/sensor/pump_plug/power/config", payload)
+                .expect("entity");
+        assert_eq!(entity.component, "sensor");
+        assert_eq!(entity.object_id, "pump_plug_power");
+        assert_eq!(entity.state_topic.as_deref(), Some("tele/pump_plug/SENSOR"));
+        assert_eq!(entity.json_pointer.as_deref(), Some("/ENERGY/Power"));
+        assert_eq!(entity.device_identifier, "A1B2C3");
+        assert_eq!(entity.manufacturer.as_deref(), Some("Tasmota"));
+
+        let switch = parse_discovery_message(
+            "homeassistant/switch/pump_relay/config",
+            br#"{"name":"Pump","cmd_t":"cmnd/pump_plug/POWER","stat_t":"stat/pump_plug/POWER","pl_on":"ON","pl_off":"OFF","dev":{"ids":"A1B2C3"}}"#,
+        )
+        .expect("switch");
+        let (points, outputs) = entities_to_points(&[entity, switch]);
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].metric, "sensor_pump_plug_power");
+        assert_eq!(points[0].sensor_type, "power");
+        assert_eq!(points[0].unit, "W");
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].command_topic, "cmnd/pump_plug/POWER");
+        assert_eq!(
+            outputs[0].state_topic.as_deref(),
+            Some("stat/pump_plug/POWER")
+        );
+    }
+}
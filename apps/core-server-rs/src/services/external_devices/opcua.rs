@@ -0,0 +1,880 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use opcua::client::{
+    Client, ClientBuilder, DataChangeCallback, IdentityToken, MonitoredItem, Session,
+};
+use opcua::crypto::SecurityPolicy;
+use opcua::types::{
+    BrowseDescription, BrowseDirection, BrowseResultMask, DataValue, MessageSecurityMode,
+    MonitoredItemCreateRequest, NodeClass, NodeClassMask, NodeId, ObjectId, ReadValueId,
+    ReferenceTypeId, TimestampsToReturn, UserTokenPolicy, UserTokenType, Variant,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{
+    insert_metric, load_certs_from_pem, load_private_key_from_pem, slugify_metric,
+    ExternalDeviceConfig,
+};
+use crate::device_catalog::DevicePoint;
+use crate::state::AppState;
+
+const DEFAULT_PORT: u16 = 4840;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const SESSION_RETRY_LIMIT: i32 = 3;
+const DEFAULT_PUBLISHING_INTERVAL_MS: u64 = 1000;
+const BROWSE_MAX_DEPTH: usize = 6;
+const BROWSE_MAX_POINTS: usize = 1024;
+const READ_BATCH_SIZE: usize = 100;
+
+static SUBSCRIPTIONS: OnceLock<std::sync::Mutex<HashMap<Uuid, ActiveSubscription>>> =
+    OnceLock::new();
+
+/// One endpoint advertised by a server's GetEndpoints service.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct OpcuaEndpointSummary {
+    pub endpoint_url: String,
+    pub security_policy: String,
+    pub security_mode: String,
+    pub security_level: u8,
+    pub user_token_types: Vec<String>,
+}
+
+/// A live session with monitored items for one device. Values are written by the
+/// session's data change callback, so the poll loop only has to keep this alive.
+struct ActiveSubscription {
+    fingerprint: String,
+    cancel: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+struct ValueUpdate {
+    metric: String,
+    value: f64,
+    ts: DateTime<Utc>,
+}
+
+pub fn endpoint_url(config: &ExternalDeviceConfig) -> Result<String> {
+    if let Some(url) = config
+        .opcua_endpoint_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        if !url.starts_with("opc.tcp://") {
+            return Err(anyhow!("OPC UA endpoint must be an opc.tcp:// URL"));
+        }
+        return Ok(url.to_string());
+    }
+    let host = config
+        .host
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .context("OPC UA device missing endpoint URL or host")?;
+    Ok(format!(
+        "opc.tcp://{}:{}",
+        host,
+        config.port.unwrap_or(DEFAULT_PORT)
+    ))
+}
+
+fn parse_security_policy(value: Option<&str>) -> Result<SecurityPolicy> {
+    let normalized = value
+        .map(|value| {
+            value
+                .trim()
+                .rsplit('#')
+                .next()
+                .unwrap_or("")
+                .replace(['_', '-'], "")
+                .to_ascii_lowercase()
+        })
+        .unwrap_or_default();
+    match normalized.as_str() {
+        "" | "none" => Ok(SecurityPolicy::None),
+        "basic128rsa15" => Ok(SecurityPolicy::Basic128Rsa15),
+        "basic256" => Ok(SecurityPolicy::Basic256),
+        "basic256sha256" => Ok(SecurityPolicy::Basic256Sha256),
+        "aes128sha256rsaoaep" => Ok(SecurityPolicy::Aes128Sha256RsaOaep),
+        "aes256sha256rsapss" => Ok(SecurityPolicy::Aes256Sha256RsaPss),
+        _ => Err(anyhow!(
+            "unsupported OPC UA security policy {}",
+            value.unwrap_or_default()
+        )),
+    }
+}
+
+fn parse_security_mode(value: Option<&str>) -> Result<MessageSecurityMode> {
+    let normalized = value
+        .map(|value| value.trim().replace(['_', '-'], "").to_ascii_lowercase())
+        .unwrap_or_default();
+    match normalized.as_str() {
+        "" | "none" => Ok(MessageSecurityMode::None),
+        "sign" => Ok(MessageSecurityMode::Sign),
+        "signandencrypt" => Ok(MessageSecurityMode::SignAndEncrypt),
+        _ => Err(anyhow!(
+            "unsupported OPC UA security mode {}",
+            value.unwrap_or_default()
+        )),
+    }
+}
+
+fn security_mode_label(mode: MessageSecurityMode) -> &'static str {
+    match mode {
+        MessageSecurityMode::Sign => "sign",
+        MessageSecurityMode::SignAndEncrypt => "sign_and_encrypt",
+        _ => "none",
+    }
+}
+
+fn user_token_type_label(token_type: UserTokenType) -> &'static str {
+    match token_type {
+        UserTokenType::Anonymous => "anonymous",
+        UserTokenType::UserName => "username",
+        UserTokenType::Certificate => "certificate",
+        UserTokenType::IssuedToken => "issued_token",
+    }
+}
+
+fn configured_username(config: &ExternalDeviceConfig) -> Option<&str> {
+    config
+        .opcua_username
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Picks the user token to log in with from the policies the endpoint offers: a
+/// configured username first, then anonymous, then the client certificate.
+fn select_user_identity(
+    config: &ExternalDeviceConfig,
+    offered: &[UserTokenPolicy],
+    pki_dir: &Path,
+    has_client_identity: bool,
+) -> Result<(UserTokenPolicy, IdentityToken)> {
+    let find = |token_type: UserTokenType| {
+        offered
+            .iter()
+            .find(|policy| policy.token_type == token_type)
+            .cloned()
+    };
+    if let Some(username) = configured_username(config) {
+        let policy = find(UserTokenType::UserName)
+            .ok_or_else(|| anyhow!("OPC UA endpoint does not accept username logins"))?;
+        let token = IdentityToken::UserName(
+            username.to_string(),
+            config.opcua_password.clone().unwrap_or_default(),
+        );
+        return Ok((policy, token));
+    }
+    if let Some(policy) = find(UserTokenType::Anonymous) {
+        return Ok((policy, IdentityToken::Anonymous));
+    }
+    if has_client_identity {
+        if let Some(policy) = find(UserTokenType::Certificate) {
+            let token = IdentityToken::X509(
+                pki_dir.join("own/cert.der"),
+                pki_dir.join("private/private.pem"),
+            );
+            return Ok((policy, token));
+        }
+    }
+    Err(anyhow!(
+        "OPC UA endpoint does not allow anonymous logins; configure a username or client certificate"
+    ))
+}
+
+/// Writes the configured client certificate into the PKI layout the OPC UA stack
+/// expects. Returns false when no certificate is configured, in which case the stack
+/// generates a self-signed one on first use.
+async fn install_client_identity(config: &ExternalDeviceConfig, pki_dir: &Path) -> Result<bool> {
+    let (Some(cert_pem), Some(key_pem)) = (
+        config.opcua_client_cert_pem.as_deref(),
+        config.opcua_client_key_pem.as_deref(),
+    ) else {
+        return Ok(false);
+    };
+    let cert = load_certs_from_pem(cert_pem)
+        .context("invalid OPC UA client cert")?
+        .into_iter()
+        .next()
+        .context("OPC UA client cert missing")?;
+    load_private_key_from_pem(key_pem).context("invalid OPC UA client key")?;
+
+    let own_dir = pki_dir.join("own");
+    let private_dir = pki_dir.join("private");
+    tokio::fs::create_dir_all(&own_dir).await?;
+    tokio::fs::create_dir_all(&private_dir).await?;
+    tokio::fs::write(own_dir.join("cert.der"), cert.as_ref()).await?;
+    let key_path = private_dir.join("private.pem");
+    tokio::fs::write(&key_path, key_pem.as_bytes()).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o600)).await?;
+    }
+    Ok(true)
+}
+
+/// Returns the client and whether a configured client certificate was installed.
+async fn build_client(config: &ExternalDeviceConfig, pki_dir: &Path) -> Result<(Client, bool)> {
+    let has_identity = install_client_identity(config, pki_dir).await?;
+    let mut builder = ClientBuilder::new()
+        .application_name("Farm Dashboard")
+        .application_uri("urn:farm-dashboard:core-server")
+        .product_uri("urn:farm-dashboard")
+        .pki_dir(pki_dir)
+        .create_sample_keypair(!has_identity)
+        .trust_server_certs(config.opcua_trust_server_cert.unwrap_or(false))
+        .session_retry_limit(SESSION_RETRY_LIMIT);
+    if has_identity {
+        builder = builder
+            .certificate_path("own/cert.der")
+            .private_key_path("private/private.pem");
+    }
+    let client = builder
+        .client()
+        .map_err(|errors| anyhow!("invalid OPC UA client config: {}", errors.join("; ")))?;
+    Ok((client, has_identity))
+}
+
+async fn connect(
+    config: &ExternalDeviceConfig,
+    pki_dir: &Path,
+) -> Result<(Arc<Session>, JoinHandle<opcua::types::StatusCode>)> {
+    let url = endpoint_url(config)?;
+    let policy = parse_security_policy(config.opcua_security_policy.as_deref())?;
+    let mode = parse_security_mode(config.opcua_security_mode.as_deref())?;
+    if (policy == SecurityPolicy::None) != (mode == MessageSecurityMode::None) {
+        return Err(anyhow!(
+            "OPC UA security policy and mode must both be none or both be set"
+        ));
+    }
+    let (mut client, has_identity) = build_client(config, pki_dir).await?;
+    let endpoints = timeout(
+        CONNECT_TIMEOUT,
+        client.get_server_endpoints_from_url(url.as_str()),
+    )
+    .await
+    .context("OPC UA endpoint discovery timed out")?
+    .map_err(|status| anyhow!("OPC UA endpoint discovery failed: {status}"))?;
+    let offered = endpoints
+        .into_iter()
+        .find(|endpoint| {
+            SecurityPolicy::from_uri(endpoint.security_policy_uri.as_ref()) == policy
+                && endpoint.security_mode == mode
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "OPC UA server has no endpoint with security policy {} and mode {}",
+                policy.to_str(),
+                security_mode_label(mode)
+            )
+        })?
+        .user_identity_tokens
+        .unwrap_or_default();
+    let (token_policy, identity) = select_user_identity(config, &offered, pki_dir, has_identity)?;
+    let (session, event_loop) = timeout(
+        CONNECT_TIMEOUT,
+        client.connect_to_matching_endpoint(
+            (url.as_str(), policy.to_str(), mode, token_policy),
+            identity,
+        ),
+    )
+    .await
+    .context("OPC UA connect timed out")?
+    .map_err(|status| anyhow!("OPC UA connect to {url} failed: {status}"))?;
+    let handle = event_loop.spawn();
+    if !timeout(CONNECT_TIMEOUT, session.wait_for_connection())
+        .await
+        .unwrap_or(false)
+    {
+        handle.abort();
+        return Err(anyhow!("OPC UA session to {url} did not activate"));
+    }
+    Ok((session, handle))
+}
+
+pub(super) fn pki_dir(state: &AppState, scope: &str) -> PathBuf {
+    state.config.data_root.join("opcua").join(scope)
+}
+
+pub async fn discover_endpoints(
+    state: &AppState,
+    config: &ExternalDeviceConfig,
+) -> Result<Vec<OpcuaEndpointSummary>> {
+    let url = endpoint_url(config)?;
+    let (client, _) = build_client(config, &pki_dir(state, "discovery")).await?;
+    let endpoints = timeout(
+        CONNECT_TIMEOUT,
+        client.get_server_endpoints_from_url(url.as_str()),
+    )
+    .await
+    .context("OPC UA endpoint discovery timed out")?
+    .map_err(|status| anyhow!("OPC UA endpoint discovery failed: {status}"))?;
+    let mut summaries = endpoints
+        .into_iter()
+        .map(|endpoint| OpcuaEndpointSummary {
+            endpoint_url: endpoint.endpoint_url.to_string(),
+            security_policy: SecurityPolicy::from_uri(endpoint.security_policy_uri.as_ref())
+                .to_str()
+                .to_string(),
+            security_mode: security_mode_label(endpoint.security_mode).to_string(),
+            security_level: endpoint.security_level,
+            user_token_types: endpoint
+                .user_identity_tokens
+                .unwrap_or_default()
+                .into_iter()
+                .map(|policy| user_token_type_label(policy.token_type).to_string())
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.security_level));
+    Ok(summaries)
+}
+
+/// Walks the address space below the browse root (the Objects folder by default) and
+/// returns every variable whose current value is numeric or boolean.
+pub(super) async fn discover_points(
+    state: &AppState,
+    node_id: Uuid,
+    config: &ExternalDeviceConfig,
+) -> Result<Vec<DevicePoint>> {
+    let (session, event_loop) = connect(config, &pki_dir(state, &node_id.to_string())).await?;
+    let result = read_variable_points(&session, config).await;
+    session.disconnect().await.ok();
+    event_loop.abort();
+    result
+}
+
+async fn read_variable_points(
+    session: &Session,
+    config: &ExternalDeviceConfig,
+) -> Result<Vec<DevicePoint>> {
+    let variables = browse_variables(session, config).await?;
+    let mut points = Vec::new();
+    let mut metrics = HashSet::new();
+    for batch in variables.chunks(READ_BATCH_SIZE) {
+        let reads = batch
+            .iter()
+            .map(|(node, _)| ReadValueId::from(node.clone()))
+            .collect::<Vec<_>>();
+        let values = match session.read(&reads, TimestampsToReturn::Neither, 0.0).await {
+            Ok(values) => values,
+            Err(status) => {
+                warn!(%status, "OPC UA read during discovery failed");
+                continue;
+            }
+        };
+        for ((node, name), value) in batch.iter().zip(values) {
+            let Some(variant) = value.value.as_ref() else {
+                continue;
+            };
+            let Some(number) = variant_to_f64(variant) else {
+                continue;
+            };
+            let mut metric = slugify_metric(name);
+            if metric == "zone" || !metrics.insert(metric.clone()) {
+                metric = format!("{}_{}", metric, slugify_metric(&node.to_string()));
+                metrics.insert(metric.clone());
+            }
+            let sensor_type = if matches!(variant, Variant::Boolean(_)) {
+                "status"
+            } else if number.fract() == 0.0 {
+                "generic"
+            } else {
+                "analog"
+            };
+            points.push(DevicePoint {
+                name: name.clone(),
+                metric,
+                sensor_type: sensor_type.to_string(),
+                unit: String::new(),
+                protocol: "opcua".to_string(),
+                register: None,
+                register_type: None,
+                data_type: None,
+                scale: None,
+                oid: None,
+                path: Some(node.to_string()),
+                json_pointer: None,
+                bacnet_object: None,
+            });
+        }
+    }
+    Ok(points)
+}
+
+async fn browse_variables(
+    session: &Session,
+    config: &ExternalDeviceConfig,
+) -> Result<Vec<(NodeId, String)>> {
+    let root = match config
+        .opcua_browse_root
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        Some(value) => {
+            NodeId::from_str(value).map_err(|_| anyhow!("invalid OPC UA browse root {value}"))?
+        }
+        None => ObjectId::ObjectsFolder.into(),
+    };
+
+    let mut variables = Vec::new();
+    let mut visited = HashSet::from([root.clone()]);
+    let mut queue = VecDeque::from([(root, 0usize)]);
+    while let Some((node, depth)) = queue.pop_front() {
+        if variables.len() >= BROWSE_MAX_POINTS {
+            break;
+        }
+        let description = BrowseDescription {
+            node_id: node,
+            browse_direction: BrowseDirection::Forward,
+            reference_type_id: ReferenceTypeId::HierarchicalReferences.into(),
+            include_subtypes: true,
+            node_class_mask: (NodeClassMask::OBJECT | NodeClassMask::VARIABLE).bits(),
+            result_mask: BrowseResultMask::All as u32,
+        };
+        let mut results = session
+            .browse(&[description], 0, None)
+            .await
+            .map_err(|status| anyhow!("OPC UA browse failed: {status}"))?;
+        let mut references = Vec::new();
+        while let Some(result) = results.pop() {
+            references.extend(result.references.unwrap_or_default());
+            if result.continuation_point.is_null() {
+                break;
+            }
+            results = session
+                .browse_next(false, &[result.continuation_point])
+                .await
+                .map_err(|status| anyhow!("OPC UA browse failed: {status}"))?;
+        }
+
+        for reference in references {
+            let child = reference.node_id.node_id;
+            if !visited.insert(child.clone()) {
+                continue;
+            }
+            match reference.node_class {
+                NodeClass::Variable => {
+                    variables.push((child, reference.display_name.text.to_string()))
+                }
+                NodeClass::Object if depth + 1 < BROWSE_MAX_DEPTH => {
+                    queue.push_back((child, depth + 1))
+                }
+                _ => {}
+            }
+        }
+    }
+    variables.truncate(BROWSE_MAX_POINTS);
+    Ok(variables)
+}
+
+/// Everything the subscription is built from; credentials are only kept as a digest.
+fn subscription_fingerprint(config: &ExternalDeviceConfig, points: &[&DevicePoint]) -> String {
+    let mut targets = points
+        .iter()
+        .filter_map(|point| {
+            let node = point.path.as_deref()?;
+            Some(format!(
+                "{node}={}*{}",
+                point.metric,
+                point.scale.unwrap_or(1.0)
+            ))
+        })
+        .collect::<Vec<_>>();
+    targets.sort_unstable();
+    let mut credentials = Sha256::new();
+    for secret in [
+        &config.opcua_password,
+        &config.opcua_client_cert_pem,
+        &config.opcua_client_key_pem,
+    ] {
+        credentials.update(secret.as_deref().unwrap_or("").as_bytes());
+        credentials.update([0]);
+    }
+    format!(
+        "{}|{}|{}|{}|{}|{}|{:x}|{}",
+        endpoint_url(config).unwrap_or_default(),
+        config.opcua_security_policy.as_deref().unwrap_or(""),
+        config.opcua_security_mode.as_deref().unwrap_or(""),
+        config.opcua_username.as_deref().unwrap_or(""),
+        config.opcua_trust_server_cert.unwrap_or(false),
+        config
+            .opcua_publishing_interval_ms
+            .unwrap_or(DEFAULT_PUBLISHING_INTERVAL_MS),
+        credentials.finalize(),
+        targets.join(",")
+    )
+}
+
+/// Makes sure the device has a live subscription covering `points`. A matching
+/// subscription is left alone; a changed config or a dead session is replaced.
+pub(super) async fn ensure_subscription(
+    state: &AppState,
+    node_id: Uuid,
+    config: &ExternalDeviceConfig,
+    points: &[&DevicePoint],
+) -> Result<()> {
+    let fingerprint = subscription_fingerprint(config, points);
+    {
+        let subscriptions = SUBSCRIPTIONS.get_or_init(|| std::sync::Mutex::new(HashMap::new()));
+        let mut subscriptions = subscriptions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(active) = subscriptions.get(&node_id) {
+            if active.fingerprint == fingerprint && !active.task.is_finished() {
+                return Ok(());
+            }
+        }
+        if let Some(stale) = subscriptions.remove(&node_id) {
+            stale.cancel.cancel();
+        }
+    }
+
+    let mut targets = HashMap::new();
+    let mut requests = Vec::new();
+    for point in points {
+        let Some(path) = point.path.as_deref() else {
+            continue;
+        };
+        let Ok(node) = NodeId::from_str(path.trim()) else {
+            warn!(%node_id, metric = %point.metric, node = path, "invalid OPC UA node id");
+            continue;
+        };
+        targets.insert(
+            node.clone(),
+            (point.metric.clone(), point.scale.unwrap_or(1.0)),
+        );
+        requests.push(MonitoredItemCreateRequest::from(node));
+    }
+    if requests.is_empty() {
+        return Err(anyhow!("OPC UA device had no readable points configured"));
+    }
+
+    let (session, event_loop) = connect(config, &pki_dir(state, &node_id.to_string())).await?;
+    let (tx, mut rx) = mpsc::unbounded_channel::<ValueUpdate>();
+    let publishing_interval = Duration::from_millis(
+        config
+            .opcua_publishing_interval_ms
+            .unwrap_or(DEFAULT_PUBLISHING_INTERVAL_MS)
+            .max(100),
+    );
+    let callback = DataChangeCallback::new(move |value: DataValue, item: &MonitoredItem| {
+        let Some((metric, scale)) = targets.get(&item.item_to_monitor().node_id) else {
+            return;
+        };
+        if value.status.is_some_and(|status| !status.is_good()) {
+            return;
+        }
+        let Some(number) = value.value.as_ref().and_then(variant_to_f64) else {
+            return;
+        };
+        let ts = value
+            .source_timestamp
+            .or(value.server_timestamp)
+            .map(|ts| ts.as_chrono())
+            .unwrap_or_else(Utc::now);
+        let _ = tx.send(ValueUpdate {
+            metric: metric.clone(),
+            value: number * scale,
+            ts,
+        });
+    });
+    let created = async {
+        let subscription_id = session
+            .create_subscription(publishing_interval, 60, 20, 0, 0, true, callback)
+            .await
+            .map_err(|status| anyhow!("OPC UA create subscription failed: {status}"))?;
+        session
+            .create_monitored_items(subscription_id, TimestampsToReturn::Both, requests)
+            .await
+            .map_err(|status| anyhow!("OPC UA create monitored items failed: {status}"))
+    }
+    .await;
+    let created = match created {
+        Ok(created) => created,
+        Err(err) => {
+            session.disconnect().await.ok();
+            event_loop.abort();
+            return Err(err);
+        }
+    };
+    let rejected = created
+        .iter()
+        .filter(|item| !item.status_code.is_good())
+        .count();
+    if rejected > 0 {
+        warn!(%node_id, rejected, "OPC UA server rejected some monitored items");
+    }
+
+    let cancel = CancellationToken::new();
+    let task_cancel = cancel.clone();
+    let task_state = state.clone();
+    let model_id = config.model_id.clone();
+    let task = tokio::spawn(async move {
+        let mut event_loop = event_loop;
+        loop {
+            tokio::select! {
+                _ = task_cancel.cancelled() => break,
+                status = &mut event_loop => {
+                    warn!(%node_id, status = ?status, "OPC UA session ended");
+                    break;
+                }
+                update = rx.recv() => {
+                    let Some(update) = update else { break };
+                    if let Err(err) = insert_metric(
+                        &task_state,
+                        update.ts,
+                        node_id,
+                        &model_id,
+                        &update.metric,
+                        update.value,
+                    )
+                    .await
+                    {
+                        warn!(%node_id, metric = %update.metric, error = %err, "OPC UA value insert failed");
+                    }
+                }
+            }
+        }
+        session.disconnect().await.ok();
+        event_loop.abort();
+    });
+
+    let subscriptions = SUBSCRIPTIONS.get_or_init(|| std::sync::Mutex::new(HashMap::new()));
+    let mut subscriptions = subscriptions
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(previous) = subscriptions.insert(
+        node_id,
+        ActiveSubscription {
+            fingerprint,
+            cancel,
+            task,
+        },
+    ) {
+        previous.cancel.cancel();
+    }
+    Ok(())
+}
+
+/// Tears down subscriptions for devices that no longer exist.
+pub(super) fn retain_subscriptions(active: &HashSet<Uuid>) {
+    let Some(subscriptions) = SUBSCRIPTIONS.get() else {
+        return;
+    };
+    let mut subscriptions = subscriptions
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    subscriptions.retain(|node_id, subscription| {
+        let keep = active.contains(node_id);
+        if !keep {
+            subscription.cancel.cancel();
+        }
+        keep
+    });
+}
+
+fn variant_to_f64(value: &Variant) -> Option<f64> {
+    let number = match value {
+        Variant::Boolean(value) => {
+            if *value {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        Variant::SByte(value) => *value as f64,
+        Variant::Byte(value) => *value as f64,
+        Variant::Int16(value) => *value as f64,
+        Variant::UInt16(value) => *value as f64,
+        Variant::Int32(value) => *value as f64,
+        Variant::UInt32(value) => *value as f64,
+        Variant::Int64(value) => *value as f64,
+        Variant::UInt64(value) => *value as f64,
+        Variant::Float(value) => *value as f64,
+        Variant::Double(value) => *value,
+        _ => return None,
+    };
+    number.is_finite().then_some(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opcua_config(host: Option<&str>, endpoint: Option<&str>) -> ExternalDeviceConfig {
+        ExternalDeviceConfig {
+            vendor_id: "opcua".to_string(),
+            model_id: "opcua_server".to_string(),
+            protocol: "opcua".to_string(),
+            host: host.map(str::to_string),
+            opcua_endpoint_url: endpoint.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn endpoint_url_prefers_explicit_url_and_defaults_port() {
+        let config = opcua_config(Some("10.0.0.5"), None);
+        assert_eq!(endpoint_url(&config).unwrap(), "opc.tcp://10.0.0.5:4840");
+
+        let config = opcua_config(Some("10.0.0.5"), Some("opc.tcp://plc.local:48010/ua"));
+        assert_eq!(
+            endpoint_url(&config).unwrap(),
+            "opc.tcp://plc.local:48010/ua"
+        );
+
+        let config = opcua_config(None, Some("http://plc.local"));
+        assert!(endpoint_url(&config).is_err());
+        assert!(endpoint_url(&opcua_config(None, None)).is_err());
+    }
+
+    #[test]
+    fn security_settings_accept_names_and_uris() {
+        assert_eq!(parse_security_policy(None).unwrap(), SecurityPolicy::None);
+        assert_eq!(
+            parse_security_policy(Some("Basic256Sha256")).unwrap(),
+            SecurityPolicy::Basic256Sha256
+        );
+        assert_eq!(
+            parse_security_policy(Some(
+                "http://opcfoundation.org/UA/SecurityPolicy#Aes128_Sha256_RsaOaep"
+            ))
+            .unwrap(),
+            SecurityPolicy::Aes128Sha256RsaOaep
+        );
+        assert!(parse_security_policy(Some("rot13")).is_err());
+
+        assert_eq!(
+            parse_security_mode(Some("sign_and_encrypt")).unwrap(),
+            MessageSecurityMode::SignAndEncrypt
+        );
+        assert_eq!(
+            parse_security_mode(Some("Sign")).unwrap(),
+            MessageSecurityMode::Sign
+        );
+        assert!(parse_security_mode(Some("encrypt")).is_err());
+    }
+
+    #[test]
+    fn variant_conversion_covers_numeric_types() {
+        assert_eq!(variant_to_f64(&Variant::Boolean(true)), Some(1.0));
+        assert_eq!(variant_to_f64(&Variant::Int16(-4)), Some(-4.0));
+        assert_eq!(variant_to_f64(&Variant::UInt64(42)), Some(42.0));
+        assert_eq!(variant_to_f64(&Variant::Float(1.5)), Some(1.5));
+        assert_eq!(variant_to_f64(&Variant::Double(f64::NAN)), None);
+        assert_eq!(variant_to_f64(&Variant::from("text")), None);
+    }
+
+    #[test]
+    fn fingerprint_ignores_point_order() {
+        let config = opcua_config(Some("10.0.0.5"), None);
+        let point = |metric: &str, node: &str| DevicePoint {
+            name: metric.to_string(),
+            metric: metric.to_string(),
+            sensor_type: "generic".to_string(),
+            unit: String::new(),
+            protocol: "opcua".to_string(),
+            register: None,
+            register_type: None,
+            data_type: None,
+            scale: None,
+            oid: None,
+            path: Some(node.to_string()),
+            json_pointer: None,
+            bacnet_object: None,
+        };
+        let a = point("supply_temp", "ns=2;s=Boiler.Supply");
+        let b = point("return_temp", "ns=2;s=Boiler.Return");
+        assert_eq!(
+            subscription_fingerprint(&config, &[&a, &b]),
+            subscription_fingerprint(&config, &[&b, &a])
+        );
+        let mut faster = config.clone();
+        faster.opcua_publishing_interval_ms = Some(250);
+        assert_ne!(
+            subscription_fingerprint(&config, &[&a]),
+            subscription_fingerprint(&faster, &[&a])
+        );
+        let mut rekeyed = config.clone();
+        rekeyed.opcua_password = Some("new-secret".to_string());
+        assert_ne!(
+            subscription_fingerprint(&config, &[&a]),
+            subscription_fingerprint(&rekeyed, &[&a])
+        );
+        assert!(!subscription_fingerprint(&rekeyed, &[&a]).contains("new-secret"));
+        let mut rescaled = a.clone();
+        rescaled.scale = Some(0.1);
+        assert_ne!(
+            subscription_fingerprint(&config, &[&a]),
+            subscription_fingerprint(&config, &[&rescaled])
+        );
+    }
+
+    #[test]
+    fn user_identity_follows_configured_credentials() {
+        let pki = Path::new("/tmp/pki");
+        let offered = |types: &[UserTokenType]| {
+            types
+                .iter()
+                .map(|token_type| UserTokenPolicy {
+                    token_type: *token_type,
+                    ..UserTokenPolicy::anonymous()
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut config = opcua_config(Some("10.0.0.5"), None);
+
+        let (policy, token) = select_user_identity(
+            &config,
+            &offered(&[UserTokenType::Anonymous, UserTokenType::UserName]),
+            pki,
+            false,
+        )
+        .unwrap();
+        assert_eq!(policy.token_type, UserTokenType::Anonymous);
+        assert!(matches!(token, IdentityToken::Anonymous));
+
+        let (policy, token) =
+            select_user_identity(&config, &offered(&[UserTokenType::Certificate]), pki, true)
+                .unwrap();
+        assert_eq!(policy.token_type, UserTokenType::Certificate);
+        assert!(matches!(token, IdentityToken::X509(_, _)));
+        assert!(
+            select_user_identity(&config, &offered(&[UserTokenType::Certificate]), pki, false)
+                .is_err()
+        );
+
+        config.opcua_username = Some("operator".to_string());
+        config.opcua_password = Some("secret".to_string());
+        let (policy, token) = select_user_identity(
+            &config,
+            &offered(&[UserTokenType::Anonymous, UserTokenType::UserName]),
+            pki,
+            false,
+        )
+        .unwrap();
+        assert_eq!(policy.token_type, UserTokenType::UserName);
+        assert!(matches!(token, IdentityToken::UserName(ref user, _) if user == "operator"));
+        assert!(
+            select_user_identity(&config, &offered(&[UserTokenType::Anonymous]), pki, false)
+                .is_err()
+        );
+    }
+}
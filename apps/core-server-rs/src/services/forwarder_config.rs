@@ -0,0 +1,440 @@
+//! node-forwarder settings managed from the controller.
+//!
+//! Each node with a node-forwarder id gets a document, retained, on `iot/<node>/forwarder-config`
+//! (empty settings when nothing is configured, so removed overrides are reverted). The forwarder
+//! reports what it applied on `iot/<node>/forwarder-config/state`; [`drift_status`] compares the
+//! two for the API.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use super::mqtt::MqttPublisher;
+use crate::state::AppState;
+
+const STATE_TOPIC_FILTER: &str = "iot/+/forwarder-config/state";
+
+// Mirrors the limits node-forwarder enforces, so bad values fail at the API instead of on the node.
+const MIN_SPOOL_BYTES: u64 = 16 * 1024 * 1024;
+const MIN_SEGMENT_ROLL_BYTES: u64 = 1024 * 1024;
+const MAX_SEGMENT_ROLL_BYTES: u64 = 1024 * 1024 * 1024;
+const MIN_SEGMENT_ROLL_SECONDS: u64 = 60;
+const MAX_SEGMENT_ROLL_SECONDS: u64 = 24 * 3600;
+const MIN_SPOOL_AGE_SECONDS: u64 = 3600;
+const MAX_REPLAY_MSGS_PER_SEC: u32 = 100_000;
+const MIN_REPLAY_BYTES_PER_SEC: u32 = 4096;
+
+/// Overrides for node-forwarder knobs. Unset fields keep the node's environment value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ForwarderSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_spool_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_free_bytes: Option<u64>,
+    /// `0` removes the age limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_spool_age_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_msgs_per_sec: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_bytes_per_sec: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment_roll_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment_roll_seconds: Option<u64>,
+}
+
+impl ForwarderSettings {
+    /// sha256 of the settings; the forwarder skips documents whose version it already applied.
+    pub fn version(&self) -> String {
+        let encoded = serde_json::to_vec(self).unwrap_or_default();
+        format!("{:x}", Sha256::digest(&encoded))
+    }
+
+    /// Range checks that do not depend on the node's environment. The forwarder also rejects a
+    /// segment roll size above half the spool budget, which may come from its environment.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self
+            .max_spool_bytes
+            .is_some_and(|bytes| bytes < MIN_SPOOL_BYTES)
+        {
+            errors.push(format!(
+                "max_spool_bytes must be at least {MIN_SPOOL_BYTES}"
+            ));
+        }
+        if self
+            .max_spool_age_seconds
+            .is_some_and(|secs| secs != 0 && secs < MIN_SPOOL_AGE_SECONDS)
+        {
+            errors.push(format!(
+                "max_spool_age_seconds must be 0 or at least {MIN_SPOOL_AGE_SECONDS}"
+            ));
+        }
+        if self
+            .replay_msgs_per_sec
+            .is_some_and(|rate| rate == 0 || rate > MAX_REPLAY_MSGS_PER_SEC)
+        {
+            errors.push(format!(
+                "replay_msgs_per_sec must be between 1 and {MAX_REPLAY_MSGS_PER_SEC}"
+            ));
+        }
+        if self
+            .replay_bytes_per_sec
+            .is_some_and(|rate| rate < MIN_REPLAY_BYTES_PER_SEC)
+        {
+            errors.push(format!(
+                "replay_bytes_per_sec must be at least {MIN_REPLAY_BYTES_PER_SEC}"
+            ));
+        }
+        if self.segment_roll_bytes.is_some_and(|bytes| {
+            !(MIN_SEGMENT_ROLL_BYTES..=MAX_SEGMENT_ROLL_BYTES).contains(&bytes)
+        }) {
+            errors.push(format!(
+                "segment_roll_bytes must be between {MIN_SEGMENT_ROLL_BYTES} and {MAX_SEGMENT_ROLL_BYTES}"
+            ));
+        }
+        if self.segment_roll_seconds.is_some_and(|secs| {
+            !(MIN_SEGMENT_ROLL_SECONDS..=MAX_SEGMENT_ROLL_SECONDS).contains(&secs)
+        }) {
+            errors.push(format!(
+                "segment_roll_seconds must be between {MIN_SEGMENT_ROLL_SECONDS} and {MAX_SEGMENT_ROLL_SECONDS}"
+            ));
+        }
+        if let (Some(roll), Some(max)) = (self.segment_roll_bytes, self.max_spool_bytes) {
+            if roll.saturating_mul(2) > max {
+                errors
+                    .push("segment_roll_bytes must be at most half of max_spool_bytes".to_string());
+            }
+        }
+        errors
+    }
+
+    /// Desired fields the forwarder is not running with, compared against its `effective` report.
+    pub fn drifted_fields(&self, effective: &JsonValue) -> Vec<String> {
+        let desired = match serde_json::to_value(self) {
+            Ok(JsonValue::Object(map)) => map,
+            _ => return Vec::new(),
+        };
+        desired
+            .into_iter()
+            .filter(|(key, value)| {
+                // An age of 0 is reported back as "no limit".
+                let expected = if key == "max_spool_age_seconds" && value.as_u64() == Some(0) {
+                    JsonValue::Null
+                } else {
+                    value.clone()
+                };
+                effective.get(key).unwrap_or(&JsonValue::Null) != &expected
+            })
+            .map(|(key, _)| key)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwarderConfigDocument {
+    pub version: String,
+    pub generated_at: String,
+    pub settings: ForwarderSettings,
+}
+
+impl ForwarderConfigDocument {
+    pub fn new(settings: ForwarderSettings) -> Self {
+        Self {
+            version: settings.version(),
+            generated_at: Utc::now().to_rfc3339(),
+            settings,
+        }
+    }
+}
+
+/// Report published by node-forwarder on `iot/<node>/forwarder-config/state`.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ForwarderConfigReport {
+    applied_version: Option<String>,
+    applied_at: Option<DateTime<Utc>>,
+    rejected_version: Option<String>,
+    error: Option<String>,
+    effective: Option<JsonValue>,
+}
+
+/// `in_sync`, `pending` (not applied yet), `rejected` (the node refused the desired document) or
+/// `unreported` (no report from the node).
+pub fn drift_status(
+    desired_version: &str,
+    applied_version: Option<&str>,
+    rejected_version: Option<&str>,
+    reported: bool,
+) -> &'static str {
+    if !reported {
+        "unreported"
+    } else if applied_version == Some(desired_version) {
+        "in_sync"
+    } else if rejected_version == Some(desired_version) {
+        "rejected"
+    } else {
+        "pending"
+    }
+}
+
+pub async fn publish_document(
+    mqtt: &MqttPublisher,
+    node_mqtt_id: &str,
+    document: &ForwarderConfigDocument,
+) -> Result<()> {
+    let topic = format!("iot/{node_mqtt_id}/forwarder-config");
+    mqtt.publish_retained_json(&topic, &serde_json::to_value(document)?)
+        .await
+}
+
+/// One document for every node with a node-forwarder id.
+pub async fn desired_documents(pool: &PgPool) -> Result<BTreeMap<String, ForwarderConfigDocument>> {
+    let rows: Vec<(String, Option<SqlJson<JsonValue>>)> = sqlx::query_as(
+        r#"
+        SELECT DISTINCT ON (TRIM(n.config->>'agent_node_id'))
+            TRIM(n.config->>'agent_node_id'),
+            c.settings
+        FROM nodes n
+        LEFT JOIN node_forwarder_config c ON c.node_id = n.id
+        WHERE NULLIF(TRIM(n.config->>'agent_node_id'), '') IS NOT NULL
+        ORDER BY TRIM(n.config->>'agent_node_id'), c.updated_at DESC NULLS LAST
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut documents = BTreeMap::new();
+    for (node_mqtt_id, settings) in rows {
+        let settings = match settings {
+            Some(raw) => match serde_json::from_value::<ForwarderSettings>(raw.0) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    tracing::warn!(node = %node_mqtt_id, error = %err, "skipping unreadable forwarder config");
+                    continue;
+                }
+            },
+            None => ForwarderSettings::default(),
+        };
+        documents.insert(node_mqtt_id, ForwarderConfigDocument::new(settings));
+    }
+    Ok(documents)
+}
+
+/// Publishes desired forwarder config documents and records what each forwarder reports back.
+pub struct ForwarderConfigService {
+    state: AppState,
+    poll_interval: Duration,
+}
+
+impl ForwarderConfigService {
+    pub fn new(state: AppState, poll_interval_seconds: u64) -> Self {
+        Self {
+            state,
+            poll_interval: Duration::from_secs(poll_interval_seconds.max(10)),
+        }
+    }
+
+    pub fn start(self, cancel: CancellationToken) {
+        let state = self.state.clone();
+        let report_cancel = cancel.clone();
+        tokio::spawn(async move {
+            loop {
+                if report_cancel.is_cancelled() {
+                    break;
+                }
+                if let Err(err) = ingest_reports(&state, report_cancel.clone()).await {
+                    tracing::warn!("forwarder config report loop failed: {err:#}");
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            // Versions published by this process. Empty after a restart, so every node gets its
+            // document once again.
+            let mut published: HashMap<String, String> = HashMap::new();
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = interval.tick() => {
+                        if let Err(err) = self.publish(&mut published).await {
+                            tracing::warn!(error = %err, "forwarder config publish failed");
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    async fn publish(&self, published: &mut HashMap<String, String>) -> Result<()> {
+        for (node_mqtt_id, document) in desired_documents(&self.state.db).await? {
+            if published.get(&node_mqtt_id) == Some(&document.version) {
+                continue;
+            }
+            publish_document(&self.state.mqtt, &node_mqtt_id, &document).await?;
+            tracing::info!(
+                node = %node_mqtt_id,
+                version = %document.version,
+                "published forwarder config"
+            );
+            published.insert(node_mqtt_id, document.version);
+        }
+        Ok(())
+    }
+}
+
+async fn ingest_reports(state: &AppState, cancel: CancellationToken) -> Result<()> {
+    let mut options = MqttOptions::new(
+        "farmdashboard-core-forwarder-config",
+        &state.config.mqtt_host,
+        state.config.mqtt_port,
+    );
+    options.set_keep_alive(Duration::from_secs(10));
+    if let (Some(username), Some(password)) = (
+        state.config.mqtt_username.as_deref(),
+        state.config.mqtt_password.as_deref(),
+    ) {
+        options.set_credentials(username, password);
+    }
+
+    let (client, mut eventloop) = AsyncClient::new(options, 10);
+    client
+        .subscribe(STATE_TOPIC_FILTER, QoS::AtLeastOnce)
+        .await?;
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                break;
+            }
+            event = eventloop.poll() => {
+                match event {
+                    Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                        record_report(&state.db, publish.topic.as_str(), publish.payload.as_ref())
+                            .await;
+                    }
+                    Ok(Event::Incoming(Incoming::Disconnect)) => anyhow::bail!("mqtt disconnected"),
+                    Ok(_) => {}
+                    Err(err) => {
+                        anyhow::bail!(err);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn record_report(db: &PgPool, topic: &str, payload: &[u8]) {
+    let Some(node_mqtt_id) = topic
+        .strip_prefix("iot/")
+        .and_then(|rest| rest.strip_suffix("/forwarder-config/state"))
+        .filter(|id| !id.is_empty() && !id.contains('/'))
+    else {
+        return;
+    };
+    let report: ForwarderConfigReport = match serde_json::from_slice(payload) {
+        Ok(report) => report,
+        Err(err) => {
+            tracing::debug!(node = %node_mqtt_id, "invalid forwarder config report: {err}");
+            return;
+        }
+    };
+    if let Err(err) = sqlx::query(
+        r#"
+        INSERT INTO node_forwarder_config_state (
+            node_mqtt_id,
+            applied_version,
+            applied_at,
+            rejected_version,
+            error,
+            effective,
+            reported_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, now())
+        ON CONFLICT (node_mqtt_id)
+        DO UPDATE SET
+            applied_version = EXCLUDED.applied_version,
+            applied_at = EXCLUDED.applied_at,
+            rejected_version = EXCLUDED.rejected_version,
+            error = EXCLUDED.error,
+            effective = EXCLUDED.effective,
+            reported_at = EXCLUDED.reported_at
+        "#,
+    )
+    .bind(node_mqtt_id)
+    .bind(report.applied_version)
+    .bind(report.applied_at)
+    .bind(report.rejected_version)
+    .bind(report.error)
+    .bind(report.effective.map(SqlJson))
+    .execute(db)
+    .await
+    {
+        tracing::warn!(node = %node_mqtt_id, error = %err, "failed to record forwarder config report");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn version_ignores_unset_fields_and_validation_mirrors_the_node() {
+        assert_eq!(
+            ForwarderSettings::default().version(),
+            format!("{:x}", Sha256::digest(b"{}"))
+        );
+        let settings = ForwarderSettings {
+            replay_msgs_per_sec: Some(0),
+            segment_roll_bytes: Some(64 * 1024 * 1024),
+            max_spool_bytes: Some(100 * 1024 * 1024),
+            ..Default::default()
+        };
+        let errors = settings.validate();
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(serde_json::from_value::<ForwarderSettings>(json!({"replay_rate": 5})).is_err());
+    }
+
+    #[test]
+    fn drift_compares_desired_fields_with_the_reported_values() {
+        let settings = ForwarderSettings {
+            replay_msgs_per_sec: Some(500),
+            max_spool_age_seconds: Some(0),
+            ..Default::default()
+        };
+        let effective = json!({
+            "max_spool_bytes": 1073741824u64,
+            "max_spool_age_seconds": null,
+            "replay_msgs_per_sec": 2000,
+        });
+        assert_eq!(
+            settings.drifted_fields(&effective),
+            vec!["replay_msgs_per_sec"]
+        );
+
+        let version = settings.version();
+        assert_eq!(drift_status(&version, None, None, false), "unreported");
+        assert_eq!(
+            drift_status(&version, Some(&version), None, true),
+            "in_sync"
+        );
+        assert_eq!(
+            drift_status(&version, Some("old"), Some(&version), true),
+            "rejected"
+        );
+        assert_eq!(drift_status(&version, Some("old"), None, true), "pending");
+    }
+}
@@ -0,0 +1,423 @@
+//! Chart annotations and alarms for data lost by node-forwarder.
+//!
+//! telemetry-sidecar writes one `node_forwarder_loss_events` row per loss range and fills in the
+//! time range once the sample after the gap arrives. This service annotates each event on the
+//! node's sensors, closes the annotation of an event annotated while still open once its end
+//! arrives, and, when `CORE_DATA_LOSS_ALARM_ENABLED` is set, keeps a per-node alarm firing
+//! until the node has gone [`ALARM_QUIET_HOURS`] without losing data.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::state::AppState;
+
+const TICK: Duration = Duration::from_secs(30);
+/// Events whose end is still unknown after this long are annotated up to the time they were reported.
+const UNRESOLVED_ANNOTATE_AFTER_MINUTES: i32 = 10;
+const BATCH_LIMIT: i64 = 200;
+pub(crate) const ALARM_ORIGIN: &str = "data_loss";
+const ALARM_QUIET_HOURS: i32 = 24;
+
+#[derive(sqlx::FromRow)]
+struct PendingEvent {
+    id: i64,
+    node_id: Uuid,
+    node_name: String,
+    start_seq: i64,
+    end_seq: i64,
+    kind: String,
+    reason: Option<String>,
+    time_start: Option<DateTime<Utc>>,
+    time_end: Option<DateTime<Utc>>,
+    dropped_at: Option<DateTime<Utc>>,
+    received_at: DateTime<Utc>,
+}
+
+pub(crate) fn alarm_target_key(node_id: Uuid) -> String {
+    format!("data_loss:{node_id}")
+}
+
+fn alarm_name(node_name: &str) -> String {
+    format!("Data loss on {node_name}")
+}
+
+/// Samples covered by an inclusive seq range.
+pub(crate) fn lost_samples(start_seq: i64, end_seq: i64) -> i64 {
+    (end_seq - start_seq + 1).max(0)
+}
+
+fn annotation_label(kind: &str, reason: Option<&str>, samples: i64) -> String {
+    let noun = if samples == 1 { "sample" } else { "samples" };
+    match reason.map(str::trim).filter(|reason| !reason.is_empty()) {
+        Some(reason) => format!("Data lost: {samples} {noun} {kind} ({reason})"),
+        None => format!("Data lost: {samples} {noun} {kind}"),
+    }
+}
+
+pub struct ForwarderLossService {
+    state: AppState,
+}
+
+impl ForwarderLossService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    pub fn start(self, cancel: CancellationToken) {
+        let alarm_enabled = self.state.config.data_loss_alarm_enabled;
+        let db = self.state.db.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(TICK);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if let Err(err) = annotate_pending(&db).await {
+                            tracing::warn!("data loss annotation failed: {err:#}");
+                        }
+                        if let Err(err) = resolve_annotations(&db).await {
+                            tracing::warn!("data loss annotation update failed: {err:#}");
+                        }
+                        if alarm_enabled {
+                            if let Err(err) = update_alarms(&db).await {
+                                tracing::warn!("data loss alarm update failed: {err:#}");
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Events only match a node once it reports its forwarder id in `config.agent_node_id`.
+const PENDING_SELECT: &str = r#"
+    SELECT
+        e.id,
+        n.id AS node_id,
+        n.name AS node_name,
+        e.start_seq,
+        e.end_seq,
+        e.kind,
+        e.reason,
+        e.time_start,
+        e.time_end,
+        e.dropped_at,
+        e.received_at
+    FROM node_forwarder_loss_events e
+    JOIN nodes n ON NULLIF(TRIM(n.config->>'agent_node_id'), '') = e.node_mqtt_id
+"#;
+
+async fn annotate_pending(db: &PgPool) -> Result<()> {
+    let events: Vec<PendingEvent> = sqlx::query_as(&format!(
+        r#"
+        {PENDING_SELECT}
+        WHERE e.annotated_at IS NULL
+          AND (
+              e.time_end IS NOT NULL
+              OR e.received_at < now() - make_interval(mins => $1)
+          )
+        ORDER BY e.id
+        LIMIT $2
+        "#
+    ))
+    .bind(UNRESOLVED_ANNOTATE_AFTER_MINUTES)
+    .bind(BATCH_LIMIT)
+    .fetch_all(db)
+    .await?;
+
+    for event in events {
+        let sensor_ids: Vec<String> = sqlx::query_scalar(
+            "SELECT sensor_id FROM sensors WHERE node_id = $1 AND deleted_at IS NULL ORDER BY sensor_id",
+        )
+        .bind(event.node_id)
+        .fetch_all(db)
+        .await?;
+        // Without a sample before the gap, the drop time is the best estimate of where it started.
+        let time_start = event
+            .time_start
+            .or(event.dropped_at)
+            .unwrap_or(event.received_at);
+        let time_end = event.time_end.unwrap_or(event.received_at).max(time_start);
+        let samples = lost_samples(event.start_seq, event.end_seq);
+        let chart_state = serde_json::json!({
+            "type": "data_loss",
+            "loss_event_id": event.id,
+            "node_id": event.node_id,
+            "kind": event.kind,
+            "start_seq": event.start_seq,
+            "end_seq": event.end_seq,
+            "samples": samples,
+            "resolved": event.time_end.is_some(),
+        });
+
+        let mut tx = db.begin().await?;
+        let annotation_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO chart_annotations (chart_state, sensor_ids, time_start, time_end, label)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#,
+        )
+        .bind(SqlJson(&chart_state))
+        .bind(&sensor_ids)
+        .bind(time_start)
+        .bind(time_end)
+        .bind(annotation_label(
+            &event.kind,
+            event.reason.as_deref(),
+            samples,
+        ))
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            UPDATE node_forwarder_loss_events
+            SET annotation_id = $2, annotated_at = now()
+            WHERE id = $1
+            "#,
+        )
+        .bind(event.id)
+        .bind(annotation_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        tracing::info!(
+            node = %event.node_name,
+            loss_event_id = event.id,
+            samples,
+            "annotated forwarder data loss"
+        );
+    }
+    Ok(())
+}
+
+/// Moves the end of annotations written before their gap had one onto the first sample after
+/// the gap. Annotations a user has deleted stay deleted.
+async fn resolve_annotations(db: &PgPool) -> Result<()> {
+    let updated = sqlx::query(
+        r#"
+        UPDATE chart_annotations a
+        SET time_start = COALESCE(e.time_start, a.time_start),
+            time_end = GREATEST(e.time_end, COALESCE(e.time_start, a.time_start)),
+            chart_state = jsonb_set(a.chart_state, '{resolved}', 'true'::jsonb),
+            updated_at = now()
+        FROM node_forwarder_loss_events e
+        WHERE e.annotation_id = a.id
+          AND e.time_end IS NOT NULL
+          AND a.chart_state->>'type' = 'data_loss'
+          AND a.chart_state->>'resolved' = 'false'
+        "#,
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+    if updated > 0 {
+        tracing::info!(
+            annotations = updated,
+            "closed forwarder data loss annotations"
+        );
+    }
+    Ok(())
+}
+
+async fn update_alarms(db: &PgPool) -> Result<()> {
+    let events: Vec<PendingEvent> = sqlx::query_as(&format!(
+        r#"
+        {PENDING_SELECT}
+        WHERE e.alarm_notified_at IS NULL
+          AND e.received_at > now() - make_interval(hours => $1)
+        ORDER BY e.id
+        LIMIT $2
+        "#
+    ))
+    .bind(ALARM_QUIET_HOURS)
+    .bind(BATCH_LIMIT)
+    .fetch_all(db)
+    .await?;
+
+    for event in events {
+        let samples = lost_samples(event.start_seq, event.end_seq);
+        let message = format!(
+            "{} lost {samples} samples ({})",
+            event.node_name,
+            event.reason.as_deref().unwrap_or(&event.kind)
+        );
+        raise_alarm(db, event.node_id, &event.node_name, &message).await?;
+        sqlx::query(
+            "UPDATE node_forwarder_loss_events SET alarm_notified_at = now() WHERE id = $1",
+        )
+        .bind(event.id)
+        .execute(db)
+        .await?;
+    }
+
+    let quiet: Vec<(i64, Uuid, String)> = sqlx::query_as(
+        r#"
+        SELECT a.id, a.node_id, a.name
+        FROM alarms a
+        WHERE a.origin = $1
+          AND a.status = 'firing'
+          AND a.node_id IS NOT NULL
+          AND NOT EXISTS (
+              SELECT 1
+              FROM node_forwarder_loss_events e
+              JOIN nodes n ON NULLIF(TRIM(n.config->>'agent_node_id'), '') = e.node_mqtt_id
+              WHERE n.id = a.node_id
+                AND e.received_at > now() - make_interval(hours => $2)
+          )
+        "#,
+    )
+    .bind(ALARM_ORIGIN)
+    .bind(ALARM_QUIET_HOURS)
+    .fetch_all(db)
+    .await?;
+    for (alarm_id, node_id, name) in quiet {
+        resolve_alarm(db, alarm_id, node_id, &name).await?;
+    }
+    Ok(())
+}
+
+async fn raise_alarm(db: &PgPool, node_id: Uuid, node_name: &str, message: &str) -> Result<()> {
+    let now = Utc::now();
+    let name = alarm_name(node_name);
+    let mut tx = db.begin().await?;
+    let rule = serde_json::json!({ "type": "data_loss", "severity": "warning" });
+    let existing: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM alarms
+        WHERE origin = $1 AND node_id = $2
+        LIMIT 1
+        "#,
+    )
+    .bind(ALARM_ORIGIN)
+    .bind(node_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let alarm_id = if let Some(alarm_id) = existing {
+        sqlx::query(
+            r#"
+            UPDATE alarms SET name = $2, status = 'firing', last_fired = NOW(), resolved_at = NULL
+            WHERE id = $1
+            "#,
+        )
+        .bind(alarm_id)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?;
+        alarm_id
+    } else {
+        sqlx::query_scalar(
+            r#"
+            INSERT INTO alarms (name, node_id, rule, status, origin, last_fired)
+            VALUES ($1, $2, $3, 'firing', $4, NOW())
+            RETURNING id
+            "#,
+        )
+        .bind(&name)
+        .bind(node_id)
+        .bind(&rule)
+        .bind(ALARM_ORIGIN)
+        .fetch_one(&mut *tx)
+        .await?
+    };
+    record_alarm_event(
+        &mut tx, now, alarm_id, node_id, &name, "firing", "fired", message,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn resolve_alarm(db: &PgPool, alarm_id: i64, node_id: Uuid, name: &str) -> Result<()> {
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+    sqlx::query("UPDATE alarms SET status = 'ok', resolved_at = $2 WHERE id = $1")
+        .bind(alarm_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+    let message = format!("No data lost in the last {ALARM_QUIET_HOURS} hours");
+    record_alarm_event(
+        &mut tx, now, alarm_id, node_id, name, "ok", "resolved", &message,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record_alarm_event(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    now: DateTime<Utc>,
+    alarm_id: i64,
+    node_id: Uuid,
+    name: &str,
+    status: &str,
+    transition: &str,
+    message: &str,
+) -> Result<()> {
+    let target_key = alarm_target_key(node_id);
+    let incident_id = crate::services::incidents::get_or_create_incident(
+        tx,
+        now,
+        &crate::services::incidents::IncidentKey {
+            rule_id: None,
+            target_key: Some(target_key.clone()),
+        },
+        "warning",
+        name,
+        transition,
+    )
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO alarm_events (
+            alarm_id,
+            node_id,
+            status,
+            message,
+            origin,
+            transition,
+            incident_id,
+            target_key
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(alarm_id)
+    .bind(node_id)
+    .bind(status)
+    .bind(message)
+    .bind(ALARM_ORIGIN)
+    .bind(transition)
+    .bind(incident_id)
+    .bind(target_key)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_name_the_sample_count_and_reason() {
+        assert_eq!(
+            annotation_label("dropped", Some("spool cap"), lost_samples(10, 14)),
+            "Data lost: 5 samples dropped (spool cap)"
+        );
+        assert_eq!(
+            annotation_label("expired", Some("  "), lost_samples(7, 7)),
+            "Data lost: 1 sample expired"
+        );
+        assert_eq!(lost_samples(9, 3), 0);
+    }
+}
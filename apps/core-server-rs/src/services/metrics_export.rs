@@ -0,0 +1,369 @@
+//! Chunked reads behind `GET /api/metrics/export`.
+//!
+//! An export is served one chunk per request so multi-year ranges download as
+//! a sequence of bounded files, each of which can be retried on its own by
+//! repeating its cursor. Chunks the analysis lake has fully replicated are
+//! read from Parquet through DuckDB; anything newer comes from Postgres.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use sqlx::PgPool;
+
+use crate::services::analysis::lake::{read_replication_state, AnalysisLakeConfig};
+use crate::services::analysis::parquet_duckdb::{
+    BucketAggregationMode, DuckDbQueryService, MetricsExportRow,
+};
+
+/// Upper bound on rows held in memory for one chunk.
+pub(crate) const MAX_EXPORT_ROWS_PER_CHUNK: usize = 500_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportSource {
+    Postgres,
+    Lake,
+}
+
+impl ExportSource {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Postgres => "postgres",
+            Self::Lake => "lake",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportMode {
+    Raw,
+    Buckets {
+        interval_seconds: i64,
+        aggregation: BucketAggregationMode,
+    },
+}
+
+impl ExportMode {
+    /// Name of the per-row integer column: quality flag or sample count.
+    pub(crate) fn extra_column(self) -> &'static str {
+        match self {
+            Self::Raw => "quality",
+            Self::Buckets { .. } => "samples",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ExportChunk {
+    pub start: DateTime<Utc>,
+    /// Exclusive end of the range this chunk covers.
+    pub end: DateTime<Utc>,
+    pub next_cursor: Option<DateTime<Utc>>,
+    pub source: ExportSource,
+    /// Ordered by `(ts, sensor_id)`.
+    pub rows: Vec<MetricsExportRow>,
+}
+
+fn align_down(ts: DateTime<Utc>, interval_seconds: i64) -> DateTime<Utc> {
+    let epoch = ts.timestamp().div_euclid(interval_seconds) * interval_seconds;
+    Utc.timestamp_opt(epoch, 0).single().unwrap_or(ts)
+}
+
+/// Exclusive end of the chunk starting at `cursor`, before any row cap.
+/// Bucketed chunks end on a bucket boundary and hold at most
+/// [`MAX_EXPORT_ROWS_PER_CHUNK`] buckets across all sensors.
+pub(crate) fn plan_chunk_end(
+    cursor: DateTime<Utc>,
+    end: DateTime<Utc>,
+    mode: ExportMode,
+    sensor_count: usize,
+    chunk_span: Duration,
+) -> DateTime<Utc> {
+    let mut candidate = cursor + chunk_span;
+    if let ExportMode::Buckets {
+        interval_seconds, ..
+    } = mode
+    {
+        let interval_seconds = interval_seconds.max(1);
+        let max_buckets = (MAX_EXPORT_ROWS_PER_CHUNK / sensor_count.max(1)).max(1) as i64;
+        let first_bucket = align_down(cursor, interval_seconds);
+        candidate = candidate
+            .min(first_bucket + Duration::seconds(interval_seconds.saturating_mul(max_buckets)));
+        let aligned = align_down(candidate, interval_seconds);
+        candidate = if aligned > cursor {
+            aligned
+        } else {
+            first_bucket + Duration::seconds(interval_seconds)
+        };
+    }
+    candidate.min(end)
+}
+
+/// Trims rows fetched with a `cap + 1` limit back under the cap without
+/// splitting a timestamp across chunks. Returns where the next chunk starts.
+pub(crate) fn split_at_row_cap(
+    rows: &mut Vec<MetricsExportRow>,
+    cap: usize,
+) -> Option<DateTime<Utc>> {
+    if rows.len() <= cap {
+        return None;
+    }
+    let boundary = rows[cap].ts;
+    rows.retain(|row| row.ts < boundary);
+    Some(boundary)
+}
+
+/// End of the range the lake holds completely, if replication has run.
+pub(crate) fn lake_watermark(lake: &AnalysisLakeConfig) -> Option<DateTime<Utc>> {
+    let state = read_replication_state(lake).ok()?;
+    let raw = state.computed_through_ts?;
+    DateTime::parse_from_rfc3339(&raw)
+        .ok()
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+fn postgres_aggregation(aggregation: BucketAggregationMode) -> &'static str {
+    match aggregation {
+        BucketAggregationMode::Avg => "avg(value)",
+        BucketAggregationMode::Last => "last(value, ts)",
+        BucketAggregationMode::Sum => "sum(value)",
+        BucketAggregationMode::Min => "min(value)",
+        BucketAggregationMode::Max => "max(value)",
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ExportDbRow {
+    sensor_id: String,
+    ts: DateTime<Utc>,
+    value: f64,
+    extra: i64,
+}
+
+impl From<ExportDbRow> for MetricsExportRow {
+    fn from(row: ExportDbRow) -> Self {
+        Self {
+            sensor_id: row.sensor_id,
+            ts: row.ts,
+            value: row.value,
+            extra: row.extra,
+        }
+    }
+}
+
+async fn read_postgres(
+    db: &PgPool,
+    sensor_ids: &[String],
+    mode: ExportMode,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<MetricsExportRow>> {
+    let rows: Vec<ExportDbRow> = match mode {
+        ExportMode::Raw => {
+            sqlx::query_as(
+                r#"
+                SELECT sensor_id, ts, value, COALESCE(quality, 0)::bigint AS extra
+                FROM metrics
+                WHERE sensor_id = ANY($1)
+                  AND ts >= $2
+                  AND ts < $3
+                ORDER BY ts, sensor_id
+                LIMIT $4
+                "#,
+            )
+            .bind(sensor_ids)
+            .bind(start)
+            .bind(end)
+            .bind(limit as i64)
+            .fetch_all(db)
+            .await?
+        }
+        ExportMode::Buckets {
+            interval_seconds,
+            aggregation,
+        } => {
+            // Epoch-aligned like the lake reader, so buckets line up across sources.
+            sqlx::query_as(&format!(
+                r#"
+                SELECT
+                    sensor_id,
+                    to_timestamp((floor(extract(epoch FROM ts) / $4) * $4)::double precision) AS ts,
+                    {agg}::double precision AS value,
+                    count(*) AS extra
+                FROM metrics
+                WHERE sensor_id = ANY($1)
+                  AND ts >= $2
+                  AND ts < $3
+                GROUP BY 1, 2
+                ORDER BY 2, 1
+                "#,
+                agg = postgres_aggregation(aggregation),
+            ))
+            .bind(sensor_ids)
+            .bind(start)
+            .bind(end)
+            .bind(interval_seconds.max(1))
+            .fetch_all(db)
+            .await?
+        }
+    };
+    Ok(rows.into_iter().map(MetricsExportRow::from).collect())
+}
+
+async fn read_lake(
+    duckdb: &DuckDbQueryService,
+    lake: &AnalysisLakeConfig,
+    sensor_ids: &[String],
+    mode: ExportMode,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<MetricsExportRow>> {
+    match mode {
+        ExportMode::Raw => Ok(duckdb
+            .read_metrics_points_page_from_lake(lake, start, end, sensor_ids.to_vec(), limit)
+            .await?
+            .into_iter()
+            .map(|row| MetricsExportRow {
+                sensor_id: row.sensor_id,
+                ts: row.ts,
+                value: row.value,
+                extra: row.quality as i64,
+            })
+            .collect()),
+        ExportMode::Buckets {
+            interval_seconds,
+            aggregation,
+        } => {
+            let mut rows: Vec<MetricsExportRow> = duckdb
+                .read_metrics_buckets_from_lake_with_mode(
+                    lake,
+                    start,
+                    end,
+                    sensor_ids.to_vec(),
+                    interval_seconds,
+                    aggregation,
+                )
+                .await?
+                .into_iter()
+                .map(|row| MetricsExportRow {
+                    sensor_id: row.sensor_id,
+                    ts: row.bucket,
+                    value: row.value,
+                    extra: row.samples,
+                })
+                .collect();
+            rows.sort_by(|a, b| a.ts.cmp(&b.ts).then_with(|| a.sensor_id.cmp(&b.sensor_id)));
+            Ok(rows)
+        }
+    }
+}
+
+/// Reads the chunk of `[cursor, end)` that starts at `cursor`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn read_chunk(
+    db: &PgPool,
+    duckdb: &DuckDbQueryService,
+    lake: &AnalysisLakeConfig,
+    sensor_ids: &[String],
+    mode: ExportMode,
+    cursor: DateTime<Utc>,
+    end: DateTime<Utc>,
+    chunk_span: Duration,
+) -> Result<ExportChunk> {
+    let mut chunk_end = plan_chunk_end(cursor, end, mode, sensor_ids.len(), chunk_span);
+    let source = match lake_watermark(lake) {
+        Some(watermark) if chunk_end <= watermark => ExportSource::Lake,
+        _ => ExportSource::Postgres,
+    };
+    let limit = MAX_EXPORT_ROWS_PER_CHUNK + 1;
+    let mut rows = match source {
+        ExportSource::Lake => {
+            read_lake(duckdb, lake, sensor_ids, mode, cursor, chunk_end, limit).await?
+        }
+        ExportSource::Postgres => {
+            read_postgres(db, sensor_ids, mode, cursor, chunk_end, limit).await?
+        }
+    };
+    if let Some(boundary) = split_at_row_cap(&mut rows, MAX_EXPORT_ROWS_PER_CHUNK) {
+        chunk_end = boundary;
+    }
+    Ok(ExportChunk {
+        start: cursor,
+        end: chunk_end,
+        next_cursor: (chunk_end < end).then_some(chunk_end),
+        source,
+        rows,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, hour, minute, 0).unwrap()
+    }
+
+    fn row(sensor_id: &str, at: DateTime<Utc>) -> MetricsExportRow {
+        MetricsExportRow {
+            sensor_id: sensor_id.to_string(),
+            ts: at,
+            value: 1.0,
+            extra: 0,
+        }
+    }
+
+    #[test]
+    fn raw_chunks_follow_the_requested_span() {
+        let end = ts(12, 0);
+        assert_eq!(
+            plan_chunk_end(ts(0, 0), end, ExportMode::Raw, 3, Duration::hours(5)),
+            ts(5, 0)
+        );
+        assert_eq!(
+            plan_chunk_end(ts(10, 0), end, ExportMode::Raw, 3, Duration::hours(5)),
+            end
+        );
+    }
+
+    #[test]
+    fn bucket_chunks_end_on_bucket_boundaries_and_respect_the_row_cap() {
+        let mode = ExportMode::Buckets {
+            interval_seconds: 3600,
+            aggregation: BucketAggregationMode::Avg,
+        };
+        let far = ts(0, 0) + Duration::days(400);
+        // Mid-bucket cursor: the chunk still ends on the hour.
+        assert_eq!(
+            plan_chunk_end(ts(0, 30), far, mode, 1, Duration::minutes(200)),
+            ts(3, 0)
+        );
+        // 250k sensors leave room for two hourly buckets per chunk.
+        assert_eq!(
+            plan_chunk_end(ts(0, 0), far, mode, 250_000, Duration::days(31)),
+            ts(2, 0)
+        );
+        // A span shorter than one bucket still advances by a bucket.
+        assert_eq!(
+            plan_chunk_end(ts(0, 10), far, mode, 1, Duration::minutes(5)),
+            ts(1, 0)
+        );
+    }
+
+    #[test]
+    fn row_cap_never_splits_a_timestamp() {
+        let mut rows = vec![
+            row("a", ts(0, 0)),
+            row("b", ts(0, 0)),
+            row("a", ts(0, 1)),
+            row("b", ts(0, 1)),
+            row("a", ts(0, 2)),
+        ];
+        assert_eq!(split_at_row_cap(&mut rows, 3), Some(ts(0, 1)));
+        assert_eq!(rows.len(), 2);
+
+        let mut rows = vec![row("a", ts(0, 0)), row("a", ts(0, 1))];
+        assert_eq!(split_at_row_cap(&mut rows, 2), None);
+        assert_eq!(rows.len(), 2);
+    }
+}
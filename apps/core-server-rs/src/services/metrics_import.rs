@@ -0,0 +1,1042 @@
+//! Bulk historical imports behind `POST /api/metrics/imports`.
+//!
+//! A file is uploaded once, then imported by a `metrics_import_v1` analysis
+//! job that maps its columns onto sensors. This module holds the upload
+//! storage and the per-row parsing (timestamps, units, layouts) so the job
+//! itself only deals with batching and writing.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::services::analysis::security;
+
+pub(crate) const METRICS_IMPORT_JOB_TYPE: &str = "metrics_import_v1";
+/// Largest file accepted by the upload endpoint.
+pub(crate) const MAX_IMPORT_UPLOAD_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+/// Uploads nobody imported are removed after this long.
+pub(crate) const IMPORT_UPLOAD_TTL_HOURS: i64 = 72;
+const MAX_REPORTED_ISSUES: usize = 100;
+const MAX_REPORTED_UNMAPPED: usize = 50;
+
+const UPLOADS_DIR: &str = "imports";
+const UPLOAD_MANIFEST_FILE: &str = "upload.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ImportFormat {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "csv" | "text/csv" => Some(Self::Csv),
+            "parquet" | "application/vnd.apache.parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_filename(name: &str) -> Option<Self> {
+        let ext = Path::new(name).extension()?.to_str()?;
+        Self::parse(ext)
+    }
+
+    fn data_file_name(self) -> &'static str {
+        match self {
+            Self::Csv => "data.csv",
+            Self::Parquet => "data.parquet",
+        }
+    }
+}
+
+/// Manifest stored next to an uploaded file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ImportUpload {
+    pub upload_id: Uuid,
+    pub format: ImportFormat,
+    pub filename: Option<String>,
+    pub bytes: u64,
+    /// Header columns; for Parquet uploads, the file's column names.
+    pub columns: Vec<String>,
+    pub delimiter: char,
+    /// `AuthenticatedUser::id` of the uploader.
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub(crate) fn upload_dir(tmp_path: &Path, upload_id: Uuid) -> PathBuf {
+    tmp_path.join(UPLOADS_DIR).join(upload_id.to_string())
+}
+
+pub(crate) fn upload_data_path(tmp_path: &Path, upload_id: Uuid, format: ImportFormat) -> PathBuf {
+    upload_dir(tmp_path, upload_id).join(format.data_file_name())
+}
+
+pub(crate) fn create_upload_dir(tmp_path: &Path, upload_id: Uuid) -> Result<PathBuf> {
+    let root = tmp_path.join(UPLOADS_DIR);
+    security::ensure_dir_mode(&root, 0o700)?;
+    let dir = upload_dir(tmp_path, upload_id);
+    security::ensure_dir_mode(&dir, 0o700)?;
+    Ok(dir)
+}
+
+pub(crate) fn write_upload(tmp_path: &Path, upload: &ImportUpload) -> Result<()> {
+    let path = upload_dir(tmp_path, upload.upload_id).join(UPLOAD_MANIFEST_FILE);
+    let payload = serde_json::to_vec_pretty(upload)?;
+    std::fs::write(&path, payload)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    let _ = security::ensure_file_mode(&path, 0o600);
+    Ok(())
+}
+
+/// Looks up an upload by id; anything that is not a UUID is treated as missing.
+pub(crate) fn read_upload(tmp_path: &Path, upload_id: &str) -> Result<Option<ImportUpload>> {
+    let Ok(upload_id) = Uuid::parse_str(upload_id.trim()) else {
+        return Ok(None);
+    };
+    let path = upload_dir(tmp_path, upload_id).join(UPLOAD_MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw = std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(Some(serde_json::from_slice(&raw)?))
+}
+
+pub(crate) fn remove_upload(tmp_path: &Path, upload_id: Uuid) {
+    let dir = upload_dir(tmp_path, upload_id);
+    if dir.exists() {
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
+
+/// Removes uploads older than [`IMPORT_UPLOAD_TTL_HOURS`]. Returns how many were removed.
+pub(crate) fn prune_stale_uploads(tmp_path: &Path, now: DateTime<Utc>) -> usize {
+    let root = tmp_path.join(UPLOADS_DIR);
+    let Ok(entries) = std::fs::read_dir(&root) else {
+        return 0;
+    };
+    let cutoff = now - chrono::Duration::hours(IMPORT_UPLOAD_TTL_HOURS);
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(upload_id) = name.to_str().and_then(|raw| Uuid::parse_str(raw).ok()) else {
+            continue;
+        };
+        let stale = match read_upload(tmp_path, &upload_id.to_string()) {
+            Ok(Some(upload)) => upload.created_at < cutoff,
+            // A directory without a readable manifest is a failed upload.
+            _ => entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .map(|modified| DateTime::<Utc>::from(modified) < cutoff)
+                .unwrap_or(true),
+        };
+        if stale {
+            remove_upload(tmp_path, upload_id);
+            removed += 1;
+        }
+    }
+    removed
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportLayout {
+    /// One timestamp column plus one value column per sensor.
+    #[default]
+    Wide,
+    /// One row per point: timestamp, source name and value columns.
+    Long,
+}
+
+/// Maps one source (a column in `wide` layout, a source name in `long`
+/// layout) onto a sensor. `scale`/`offset` adjust raw values before any
+/// `unit` conversion to the sensor's unit.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ImportMapping {
+    pub source: String,
+    pub sensor_id: String,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub scale: Option<f64>,
+    #[serde(default)]
+    pub offset: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct MetricsImportParams {
+    pub upload_id: String,
+    #[serde(default)]
+    pub layout: ImportLayout,
+    pub timestamp_column: String,
+    /// `auto` (default), `epoch_s`, `epoch_ms`, `epoch_us` or a strftime pattern.
+    #[serde(default)]
+    pub timestamp_format: Option<String>,
+    /// IANA zone applied to timestamps without an offset (default UTC).
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// `long` layout: column holding the source name.
+    #[serde(default)]
+    pub sensor_column: Option<String>,
+    /// `long` layout: column holding the value.
+    #[serde(default)]
+    pub value_column: Option<String>,
+    #[serde(default)]
+    pub quality_column: Option<String>,
+    pub mappings: Vec<ImportMapping>,
+    /// Validate and report without writing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Points before this instant are written to the analysis lake instead of
+    /// Postgres. Clamped to the lake's replication watermark.
+    #[serde(default)]
+    pub lake_cutoff: Option<String>,
+}
+
+impl MetricsImportParams {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.timestamp_column.trim().is_empty() {
+            return Err("timestamp_column is required".to_string());
+        }
+        if self.mappings.is_empty() {
+            return Err("At least one mapping is required".to_string());
+        }
+        let mut sources = std::collections::HashSet::new();
+        for mapping in &self.mappings {
+            if mapping.source.trim().is_empty() || mapping.sensor_id.trim().is_empty() {
+                return Err("Mappings need a source and a sensor_id".to_string());
+            }
+            if !sources.insert(mapping.source.trim()) {
+                return Err(format!(
+                    "Duplicate mapping source: {}",
+                    mapping.source.trim()
+                ));
+            }
+            if mapping.scale.is_some_and(|v| !v.is_finite())
+                || mapping.offset.is_some_and(|v| !v.is_finite())
+            {
+                return Err(format!(
+                    "Mapping {} has a non-finite scale or offset",
+                    mapping.source.trim()
+                ));
+            }
+        }
+        if self.layout == ImportLayout::Long
+            && (blank(&self.sensor_column) || blank(&self.value_column))
+        {
+            return Err("long layout requires sensor_column and value_column".to_string());
+        }
+        self.timezone()?;
+        self.lake_cutoff()?;
+        Ok(())
+    }
+
+    pub(crate) fn timezone(&self) -> Result<Tz, String> {
+        match self
+            .timezone
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+        {
+            Some(raw) => raw
+                .parse::<Tz>()
+                .map_err(|_| format!("Unknown timezone: {raw}")),
+            None => Ok(Tz::UTC),
+        }
+    }
+
+    pub(crate) fn lake_cutoff(&self) -> Result<Option<DateTime<Utc>>, String> {
+        match self
+            .lake_cutoff
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+        {
+            Some(raw) => DateTime::parse_from_rfc3339(raw)
+                .map(|ts| Some(ts.with_timezone(&Utc)))
+                .map_err(|_| "lake_cutoff must be an RFC3339 timestamp".to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks that every referenced column exists in an upload's header.
+    pub(crate) fn check_columns(&self, columns: &[String]) -> Result<(), String> {
+        find_column(columns, &self.timestamp_column)?;
+        if let Some(name) = self
+            .quality_column
+            .as_deref()
+            .filter(|v| !v.trim().is_empty())
+        {
+            find_column(columns, name)?;
+        }
+        match self.layout {
+            ImportLayout::Wide => {
+                for mapping in &self.mappings {
+                    find_column(columns, &mapping.source)?;
+                }
+            }
+            ImportLayout::Long => {
+                find_column(columns, self.sensor_column.as_deref().unwrap_or_default())?;
+                find_column(columns, self.value_column.as_deref().unwrap_or_default())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn blank(value: &Option<String>) -> bool {
+    value
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default()
+        .is_empty()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TimestampFormat {
+    Auto,
+    EpochSeconds,
+    EpochMillis,
+    EpochMicros,
+    Pattern(String),
+}
+
+const AUTO_OFFSET_PATTERNS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f%#z", "%Y-%m-%dT%H:%M:%S%.f%#z"];
+const AUTO_NAIVE_PATTERNS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S%.f",
+    "%Y/%m/%d %H:%M",
+];
+
+#[derive(Debug, Clone)]
+pub(crate) struct TimestampParser {
+    format: TimestampFormat,
+    tz: Tz,
+}
+
+impl TimestampParser {
+    pub(crate) fn new(format: Option<&str>, tz: Tz) -> Self {
+        let format = match format.map(str::trim).filter(|v| !v.is_empty()) {
+            None | Some("auto") => TimestampFormat::Auto,
+            Some("epoch_s") => TimestampFormat::EpochSeconds,
+            Some("epoch_ms") => TimestampFormat::EpochMillis,
+            Some("epoch_us") => TimestampFormat::EpochMicros,
+            Some(pattern) => TimestampFormat::Pattern(pattern.to_string()),
+        };
+        Self { format, tz }
+    }
+
+    /// Parses one cell. The flag is set when a local time fell in a DST
+    /// overlap and the earlier instant was chosen.
+    pub(crate) fn parse(&self, raw: &str) -> Result<(DateTime<Utc>, bool), String> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            return Err("empty timestamp".to_string());
+        }
+        match &self.format {
+            TimestampFormat::EpochSeconds => parse_epoch(raw, 1_000_000.0),
+            TimestampFormat::EpochMillis => parse_epoch(raw, 1_000.0),
+            TimestampFormat::EpochMicros => parse_epoch(raw, 1.0),
+            TimestampFormat::Pattern(pattern) => {
+                if let Ok(ts) = DateTime::parse_from_str(raw, pattern) {
+                    return Ok((ts.with_timezone(&Utc), false));
+                }
+                if let Ok(naive) = NaiveDateTime::parse_from_str(raw, pattern) {
+                    return self.localize(naive);
+                }
+                if let Ok(date) = NaiveDate::parse_from_str(raw, pattern) {
+                    return self.localize(date.and_time(chrono::NaiveTime::MIN));
+                }
+                Err(format!("'{raw}' does not match {pattern}"))
+            }
+            TimestampFormat::Auto => {
+                if let Ok(ts) = DateTime::parse_from_rfc3339(raw) {
+                    return Ok((ts.with_timezone(&Utc), false));
+                }
+                for pattern in AUTO_OFFSET_PATTERNS {
+                    if let Ok(ts) = DateTime::parse_from_str(raw, pattern) {
+                        return Ok((ts.with_timezone(&Utc), false));
+                    }
+                }
+                for pattern in AUTO_NAIVE_PATTERNS {
+                    if let Ok(naive) = NaiveDateTime::parse_from_str(raw, pattern) {
+                        return self.localize(naive);
+                    }
+                }
+                if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+                    return self.localize(date.and_time(chrono::NaiveTime::MIN));
+                }
+                Err(format!(
+                    "unrecognised timestamp '{raw}'; set timestamp_format"
+                ))
+            }
+        }
+    }
+
+    fn localize(&self, naive: NaiveDateTime) -> Result<(DateTime<Utc>, bool), String> {
+        match self.tz.from_local_datetime(&naive) {
+            LocalResult::Single(ts) => Ok((ts.with_timezone(&Utc), false)),
+            LocalResult::Ambiguous(earliest, _) => Ok((earliest.with_timezone(&Utc), true)),
+            LocalResult::None => Err(format!(
+                "{naive} does not exist in {} (DST gap)",
+                self.tz.name()
+            )),
+        }
+    }
+}
+
+fn parse_epoch(raw: &str, micros_per_unit: f64) -> Result<(DateTime<Utc>, bool), String> {
+    let value: f64 = raw
+        .parse()
+        .map_err(|_| format!("'{raw}' is not an epoch number"))?;
+    if !value.is_finite() {
+        return Err(format!("'{raw}' is not an epoch number"));
+    }
+    DateTime::from_timestamp_micros((value * micros_per_unit).round() as i64)
+        .map(|ts| (ts, false))
+        .ok_or_else(|| format!("epoch value {raw} is out of range"))
+}
+
+/// `(dimension, factor, offset)` with `base = value * factor + offset`.
+fn unit_definition(unit: &str) -> Option<(&'static str, f64, f64)> {
+    let normalized = unit.trim().to_ascii_lowercase().replace(['°', ' '], "");
+    let def = match normalized.as_str() {
+        "c" | "degc" | "celsius" => ("temperature", 1.0, 0.0),
+        "f" | "degf" | "fahrenheit" => ("temperature", 5.0 / 9.0, -32.0 * 5.0 / 9.0),
+        "k" | "kelvin" => ("temperature", 1.0, -273.15),
+        "kpa" => ("pressure", 1.0, 0.0),
+        "pa" => ("pressure", 0.001, 0.0),
+        "hpa" | "mbar" => ("pressure", 0.1, 0.0),
+        "bar" => ("pressure", 100.0, 0.0),
+        "psi" => ("pressure", 6.894_757_293, 0.0),
+        "inhg" => ("pressure", 3.386_388_64, 0.0),
+        "mm" => ("length", 1.0, 0.0),
+        "cm" => ("length", 10.0, 0.0),
+        "m" => ("length", 1000.0, 0.0),
+        "in" => ("length", 25.4, 0.0),
+        "ft" => ("length", 304.8, 0.0),
+        "m/s" => ("speed", 1.0, 0.0),
+        "km/h" | "kph" => ("speed", 1.0 / 3.6, 0.0),
+        "mph" => ("speed", 0.447_04, 0.0),
+        "kn" | "kt" | "knots" => ("speed", 0.514_444, 0.0),
+        "w" => ("power", 1.0, 0.0),
+        "kw" => ("power", 1000.0, 0.0),
+        "wh" => ("energy", 1.0, 0.0),
+        "kwh" => ("energy", 1000.0, 0.0),
+        "l" => ("volume", 1.0, 0.0),
+        "gal" => ("volume", 3.785_411_784, 0.0),
+        "m3" => ("volume", 1000.0, 0.0),
+        "l/min" | "lpm" => ("flow", 1.0, 0.0),
+        "l/s" => ("flow", 60.0, 0.0),
+        "gpm" => ("flow", 3.785_411_784, 0.0),
+        "m3/h" => ("flow", 1000.0 / 60.0, 0.0),
+        _ => return None,
+    };
+    Some(def)
+}
+
+/// Linear transform from raw file values to the sensor's stored unit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ValueConversion {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl ValueConversion {
+    pub(crate) fn apply(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+}
+
+/// Combines a mapping's `scale`/`offset` with conversion from its `unit`
+/// to the sensor's unit.
+pub(crate) fn resolve_conversion(
+    mapping: &ImportMapping,
+    sensor_unit: Option<&str>,
+) -> Result<ValueConversion, String> {
+    let scale = mapping.scale.unwrap_or(1.0);
+    let offset = mapping.offset.unwrap_or(0.0);
+    let from = mapping
+        .unit
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    let to = sensor_unit.map(str::trim).filter(|v| !v.is_empty());
+    let (unit_scale, unit_offset) = match (from, to) {
+        (None, _) => (1.0, 0.0),
+        (Some(from), Some(to)) if from.eq_ignore_ascii_case(to) => (1.0, 0.0),
+        (Some(from), None) => {
+            return Err(format!(
+                "Sensor {} has no unit to convert {from} into",
+                mapping.sensor_id.trim()
+            ))
+        }
+        (Some(from), Some(to)) => match (unit_definition(from), unit_definition(to)) {
+            (Some((from_dim, f1, o1)), Some((to_dim, f2, o2))) if from_dim == to_dim => {
+                (f1 / f2, (o1 - o2) / f2)
+            }
+            _ => {
+                return Err(format!(
+                    "Cannot convert {from} to {to} for sensor {}",
+                    mapping.sensor_id.trim()
+                ))
+            }
+        },
+    };
+    Ok(ValueConversion {
+        scale: scale * unit_scale,
+        offset: offset * unit_scale + unit_offset,
+    })
+}
+
+/// One parsed point; `mapping` indexes `MetricsImportParams::mappings`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ImportPoint {
+    pub mapping: usize,
+    pub ts: DateTime<Utc>,
+    pub value: f64,
+    pub quality: i16,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ImportIssue {
+    pub line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SensorImportStats {
+    pub source: String,
+    pub sensor_id: String,
+    pub points: u64,
+    pub first_ts: Option<DateTime<Utc>>,
+    pub last_ts: Option<DateTime<Utc>>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Validation half of the job result: what the file contained and what was
+/// wrong with it. Only the first [`MAX_REPORTED_ISSUES`] issues are listed.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ImportReport {
+    pub rows_read: u64,
+    pub rows_rejected: u64,
+    pub points: u64,
+    pub empty_cells: u64,
+    pub ambiguous_local_times: u64,
+    pub issue_counts: BTreeMap<&'static str, u64>,
+    pub issues: Vec<ImportIssue>,
+    pub unmapped_sources: BTreeMap<String, u64>,
+    pub sensors: Vec<SensorImportStats>,
+}
+
+impl ImportReport {
+    pub(crate) fn new(mappings: &[ImportMapping]) -> Self {
+        Self {
+            rows_read: 0,
+            rows_rejected: 0,
+            points: 0,
+            empty_cells: 0,
+            ambiguous_local_times: 0,
+            issue_counts: BTreeMap::new(),
+            issues: Vec::new(),
+            unmapped_sources: BTreeMap::new(),
+            sensors: mappings
+                .iter()
+                .map(|mapping| SensorImportStats {
+                    source: mapping.source.trim().to_string(),
+                    sensor_id: mapping.sensor_id.trim().to_string(),
+                    points: 0,
+                    first_ts: None,
+                    last_ts: None,
+                    min: None,
+                    max: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn issue(&mut self, line: u64, column: Option<&str>, kind: &'static str, message: String) {
+        *self.issue_counts.entry(kind).or_default() += 1;
+        if self.issues.len() < MAX_REPORTED_ISSUES {
+            self.issues.push(ImportIssue {
+                line,
+                column: column.map(str::to_string),
+                kind,
+                message,
+            });
+        }
+    }
+
+    fn unmapped(&mut self, source: &str) {
+        *self.issue_counts.entry("unmapped_source").or_default() += 1;
+        if let Some(count) = self.unmapped_sources.get_mut(source) {
+            *count += 1;
+        } else if self.unmapped_sources.len() < MAX_REPORTED_UNMAPPED {
+            self.unmapped_sources.insert(source.to_string(), 1);
+        }
+    }
+
+    fn record(&mut self, point: &ImportPoint) {
+        self.points += 1;
+        let stats = &mut self.sensors[point.mapping];
+        stats.points += 1;
+        stats.first_ts = Some(stats.first_ts.map_or(point.ts, |ts| ts.min(point.ts)));
+        stats.last_ts = Some(stats.last_ts.map_or(point.ts, |ts| ts.max(point.ts)));
+        stats.min = Some(stats.min.map_or(point.value, |v| v.min(point.value)));
+        stats.max = Some(stats.max.map_or(point.value, |v| v.max(point.value)));
+    }
+}
+
+#[derive(Debug)]
+enum MappedLayout {
+    /// `(column index, mapping index)` pairs.
+    Wide { columns: Vec<(usize, usize)> },
+    Long {
+        sensor_index: usize,
+        value_index: usize,
+        sources: HashMap<String, usize>,
+    },
+}
+
+/// Turns CSV records into points according to the import params.
+#[derive(Debug)]
+pub(crate) struct RowMapper {
+    timestamp_index: usize,
+    quality_index: Option<usize>,
+    layout: MappedLayout,
+    headers: Vec<String>,
+    parser: TimestampParser,
+    conversions: Vec<ValueConversion>,
+}
+
+fn find_column(headers: &[String], name: &str) -> Result<usize, String> {
+    let name = name.trim();
+    headers
+        .iter()
+        .position(|header| header == name)
+        .or_else(|| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| format!("Column not found: {name}"))
+}
+
+impl RowMapper {
+    /// `conversions` is parallel to `params.mappings`.
+    pub(crate) fn new(
+        params: &MetricsImportParams,
+        headers: &csv::StringRecord,
+        conversions: Vec<ValueConversion>,
+    ) -> Result<Self, String> {
+        let headers: Vec<String> = headers
+            .iter()
+            .map(|header| header.trim_start_matches('\u{feff}').trim().to_string())
+            .collect();
+        let timestamp_index = find_column(&headers, &params.timestamp_column)?;
+        let quality_index = match params.quality_column.as_deref().map(str::trim) {
+            Some(name) if !name.is_empty() => Some(find_column(&headers, name)?),
+            _ => None,
+        };
+        let layout = match params.layout {
+            ImportLayout::Wide => MappedLayout::Wide {
+                columns: params
+                    .mappings
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, mapping)| Ok((find_column(&headers, &mapping.source)?, idx)))
+                    .collect::<Result<_, String>>()?,
+            },
+            ImportLayout::Long => MappedLayout::Long {
+                sensor_index: find_column(
+                    &headers,
+                    params.sensor_column.as_deref().unwrap_or_default(),
+                )?,
+                value_index: find_column(
+                    &headers,
+                    params.value_column.as_deref().unwrap_or_default(),
+                )?,
+                sources: params
+                    .mappings
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, mapping)| (mapping.source.trim().to_string(), idx))
+                    .collect(),
+            },
+        };
+        let parser = TimestampParser::new(params.timestamp_format.as_deref(), params.timezone()?);
+        Ok(Self {
+            timestamp_index,
+            quality_index,
+            layout,
+            headers,
+            parser,
+            conversions,
+        })
+    }
+
+    fn value(&self, raw: &str) -> Result<Option<f64>, String> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            return Ok(None);
+        }
+        match raw.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(Some(value)),
+            _ => Err(format!("'{raw}' is not a number")),
+        }
+    }
+
+    fn point(&self, mapping: usize, ts: DateTime<Utc>, value: f64, quality: i16) -> ImportPoint {
+        ImportPoint {
+            mapping,
+            ts,
+            value: self.conversions[mapping].apply(value),
+            quality,
+        }
+    }
+
+    /// Appends the record's points to `out` and its problems to `report`.
+    pub(crate) fn map_record(
+        &self,
+        record: &csv::StringRecord,
+        line: u64,
+        out: &mut Vec<ImportPoint>,
+        report: &mut ImportReport,
+    ) {
+        report.rows_read += 1;
+        let cell = |idx: usize| record.get(idx).unwrap_or_default();
+        if record.len() < self.headers.len() {
+            report.rows_rejected += 1;
+            report.issue(
+                line,
+                None,
+                "column_count",
+                format!(
+                    "expected {} columns, found {}",
+                    self.headers.len(),
+                    record.len()
+                ),
+            );
+            return;
+        }
+        let ts = match self.parser.parse(cell(self.timestamp_index)) {
+            Ok((ts, ambiguous)) => {
+                if ambiguous {
+                    report.ambiguous_local_times += 1;
+                }
+                ts
+            }
+            Err(message) => {
+                report.rows_rejected += 1;
+                let column = self.headers[self.timestamp_index].as_str();
+                report.issue(line, Some(column), "timestamp", message);
+                return;
+            }
+        };
+        let quality = match self.quality_index {
+            Some(idx) if !cell(idx).trim().is_empty() => match cell(idx).trim().parse::<i16>() {
+                Ok(quality) => quality,
+                Err(_) => {
+                    report.rows_rejected += 1;
+                    let message = format!("'{}' is not a quality code", cell(idx).trim());
+                    report.issue(line, Some(self.headers[idx].as_str()), "quality", message);
+                    return;
+                }
+            },
+            _ => 0,
+        };
+
+        match &self.layout {
+            MappedLayout::Wide { columns } => {
+                for (idx, mapping) in columns {
+                    match self.value(cell(*idx)) {
+                        Ok(Some(value)) => {
+                            let point = self.point(*mapping, ts, value, quality);
+                            report.record(&point);
+                            out.push(point);
+                        }
+                        Ok(None) => report.empty_cells += 1,
+                        Err(message) => {
+                            report.issue(line, Some(self.headers[*idx].as_str()), "value", message)
+                        }
+                    }
+                }
+            }
+            MappedLayout::Long {
+                sensor_index,
+                value_index,
+                sources,
+            } => {
+                let source = cell(*sensor_index).trim();
+                let Some(mapping) = sources.get(source).copied() else {
+                    report.rows_rejected += 1;
+                    report.unmapped(source);
+                    return;
+                };
+                match self.value(cell(*value_index)) {
+                    Ok(Some(value)) => {
+                        let point = self.point(mapping, ts, value, quality);
+                        report.record(&point);
+                        out.push(point);
+                    }
+                    Ok(None) => report.empty_cells += 1,
+                    Err(message) => {
+                        report.rows_rejected += 1;
+                        let column = self.headers[*value_index].as_str();
+                        report.issue(line, Some(column), "value", message);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reads the header row of an uploaded CSV.
+pub(crate) fn read_csv_headers(path: &Path, delimiter: char) -> Result<Vec<String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .flexible(true)
+        .from_path(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    Ok(reader
+        .headers()?
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').trim().to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(source: &str, sensor_id: &str, unit: Option<&str>) -> ImportMapping {
+        ImportMapping {
+            source: source.to_string(),
+            sensor_id: sensor_id.to_string(),
+            unit: unit.map(str::to_string),
+            scale: None,
+            offset: None,
+        }
+    }
+
+    fn params(layout: ImportLayout, mappings: Vec<ImportMapping>) -> MetricsImportParams {
+        MetricsImportParams {
+            upload_id: Uuid::nil().to_string(),
+            layout,
+            timestamp_column: "time".to_string(),
+            timestamp_format: None,
+            timezone: None,
+            sensor_column: Some("tag".to_string()),
+            value_column: Some("value".to_string()),
+            quality_column: None,
+            mappings,
+            dry_run: true,
+            lake_cutoff: None,
+        }
+    }
+
+    fn identity(count: usize) -> Vec<ValueConversion> {
+        vec![
+            ValueConversion {
+                scale: 1.0,
+                offset: 0.0
+            };
+            count
+        ]
+    }
+
+    #[test]
+    fn auto_timestamps_cover_offsets_naive_and_dates() {
+        let parser = TimestampParser::new(None, chrono_tz::America::Chicago);
+        let expected = Utc.with_ymd_and_hms(2026, 1, 15, 18, 0, 0).unwrap();
+        assert_eq!(parser.parse("2026-01-15T18:00:00Z").unwrap().0, expected);
+        assert_eq!(parser.parse("2026-01-15 18:00:00+00").unwrap().0, expected);
+        // Naive values are local to the configured zone (CST = UTC-6).
+        assert_eq!(parser.parse("2026-01-15 12:00:00").unwrap().0, expected);
+        assert_eq!(parser.parse("2026-01-15T12:00").unwrap().0, expected);
+        assert_eq!(
+            parser.parse("2026-01-15").unwrap().0,
+            Utc.with_ymd_and_hms(2026, 1, 15, 6, 0, 0).unwrap()
+        );
+        assert!(parser.parse("1768500000").is_err());
+    }
+
+    #[test]
+    fn dst_overlaps_pick_the_earlier_instant_and_gaps_are_rejected() {
+        let parser = TimestampParser::new(None, chrono_tz::America::Chicago);
+        let (ts, ambiguous) = parser.parse("2026-11-01 01:30:00").unwrap();
+        assert!(ambiguous);
+        assert_eq!(ts, Utc.with_ymd_and_hms(2026, 11, 1, 6, 30, 0).unwrap());
+        assert!(parser
+            .parse("2026-03-08 02:30:00")
+            .unwrap_err()
+            .contains("DST gap"));
+    }
+
+    #[test]
+    fn explicit_formats_parse_epochs_and_patterns() {
+        let expected = Utc.with_ymd_and_hms(2026, 1, 15, 18, 0, 0).unwrap();
+        let epoch = expected.timestamp();
+        assert_eq!(
+            TimestampParser::new(Some("epoch_s"), Tz::UTC)
+                .parse(&epoch.to_string())
+                .unwrap()
+                .0,
+            expected
+        );
+        assert_eq!(
+            TimestampParser::new(Some("epoch_ms"), Tz::UTC)
+                .parse(&(epoch * 1000).to_string())
+                .unwrap()
+                .0,
+            expected
+        );
+        assert_eq!(
+            TimestampParser::new(Some("%m/%d/%Y %H:%M"), Tz::UTC)
+                .parse("01/15/2026 18:00")
+                .unwrap()
+                .0,
+            expected
+        );
+    }
+
+    #[test]
+    fn unit_conversions_combine_with_scale_and_offset() {
+        let conv = resolve_conversion(&mapping("t", "s1", Some("degF")), Some("C")).unwrap();
+        assert!((conv.apply(212.0) - 100.0).abs() < 1e-9);
+        assert!((conv.apply(32.0)).abs() < 1e-9);
+
+        let conv = resolve_conversion(&mapping("p", "s1", Some("psi")), Some("kPa")).unwrap();
+        assert!((conv.apply(1.0) - 6.894_757_293).abs() < 1e-9);
+
+        // Raw counts scaled to inches, then converted to mm.
+        let mut scaled = mapping("r", "s1", Some("in"));
+        scaled.scale = Some(0.01);
+        let conv = resolve_conversion(&scaled, Some("mm")).unwrap();
+        assert!((conv.apply(100.0) - 25.4).abs() < 1e-9);
+
+        assert_eq!(
+            resolve_conversion(&mapping("x", "s1", Some("V")), Some("v")).unwrap(),
+            ValueConversion {
+                scale: 1.0,
+                offset: 0.0
+            }
+        );
+        assert!(resolve_conversion(&mapping("x", "s1", Some("psi")), Some("C")).is_err());
+        assert!(resolve_conversion(&mapping("x", "s1", Some("psi")), None).is_err());
+    }
+
+    #[test]
+    fn wide_rows_yield_one_point_per_mapped_column() {
+        let params = params(
+            ImportLayout::Wide,
+            vec![mapping("Temp", "s1", None), mapping("RH", "s2", None)],
+        );
+        let headers = csv::StringRecord::from(vec!["\u{feff}time", "temp", "RH", "ignored"]);
+        let mapper = RowMapper::new(&params, &headers, identity(2)).unwrap();
+        let mut report = ImportReport::new(&params.mappings);
+        let mut out = Vec::new();
+
+        let row = csv::StringRecord::from(vec!["2026-01-15T18:00:00Z", "21.5", "", "x"]);
+        mapper.map_record(&row, 2, &mut out, &mut report);
+        let row = csv::StringRecord::from(vec!["not a time", "1", "2", "x"]);
+        mapper.map_record(&row, 3, &mut out, &mut report);
+        let row = csv::StringRecord::from(vec!["2026-01-15T18:01:00Z", "bad", "40", "x"]);
+        mapper.map_record(&row, 4, &mut out, &mut report);
+
+        assert_eq!(out.len(), 2);
+        assert_eq!((out[0].mapping, out[0].value), (0, 21.5));
+        assert_eq!((out[1].mapping, out[1].value), (1, 40.0));
+        assert_eq!(report.rows_read, 3);
+        assert_eq!(report.rows_rejected, 1);
+        assert_eq!(report.empty_cells, 1);
+        assert_eq!(report.issue_counts.get("timestamp"), Some(&1));
+        assert_eq!(report.issue_counts.get("value"), Some(&1));
+        assert_eq!(report.issues[0].line, 3);
+        assert_eq!(report.sensors[1].points, 1);
+    }
+
+    #[test]
+    fn long_rows_count_unmapped_sources() {
+        let params = params(ImportLayout::Long, vec![mapping("PUMP.FLOW", "s1", None)]);
+        let headers = csv::StringRecord::from(vec!["time", "tag", "value"]);
+        let mapper = RowMapper::new(&params, &headers, identity(1)).unwrap();
+        let mut report = ImportReport::new(&params.mappings);
+        let mut out = Vec::new();
+
+        for (line, tag) in [(2, "PUMP.FLOW"), (3, "PUMP.AMPS"), (4, "PUMP.AMPS")] {
+            let row = csv::StringRecord::from(vec!["2026-01-15T18:00:00Z", tag, "3"]);
+            mapper.map_record(&row, line, &mut out, &mut report);
+        }
+        assert_eq!(out.len(), 1);
+        assert_eq!(report.rows_rejected, 2);
+        assert_eq!(report.unmapped_sources.get("PUMP.AMPS"), Some(&2));
+    }
+
+    #[test]
+    fn missing_columns_and_bad_params_are_reported() {
+        let params = params(ImportLayout::Wide, vec![mapping("missing", "s1", None)]);
+        let headers = csv::StringRecord::from(vec!["time", "temp"]);
+        let err = RowMapper::new(&params, &headers, identity(1)).unwrap_err();
+        assert!(err.contains("missing"));
+        let columns = vec!["time".to_string(), "Missing".to_string()];
+        assert!(params.check_columns(&columns).is_ok());
+        assert!(params.check_columns(&columns[..1]).is_err());
+
+        let mut bad = params.clone();
+        bad.mappings.push(mapping("missing", "s2", None));
+        assert!(bad.validate().unwrap_err().contains("Duplicate"));
+
+        let mut bad = params.clone();
+        bad.timezone = Some("Mars/Olympus".to_string());
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn stale_uploads_are_pruned() {
+        let tmp = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+        let mut ids = Vec::new();
+        for age_hours in [1, IMPORT_UPLOAD_TTL_HOURS + 1] {
+            let upload_id = Uuid::new_v4();
+            create_upload_dir(tmp.path(), upload_id).unwrap();
+            write_upload(
+                tmp.path(),
+                &ImportUpload {
+                    upload_id,
+                    format: ImportFormat::Csv,
+                    filename: None,
+                    bytes: 0,
+                    columns: vec![],
+                    delimiter: ',',
+                    created_by: "u".to_string(),
+                    created_at: now - chrono::Duration::hours(age_hours),
+                },
+            )
+            .unwrap();
+            ids.push(upload_id);
+        }
+        assert_eq!(prune_stale_uploads(tmp.path(), now), 1);
+        assert!(read_upload(tmp.path(), &ids[0].to_string())
+            .unwrap()
+            .is_some());
+        assert!(read_upload(tmp.path(), &ids[1].to_string())
+            .unwrap()
+            .is_none());
+        assert!(read_upload(tmp.path(), "../etc").unwrap().is_none());
+    }
+}
@@ -1,24 +1,34 @@
 pub mod alarm_engine;
 pub mod analysis;
 pub mod analytics_feeds;
+pub mod audit_log;
+pub mod backup_verification;
 pub mod battery_model;
 pub mod cloud_sync;
 pub mod deployments;
+pub mod device_profiles;
 pub mod derived_sensors;
+pub mod edge_rules;
 pub mod emporia;
 pub mod emporia_ingest;
 pub mod emporia_preferences;
 pub mod external_devices;
 pub mod forecasts;
+pub mod forwarder_config;
+pub mod forwarder_losses;
 pub mod incidents;
 pub mod map_offline;
 pub mod mdns_iotnode;
+pub mod metrics_export;
+pub mod metrics_import;
 pub mod mqtt;
 pub mod mqtt_status_ingest;
 pub mod node_agent_resolver;
+pub mod offsite_backup;
 pub mod power_runway;
 pub mod renogy_settings_apply;
 pub mod restore_worker;
 pub mod schedule_engine;
 pub mod sensor_visibility;
+pub mod spool_bundle;
 pub mod virtual_sensors;
@@ -44,4 +44,20 @@ impl MqttPublisher {
             .await?;
         Ok(())
     }
+
+    pub async fn publish_raw(&self, topic: &str, payload: Vec<u8>) -> Result<()> {
+        self.client
+            .publish(topic, QoS::AtLeastOnce, false, payload)
+            .await?;
+        Ok(())
+    }
+
+    /// Retained, so a node that (re)connects later still receives the last payload.
+    pub async fn publish_retained_json(&self, topic: &str, payload: &JsonValue) -> Result<()> {
+        let bytes = serde_json::to_vec(payload)?;
+        self.client
+            .publish(topic, QoS::AtLeastOnce, true, bytes)
+            .await?;
+        Ok(())
+    }
 }
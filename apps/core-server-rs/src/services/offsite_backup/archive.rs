@@ -0,0 +1,268 @@
+//! Snapshot archive layout.
+//!
+//! A snapshot is a gzipped tarball:
+//!
+//! ```text
+//! database.dump        pg_dump custom format
+//! setup_config.json    controller setup config
+//! lake_state/...       analysis lake `_state` directory (watermarks, manifests)
+//! map/...              offline map packs (optional)
+//! manifest.json        per-file sha256 + size, written last
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub(crate) const COMPONENT_DATABASE: &str = "database";
+pub(crate) const COMPONENT_SETUP_CONFIG: &str = "setup_config";
+pub(crate) const COMPONENT_LAKE_STATE: &str = "lake_state";
+pub(crate) const COMPONENT_MAP: &str = "map";
+pub(crate) const ALL_COMPONENTS: &[&str] = &[
+    COMPONENT_DATABASE,
+    COMPONENT_SETUP_CONFIG,
+    COMPONENT_LAKE_STATE,
+    COMPONENT_MAP,
+];
+
+pub(crate) const DATABASE_ENTRY: &str = "database.dump";
+pub(crate) const SETUP_CONFIG_ENTRY: &str = "setup_config.json";
+const MANIFEST_ENTRY: &str = "manifest.json";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ManifestFile {
+    pub sha256: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    pub format_version: u32,
+    pub created_at: String,
+    pub components: Vec<String>,
+    pub files: BTreeMap<String, ManifestFile>,
+}
+
+/// One component to include: a file or a directory stored under `entry`.
+#[derive(Debug, Clone)]
+pub(crate) struct ArchiveSource {
+    pub component: &'static str,
+    pub entry: String,
+    pub local: PathBuf,
+}
+
+fn hash_file(path: &Path) -> Result<ManifestFile> {
+    let mut file =
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut size_bytes = 0u64;
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        size_bytes += read as u64;
+    }
+    Ok(ManifestFile {
+        sha256: format!("{:x}", hasher.finalize()),
+        size_bytes,
+    })
+}
+
+fn collect_files(root: &Path, entry: &str, out: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    let meta = std::fs::symlink_metadata(root)
+        .with_context(|| format!("failed to stat {}", root.display()))?;
+    if meta.is_file() {
+        out.push((entry.to_string(), root.to_path_buf()));
+    } else if meta.is_dir() {
+        let mut children: Vec<_> = std::fs::read_dir(root)?.collect::<std::io::Result<_>>()?;
+        children.sort_by_key(|child| child.file_name());
+        for child in children {
+            let name = child.file_name().to_string_lossy().into_owned();
+            collect_files(&child.path(), &format!("{entry}/{name}"), out)?;
+        }
+    }
+    // Symlinks and sockets are skipped; none of the components rely on them.
+    Ok(())
+}
+
+/// Writes the tarball for `sources` to `out`. Missing sources are skipped so a
+/// controller without map packs or lake state still backs up.
+pub(crate) fn build_archive(sources: &[ArchiveSource], out: &Path) -> Result<Manifest> {
+    let file = File::create(out).with_context(|| format!("failed to create {}", out.display()))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mut manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        components: Vec::new(),
+        files: BTreeMap::new(),
+    };
+
+    for source in sources {
+        if !source.local.exists() {
+            continue;
+        }
+        let mut files = Vec::new();
+        collect_files(&source.local, &source.entry, &mut files)?;
+        for (entry, local) in files {
+            let digest = hash_file(&local)?;
+            builder
+                .append_path_with_name(&local, &entry)
+                .with_context(|| format!("failed to archive {}", local.display()))?;
+            manifest.files.insert(entry, digest);
+        }
+        if !manifest.components.iter().any(|c| c == source.component) {
+            manifest.components.push(source.component.to_string());
+        }
+    }
+
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_ENTRY, manifest_bytes.as_slice())?;
+    builder.into_inner()?.finish()?.flush()?;
+    Ok(manifest)
+}
+
+/// Unpacks `archive` into `dest` and checks every file against the manifest.
+pub(crate) fn extract_archive(archive: &Path, dest: &Path) -> Result<Manifest> {
+    let file =
+        File::open(archive).with_context(|| format!("failed to open {}", archive.display()))?;
+    std::fs::create_dir_all(dest)?;
+    let mut tar = tar::Archive::new(GzDecoder::new(file));
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        // `unpack_in` refuses paths that escape `dest`.
+        if !entry.unpack_in(dest)? {
+            bail!(
+                "archive entry {:?} escapes the extraction directory",
+                entry.path()?
+            );
+        }
+    }
+
+    let manifest_path = dest.join(MANIFEST_ENTRY);
+    let manifest: Manifest = serde_json::from_slice(
+        &std::fs::read(&manifest_path).context("snapshot has no manifest.json")?,
+    )
+    .context("snapshot manifest is not valid JSON")?;
+    if manifest.format_version != FORMAT_VERSION {
+        bail!(
+            "unsupported snapshot format version {}",
+            manifest.format_version
+        );
+    }
+
+    for (entry, expected) in &manifest.files {
+        let actual =
+            hash_file(&dest.join(entry)).map_err(|_| anyhow!("snapshot is missing {entry}"))?;
+        if actual.sha256 != expected.sha256 || actual.size_bytes != expected.size_bytes {
+            bail!("snapshot file {entry} does not match its manifest digest");
+        }
+    }
+    Ok(manifest)
+}
+
+/// Integrity check without keeping the extracted files.
+pub(crate) fn check_archive(archive: &Path) -> Result<Manifest> {
+    let scratch = tempfile::tempdir()?;
+    extract_archive(archive, scratch.path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(root: &Path) -> Vec<ArchiveSource> {
+        std::fs::write(root.join("db.dump"), b"PGDMP").unwrap();
+        std::fs::write(root.join("setup.json"), br#"{"mqtt_host":"localhost"}"#).unwrap();
+        std::fs::create_dir_all(root.join("state/manifests")).unwrap();
+        std::fs::write(root.join("state/replication.json"), b"{}").unwrap();
+        std::fs::write(root.join("state/manifests/shard-0.json"), b"[]").unwrap();
+        vec![
+            ArchiveSource {
+                component: COMPONENT_DATABASE,
+                entry: DATABASE_ENTRY.to_string(),
+                local: root.join("db.dump"),
+            },
+            ArchiveSource {
+                component: COMPONENT_SETUP_CONFIG,
+                entry: SETUP_CONFIG_ENTRY.to_string(),
+                local: root.join("setup.json"),
+            },
+            ArchiveSource {
+                component: COMPONENT_LAKE_STATE,
+                entry: COMPONENT_LAKE_STATE.to_string(),
+                local: root.join("state"),
+            },
+            ArchiveSource {
+                component: COMPONENT_MAP,
+                entry: COMPONENT_MAP.to_string(),
+                local: root.join("no-map-packs"),
+            },
+        ]
+    }
+
+    #[test]
+    fn roundtrips_and_lists_present_components() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("snapshot.tar.gz");
+        let built = build_archive(&sources(dir.path()), &archive).unwrap();
+        assert_eq!(
+            built.components,
+            vec![
+                COMPONENT_DATABASE,
+                COMPONENT_SETUP_CONFIG,
+                COMPONENT_LAKE_STATE
+            ]
+        );
+        assert!(built
+            .files
+            .contains_key("lake_state/manifests/shard-0.json"));
+
+        let out = dir.path().join("out");
+        let manifest = extract_archive(&archive, &out).unwrap();
+        assert_eq!(manifest.files.len(), 4);
+        assert_eq!(std::fs::read(out.join(DATABASE_ENTRY)).unwrap(), b"PGDMP");
+    }
+
+    #[test]
+    fn detects_tampered_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("snapshot.tar.gz");
+        let mut manifest = build_archive(&sources(dir.path()), &archive).unwrap();
+
+        // Rebuild with a manifest that no longer matches the payload.
+        manifest.files.get_mut(DATABASE_ENTRY).unwrap().sha256 = "0".repeat(64);
+        let staged = dir.path().join("staged");
+        extract_archive(&archive, &staged).unwrap();
+        std::fs::write(
+            staged.join(MANIFEST_ENTRY),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+        let tampered = dir.path().join("tampered.tar.gz");
+        {
+            let file = File::create(&tampered).unwrap();
+            let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            builder.append_dir_all(".", &staged).unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+        }
+        let err = check_archive(&tampered).unwrap_err();
+        assert!(err.to_string().contains(DATABASE_ENTRY));
+    }
+}
@@ -0,0 +1,301 @@
+//! Client-side encryption for offsite snapshots.
+//!
+//! The layout follows age's STREAM construction: a short header, then the
+//! archive split into 64 KiB chunks sealed with ChaCha20-Poly1305. Chunk nonces
+//! are a big-endian counter plus a final-chunk flag, so reordered, dropped or
+//! truncated chunks fail to open. Each file gets its own key derived from the
+//! master key and a random salt; the header is bound in as associated data.
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::config::CoreConfig;
+
+const MAGIC: &[u8; 8] = b"FDBACKUP";
+const VERSION: u8 = 1;
+const KDF_RAW: u8 = 0;
+const KDF_PBKDF2_SHA256: u8 = 1;
+// Stored in the header, so tests can use a cheaper count.
+const PBKDF2_ROUNDS: u32 = if cfg!(test) { 1_000 } else { 200_000 };
+const SALT_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 1 + 4 + SALT_LEN + SALT_LEN;
+const CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const FILE_KEY_LABEL: &[u8] = b"farm-dashboard offsite backup v1";
+
+/// Master secret for offsite snapshots.
+#[derive(Clone)]
+pub(crate) enum BackupKey {
+    Raw([u8; 32]),
+    Passphrase(String),
+}
+
+impl std::fmt::Debug for BackupKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupKey::Raw(_) => f.write_str("BackupKey::Raw(..)"),
+            BackupKey::Passphrase(_) => f.write_str("BackupKey::Passphrase(..)"),
+        }
+    }
+}
+
+impl BackupKey {
+    /// Key file first, then passphrase; `None` when neither is configured.
+    pub(crate) fn from_config(config: &CoreConfig) -> Result<Option<Self>> {
+        if let Some(path) = config.backup_encryption_key_file.as_deref() {
+            return Self::from_key_file(path).map(Some);
+        }
+        Ok(config
+            .backup_encryption_passphrase
+            .as_deref()
+            .filter(|value| !value.is_empty())
+            .map(|value| BackupKey::Passphrase(value.to_string())))
+    }
+
+    /// Reads a base64 key file; `#` lines are comments, as in age identity files.
+    pub(crate) fn from_key_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read backup key file {}", path.display()))?;
+        let encoded = contents
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .ok_or_else(|| anyhow!("backup key file {} is empty", path.display()))?;
+        let bytes = STANDARD
+            .decode(encoded)
+            .context("backup key file must contain base64")?;
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| anyhow!("backup key must decode to 32 bytes"))?;
+        Ok(BackupKey::Raw(key))
+    }
+
+    fn master_key(&self, kdf: u8, rounds: u32, salt: &[u8]) -> Result<[u8; 32]> {
+        match (self, kdf) {
+            (BackupKey::Raw(key), KDF_RAW) => Ok(*key),
+            (BackupKey::Passphrase(passphrase), KDF_PBKDF2_SHA256) => {
+                let mut key = [0u8; 32];
+                pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, rounds, &mut key);
+                Ok(key)
+            }
+            (BackupKey::Raw(_), _) => bail!("snapshot was encrypted with a passphrase"),
+            (BackupKey::Passphrase(_), _) => bail!("snapshot was encrypted with a key file"),
+        }
+    }
+}
+
+fn file_cipher(master: &[u8; 32], file_salt: &[u8]) -> ChaCha20Poly1305 {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(master).expect("HMAC accepts any key length");
+    mac.update(FILE_KEY_LABEL);
+    mac.update(file_salt);
+    let file_key = mac.finalize().into_bytes();
+    ChaCha20Poly1305::new(Key::from_slice(&file_key))
+}
+
+fn chunk_nonce(counter: u64, last: bool) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = u8::from(last);
+    Nonce::clone_from_slice(&nonce)
+}
+
+/// Fills `buf` as far as the reader allows; returns the byte count.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(filled)
+}
+
+/// Encrypts `input` into `output`; returns the number of bytes written.
+pub(crate) fn encrypt_stream(
+    key: &BackupKey,
+    mut input: impl Read,
+    mut output: impl Write,
+) -> Result<u64> {
+    let mut kdf_salt = [0u8; SALT_LEN];
+    let mut file_salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut kdf_salt);
+    rand::thread_rng().fill_bytes(&mut file_salt);
+    let (kdf, rounds) = match key {
+        BackupKey::Raw(_) => (KDF_RAW, 0),
+        BackupKey::Passphrase(_) => (KDF_PBKDF2_SHA256, PBKDF2_ROUNDS),
+    };
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+    header.push(kdf);
+    header.extend_from_slice(&rounds.to_be_bytes());
+    header.extend_from_slice(&kdf_salt);
+    header.extend_from_slice(&file_salt);
+    output.write_all(&header)?;
+    let mut written = header.len() as u64;
+
+    let cipher = file_cipher(&key.master_key(kdf, rounds, &kdf_salt)?, &file_salt);
+    let mut current = vec![0u8; CHUNK_LEN];
+    let mut next = vec![0u8; CHUNK_LEN];
+    let mut current_len = read_full(&mut input, &mut current)?;
+    let mut counter = 0u64;
+    loop {
+        let next_len = if current_len == CHUNK_LEN {
+            read_full(&mut input, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        let sealed = cipher
+            .encrypt(
+                &chunk_nonce(counter, last),
+                Payload {
+                    msg: &current[..current_len],
+                    aad: &header,
+                },
+            )
+            .map_err(|_| anyhow!("failed to encrypt backup chunk"))?;
+        output.write_all(&sealed)?;
+        written += sealed.len() as u64;
+        if last {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+        counter += 1;
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+/// Decrypts a stream written by [`encrypt_stream`]; returns plaintext bytes written.
+pub(crate) fn decrypt_stream(
+    key: &BackupKey,
+    mut input: impl Read,
+    mut output: impl Write,
+) -> Result<u64> {
+    let mut header = [0u8; HEADER_LEN];
+    if read_full(&mut input, &mut header)? != HEADER_LEN || &header[..MAGIC.len()] != MAGIC {
+        bail!("not an encrypted controller backup");
+    }
+    let mut offset = MAGIC.len();
+    let version = header[offset];
+    if version != VERSION {
+        bail!("unsupported backup encryption version {version}");
+    }
+    let kdf = header[offset + 1];
+    offset += 2;
+    let rounds = u32::from_be_bytes(header[offset..offset + 4].try_into()?);
+    if rounds > 10 * PBKDF2_ROUNDS.max(200_000) {
+        bail!("backup header asks for an unreasonable key derivation cost");
+    }
+    offset += 4;
+    let kdf_salt = &header[offset..offset + SALT_LEN];
+    let file_salt = &header[offset + SALT_LEN..offset + 2 * SALT_LEN];
+    let cipher = file_cipher(&key.master_key(kdf, rounds, kdf_salt)?, file_salt);
+
+    let sealed_len = CHUNK_LEN + TAG_LEN;
+    let mut current = vec![0u8; sealed_len];
+    let mut next = vec![0u8; sealed_len];
+    let mut current_len = read_full(&mut input, &mut current)?;
+    let mut counter = 0u64;
+    let mut written = 0u64;
+    loop {
+        let next_len = if current_len == sealed_len {
+            read_full(&mut input, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        let plain = cipher
+            .decrypt(
+                &chunk_nonce(counter, last),
+                Payload {
+                    msg: &current[..current_len],
+                    aad: &header,
+                },
+            )
+            .map_err(|_| {
+                anyhow!("backup chunk {counter} failed authentication (wrong key, corrupt or truncated)")
+            })?;
+        output.write_all(&plain)?;
+        written += plain.len() as u64;
+        if last {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+        counter += 1;
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(key: &BackupKey, plain: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        encrypt_stream(key, plain, &mut sealed).unwrap();
+        let mut opened = Vec::new();
+        decrypt_stream(key, sealed.as_slice(), &mut opened).unwrap();
+        opened
+    }
+
+    #[test]
+    fn roundtrips_across_chunk_boundaries() {
+        let key = BackupKey::Raw([7u8; 32]);
+        for len in [0, 1, CHUNK_LEN - 1, CHUNK_LEN, CHUNK_LEN + 1, 3 * CHUNK_LEN] {
+            let plain: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            assert_eq!(roundtrip(&key, &plain), plain, "len {len}");
+        }
+    }
+
+    #[test]
+    fn rejects_truncation_and_wrong_key() {
+        let key = BackupKey::Raw([1u8; 32]);
+        let plain = vec![42u8; 2 * CHUNK_LEN];
+        let mut sealed = Vec::new();
+        encrypt_stream(&key, plain.as_slice(), &mut sealed).unwrap();
+
+        let truncated = &sealed[..HEADER_LEN + CHUNK_LEN + TAG_LEN];
+        assert!(decrypt_stream(&key, truncated, &mut Vec::new()).is_err());
+
+        let other = BackupKey::Raw([2u8; 32]);
+        assert!(decrypt_stream(&other, sealed.as_slice(), &mut Vec::new()).is_err());
+
+        let mut flipped = sealed.clone();
+        let last = flipped.len() - 1;
+        flipped[last] ^= 1;
+        assert!(decrypt_stream(&key, flipped.as_slice(), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn passphrase_snapshots_need_the_passphrase() {
+        let key = BackupKey::Passphrase("correct horse".to_string());
+        let mut sealed = Vec::new();
+        encrypt_stream(&key, &b"setup config"[..], &mut sealed).unwrap();
+        let mut opened = Vec::new();
+        decrypt_stream(&key, sealed.as_slice(), &mut opened).unwrap();
+        assert_eq!(opened, b"setup config");
+
+        let raw = BackupKey::Raw([0u8; 32]);
+        assert!(decrypt_stream(&raw, sealed.as_slice(), &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,711 @@
+//! Scheduled full-controller backups pushed to offsite targets.
+//!
+//! Each run dumps Postgres, packs it with the setup config, the analysis lake
+//! `_state` directory and (optionally) offline map packs, encrypts the archive on
+//! the controller and uploads it to the target. Old snapshots are pruned with a
+//! grandfather-father-son policy. Restores are queued and applied by the restore
+//! worker.
+
+pub(crate) mod archive;
+pub(crate) mod crypto;
+pub(crate) mod retention;
+pub(crate) mod targets;
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::routes::backups_exports::{
+    find_postgres_tool, parse_pg_connection, postgres_command, run_pg_dump_to_file, PgConnection,
+};
+use crate::state::AppState;
+use archive::{ArchiveSource, Manifest};
+use crypto::BackupKey;
+use retention::GfsPolicy;
+use targets::BackupStore;
+
+const SCHEDULE_TICK: Duration = Duration::from_secs(60);
+/// A run still marked `running` after this long is assumed dead (e.g. the
+/// controller restarted mid-upload) and no longer blocks new runs.
+const STALE_RUN_HOURS: i32 = 6;
+const OBJECT_SUFFIX: &str = ".fdbak";
+const TMP_DIR: &str = ".offsite-tmp";
+
+/// Tables describing the offsite backups themselves. A restore keeps the live
+/// rows so the running restore job and target credentials survive.
+const RESTORE_EXCLUDED_TABLES: &[&str] =
+    &["backup_targets", "offsite_backups", "offsite_restore_jobs"];
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub(crate) struct BackupTargetRow {
+    pub id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub config: SqlJson<JsonValue>,
+    pub secrets: SqlJson<JsonValue>,
+    pub enabled: bool,
+    pub interval_hours: i32,
+    pub include_map_packs: bool,
+    pub keep_daily: i32,
+    pub keep_weekly: i32,
+    pub keep_monthly: i32,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BackupTargetRow {
+    pub(crate) fn store(&self) -> Result<BackupStore> {
+        BackupStore::parse(&self.kind, &self.config.0, &self.secrets.0).map_err(|err| anyhow!(err))
+    }
+
+    fn policy(&self) -> GfsPolicy {
+        GfsPolicy {
+            daily: self.keep_daily.max(0) as u32,
+            weekly: self.keep_weekly.max(0) as u32,
+            monthly: self.keep_monthly.max(0) as u32,
+        }
+    }
+}
+
+pub(crate) const TARGET_COLUMNS: &str = r#"
+    id, name, kind, config, secrets, enabled, interval_hours, include_map_packs,
+    keep_daily, keep_weekly, keep_monthly, last_run_at, last_status, last_error,
+    created_at, updated_at
+"#;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub(crate) struct OffsiteBackupRow {
+    pub id: Uuid,
+    pub target_id: Uuid,
+    pub object_key: String,
+    pub status: String,
+    pub trigger: String,
+    pub archive_sha256: Option<String>,
+    pub object_sha256: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub components: SqlJson<Vec<String>>,
+    pub message: Option<String>,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub verify_status: Option<String>,
+    pub verify_message: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub pruned_at: Option<DateTime<Utc>>,
+}
+
+pub(crate) const BACKUP_COLUMNS: &str = r#"
+    id, target_id, object_key, status, trigger, archive_sha256, object_sha256,
+    size_bytes, components, message, verified_at, verify_status, verify_message,
+    started_at, finished_at, pruned_at
+"#;
+
+pub struct OffsiteBackupService {
+    state: AppState,
+}
+
+impl OffsiteBackupService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    pub fn start(self, cancel: CancellationToken) {
+        let state = self.state;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SCHEDULE_TICK);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if let Err(err) = run_due_targets(&state).await {
+                            tracing::warn!("offsite backup scheduler failed: {err:#}");
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn run_due_targets(state: &AppState) -> Result<()> {
+    let due: Vec<BackupTargetRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT {TARGET_COLUMNS}
+        FROM backup_targets
+        WHERE enabled
+          AND (last_run_at IS NULL OR last_run_at + make_interval(hours => interval_hours) <= now())
+        ORDER BY last_run_at ASC NULLS FIRST
+        "#
+    ))
+    .fetch_all(&state.db)
+    .await?;
+    for target in due {
+        if let Some(backup_id) = start_backup(&state.db, &target, "schedule").await? {
+            execute_backup(state, &target, backup_id).await;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn load_target(
+    db: &PgPool,
+    target_id: Uuid,
+) -> Result<Option<BackupTargetRow>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "SELECT {TARGET_COLUMNS} FROM backup_targets WHERE id = $1"
+    ))
+    .bind(target_id)
+    .fetch_optional(db)
+    .await
+}
+
+pub(crate) async fn load_backup(
+    db: &PgPool,
+    backup_id: Uuid,
+) -> Result<Option<OffsiteBackupRow>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "SELECT {BACKUP_COLUMNS} FROM offsite_backups WHERE id = $1"
+    ))
+    .bind(backup_id)
+    .fetch_optional(db)
+    .await
+}
+
+/// Records a `running` snapshot row. Returns `None` when the target already has
+/// a run in flight.
+pub(crate) async fn start_backup(
+    db: &PgPool,
+    target: &BackupTargetRow,
+    trigger: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let object_key = format!(
+        "farm-controller-{}-{}{OBJECT_SUFFIX}",
+        Utc::now().format("%Y%m%dT%H%M%SZ"),
+        &Uuid::new_v4().simple().to_string()[..8]
+    );
+    let backup_id: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        INSERT INTO offsite_backups (target_id, object_key, status, trigger)
+        SELECT $1, $2, 'running', $3
+        WHERE NOT EXISTS (
+            SELECT 1 FROM offsite_backups
+            WHERE target_id = $1
+              AND status = 'running'
+              AND started_at > now() - make_interval(hours => $4)
+        )
+        RETURNING id
+        "#,
+    )
+    .bind(target.id)
+    .bind(&object_key)
+    .bind(trigger)
+    .bind(STALE_RUN_HOURS)
+    .fetch_optional(db)
+    .await?;
+    if backup_id.is_some() {
+        sqlx::query(
+            "UPDATE backup_targets SET last_run_at = now(), last_status = 'running' WHERE id = $1",
+        )
+        .bind(target.id)
+        .execute(db)
+        .await?;
+    }
+    Ok(backup_id)
+}
+
+/// Runs a snapshot started with [`start_backup`] and records the outcome.
+pub(crate) async fn execute_backup(state: &AppState, target: &BackupTargetRow, backup_id: Uuid) {
+    let outcome = create_snapshot(state, target, backup_id).await;
+    let (status, error) = match &outcome {
+        Ok(_) => ("ok", None),
+        Err(err) => ("error", Some(format!("{err:#}"))),
+    };
+    if let Some(error) = error.as_deref() {
+        tracing::warn!(backup_target = %target.name, "offsite backup failed: {error}");
+        let _ = sqlx::query(
+            "UPDATE offsite_backups SET status = 'error', message = $2, finished_at = now() WHERE id = $1",
+        )
+        .bind(backup_id)
+        .bind(error)
+        .execute(&state.db)
+        .await;
+    }
+    let _ = sqlx::query(
+        "UPDATE backup_targets SET last_status = $2, last_error = $3, updated_at = now() WHERE id = $1",
+    )
+    .bind(target.id)
+    .bind(status)
+    .bind(error.as_deref())
+    .execute(&state.db)
+    .await;
+
+    if outcome.is_ok() {
+        if let Err(err) = apply_retention(&state.db, target).await {
+            tracing::warn!(backup_target = %target.name, "offsite backup pruning failed: {err:#}");
+        }
+    }
+}
+
+fn scratch_dir(state: &AppState) -> Result<tempfile::TempDir> {
+    let root = state.config.backup_storage_path.join(TMP_DIR);
+    std::fs::create_dir_all(&root)
+        .with_context(|| format!("failed to create {}", root.display()))?;
+    tempfile::Builder::new()
+        .prefix("offsite-")
+        .tempdir_in(&root)
+        .context("failed to create scratch directory")
+}
+
+fn pg_connection(state: &AppState) -> Result<PgConnection> {
+    parse_pg_connection(&state.config.database_url).map_err(|(_, message)| anyhow!(message))
+}
+
+fn require_key(state: &AppState) -> Result<BackupKey> {
+    BackupKey::from_config(&state.config)?.ok_or_else(|| {
+        anyhow!(
+            "no backup encryption key configured (set CORE_BACKUP_ENCRYPTION_KEY_FILE or CORE_BACKUP_ENCRYPTION_PASSPHRASE)"
+        )
+    })
+}
+
+fn lake_state_dir(state: &AppState) -> PathBuf {
+    state.config.analysis_lake_hot_path.join("_state")
+}
+
+async fn create_snapshot(
+    state: &AppState,
+    target: &BackupTargetRow,
+    backup_id: Uuid,
+) -> Result<()> {
+    let key = require_key(state)?;
+    let store = target.store()?;
+    let object_key: String =
+        sqlx::query_scalar("SELECT object_key FROM offsite_backups WHERE id = $1")
+            .bind(backup_id)
+            .fetch_one(&state.db)
+            .await?;
+    let scratch = scratch_dir(state)?;
+
+    let dump = scratch.path().join(archive::DATABASE_ENTRY);
+    run_pg_dump_to_file(&pg_connection(state)?, None, &["--format=custom"], &dump)
+        .await
+        .map_err(|(_, message)| anyhow!(message))?;
+
+    let mut sources = vec![
+        ArchiveSource {
+            component: archive::COMPONENT_DATABASE,
+            entry: archive::DATABASE_ENTRY.to_string(),
+            local: dump,
+        },
+        ArchiveSource {
+            component: archive::COMPONENT_SETUP_CONFIG,
+            entry: archive::SETUP_CONFIG_ENTRY.to_string(),
+            local: crate::config::setup_config_path(),
+        },
+        ArchiveSource {
+            component: archive::COMPONENT_LAKE_STATE,
+            entry: archive::COMPONENT_LAKE_STATE.to_string(),
+            local: lake_state_dir(state),
+        },
+    ];
+    if target.include_map_packs {
+        sources.push(ArchiveSource {
+            component: archive::COMPONENT_MAP,
+            entry: archive::COMPONENT_MAP.to_string(),
+            local: state.config.map_storage_path.clone(),
+        });
+    }
+
+    let archive_path = scratch.path().join("snapshot.tar.gz");
+    let sealed_path = scratch.path().join(&object_key);
+    let (manifest, archive_sha256, object_sha256, size_bytes) = {
+        let (archive_path, sealed_path) = (archive_path.clone(), sealed_path.clone());
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            let manifest = archive::build_archive(&sources, &archive_path)?;
+            let mut input = Hashing::new(std::fs::File::open(&archive_path)?);
+            let mut output = Hashing::new(std::io::BufWriter::new(std::fs::File::create(
+                &sealed_path,
+            )?));
+            crypto::encrypt_stream(&key, &mut input, &mut output)?;
+            Ok((
+                manifest,
+                input.hex_digest(),
+                output.hex_digest(),
+                output.bytes,
+            ))
+        })
+        .await??
+    };
+
+    store.put(&object_key, &sealed_path).await?;
+
+    sqlx::query(
+        r#"
+        UPDATE offsite_backups
+        SET status = 'ok',
+            archive_sha256 = $2,
+            object_sha256 = $3,
+            size_bytes = $4,
+            components = $5,
+            message = NULL,
+            finished_at = now()
+        WHERE id = $1
+        "#,
+    )
+    .bind(backup_id)
+    .bind(&archive_sha256)
+    .bind(&object_sha256)
+    .bind(size_bytes as i64)
+    .bind(SqlJson(&manifest.components))
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+/// Deletes snapshots outside the target's retention policy.
+async fn apply_retention(db: &PgPool, target: &BackupTargetRow) -> Result<()> {
+    let store = target.store()?;
+    let snapshots: Vec<(Uuid, DateTime<Utc>, String)> = sqlx::query_as(
+        "SELECT id, started_at, object_key FROM offsite_backups WHERE target_id = $1 AND status = 'ok'",
+    )
+    .bind(target.id)
+    .fetch_all(db)
+    .await?;
+    let stamps: Vec<(Uuid, DateTime<Utc>)> =
+        snapshots.iter().map(|(id, ts, _)| (*id, *ts)).collect();
+    let keep = retention::select_keep(&stamps, target.policy());
+    for (id, _, object_key) in snapshots.iter().filter(|(id, _, _)| !keep.contains(id)) {
+        if let Err(err) = store.delete(object_key).await {
+            tracing::warn!(backup_target = %target.name, object_key = %object_key, "failed to prune offsite snapshot: {err:#}");
+            continue;
+        }
+        sqlx::query(
+            "UPDATE offsite_backups SET status = 'pruned', pruned_at = now() WHERE id = $1",
+        )
+        .bind(id)
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Downloads a snapshot, checks both digests and decrypts it into `dir`.
+/// Returns the path of the plaintext archive.
+async fn fetch_snapshot(
+    state: &AppState,
+    backup: &OffsiteBackupRow,
+    dir: &Path,
+) -> Result<PathBuf> {
+    if backup.status != "ok" {
+        bail!("snapshot is {}, not ok", backup.status);
+    }
+    let key = require_key(state)?;
+    let target = load_target(&state.db, backup.target_id)
+        .await?
+        .ok_or_else(|| anyhow!("backup target no longer exists"))?;
+    let sealed_path = dir.join(&backup.object_key);
+    target
+        .store()?
+        .get(&backup.object_key, &sealed_path)
+        .await?;
+
+    let archive_path = dir.join("snapshot.tar.gz");
+    let (object_sha256, archive_sha256) = {
+        let (sealed_path, archive_path) = (sealed_path.clone(), archive_path.clone());
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            let mut input =
+                Hashing::new(std::io::BufReader::new(std::fs::File::open(&sealed_path)?));
+            let mut output = Hashing::new(std::io::BufWriter::new(std::fs::File::create(
+                &archive_path,
+            )?));
+            let decrypted = crypto::decrypt_stream(&key, &mut input, &mut output);
+            // Drain so a trailing-garbage object still reports its real digest.
+            std::io::copy(&mut input, &mut std::io::sink())?;
+            let object_sha256 = input.hex_digest();
+            decrypted.map(|_| (object_sha256, output.hex_digest()))
+        })
+        .await??
+    };
+    if backup.object_sha256.as_deref() != Some(object_sha256.as_str()) {
+        bail!("stored object does not match the digest recorded at upload");
+    }
+    if backup.archive_sha256.as_deref() != Some(archive_sha256.as_str()) {
+        bail!("decrypted archive does not match the digest recorded at backup time");
+    }
+    Ok(archive_path)
+}
+
+/// Downloads, decrypts and checks a snapshot end to end; records the result.
+pub(crate) async fn verify_backup(state: &AppState, backup_id: Uuid) -> Result<Manifest> {
+    let backup = load_backup(&state.db, backup_id)
+        .await?
+        .ok_or_else(|| anyhow!("snapshot not found"))?;
+    let outcome: Result<Manifest> = async {
+        let scratch = scratch_dir(state)?;
+        let archive_path = fetch_snapshot(state, &backup, scratch.path()).await?;
+        tokio::task::spawn_blocking(move || archive::check_archive(&archive_path)).await?
+    }
+    .await;
+    let (status, message) = match &outcome {
+        Ok(manifest) => ("ok", format!("{} files verified", manifest.files.len())),
+        Err(err) => ("error", format!("{err:#}")),
+    };
+    sqlx::query(
+        "UPDATE offsite_backups SET verified_at = now(), verify_status = $2, verify_message = $3 WHERE id = $1",
+    )
+    .bind(backup_id)
+    .bind(status)
+    .bind(&message)
+    .execute(&state.db)
+    .await?;
+    outcome
+}
+
+/// Applies the requested components of a snapshot to this controller. Files
+/// that get replaced are kept next to the originals with a `.pre-restore-*`
+/// suffix.
+pub(crate) async fn restore_snapshot(
+    state: &AppState,
+    backup_id: Uuid,
+    components: &[String],
+) -> Result<String> {
+    let backup = load_backup(&state.db, backup_id)
+        .await?
+        .ok_or_else(|| anyhow!("snapshot not found"))?;
+    let scratch = scratch_dir(state)?;
+    let archive_path = fetch_snapshot(state, &backup, scratch.path()).await?;
+    let extracted = scratch.path().join("extracted");
+    let manifest = {
+        let extracted = extracted.clone();
+        tokio::task::spawn_blocking(move || archive::extract_archive(&archive_path, &extracted))
+            .await??
+    };
+    for component in components {
+        if !manifest.components.contains(component) {
+            bail!("snapshot does not contain {component}");
+        }
+    }
+
+    let suffix = format!("pre-restore-{}", Utc::now().format("%Y%m%dT%H%M%SZ"));
+    let wants = |name: &str| components.iter().any(|c| c == name);
+    if wants(archive::COMPONENT_SETUP_CONFIG) {
+        let path = crate::config::setup_config_path();
+        let source = extracted.join(archive::SETUP_CONFIG_ENTRY);
+        let staged = path.with_extension("json.restore");
+        tokio::fs::copy(&source, &staged).await?;
+        if path.exists() {
+            tokio::fs::copy(&path, path.with_extension(format!("json.{suffix}"))).await?;
+        }
+        tokio::fs::rename(&staged, &path).await?;
+    }
+    if wants(archive::COMPONENT_LAKE_STATE) {
+        let state_dir = lake_state_dir(state);
+        let source = extracted.join(archive::COMPONENT_LAKE_STATE);
+        let suffix = suffix.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            if state_dir.exists() {
+                let aside = state_dir.with_file_name(format!("_state.{suffix}"));
+                std::fs::rename(&state_dir, &aside)?;
+            }
+            copy_dir(&source, &state_dir)
+        })
+        .await??;
+    }
+    if wants(archive::COMPONENT_MAP) {
+        let map_dir = state.config.map_storage_path.clone();
+        let source = extracted.join(archive::COMPONENT_MAP);
+        tokio::task::spawn_blocking(move || copy_dir(&source, &map_dir)).await??;
+    }
+    if wants(archive::COMPONENT_DATABASE) {
+        restore_database(
+            state,
+            &extracted.join(archive::DATABASE_ENTRY),
+            scratch.path(),
+        )
+        .await?;
+    }
+
+    Ok(format!(
+        "Restored {} from snapshot {}; restart the controller to reload configuration.",
+        components.join(", "),
+        backup.object_key
+    ))
+}
+
+/// Copies `source` into `dest`, merging with and overwriting what is there.
+fn copy_dir(source: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+async fn restore_database(state: &AppState, dump: &Path, scratch: &Path) -> Result<()> {
+    let pg_conn = pg_connection(state)?;
+    let listing = Command::new(find_postgres_tool("pg_restore"))
+        .arg("--list")
+        .arg(dump)
+        .output()
+        .await
+        .context("failed to run pg_restore")?;
+    if !listing.status.success() {
+        bail!("pg_restore could not read the database dump");
+    }
+    let list_path = scratch.join("restore.list");
+    tokio::fs::write(
+        &list_path,
+        filter_restore_list(&String::from_utf8_lossy(&listing.stdout)),
+    )
+    .await?;
+
+    let output = postgres_command("pg_restore", &pg_conn)
+        .args([
+            "--clean",
+            "--if-exists",
+            "--no-owner",
+            "--no-acl",
+            "--single-transaction",
+        ])
+        .arg("--use-list")
+        .arg(&list_path)
+        .arg("--dbname")
+        .arg(&pg_conn.dbname)
+        .arg(dump)
+        .output()
+        .await
+        .context("failed to run pg_restore")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "pg_restore failed: {}",
+            stderr.lines().last().unwrap_or("unknown error")
+        );
+    }
+    Ok(())
+}
+
+/// Drops TOC entries for [`RESTORE_EXCLUDED_TABLES`] from a `pg_restore --list`
+/// listing. Entries name their table right after the schema, e.g.
+/// `215; 1259 16601 TABLE public backup_targets farm` or
+/// `3301; 2606 16702 FK CONSTRAINT public offsite_backups offsite_backups_target_id_fkey farm`.
+fn filter_restore_list(listing: &str) -> String {
+    listing
+        .lines()
+        .filter(|line| {
+            if line.trim_start().starts_with(';') {
+                return true;
+            }
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            !tokens
+                .windows(2)
+                .any(|pair| pair[0] == "public" && RESTORE_EXCLUDED_TABLES.contains(&pair[1]))
+        })
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+/// Passes bytes through while computing their SHA-256.
+struct Hashing<T> {
+    inner: T,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<T> Hashing<T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            bytes: 0,
+        }
+    }
+
+    fn hex_digest(&self) -> String {
+        format!("{:x}", self.hasher.clone().finalize())
+    }
+}
+
+impl<T: Read> Read for Hashing<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+impl<T: Write> Write for Hashing<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_list_skips_offsite_backup_tables() {
+        let listing = "\
+;
+; Archive created at 2026-10-01 03:00:00 UTC
+;
+214; 1259 16590 TABLE public nodes farm
+215; 1259 16601 TABLE public backup_targets farm
+216; 1259 16612 TABLE public offsite_backups farm
+3290; 0 16590 TABLE DATA public nodes farm
+3291; 0 16601 TABLE DATA public backup_targets farm
+3301; 2606 16702 FK CONSTRAINT public offsite_backups offsite_backups_target_id_fkey farm
+3302; 2606 16703 FK CONSTRAINT public offsite_restore_jobs offsite_restore_jobs_offsite_backup_id_fkey farm
+3303; 1259 16704 INDEX public nodes_name_idx farm
+";
+        let filtered = filter_restore_list(listing);
+        assert!(filtered.contains("TABLE public nodes farm"));
+        assert!(filtered.contains("TABLE DATA public nodes farm"));
+        assert!(filtered.contains("INDEX public nodes_name_idx"));
+        assert!(filtered.contains("; Archive created"));
+        for table in RESTORE_EXCLUDED_TABLES {
+            assert!(
+                !filtered.contains(&format!("public {table} ")),
+                "{table} kept"
+            );
+        }
+    }
+
+    #[test]
+    fn hashing_matches_plain_digest() {
+        let mut sink = Hashing::new(Vec::new());
+        sink.write_all(b"snapshot").unwrap();
+        assert_eq!(
+            sink.hex_digest(),
+            format!("{:x}", Sha256::digest(b"snapshot"))
+        );
+        assert_eq!(sink.bytes, 8);
+    }
+}
@@ -0,0 +1,124 @@
+//! Grandfather-father-son pruning for offsite snapshots.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Datelike, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GfsPolicy {
+    pub daily: u32,
+    pub weekly: u32,
+    pub monthly: u32,
+}
+
+/// Snapshots to keep: the newest of each of the last `daily` days, `weekly` ISO
+/// weeks and `monthly` months that have one. The newest snapshot is always kept
+/// so a zero policy never empties a target.
+pub(crate) fn select_keep(snapshots: &[(Uuid, DateTime<Utc>)], policy: GfsPolicy) -> HashSet<Uuid> {
+    let mut ordered: Vec<&(Uuid, DateTime<Utc>)> = snapshots.iter().collect();
+    ordered.sort_by_key(|(_, taken_at)| std::cmp::Reverse(*taken_at));
+
+    let mut keep = HashSet::new();
+    if let Some((newest, _)) = ordered.first() {
+        keep.insert(*newest);
+    }
+    keep_newest_per_bucket(&ordered, policy.daily, &mut keep, |ts| {
+        (ts.year(), ts.ordinal())
+    });
+    keep_newest_per_bucket(&ordered, policy.weekly, &mut keep, |ts| {
+        let week = ts.iso_week();
+        (week.year(), week.week())
+    });
+    keep_newest_per_bucket(&ordered, policy.monthly, &mut keep, |ts| {
+        (ts.year(), ts.month())
+    });
+    keep
+}
+
+fn keep_newest_per_bucket(
+    newest_first: &[&(Uuid, DateTime<Utc>)],
+    buckets: u32,
+    keep: &mut HashSet<Uuid>,
+    bucket_of: impl Fn(&DateTime<Utc>) -> (i32, u32),
+) {
+    let mut seen = HashSet::new();
+    for (id, ts) in newest_first {
+        if seen.len() >= buckets as usize {
+            break;
+        }
+        if seen.insert(bucket_of(ts)) {
+            keep.insert(*id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn daily_snapshots(days: i64) -> Vec<(Uuid, DateTime<Utc>)> {
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 3, 0, 0).unwrap();
+        (0..days)
+            .map(|day| (Uuid::new_v4(), start + Duration::days(day)))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_days_weeks_and_months() {
+        let snapshots = daily_snapshots(120);
+        let policy = GfsPolicy {
+            daily: 7,
+            weekly: 4,
+            monthly: 3,
+        };
+        let keep = select_keep(&snapshots, policy);
+        // The last 7 days overlap the most recent week and month.
+        assert!(keep.len() <= 7 + 4 + 3);
+        assert!(keep.len() >= 7 + 3);
+        for (id, _) in snapshots.iter().rev().take(7) {
+            assert!(keep.contains(id));
+        }
+        let oldest_kept = snapshots
+            .iter()
+            .filter(|(id, _)| keep.contains(id))
+            .map(|(_, ts)| *ts)
+            .min()
+            .unwrap();
+        // Three monthly buckets reach back into February.
+        assert_eq!(oldest_kept.month(), 2);
+    }
+
+    #[test]
+    fn zero_policy_still_keeps_the_newest() {
+        let snapshots = daily_snapshots(5);
+        let keep = select_keep(
+            &snapshots,
+            GfsPolicy {
+                daily: 0,
+                weekly: 0,
+                monthly: 0,
+            },
+        );
+        assert_eq!(keep.len(), 1);
+        assert!(keep.contains(&snapshots[4].0));
+    }
+
+    #[test]
+    fn several_snapshots_a_day_keep_the_latest() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 2, 0, 0, 0).unwrap();
+        let snapshots: Vec<_> = (0..6)
+            .map(|hour| (Uuid::new_v4(), start + Duration::hours(hour * 4)))
+            .collect();
+        let keep = select_keep(
+            &snapshots,
+            GfsPolicy {
+                daily: 1,
+                weekly: 1,
+                monthly: 1,
+            },
+        );
+        assert_eq!(keep, HashSet::from([snapshots[5].0]));
+    }
+}
@@ -0,0 +1,449 @@
+//! Storage backends for offsite snapshots.
+
+use std::io::Read;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_s3::primitives::ByteStream;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use ssh2::Session;
+
+pub(crate) const KIND_S3: &str = "s3";
+pub(crate) const KIND_SFTP: &str = "sftp";
+pub(crate) const KIND_PATH: &str = "path";
+
+const SFTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct S3Config {
+    /// Custom endpoint for S3-compatible stores such as MinIO.
+    #[serde(default)]
+    endpoint: Option<String>,
+    #[serde(default = "default_region")]
+    region: String,
+    bucket: String,
+    #[serde(default)]
+    prefix: String,
+    #[serde(default)]
+    force_path_style: bool,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub(crate) struct S3Secrets {
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct SftpConfig {
+    host: String,
+    #[serde(default = "default_ssh_port")]
+    port: u16,
+    username: String,
+    /// Remote directory snapshots are written to.
+    path: String,
+    /// Pinned host key, `SHA256:<base64>` as printed by `ssh-keygen -lf`.
+    #[serde(default)]
+    host_key_sha256: Option<String>,
+}
+
+#[derive(Clone, Default, serde::Deserialize)]
+pub(crate) struct SftpSecrets {
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    private_key: Option<String>,
+    #[serde(default)]
+    private_key_passphrase: Option<String>,
+}
+
+// Secrets stay out of logs and error chains.
+impl std::fmt::Debug for S3Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("S3Secrets(..)")
+    }
+}
+
+impl std::fmt::Debug for SftpSecrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SftpSecrets(..)")
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct PathConfig {
+    path: PathBuf,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_ssh_port() -> u16 {
+    22
+}
+
+/// A configured target, ready to move snapshot files.
+#[derive(Debug, Clone)]
+pub(crate) enum BackupStore {
+    S3 {
+        config: S3Config,
+        secrets: S3Secrets,
+    },
+    Sftp {
+        config: SftpConfig,
+        secrets: SftpSecrets,
+    },
+    Path(PathBuf),
+}
+
+fn parse<T: serde::de::DeserializeOwned>(value: &JsonValue, what: &str) -> Result<T, String> {
+    serde_json::from_value(value.clone()).map_err(|err| format!("Invalid {what}: {err}"))
+}
+
+fn require(value: &str, field: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("{field} is required"));
+    }
+    Ok(())
+}
+
+impl BackupStore {
+    /// Validates a target's settings; errors are user-facing.
+    pub(crate) fn parse(
+        kind: &str,
+        config: &JsonValue,
+        secrets: &JsonValue,
+    ) -> Result<Self, String> {
+        match kind {
+            KIND_S3 => {
+                let config: S3Config = parse(config, "s3 config")?;
+                require(&config.bucket, "bucket")?;
+                let secrets: S3Secrets = parse(secrets, "s3 credentials")?;
+                require(&secrets.access_key_id, "access_key_id")?;
+                require(&secrets.secret_access_key, "secret_access_key")?;
+                Ok(BackupStore::S3 { config, secrets })
+            }
+            KIND_SFTP => {
+                let config: SftpConfig = parse(config, "sftp config")?;
+                require(&config.host, "host")?;
+                require(&config.username, "username")?;
+                require(&config.path, "path")?;
+                let secrets: SftpSecrets = parse(secrets, "sftp credentials")?;
+                if secrets.password.is_none() && secrets.private_key.is_none() {
+                    return Err("sftp targets need a password or private_key".to_string());
+                }
+                Ok(BackupStore::Sftp { config, secrets })
+            }
+            KIND_PATH => {
+                let config: PathConfig = parse(config, "path config")?;
+                if !config.path.is_absolute() {
+                    return Err("path must be absolute".to_string());
+                }
+                Ok(BackupStore::Path(config.path))
+            }
+            _ => Err("kind must be one of: s3, sftp, path".to_string()),
+        }
+    }
+
+    fn s3_client(config: &S3Config, secrets: &S3Secrets) -> aws_sdk_s3::Client {
+        let credentials = Credentials::new(
+            secrets.access_key_id.trim(),
+            secrets.secret_access_key.trim(),
+            None,
+            None,
+            "farm-dashboard-backup-target",
+        );
+        let mut builder = aws_sdk_s3::config::Builder::new()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(config.region.clone()))
+            .credentials_provider(credentials)
+            .force_path_style(config.force_path_style);
+        if let Some(endpoint) = config.endpoint.as_deref().filter(|value| !value.is_empty()) {
+            builder = builder.endpoint_url(endpoint);
+        }
+        aws_sdk_s3::Client::from_conf(builder.build())
+    }
+
+    fn s3_key(config: &S3Config, name: &str) -> String {
+        let prefix = config.prefix.trim_matches('/');
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{prefix}/{name}")
+        }
+    }
+
+    /// Uploads `local` as `name`. S3 uploads are a single PUT, which caps a
+    /// snapshot at 5 GiB.
+    pub(crate) async fn put(&self, name: &str, local: &Path) -> Result<()> {
+        match self {
+            BackupStore::S3 { config, secrets } => {
+                let body = ByteStream::from_path(local)
+                    .await
+                    .with_context(|| format!("failed to open {}", local.display()))?;
+                Self::s3_client(config, secrets)
+                    .put_object()
+                    .bucket(&config.bucket)
+                    .key(Self::s3_key(config, name))
+                    .body(body)
+                    .send()
+                    .await
+                    .context("S3 upload failed")?;
+                Ok(())
+            }
+            BackupStore::Sftp { config, secrets } => {
+                let (config, secrets) = (config.clone(), secrets.clone());
+                let (name, local) = (name.to_string(), local.to_path_buf());
+                tokio::task::spawn_blocking(move || sftp_put(&config, &secrets, &name, &local))
+                    .await?
+            }
+            BackupStore::Path(root) => {
+                tokio::fs::create_dir_all(root)
+                    .await
+                    .with_context(|| format!("failed to create {}", root.display()))?;
+                let partial = root.join(format!("{name}.partial"));
+                tokio::fs::copy(local, &partial)
+                    .await
+                    .with_context(|| format!("failed to write {}", partial.display()))?;
+                tokio::fs::rename(&partial, root.join(name)).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Downloads `name` into `local`.
+    pub(crate) async fn get(&self, name: &str, local: &Path) -> Result<()> {
+        match self {
+            BackupStore::S3 { config, secrets } => {
+                let object = Self::s3_client(config, secrets)
+                    .get_object()
+                    .bucket(&config.bucket)
+                    .key(Self::s3_key(config, name))
+                    .send()
+                    .await
+                    .context("S3 download failed")?;
+                let mut reader = object.body.into_async_read();
+                let mut file = tokio::fs::File::create(local).await?;
+                tokio::io::copy(&mut reader, &mut file).await?;
+                Ok(())
+            }
+            BackupStore::Sftp { config, secrets } => {
+                let (config, secrets) = (config.clone(), secrets.clone());
+                let (name, local) = (name.to_string(), local.to_path_buf());
+                tokio::task::spawn_blocking(move || sftp_get(&config, &secrets, &name, &local))
+                    .await?
+            }
+            BackupStore::Path(root) => {
+                tokio::fs::copy(root.join(name), local)
+                    .await
+                    .with_context(|| format!("failed to read {name} from {}", root.display()))?;
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) async fn delete(&self, name: &str) -> Result<()> {
+        match self {
+            BackupStore::S3 { config, secrets } => {
+                Self::s3_client(config, secrets)
+                    .delete_object()
+                    .bucket(&config.bucket)
+                    .key(Self::s3_key(config, name))
+                    .send()
+                    .await
+                    .context("S3 delete failed")?;
+                Ok(())
+            }
+            BackupStore::Sftp { config, secrets } => {
+                let (config, secrets) = (config.clone(), secrets.clone());
+                let name = name.to_string();
+                tokio::task::spawn_blocking(move || {
+                    let session = sftp_connect(&config, &secrets)?;
+                    let sftp = session.sftp().context("SFTP unavailable")?;
+                    sftp.unlink(&Path::new(&config.path).join(&name))
+                        .with_context(|| format!("failed to delete {name}"))
+                })
+                .await?
+            }
+            BackupStore::Path(root) => match tokio::fs::remove_file(root.join(name)).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Connectivity and permission check used by the "test target" action.
+    pub(crate) async fn check(&self) -> Result<()> {
+        match self {
+            BackupStore::S3 { config, secrets } => {
+                Self::s3_client(config, secrets)
+                    .head_bucket()
+                    .bucket(&config.bucket)
+                    .send()
+                    .await
+                    .with_context(|| format!("bucket {} is not reachable", config.bucket))?;
+                Ok(())
+            }
+            BackupStore::Sftp { config, secrets } => {
+                let (config, secrets) = (config.clone(), secrets.clone());
+                tokio::task::spawn_blocking(move || {
+                    let session = sftp_connect(&config, &secrets)?;
+                    let sftp = session.sftp().context("SFTP unavailable")?;
+                    sftp.stat(Path::new(&config.path)).with_context(|| {
+                        format!("remote path {} is not accessible", config.path)
+                    })?;
+                    Ok(())
+                })
+                .await?
+            }
+            BackupStore::Path(root) => {
+                tokio::fs::create_dir_all(root)
+                    .await
+                    .with_context(|| format!("failed to create {}", root.display()))?;
+                let probe = root.join(".farm-dashboard-write-test");
+                tokio::fs::write(&probe, b"ok")
+                    .await
+                    .with_context(|| format!("{} is not writable", root.display()))?;
+                let _ = tokio::fs::remove_file(&probe).await;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn sftp_connect(config: &SftpConfig, secrets: &SftpSecrets) -> Result<Session> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))
+        .with_context(|| format!("failed to connect to {}:{}", config.host, config.port))?;
+    tcp.set_read_timeout(Some(SFTP_TIMEOUT)).ok();
+    tcp.set_write_timeout(Some(SFTP_TIMEOUT)).ok();
+    let mut session = Session::new().context("Failed to create SSH session")?;
+    session.set_tcp_stream(tcp);
+    session.handshake().context("SSH handshake failed")?;
+
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| anyhow!("server sent no host key"))?;
+    let observed = format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(key)));
+    match config.host_key_sha256.as_deref().map(str::trim) {
+        Some(pinned) if pinned == observed => {}
+        Some(_) => bail!("host key mismatch: server presented {observed}"),
+        None => bail!("host key {observed} is not pinned; set host_key_sha256 to trust it"),
+    }
+
+    if let Some(private_key) = secrets.private_key.as_deref() {
+        session
+            .userauth_pubkey_memory(
+                &config.username,
+                None,
+                private_key,
+                secrets.private_key_passphrase.as_deref(),
+            )
+            .context("SSH key authentication failed")?;
+    } else if let Some(password) = secrets.password.as_deref() {
+        session
+            .userauth_password(&config.username, password)
+            .context("SSH password authentication failed")?;
+    }
+    if !session.authenticated() {
+        bail!("SSH authentication failed");
+    }
+    Ok(session)
+}
+
+fn sftp_put(config: &SftpConfig, secrets: &SftpSecrets, name: &str, local: &Path) -> Result<()> {
+    let session = sftp_connect(config, secrets)?;
+    let sftp = session.sftp().context("SFTP unavailable")?;
+    let dir = Path::new(&config.path);
+    let partial = dir.join(format!("{name}.partial"));
+    {
+        let mut source = std::fs::File::open(local)
+            .with_context(|| format!("failed to open {}", local.display()))?;
+        let mut remote = sftp
+            .create(&partial)
+            .with_context(|| format!("failed to create {}", partial.display()))?;
+        std::io::copy(&mut source, &mut remote).context("SFTP upload failed")?;
+    }
+    sftp.rename(&partial, &dir.join(name), None)
+        .context("failed to finalize SFTP upload")?;
+    Ok(())
+}
+
+fn sftp_get(config: &SftpConfig, secrets: &SftpSecrets, name: &str, local: &Path) -> Result<()> {
+    let session = sftp_connect(config, secrets)?;
+    let sftp = session.sftp().context("SFTP unavailable")?;
+    let mut remote = sftp
+        .open(Path::new(&config.path).join(name))
+        .with_context(|| format!("failed to open remote {name}"))?;
+    let mut file = std::fs::File::create(local)?;
+    let mut buf = vec![0u8; 256 * 1024];
+    loop {
+        let read = remote.read(&mut buf).context("SFTP download failed")?;
+        if read == 0 {
+            break;
+        }
+        std::io::Write::write_all(&mut file, &buf[..read])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_validates_per_kind() {
+        let s3 = BackupStore::parse(
+            KIND_S3,
+            &serde_json::json!({"bucket": "farm", "endpoint": "http://127.0.0.1:9000", "force_path_style": true}),
+            &serde_json::json!({"access_key_id": "minio", "secret_access_key": "minio123"}),
+        );
+        assert!(matches!(s3, Ok(BackupStore::S3 { .. })));
+
+        let missing_secret = BackupStore::parse(
+            KIND_S3,
+            &serde_json::json!({"bucket": "farm"}),
+            &serde_json::json!({}),
+        );
+        assert!(missing_secret.is_err());
+
+        let sftp = BackupStore::parse(
+            KIND_SFTP,
+            &serde_json::json!({"host": "backup.lan", "username": "farm", "path": "/srv/backups"}),
+            &serde_json::json!({}),
+        );
+        assert!(sftp.unwrap_err().contains("password or private_key"));
+
+        let relative = BackupStore::parse(
+            KIND_PATH,
+            &serde_json::json!({"path": "backups"}),
+            &serde_json::json!({}),
+        );
+        assert!(relative.is_err());
+    }
+
+    #[tokio::test]
+    async fn path_target_roundtrips_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BackupStore::Path(dir.path().join("offsite"));
+        store.check().await.unwrap();
+
+        let local = dir.path().join("snapshot.fdbak");
+        tokio::fs::write(&local, b"sealed").await.unwrap();
+        store.put("snap-1.fdbak", &local).await.unwrap();
+
+        let fetched = dir.path().join("fetched");
+        store.get("snap-1.fdbak", &fetched).await.unwrap();
+        assert_eq!(tokio::fs::read(&fetched).await.unwrap(), b"sealed");
+
+        store.delete("snap-1.fdbak").await.unwrap();
+        store.delete("snap-1.fdbak").await.unwrap();
+        assert!(store.get("snap-1.fdbak", &fetched).await.is_err());
+    }
+}
@@ -54,6 +54,8 @@ struct RestoreJobRow {
 }
 
 async fn poll_once(state: &AppState) -> anyhow::Result<()> {
+    poll_offsite_restore(state).await?;
+
     let Some(job) = claim_next_job(&state.db).await? else {
         return Ok(());
     };
@@ -182,6 +184,66 @@ async fn requeue_job(db: &PgPool, job_id: Uuid, message: &str) -> anyhow::Result
     Ok(())
 }
 
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct OffsiteRestoreJobRow {
+    id: Uuid,
+    offsite_backup_id: Uuid,
+    components: SqlJson<Vec<String>>,
+}
+
+/// Applies a queued controller restore from an offsite snapshot. Unlike node
+/// restores these are not retried: a half-applied restore needs an operator.
+async fn poll_offsite_restore(state: &AppState) -> anyhow::Result<()> {
+    let job: Option<OffsiteRestoreJobRow> = sqlx::query_as(
+        r#"
+        UPDATE offsite_restore_jobs
+        SET status = 'running', started_at = NOW(), updated_at = NOW()
+        WHERE id = (
+            SELECT id
+            FROM offsite_restore_jobs
+            WHERE status = 'queued'
+            ORDER BY created_at ASC
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, offsite_backup_id, components
+        "#,
+    )
+    .fetch_optional(&state.db)
+    .await?;
+    let Some(job) = job else {
+        return Ok(());
+    };
+
+    let outcome = crate::services::offsite_backup::restore_snapshot(
+        state,
+        job.offsite_backup_id,
+        &job.components.0,
+    )
+    .await;
+    let (status, message) = match outcome {
+        Ok(message) => ("ok", message),
+        Err(err) => ("error", format!("{err:#}")),
+    };
+    let _ = sqlx::query(
+        r#"
+        UPDATE offsite_restore_jobs
+        SET
+            status = $2,
+            message = $3,
+            finished_at = NOW(),
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job.id)
+    .bind(status)
+    .bind(&message)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
 async fn update_target_last_restore(
     db: &PgPool,
     job_id: Uuid,
@@ -243,25 +305,7 @@ async fn process_job(state: &AppState, job: &RestoreJobRow) -> anyhow::Result<Pr
         .await
         .with_context(|| format!("failed to read backup file {}", backup_path.display()))?;
 
-    let bundle: NodeBackupBundle =
-        serde_json::from_slice(&bytes).context("failed to parse backup bundle JSON")?;
-    if bundle.schema_version != NODE_BACKUP_SCHEMA_VERSION {
-        anyhow::bail!(
-            "unsupported backup bundle schema_version {} (expected {})",
-            bundle.schema_version,
-            NODE_BACKUP_SCHEMA_VERSION
-        );
-    }
-
-    let backup_node_from_file =
-        Uuid::parse_str(bundle.node_id.trim()).context("backup bundle contains invalid node_id")?;
-    if backup_node_from_file != job.backup_node_id {
-        anyhow::bail!(
-            "backup bundle node_id {} does not match requested backup_node_id {}",
-            bundle.node_id,
-            job.backup_node_id
-        );
-    }
+    let bundle = parse_bundle(&bytes, job.backup_node_id)?;
 
     let applied = apply_bundle_to_db(&state.db, job, &bundle).await?;
 
@@ -358,6 +402,49 @@ async fn allocate_sensor_id(
     anyhow::bail!("Unable to allocate unique sensor id");
 }
 
+/// Parses a node backup bundle and checks it belongs to `backup_node_id`.
+pub(crate) fn parse_bundle(bytes: &[u8], backup_node_id: Uuid) -> anyhow::Result<NodeBackupBundle> {
+    let bundle: NodeBackupBundle =
+        serde_json::from_slice(bytes).context("failed to parse backup bundle JSON")?;
+    if bundle.schema_version != NODE_BACKUP_SCHEMA_VERSION {
+        anyhow::bail!(
+            "unsupported backup bundle schema_version {} (expected {})",
+            bundle.schema_version,
+            NODE_BACKUP_SCHEMA_VERSION
+        );
+    }
+
+    let backup_node_from_file =
+        Uuid::parse_str(bundle.node_id.trim()).context("backup bundle contains invalid node_id")?;
+    if backup_node_from_file != backup_node_id {
+        anyhow::bail!(
+            "backup bundle node_id {} does not match requested backup_node_id {}",
+            bundle.node_id,
+            backup_node_id
+        );
+    }
+    Ok(bundle)
+}
+
+/// Applies a bundle onto its own node in `db` without touching the node agent.
+/// Used by backup verification against a scratch database; returns the number
+/// of sensors the restore would leave on the node.
+pub(crate) async fn apply_bundle_in_place(
+    db: &PgPool,
+    node_id: Uuid,
+    backup_date: NaiveDate,
+    bundle: &NodeBackupBundle,
+) -> anyhow::Result<usize> {
+    let job = RestoreJobRow {
+        id: Uuid::new_v4(),
+        backup_node_id: node_id,
+        backup_date,
+        target_node_id: node_id,
+    };
+    let applied = apply_bundle_to_db(db, &job, bundle).await?;
+    Ok(applied.desired_sensors.len())
+}
+
 async fn apply_bundle_to_db(
     db: &PgPool,
     job: &RestoreJobRow,
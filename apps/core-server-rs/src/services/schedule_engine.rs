@@ -3,6 +3,7 @@ use anyhow::Result;
 use chrono::{DateTime, Datelike, Local, Utc};
 use rrule::RRuleSet;
 use serde_json::Value as JsonValue;
+use sqlx::types::Json as SqlJson;
 use sqlx::PgPool;
 use std::sync::Arc;
 use std::time::Duration;
@@ -432,6 +433,26 @@ impl ScheduleEngine {
                 serde_json::json!({ "state": state, "reason": format!("schedule:{schedule_id}") });
             let topic = format!("iot/broadcast/outputs/{output_id}");
             self.mqtt.publish_json(&topic, &payload).await?;
+            let output_config: Option<(SqlJson<JsonValue>,)> =
+                sqlx::query_as("SELECT config FROM outputs WHERE id = $1")
+                    .bind(output_id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+            if let Some((output_config,)) = output_config {
+                if let Err(err) = crate::services::external_devices::publish_mqtt_output_command(
+                    &self.mqtt,
+                    &output_config.0,
+                    state,
+                )
+                .await
+                {
+                    tracing::warn!(
+                        output_id,
+                        schedule_id,
+                        "failed to publish mqtt device command: {err:#}"
+                    );
+                }
+            }
             return Ok(());
         }
 
@@ -0,0 +1,1065 @@
+//! Offline import of node-forwarder spool bundles (`node-forwarder spool export`).
+//!
+//! A bundle carries a node's unacked spool segments plus the files needed to
+//! read them. Samples are written straight into `metrics` (duplicates are
+//! skipped), the timestamps of unsynced samples are repaired with the bundled
+//! clock anchors, and the node's ACK is advanced over every seq that reached
+//! the database so live replay resumes after the imported data. Nothing in the
+//! manifest moves the ACK past seqs the bundle did not deliver.
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::error::AppResult;
+
+pub(crate) const BUNDLE_FORMAT: &str = "node-forwarder-spool-bundle";
+pub(crate) const BUNDLE_VERSION: u32 = 1;
+/// Largest bundle accepted by the import endpoint.
+pub(crate) const MAX_BUNDLE_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+
+/// Bit set on `quality` for a sample whose timestamp was re-based onto a synced clock.
+const QUALITY_TIME_CORRECTED: i16 = 0x40;
+/// Bit set on `quality` for an average written by spool compaction.
+const QUALITY_DOWNSAMPLED: i16 = 0x80;
+
+const SEGMENT_MAGIC: &[u8; 8] = b"FDSPOOL1";
+const SEGMENT_HEADER_LEN: usize = 64;
+const HEADER_LEVEL_OFFSET: usize = 48;
+const SAMPLE_RECORD_LEN: usize = 40;
+const MAX_FRAME_LEN: usize = 1024 * 1024;
+/// Segments are a few MiB; anything far larger is not a spool segment.
+const MAX_SEGMENT_BYTES: u64 = 512 * 1024 * 1024;
+const TIME_QUALITY_UNSYNCED: u16 = 2;
+
+const INSERT_BATCH: usize = 5_000;
+const MAX_REPORTED_UNKNOWN_SENSORS: usize = 50;
+const DROPPED_LOSS_REASON: &str = "spool_cap_drop_oldest_segment";
+
+#[derive(Debug)]
+pub(crate) enum BundleImportError {
+    Invalid(String),
+    Forbidden(String),
+    Internal(anyhow::Error),
+}
+
+impl From<sqlx::Error> for BundleImportError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Internal(err.into())
+    }
+}
+
+impl From<anyhow::Error> for BundleImportError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Internal(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BundleImportOptions {
+    pub dry_run: bool,
+    /// Replace the controller's ACK state when it belongs to another stream.
+    pub adopt_stream: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BundleManifest {
+    format: String,
+    version: u32,
+    node_id: String,
+    stream_id: Uuid,
+    #[serde(default)]
+    losses: Vec<BundleLoss>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub(crate) struct BundleLoss {
+    pub start_seq: u64,
+    pub end_seq: u64,
+    #[serde(default)]
+    pub dropped_at: Option<String>,
+    /// `dropped` (the seqs are gone) or `downsampled` (informational).
+    #[serde(default = "default_loss_kind")]
+    pub kind: String,
+}
+
+fn default_loss_kind() -> String {
+    "dropped".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AnchorKind {
+    Boot,
+    Sync,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ClockAnchor {
+    kind: AnchorKind,
+    boot_id: String,
+    seq: u64,
+    mono_ms: u64,
+    wall_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SpoolSample {
+    sensor_idx: u32,
+    seq: u64,
+    timestamp_ms: i64,
+    value: f64,
+    quality: i16,
+    time_quality: u16,
+    mono_ms: u64,
+    merged_seqs: Vec<u64>,
+}
+
+impl SpoolSample {
+    fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < SAMPLE_RECORD_LEN {
+            return None;
+        }
+        let mut sample = SpoolSample {
+            sensor_idx: u32::from_le_bytes(buf[0..4].try_into().ok()?),
+            seq: u64::from_le_bytes(buf[4..12].try_into().ok()?),
+            timestamp_ms: i64::from_le_bytes(buf[12..20].try_into().ok()?),
+            value: f64::from_le_bytes(buf[20..28].try_into().ok()?),
+            quality: i16::from_le_bytes(buf[28..30].try_into().ok()?),
+            time_quality: u16::from_le_bytes(buf[30..32].try_into().ok()?),
+            mono_ms: u64::from_le_bytes(buf[32..40].try_into().ok()?),
+            merged_seqs: Vec::new(),
+        };
+        let rest = &buf[SAMPLE_RECORD_LEN..];
+        if rest.is_empty() {
+            return Some(sample);
+        }
+        let count = u32::from_le_bytes(rest.get(0..4)?.try_into().ok()?) as usize;
+        let seqs = &rest[4..];
+        if seqs.len() != count.checked_mul(8)? {
+            return None;
+        }
+        sample.merged_seqs = seqs
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Some(sample)
+    }
+
+    fn seqs(&self) -> impl Iterator<Item = u64> + '_ {
+        std::iter::once(self.seq).chain(self.merged_seqs.iter().copied())
+    }
+
+    fn last_seq(&self) -> u64 {
+        self.seqs().fold(self.seq, u64::max)
+    }
+}
+
+fn decode_block(payload: &[u8]) -> Option<Vec<SpoolSample>> {
+    let raw = zstd::stream::decode_all(payload).ok()?;
+    let mut out = Vec::new();
+    let mut pos = 0usize;
+    while pos < raw.len() {
+        let len = u32::from_le_bytes(raw.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        pos += 4;
+        out.push(SpoolSample::decode(raw.get(pos..pos + len)?)?);
+        pos += len;
+    }
+    Some(out)
+}
+
+/// What was read from one bundled segment.
+#[derive(Debug, Clone, Default, Serialize, utoipa::ToSchema)]
+pub(crate) struct SegmentCheck {
+    pub path: String,
+    pub compaction_level: u32,
+    pub frames: u64,
+    /// Samples in the segment, including those merged into averages.
+    pub samples: u64,
+    pub crc_failures: u64,
+    pub undecodable_frames: u64,
+    pub trailing_bytes: u64,
+    /// Set when the segment could not be read at all.
+    pub error: Option<String>,
+}
+
+/// Decodes every frame of a segment, skipping frames that fail their CRC.
+fn decode_segment(path: &str, data: &[u8], stream_id: Uuid) -> (SegmentCheck, Vec<SpoolSample>) {
+    let mut check = SegmentCheck {
+        path: path.to_string(),
+        ..Default::default()
+    };
+    let mut samples = Vec::new();
+    if data.len() < SEGMENT_HEADER_LEN || &data[0..8] != SEGMENT_MAGIC {
+        check.error = Some("not a spool segment".to_string());
+        return (check, samples);
+    }
+    if data[16..32] != *stream_id.as_bytes() {
+        check.error = Some("segment belongs to another stream".to_string());
+        return (check, samples);
+    }
+    check.compaction_level = u32::from_le_bytes(
+        data[HEADER_LEVEL_OFFSET..HEADER_LEVEL_OFFSET + 4]
+            .try_into()
+            .unwrap(),
+    );
+
+    let mut pos = SEGMENT_HEADER_LEN;
+    while pos < data.len() {
+        let remaining = data.len() - pos;
+        if remaining < 8 {
+            check.trailing_bytes = remaining as u64;
+            break;
+        }
+        let len = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap());
+        if len == 0 || len > MAX_FRAME_LEN || len > remaining - 8 {
+            check.trailing_bytes = remaining as u64;
+            break;
+        }
+        let payload = &data[pos + 8..pos + 8 + len];
+        pos += 8 + len;
+        check.frames += 1;
+        if crc32c::crc32c(payload) != crc {
+            check.crc_failures += 1;
+            continue;
+        }
+        let decoded = if check.compaction_level == 0 {
+            (payload.len() == SAMPLE_RECORD_LEN)
+                .then(|| SpoolSample::decode(payload))
+                .flatten()
+                .map(|sample| vec![sample])
+        } else {
+            decode_block(payload)
+        };
+        let Some(decoded) = decoded else {
+            check.undecodable_frames += 1;
+            continue;
+        };
+        check.samples += decoded
+            .iter()
+            .map(|sample| 1 + sample.merged_seqs.len() as u64)
+            .sum::<u64>();
+        samples.extend(decoded);
+    }
+    (check, samples)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TimeRepair {
+    Unchanged,
+    Rebased(i64),
+    /// The node's current boot has not synced yet; leave the sample for live replay.
+    Deferred,
+    Uncorrectable {
+        reason: &'static str,
+        boot_id: Option<String>,
+    },
+}
+
+/// Mirrors node-forwarder's replay-time repair. The last boot session in the
+/// bundle may still sync on the node, so its unsynced samples are deferred
+/// instead of being held.
+fn repair_timestamp(anchors: &[ClockAnchor], sample: &SpoolSample) -> TimeRepair {
+    if sample.time_quality != TIME_QUALITY_UNSYNCED {
+        return TimeRepair::Unchanged;
+    }
+    let boots = anchors
+        .iter()
+        .filter(|anchor| anchor.kind == AnchorKind::Boot);
+    let Some(boot) = boots
+        .clone()
+        .filter(|anchor| anchor.seq <= sample.seq)
+        .max_by_key(|anchor| anchor.seq)
+    else {
+        return TimeRepair::Uncorrectable {
+            reason: "no_boot_anchor",
+            boot_id: None,
+        };
+    };
+    if let Some(sync) = anchors
+        .iter()
+        .find(|anchor| anchor.kind == AnchorKind::Sync && anchor.boot_id == boot.boot_id)
+    {
+        let offset = sample.mono_ms as i64 - sync.mono_ms as i64;
+        return TimeRepair::Rebased(sync.wall_ms.saturating_add(offset));
+    }
+    let is_last_boot = boots
+        .max_by_key(|anchor| anchor.seq)
+        .is_some_and(|last| last.seq == boot.seq);
+    if is_last_boot {
+        return TimeRepair::Deferred;
+    }
+    TimeRepair::Uncorrectable {
+        reason: "never_synced",
+        boot_id: Some(boot.boot_id.clone()),
+    }
+}
+
+/// Sorted, merged inclusive seq ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct SeqRanges(Vec<(u64, u64)>);
+
+impl SeqRanges {
+    fn insert(&mut self, start: u64, end: u64) {
+        if end < start {
+            return;
+        }
+        self.0.push((start, end));
+        self.0.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.0.len());
+        for (start, end) in self.0.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        self.0 = merged;
+    }
+
+    fn insert_seqs(&mut self, seqs: impl IntoIterator<Item = u64>) {
+        let seqs: BTreeSet<u64> = seqs.into_iter().collect();
+        let mut run: Option<(u64, u64)> = None;
+        for seq in seqs {
+            run = match run {
+                Some((start, end)) if seq == end + 1 => Some((start, seq)),
+                Some((start, end)) => {
+                    self.insert(start, end);
+                    Some((seq, seq))
+                }
+                None => Some((seq, seq)),
+            };
+        }
+        if let Some((start, end)) = run {
+            self.insert(start, end);
+        }
+    }
+
+    /// Highest seq reachable from `acked_seq` without a gap.
+    fn advance(&self, acked_seq: u64) -> u64 {
+        let mut acked = acked_seq;
+        for &(start, end) in &self.0 {
+            if start > acked.saturating_add(1) {
+                break;
+            }
+            acked = acked.max(end);
+        }
+        acked
+    }
+
+    fn to_report(&self) -> Vec<SeqRange> {
+        self.0
+            .iter()
+            .map(|&(start_seq, end_seq)| SeqRange { start_seq, end_seq })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub(crate) struct SeqRange {
+    pub start_seq: u64,
+    pub end_seq: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, utoipa::ToSchema)]
+pub(crate) struct SpoolBundleImportReport {
+    /// Node whose `agent_node_id` matches the bundle's `node_id`.
+    pub node_id: String,
+    pub node_mqtt_id: String,
+    pub stream_id: String,
+    pub dry_run: bool,
+    pub segments: Vec<SegmentCheck>,
+    /// Samples decoded from the bundle, including those merged into averages.
+    pub samples: u64,
+    /// Rows written to `metrics` (or, on a dry run, rows that would be).
+    pub accepted: u64,
+    /// Rows already present in `metrics`.
+    pub duplicates: u64,
+    /// Samples at or below the controller's ACK, which it already has.
+    pub already_acked: u64,
+    /// Samples re-based onto a synced clock (quality bit `0x40`).
+    pub time_corrected: u64,
+    /// Averaged rows (quality bit `0x80`).
+    pub downsampled: u64,
+    /// Samples parked in `metric_time_holding` because their clock never synced.
+    pub held: u64,
+    /// Samples from the node's current, still unsynced boot; they arrive with live replay.
+    pub deferred: u64,
+    /// Sensors in the bundle that the controller does not know; their samples are
+    /// skipped and left unacked for live replay.
+    pub unknown_sensors: Vec<String>,
+    pub unknown_sensor_samples: u64,
+    /// Seqs now accounted for on the controller.
+    pub delivered: Vec<SeqRange>,
+    pub losses: Vec<BundleLoss>,
+    pub acked_seq_before: u64,
+    pub acked_seq_after: u64,
+    pub ack_updated: bool,
+    pub notes: Vec<String>,
+}
+
+enum BundlePart {
+    Manifest(BundleManifest),
+    SensorMap(HashMap<String, u32>),
+    Anchors(Vec<ClockAnchor>),
+    Segment(SegmentCheck, Vec<SpoolSample>),
+}
+
+type PartResult = Result<BundlePart, BundleImportError>;
+
+/// Reads the tar on a blocking thread and hands parts over one at a time, so
+/// only one segment is held in memory.
+fn read_bundle(path: PathBuf, tx: mpsc::Sender<PartResult>) {
+    let result = (|| -> Result<(), BundleImportError> {
+        let file = std::fs::File::open(&path).context("open bundle")?;
+        let mut archive = tar::Archive::new(file);
+        let mut stream_id: Option<Uuid> = None;
+        let entries = archive
+            .entries()
+            .map_err(|err| BundleImportError::Invalid(format!("Not a tar file: {err}")))?;
+        for entry in entries {
+            let mut entry = entry.map_err(|err| {
+                BundleImportError::Invalid(format!("Unreadable tar entry: {err}"))
+            })?;
+            let name = entry
+                .path()
+                .map_err(|err| BundleImportError::Invalid(format!("Bad tar path: {err}")))?
+                .to_string_lossy()
+                .into_owned();
+            if entry.size() > MAX_SEGMENT_BYTES {
+                return Err(BundleImportError::Invalid(format!(
+                    "{name} is larger than {MAX_SEGMENT_BYTES} bytes"
+                )));
+            }
+            let mut data = Vec::with_capacity(entry.size() as usize);
+            entry
+                .read_to_end(&mut data)
+                .map_err(|err| BundleImportError::Invalid(format!("Truncated {name}: {err}")))?;
+            let invalid_json = |err: serde_json::Error| {
+                BundleImportError::Invalid(format!("Invalid {name}: {err}"))
+            };
+            let part = match name.as_str() {
+                "manifest.json" => {
+                    let manifest: BundleManifest =
+                        serde_json::from_slice(&data).map_err(invalid_json)?;
+                    stream_id = Some(manifest.stream_id);
+                    BundlePart::Manifest(manifest)
+                }
+                "sensor_map.json" => {
+                    BundlePart::SensorMap(serde_json::from_slice(&data).map_err(invalid_json)?)
+                }
+                "clock_anchors.json" => {
+                    BundlePart::Anchors(serde_json::from_slice(&data).map_err(invalid_json)?)
+                }
+                _ if name.starts_with("segments/") => {
+                    let stream_id = stream_id.ok_or_else(|| {
+                        BundleImportError::Invalid("manifest.json must come first".to_string())
+                    })?;
+                    let (check, samples) = decode_segment(&name, &data, stream_id);
+                    BundlePart::Segment(check, samples)
+                }
+                _ => continue,
+            };
+            if tx.blocking_send(Ok(part)).is_err() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    })();
+    if let Err(err) = result {
+        let _ = tx.blocking_send(Err(err));
+    }
+}
+
+struct PendingRow {
+    sensor_id: String,
+    ts: DateTime<Utc>,
+    value: f64,
+    quality: i16,
+}
+
+struct HeldRow {
+    sensor_id: String,
+    reported_ts: DateTime<Utc>,
+    value: f64,
+    quality: i16,
+    seq: u64,
+    mono_ms: u64,
+    boot_id: Option<String>,
+    reason: &'static str,
+}
+
+fn millis_to_dt(ms: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(ms)
+        .single()
+        .unwrap_or(DateTime::<Utc>::UNIX_EPOCH)
+}
+
+/// Imports the bundle at `path`. `authorize` is called with the node the bundle
+/// belongs to before anything is written; every sensor must be on that node.
+pub(crate) async fn import_bundle(
+    db: &PgPool,
+    path: &Path,
+    authorize: impl FnOnce(Uuid) -> AppResult<()>,
+    options: BundleImportOptions,
+) -> Result<SpoolBundleImportReport, BundleImportError> {
+    let (tx, mut rx) = mpsc::channel::<PartResult>(2);
+    let path = path.to_path_buf();
+    let reader = tokio::task::spawn_blocking(move || read_bundle(path, tx));
+
+    let mut report = SpoolBundleImportReport {
+        dry_run: options.dry_run,
+        ..Default::default()
+    };
+    let manifest = match rx.recv().await {
+        Some(Ok(BundlePart::Manifest(manifest))) => manifest,
+        Some(Err(err)) => return Err(err),
+        _ => {
+            return Err(BundleImportError::Invalid(
+                "Bundle does not start with manifest.json".to_string(),
+            ))
+        }
+    };
+    if manifest.format != BUNDLE_FORMAT || manifest.version != BUNDLE_VERSION {
+        return Err(BundleImportError::Invalid(format!(
+            "Unsupported bundle {} v{}",
+            manifest.format, manifest.version
+        )));
+    }
+    let node_mqtt_id = manifest.node_id.trim().to_string();
+    if node_mqtt_id.is_empty() {
+        return Err(BundleImportError::Invalid(
+            "Bundle has no node_id; export it with --node-id".to_string(),
+        ));
+    }
+    let node_ids: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM nodes WHERE NULLIF(TRIM(config->>'agent_node_id'), '') = $1",
+    )
+    .bind(&node_mqtt_id)
+    .fetch_all(db)
+    .await?;
+    let node_id = match node_ids.as_slice() {
+        [node_id] => *node_id,
+        [] => {
+            return Err(BundleImportError::Invalid(format!(
+                "No node reports forwarder id {node_mqtt_id}"
+            )))
+        }
+        _ => {
+            return Err(BundleImportError::Invalid(format!(
+                "More than one node reports forwarder id {node_mqtt_id}"
+            )))
+        }
+    };
+    authorize(node_id).map_err(|err| BundleImportError::Forbidden(err.message))?;
+    report.node_id = node_id.to_string();
+    report.node_mqtt_id = node_mqtt_id.clone();
+    report.stream_id = manifest.stream_id.to_string();
+    report.losses = manifest.losses.clone();
+
+    let ack_row: Option<(Uuid, i64)> = sqlx::query_as(
+        r#"
+        SELECT stream_id, acked_seq
+        FROM node_forwarder_ack_state
+        WHERE node_mqtt_id = $1
+        "#,
+    )
+    .bind(&node_mqtt_id)
+    .fetch_optional(db)
+    .await?;
+    let same_stream = ack_row.is_none_or(|(stream_id, _)| stream_id == manifest.stream_id);
+    let base_acked = match ack_row {
+        Some((_, acked)) if same_stream => acked.max(0) as u64,
+        _ => 0,
+    };
+    report.acked_seq_before = ack_row.map_or(0, |(_, acked)| acked.max(0) as u64);
+    if !same_stream && !options.adopt_stream {
+        report.notes.push(format!(
+            "The controller tracks stream {} for this node; samples are imported but the ACK is left alone (pass adopt_stream to switch).",
+            ack_row.map(|(stream_id, _)| stream_id).unwrap_or_default()
+        ));
+    }
+
+    let mut sensor_map: Option<HashMap<u32, String>> = None;
+    let mut anchors: Vec<ClockAnchor> = Vec::new();
+    let mut delivered = SeqRanges::default();
+    let mut unknown: BTreeSet<String> = BTreeSet::new();
+    let mut pending: Vec<PendingRow> = Vec::new();
+    let mut held: Vec<HeldRow> = Vec::new();
+
+    while let Some(part) = rx.recv().await {
+        match part? {
+            BundlePart::Manifest(_) => {
+                return Err(BundleImportError::Invalid(
+                    "Bundle has more than one manifest.json".to_string(),
+                ))
+            }
+            BundlePart::Anchors(list) => anchors = list,
+            BundlePart::SensorMap(map) => {
+                let ids: Vec<String> = map.keys().cloned().collect();
+                let rows: Vec<(String, Uuid)> = sqlx::query_as(
+                    r#"
+                    SELECT sensor_id, node_id
+                    FROM sensors
+                    WHERE sensor_id = ANY($1)
+                      AND deleted_at IS NULL
+                    "#,
+                )
+                .bind(&ids)
+                .fetch_all(db)
+                .await?;
+                if let Some((sensor_id, _)) = rows.iter().find(|(_, owner)| *owner != node_id) {
+                    return Err(BundleImportError::Invalid(format!(
+                        "Sensor {sensor_id} is not on node {node_mqtt_id}"
+                    )));
+                }
+                let known: HashMap<String, Uuid> = rows.into_iter().collect();
+                sensor_map = Some(
+                    map.into_iter()
+                        .filter_map(|(sensor_id, idx)| {
+                            if known.contains_key(&sensor_id) {
+                                Some((idx, sensor_id))
+                            } else {
+                                unknown.insert(sensor_id);
+                                None
+                            }
+                        })
+                        .collect(),
+                );
+            }
+            BundlePart::Segment(check, samples) => {
+                let sensors = sensor_map.as_ref().ok_or_else(|| {
+                    BundleImportError::Invalid(
+                        "sensor_map.json must come before the segments".to_string(),
+                    )
+                })?;
+                report.samples += check.samples;
+                report.segments.push(check);
+                for sample in samples {
+                    let count = 1 + sample.merged_seqs.len() as u64;
+                    if sample.last_seq() <= base_acked {
+                        report.already_acked += count;
+                        continue;
+                    }
+                    let Some(sensor_id) = sensors.get(&sample.sensor_idx) else {
+                        report.unknown_sensor_samples += count;
+                        continue;
+                    };
+                    let mut quality = sample.quality;
+                    if !sample.merged_seqs.is_empty() {
+                        quality |= QUALITY_DOWNSAMPLED;
+                        report.downsampled += 1;
+                    }
+                    let ts = match repair_timestamp(&anchors, &sample) {
+                        TimeRepair::Unchanged => sample.timestamp_ms,
+                        TimeRepair::Rebased(ts) => {
+                            quality |= QUALITY_TIME_CORRECTED;
+                            report.time_corrected += count;
+                            ts
+                        }
+                        TimeRepair::Deferred => {
+                            report.deferred += count;
+                            continue;
+                        }
+                        TimeRepair::Uncorrectable { reason, boot_id } => {
+                            report.held += count;
+                            delivered.insert_seqs(sample.seqs());
+                            held.push(HeldRow {
+                                sensor_id: sensor_id.clone(),
+                                reported_ts: millis_to_dt(sample.timestamp_ms),
+                                value: sample.value,
+                                quality,
+                                seq: sample.seq,
+                                mono_ms: sample.mono_ms,
+                                boot_id,
+                                reason,
+                            });
+                            continue;
+                        }
+                    };
+                    delivered.insert_seqs(sample.seqs());
+                    pending.push(PendingRow {
+                        sensor_id: sensor_id.clone(),
+                        ts: millis_to_dt(ts),
+                        value: sample.value,
+                        quality,
+                    });
+                    if pending.len() >= INSERT_BATCH {
+                        write_rows(db, &mut pending, &mut report, options.dry_run).await?;
+                    }
+                }
+            }
+        }
+    }
+    reader
+        .await
+        .map_err(|err| BundleImportError::Internal(anyhow!("bundle reader failed: {err}")))?;
+    if sensor_map.is_none() {
+        return Err(BundleImportError::Invalid(
+            "Bundle has no sensor_map.json".to_string(),
+        ));
+    }
+    write_rows(db, &mut pending, &mut report, options.dry_run).await?;
+    if !options.dry_run {
+        write_held(db, &node_mqtt_id, manifest.stream_id, &held).await?;
+    }
+
+    report.unknown_sensors = unknown
+        .into_iter()
+        .take(MAX_REPORTED_UNKNOWN_SENSORS)
+        .collect();
+    report.delivered = delivered.to_report();
+    if report.deferred > 0 {
+        report.notes.push(
+            "Samples from the node's current boot have no clock sync yet; they will arrive with live replay."
+                .to_string(),
+        );
+    }
+
+    // What the controller may ACK: everything delivered and every dropped range.
+    // The manifest's `acked_seq` is the node's word, not proof the data arrived.
+    let db_losses: Vec<(i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT start_seq, end_seq
+        FROM node_forwarder_loss_ranges
+        WHERE node_mqtt_id = $1
+          AND stream_id = $2
+        "#,
+    )
+    .bind(&node_mqtt_id)
+    .bind(manifest.stream_id)
+    .fetch_all(db)
+    .await?;
+    let mut skippable = delivered;
+    for loss in manifest.losses.iter().filter(|loss| loss.kind == "dropped") {
+        skippable.insert(loss.start_seq, loss.end_seq);
+    }
+    for (start, end) in db_losses {
+        skippable.insert(start.max(0) as u64, end.max(0) as u64);
+    }
+    let acked_after = skippable.advance(base_acked);
+
+    let may_update = same_stream || options.adopt_stream;
+    report.acked_seq_after = if may_update {
+        acked_after
+    } else {
+        report.acked_seq_before
+    };
+    if may_update && !options.dry_run {
+        report.ack_updated = persist_ack(db, &node_mqtt_id, &manifest, acked_after).await?;
+    }
+    Ok(report)
+}
+
+async fn write_rows(
+    db: &PgPool,
+    rows: &mut Vec<PendingRow>,
+    report: &mut SpoolBundleImportReport,
+    dry_run: bool,
+) -> Result<(), BundleImportError> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<String> = rows.iter().map(|row| row.sensor_id.clone()).collect();
+    let ts: Vec<DateTime<Utc>> = rows.iter().map(|row| row.ts).collect();
+    let written = if dry_run {
+        let existing: i64 = sqlx::query_scalar(
+            r#"
+            SELECT count(*)
+            FROM (
+                SELECT DISTINCT u.sensor_id, u.ts
+                FROM UNNEST($1::text[], $2::timestamptz[]) AS u(sensor_id, ts)
+                JOIN metrics m ON m.sensor_id = u.sensor_id AND m.ts = u.ts
+            ) matched
+            "#,
+        )
+        .bind(&ids)
+        .bind(&ts)
+        .fetch_one(db)
+        .await?;
+        (rows.len() as u64).saturating_sub(existing.max(0) as u64)
+    } else {
+        let values: Vec<f64> = rows.iter().map(|row| row.value).collect();
+        let qualities: Vec<i16> = rows.iter().map(|row| row.quality).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO metrics (sensor_id, ts, value, quality, inserted_at)
+            SELECT sensor_id, ts, value, quality, now()
+            FROM UNNEST($1::text[], $2::timestamptz[], $3::float8[], $4::int2[])
+                AS u(sensor_id, ts, value, quality)
+            ON CONFLICT (sensor_id, ts) DO NOTHING
+            "#,
+        )
+        .bind(&ids)
+        .bind(&ts)
+        .bind(&values)
+        .bind(&qualities)
+        .execute(db)
+        .await?
+        .rows_affected()
+    };
+    report.accepted += written;
+    report.duplicates += (rows.len() as u64).saturating_sub(written);
+    rows.clear();
+    Ok(())
+}
+
+async fn write_held(
+    db: &PgPool,
+    node_mqtt_id: &str,
+    stream_id: Uuid,
+    rows: &[HeldRow],
+) -> Result<(), BundleImportError> {
+    for chunk in rows.chunks(INSERT_BATCH) {
+        let sensor_ids: Vec<&str> = chunk.iter().map(|row| row.sensor_id.as_str()).collect();
+        let reported: Vec<DateTime<Utc>> = chunk.iter().map(|row| row.reported_ts).collect();
+        let values: Vec<f64> = chunk.iter().map(|row| row.value).collect();
+        let qualities: Vec<i32> = chunk.iter().map(|row| row.quality as i32).collect();
+        let seqs: Vec<i64> = chunk.iter().map(|row| row.seq as i64).collect();
+        let monos: Vec<i64> = chunk.iter().map(|row| row.mono_ms as i64).collect();
+        let boots: Vec<Option<&str>> = chunk.iter().map(|row| row.boot_id.as_deref()).collect();
+        let reasons: Vec<&str> = chunk.iter().map(|row| row.reason).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO metric_time_holding (
+                node_mqtt_id, sensor_id, reported_ts, value, quality, stream_id, seq, mono_ms, boot_id, reason
+            )
+            SELECT $1, sensor_id, reported_ts, value, quality, $2, seq, mono_ms, boot_id, reason
+            FROM UNNEST($3::text[], $4::timestamptz[], $5::float8[], $6::int4[], $7::int8[], $8::int8[], $9::text[], $10::text[])
+                AS u(sensor_id, reported_ts, value, quality, seq, mono_ms, boot_id, reason)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(node_mqtt_id)
+        .bind(stream_id)
+        .bind(&sensor_ids)
+        .bind(&reported)
+        .bind(&values)
+        .bind(&qualities)
+        .bind(&seqs)
+        .bind(&monos)
+        .bind(&boots)
+        .bind(&reasons)
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Writes the bundle's dropped ranges and the new ACK. telemetry-sidecar picks
+/// the ACK up from the database and publishes it to the node.
+async fn persist_ack(
+    db: &PgPool,
+    node_mqtt_id: &str,
+    manifest: &BundleManifest,
+    acked_seq: u64,
+) -> Result<bool, BundleImportError> {
+    let mut tx = db.begin().await?;
+    for loss in manifest.losses.iter().filter(|loss| loss.kind == "dropped") {
+        let dropped_at = loss
+            .dropped_at
+            .as_deref()
+            .and_then(|raw| DateTime::parse_from_rfc3339(raw).ok())
+            .map(|dt| dt.with_timezone(&Utc));
+        sqlx::query(
+            r#"
+            INSERT INTO node_forwarder_loss_ranges (node_mqtt_id, stream_id, start_seq, end_seq, dropped_at, reason)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(node_mqtt_id)
+        .bind(manifest.stream_id)
+        .bind(loss.start_seq as i64)
+        .bind(loss.end_seq as i64)
+        .bind(dropped_at)
+        .bind(DROPPED_LOSS_REASON)
+        .execute(&mut *tx)
+        .await?;
+    }
+    let updated = sqlx::query(
+        r#"
+        INSERT INTO node_forwarder_ack_state (node_mqtt_id, stream_id, acked_seq, updated_at)
+        VALUES ($1, $2, $3, NOW())
+        ON CONFLICT (node_mqtt_id)
+        DO UPDATE SET
+            stream_id = EXCLUDED.stream_id,
+            acked_seq = CASE
+                WHEN node_forwarder_ack_state.stream_id = EXCLUDED.stream_id
+                    THEN GREATEST(node_forwarder_ack_state.acked_seq, EXCLUDED.acked_seq)
+                ELSE EXCLUDED.acked_seq
+            END,
+            updated_at = NOW()
+        WHERE node_forwarder_ack_state.stream_id <> EXCLUDED.stream_id
+           OR node_forwarder_ack_state.acked_seq < EXCLUDED.acked_seq
+        "#,
+    )
+    .bind(node_mqtt_id)
+    .bind(manifest.stream_id)
+    .bind(acked_seq as i64)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        > 0;
+    sqlx::query(
+        r#"
+        DELETE FROM node_forwarder_loss_ranges
+        WHERE node_mqtt_id = $1
+          AND stream_id <> $2
+        "#,
+    )
+    .bind(node_mqtt_id)
+    .bind(manifest.stream_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(seq: u64, time_quality: u16, mono_ms: u64) -> SpoolSample {
+        SpoolSample {
+            sensor_idx: 0,
+            seq,
+            timestamp_ms: 1_000,
+            value: 1.0,
+            quality: 0,
+            time_quality,
+            mono_ms,
+            merged_seqs: Vec::new(),
+        }
+    }
+
+    fn encode(sample: &SpoolSample) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&sample.sensor_idx.to_le_bytes());
+        out.extend_from_slice(&sample.seq.to_le_bytes());
+        out.extend_from_slice(&sample.timestamp_ms.to_le_bytes());
+        out.extend_from_slice(&sample.value.to_le_bytes());
+        out.extend_from_slice(&sample.quality.to_le_bytes());
+        out.extend_from_slice(&sample.time_quality.to_le_bytes());
+        out.extend_from_slice(&sample.mono_ms.to_le_bytes());
+        if !sample.merged_seqs.is_empty() {
+            out.extend_from_slice(&(sample.merged_seqs.len() as u32).to_le_bytes());
+            for seq in &sample.merged_seqs {
+                out.extend_from_slice(&seq.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    fn segment(stream_id: Uuid, level: u32, frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = vec![0u8; SEGMENT_HEADER_LEN];
+        out[0..8].copy_from_slice(SEGMENT_MAGIC);
+        out[16..32].copy_from_slice(stream_id.as_bytes());
+        out[HEADER_LEVEL_OFFSET..HEADER_LEVEL_OFFSET + 4].copy_from_slice(&level.to_le_bytes());
+        for payload in frames {
+            out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            out.extend_from_slice(&crc32c::crc32c(payload).to_le_bytes());
+            out.extend_from_slice(payload);
+        }
+        out
+    }
+
+    #[test]
+    fn decodes_raw_and_compacted_segments_and_skips_bad_frames() {
+        let stream_id = Uuid::new_v4();
+        let raw = segment(
+            stream_id,
+            0,
+            &[encode(&sample(1, 1, 0)), encode(&sample(2, 1, 0))],
+        );
+        let crc_pos = SEGMENT_HEADER_LEN + 8 + SAMPLE_RECORD_LEN + 4;
+        let mut corrupted = raw.clone();
+        corrupted[crc_pos] ^= 0xff;
+        let (check, samples) = decode_segment("segments/a.seg", &corrupted, stream_id);
+        assert_eq!(check.frames, 2);
+        assert_eq!(check.crc_failures, 1);
+        assert_eq!(samples.len(), 1);
+
+        let mut averaged = sample(3, 1, 0);
+        averaged.merged_seqs = vec![4, 5];
+        let mut block = Vec::new();
+        for entry in [sample(2, 1, 0), averaged.clone()] {
+            let encoded = encode(&entry);
+            block.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+            block.extend_from_slice(&encoded);
+        }
+        let compressed = zstd::bulk::compress(&block, 3).unwrap();
+        let (check, samples) = decode_segment(
+            "segments/b.seg",
+            &segment(stream_id, 1, &[compressed]),
+            stream_id,
+        );
+        assert_eq!(check.compaction_level, 1);
+        assert_eq!(check.samples, 4);
+        assert_eq!(samples[1], averaged);
+
+        let (check, _) = decode_segment("segments/c.seg", &raw, Uuid::new_v4());
+        assert!(check.error.is_some());
+    }
+
+    #[test]
+    fn repairs_rebase_hold_or_defer_unsynced_samples() {
+        let anchor = |kind, boot_id: &str, seq, mono_ms, wall_ms| ClockAnchor {
+            kind,
+            boot_id: boot_id.to_string(),
+            seq,
+            mono_ms,
+            wall_ms,
+        };
+        let anchors = vec![
+            anchor(AnchorKind::Boot, "a", 1, 0, 0),
+            anchor(AnchorKind::Sync, "a", 5, 10_000, 1_700_000_000_000),
+            anchor(AnchorKind::Boot, "b", 10, 0, 0),
+            anchor(AnchorKind::Boot, "c", 20, 0, 0),
+        ];
+        assert_eq!(
+            repair_timestamp(&anchors, &sample(2, TIME_QUALITY_UNSYNCED, 4_000)),
+            TimeRepair::Rebased(1_700_000_000_000 - 6_000)
+        );
+        assert_eq!(
+            repair_timestamp(&anchors, &sample(12, TIME_QUALITY_UNSYNCED, 4_000)),
+            TimeRepair::Uncorrectable {
+                reason: "never_synced",
+                boot_id: Some("b".to_string()),
+            }
+        );
+        assert_eq!(
+            repair_timestamp(&anchors, &sample(21, TIME_QUALITY_UNSYNCED, 4_000)),
+            TimeRepair::Deferred
+        );
+        assert_eq!(
+            repair_timestamp(&anchors, &sample(21, 1, 4_000)),
+            TimeRepair::Unchanged
+        );
+        assert_eq!(
+            repair_timestamp(&[], &sample(21, TIME_QUALITY_UNSYNCED, 4_000)),
+            TimeRepair::Uncorrectable {
+                reason: "no_boot_anchor",
+                boot_id: None,
+            }
+        );
+    }
+
+    #[test]
+    fn ack_advances_over_delivered_seqs_and_losses_only() {
+        let mut ranges = SeqRanges::default();
+        ranges.insert_seqs([11, 12, 13, 15, 16, 30]);
+        ranges.insert(1, 10);
+        assert_eq!(ranges.0, vec![(1, 13), (15, 16), (30, 30)]);
+        assert_eq!(ranges.advance(0), 13);
+        ranges.insert(14, 14);
+        assert_eq!(ranges.advance(5), 16);
+        assert_eq!(ranges.advance(40), 40);
+    }
+}
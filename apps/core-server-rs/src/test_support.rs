@@ -62,6 +62,12 @@ pub fn test_config() -> CoreConfig {
         analysis_profile_enabled: false,
         analysis_profile_output_path: data_root.join("storage/analysis/tmp/profiles"),
         qdrant_url: "http://127.0.0.1:6333".to_string(),
+        oidc: None,
+        backup_encryption_passphrase: None,
+        backup_encryption_key_file: None,
+        backup_verify_interval_hours: 0,
+        data_loss_alarm_enabled: false,
+        trusted_proxies: Vec::new(),
     }
 }
 
@@ -117,5 +123,7 @@ pub fn test_user_with_caps(caps: &[&str]) -> crate::auth::AuthenticatedUser {
         role: "view".to_string(),
         capabilities,
         source: "test".to_string(),
+        session_id: None,
+        scopes: Default::default(),
     }
 }
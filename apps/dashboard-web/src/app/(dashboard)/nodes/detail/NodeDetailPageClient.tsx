@@ -32,6 +32,8 @@ import { formatNodeStatusLabel } from "@/lib/nodeStatus";
 import { sha256Hex } from "@/lib/sha256";
 import LiveWeatherPanel from "@/features/nodes/components/LiveWeatherPanel";
 import RenogyBt2SettingsSection from "@/features/nodes/components/RenogyBt2SettingsSection";
+import ForwarderConfigSection from "@/features/nodes/components/ForwarderConfigSection";
+import DataLossSection from "@/features/nodes/components/DataLossSection";
 import { deleteJson, putJson } from "@/lib/http";
 import { useAuth } from "@/components/AuthProvider";
 import { Input } from "@/components/ui/input";
@@ -1113,6 +1115,26 @@ export default function NodeDetailPageClient() {
         />
       </CollapsibleCard>
 
+      {!coreNode ? (
+        <CollapsibleCard
+          title="Telemetry forwarder"
+          description="Spool and replay settings pushed to node-forwarder over MQTT."
+          defaultOpen={false}
+        >
+          <ForwarderConfigSection nodeId={node.id} canEdit={canEdit} />
+        </CollapsibleCard>
+      ) : null}
+
+      {!coreNode ? (
+        <CollapsibleCard
+          title="Data losses"
+          description="Ranges node-forwarder reported as lost before they reached the controller."
+          defaultOpen={false}
+        >
+          <DataLossSection nodeId={node.id} />
+        </CollapsibleCard>
+      ) : null}
+
       <CollapsibleCard
         title="PV forecast (Forecast.Solar)"
         description="PV panel parameters are configured in Setup Center and applied per-node."
@@ -415,10 +415,51 @@ export default function TrendsPage() {
         } as AnnotationsOptions);
         continue;
       }
+
+      // Written by the controller when a node forwarder reports lost data; only shown for the
+      // charted sensors of that node.
+      if (type === "data_loss") {
+        if (!row.sensor_ids?.some((sensorId) => selected.includes(sensorId))) continue;
+        const startMs = new Date(row.time_start ?? row.created_at).getTime();
+        const endMs = new Date(row.time_end ?? row.time_start ?? row.created_at).getTime();
+        if (!Number.isFinite(startMs) || !Number.isFinite(endMs)) continue;
+        const edge = (tMs: number) => ({
+          type: "path",
+          strokeWidth: 1,
+          stroke: "#d97706",
+          dashStyle: "Dash",
+          points: [
+            { x: tMs, y: 0, xAxis: 0, yAxis: 0 },
+            { x: tMs, y: 1, xAxis: 0, yAxis: 0 },
+          ],
+          point: { x: tMs, xAxis: 0 },
+        });
+        out.push({
+          id: row.id,
+          draggable: "",
+          zIndex: 4,
+          labels: row.label
+            ? [
+                {
+                  point: { x: startMs, y: 0, xAxis: 0, yAxis: 0 },
+                  text: row.label,
+                  backgroundColor: "#fef3c7",
+                  borderColor: "#d97706",
+                  style: { color: "#92400e", fontSize: "10px", fontWeight: "600" },
+                  padding: 4,
+                  borderRadius: 4,
+                  y: -10,
+                },
+              ]
+            : [],
+          shapes: endMs > startMs ? [edge(startMs), edge(endMs)] : [edge(startMs)],
+        } as AnnotationsOptions);
+        continue;
+      }
     }
 
     return out;
-  }, [persistentAnnotations]);
+  }, [persistentAnnotations, selected]);
 
   const persistedBestFits: PersistedBestFit[] = useMemo(() => {
     if (!persistentAnnotations) return [];
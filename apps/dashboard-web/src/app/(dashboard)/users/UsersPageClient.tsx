@@ -26,6 +26,7 @@ const KNOWN_CAPABILITIES = [
   "setup.credentials.view",
   "config.write",
   "users.manage",
+  "audit.view",
   "schedules.write",
   "outputs.command",
   "alerts.view",
@@ -44,6 +45,7 @@ const ROLE_CAPABILITIES: Record<string, string[]> = {
     "setup.credentials.view",
     "config.write",
     "users.manage",
+    "audit.view",
     "schedules.write",
     "outputs.command",
     "alerts.view",
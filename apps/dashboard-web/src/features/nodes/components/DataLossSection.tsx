@@ -0,0 +1,59 @@
+"use client";
+
+import InlineBanner from "@/components/InlineBanner";
+import NodePill from "@/features/nodes/components/NodePill";
+import { useDataLossesQuery } from "@/lib/queries";
+
+function formatTime(value: string | null | undefined): string {
+  return value ? new Date(value).toLocaleString() : "—";
+}
+
+export default function DataLossSection({ nodeId }: { nodeId: string }) {
+  const lossesQuery = useDataLossesQuery(nodeId);
+  const losses = lossesQuery.data ?? [];
+
+  if (lossesQuery.isLoading) {
+    return <p className="text-sm text-muted-foreground">Loading data losses…</p>;
+  }
+  if (lossesQuery.error) {
+    return <InlineBanner tone="error">Data losses unavailable.</InlineBanner>;
+  }
+  if (!losses.length) {
+    return <p className="text-sm text-muted-foreground">The forwarder has not reported any lost data.</p>;
+  }
+
+  return (
+    <div className="overflow-x-auto">
+      <table className="w-full text-sm">
+        <thead>
+          <tr className="text-left text-xs uppercase tracking-wide text-muted-foreground">
+            <th className="py-1 pr-3 font-semibold">Gap</th>
+            <th className="py-1 pr-3 font-semibold">Samples</th>
+            <th className="py-1 pr-3 font-semibold">Cause</th>
+            <th className="py-1 font-semibold">Reported</th>
+          </tr>
+        </thead>
+        <tbody>
+          {losses.map((loss) => (
+            <tr key={loss.id} className="border-t border-border">
+              <td className="py-2 pr-3">
+                <p className="text-foreground">
+                  {formatTime(loss.time_start ?? loss.dropped_at)} – {loss.time_end ? formatTime(loss.time_end) : "open"}
+                </p>
+                <p className="text-xs text-muted-foreground">
+                  seq {loss.start_seq}–{loss.end_seq}
+                </p>
+              </td>
+              <td className="py-2 pr-3 text-foreground">{loss.samples.toLocaleString()}</td>
+              <td className="py-2 pr-3">
+                <NodePill tone="warning">{loss.kind}</NodePill>
+                {loss.reason ? <p className="mt-1 text-xs text-muted-foreground">{loss.reason}</p> : null}
+              </td>
+              <td className="py-2 text-muted-foreground">{formatTime(loss.received_at)}</td>
+            </tr>
+          ))}
+        </tbody>
+      </table>
+    </div>
+  );
+}
@@ -0,0 +1,197 @@
+"use client";
+
+import { useEffect, useMemo, useState } from "react";
+import { useQueryClient } from "@tanstack/react-query";
+import NodeButton from "@/features/nodes/components/NodeButton";
+import NodePill, { type NodePillTone } from "@/features/nodes/components/NodePill";
+import { NumericDraftInput } from "@/components/forms/NumericDraftInput";
+import InlineBanner from "@/components/InlineBanner";
+import { resetForwarderConfig, updateForwarderConfig } from "@/lib/api";
+import type { ForwarderSettings } from "@/lib/apiSchemas";
+import { queryKeys, useForwarderConfigQuery } from "@/lib/queries";
+
+type SettingKey = keyof ForwarderSettings & string;
+
+const FIELDS: Array<{ key: SettingKey; label: string; hint: string }> = [
+  { key: "max_spool_bytes", label: "Max spool (bytes)", hint: "At least 16 MiB" },
+  { key: "keep_free_bytes", label: "Keep free (bytes)", hint: "Disk space the spool never uses" },
+  { key: "max_spool_age_seconds", label: "Max spool age (s)", hint: "0 removes the limit; otherwise at least 3600" },
+  { key: "replay_msgs_per_sec", label: "Replay messages/s", hint: "1 to 100000" },
+  { key: "replay_bytes_per_sec", label: "Replay bytes/s", hint: "At least 4096" },
+  { key: "segment_roll_bytes", label: "Segment roll (bytes)", hint: "1 MiB to 1 GiB, at most half the spool" },
+  { key: "segment_roll_seconds", label: "Segment roll (s)", hint: "60 to 86400" },
+];
+
+const DRIFT_LABELS: Record<string, { label: string; tone: NodePillTone }> = {
+  in_sync: { label: "In sync", tone: "success" },
+  pending: { label: "Waiting for node", tone: "warning" },
+  rejected: { label: "Rejected by node", tone: "danger" },
+  unreported: { label: "No report from node", tone: "muted" },
+};
+
+type Draft = Partial<Record<SettingKey, number | null>>;
+
+function draftFromSettings(settings: ForwarderSettings | undefined): Draft {
+  const draft: Draft = {};
+  for (const field of FIELDS) {
+    const value = settings?.[field.key];
+    draft[field.key] = typeof value === "number" ? value : null;
+  }
+  return draft;
+}
+
+function formatEffective(value: unknown): string {
+  if (value === null) return "no limit";
+  if (typeof value === "number") return value.toLocaleString();
+  return "—";
+}
+
+export default function ForwarderConfigSection({ nodeId, canEdit }: { nodeId: string; canEdit: boolean }) {
+  const queryClient = useQueryClient();
+  const configQuery = useForwarderConfigQuery(nodeId);
+  const config = configQuery.data;
+  const [draft, setDraft] = useState<Draft>({});
+  const [busy, setBusy] = useState(false);
+  const [message, setMessage] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    setDraft(draftFromSettings(config?.settings));
+    // eslint-disable-next-line react-hooks/exhaustive-deps -- reset the draft only when the stored document changes
+  }, [config?.desired_version]);
+
+  const settings = useMemo(() => {
+    const out: ForwarderSettings = {};
+    for (const field of FIELDS) {
+      const value = draft[field.key];
+      if (typeof value === "number" && Number.isFinite(value)) out[field.key] = value;
+    }
+    return out;
+  }, [draft]);
+
+  const dirty = useMemo(
+    () => FIELDS.some((field) => (draft[field.key] ?? null) !== (config?.settings?.[field.key] ?? null)),
+    [draft, config?.settings],
+  );
+
+  const run = async (action: () => Promise<unknown>, success: string) => {
+    setBusy(true);
+    setError(null);
+    setMessage(null);
+    try {
+      const next = await action();
+      queryClient.setQueryData(queryKeys.forwarderConfig(nodeId), next);
+      setMessage(success);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : "Request failed");
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  if (configQuery.isLoading) {
+    return <p className="text-sm text-muted-foreground">Loading forwarder config…</p>;
+  }
+  if (configQuery.error || !config) {
+    return <InlineBanner tone="error">Forwarder config unavailable.</InlineBanner>;
+  }
+
+  const drift = DRIFT_LABELS[config.drift] ?? { label: config.drift, tone: "muted" as NodePillTone };
+  const effective = config.effective ?? null;
+
+  return (
+    <div className="space-y-4">
+      <div className="flex flex-wrap items-center gap-2">
+        <NodePill tone={drift.tone} size="md">
+          {drift.label}
+        </NodePill>
+        <NodePill size="md">{config.configured ? "Controller overrides" : "Node defaults"}</NodePill>
+        {config.applied_version ? (
+          <NodePill tone="muted" size="md">
+            Applied {config.applied_version.slice(0, 12)}
+          </NodePill>
+        ) : null}
+        {config.reported_at ? (
+          <span className="text-xs text-muted-foreground">
+            Reported {new Date(config.reported_at).toLocaleString()}
+          </span>
+        ) : null}
+      </div>
+
+      {!config.node_mqtt_id ? (
+        <InlineBanner tone="warning">
+          This node has not reported a forwarder id yet, so settings are stored but not delivered.
+        </InlineBanner>
+      ) : null}
+      {config.drift === "rejected" && config.error ? (
+        <InlineBanner tone="error">The node rejected these settings: {config.error}</InlineBanner>
+      ) : null}
+      {message ? <InlineBanner tone="success">{message}</InlineBanner> : null}
+      {error ? <InlineBanner tone="error">{error}</InlineBanner> : null}
+
+      <div className="overflow-x-auto">
+        <table className="w-full text-sm">
+          <thead>
+            <tr className="text-left text-xs uppercase tracking-wide text-muted-foreground">
+              <th className="py-1 pr-3 font-semibold">Setting</th>
+              <th className="py-1 pr-3 font-semibold">Desired</th>
+              <th className="py-1 font-semibold">Running</th>
+            </tr>
+          </thead>
+          <tbody>
+            {FIELDS.map((field) => {
+              const drifted = config.drifted_fields.includes(field.key);
+              return (
+                <tr key={field.key} className="border-t border-border">
+                  <td className="py-2 pr-3">
+                    <p className="font-medium text-foreground">{field.label}</p>
+                    <p className="text-xs text-muted-foreground">{field.hint}</p>
+                  </td>
+                  <td className="py-2 pr-3">
+                    <NumericDraftInput
+                      value={draft[field.key] ?? null}
+                      onValueChange={(next) =>
+                        setDraft((current) => ({ ...current, [field.key]: typeof next === "number" ? next : null }))
+                      }
+                      min={0}
+                      integer
+                      inputMode="numeric"
+                      placeholder="Node default"
+                      disabled={!canEdit || busy}
+                      className="w-40 rounded-md border border-border bg-white px-2 py-1 text-sm text-foreground shadow-xs focus:border-indigo-500 focus:outline-none focus:ring-2 focus:ring-indigo-500/30"
+                    />
+                  </td>
+                  <td className="py-2">
+                    <span className={drifted ? "font-semibold text-amber-700" : "text-foreground"}>
+                      {effective ? formatEffective(effective[field.key]) : "—"}
+                    </span>
+                  </td>
+                </tr>
+              );
+            })}
+          </tbody>
+        </table>
+      </div>
+
+      {canEdit ? (
+        <div className="flex flex-wrap gap-2">
+          <NodeButton
+            size="sm"
+            variant="primary"
+            disabled={busy || !dirty}
+            onClick={() => void run(() => updateForwarderConfig(nodeId, settings), "Forwarder settings saved.")}
+          >
+            Save
+          </NodeButton>
+          <NodeButton
+            size="sm"
+            disabled={busy || !config.configured}
+            onClick={() => void run(() => resetForwarderConfig(nodeId), "Node returned to its default settings.")}
+          >
+            Revert to node defaults
+          </NodeButton>
+        </div>
+      ) : null}
+    </div>
+  );
+}
@@ -149,6 +149,8 @@ import {
   RenogyReadCurrentResponseSchema,
   RenogyApplyResponseSchema,
   RenogyHistoryResponseSchema,
+  ForwarderConfigResponseSchema,
+  DataLossEventsResponseSchema,
   NodeDisplayProfileSchema,
   UpdateNodeDisplayProfileResponseSchema,
   NodeSensorsConfigResponseSchema,
@@ -180,6 +182,9 @@ import {
   type RenogyReadCurrentResponse as RenogyReadCurrentResponseType,
   type RenogyApplyResponse as RenogyApplyResponseType,
   type RenogyHistoryEntry as RenogyHistoryEntryType,
+  type ForwarderConfigResponse as ForwarderConfigResponseType,
+  type DataLossEvent as DataLossEventType,
+  type ForwarderSettings as ForwarderSettingsType,
 } from "@/lib/apiSchemas";
 
 export {
@@ -1212,6 +1217,33 @@ export async function updateRenogyDesiredSettings(
   return parseApiResponse(RenogyDesiredSettingsResponseSchema, raw, path);
 }
 
+export async function fetchForwarderConfig(nodeId: string): Promise<ForwarderConfigResponseType> {
+  return fetchJsonValidated(
+    `/api/nodes/${encodeURIComponent(nodeId)}/forwarder-config`,
+    ForwarderConfigResponseSchema,
+  );
+}
+
+export async function updateForwarderConfig(
+  nodeId: string,
+  settings: ForwarderSettingsType,
+): Promise<ForwarderConfigResponseType> {
+  const path = `/api/nodes/${encodeURIComponent(nodeId)}/forwarder-config`;
+  const raw = await putJson<unknown>(path, { settings });
+  return parseApiResponse(ForwarderConfigResponseSchema, raw, path);
+}
+
+export async function resetForwarderConfig(nodeId: string): Promise<ForwarderConfigResponseType> {
+  const path = `/api/nodes/${encodeURIComponent(nodeId)}/forwarder-config`;
+  const raw = await deleteJson<unknown>(path);
+  return parseApiResponse(ForwarderConfigResponseSchema, raw, path);
+}
+
+export async function fetchDataLosses(nodeId: string, limit = 50): Promise<DataLossEventType[]> {
+  const params = new URLSearchParams({ node_id: nodeId, limit: String(limit) });
+  return fetchJsonValidated(`/api/data-losses?${params.toString()}`, DataLossEventsResponseSchema);
+}
+
 export async function validateRenogyDesiredSettings(
   nodeId: string,
   desired: Record<string, unknown>,
@@ -1120,6 +1120,66 @@ export type RenogyReadCurrentResponse = z.infer<typeof RenogyReadCurrentResponse
 export type RenogyApplyResponse = z.infer<typeof RenogyApplyResponseSchema>;
 export type RenogyHistoryEntry = z.infer<typeof RenogyHistoryEntrySchema>;
 
+export const ForwarderSettingsSchema = z
+  .object({
+    max_spool_bytes: z.number().nullable().optional(),
+    keep_free_bytes: z.number().nullable().optional(),
+    max_spool_age_seconds: z.number().nullable().optional(),
+    replay_msgs_per_sec: z.number().nullable().optional(),
+    replay_bytes_per_sec: z.number().nullable().optional(),
+    segment_roll_bytes: z.number().nullable().optional(),
+    segment_roll_seconds: z.number().nullable().optional(),
+  })
+  .passthrough();
+
+export const ForwarderConfigResponseSchema = z
+  .object({
+    node_id: z.string(),
+    node_name: z.string(),
+    node_mqtt_id: z.string().nullable().optional(),
+    configured: z.boolean(),
+    settings: ForwarderSettingsSchema,
+    desired_version: z.string(),
+    updated_at: z.string().nullable().optional(),
+    updated_by: z.string().nullable().optional(),
+    applied_version: z.string().nullable().optional(),
+    applied_at: z.string().nullable().optional(),
+    rejected_version: z.string().nullable().optional(),
+    error: z.string().nullable().optional(),
+    effective: RecordSchema.nullable().optional(),
+    reported_at: z.string().nullable().optional(),
+    drift: z.string(),
+    drifted_fields: z.array(z.string()),
+  })
+  .passthrough();
+
+export type ForwarderSettings = z.infer<typeof ForwarderSettingsSchema>;
+export type ForwarderConfigResponse = z.infer<typeof ForwarderConfigResponseSchema>;
+
+export const DataLossEventSchema = z
+  .object({
+    id: z.string(),
+    node_id: z.string().nullable().optional(),
+    node_name: z.string().nullable().optional(),
+    node_mqtt_id: z.string(),
+    stream_id: z.string(),
+    start_seq: z.number(),
+    end_seq: z.number(),
+    samples: z.number(),
+    kind: z.string(),
+    reason: z.string().nullable().optional(),
+    dropped_at: z.string().nullable().optional(),
+    time_start: z.string().nullable().optional(),
+    time_end: z.string().nullable().optional(),
+    received_at: z.string(),
+    annotation_id: z.string().nullable().optional(),
+  })
+  .passthrough();
+
+export const DataLossEventsResponseSchema = z.array(DataLossEventSchema);
+
+export type DataLossEvent = z.infer<typeof DataLossEventSchema>;
+
 export const MapSettingsSchema = z
   .object({
     active_save_id: z.number(),
@@ -40,6 +40,8 @@ import {
   fetchUsers,
   fetchRenogySettingsSchema,
   fetchRenogyDesiredSettings,
+  fetchForwarderConfig,
+  fetchDataLosses,
   fetchRenogySettingsHistory,
   fetchBatteryConfig,
   fetchPowerRunwayConfig,
@@ -106,6 +108,8 @@ export const queryKeys = {
   renogySettingsSchema: (nodeId: string) => ["renogy", nodeId, "settings", "schema"] as const,
   renogyDesiredSettings: (nodeId: string) => ["renogy", nodeId, "settings", "desired"] as const,
   renogySettingsHistory: (nodeId: string) => ["renogy", nodeId, "settings", "history"] as const,
+  forwarderConfig: (nodeId: string) => ["nodes", nodeId, "forwarder-config"] as const,
+  dataLosses: (nodeId: string) => ["nodes", nodeId, "data-losses"] as const,
 };
 
 export const useNodesQuery = () =>
@@ -463,6 +467,26 @@ export const useRenogyDesiredSettingsQuery = (nodeId: string | null, options?: Q
   });
 };
 
+export const useForwarderConfigQuery = (nodeId: string | null, options?: QueryToggleOptions) => {
+  const enabled = Boolean(nodeId) && (options?.enabled ?? true);
+  return useQuery({
+    queryKey: queryKeys.forwarderConfig(nodeId ?? "missing"),
+    queryFn: () => fetchForwarderConfig(nodeId as string),
+    enabled,
+    staleTime: STALE_MEDIUM,
+  });
+};
+
+export const useDataLossesQuery = (nodeId: string | null, options?: QueryToggleOptions) => {
+  const enabled = Boolean(nodeId) && (options?.enabled ?? true);
+  return useQuery({
+    queryKey: queryKeys.dataLosses(nodeId ?? "missing"),
+    queryFn: () => fetchDataLosses(nodeId as string),
+    enabled,
+    staleTime: STALE_MEDIUM,
+  });
+};
+
 export const useRenogySettingsHistoryQuery = (nodeId: string | null, options?: QueryToggleOptions) => {
   const enabled = Boolean(nodeId) && (options?.enabled ?? true);
   return useQuery({
@@ -29,6 +29,7 @@ pub enum Commands {
     Config(ConfigArgs),
     Db(DbArgs),
     DevActivity(DevActivityArgs),
+    Spool(SpoolArgs),
     Bundle(BundleArgs),
     Installer(InstallerArgs),
     Dist(DistArgs),
@@ -201,6 +202,39 @@ pub struct DevActivityStatusArgs {
     pub json: bool,
 }
 
+#[derive(Args)]
+pub struct SpoolArgs {
+    #[command(subcommand)]
+    pub command: SpoolCommand,
+    /// Override the core-server base URL (defaults to http://127.0.0.1:<core_port>).
+    #[arg(long)]
+    pub core_url: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum SpoolCommand {
+    /// Import a bundle written by `node-forwarder spool export`.
+    Import(SpoolImportArgs),
+}
+
+#[derive(Args)]
+pub struct SpoolImportArgs {
+    /// Bundle file (tar).
+    pub file: PathBuf,
+    /// API token with metrics.ingest or config.write (defaults to $FARM_API_TOKEN).
+    #[arg(long)]
+    pub token: Option<String>,
+    /// Decode and count without writing anything.
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+    /// Take over the node's ACK state even if the controller tracks another stream.
+    #[arg(long, default_value_t = false)]
+    pub adopt_stream: bool,
+    /// Print the JSON report instead of a summary.
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
 #[derive(Args)]
 pub struct BundleArgs {
     #[arg(long)]
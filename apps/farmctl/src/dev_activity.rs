@@ -19,7 +19,7 @@ struct DevActivityStatusResponse {
     expires_at: Option<String>,
 }
 
-fn resolve_base_url(override_url: Option<String>) -> String {
+pub(crate) fn resolve_base_url(override_url: Option<String>) -> String {
     if let Some(url) = override_url {
         let trimmed = url.trim().trim_end_matches('/').to_string();
         if !trimmed.is_empty() {
@@ -23,6 +23,7 @@ mod processes;
 mod profile;
 mod server;
 mod service_user;
+mod spool;
 mod sysv_ipc;
 mod uninstall;
 mod utils;
@@ -47,6 +48,7 @@ fn main() -> Result<()> {
         Commands::Config(args) => config_cmd::handle(args),
         Commands::Db(args) => dev_db::handle(args),
         Commands::DevActivity(args) => dev_activity::handle(args),
+        Commands::Spool(args) => spool::handle(args),
         Commands::Bundle(args) => bundle::bundle(args),
         Commands::Installer(args) => bundle::installer(args),
         Commands::Dist(args) => dist::dist(args),
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::time::Duration;
+
+use crate::cli::{SpoolArgs, SpoolCommand, SpoolImportArgs};
+use crate::dev_activity::resolve_base_url;
+
+/// Large bundles are decoded and written while the request is open.
+const IMPORT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Deserialize)]
+struct SeqRange {
+    start_seq: u64,
+    end_seq: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SegmentCheck {
+    path: String,
+    crc_failures: u64,
+    undecodable_frames: u64,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportReport {
+    node_mqtt_id: String,
+    stream_id: String,
+    dry_run: bool,
+    segments: Vec<SegmentCheck>,
+    samples: u64,
+    accepted: u64,
+    duplicates: u64,
+    already_acked: u64,
+    time_corrected: u64,
+    held: u64,
+    deferred: u64,
+    unknown_sensors: Vec<String>,
+    unknown_sensor_samples: u64,
+    delivered: Vec<SeqRange>,
+    acked_seq_before: u64,
+    acked_seq_after: u64,
+    ack_updated: bool,
+    notes: Vec<String>,
+}
+
+fn handle_import(base: &str, args: SpoolImportArgs) -> Result<()> {
+    let token = args
+        .token
+        .or_else(|| std::env::var("FARM_API_TOKEN").ok())
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .context("an API token is required (--token or FARM_API_TOKEN)")?;
+    let file = fs::File::open(&args.file)
+        .with_context(|| format!("failed to open {}", args.file.display()))?;
+    let len = file.metadata()?.len();
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(IMPORT_TIMEOUT)
+        .build()
+        .context("failed to build HTTP client")?;
+    let response = client
+        .post(format!("{base}/api/nodes/spool-bundles"))
+        .query(&[
+            ("dry_run", args.dry_run.to_string()),
+            ("adopt_stream", args.adopt_stream.to_string()),
+        ])
+        .bearer_auth(token)
+        .header("Content-Type", "application/x-tar")
+        .body(reqwest::blocking::Body::sized(file, len))
+        .send()
+        .context("failed to upload spool bundle")?;
+    let http_status = response.status();
+    let text = response.text().unwrap_or_default();
+    if !http_status.is_success() {
+        anyhow::bail!("spool bundle import failed: {} {}", http_status, text);
+    }
+
+    if args.json {
+        let value: serde_json::Value =
+            serde_json::from_str(&text).context("failed to parse import report")?;
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&value).unwrap_or_else(|_| value.to_string())
+        );
+        return Ok(());
+    }
+
+    let report: ImportReport =
+        serde_json::from_str(&text).context("failed to parse import report")?;
+    print_report(&report);
+    Ok(())
+}
+
+fn print_report(report: &ImportReport) {
+    let mode = if report.dry_run { " (dry run)" } else { "" };
+    println!(
+        "node {} stream {}{mode}",
+        report.node_mqtt_id, report.stream_id
+    );
+    println!(
+        "segments: {}  samples: {}",
+        report.segments.len(),
+        report.samples
+    );
+    for segment in &report.segments {
+        if let Some(error) = &segment.error {
+            println!("  {}: {error}", segment.path);
+        } else if segment.crc_failures > 0 || segment.undecodable_frames > 0 {
+            println!(
+                "  {}: {} CRC failures, {} undecodable frames",
+                segment.path, segment.crc_failures, segment.undecodable_frames
+            );
+        }
+    }
+    println!(
+        "accepted: {}  duplicates: {}  already acked: {}",
+        report.accepted, report.duplicates, report.already_acked
+    );
+    println!(
+        "time corrected: {}  held: {}  deferred: {}",
+        report.time_corrected, report.held, report.deferred
+    );
+    if !report.unknown_sensors.is_empty() {
+        println!(
+            "unknown sensors ({} samples skipped): {}",
+            report.unknown_sensor_samples,
+            report.unknown_sensors.join(", ")
+        );
+    }
+    let delivered: Vec<String> = report
+        .delivered
+        .iter()
+        .map(|range| format!("{}-{}", range.start_seq, range.end_seq))
+        .collect();
+    if !delivered.is_empty() {
+        println!("delivered seqs: {}", delivered.join(", "));
+    }
+    let ack = if report.ack_updated {
+        "updated"
+    } else {
+        "unchanged"
+    };
+    println!(
+        "acked_seq: {} -> {} ({ack})",
+        report.acked_seq_before, report.acked_seq_after
+    );
+    for note in &report.notes {
+        println!("note: {note}");
+    }
+}
+
+pub fn handle(args: SpoolArgs) -> Result<()> {
+    let base = resolve_base_url(args.core_url);
+    match args.command {
+        SpoolCommand::Import(cmd) => handle_import(&base, cmd),
+    }
+}
@@ -17,6 +17,7 @@ from app.observability import configure_observability
 from app.routers import config as config_router
 from app.routers import display as display_router
 from app.routers import mesh as mesh_router
+from app.routers import outputs as outputs_router
 from app.routers import provisioning as provisioning_router
 from app.routers import renogy as renogy_router
 from app.routers import root as root_router
@@ -248,6 +249,7 @@ app.include_router(display_router.router)
 if build_info.BUILD_FLAVOR != "prod":
     app.include_router(simulation_router.router)
 app.include_router(mesh_router.router)
+app.include_router(outputs_router.router)
 app.include_router(renogy_router.router)
 app.include_router(provisioning_router.router)
 
@@ -0,0 +1,52 @@
+from __future__ import annotations
+
+import logging
+
+from fastapi import APIRouter, Depends, HTTPException, Request, status
+
+from app.auth import require_node_auth
+from app.config import Settings, get_settings
+from app.schemas import LocalOutputCommandPayload
+from app.services.output_listener import apply_output_command
+
+logger = logging.getLogger(__name__)
+
+router = APIRouter(prefix="/v1/local")
+
+LOOPBACK_HOSTS = {"127.0.0.1", "::1", "::ffff:127.0.0.1"}
+
+
+def require_loopback(request: Request) -> None:
+    """Only processes on the node itself (node-forwarder edge alarm rules) may call these.
+
+    Callers must also present the node bearer token, so other local processes cannot
+    drive outputs.
+    """
+
+    host = request.client.host if request.client else None
+    if host not in LOOPBACK_HOSTS:
+        raise HTTPException(status_code=status.HTTP_403_FORBIDDEN, detail="Local callers only")
+
+
+@router.post(
+    "/outputs/{output_id}/command",
+    dependencies=[Depends(require_loopback), Depends(require_node_auth)],
+)
+async def local_output_command(
+    output_id: str,
+    payload: LocalOutputCommandPayload,
+    request: Request,
+    settings: Settings = Depends(get_settings),
+):
+    if not any(output.output_id == output_id for output in settings.outputs):
+        raise HTTPException(status_code=status.HTTP_404_NOT_FOUND, detail="Unknown output")
+    simulator = getattr(request.app.state, "simulator", None)
+    applied, stuck = apply_output_command(settings, simulator, output_id, payload.state)
+    logger.warning(
+        "Applied local output command %s=%s (reason=%s, request_id=%s)",
+        output_id,
+        applied,
+        payload.reason or "local",
+        payload.request_id,
+    )
+    return {"output_id": output_id, "requested": payload.state, "state": applied, "stuck": stuck}
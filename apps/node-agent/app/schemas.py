@@ -29,6 +29,12 @@ class OutputUpdatePayload(BaseModel):
     supported_states: Optional[List[str]] = None
 
 
+class LocalOutputCommandPayload(BaseModel):
+    state: str
+    reason: Optional[str] = None
+    request_id: Optional[str] = None
+
+
 class NodeUpdatePayload(BaseModel):
     node_name: Optional[str] = None
     heartbeat_interval_seconds: Optional[float] = None
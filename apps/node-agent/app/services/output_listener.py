@@ -15,6 +15,33 @@ from app.services.simulator import SimulatedDevice
 logger = logging.getLogger(__name__)
 
 
+def apply_output_command(
+    settings: Settings,
+    simulator: SimulatedDevice | None,
+    output_id: str,
+    desired_state: str,
+) -> tuple[str, bool]:
+    """Apply a command to an output and record its new state; returns (applied state, stuck)."""
+
+    result_state = desired_state
+    stuck = False
+    if simulator:
+        result = simulator.apply_command(output_id, desired_state)
+        result_state = result.applied_state
+        stuck = result.stuck
+
+    for index, output in enumerate(settings.outputs):
+        if output.output_id == output_id:
+            updated = OutputConfig(
+                **output.model_dump(exclude={"default_state"}, exclude_none=True),
+                default_state=output.default_state,
+            )
+            updated.state = result_state
+            settings.outputs[index] = updated
+            break
+    return result_state, stuck
+
+
 class OutputCommandListener:
     """Subscribe to command topics and reflect state changes locally."""
 
@@ -101,14 +128,7 @@ class OutputCommandListener:
             logger.debug("Ignoring command without state for %s", topic)
             return
 
-        result_state = desired_state
-        stuck = False
-        if self.simulator:
-            result = self.simulator.apply_command(output_id, desired_state)
-            result_state = result.applied_state
-            stuck = result.stuck
-
-        self._update_output_state(output_id, result_state)
+        result_state, stuck = apply_output_command(self.settings, self.simulator, output_id, desired_state)
         await self._publish_ack(client, output_id, desired_state, result_state, reason, request_id, stuck)
 
     def _output_for_topic(self, topic: str) -> Optional[str]:
@@ -139,17 +159,6 @@ class OutputCommandListener:
             return str(state), reason, request_id
         return text.strip(), None, None
 
-    def _update_output_state(self, output_id: str, state: str) -> None:
-        for index, output in enumerate(self.settings.outputs):
-            if output.output_id == output_id:
-                updated = OutputConfig(
-                    **output.model_dump(exclude={"default_state"}, exclude_none=True),
-                    default_state=output.default_state,
-                )
-                updated.state = state
-                self.settings.outputs[index] = updated
-                break
-
     async def _publish_ack(
         self,
         client: Client,
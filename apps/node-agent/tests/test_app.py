@@ -124,6 +124,63 @@ def test_restore_config_replaces_outputs(api):
     assert any(output["output_id"] == "out-new" for output in config["outputs"])
 
 
+def test_local_output_command_rejects_remote_callers(api):
+    resp = api.post("/v1/local/outputs/out-new/command", json={"state": "on"}, headers=_auth())
+    assert resp.status_code == 403
+
+
+def test_local_output_command_requires_auth(api):
+    from app.routers.outputs import require_loopback
+
+    api.app.dependency_overrides[require_loopback] = lambda: None
+    try:
+        resp = api.post("/v1/local/outputs/out-new/command", json={"state": "on"})
+        assert resp.status_code == 401
+        resp = api.post(
+            "/v1/local/outputs/out-new/command",
+            json={"state": "on"},
+            headers={"Authorization": "Bearer wrong"},
+        )
+        assert resp.status_code == 403
+    finally:
+        api.app.dependency_overrides.pop(require_loopback, None)
+
+
+def test_local_output_command_applies_state(api):
+    from app.routers.outputs import require_loopback
+
+    payload = {
+        "outputs": [
+            {
+                "output_id": "out-local",
+                "name": "Irrigation Valve",
+                "type": "relay",
+                "channel": 3,
+                "supported_states": ["off", "on"],
+                "default_state": "off",
+            }
+        ]
+    }
+    assert api.post("/v1/config/restore", json=payload, headers=_auth()).status_code == 200
+    api.app.dependency_overrides[require_loopback] = lambda: None
+    try:
+        resp = api.post(
+            "/v1/local/outputs/out-local/command",
+            json={"state": "on", "reason": "edge_alarm", "request_id": "req-1"},
+            headers=_auth(),
+        )
+        assert resp.status_code == 200
+        body = resp.json()
+        assert body["output_id"] == "out-local"
+        assert body["requested"] == "on"
+        missing = api.post(
+            "/v1/local/outputs/out-missing/command", json={"state": "on"}, headers=_auth()
+        )
+        assert missing.status_code == 404
+    finally:
+        api.app.dependency_overrides.pop(require_loopback, None)
+
+
 def test_import_config_aliases_restore(api):
     payload = {
         "sensors": [
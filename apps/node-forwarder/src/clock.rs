@@ -0,0 +1,389 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::spool::TimeQuality;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorKind {
+    Boot,
+    Sync,
+}
+
+/// Pairs a spool seq with the monotonic clock and the wall clock at one instant.
+///
+/// A `boot` anchor opens a boot session: every seq from `seq` up to the next boot anchor was
+/// stamped with the same monotonic clock. A `sync` anchor is the first moment in that session the
+/// kernel reported the wall clock as synchronized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClockAnchor {
+    pub kind: AnchorKind,
+    pub boot_id: String,
+    pub seq: u64,
+    pub mono_ms: u64,
+    pub wall_ms: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UncorrectableReason {
+    /// The sample predates anchor tracking, so its boot session is unknown.
+    NoBootAnchor,
+    /// The boot session ended without the clock ever syncing.
+    NeverSynced,
+    /// The clock has not synced within the hold window of the current boot.
+    HoldExpired,
+}
+
+impl UncorrectableReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UncorrectableReason::NoBootAnchor => "no_boot_anchor",
+            UncorrectableReason::NeverSynced => "never_synced",
+            UncorrectableReason::HoldExpired => "hold_expired",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeRepair {
+    Unchanged,
+    Rebased {
+        timestamp_ms: i64,
+    },
+    /// The current boot may still sync; hold the sample until it does.
+    Pending,
+    Uncorrectable {
+        reason: UncorrectableReason,
+        boot_id: Option<String>,
+    },
+}
+
+/// What was done to a published sample's timestamp.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppliedRepair {
+    Rebased {
+        reported_timestamp_ms: i64,
+    },
+    Uncorrectable {
+        reason: UncorrectableReason,
+        boot_id: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ClockAnchors {
+    path: PathBuf,
+    boot_id: String,
+    anchors: Vec<ClockAnchor>,
+}
+
+impl ClockAnchors {
+    /// Loads anchors and opens a boot session at `next_seq` when this is a new boot.
+    pub fn open(
+        spool_dir: &Path,
+        boot_id: &str,
+        next_seq: u64,
+        mono_ms: u64,
+        wall_ms: i64,
+    ) -> Result<Self> {
+        let mut anchors = Self::load(spool_dir, boot_id)?;
+        let last_boot = anchors
+            .anchors
+            .iter()
+            .rev()
+            .find(|anchor| anchor.kind == AnchorKind::Boot);
+        if last_boot
+            .map(|anchor| anchor.boot_id != boot_id)
+            .unwrap_or(true)
+        {
+            anchors.anchors.push(ClockAnchor {
+                kind: AnchorKind::Boot,
+                boot_id: boot_id.to_string(),
+                seq: next_seq,
+                mono_ms,
+                wall_ms,
+            });
+            anchors.persist()?;
+        }
+        Ok(anchors)
+    }
+
+    /// Read-only view of the anchors on disk (used by replay).
+    pub fn load(spool_dir: &Path, boot_id: &str) -> Result<Self> {
+        let path = anchors_path(spool_dir);
+        let anchors = if path.exists() {
+            let raw =
+                fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+            serde_json::from_str(&raw).context("parse clock_anchors.json")?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path,
+            boot_id: boot_id.to_string(),
+            anchors,
+        })
+    }
+
+    pub fn current_synced(&self) -> bool {
+        self.anchors
+            .iter()
+            .any(|anchor| anchor.kind == AnchorKind::Sync && anchor.boot_id == self.boot_id)
+    }
+
+    /// Records the first clock sync of the current boot. Returns false when one already exists.
+    pub fn record_sync(&mut self, seq: u64, mono_ms: u64, wall_ms: i64) -> Result<bool> {
+        if self.current_synced() {
+            return Ok(false);
+        }
+        self.anchors.push(ClockAnchor {
+            kind: AnchorKind::Sync,
+            boot_id: self.boot_id.clone(),
+            seq,
+            mono_ms,
+            wall_ms,
+        });
+        self.persist()?;
+        Ok(true)
+    }
+
+    /// Decides how to publish a spooled sample. Only `Unsynced` samples are rebased, using the
+    /// first sync anchor of the boot session the sample was spooled in.
+    pub fn repair(
+        &self,
+        seq: u64,
+        mono_ms: u64,
+        time_quality: TimeQuality,
+        now_mono_ms: u64,
+        max_hold_ms: u64,
+    ) -> TimeRepair {
+        if !matches!(time_quality, TimeQuality::Unsynced) {
+            return TimeRepair::Unchanged;
+        }
+        let Some(boot) = self
+            .anchors
+            .iter()
+            .filter(|anchor| anchor.kind == AnchorKind::Boot && anchor.seq <= seq)
+            .max_by_key(|anchor| anchor.seq)
+        else {
+            return TimeRepair::Uncorrectable {
+                reason: UncorrectableReason::NoBootAnchor,
+                boot_id: None,
+            };
+        };
+
+        let sync = self
+            .anchors
+            .iter()
+            .find(|anchor| anchor.kind == AnchorKind::Sync && anchor.boot_id == boot.boot_id);
+        if let Some(sync) = sync {
+            let offset = mono_ms as i64 - sync.mono_ms as i64;
+            return TimeRepair::Rebased {
+                timestamp_ms: sync.wall_ms.saturating_add(offset),
+            };
+        }
+
+        if boot.boot_id != self.boot_id {
+            return TimeRepair::Uncorrectable {
+                reason: UncorrectableReason::NeverSynced,
+                boot_id: Some(boot.boot_id.clone()),
+            };
+        }
+        if now_mono_ms.saturating_sub(mono_ms) > max_hold_ms {
+            return TimeRepair::Uncorrectable {
+                reason: UncorrectableReason::HoldExpired,
+                boot_id: Some(boot.boot_id.clone()),
+            };
+        }
+        TimeRepair::Pending
+    }
+
+    /// Drops anchors for boot sessions that end at or before `acked_seq`.
+    pub fn prune(&mut self, acked_seq: u64) -> Result<()> {
+        let first_unacked = acked_seq.saturating_add(1);
+        let keep_from = self
+            .anchors
+            .iter()
+            .filter(|anchor| anchor.kind == AnchorKind::Boot && anchor.seq <= first_unacked)
+            .map(|anchor| anchor.seq)
+            .max();
+        let Some(keep_from) = keep_from else {
+            return Ok(());
+        };
+        let kept_boots: Vec<String> = self
+            .anchors
+            .iter()
+            .filter(|anchor| anchor.kind == AnchorKind::Boot && anchor.seq >= keep_from)
+            .map(|anchor| anchor.boot_id.clone())
+            .collect();
+        let before = self.anchors.len();
+        self.anchors.retain(|anchor| match anchor.kind {
+            AnchorKind::Boot => anchor.seq >= keep_from,
+            AnchorKind::Sync => kept_boots.contains(&anchor.boot_id),
+        });
+        if self.anchors.len() != before {
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    fn persist(&self) -> Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&self.anchors)?)
+            .context("write clock anchors tmp")?;
+        fs::rename(&tmp, &self.path).context("rename clock anchors")?;
+        Ok(())
+    }
+}
+
+fn anchors_path(spool_dir: &Path) -> PathBuf {
+    spool_dir.join("clock_anchors.json")
+}
+
+/// Kernel boot id; the monotonic clock is only comparable within one boot. Falls back to a
+/// per-process id where `/proc` is unavailable, which treats every restart as a new boot.
+pub fn current_boot_id() -> &'static str {
+    static BOOT_ID: OnceLock<String> = OnceLock::new();
+    BOOT_ID.get_or_init(|| {
+        fs::read_to_string("/proc/sys/kernel/random/boot_id")
+            .ok()
+            .map(|raw| raw.trim().to_string())
+            .filter(|raw| !raw.is_empty())
+            .unwrap_or_else(|| format!("process-{}", Uuid::new_v4()))
+    })
+}
+
+/// Whether the kernel considers the wall clock synchronized (NTP/chrony/RTC discipline).
+pub fn system_clock_synced() -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let mut tx: libc::timex = std::mem::zeroed();
+        let state = libc::adjtimex(&mut tx);
+        if state < 0 {
+            return None;
+        }
+        Some(state != libc::TIME_ERROR && tx.status & libc::STA_UNSYNC == 0)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+pub fn monotonic_ms() -> u64 {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        if libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) != 0 {
+            return 0;
+        }
+        let secs = ts.tv_sec.max(0) as u64;
+        let nanos = ts.tv_nsec.max(0) as u64;
+        secs.saturating_mul(1000) + nanos / 1_000_000
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const HOUR_MS: u64 = 3_600_000;
+
+    #[test]
+    fn unsynced_samples_are_rebased_from_first_sync_in_boot() {
+        let dir = TempDir::new().unwrap();
+        let mut anchors = ClockAnchors::open(dir.path(), "boot-a", 1, 5_000, 0).unwrap();
+
+        // Clock syncs at mono 65s: the true time was 1_700_000_000_000.
+        anchors.record_sync(11, 65_000, 1_700_000_000_000).unwrap();
+        assert!(!anchors.record_sync(12, 70_000, 1_700_000_005_000).unwrap());
+
+        let repair = anchors.repair(3, 15_000, TimeQuality::Unsynced, 70_000, HOUR_MS);
+        assert_eq!(
+            repair,
+            TimeRepair::Rebased {
+                timestamp_ms: 1_700_000_000_000 - 50_000
+            }
+        );
+        assert_eq!(
+            anchors.repair(3, 15_000, TimeQuality::Good, 70_000, HOUR_MS),
+            TimeRepair::Unchanged
+        );
+    }
+
+    #[test]
+    fn unsynced_samples_wait_for_sync_then_expire_or_become_uncorrectable() {
+        let dir = TempDir::new().unwrap();
+        let anchors = ClockAnchors::open(dir.path(), "boot-a", 1, 5_000, 0).unwrap();
+        assert_eq!(
+            anchors.repair(2, 6_000, TimeQuality::Unsynced, 7_000, HOUR_MS),
+            TimeRepair::Pending
+        );
+        assert_eq!(
+            anchors.repair(
+                2,
+                6_000,
+                TimeQuality::Unsynced,
+                6_000 + HOUR_MS + 1,
+                HOUR_MS
+            ),
+            TimeRepair::Uncorrectable {
+                reason: UncorrectableReason::HoldExpired,
+                boot_id: Some("boot-a".to_string()),
+            }
+        );
+
+        // After a reboot, the earlier session can never sync anymore.
+        drop(anchors);
+        let mut anchors = ClockAnchors::open(dir.path(), "boot-b", 40, 2_000, 0).unwrap();
+        anchors.record_sync(41, 3_000, 1_700_000_000_000).unwrap();
+        assert_eq!(
+            anchors.repair(2, 6_000, TimeQuality::Unsynced, 4_000, HOUR_MS),
+            TimeRepair::Uncorrectable {
+                reason: UncorrectableReason::NeverSynced,
+                boot_id: Some("boot-a".to_string()),
+            }
+        );
+        assert_eq!(
+            anchors.repair(40, 2_500, TimeQuality::Unsynced, 4_000, HOUR_MS),
+            TimeRepair::Rebased {
+                timestamp_ms: 1_700_000_000_000 - 500
+            }
+        );
+    }
+
+    #[test]
+    fn restart_within_boot_keeps_session_and_prune_drops_acked_sessions() {
+        let dir = TempDir::new().unwrap();
+        ClockAnchors::open(dir.path(), "boot-a", 1, 5_000, 0).unwrap();
+        let anchors = ClockAnchors::open(dir.path(), "boot-a", 30, 9_000, 0).unwrap();
+        assert_eq!(anchors.anchors.len(), 1);
+
+        let mut anchors = ClockAnchors::open(dir.path(), "boot-b", 50, 1_000, 0).unwrap();
+        anchors.record_sync(55, 2_000, 1_700_000_000_000).unwrap();
+        anchors.prune(30).unwrap();
+        assert_eq!(anchors.anchors.len(), 3);
+        anchors.prune(49).unwrap();
+        assert_eq!(anchors.anchors.len(), 2);
+        assert!(anchors
+            .anchors
+            .iter()
+            .all(|anchor| anchor.boot_id == "boot-b"));
+
+        let reloaded = ClockAnchors::load(dir.path(), "boot-b").unwrap();
+        assert_eq!(reloaded.anchors, anchors.anchors);
+        assert!(reloaded.current_synced());
+    }
+}
@@ -0,0 +1,937 @@
+//! Alarm rules pushed by the controller on `<prefix>/<node>/edge-rules` and evaluated here.
+//!
+//! Rules are evaluated against every incoming sample so window, streak and firing state are warm
+//! when the link drops. Transitions only have effects once no MQTT upstream has been reachable for
+//! `edge_offline_after`: the output action is sent to node-agent, and the transition is spooled as
+//! an `edge-alarm.*` sample for the controller to reconcile after it reconnects.
+
+use crate::config::Config;
+use crate::spool::{IncomingSample, SpoolHandle};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Sensor id prefix of spooled transitions: `edge-alarm.<rule_id>.<target hash>`.
+pub const EDGE_ALARM_SENSOR_PREFIX: &str = "edge-alarm.";
+const QUALITY_FIRED: i16 = 1;
+const QUALITY_RESOLVED: i16 = 2;
+/// Per-sensor cap on rolling window history.
+const MAX_WINDOW_SAMPLES: usize = 10_000;
+const BUNDLE_FILE: &str = "edge_rules.json";
+const ACTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Eq,
+    Neq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RangeMode {
+    Inside,
+    Outside,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateOp {
+    Avg,
+    Min,
+    Max,
+    Stddev,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsecutivePeriod {
+    Eval,
+    Hour,
+    Day,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    #[default]
+    PerSensor,
+    Any,
+    All,
+}
+
+/// The subset of the controller's alarm condition AST a node can evaluate on its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    Threshold {
+        op: CompareOp,
+        value: f64,
+    },
+    Range {
+        mode: RangeMode,
+        low: f64,
+        high: f64,
+    },
+    RollingWindow {
+        window_seconds: i64,
+        aggregate: AggregateOp,
+        op: CompareOp,
+        value: f64,
+    },
+    ConsecutivePeriods {
+        period: ConsecutivePeriod,
+        count: u32,
+        child: Box<Condition>,
+    },
+}
+
+impl Condition {
+    fn max_window_ms(&self) -> i64 {
+        match self {
+            Self::RollingWindow { window_seconds, .. } => window_seconds.max(&1) * 1000,
+            Self::ConsecutivePeriods { child, .. } => child.max_window_ms(),
+            Self::Threshold { .. } | Self::Range { .. } => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timing {
+    #[serde(default)]
+    pub debounce_seconds: i64,
+    #[serde(default)]
+    pub clear_hysteresis_seconds: i64,
+    #[serde(default)]
+    pub eval_interval_seconds: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EdgeAction {
+    pub output_id: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EdgeRule {
+    pub rule_id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub severity: String,
+    pub target_key: String,
+    pub event_sensor_id: String,
+    pub sensor_ids: Vec<String>,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    pub condition: Condition,
+    #[serde(default)]
+    pub timing: Timing,
+    #[serde(default)]
+    pub fire_action: Option<EdgeAction>,
+    #[serde(default)]
+    pub clear_action: Option<EdgeAction>,
+}
+
+/// Rules stay raw JSON until installed so one rule this build cannot parse does not discard
+/// the rest of the bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Bundle {
+    version: String,
+    #[serde(default)]
+    generated_at: Option<String>,
+    #[serde(default)]
+    rules: Vec<JsonValue>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Fired,
+    Resolved,
+}
+
+impl Transition {
+    fn quality(self) -> i16 {
+        match self {
+            Self::Fired => QUALITY_FIRED,
+            Self::Resolved => QUALITY_RESOLVED,
+        }
+    }
+}
+
+/// The `edge_transition` label for a spooled transition sample, if `sensor_id` is one.
+pub fn transition_label(sensor_id: &str, quality: i16) -> Option<&'static str> {
+    if !sensor_id.starts_with(EDGE_ALARM_SENSOR_PREFIX) {
+        return None;
+    }
+    match quality {
+        QUALITY_FIRED => Some("fired"),
+        QUALITY_RESOLVED => Some("resolved"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Streak {
+    streak: i64,
+    last_period: Option<i64>,
+}
+
+#[derive(Debug)]
+struct TargetState {
+    rule: EdgeRule,
+    window_ms: i64,
+    latest: HashMap<String, f64>,
+    windows: HashMap<String, VecDeque<(i64, f64)>>,
+    streaks: HashMap<String, Streak>,
+    firing: bool,
+    /// Firing state the controller knows about: its own while the link is up, the last
+    /// spooled edge event once the node acts alone.
+    reported: bool,
+    /// When `firing` last changed, and the value that changed it.
+    changed_at_ms: i64,
+    changed_observed: Option<f64>,
+    first_true_at: Option<i64>,
+    first_false_at: Option<i64>,
+    last_eval_ms: Option<i64>,
+}
+
+struct Outcome {
+    passed: bool,
+    observed: Option<f64>,
+}
+
+impl TargetState {
+    fn new(rule: EdgeRule) -> Self {
+        Self {
+            window_ms: rule.condition.max_window_ms(),
+            rule,
+            latest: HashMap::new(),
+            windows: HashMap::new(),
+            streaks: HashMap::new(),
+            firing: false,
+            reported: false,
+            changed_at_ms: 0,
+            changed_observed: None,
+            first_true_at: None,
+            first_false_at: None,
+            last_eval_ms: None,
+        }
+    }
+
+    fn record(&mut self, sensor_id: &str, timestamp_ms: i64, value: f64) {
+        self.latest.insert(sensor_id.to_string(), value);
+        if self.window_ms <= 0 {
+            return;
+        }
+        let window = self.windows.entry(sensor_id.to_string()).or_default();
+        window.push_back((timestamp_ms, value));
+        if window.len() > MAX_WINDOW_SAMPLES {
+            window.pop_front();
+        }
+    }
+
+    /// Evaluates the rule at `now_ms` and returns the transition, if the firing state changed.
+    fn evaluate(&mut self, now_ms: i64) -> Option<(Transition, Option<f64>)> {
+        let interval_ms = self.rule.timing.eval_interval_seconds.max(0) * 1000;
+        if interval_ms > 0
+            && self
+                .last_eval_ms
+                .is_some_and(|last| now_ms - last < interval_ms)
+        {
+            return None;
+        }
+        self.last_eval_ms = Some(now_ms);
+
+        let cutoff = now_ms - self.window_ms;
+        for window in self.windows.values_mut() {
+            while window.front().is_some_and(|(ts, _)| *ts < cutoff) {
+                window.pop_front();
+            }
+        }
+
+        let condition = self.rule.condition.clone();
+        let outcome = self.eval_condition(&condition, now_ms, "root");
+        let desired = self.apply_timing(outcome.passed, now_ms / 1000);
+        if desired == self.firing {
+            return None;
+        }
+        self.firing = desired;
+        self.changed_at_ms = now_ms;
+        self.changed_observed = outcome.observed;
+        let transition = if desired {
+            Transition::Fired
+        } else {
+            Transition::Resolved
+        };
+        Some((transition, outcome.observed))
+    }
+
+    fn eval_condition(&mut self, condition: &Condition, now_ms: i64, path: &str) -> Outcome {
+        match condition {
+            Condition::Threshold { op, value } => {
+                let values = self.latest_values();
+                eval_values(values, self.rule.match_mode, |sample| {
+                    compare(sample, *op, *value)
+                })
+            }
+            Condition::Range { mode, low, high } => {
+                let values = self.latest_values();
+                eval_values(values, self.rule.match_mode, |sample| {
+                    let inside = sample >= *low && sample <= *high;
+                    match mode {
+                        RangeMode::Inside => inside,
+                        RangeMode::Outside => !inside,
+                    }
+                })
+            }
+            Condition::RollingWindow {
+                window_seconds,
+                aggregate,
+                op,
+                value,
+            } => {
+                let start = now_ms - window_seconds.max(&1) * 1000;
+                let samples: Vec<f64> = self
+                    .rule
+                    .sensor_ids
+                    .iter()
+                    .filter_map(|sensor_id| {
+                        let points: Vec<f64> = self
+                            .windows
+                            .get(sensor_id)?
+                            .iter()
+                            .filter(|(ts, _)| *ts >= start)
+                            .map(|(_, v)| *v)
+                            .collect();
+                        aggregate_values(&points, *aggregate)
+                    })
+                    .collect();
+                eval_values(samples, self.rule.match_mode, |sample| {
+                    compare(sample, *op, *value)
+                })
+            }
+            Condition::ConsecutivePeriods {
+                period,
+                count,
+                child,
+            } => {
+                let child_outcome = self.eval_condition(child, now_ms, &format!("{path}.cp"));
+                let current = period_bucket(*period, now_ms / 1000);
+                let state = self.streaks.entry(path.to_string()).or_default();
+                if child_outcome.passed {
+                    state.streak = match (period, state.last_period) {
+                        (ConsecutivePeriod::Eval, _) => state.streak + 1,
+                        (_, Some(last)) if last == current => state.streak.max(1),
+                        (_, Some(last)) if last + 1 == current => state.streak + 1,
+                        _ => 1,
+                    };
+                } else {
+                    state.streak = 0;
+                }
+                state.last_period = Some(current);
+                Outcome {
+                    passed: state.streak >= i64::from(*count),
+                    observed: child_outcome.observed,
+                }
+            }
+        }
+    }
+
+    fn latest_values(&self) -> Vec<f64> {
+        self.rule
+            .sensor_ids
+            .iter()
+            .filter_map(|sensor_id| self.latest.get(sensor_id).copied())
+            .collect()
+    }
+
+    /// Debounce and clear hysteresis, as the controller's alarm engine applies them.
+    fn apply_timing(&mut self, should_fire: bool, now_secs: i64) -> bool {
+        let timing = &self.rule.timing;
+        if should_fire {
+            self.first_false_at = None;
+            if self.firing {
+                return true;
+            }
+            if timing.debounce_seconds <= 0 {
+                self.first_true_at = None;
+                return true;
+            }
+            let first_true = *self.first_true_at.get_or_insert(now_secs);
+            return now_secs - first_true >= timing.debounce_seconds;
+        }
+
+        self.first_true_at = None;
+        if !self.firing || timing.clear_hysteresis_seconds <= 0 {
+            self.first_false_at = None;
+            return false;
+        }
+        let first_false = *self.first_false_at.get_or_insert(now_secs);
+        now_secs - first_false < timing.clear_hysteresis_seconds
+    }
+}
+
+fn compare(value: f64, op: CompareOp, threshold: f64) -> bool {
+    match op {
+        CompareOp::Lt => value < threshold,
+        CompareOp::Lte => value <= threshold,
+        CompareOp::Gt => value > threshold,
+        CompareOp::Gte => value >= threshold,
+        CompareOp::Eq => (value - threshold).abs() <= f64::EPSILON,
+        CompareOp::Neq => (value - threshold).abs() > f64::EPSILON,
+    }
+}
+
+fn eval_values(values: Vec<f64>, mode: MatchMode, predicate: impl Fn(f64) -> bool) -> Outcome {
+    let observed = values.first().copied();
+    let passed = !values.is_empty()
+        && match mode {
+            MatchMode::All => values.iter().all(|value| predicate(*value)),
+            MatchMode::PerSensor | MatchMode::Any => values.iter().any(|value| predicate(*value)),
+        };
+    Outcome { passed, observed }
+}
+
+fn aggregate_values(points: &[f64], aggregate: AggregateOp) -> Option<f64> {
+    if points.is_empty() {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean = points.iter().sum::<f64>() / n;
+    Some(match aggregate {
+        AggregateOp::Avg => mean,
+        AggregateOp::Min => points.iter().copied().fold(f64::INFINITY, f64::min),
+        AggregateOp::Max => points.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        // Population stddev, like the controller's `stddev_pop`.
+        AggregateOp::Stddev => (points.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt(),
+    })
+}
+
+fn period_bucket(period: ConsecutivePeriod, now_secs: i64) -> i64 {
+    match period {
+        ConsecutivePeriod::Eval => now_secs,
+        ConsecutivePeriod::Hour => now_secs.div_euclid(3600),
+        ConsecutivePeriod::Day => now_secs.div_euclid(86_400),
+    }
+}
+
+#[derive(Debug, Default)]
+struct EngineState {
+    version: Option<String>,
+    targets: Vec<TargetState>,
+    by_sensor: HashMap<String, Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EdgeTargetStatus {
+    pub rule_id: i64,
+    pub name: String,
+    pub target_key: String,
+    pub firing: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EdgeRulesStatus {
+    pub version: Option<String>,
+    /// True while transitions are acted on (no upstream for at least `offline_after_seconds`).
+    pub acting: bool,
+    pub offline_after_seconds: u64,
+    pub disconnected_for_seconds: Option<u64>,
+    pub targets: Vec<EdgeTargetStatus>,
+}
+
+struct PendingTransition {
+    rule_id: i64,
+    event_sensor_id: String,
+    transition: Transition,
+    observed: Option<f64>,
+    occurred_at_ms: i64,
+    action: Option<EdgeAction>,
+}
+
+pub struct EdgeRules {
+    spool: SpoolHandle,
+    bundle_path: PathBuf,
+    offline_after: Duration,
+    output_command_url: String,
+    output_command_token: Option<String>,
+    http: reqwest::Client,
+    state: Mutex<EngineState>,
+    /// `None` while an upstream session is up.
+    disconnected_since: Mutex<Option<Instant>>,
+}
+
+impl EdgeRules {
+    /// Loads the last bundle received, if any. The link counts as down from start until the first
+    /// upstream accepts a connection.
+    pub fn load(config: &Config, spool: SpoolHandle) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(ACTION_TIMEOUT)
+            .build()
+            .context("failed to build HTTP client")?;
+        let rules = Self {
+            spool,
+            bundle_path: config.spool_dir.join(BUNDLE_FILE),
+            offline_after: config.edge_offline_after,
+            output_command_url: config.output_command_url.clone(),
+            output_command_token: config.output_command_token.clone(),
+            http,
+            state: Mutex::new(EngineState::default()),
+            disconnected_since: Mutex::new(Some(Instant::now())),
+        };
+        match std::fs::read(&rules.bundle_path) {
+            Ok(raw) => {
+                if let Err(err) = rules.install(&raw, false) {
+                    tracing::warn!(error=%err, "ignoring unreadable edge rule bundle");
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).context("failed to read edge rule bundle"),
+        }
+        Ok(rules)
+    }
+
+    /// Installs a bundle received over MQTT and persists it for the next start.
+    pub fn install_bundle(&self, payload: &[u8]) {
+        if let Err(err) = self.install(payload, true) {
+            tracing::warn!(error=%err, "rejected edge rule bundle");
+        }
+    }
+
+    fn install(&self, payload: &[u8], persist: bool) -> Result<()> {
+        let bundle: Bundle = serde_json::from_slice(payload).context("invalid edge rule bundle")?;
+        let mut state = self.state.lock().expect("edge rule state poisoned");
+        if state.version.as_deref() == Some(bundle.version.as_str()) {
+            return Ok(());
+        }
+        if persist {
+            write_atomic(&self.bundle_path, payload)?;
+        }
+
+        let mut previous: HashMap<String, TargetState> = std::mem::take(&mut state.targets)
+            .into_iter()
+            .map(|target| (target.rule.event_sensor_id.clone(), target))
+            .collect();
+        let mut targets = Vec::with_capacity(bundle.rules.len());
+        for raw in bundle.rules {
+            let rule: EdgeRule = match serde_json::from_value(raw) {
+                Ok(rule) => rule,
+                Err(err) => {
+                    tracing::warn!(error=%err, "skipping edge rule this node cannot evaluate");
+                    continue;
+                }
+            };
+            // An unchanged rule keeps its windows, streaks and firing state.
+            let target = match previous.remove(&rule.event_sensor_id) {
+                Some(existing) if existing.rule == rule => existing,
+                _ => TargetState::new(rule),
+            };
+            targets.push(target);
+        }
+
+        let mut by_sensor: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, target) in targets.iter().enumerate() {
+            for sensor_id in &target.rule.sensor_ids {
+                by_sensor.entry(sensor_id.clone()).or_default().push(index);
+            }
+        }
+        tracing::info!(
+            version=%bundle.version,
+            generated_at=?bundle.generated_at,
+            targets=targets.len(),
+            "installed edge alarm rules"
+        );
+        state.version = Some(bundle.version);
+        state.targets = targets;
+        state.by_sensor = by_sensor;
+        Ok(())
+    }
+
+    pub fn link_up(&self) {
+        *self
+            .disconnected_since
+            .lock()
+            .expect("edge link state poisoned") = None;
+    }
+
+    pub fn link_down(&self) {
+        self.disconnected_since
+            .lock()
+            .expect("edge link state poisoned")
+            .get_or_insert_with(Instant::now);
+    }
+
+    fn disconnected_for(&self) -> Option<Duration> {
+        self.disconnected_since
+            .lock()
+            .expect("edge link state poisoned")
+            .map(|since| since.elapsed())
+    }
+
+    fn acting(&self) -> bool {
+        self.disconnected_for()
+            .is_some_and(|elapsed| elapsed >= self.offline_after)
+    }
+
+    pub fn status(&self) -> EdgeRulesStatus {
+        let state = self.state.lock().expect("edge rule state poisoned");
+        EdgeRulesStatus {
+            version: state.version.clone(),
+            acting: self.acting(),
+            offline_after_seconds: self.offline_after.as_secs(),
+            disconnected_for_seconds: self.disconnected_for().map(|elapsed| elapsed.as_secs()),
+            targets: state
+                .targets
+                .iter()
+                .map(|target| EdgeTargetStatus {
+                    rule_id: target.rule.rule_id,
+                    name: target.rule.name.clone(),
+                    target_key: target.rule.target_key.clone(),
+                    firing: target.firing,
+                })
+                .collect(),
+        }
+    }
+
+    /// Feeds accepted samples to the rules that watch them and acts on any transitions.
+    /// Transitions during the `edge_offline_after` grace period are held, not dropped: once
+    /// the node acts alone, every target whose state differs from what the controller last saw
+    /// is spooled and acted on.
+    pub async fn observe(&self, samples: &[IncomingSample]) {
+        let now_ms = now_ms();
+        let connected = self.disconnected_for().is_none();
+        let acting = self.acting();
+        let pending = {
+            let mut state = self.state.lock().expect("edge rule state poisoned");
+            if state.targets.is_empty() {
+                return;
+            }
+            let mut touched: Vec<usize> = Vec::new();
+            for sample in samples {
+                let Some(indexes) = state.by_sensor.get(&sample.sensor_id).cloned() else {
+                    continue;
+                };
+                for index in indexes {
+                    state.targets[index].record(
+                        &sample.sensor_id,
+                        sample.timestamp_ms,
+                        sample.value,
+                    );
+                    if !touched.contains(&index) {
+                        touched.push(index);
+                    }
+                }
+            }
+            for index in touched {
+                state.targets[index].evaluate(now_ms);
+            }
+            if connected {
+                // The controller sees the same samples and acts on them itself.
+                for target in &mut state.targets {
+                    target.reported = target.firing;
+                }
+                return;
+            }
+            if !acting {
+                return;
+            }
+            let mut pending = Vec::new();
+            for target in &mut state.targets {
+                if target.firing == target.reported {
+                    continue;
+                }
+                target.reported = target.firing;
+                let (transition, action) = if target.firing {
+                    (Transition::Fired, target.rule.fire_action.clone())
+                } else {
+                    (Transition::Resolved, target.rule.clear_action.clone())
+                };
+                pending.push(PendingTransition {
+                    rule_id: target.rule.rule_id,
+                    event_sensor_id: target.rule.event_sensor_id.clone(),
+                    transition,
+                    observed: target.changed_observed,
+                    occurred_at_ms: target.changed_at_ms,
+                    action,
+                });
+            }
+            pending
+        };
+
+        for transition in pending {
+            tracing::warn!(
+                rule_id = transition.rule_id,
+                transition = ?transition.transition,
+                observed = ?transition.observed,
+                "edge alarm transition while controller unreachable"
+            );
+            let event = IncomingSample {
+                sensor_id: transition.event_sensor_id.clone(),
+                timestamp_ms: transition.occurred_at_ms,
+                value: transition.observed.unwrap_or(0.0),
+                quality: transition.transition.quality(),
+                time_quality: None,
+            };
+            if let Err(err) = self.spool.append_samples(vec![event]).await {
+                tracing::warn!(error=%err, rule_id = transition.rule_id, "failed to spool edge alarm event");
+            }
+            if let Some(action) = &transition.action {
+                if let Err(err) = self.send_action(transition.rule_id, action).await {
+                    tracing::warn!(
+                        error=%err,
+                        rule_id = transition.rule_id,
+                        output_id=%action.output_id,
+                        "edge alarm output action failed"
+                    );
+                }
+            }
+        }
+    }
+
+    async fn send_action(&self, rule_id: i64, action: &EdgeAction) -> Result<()> {
+        let url = self
+            .output_command_url
+            .replace("{output_id}", &action.output_id);
+        let mut request = self.http.post(&url).json(&json!({
+            "state": action.state,
+            "reason": "edge_alarm",
+            "request_id": format!("edge-alarm-{rule_id}-{}", now_ms()),
+        }));
+        if let Some(token) = self.output_command_token.as_deref() {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, contents).with_context(|| format!("failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spool::{AppendResult, SpoolCommand};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use tokio::sync::mpsc;
+
+    fn rule(condition: Condition, timing: Timing) -> EdgeRule {
+        EdgeRule {
+            rule_id: 7,
+            name: "tank high".to_string(),
+            severity: "critical".to_string(),
+            target_key: "sensor:tank-1".to_string(),
+            event_sensor_id: "edge-alarm.7.0123456789ab".to_string(),
+            sensor_ids: vec!["tank-1".to_string()],
+            match_mode: MatchMode::PerSensor,
+            condition,
+            timing,
+            fire_action: None,
+            clear_action: None,
+        }
+    }
+
+    #[test]
+    fn threshold_fires_after_debounce_and_clears_after_hysteresis() {
+        let mut target = TargetState::new(rule(
+            Condition::Threshold {
+                op: CompareOp::Gt,
+                value: 80.0,
+            },
+            Timing {
+                debounce_seconds: 10,
+                clear_hysteresis_seconds: 5,
+                eval_interval_seconds: 0,
+            },
+        ));
+        target.record("tank-1", 0, 85.0);
+        assert_eq!(target.evaluate(0), None);
+        target.record("tank-1", 9_000, 86.0);
+        assert_eq!(target.evaluate(9_000), None);
+        target.record("tank-1", 10_000, 87.0);
+        assert_eq!(
+            target.evaluate(10_000),
+            Some((Transition::Fired, Some(87.0)))
+        );
+        target.record("tank-1", 11_000, 70.0);
+        assert_eq!(target.evaluate(11_000), None);
+        target.record("tank-1", 16_000, 70.0);
+        assert_eq!(
+            target.evaluate(16_000),
+            Some((Transition::Resolved, Some(70.0)))
+        );
+    }
+
+    #[test]
+    fn rolling_window_and_consecutive_periods_match_the_controller() {
+        let mut target = TargetState::new(rule(
+            Condition::ConsecutivePeriods {
+                period: ConsecutivePeriod::Eval,
+                count: 2,
+                child: Box::new(Condition::RollingWindow {
+                    window_seconds: 60,
+                    aggregate: AggregateOp::Avg,
+                    op: CompareOp::Gte,
+                    value: 10.0,
+                }),
+            },
+            Timing::default(),
+        ));
+        target.record("tank-1", 0, 30.0);
+        // Average 30 passes, but the streak is only 1.
+        assert_eq!(target.evaluate(0), None);
+        target.record("tank-1", 30_000, 0.0);
+        // Average of 30 and 0 is 15: second passing evaluation.
+        assert_eq!(
+            target.evaluate(30_000),
+            Some((Transition::Fired, Some(15.0)))
+        );
+        // 30 has left the window; the average is 0 and the streak resets.
+        target.record("tank-1", 90_000, 0.0);
+        assert_eq!(
+            target.evaluate(90_000),
+            Some((Transition::Resolved, Some(0.0)))
+        );
+        assert!(target.windows["tank-1"].iter().all(|(ts, _)| *ts >= 30_000));
+    }
+
+    /// Engine with a spool that records what it is asked to append.
+    fn engine(
+        dir: &Path,
+        disconnected_for: Option<Duration>,
+    ) -> (EdgeRules, Arc<Mutex<Vec<IncomingSample>>>) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let spooled = Arc::new(Mutex::new(Vec::new()));
+        let recorded = spooled.clone();
+        tokio::spawn(async move {
+            while let Some(command) = rx.recv().await {
+                if let SpoolCommand::AppendSamples {
+                    samples,
+                    respond_to,
+                } = command
+                {
+                    let accepted = samples.len() as u64;
+                    recorded.lock().unwrap().extend(samples);
+                    let _ = respond_to.send(Ok(AppendResult { accepted }));
+                }
+            }
+        });
+        let rules = EdgeRules {
+            spool: SpoolHandle::new(tx),
+            bundle_path: dir.join(BUNDLE_FILE),
+            offline_after: Duration::from_secs(60),
+            output_command_url: "http://127.0.0.1:9/v1/local/outputs/{output_id}/command"
+                .to_string(),
+            output_command_token: None,
+            http: reqwest::Client::new(),
+            state: Mutex::new(EngineState::default()),
+            disconnected_since: Mutex::new(
+                disconnected_for.map(|elapsed| Instant::now() - elapsed),
+            ),
+        };
+        (rules, spooled)
+    }
+
+    fn sample(sensor_id: &str, value: f64) -> IncomingSample {
+        IncomingSample {
+            sensor_id: sensor_id.to_string(),
+            timestamp_ms: now_ms(),
+            value,
+            quality: 0,
+            time_quality: None,
+        }
+    }
+
+    fn bundle(version: &str, threshold: f64) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "version": version,
+            "rules": [
+                {"rule_id": 1, "name": "a", "target_key": "sensor:a", "event_sensor_id": "edge-alarm.1.a",
+                 "sensor_ids": ["a"], "condition": {"type": "threshold", "op": "gt", "value": 1.0}},
+                {"rule_id": 2, "name": "b", "target_key": "sensor:b", "event_sensor_id": "edge-alarm.2.b",
+                 "sensor_ids": ["b"], "condition": {"type": "offline", "missing_for_seconds": 60}},
+                {"rule_id": 3, "name": "c", "target_key": "sensor:c", "event_sensor_id": "edge-alarm.3.c",
+                 "sensor_ids": ["c"], "condition": {"type": "threshold", "op": "gt", "value": threshold}}
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn firing(rules: &EdgeRules) -> HashMap<i64, bool> {
+        rules
+            .status()
+            .targets
+            .into_iter()
+            .map(|target| (target.rule_id, target.firing))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn install_skips_unparsable_rules_and_keeps_unchanged_state() {
+        let dir = TempDir::new().unwrap();
+        let (rules, _) = engine(dir.path(), None);
+        rules.install(&bundle("v1", 1.0), true).unwrap();
+        assert!(dir.path().join(BUNDLE_FILE).exists());
+        rules.observe(&[sample("a", 5.0), sample("c", 5.0)]).await;
+        assert_eq!(firing(&rules), HashMap::from([(1, true), (3, true)]));
+
+        // Rule 1 is unchanged and stays firing; rule 3's threshold moved, so it starts over.
+        rules.install(&bundle("v2", 10.0), true).unwrap();
+        assert_eq!(rules.status().version.as_deref(), Some("v2"));
+        assert_eq!(firing(&rules), HashMap::from([(1, true), (3, false)]));
+
+        assert_eq!(
+            transition_label("edge-alarm.1.a", QUALITY_FIRED),
+            Some("fired")
+        );
+        assert_eq!(transition_label("tank-1", QUALITY_FIRED), None);
+    }
+
+    #[tokio::test]
+    async fn fire_during_grace_period_is_acted_on_once_offline() {
+        let dir = TempDir::new().unwrap();
+        let (rules, spooled) = engine(dir.path(), Some(Duration::from_secs(10)));
+        rules.install(&bundle("v1", 1.0), false).unwrap();
+
+        // Link down but still inside `offline_after`: nothing is spooled yet.
+        rules.observe(&[sample("a", 5.0)]).await;
+        assert!(spooled.lock().unwrap().is_empty());
+        let fired_at = rules.state.lock().unwrap().targets[0].changed_at_ms;
+
+        // The target is already firing, so this evaluation has no transition of its own.
+        *rules.disconnected_since.lock().unwrap() = Some(Instant::now() - Duration::from_secs(61));
+        rules.observe(&[sample("a", 6.0)]).await;
+        {
+            let spooled = spooled.lock().unwrap();
+            assert_eq!(spooled.len(), 1);
+            assert_eq!(spooled[0].sensor_id, "edge-alarm.1.a");
+            assert_eq!(spooled[0].quality, QUALITY_FIRED);
+            assert_eq!(spooled[0].timestamp_ms, fired_at);
+            assert_eq!(spooled[0].value, 5.0);
+        }
+
+        // Reported once; later samples do not repeat it.
+        rules.observe(&[sample("a", 7.0)]).await;
+        assert_eq!(spooled.lock().unwrap().len(), 1);
+    }
+}
@@ -1,13 +1,24 @@
+use crate::edge_rules::{EdgeRules, EdgeRulesStatus};
+use crate::ingest::{Ingest, IngestStats, Transport};
+use crate::line_protocol::Precision;
+use crate::remote_config::{RemoteConfig, RemoteConfigReport};
 use crate::spool::{IncomingSample, SpoolHandle};
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::routing::{get, post};
 use axum::{Json, Router};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Line errors echoed back in a partial-write response.
+const MAX_REPORTED_LINE_ERRORS: usize = 10;
 
 #[derive(Clone)]
 pub struct HttpState {
     pub spool: SpoolHandle,
+    pub ingest: Arc<Ingest>,
+    pub edge: Arc<EdgeRules>,
+    pub remote: Arc<RemoteConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,19 +50,84 @@ async fn post_samples(
 ) -> Result<Json<SamplesResponse>, (StatusCode, String)> {
     let result = state
         .spool
-        .append_samples(payload.samples)
+        .append_samples(payload.samples.clone())
         .await
         .map_err(|err| (StatusCode::SERVICE_UNAVAILABLE, err.to_string()))?;
+    state.edge.observe(&payload.samples).await;
     Ok(Json(SamplesResponse {
         accepted: result.accepted,
     }))
 }
 
+#[derive(Debug, Deserialize)]
+struct WriteQuery {
+    precision: Option<String>,
+}
+
+/// InfluxDB 1.x `/write` and 2.x `/api/v2/write`. Database, bucket and org are ignored; valid
+/// lines are spooled even when others fail, as InfluxDB does for a partial write.
+async fn post_line_protocol(
+    State(state): State<HttpState>,
+    Query(query): Query<WriteQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| !value.trim().eq_ignore_ascii_case("identity"))
+    {
+        return Err((
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "compressed line protocol is not supported; send identity encoding".to_string(),
+        ));
+    }
+    let precision = Precision::parse(query.precision.as_deref())
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    let (samples, errors) = state
+        .ingest
+        .line_protocol_samples(Transport::InfluxHttp, &body, precision);
+    state
+        .ingest
+        .submit(Transport::InfluxHttp, samples)
+        .await
+        .map_err(|err| (StatusCode::SERVICE_UNAVAILABLE, err.to_string()))?;
+    if !errors.is_empty() {
+        let shown: Vec<&str> = errors
+            .iter()
+            .take(MAX_REPORTED_LINE_ERRORS)
+            .map(String::as_str)
+            .collect();
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("partial write: {} bad lines: {}", errors.len(), shown.join("; ")),
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_ingest_stats(State(state): State<HttpState>) -> Json<IngestStats> {
+    Json(state.ingest.stats())
+}
+
+async fn get_edge_rules(State(state): State<HttpState>) -> Json<EdgeRulesStatus> {
+    Json(state.edge.status())
+}
+
+async fn get_forwarder_config(State(state): State<HttpState>) -> Json<RemoteConfigReport> {
+    Json(state.remote.report())
+}
+
 pub fn router(state: HttpState) -> Router {
     Router::new()
         .route("/healthz", get(healthz))
         .route("/v1/status", get(get_status))
         .route("/v1/samples", post(post_samples))
+        .route("/v1/ingest", get(get_ingest_stats))
+        .route("/v1/edge-rules", get(get_edge_rules))
+        .route("/v1/forwarder-config", get(get_forwarder_config))
+        .route("/write", post(post_line_protocol))
+        .route("/api/v2/write", post(post_line_protocol))
         .with_state(state)
 }
 
@@ -0,0 +1,588 @@
+//! Local ingest transports besides `POST /v1/samples`: InfluxDB line protocol over HTTP and UDP,
+//! StatsD gauges over UDP, and a unix socket of `<topic> <payload>` lines. Every transport maps
+//! its own keys to sensor ids through the sensor-id map and appends through the spool.
+
+use crate::config::Config;
+use crate::edge_rules::EdgeRules;
+use crate::line_protocol::{self, Precision};
+use crate::spool::{IncomingSample, SpoolHandle};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::{UdpSocket, UnixListener, UnixStream};
+use tokio::task::JoinHandle;
+
+/// Samples buffered from one socket connection before they are appended.
+const SOCKET_BATCH: usize = 256;
+const MAX_DATAGRAM: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    InfluxHttp,
+    InfluxUdp,
+    Statsd,
+    Socket,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unmapped {
+    /// Use the transport key itself (sanitized) as the sensor id.
+    #[default]
+    Passthrough,
+    Drop,
+}
+
+/// `NODE_FORWARDER_SENSOR_ID_MAP`: `{"unmapped": "passthrough"|"drop", "sensors": {key: id}}`.
+#[derive(Debug, Default, Deserialize)]
+pub struct SensorIdMap {
+    #[serde(default)]
+    pub unmapped: Unmapped,
+    #[serde(default)]
+    pub sensors: HashMap<String, String>,
+}
+
+impl SensorIdMap {
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let raw = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        let map: Self = serde_json::from_str(&raw)
+            .with_context(|| format!("parse sensor id map {}", path.display()))?;
+        for (key, sensor_id) in &map.sensors {
+            if sanitize_sensor_id(sensor_id).as_deref() != Some(sensor_id.as_str()) {
+                return Err(anyhow!(
+                    "sensor id {sensor_id:?} for {key:?} is not a valid sensor id"
+                ));
+            }
+        }
+        Ok(map)
+    }
+
+    /// Resolves the first mapped key; `keys` are ordered most specific first.
+    pub fn resolve(&self, keys: &[String]) -> Option<String> {
+        if let Some(sensor_id) = keys.iter().find_map(|key| self.sensors.get(key)) {
+            return Some(sensor_id.clone());
+        }
+        match self.unmapped {
+            Unmapped::Passthrough => keys.first().and_then(|key| sanitize_sensor_id(key)),
+            Unmapped::Drop => None,
+        }
+    }
+}
+
+/// Sensor ids become MQTT topic levels, so only `[A-Za-z0-9._-]` survives.
+fn sanitize_sensor_id(raw: &str) -> Option<String> {
+    let mut out = String::with_capacity(raw.len());
+    for ch in raw.chars() {
+        if ch.is_ascii_alphanumeric() || matches!(ch, '.' | '_' | '-') {
+            out.push(ch);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let trimmed = out.trim_matches('-');
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+#[derive(Debug, Default)]
+struct TransportCounters {
+    accepted: AtomicU64,
+    rejected: AtomicU64,
+    unmapped: AtomicU64,
+}
+
+impl TransportCounters {
+    fn snapshot(&self) -> TransportStats {
+        TransportStats {
+            accepted: self.accepted.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            unmapped: self.unmapped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TransportStats {
+    pub accepted: u64,
+    /// Lines or packets that could not be parsed, and StatsD metrics that are not gauges.
+    pub rejected: u64,
+    /// Values dropped because the sensor-id map has no entry and `unmapped` is `drop`.
+    pub unmapped: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestStats {
+    pub influx_http: TransportStats,
+    pub influx_udp: TransportStats,
+    pub statsd: TransportStats,
+    pub socket: TransportStats,
+}
+
+pub struct Ingest {
+    spool: SpoolHandle,
+    edge: Arc<EdgeRules>,
+    sensor_map: SensorIdMap,
+    influx_http: TransportCounters,
+    influx_udp: TransportCounters,
+    statsd: TransportCounters,
+    socket: TransportCounters,
+}
+
+impl Ingest {
+    pub fn new(spool: SpoolHandle, edge: Arc<EdgeRules>, sensor_map: SensorIdMap) -> Self {
+        Self {
+            spool,
+            edge,
+            sensor_map,
+            influx_http: TransportCounters::default(),
+            influx_udp: TransportCounters::default(),
+            statsd: TransportCounters::default(),
+            socket: TransportCounters::default(),
+        }
+    }
+
+    fn counters(&self, transport: Transport) -> &TransportCounters {
+        match transport {
+            Transport::InfluxHttp => &self.influx_http,
+            Transport::InfluxUdp => &self.influx_udp,
+            Transport::Statsd => &self.statsd,
+            Transport::Socket => &self.socket,
+        }
+    }
+
+    pub fn stats(&self) -> IngestStats {
+        IngestStats {
+            influx_http: self.influx_http.snapshot(),
+            influx_udp: self.influx_udp.snapshot(),
+            statsd: self.statsd.snapshot(),
+            socket: self.socket.snapshot(),
+        }
+    }
+
+    fn reject(&self, transport: Transport) {
+        self.counters(transport)
+            .rejected
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn sample(
+        &self,
+        transport: Transport,
+        keys: &[String],
+        value: f64,
+        timestamp_ms: Option<i64>,
+        quality: i16,
+    ) -> Option<IncomingSample> {
+        let Some(sensor_id) = self.sensor_map.resolve(keys) else {
+            self.counters(transport)
+                .unmapped
+                .fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+        Some(IncomingSample {
+            sensor_id,
+            timestamp_ms: timestamp_ms.unwrap_or_else(|| Utc::now().timestamp_millis()),
+            value,
+            quality,
+            time_quality: None,
+        })
+    }
+
+    /// Maps every numeric field of every line; returns the samples and one message per bad line.
+    pub fn line_protocol_samples(
+        &self,
+        transport: Transport,
+        body: &str,
+        precision: Precision,
+    ) -> (Vec<IncomingSample>, Vec<String>) {
+        let mut samples = Vec::new();
+        let mut errors = Vec::new();
+        for (idx, line) in body.lines().enumerate() {
+            match line_protocol::parse_line(line, precision) {
+                Ok(Some(point)) => {
+                    for (field, value) in &point.fields {
+                        let keys = point.field_keys(field);
+                        samples.extend(self.sample(
+                            transport,
+                            &keys,
+                            *value,
+                            point.timestamp_ms,
+                            0,
+                        ));
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    self.reject(transport);
+                    errors.push(format!("line {}: {err}", idx + 1));
+                }
+            }
+        }
+        (samples, errors)
+    }
+
+    pub async fn submit(&self, transport: Transport, samples: Vec<IncomingSample>) -> Result<u64> {
+        if samples.is_empty() {
+            return Ok(0);
+        }
+        let result = self.spool.append_samples(samples.clone()).await?;
+        self.counters(transport)
+            .accepted
+            .fetch_add(result.accepted, Ordering::Relaxed);
+        self.edge.observe(&samples).await;
+        Ok(result.accepted)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Gauge {
+    name: String,
+    value: f64,
+    /// `+n` / `-n` adjust the previous value instead of replacing it.
+    relative: bool,
+}
+
+/// Parses `name:value|type[|@rate][|#tags]`. Returns `Ok(None)` for metric types other than
+/// gauges, which have no single value to spool.
+fn parse_statsd_line(line: &str) -> Result<Option<Gauge>> {
+    let (name, rest) = line
+        .split_once(':')
+        .ok_or_else(|| anyhow!("missing ':' in {line:?}"))?;
+    let mut sections = rest.split('|');
+    let raw_value = sections.next().unwrap_or_default().trim();
+    let kind = sections
+        .next()
+        .ok_or_else(|| anyhow!("missing metric type in {line:?}"))?
+        .trim();
+    if kind != "g" {
+        return Ok(None);
+    }
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("missing metric name in {line:?}"));
+    }
+    let value: f64 = raw_value
+        .parse()
+        .ok()
+        .filter(|value: &f64| value.is_finite())
+        .ok_or_else(|| anyhow!("invalid gauge value {raw_value:?}"))?;
+    Ok(Some(Gauge {
+        name: name.to_string(),
+        value,
+        relative: raw_value.starts_with(['+', '-']),
+    }))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SocketMessage {
+    topic: String,
+    value: f64,
+    timestamp_ms: Option<i64>,
+    quality: i16,
+}
+
+#[derive(Debug, Deserialize)]
+struct SocketPayload {
+    value: f64,
+    #[serde(default)]
+    timestamp_ms: Option<i64>,
+    #[serde(default)]
+    quality: i16,
+}
+
+/// Parses `<topic> <payload>` where the payload is a bare number or
+/// `{"value": .., "timestamp_ms": .., "quality": ..}`.
+fn parse_socket_line(line: &str) -> Result<Option<SocketMessage>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let (topic, payload) = line
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| anyhow!("missing payload after topic {line:?}"))?;
+    let payload = payload.trim();
+    let parsed = if payload.starts_with('{') {
+        serde_json::from_str::<SocketPayload>(payload).context("invalid JSON payload")?
+    } else {
+        SocketPayload {
+            value: payload
+                .parse()
+                .map_err(|_| anyhow!("invalid value {payload:?}"))?,
+            timestamp_ms: None,
+            quality: 0,
+        }
+    };
+    if !parsed.value.is_finite() {
+        return Err(anyhow!("value must be finite"));
+    }
+    Ok(Some(SocketMessage {
+        topic: topic.to_string(),
+        value: parsed.value,
+        timestamp_ms: parsed.timestamp_ms,
+        quality: parsed.quality,
+    }))
+}
+
+/// Binds every configured transport up front so a bad address fails startup, then serves them.
+pub async fn spawn_transports(config: &Config, ingest: Arc<Ingest>) -> Result<Vec<JoinHandle<()>>> {
+    let mut handles = Vec::new();
+
+    if let Some(bind) = &config.influx_udp_bind {
+        let socket = UdpSocket::bind(bind)
+            .await
+            .with_context(|| format!("bind line protocol UDP {bind}"))?;
+        tracing::info!(bind=%bind, "line protocol UDP listening");
+        handles.push(tokio::spawn(serve_influx_udp(socket, ingest.clone())));
+    }
+
+    if let Some(bind) = &config.statsd_bind {
+        let socket = UdpSocket::bind(bind)
+            .await
+            .with_context(|| format!("bind StatsD UDP {bind}"))?;
+        tracing::info!(bind=%bind, "StatsD UDP listening");
+        handles.push(tokio::spawn(serve_statsd(socket, ingest.clone())));
+    }
+
+    if let Some(path) = &config.ingest_socket_path {
+        // Only clear a stale socket; never delete some other file at the configured path.
+        if let Ok(meta) = fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() {
+                return Err(anyhow!("{} exists and is not a socket", path.display()));
+            }
+            fs::remove_file(path).with_context(|| format!("remove stale {}", path.display()))?;
+        }
+        let listener =
+            UnixListener::bind(path).with_context(|| format!("bind {}", path.display()))?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o660))
+            .with_context(|| format!("chmod {}", path.display()))?;
+        tracing::info!(path=%path.display(), "ingest socket listening");
+        handles.push(tokio::spawn(serve_socket(listener, ingest)));
+    }
+
+    Ok(handles)
+}
+
+async fn serve_influx_udp(socket: UdpSocket, ingest: Arc<Ingest>) {
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    loop {
+        let len = match socket.recv_from(&mut buf).await {
+            Ok((len, _)) => len,
+            Err(err) => {
+                tracing::warn!(error=%err, "line protocol UDP receive failed");
+                continue;
+            }
+        };
+        let Ok(body) = std::str::from_utf8(&buf[..len]) else {
+            ingest.reject(Transport::InfluxUdp);
+            continue;
+        };
+        let (samples, errors) =
+            ingest.line_protocol_samples(Transport::InfluxUdp, body, Precision::Nanoseconds);
+        for error in errors {
+            tracing::debug!(error=%error, "rejected line protocol datagram line");
+        }
+        if let Err(err) = ingest.submit(Transport::InfluxUdp, samples).await {
+            tracing::warn!(error=%err, "failed to spool line protocol samples");
+        }
+    }
+}
+
+async fn serve_statsd(socket: UdpSocket, ingest: Arc<Ingest>) {
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    let mut gauges: HashMap<String, f64> = HashMap::new();
+    loop {
+        let len = match socket.recv_from(&mut buf).await {
+            Ok((len, _)) => len,
+            Err(err) => {
+                tracing::warn!(error=%err, "StatsD receive failed");
+                continue;
+            }
+        };
+        let Ok(body) = std::str::from_utf8(&buf[..len]) else {
+            ingest.reject(Transport::Statsd);
+            continue;
+        };
+        let mut samples = Vec::new();
+        for line in body.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let gauge = match parse_statsd_line(line) {
+                Ok(Some(gauge)) => gauge,
+                Ok(None) => {
+                    ingest.reject(Transport::Statsd);
+                    continue;
+                }
+                Err(err) => {
+                    tracing::debug!(error=%err, "rejected StatsD line");
+                    ingest.reject(Transport::Statsd);
+                    continue;
+                }
+            };
+            let previous = gauges.entry(gauge.name.clone()).or_insert(0.0);
+            *previous = if gauge.relative {
+                *previous + gauge.value
+            } else {
+                gauge.value
+            };
+            let value = *previous;
+            samples.extend(ingest.sample(Transport::Statsd, &[gauge.name], value, None, 0));
+        }
+        if let Err(err) = ingest.submit(Transport::Statsd, samples).await {
+            tracing::warn!(error=%err, "failed to spool StatsD gauges");
+        }
+    }
+}
+
+async fn serve_socket(listener: UnixListener, ingest: Arc<Ingest>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve_socket_connection(stream, ingest.clone()));
+            }
+            Err(err) => tracing::warn!(error=%err, "ingest socket accept failed"),
+        }
+    }
+}
+
+async fn serve_socket_connection(stream: UnixStream, ingest: Arc<Ingest>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    let mut batch = Vec::with_capacity(SOCKET_BATCH);
+    loop {
+        line.clear();
+        let read = match reader.read_line(&mut line).await {
+            Ok(read) => read,
+            Err(err) => {
+                tracing::debug!(error=%err, "ingest socket read failed");
+                0
+            }
+        };
+        if read > 0 {
+            match parse_socket_line(&line) {
+                Ok(Some(message)) => batch.extend(ingest.sample(
+                    Transport::Socket,
+                    &[message.topic],
+                    message.value,
+                    message.timestamp_ms,
+                    message.quality,
+                )),
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::debug!(error=%err, "rejected ingest socket line");
+                    ingest.reject(Transport::Socket);
+                }
+            }
+        }
+        // Flush when the batch is full or nothing else is already buffered.
+        if read == 0 || batch.len() >= SOCKET_BATCH || reader.buffer().is_empty() {
+            let samples = std::mem::take(&mut batch);
+            if let Err(err) = ingest.submit(Transport::Socket, samples).await {
+                tracing::warn!(error=%err, "failed to spool ingest socket samples");
+            }
+        }
+        if read == 0 {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sensor_map_prefers_specific_keys_and_sanitizes_passthrough() {
+        let mut map = SensorIdMap::default();
+        map.sensors.insert(
+            "tank,site=north.level".to_string(),
+            "tank-level-1".to_string(),
+        );
+        map.sensors
+            .insert("cpu.temp".to_string(), "pi-cpu-temp".to_string());
+
+        let keys = vec![
+            "tank,site=north.level".to_string(),
+            "tank.level".to_string(),
+        ];
+        assert_eq!(map.resolve(&keys).as_deref(), Some("tank-level-1"));
+        let keys = vec!["cpu,host=pi.temp".to_string(), "cpu.temp".to_string()];
+        assert_eq!(map.resolve(&keys).as_deref(), Some("pi-cpu-temp"));
+        let keys = vec!["sensors/pump 1/amps".to_string()];
+        assert_eq!(map.resolve(&keys).as_deref(), Some("sensors-pump-1-amps"));
+
+        map.unmapped = Unmapped::Drop;
+        assert_eq!(map.resolve(&keys), None);
+    }
+
+    #[test]
+    fn sensor_map_file_rejects_ids_that_cannot_be_topics() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("map.json");
+        fs::write(
+            &path,
+            r#"{"unmapped": "drop", "sensors": {"a.b": "tank/level"}}"#,
+        )
+        .unwrap();
+        assert!(SensorIdMap::load(Some(&path)).is_err());
+        fs::write(
+            &path,
+            r#"{"unmapped": "drop", "sensors": {"a.b": "tank-level"}}"#,
+        )
+        .unwrap();
+        let map = SensorIdMap::load(Some(&path)).unwrap();
+        assert_eq!(map.unmapped, Unmapped::Drop);
+    }
+
+    #[test]
+    fn parses_statsd_gauges_and_skips_other_types() {
+        assert_eq!(
+            parse_statsd_line("pump.amps:4.5|g|#site:north").unwrap(),
+            Some(Gauge {
+                name: "pump.amps".to_string(),
+                value: 4.5,
+                relative: false
+            })
+        );
+        assert_eq!(
+            parse_statsd_line("tank.level:-2|g").unwrap().unwrap(),
+            Gauge {
+                name: "tank.level".to_string(),
+                value: -2.0,
+                relative: true
+            }
+        );
+        assert_eq!(parse_statsd_line("requests:1|c").unwrap(), None);
+        assert!(parse_statsd_line("broken").is_err());
+        assert!(parse_statsd_line("x:abc|g").is_err());
+    }
+
+    #[test]
+    fn parses_socket_lines() {
+        assert_eq!(
+            parse_socket_line("sensors/tank/level 1.25\n").unwrap(),
+            Some(SocketMessage {
+                topic: "sensors/tank/level".to_string(),
+                value: 1.25,
+                timestamp_ms: None,
+                quality: 0
+            })
+        );
+        let message = parse_socket_line(
+            r#"sensors/pump {"value": 3, "timestamp_ms": 1700000000000, "quality": 2}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(message.timestamp_ms, Some(1_700_000_000_000));
+        assert_eq!(message.quality, 2);
+        assert_eq!(parse_socket_line("  ").unwrap(), None);
+        assert!(parse_socket_line("sensors/pump").is_err());
+        assert!(parse_socket_line("sensors/pump on").is_err());
+    }
+}
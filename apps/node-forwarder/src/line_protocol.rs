@@ -0,0 +1,259 @@
+//! InfluxDB line protocol: `measurement[,tag=v...] field=v[,field=v...] [timestamp]`.
+
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+    Minutes,
+    Hours,
+}
+
+impl Precision {
+    /// Accepts both the 1.x (`n`, `u`, `ms`, `s`, `m`, `h`) and 2.x (`ns`, `us`) spellings.
+    pub fn parse(raw: Option<&str>) -> Result<Self> {
+        match raw.map(str::trim).unwrap_or("ns") {
+            "" | "n" | "ns" => Ok(Self::Nanoseconds),
+            "u" | "us" => Ok(Self::Microseconds),
+            "ms" => Ok(Self::Milliseconds),
+            "s" => Ok(Self::Seconds),
+            "m" => Ok(Self::Minutes),
+            "h" => Ok(Self::Hours),
+            other => Err(anyhow!("unsupported precision {other:?}")),
+        }
+    }
+
+    fn to_millis(self, timestamp: i64) -> i64 {
+        match self {
+            Self::Nanoseconds => timestamp.div_euclid(1_000_000),
+            Self::Microseconds => timestamp.div_euclid(1_000),
+            Self::Milliseconds => timestamp,
+            Self::Seconds => timestamp.saturating_mul(1_000),
+            Self::Minutes => timestamp.saturating_mul(60_000),
+            Self::Hours => timestamp.saturating_mul(3_600_000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    pub tags: Vec<(String, String)>,
+    /// Numeric and boolean fields; string fields cannot become samples and are left out.
+    pub fields: Vec<(String, f64)>,
+    pub timestamp_ms: Option<i64>,
+}
+
+impl Point {
+    /// Lookup keys for one field, most specific first: `measurement,tag=v,....field` with tags
+    /// sorted by key, then `measurement.field`.
+    pub fn field_keys(&self, field: &str) -> Vec<String> {
+        let mut keys = Vec::with_capacity(2);
+        if !self.tags.is_empty() {
+            let mut tags = self.tags.clone();
+            tags.sort();
+            let tags: Vec<String> = tags.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            keys.push(format!("{},{}.{field}", self.measurement, tags.join(",")));
+        }
+        keys.push(format!("{}.{field}", self.measurement));
+        keys
+    }
+}
+
+/// Parses one line. Returns `Ok(None)` for blank lines and comments.
+pub fn parse_line(line: &str, precision: Precision) -> Result<Option<Point>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let series_end =
+        find_unescaped(line, 0, ' ', false).ok_or_else(|| anyhow!("missing field set"))?;
+    let series = &line[..series_end];
+    let rest = line[series_end..].trim_start();
+    let fields_end = find_unescaped(rest, 0, ' ', true).unwrap_or(rest.len());
+    let field_set = &rest[..fields_end];
+    let timestamp = rest[fields_end..].trim();
+
+    let mut series_parts = split_unescaped(series, ',', false).into_iter();
+    let measurement = unescape(series_parts.next().unwrap_or_default());
+    if measurement.is_empty() {
+        return Err(anyhow!("missing measurement"));
+    }
+    let mut tags = Vec::new();
+    for part in series_parts {
+        let (key, value) = split_pair(part).ok_or_else(|| anyhow!("invalid tag {part:?}"))?;
+        tags.push((unescape(key), unescape(value)));
+    }
+
+    if field_set.is_empty() {
+        return Err(anyhow!("missing field set"));
+    }
+    let mut fields = Vec::new();
+    for part in split_unescaped(field_set, ',', true) {
+        let (key, value) = split_pair(part).ok_or_else(|| anyhow!("invalid field {part:?}"))?;
+        if let Some(value) = parse_field_value(value)? {
+            fields.push((unescape(key), value));
+        }
+    }
+
+    let timestamp_ms = if timestamp.is_empty() {
+        None
+    } else {
+        let raw: i64 = timestamp
+            .parse()
+            .map_err(|_| anyhow!("invalid timestamp {timestamp:?}"))?;
+        Some(precision.to_millis(raw))
+    };
+
+    Ok(Some(Point {
+        measurement,
+        tags,
+        fields,
+        timestamp_ms,
+    }))
+}
+
+fn parse_field_value(raw: &str) -> Result<Option<f64>> {
+    if raw.starts_with('"') {
+        if raw.len() < 2 || !raw.ends_with('"') {
+            return Err(anyhow!("unterminated string field"));
+        }
+        return Ok(None);
+    }
+    match raw {
+        "t" | "T" | "true" | "True" | "TRUE" => return Ok(Some(1.0)),
+        "f" | "F" | "false" | "False" | "FALSE" => return Ok(Some(0.0)),
+        _ => {}
+    }
+    let parsed = if let Some(int) = raw.strip_suffix('i') {
+        int.parse::<i64>().map(|v| v as f64).ok()
+    } else if let Some(uint) = raw.strip_suffix('u') {
+        uint.parse::<u64>().map(|v| v as f64).ok()
+    } else {
+        raw.parse::<f64>().ok().filter(|v| v.is_finite())
+    };
+    parsed
+        .map(Some)
+        .ok_or_else(|| anyhow!("invalid field value {raw:?}"))
+}
+
+/// Byte offset of the first `sep` at or after `from` that is not backslash-escaped (and, when
+/// `quotes` is set, not inside a double-quoted string).
+fn find_unescaped(raw: &str, from: usize, sep: char, quotes: bool) -> Option<usize> {
+    let mut escaped = false;
+    let mut quoted = false;
+    for (idx, ch) in raw[from..].char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' => escaped = true,
+            '"' if quotes => quoted = !quoted,
+            c if c == sep && !quoted => return Some(from + idx),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn split_unescaped(raw: &str, sep: char, quotes: bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while let Some(idx) = find_unescaped(raw, start, sep, quotes) {
+        parts.push(&raw[start..idx]);
+        start = idx + sep.len_utf8();
+    }
+    parts.push(&raw[start..]);
+    parts
+}
+
+fn split_pair(raw: &str) -> Option<(&str, &str)> {
+    let idx = find_unescaped(raw, 0, '=', false)?;
+    let (key, value) = (&raw[..idx], &raw[idx + 1..]);
+    (!key.is_empty() && !value.is_empty()).then_some((key, value))
+}
+
+fn unescape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            if let Some(&next) = chars.peek() {
+                if matches!(next, ',' | '=' | ' ' | '"' | '\\') {
+                    out.push(next);
+                    chars.next();
+                    continue;
+                }
+            }
+        }
+        out.push(ch);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tags_fields_and_timestamp() {
+        let point = parse_line(
+            r#"tank,site=north,id=t\ 1 level=1.5,pump=t,count=3i,note="a b, c" 1700000000123000000"#,
+            Precision::Nanoseconds,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(point.measurement, "tank");
+        assert_eq!(
+            point.tags,
+            vec![
+                ("site".to_string(), "north".to_string()),
+                ("id".to_string(), "t 1".to_string())
+            ]
+        );
+        assert_eq!(
+            point.fields,
+            vec![
+                ("level".to_string(), 1.5),
+                ("pump".to_string(), 1.0),
+                ("count".to_string(), 3.0)
+            ]
+        );
+        assert_eq!(point.timestamp_ms, Some(1_700_000_000_123));
+        assert_eq!(
+            point.field_keys("level"),
+            vec!["tank,id=t 1,site=north.level", "tank.level"]
+        );
+    }
+
+    #[test]
+    fn timestamp_is_optional_and_scaled_by_precision() {
+        let point = parse_line("cpu temp=41", Precision::Seconds)
+            .unwrap()
+            .unwrap();
+        assert_eq!(point.timestamp_ms, None);
+        let point = parse_line(
+            "cpu temp=41 1700000000",
+            Precision::parse(Some("s")).unwrap(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(point.timestamp_ms, Some(1_700_000_000_000));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(parse_line("# comment", Precision::Nanoseconds)
+            .unwrap()
+            .is_none());
+        assert!(parse_line("cpu", Precision::Nanoseconds).is_err());
+        assert!(parse_line("cpu temp=abc", Precision::Nanoseconds).is_err());
+        assert!(parse_line("cpu temp=1 soon", Precision::Nanoseconds).is_err());
+        assert!(Precision::parse(Some("d")).is_err());
+    }
+}
@@ -1,10 +1,19 @@
+mod clock;
 mod config;
+mod edge_rules;
 mod http;
+mod ingest;
+mod line_protocol;
 mod mqtt;
+mod remote_config;
+mod replay_policy;
+mod segment;
 mod spool;
+mod spool_cli;
 
 use crate::config::Config;
 use anyhow::Result;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 fn init_tracing() -> Result<()> {
@@ -20,24 +29,49 @@ fn init_tracing() -> Result<()> {
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("spool") {
+        return spool_cli::run(&args[1..]);
+    }
+
     let config = Config::from_env()?;
     init_tracing()?;
 
     let (publish_tx, publish_rx) = mpsc::channel::<spool::PublishSample>(10_000);
     let (loss_tx, loss_rx) = mpsc::channel::<spool::LossEvent>(256);
     let spool = spool::spawn_spool_thread(config.clone(), publish_tx, loss_tx)?;
+    let edge = Arc::new(edge_rules::EdgeRules::load(&config, spool.clone())?);
+    let remote = Arc::new(remote_config::RemoteConfig::load(&config, spool.clone()).await?);
 
     let mqtt_config = config.clone();
     let mqtt_spool = spool.clone();
+    let mqtt_edge = edge.clone();
+    let mqtt_remote = remote.clone();
     let mqtt_handle = tokio::spawn(async move {
-        if let Err(err) =
-            mqtt::run_mqtt_forwarder(mqtt_config, mqtt_spool, publish_rx, loss_rx).await
+        if let Err(err) = mqtt::run_mqtt_forwarder(
+            mqtt_config,
+            mqtt_spool,
+            mqtt_edge,
+            mqtt_remote,
+            publish_rx,
+            loss_rx,
+        )
+        .await
         {
             tracing::error!(error=%err, "mqtt forwarder exited");
         }
     });
 
-    let app = http::router(http::HttpState { spool: spool.clone() });
+    let sensor_map = ingest::SensorIdMap::load(config.sensor_id_map_path.as_deref())?;
+    let ingest = Arc::new(ingest::Ingest::new(spool.clone(), edge.clone(), sensor_map));
+    let _ingest_handles = ingest::spawn_transports(&config, ingest.clone()).await?;
+
+    let app = http::router(http::HttpState {
+        spool: spool.clone(),
+        ingest,
+        edge,
+        remote,
+    });
     let listener = tokio::net::TcpListener::bind(&config.http_bind).await?;
     tracing::info!(bind=%config.http_bind, "node-forwarder HTTP listening");
     let http_handle = tokio::spawn(async move {
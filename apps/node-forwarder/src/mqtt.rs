@@ -1,25 +1,26 @@
-use crate::config::Config;
-use crate::spool::{LossEvent, LossRange, PublishSample, SpoolHandle, TimeQuality};
+use crate::clock::{self, AppliedRepair, ClockAnchors};
+use crate::config::{Config, Upstream};
+use crate::edge_rules::{transition_label, EdgeRules};
+use crate::remote_config::RemoteConfig;
+use crate::replay_policy::{self, ReplayOrder, ReplayPolicy};
+use crate::spool::{LossEvent, LossKind, LossRange, PublishSample, SpoolHandle, TimeQuality};
+use crate::segment::SegmentReader;
 use anyhow::{anyhow, Context, Result};
-use crc32c::crc32c;
-use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+use chrono::{Local, Utc};
+use rumqttc::{AsyncClient, ConnectReturnCode, Event, Incoming, MqttOptions, QoS};
 use serde::Deserialize;
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
-use tokio::time::{sleep, Sleep};
+use tokio::time::{sleep, timeout, Sleep};
 use uuid::Uuid;
 
-const SEGMENT_HEADER_LEN: u64 = 64;
-const FRAME_HEADER_LEN: usize = 8;
-const MAX_FRAME_LEN: usize = 1024 * 1024;
-const SAMPLE_RECORD_LEN: usize = 40;
-
 #[derive(Debug, Deserialize)]
 struct AckPayload {
     stream_id: String,
@@ -29,27 +30,115 @@ struct AckPayload {
 #[derive(Debug, Deserialize)]
 struct SpoolStateDisk {
     stream_id: String,
+    acked_seq: u64,
     losses: Vec<LossRange>,
+    #[serde(default)]
+    upstream_acks: BTreeMap<String, u64>,
+}
+
+/// What the fast poller hands back to the connection loop.
+#[derive(Debug)]
+enum PollerEvent {
+    /// An ACK moved; replay should refresh its view of the spool.
+    AckProgress,
+    /// A forwarder config document arrived on `<prefix>/<node>/forwarder-config`.
+    ForwarderConfig(Vec<u8>),
+}
+
+/// How long a health probe waits for the broker's CONNACK.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Picks the upstream to publish to. Fails over to the next entry after `failover_after`
+/// consecutive sessions end, and back to a higher-priority entry once probes have seen it
+/// healthy for the whole failback window.
+#[derive(Debug)]
+struct UpstreamSelector {
+    count: usize,
+    active: usize,
+    failures: u32,
+    healthy_since: Vec<Option<Instant>>,
+}
+
+impl UpstreamSelector {
+    fn new(count: usize) -> Self {
+        Self {
+            count: count.max(1),
+            active: 0,
+            failures: 0,
+            healthy_since: vec![None; count.max(1)],
+        }
+    }
+
+    fn on_connected(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Returns true when the session end moved the selector to another upstream.
+    fn on_session_end(&mut self, failover_after: u32) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        if self.count < 2 || self.failures < failover_after.max(1) {
+            return false;
+        }
+        self.switch_to((self.active + 1) % self.count);
+        true
+    }
+
+    fn on_probe(&mut self, index: usize, healthy: bool, now: Instant) {
+        // Results that arrive after a switch may be for upstreams no longer above us.
+        if index >= self.active {
+            return;
+        }
+        let slot = &mut self.healthy_since[index];
+        *slot = if healthy { Some(slot.unwrap_or(now)) } else { None };
+    }
+
+    fn failback_target(&self, now: Instant, failback_after: Duration) -> Option<usize> {
+        self.healthy_since[..self.active]
+            .iter()
+            .position(|since| since.is_some_and(|since| now.duration_since(since) >= failback_after))
+    }
+
+    fn switch_to(&mut self, index: usize) {
+        self.active = index;
+        self.failures = 0;
+        self.healthy_since.iter_mut().for_each(|since| *since = None);
+    }
 }
 
 pub async fn run_mqtt_forwarder(
     config: Config,
     spool: SpoolHandle,
+    edge: Arc<EdgeRules>,
+    remote: Arc<RemoteConfig>,
     live_rx: mpsc::Receiver<PublishSample>,
     loss_rx: mpsc::Receiver<LossEvent>,
 ) -> Result<()> {
     let ack_topic = format!("{}/{}/ack", config.mqtt_topic_prefix, config.node_id);
     let loss_topic = format!("{}/{}/loss", config.mqtt_topic_prefix, config.node_id);
+    let edge_rules_topic = format!("{}/{}/edge-rules", config.mqtt_topic_prefix, config.node_id);
+    let forwarder_config_topic = format!(
+        "{}/{}/forwarder-config",
+        config.mqtt_topic_prefix, config.node_id
+    );
+    let forwarder_config_state_topic = format!("{forwarder_config_topic}/state");
 
     let mut live_rx = live_rx;
     let mut loss_rx = loss_rx;
 
+    let mut selector = UpstreamSelector::new(config.upstreams.len());
+    let (probe_tx, mut probe_rx) = mpsc::unbounded_channel::<(usize, bool)>();
+
+    let mut policy = ReplayPolicy::load(&config);
+    let (rtt_tx, mut rtt_rx) = mpsc::unbounded_channel::<(String, Option<f64>)>();
+    let mut rtt_upstream: Option<String> = None;
+
     loop {
-        let mut fast_opts = mqtt_options(&config, &config.mqtt_client_id);
+        let upstream = config.upstreams[selector.active].clone();
+        let mut fast_opts = mqtt_options(&upstream, &config.mqtt_client_id);
         fast_opts.set_keep_alive(Duration::from_secs(15));
 
         let replay_id = format!("{}-replay", config.mqtt_client_id);
-        let mut replay_opts = mqtt_options(&config, &replay_id);
+        let mut replay_opts = mqtt_options(&upstream, &replay_id);
         replay_opts.set_keep_alive(Duration::from_secs(15));
 
         let (fast_client, fast_eventloop) = AsyncClient::new(fast_opts, 256);
@@ -60,20 +149,45 @@ pub async fn run_mqtt_forwarder(
             sleep(Duration::from_secs(2)).await;
             continue;
         }
+        if let Err(err) = fast_client
+            .subscribe(edge_rules_topic.clone(), QoS::AtLeastOnce)
+            .await
+        {
+            tracing::warn!(error=%err, "failed to subscribe to edge rules topic; retrying");
+            sleep(Duration::from_secs(2)).await;
+            continue;
+        }
+        if let Err(err) = fast_client
+            .subscribe(forwarder_config_topic.clone(), QoS::AtLeastOnce)
+            .await
+        {
+            tracing::warn!(error=%err, "failed to subscribe to forwarder config topic; retrying");
+            sleep(Duration::from_secs(2)).await;
+            continue;
+        }
 
-        tracing::info!("MQTT connected; publishing live telemetry + replay");
+        tracing::info!(upstream=%upstream.name, "MQTT connected; publishing live telemetry + replay");
 
         // Best-effort: publish any pending loss ranges on connect.
-        publish_pending_losses_from_state(&fast_client, &config, &loss_topic).await;
+        publish_pending_losses_from_state(&fast_client, &config, &upstream, &loss_topic).await;
+        publish_config_report(&fast_client, &forwarder_config_state_topic, &remote).await;
 
-        let mut replay = ReplayState::new(config.clone())?;
+        let mut replay = ReplayState::new(config.clone(), upstream.name.clone())?;
+        if rtt_upstream.as_deref() != Some(upstream.name.as_str()) {
+            policy.reset_rtt();
+            rtt_upstream = Some(upstream.name.clone());
+        }
 
-        let (ack_notify_tx, mut ack_notify_rx) = mpsc::unbounded_channel::<()>();
+        let connected = Arc::new(AtomicBool::new(false));
+        let (poller_tx, mut poller_rx) = mpsc::unbounded_channel::<PollerEvent>();
         let mut fast_poller = spawn_fast_poller(
             fast_eventloop,
             spool.clone(),
+            upstream.name.clone(),
             ack_topic.clone(),
-            ack_notify_tx,
+            poller_tx,
+            connected.clone(),
+            edge.clone(),
         );
         let mut replay_poller = spawn_replay_poller(replay_eventloop);
 
@@ -83,7 +197,13 @@ pub async fn run_mqtt_forwarder(
         let mut loss_republish = tokio::time::interval(Duration::from_secs(30));
         loss_republish.tick().await;
 
+        let mut probe_tick = tokio::time::interval(config.upstream_probe_interval);
+        probe_tick.tick().await;
+
+        let mut rtt_tick = tokio::time::interval(policy.rtt_probe_interval());
+
         let mut last_err: Option<anyhow::Error> = None;
+        let mut failback: Option<usize> = None;
 
         loop {
             tokio::select! {
@@ -105,12 +225,19 @@ pub async fn run_mqtt_forwarder(
                     break;
                 }
 
-                maybe = ack_notify_rx.recv() => {
-                    if maybe.is_none() {
-                        break;
+                maybe = poller_rx.recv() => {
+                    match maybe {
+                        None => break,
+                        Some(PollerEvent::AckProgress) => {
+                            // Hint to the replay loop that ACK progressed; it will refresh status on next tick.
+                            replay_sleep.as_mut().reset(tokio::time::Instant::now());
+                        }
+                        Some(PollerEvent::ForwarderConfig(payload)) => {
+                            if remote.install(&payload).await {
+                                publish_config_report(&fast_client, &forwarder_config_state_topic, &remote).await;
+                            }
+                        }
                     }
-                    // Hint to the replay loop that ACK progressed; it will refresh status on next tick.
-                    replay_sleep.as_mut().reset(tokio::time::Instant::now());
                 }
 
                 maybe = loss_rx.recv() => {
@@ -122,17 +249,61 @@ pub async fn run_mqtt_forwarder(
 
                 maybe = live_rx.recv() => {
                     let Some(sample) = maybe else { break; };
-                    if let Err(err) = publish_sample(&fast_client, &config, sample, false).await {
-                        tracing::debug!(error=%err, "failed to publish live sample");
+                    if !replay.live_allowed(&sample, &mut policy) {
+                        // Already spooled; replay delivers it in seq order.
+                        continue;
+                    }
+                    let last_seq = sample.last_seq();
+                    match publish_sample(&fast_client, &config, sample, false).await {
+                        Ok(bytes) => replay.on_live_published(last_seq, bytes, &mut policy),
+                        Err(err) => tracing::debug!(error=%err, "failed to publish live sample"),
                     }
                 }
 
                 _ = loss_republish.tick() => {
-                    publish_pending_losses_from_state(&fast_client, &config, &loss_topic).await;
+                    publish_pending_losses_from_state(&fast_client, &config, &upstream, &loss_topic).await;
+                }
+
+                _ = probe_tick.tick() => {
+                    for index in 0..selector.active {
+                        let candidate = config.upstreams[index].clone();
+                        let client_id = format!("{}-probe", config.mqtt_client_id);
+                        let probe_tx = probe_tx.clone();
+                        tokio::spawn(async move {
+                            let healthy = probe_upstream(&candidate, &client_id).await;
+                            let _ = probe_tx.send((index, healthy));
+                        });
+                    }
+                }
+
+                _ = rtt_tick.tick(), if policy.adaptive() => {
+                    let rtt_tx = rtt_tx.clone();
+                    let name = upstream.name.clone();
+                    let (host, port) = (upstream.host.clone(), upstream.port);
+                    tokio::spawn(async move {
+                        let rtt = replay_policy::probe_rtt_ms(&host, port).await;
+                        let _ = rtt_tx.send((name, rtt));
+                    });
+                }
+
+                Some((name, rtt)) = rtt_rx.recv() => {
+                    // Probes still in flight from before a failover measured another broker.
+                    if name == upstream.name {
+                        policy.observe_rtt(rtt);
+                    }
+                }
+
+                Some((index, healthy)) = probe_rx.recv() => {
+                    let now = Instant::now();
+                    selector.on_probe(index, healthy, now);
+                    if let Some(target) = selector.failback_target(now, config.failback_after) {
+                        failback = Some(target);
+                        break;
+                    }
                 }
 
                 _ = &mut replay_sleep => {
-                    match replay.step(&replay_client, &spool).await {
+                    match replay.step(&replay_client, &spool, &mut policy).await {
                         Ok(next_delay) => {
                             replay_sleep.as_mut().reset(tokio::time::Instant::now() + next_delay);
                         }
@@ -147,8 +318,31 @@ pub async fn run_mqtt_forwarder(
 
         fast_poller.abort();
         replay_poller.abort();
+        edge.link_down();
+
+        if let Some(target) = failback {
+            let _ = fast_client.disconnect().await;
+            let _ = replay_client.disconnect().await;
+            tracing::info!(
+                from=%upstream.name,
+                to=%config.upstreams[target].name,
+                "higher-priority MQTT upstream healthy; failing back"
+            );
+            selector.switch_to(target);
+            continue;
+        }
 
-        tracing::warn!(error=?last_err, "MQTT connection loop restarting");
+        if connected.load(Ordering::Relaxed) {
+            selector.on_connected();
+        }
+        tracing::warn!(upstream=%upstream.name, error=?last_err, "MQTT connection loop restarting");
+        if selector.on_session_end(config.failover_after) {
+            tracing::warn!(
+                from=%upstream.name,
+                to=%config.upstreams[selector.active].name,
+                "MQTT upstream unreachable; failing over"
+            );
+        }
         sleep(Duration::from_secs(1)).await;
     }
 }
@@ -156,13 +350,31 @@ pub async fn run_mqtt_forwarder(
 fn spawn_fast_poller(
     mut eventloop: rumqttc::EventLoop,
     spool: SpoolHandle,
+    upstream: String,
     ack_topic: String,
-    notify: mpsc::UnboundedSender<()>,
+    events: mpsc::UnboundedSender<PollerEvent>,
+    connected: Arc<AtomicBool>,
+    edge: Arc<EdgeRules>,
 ) -> JoinHandle<Result<()>> {
     tokio::spawn(async move {
         loop {
             match eventloop.poll().await {
+                Ok(Event::Incoming(Incoming::ConnAck(ack))) => {
+                    if ack.code == ConnectReturnCode::Success {
+                        connected.store(true, Ordering::Relaxed);
+                        spool.set_active_upstream(&upstream);
+                        edge.link_up();
+                    }
+                }
                 Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                    if publish.topic.ends_with("/edge-rules") {
+                        edge.install_bundle(&publish.payload);
+                        continue;
+                    }
+                    if publish.topic.ends_with("/forwarder-config") {
+                        let _ = events.send(PollerEvent::ForwarderConfig(publish.payload.to_vec()));
+                        continue;
+                    }
                     if publish.topic != ack_topic {
                         continue;
                     }
@@ -173,8 +385,8 @@ fn spawn_fast_poller(
                     let Ok(stream_id) = Uuid::parse_str(ack.stream_id.trim()) else {
                         continue;
                     };
-                    spool.update_ack(stream_id, ack.acked_seq);
-                    let _ = notify.send(());
+                    spool.update_ack(&upstream, stream_id, ack.acked_seq);
+                    let _ = events.send(PollerEvent::AckProgress);
                 }
                 Ok(_) => {}
                 Err(err) => return Err(err.into()),
@@ -191,17 +403,43 @@ fn spawn_replay_poller(mut eventloop: rumqttc::EventLoop) -> JoinHandle<Result<(
     })
 }
 
-fn mqtt_options(config: &Config, client_id: &str) -> MqttOptions {
-    let mut mqttoptions = MqttOptions::new(client_id, config.mqtt_host.clone(), config.mqtt_port);
-    if let Some(username) = &config.mqtt_username {
+fn mqtt_options(upstream: &Upstream, client_id: &str) -> MqttOptions {
+    let mut mqttoptions = MqttOptions::new(client_id, upstream.host.clone(), upstream.port);
+    if let Some(username) = &upstream.username {
         mqttoptions.set_credentials(
             username.clone(),
-            config.mqtt_password.clone().unwrap_or_default(),
+            upstream.password.clone().unwrap_or_default(),
         );
     }
     mqttoptions
 }
 
+/// Connects with a throwaway clean session and reports whether the broker accepted it.
+async fn probe_upstream(upstream: &Upstream, client_id: &str) -> bool {
+    let mut opts = mqtt_options(upstream, client_id);
+    opts.set_keep_alive(Duration::from_secs(5));
+    opts.set_clean_session(true);
+    let (client, mut eventloop) = AsyncClient::new(opts, 4);
+    let healthy = timeout(PROBE_TIMEOUT, async {
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Incoming::ConnAck(ack))) => {
+                    return ack.code == ConnectReturnCode::Success;
+                }
+                Ok(_) => {}
+                Err(_) => return false,
+            }
+        }
+    })
+    .await
+    .unwrap_or(false);
+    if healthy {
+        let _ = client.disconnect().await;
+        let _ = timeout(Duration::from_millis(500), eventloop.poll()).await;
+    }
+    healthy
+}
+
 async fn publish_sample(
     client: &AsyncClient,
     config: &Config,
@@ -230,7 +468,7 @@ fn encode_telemetry_payload(sample: &PublishSample, backfill: bool) -> Result<Ve
         TimeQuality::Unknown => "unknown",
     };
 
-    let payload = json!({
+    let mut payload = json!({
         "timestamp": sample.timestamp_ms,
         "value": sample.value,
         "quality": sample.quality,
@@ -240,6 +478,29 @@ fn encode_telemetry_payload(sample: &PublishSample, backfill: bool) -> Result<Ve
         "time_quality": time_quality,
         "mono_ms": sample.monotonic_ms,
     });
+    if !sample.merged_seqs.is_empty() {
+        // An average written by spool compaction; it stands in for every listed seq.
+        payload["downsampled"] = json!(true);
+        payload["samples"] = json!(sample.merged_seqs.len() + 1);
+        payload["merged_seqs"] = json!(sample.merged_seqs);
+    }
+    if let Some(transition) = transition_label(&sample.sensor_id, sample.quality) {
+        payload["edge_transition"] = json!(transition);
+    }
+    match &sample.repair {
+        None => {}
+        Some(AppliedRepair::Rebased {
+            reported_timestamp_ms,
+        }) => {
+            payload["time_quality"] = json!("corrected");
+            payload["reported_timestamp"] = json!(reported_timestamp_ms);
+        }
+        Some(AppliedRepair::Uncorrectable { reason, boot_id }) => {
+            payload["time_quality"] = json!("uncorrectable");
+            payload["time_repair_error"] = json!(reason.as_str());
+            payload["boot_id"] = json!(boot_id);
+        }
+    }
 
     Ok(serde_json::to_vec(&payload)?)
 }
@@ -250,7 +511,12 @@ async fn publish_loss(client: &AsyncClient, topic: &str, event: &LossEvent) -> R
         "start_seq": event.range.start_seq,
         "end_seq": event.range.end_seq,
         "dropped_at": event.range.dropped_at,
-        "reason": "spool_cap_drop_oldest_segment",
+        "kind": event.range.kind,
+        "reason": match event.range.kind {
+            LossKind::Dropped => "spool_cap_drop_oldest_segment",
+            LossKind::Downsampled => "spool_downsampled",
+            LossKind::Released => "spool_released_after_other_upstream_ack",
+        },
     });
     client
         .publish(topic, QoS::AtLeastOnce, false, serde_json::to_vec(&payload)?)
@@ -258,14 +524,38 @@ async fn publish_loss(client: &AsyncClient, topic: &str, event: &LossEvent) -> R
     Ok(())
 }
 
-async fn publish_pending_losses_from_state(client: &AsyncClient, config: &Config, topic: &str) {
+/// Retained so the controller sees the applied version even if it starts after the node.
+async fn publish_config_report(client: &AsyncClient, topic: &str, remote: &RemoteConfig) {
+    let payload = match serde_json::to_vec(&remote.report()) {
+        Ok(payload) => payload,
+        Err(err) => {
+            tracing::debug!(error=%err, "failed to encode forwarder config report");
+            return;
+        }
+    };
+    if let Err(err) = client.publish(topic, QoS::AtLeastOnce, true, payload).await {
+        tracing::debug!(error=%err, "failed to publish forwarder config report");
+    }
+}
+
+async fn publish_pending_losses_from_state(
+    client: &AsyncClient,
+    config: &Config,
+    upstream: &Upstream,
+    topic: &str,
+) {
     let Ok(state) = load_spool_state(&config.spool_dir) else {
         return;
     };
     let Ok(stream_id) = Uuid::parse_str(state.stream_id.trim()) else {
         return;
     };
-    for range in state.losses {
+    // Seqs another upstream ACKed are gone from the spool; tell this one to skip them.
+    let mut ranges = state.losses;
+    if let Some(range) = released_range(&state.upstream_acks, &upstream.name, state.acked_seq) {
+        ranges.push(range);
+    }
+    for range in ranges {
         let event = LossEvent { stream_id, range };
         if publish_loss(client, topic, &event).await.is_err() {
             break;
@@ -273,6 +563,20 @@ async fn publish_pending_losses_from_state(client: &AsyncClient, config: &Config
     }
 }
 
+fn released_range(
+    upstream_acks: &BTreeMap<String, u64>,
+    upstream: &str,
+    acked_seq: u64,
+) -> Option<LossRange> {
+    let upstream_ack = upstream_acks.get(upstream).copied().unwrap_or(0);
+    (upstream_ack < acked_seq).then(|| LossRange {
+        start_seq: upstream_ack + 1,
+        end_seq: acked_seq,
+        dropped_at: Utc::now().to_rfc3339(),
+        kind: LossKind::Released,
+    })
+}
+
 fn load_spool_state(spool_dir: &Path) -> Result<SpoolStateDisk> {
     let path = spool_dir.join("state.json");
     let raw = fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
@@ -298,6 +602,21 @@ impl TokenBucket {
         }
     }
 
+    /// Changes the rate in place, keeping the tokens already earned up to the new capacity.
+    fn set_rate(&mut self, rate_per_sec: u32) {
+        self.refill();
+        let rate_per_sec = rate_per_sec.max(1) as f64;
+        self.rate_per_sec = rate_per_sec;
+        self.capacity = rate_per_sec;
+        self.tokens = self.tokens.min(rate_per_sec);
+    }
+
+    /// Charges traffic that did not go through the bucket, down to one second of debt.
+    fn debit(&mut self, cost: f64) {
+        self.refill();
+        self.tokens = (self.tokens - cost).max(-self.capacity);
+    }
+
     fn refill(&mut self) {
         let now = Instant::now();
         let elapsed = now.duration_since(self.last_refill).as_secs_f64();
@@ -329,9 +648,14 @@ impl TokenBucket {
     }
 }
 
+fn scaled_rate(rate_per_sec: u32, scale: f64) -> u32 {
+    ((rate_per_sec as f64) * scale).round().max(1.0) as u32
+}
+
 #[derive(Debug)]
 struct ReplayState {
     config: Config,
+    upstream: String,
     stream_id: Option<Uuid>,
     acked_seq: u64,
     next_seq: u64,
@@ -339,18 +663,20 @@ struct ReplayState {
     byte_bucket: TokenBucket,
     cursor: Option<ReplayCursor>,
     pending: Option<PublishSample>,
+    anchors: Option<ClockAnchors>,
     last_state_refresh: Instant,
     last_ack_progress: Instant,
     last_stall_reset: Instant,
 }
 
 impl ReplayState {
-    fn new(config: Config) -> Result<Self> {
+    fn new(config: Config, upstream: String) -> Result<Self> {
         let replay_msgs_per_sec = config.replay_msgs_per_sec;
         let replay_bytes_per_sec = config.replay_bytes_per_sec;
         let now = Instant::now();
         Ok(Self {
             config,
+            upstream,
             stream_id: None,
             acked_seq: 0,
             next_seq: 1,
@@ -358,49 +684,108 @@ impl ReplayState {
             byte_bucket: TokenBucket::new(replay_bytes_per_sec),
             cursor: None,
             pending: None,
+            anchors: None,
             last_state_refresh: now - Duration::from_secs(60),
             last_ack_progress: now,
             last_stall_reset: now,
         })
     }
 
-    async fn step(&mut self, client: &AsyncClient, spool: &SpoolHandle) -> Result<Duration> {
+    /// Whether a live sample may go out on the fast connection now. Under `backlog_first` it
+    /// waits until replay has caught up to it and the policy allows sending.
+    fn live_allowed(&self, sample: &PublishSample, policy: &mut ReplayPolicy) -> bool {
+        match policy.order() {
+            ReplayOrder::LiveFirst => true,
+            ReplayOrder::BacklogFirst => {
+                self.stream_id == Some(sample.stream_id)
+                    && sample.seq <= self.next_seq
+                    && policy.pause_reason(0, Local::now()).is_none()
+            }
+        }
+    }
+
+    fn on_live_published(&mut self, last_seq: u64, bytes: usize, policy: &mut ReplayPolicy) {
+        policy.record(bytes, Local::now());
+        match policy.order() {
+            // Live traffic shares the link, so it comes out of the replay allowance.
+            ReplayOrder::LiveFirst => {
+                self.msg_bucket.debit(1.0);
+                self.byte_bucket.debit(bytes as f64);
+            }
+            // Replay was caught up; skip what just went out live instead of sending it twice.
+            ReplayOrder::BacklogFirst => {
+                self.next_seq = self.next_seq.max(last_seq.saturating_add(1));
+            }
+        }
+    }
+
+    async fn step(
+        &mut self,
+        client: &AsyncClient,
+        spool: &SpoolHandle,
+        policy: &mut ReplayPolicy,
+    ) -> Result<Duration> {
         let mut backlog_samples = None;
         if self.last_state_refresh.elapsed() >= Duration::from_secs(1) || self.cursor.is_none() {
             let status = spool.status().await?;
             let stream_id = Uuid::parse_str(status.stream_id.trim()).context("invalid stream_id")?;
+            // Resume from what this upstream has ACKed; anything below the spool's truncation
+            // point was released by another upstream and is covered by a loss range.
+            let acked_seq = status
+                .upstream_acks
+                .get(&self.upstream)
+                .copied()
+                .unwrap_or(0)
+                .max(status.acked_seq);
             if self.stream_id != Some(stream_id) {
                 self.stream_id = Some(stream_id);
-                self.acked_seq = status.acked_seq;
-                self.next_seq = status.acked_seq.saturating_add(1);
+                self.acked_seq = acked_seq;
+                self.next_seq = acked_seq.saturating_add(1);
                 self.cursor = Some(ReplayCursor::new(self.config.spool_dir.clone(), stream_id)?);
                 self.pending = None;
                 self.last_ack_progress = Instant::now();
                 self.last_stall_reset = Instant::now();
-            } else if status.acked_seq > self.acked_seq {
-                self.acked_seq = status.acked_seq;
-                self.next_seq = self.next_seq.max(status.acked_seq.saturating_add(1));
+            } else if acked_seq > self.acked_seq {
+                self.acked_seq = acked_seq;
+                self.next_seq = self.next_seq.max(acked_seq.saturating_add(1));
                 self.last_ack_progress = Instant::now();
                 if self
                     .pending
                     .as_ref()
-                    .is_some_and(|pending| pending.seq <= self.acked_seq)
+                    .is_some_and(|pending| pending.last_seq() <= self.acked_seq)
                 {
                     self.pending = None;
                 }
             }
             backlog_samples = Some(status.backlog_samples);
             self.last_state_refresh = Instant::now();
+            // Replay rates can be changed by a forwarder config pushed from the controller, and
+            // are scaled down while the broker RTT is elevated.
+            let scale = policy.rate_scale();
+            let replay_msgs_per_sec = scaled_rate(status.replay_msgs_per_sec, scale);
+            let replay_bytes_per_sec = scaled_rate(status.replay_bytes_per_sec, scale);
+            if replay_msgs_per_sec as f64 != self.msg_bucket.rate_per_sec {
+                self.msg_bucket.set_rate(replay_msgs_per_sec);
+            }
+            if replay_bytes_per_sec as f64 != self.byte_bucket.rate_per_sec {
+                self.byte_bucket.set_rate(replay_bytes_per_sec);
+            }
+            spool.set_replay_policy(policy.status(Local::now()));
+            policy.persist_if_due();
+            match ClockAnchors::load(&self.config.spool_dir, clock::current_boot_id()) {
+                Ok(anchors) => self.anchors = Some(anchors),
+                Err(err) => tracing::debug!(error=%err, "failed to load clock anchors"),
+            }
 
             // If ACK is not moving but we still have backlog, it's likely we skipped a seq (e.g.,
             // rate-limit deferral) or the controller missed a commit. Reset our cursor to the first
             // unacked seq and try again so the spool can always make forward progress.
             if status.backlog_samples > 0
-                && self.next_seq > status.acked_seq.saturating_add(1)
+                && self.next_seq > acked_seq.saturating_add(1)
                 && self.last_ack_progress.elapsed() >= Duration::from_secs(5)
                 && self.last_stall_reset.elapsed() >= Duration::from_secs(5)
             {
-                let desired = status.acked_seq.saturating_add(1);
+                let desired = acked_seq.saturating_add(1);
                 if let Some(cursor) = self.cursor.as_mut() {
                     let _ = cursor.seek_to_seq(desired);
                 }
@@ -422,7 +807,7 @@ impl ReplayState {
         }
 
         if let Some(pending) = self.pending.as_ref() {
-            if pending.seq <= self.acked_seq {
+            if pending.last_seq() <= self.acked_seq {
                 self.pending = None;
             }
         }
@@ -432,7 +817,7 @@ impl ReplayState {
             let Some(sample) = maybe else {
                 return Ok(Duration::from_millis(200));
             };
-            if sample.seq <= self.acked_seq {
+            if sample.last_seq() <= self.acked_seq {
                 self.next_seq = self.acked_seq.saturating_add(1);
                 return Ok(Duration::from_millis(0));
             }
@@ -449,10 +834,31 @@ impl ReplayState {
             }
         }
 
+        let Some(anchors) = self.anchors.as_ref() else {
+            return Ok(Duration::from_millis(250));
+        };
+        let repair = anchors.repair(
+            sample.seq,
+            sample.monotonic_ms,
+            sample.time_quality,
+            clock::monotonic_ms(),
+            self.config.unsynced_hold.as_millis() as u64,
+        );
+        // Unsynced samples from the current boot wait here until the clock syncs (or the hold
+        // window expires); ACK cannot advance past them anyway.
+        let Some(sample) = sample.clone().with_time_repair(repair) else {
+            return Ok(Duration::from_secs(1));
+        };
+
         let topic = telemetry_topic(&self.config, &sample.sensor_id);
         let payload = encode_telemetry_payload(&sample, true)?;
         let payload_len = payload.len();
 
+        if let Some(reason) = policy.pause_reason(payload_len, Local::now()) {
+            tracing::trace!(?reason, seq = sample.seq, "replay paused by policy");
+            return Ok(Duration::from_secs(1));
+        }
+
         if !self.msg_bucket.try_take(1.0) || !self.byte_bucket.try_take(payload_len as f64) {
             let delay = self
                 .msg_bucket
@@ -465,7 +871,8 @@ impl ReplayState {
             .publish(topic, QoS::AtLeastOnce, false, payload)
             .await?;
         let bytes = payload_len;
-        let published_seq = sample.seq;
+        policy.record(bytes, Local::now());
+        let published_seq = sample.last_seq();
         let published_stream_id = sample.stream_id;
         tracing::trace!(seq = published_seq, bytes, backlog_samples, "published replay sample");
 
@@ -494,7 +901,7 @@ struct ReplayCursor {
     sensor_by_index: HashMap<u32, String>,
     segments: Vec<SegmentInfo>,
     segment_index: usize,
-    file: Option<fs::File>,
+    reader: Option<SegmentReader>,
     last_seq: Option<u64>,
     last_refresh: Instant,
 }
@@ -508,7 +915,7 @@ impl ReplayCursor {
             sensor_by_index,
             segments: Vec::new(),
             segment_index: 0,
-            file: None,
+            reader: None,
             last_seq: None,
             last_refresh: Instant::now() - Duration::from_secs(60),
         })
@@ -520,30 +927,16 @@ impl ReplayCursor {
             self.last_refresh = Instant::now();
         }
 
-        if self.file.is_none() {
+        if self.reader.is_none() {
             self.seek_to_seq(min_seq)?;
         }
 
         loop {
-            let Some(file) = self.file.as_mut() else {
+            let Some(reader) = self.reader.as_mut() else {
                 return Ok(None);
             };
-            let Some(payload) = read_next_frame_payload(file)? else {
-                // End of this segment for now.
-                let is_open = self
-                    .segments
-                    .get(self.segment_index)
-                    .map(|seg| seg.is_open)
-                    .unwrap_or(false);
-                if is_open {
-                    return Ok(None);
-                }
-                self.advance_segment()?;
-                continue;
-            };
-
-            let Some(record) = decode_sample_record(&payload) else {
-                // Corrupt tail: stop this segment.
+            // `None` is the end of this segment for now, or a corrupt tail.
+            let Some(record) = reader.next_entry()? else {
                 let is_open = self
                     .segments
                     .get(self.segment_index)
@@ -556,8 +949,8 @@ impl ReplayCursor {
                 continue;
             };
 
-            self.last_seq = Some(record.seq);
-            if record.seq < min_seq {
+            self.last_seq = Some(record.last_seq());
+            if record.last_seq() < min_seq {
                 continue;
             }
 
@@ -581,6 +974,8 @@ impl ReplayCursor {
                 stream_id: self.stream_id,
                 time_quality: record.time_quality,
                 monotonic_ms: record.monotonic_ms,
+                repair: None,
+                merged_seqs: record.merged_seqs,
             }));
         }
     }
@@ -648,7 +1043,7 @@ impl ReplayCursor {
             .find(|(_, seg)| seg.start_seq <= seq && seg.end_seq.map(|end| seq <= end).unwrap_or(true))
             .or_else(|| self.segments.iter().enumerate().find(|(_, seg)| seg.start_seq >= seq))
         else {
-            self.file = None;
+            self.reader = None;
             return Ok(());
         };
 
@@ -658,22 +1053,16 @@ impl ReplayCursor {
 
     fn open_current_segment(&mut self) -> Result<()> {
         let Some(seg) = self.segments.get(self.segment_index).cloned() else {
-            self.file = None;
+            self.reader = None;
             return Ok(());
         };
-        let mut file = fs::File::open(&seg.path).with_context(|| format!("open {}", seg.path.display()))?;
-        if file.metadata().map(|m| m.len()).unwrap_or(0) < SEGMENT_HEADER_LEN {
-            self.file = None;
-            return Ok(());
-        }
-        file.seek(SeekFrom::Start(SEGMENT_HEADER_LEN))?;
-        self.file = Some(file);
+        self.reader = SegmentReader::open(&seg.path)?;
         Ok(())
     }
 
     fn advance_segment(&mut self) -> Result<()> {
         self.segment_index = self.segment_index.saturating_add(1);
-        self.file = None;
+        self.reader = None;
         if self.segment_index >= self.segments.len() {
             self.refresh_segments()?;
             if self.segment_index >= self.segments.len() {
@@ -715,68 +1104,53 @@ fn parse_open_start_seq(name: &str) -> Option<u64> {
     parts.last()?.parse().ok()
 }
 
-#[derive(Debug, Clone)]
-struct SampleRecord {
-    sensor_idx: u32,
-    seq: u64,
-    timestamp_ms: i64,
-    value: f64,
-    quality: i16,
-    time_quality: TimeQuality,
-    monotonic_ms: u64,
-}
-
-fn decode_sample_record(buf: &[u8]) -> Option<SampleRecord> {
-    if buf.len() != SAMPLE_RECORD_LEN {
-        return None;
-    }
-    let sensor_idx = u32::from_le_bytes(buf[0..4].try_into().ok()?);
-    let seq = u64::from_le_bytes(buf[4..12].try_into().ok()?);
-    let timestamp_ms = i64::from_le_bytes(buf[12..20].try_into().ok()?);
-    let value = f64::from_le_bytes(buf[20..28].try_into().ok()?);
-    let quality = i16::from_le_bytes(buf[28..30].try_into().ok()?);
-    let time_quality_bits = u16::from_le_bytes(buf[30..32].try_into().ok()?);
-    let monotonic_ms = u64::from_le_bytes(buf[32..40].try_into().ok()?);
-    let time_quality = match time_quality_bits {
-        1 => TimeQuality::Good,
-        2 => TimeQuality::Unsynced,
-        _ => TimeQuality::Unknown,
-    };
-    Some(SampleRecord {
-        sensor_idx,
-        seq,
-        timestamp_ms,
-        value,
-        quality,
-        time_quality,
-        monotonic_ms,
-    })
-}
-
-fn read_next_frame_payload(file: &mut fs::File) -> Result<Option<Vec<u8>>> {
-    let mut header = [0u8; FRAME_HEADER_LEN];
-    match file.read_exact(&mut header) {
-        Ok(()) => {}
-        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
-        Err(err) => return Err(err.into()),
-    }
-
-    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
-    let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
-    if len == 0 || len > MAX_FRAME_LEN {
-        return Ok(None);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selector_fails_over_after_consecutive_failures_and_back_after_window() {
+        let mut selector = UpstreamSelector::new(3);
+        assert!(!selector.on_session_end(2));
+        selector.on_connected();
+        assert!(!selector.on_session_end(2));
+        assert!(selector.on_session_end(2));
+        assert_eq!(selector.active, 1);
+
+        let start = Instant::now();
+        let window = Duration::from_secs(60);
+        selector.on_probe(0, true, start);
+        selector.on_probe(2, true, start);
+        assert_eq!(selector.failback_target(start + Duration::from_secs(30), window), None);
+        selector.on_probe(0, false, start + Duration::from_secs(45));
+        selector.on_probe(0, true, start + Duration::from_secs(50));
+        assert_eq!(selector.failback_target(start + Duration::from_secs(100), window), None);
+        assert_eq!(
+            selector.failback_target(start + Duration::from_secs(110), window),
+            Some(0)
+        );
     }
 
-    let mut payload = vec![0u8; len];
-    match file.read_exact(&mut payload) {
-        Ok(()) => {}
-        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
-        Err(err) => return Err(err.into()),
+    #[test]
+    fn single_upstream_never_fails_over() {
+        let mut selector = UpstreamSelector::new(1);
+        for _ in 0..10 {
+            assert!(!selector.on_session_end(1));
+        }
+        assert_eq!(selector.active, 0);
     }
 
-    if crc32c(&payload) != crc {
-        return Ok(None);
+    #[test]
+    fn released_range_covers_seqs_truncated_after_another_upstream_acked() {
+        let acks = BTreeMap::from([
+            ("primary:1883".to_string(), 120),
+            ("standby:1883".to_string(), 80),
+        ]);
+        let range = released_range(&acks, "standby:1883", 100).unwrap();
+        assert_eq!((range.start_seq, range.end_seq), (81, 100));
+        assert_eq!(range.kind, LossKind::Released);
+        assert!(released_range(&acks, "primary:1883", 100).is_none());
+        let fresh = released_range(&acks, "relay:1883", 100).unwrap();
+        assert_eq!((fresh.start_seq, fresh.end_seq), (1, 100));
     }
-
-    Ok(Some(payload))
 }
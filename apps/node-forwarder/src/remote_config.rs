@@ -0,0 +1,354 @@
+//! Forwarder settings pushed by the controller, retained on `<prefix>/<node>/forwarder-config`.
+//!
+//! The document overrides a subset of the environment knobs. It is validated against the
+//! environment values, applied to the running spool and replay loops without a restart, and
+//! persisted so the next start uses it before the controller is reachable. The outcome is reported
+//! back, retained, on `<prefix>/<node>/forwarder-config/state` so the controller can show drift.
+
+use crate::config::Config;
+use crate::edge_rules::write_atomic;
+use crate::spool::SpoolHandle;
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const CONFIG_FILE: &str = "forwarder_config.json";
+
+const MIN_SPOOL_BYTES: u64 = 16 * 1024 * 1024;
+const MIN_SEGMENT_ROLL_BYTES: u64 = 1024 * 1024;
+const MAX_SEGMENT_ROLL_BYTES: u64 = 1024 * 1024 * 1024;
+const MIN_SEGMENT_ROLL_SECONDS: u64 = 60;
+const MAX_SEGMENT_ROLL_SECONDS: u64 = 24 * 3600;
+const MIN_SPOOL_AGE_SECONDS: u64 = 3600;
+const MAX_REPLAY_MSGS_PER_SEC: u32 = 100_000;
+const MIN_REPLAY_BYTES_PER_SEC: u32 = 4096;
+
+/// Knobs the controller may override. Unset fields keep the environment value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForwarderSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_spool_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_free_bytes: Option<u64>,
+    /// `0` removes the age limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_spool_age_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_msgs_per_sec: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_bytes_per_sec: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment_roll_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment_roll_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ForwarderConfigDocument {
+    /// sha256 of the settings, computed by the controller.
+    pub version: String,
+    #[serde(default)]
+    pub settings: ForwarderSettings,
+}
+
+/// The values the spool and replay loops are running with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Tuning {
+    pub max_spool_bytes: u64,
+    pub keep_free_bytes: u64,
+    pub max_spool_age_seconds: Option<u64>,
+    pub replay_msgs_per_sec: u32,
+    pub replay_bytes_per_sec: u32,
+    pub segment_roll_bytes: u64,
+    pub segment_roll_seconds: u64,
+}
+
+impl Tuning {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_spool_bytes: config.max_spool_bytes,
+            keep_free_bytes: config.keep_free_bytes,
+            max_spool_age_seconds: config.max_spool_age.map(|age| age.as_secs()),
+            replay_msgs_per_sec: config.replay_msgs_per_sec,
+            replay_bytes_per_sec: config.replay_bytes_per_sec,
+            segment_roll_bytes: config.segment_roll_bytes,
+            segment_roll_seconds: config.segment_roll_duration.as_secs(),
+        }
+    }
+
+    pub fn apply_to(&self, config: &mut Config) {
+        config.max_spool_bytes = self.max_spool_bytes;
+        config.keep_free_bytes = self.keep_free_bytes;
+        config.max_spool_age = self.max_spool_age_seconds.map(Duration::from_secs);
+        config.replay_msgs_per_sec = self.replay_msgs_per_sec;
+        config.replay_bytes_per_sec = self.replay_bytes_per_sec;
+        config.segment_roll_bytes = self.segment_roll_bytes;
+        config.segment_roll_duration = Duration::from_secs(self.segment_roll_seconds);
+    }
+
+    /// Overlays `settings` on these (environment) values, rejecting anything out of range.
+    pub fn with_settings(&self, settings: &ForwarderSettings) -> Result<Self> {
+        let tuning = Self {
+            max_spool_bytes: settings.max_spool_bytes.unwrap_or(self.max_spool_bytes),
+            keep_free_bytes: settings.keep_free_bytes.unwrap_or(self.keep_free_bytes),
+            max_spool_age_seconds: match settings.max_spool_age_seconds {
+                Some(0) => None,
+                Some(secs) => Some(secs),
+                None => self.max_spool_age_seconds,
+            },
+            replay_msgs_per_sec: settings
+                .replay_msgs_per_sec
+                .unwrap_or(self.replay_msgs_per_sec),
+            replay_bytes_per_sec: settings
+                .replay_bytes_per_sec
+                .unwrap_or(self.replay_bytes_per_sec),
+            segment_roll_bytes: settings
+                .segment_roll_bytes
+                .unwrap_or(self.segment_roll_bytes),
+            segment_roll_seconds: settings
+                .segment_roll_seconds
+                .unwrap_or(self.segment_roll_seconds),
+        };
+
+        let mut errors = Vec::new();
+        if settings.max_spool_bytes.is_some() && tuning.max_spool_bytes < MIN_SPOOL_BYTES {
+            errors.push(format!(
+                "max_spool_bytes must be at least {MIN_SPOOL_BYTES}"
+            ));
+        }
+        if settings
+            .max_spool_age_seconds
+            .is_some_and(|secs| secs != 0 && secs < MIN_SPOOL_AGE_SECONDS)
+        {
+            errors.push(format!(
+                "max_spool_age_seconds must be 0 or at least {MIN_SPOOL_AGE_SECONDS}"
+            ));
+        }
+        if settings
+            .replay_msgs_per_sec
+            .is_some_and(|rate| rate == 0 || rate > MAX_REPLAY_MSGS_PER_SEC)
+        {
+            errors.push(format!(
+                "replay_msgs_per_sec must be between 1 and {MAX_REPLAY_MSGS_PER_SEC}"
+            ));
+        }
+        if settings
+            .replay_bytes_per_sec
+            .is_some_and(|rate| rate < MIN_REPLAY_BYTES_PER_SEC)
+        {
+            errors.push(format!(
+                "replay_bytes_per_sec must be at least {MIN_REPLAY_BYTES_PER_SEC}"
+            ));
+        }
+        if settings.segment_roll_bytes.is_some_and(|bytes| {
+            !(MIN_SEGMENT_ROLL_BYTES..=MAX_SEGMENT_ROLL_BYTES).contains(&bytes)
+        }) {
+            errors.push(format!(
+                "segment_roll_bytes must be between {MIN_SEGMENT_ROLL_BYTES} and {MAX_SEGMENT_ROLL_BYTES}"
+            ));
+        }
+        if settings.segment_roll_seconds.is_some_and(|secs| {
+            !(MIN_SEGMENT_ROLL_SECONDS..=MAX_SEGMENT_ROLL_SECONDS).contains(&secs)
+        }) {
+            errors.push(format!(
+                "segment_roll_seconds must be between {MIN_SEGMENT_ROLL_SECONDS} and {MAX_SEGMENT_ROLL_SECONDS}"
+            ));
+        }
+        // Caps are enforced by dropping closed segments; an open segment larger than half the
+        // budget would leave nothing to drop.
+        if errors.is_empty() && tuning.segment_roll_bytes.saturating_mul(2) > tuning.max_spool_bytes
+        {
+            errors.push(format!(
+                "segment_roll_bytes ({}) must be at most half of max_spool_bytes ({})",
+                tuning.segment_roll_bytes, tuning.max_spool_bytes
+            ));
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!(errors.join("; ")));
+        }
+        Ok(tuning)
+    }
+}
+
+/// Published, retained, on `<prefix>/<node>/forwarder-config/state`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RemoteConfigReport {
+    /// Document in effect; `None` while running on environment values only.
+    pub applied_version: Option<String>,
+    pub applied_at: Option<String>,
+    /// Last document that failed validation. The previous settings stay in effect.
+    pub rejected_version: Option<String>,
+    pub error: Option<String>,
+    pub effective: Option<Tuning>,
+}
+
+pub struct RemoteConfig {
+    spool: SpoolHandle,
+    path: PathBuf,
+    report: Mutex<RemoteConfigReport>,
+}
+
+impl RemoteConfig {
+    /// Re-applies the persisted document, if any, before the controller is reachable.
+    pub async fn load(config: &Config, spool: SpoolHandle) -> Result<Self> {
+        let remote = Self {
+            spool,
+            path: config.spool_dir.join(CONFIG_FILE),
+            report: Mutex::new(RemoteConfigReport::default()),
+        };
+        match std::fs::read(&remote.path) {
+            Ok(raw) => {
+                if let Err(err) = remote.apply(&raw, false).await {
+                    tracing::warn!(error=%err, "ignoring persisted forwarder config");
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).context("failed to read persisted forwarder config"),
+        }
+        if let Ok(status) = remote.spool.status().await {
+            remote.lock().effective = Some(status.tuning());
+        }
+        Ok(remote)
+    }
+
+    /// Applies a document received over MQTT. Returns false when it was already in effect.
+    pub async fn install(&self, payload: &[u8]) -> bool {
+        match self.apply(payload, true).await {
+            Ok(changed) => changed,
+            Err(err) => {
+                tracing::warn!(error=%err, "rejected forwarder config");
+                true
+            }
+        }
+    }
+
+    pub fn report(&self) -> RemoteConfigReport {
+        self.lock().clone()
+    }
+
+    async fn apply(&self, payload: &[u8], persist: bool) -> Result<bool> {
+        let document: ForwarderConfigDocument = match serde_json::from_slice(payload) {
+            Ok(document) => document,
+            Err(err) => {
+                let mut report = self.lock();
+                report.rejected_version = None;
+                report.error = Some(format!("invalid forwarder config: {err}"));
+                return Err(anyhow!("invalid forwarder config: {err}"));
+            }
+        };
+        {
+            let report = self.lock();
+            if report.applied_version.as_deref() == Some(document.version.as_str())
+                || report.rejected_version.as_deref() == Some(document.version.as_str())
+            {
+                return Ok(false);
+            }
+        }
+
+        match self
+            .spool
+            .apply_settings(document.version.clone(), document.settings.clone())
+            .await
+        {
+            Ok(tuning) => {
+                if persist {
+                    write_atomic(&self.path, payload)?;
+                }
+                tracing::info!(version=%document.version, ?tuning, "applied forwarder config");
+                let mut report = self.lock();
+                report.applied_version = Some(document.version);
+                report.applied_at = Some(Utc::now().to_rfc3339());
+                report.rejected_version = None;
+                report.error = None;
+                report.effective = Some(tuning);
+                Ok(true)
+            }
+            Err(err) => {
+                let mut report = self.lock();
+                report.rejected_version = Some(document.version);
+                report.error = Some(err.to_string());
+                Err(err)
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RemoteConfigReport> {
+        self.report
+            .lock()
+            .expect("forwarder config report poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Tuning {
+        Tuning {
+            max_spool_bytes: 1024 * 1024 * 1024,
+            keep_free_bytes: 2 * 1024 * 1024 * 1024,
+            max_spool_age_seconds: None,
+            replay_msgs_per_sec: 2000,
+            replay_bytes_per_sec: 10 * 1024 * 1024,
+            segment_roll_bytes: 128 * 1024 * 1024,
+            segment_roll_seconds: 3600,
+        }
+    }
+
+    #[test]
+    fn settings_overlay_environment_values() {
+        let settings: ForwarderSettings =
+            serde_json::from_str(r#"{"replay_msgs_per_sec": 50, "max_spool_age_seconds": 86400}"#)
+                .unwrap();
+        let tuning = base().with_settings(&settings).unwrap();
+        assert_eq!(tuning.replay_msgs_per_sec, 50);
+        assert_eq!(tuning.max_spool_age_seconds, Some(86400));
+        assert_eq!(tuning.max_spool_bytes, base().max_spool_bytes);
+
+        let unlimited = ForwarderSettings {
+            max_spool_age_seconds: Some(0),
+            ..Default::default()
+        };
+        let mut aged = base();
+        aged.max_spool_age_seconds = Some(7200);
+        assert_eq!(
+            aged.with_settings(&unlimited)
+                .unwrap()
+                .max_spool_age_seconds,
+            None
+        );
+
+        assert_eq!(
+            base().with_settings(&ForwarderSettings::default()).unwrap(),
+            base()
+        );
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let err = base()
+            .with_settings(&ForwarderSettings {
+                replay_msgs_per_sec: Some(0),
+                segment_roll_seconds: Some(5),
+                ..Default::default()
+            })
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("replay_msgs_per_sec"));
+        assert!(err.contains("segment_roll_seconds"));
+
+        // Shrinking the budget below two open segments is rejected against the env roll size.
+        assert!(base()
+            .with_settings(&ForwarderSettings {
+                max_spool_bytes: Some(200 * 1024 * 1024),
+                ..Default::default()
+            })
+            .is_err());
+
+        assert!(serde_json::from_str::<ForwarderSettings>(r#"{"max_spool_byte": 1}"#).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+const USAGE_FILE: &str = "replay_usage.json";
+const USAGE_PERSIST_INTERVAL: Duration = Duration::from_secs(10);
+/// Probes kept for the RTT baseline; at the 5s probe interval this is five minutes.
+const RTT_BASELINE_SAMPLES: usize = 60;
+const RTT_SMOOTHING: f64 = 0.3;
+const MIN_RATE_SCALE: f64 = 0.05;
+const RATE_SCALE_STEP: f64 = 0.1;
+
+/// Whether live samples or the spooled backlog go first on a shared link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayOrder {
+    /// Live samples are published at once and their bytes come out of the replay allowance.
+    LiveFirst,
+    /// Live samples wait in the spool behind the backlog and go out in seq order.
+    BacklogFirst,
+}
+
+/// A daily time-of-day range in the node's local time; `end` before `start` wraps past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl ReplayWindow {
+    /// Parses `HH:MM-HH:MM`.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let (start, end) = raw
+            .trim()
+            .split_once('-')
+            .ok_or_else(|| anyhow!("replay window {raw:?} must look like HH:MM-HH:MM"))?;
+        let parse = |value: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M")
+                .with_context(|| format!("invalid time {value:?} in replay window {raw:?}"))
+        };
+        let window = Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+        if window.start == window.end {
+            return Err(anyhow!("replay window {raw:?} is empty"));
+        }
+        Ok(window)
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    fn label(&self) -> String {
+        format!(
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayPolicyConfig {
+    /// Replay only runs inside these windows; empty means always.
+    pub windows: Vec<ReplayWindow>,
+    pub daily_budget_bytes: Option<u64>,
+    pub monthly_budget_bytes: Option<u64>,
+    pub order: ReplayOrder,
+    /// Scale replay rates down while the broker RTT is elevated.
+    pub adaptive: bool,
+    /// RTT above which replay backs off; defaults to twice the recent minimum plus 50ms.
+    pub rtt_target_ms: Option<u64>,
+    pub rtt_probe_interval: Duration,
+}
+
+impl Default for ReplayPolicyConfig {
+    fn default() -> Self {
+        Self {
+            windows: Vec::new(),
+            daily_budget_bytes: None,
+            monthly_budget_bytes: None,
+            order: ReplayOrder::LiveFirst,
+            adaptive: false,
+            rtt_target_ms: None,
+            rtt_probe_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Why replay is currently held back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseReason {
+    OutsideWindow,
+    DailyBudget,
+    MonthlyBudget,
+}
+
+/// Bytes published to the broker in the current local day and month. Persisted so a restart
+/// does not hand out the budget twice.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct BudgetUsage {
+    day: String,
+    day_bytes: u64,
+    month: String,
+    month_bytes: u64,
+}
+
+impl BudgetUsage {
+    fn roll(&mut self, now: DateTime<Local>) -> bool {
+        let day = now.format("%Y-%m-%d").to_string();
+        let month = now.format("%Y-%m").to_string();
+        let mut changed = false;
+        if self.day != day {
+            self.day = day;
+            self.day_bytes = 0;
+            changed = true;
+        }
+        if self.month != month {
+            self.month = month;
+            self.month_bytes = 0;
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// Additive-increase / multiplicative-decrease on the replay rates, driven by broker RTT.
+#[derive(Debug, Clone)]
+struct AdaptiveThrottle {
+    samples: VecDeque<f64>,
+    smoothed_ms: Option<f64>,
+    last_ms: Option<f64>,
+    scale: f64,
+}
+
+impl AdaptiveThrottle {
+    fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(RTT_BASELINE_SAMPLES),
+            smoothed_ms: None,
+            last_ms: None,
+            scale: 1.0,
+        }
+    }
+
+    fn baseline_ms(&self) -> Option<f64> {
+        self.samples.iter().copied().reduce(f64::min)
+    }
+
+    fn observe(&mut self, rtt_ms: Option<f64>, target_ms: Option<u64>) {
+        self.last_ms = rtt_ms;
+        let Some(rtt_ms) = rtt_ms else {
+            // A probe that cannot connect is the strongest congestion signal there is.
+            self.scale = (self.scale * 0.5).max(MIN_RATE_SCALE);
+            return;
+        };
+        if self.samples.len() == RTT_BASELINE_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt_ms);
+        let smoothed = match self.smoothed_ms {
+            Some(prev) => prev + RTT_SMOOTHING * (rtt_ms - prev),
+            None => rtt_ms,
+        };
+        self.smoothed_ms = Some(smoothed);
+        let threshold = match target_ms {
+            Some(target) => target as f64,
+            None => self.baseline_ms().unwrap_or(rtt_ms) * 2.0 + 50.0,
+        };
+        self.scale = if smoothed > threshold {
+            (self.scale * 0.5).max(MIN_RATE_SCALE)
+        } else {
+            (self.scale + RATE_SCALE_STEP).min(1.0)
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayPolicyStatus {
+    pub order: ReplayOrder,
+    pub windows: Vec<String>,
+    /// `None` while replay may run.
+    pub paused: Option<PauseReason>,
+    /// Next window start when paused outside a window.
+    pub resumes_at: Option<String>,
+    pub daily_budget_bytes: Option<u64>,
+    pub daily_used_bytes: u64,
+    pub monthly_budget_bytes: Option<u64>,
+    pub monthly_used_bytes: u64,
+    pub adaptive: bool,
+    pub rtt_ms: Option<f64>,
+    pub baseline_rtt_ms: Option<f64>,
+    /// Fraction of the configured replay rates currently allowed.
+    pub rate_scale: f64,
+}
+
+/// Decides when replay may run and how fast. Outlives MQTT reconnects so budgets and the RTT
+/// history carry over.
+#[derive(Debug)]
+pub struct ReplayPolicy {
+    config: ReplayPolicyConfig,
+    usage_path: PathBuf,
+    usage: BudgetUsage,
+    usage_dirty: bool,
+    last_persist: Instant,
+    throttle: AdaptiveThrottle,
+}
+
+impl ReplayPolicy {
+    pub fn load(config: &Config) -> Self {
+        let usage_path = config.spool_dir.join(USAGE_FILE);
+        let usage = match read_usage(&usage_path) {
+            Ok(usage) => usage.unwrap_or_default(),
+            Err(err) => {
+                tracing::warn!(error=%err, "ignoring unreadable replay usage counters");
+                BudgetUsage::default()
+            }
+        };
+        Self {
+            config: config.replay_policy.clone(),
+            usage_path,
+            usage,
+            usage_dirty: false,
+            last_persist: Instant::now(),
+            throttle: AdaptiveThrottle::new(),
+        }
+    }
+
+    pub fn order(&self) -> ReplayOrder {
+        self.config.order
+    }
+
+    pub fn adaptive(&self) -> bool {
+        self.config.adaptive
+    }
+
+    pub fn rtt_probe_interval(&self) -> Duration {
+        self.config.rtt_probe_interval
+    }
+
+    /// Counts bytes published to the broker, live or replayed, against the budgets.
+    pub fn record(&mut self, bytes: usize, now: DateTime<Local>) {
+        self.usage.roll(now);
+        self.usage.day_bytes = self.usage.day_bytes.saturating_add(bytes as u64);
+        self.usage.month_bytes = self.usage.month_bytes.saturating_add(bytes as u64);
+        self.usage_dirty = true;
+    }
+
+    /// Returns why a replay publish of `bytes` must wait, if it must.
+    pub fn pause_reason(&mut self, bytes: usize, now: DateTime<Local>) -> Option<PauseReason> {
+        if self.usage.roll(now) {
+            self.usage_dirty = true;
+        }
+        if !self.in_window(now) {
+            return Some(PauseReason::OutsideWindow);
+        }
+        let over = |used: u64, budget: Option<u64>| {
+            budget.is_some_and(|budget| used.saturating_add(bytes as u64) > budget)
+        };
+        if over(self.usage.day_bytes, self.config.daily_budget_bytes) {
+            return Some(PauseReason::DailyBudget);
+        }
+        if over(self.usage.month_bytes, self.config.monthly_budget_bytes) {
+            return Some(PauseReason::MonthlyBudget);
+        }
+        None
+    }
+
+    /// Fraction of the configured replay rates to use right now.
+    pub fn rate_scale(&self) -> f64 {
+        if self.config.adaptive {
+            self.throttle.scale
+        } else {
+            1.0
+        }
+    }
+
+    pub fn observe_rtt(&mut self, rtt_ms: Option<f64>) {
+        self.throttle.observe(rtt_ms, self.config.rtt_target_ms);
+    }
+
+    /// Forgets the RTT history; a different upstream has a different baseline.
+    pub fn reset_rtt(&mut self) {
+        self.throttle = AdaptiveThrottle::new();
+    }
+
+    pub fn persist_if_due(&mut self) {
+        if !self.usage_dirty || self.last_persist.elapsed() < USAGE_PERSIST_INTERVAL {
+            return;
+        }
+        self.last_persist = Instant::now();
+        let result = serde_json::to_vec_pretty(&self.usage)
+            .map_err(anyhow::Error::from)
+            .and_then(|raw| crate::edge_rules::write_atomic(&self.usage_path, &raw));
+        match result {
+            Ok(()) => self.usage_dirty = false,
+            Err(err) => tracing::warn!(error=%err, "failed to persist replay usage counters"),
+        }
+    }
+
+    pub fn status(&mut self, now: DateTime<Local>) -> ReplayPolicyStatus {
+        let paused = self.pause_reason(0, now);
+        let resumes_at = (paused == Some(PauseReason::OutsideWindow))
+            .then(|| self.next_window_start(now))
+            .flatten()
+            .map(|at| at.to_rfc3339());
+        ReplayPolicyStatus {
+            order: self.config.order,
+            windows: self
+                .config
+                .windows
+                .iter()
+                .map(ReplayWindow::label)
+                .collect(),
+            paused,
+            resumes_at,
+            daily_budget_bytes: self.config.daily_budget_bytes,
+            daily_used_bytes: self.usage.day_bytes,
+            monthly_budget_bytes: self.config.monthly_budget_bytes,
+            monthly_used_bytes: self.usage.month_bytes,
+            adaptive: self.config.adaptive,
+            rtt_ms: self.throttle.last_ms,
+            baseline_rtt_ms: self.throttle.baseline_ms(),
+            rate_scale: self.rate_scale(),
+        }
+    }
+
+    fn in_window(&self, now: DateTime<Local>) -> bool {
+        let time = now.time();
+        self.config.windows.is_empty() || self.config.windows.iter().any(|w| w.contains(time))
+    }
+
+    fn next_window_start(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let today = now.date_naive();
+        self.config
+            .windows
+            .iter()
+            .filter_map(|window| {
+                let day = if window.start > now.time() {
+                    today
+                } else {
+                    today + ChronoDuration::days(1)
+                };
+                let naive = day.and_hms_opt(window.start.hour(), window.start.minute(), 0)?;
+                Local.from_local_datetime(&naive).earliest()
+            })
+            .min()
+    }
+}
+
+fn read_usage(path: &Path) -> Result<Option<BudgetUsage>> {
+    match std::fs::read(path) {
+        Ok(raw) => Ok(Some(
+            serde_json::from_slice(&raw).with_context(|| format!("parse {}", path.display()))?,
+        )),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("read {}", path.display())),
+    }
+}
+
+/// Round trip of a TCP connect to the broker, the same measurement node status reports.
+pub async fn probe_rtt_ms(host: &str, port: u16) -> Option<f64> {
+    let start = Instant::now();
+    let connect = tokio::net::TcpStream::connect((host, port));
+    match tokio::time::timeout(Duration::from_secs(2), connect).await {
+        Ok(Ok(_stream)) => Some(start.elapsed().as_secs_f64() * 1000.0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 3, 14, hour, minute, 0)
+            .single()
+            .expect("unambiguous local time")
+    }
+
+    fn policy(config: ReplayPolicyConfig) -> ReplayPolicy {
+        ReplayPolicy {
+            config,
+            usage_path: PathBuf::from("/nonexistent/replay_usage.json"),
+            usage: BudgetUsage::default(),
+            usage_dirty: false,
+            last_persist: Instant::now(),
+            throttle: AdaptiveThrottle::new(),
+        }
+    }
+
+    #[test]
+    fn windows_wrap_past_midnight_and_gate_budgets() {
+        let mut policy = policy(ReplayPolicyConfig {
+            windows: vec![ReplayWindow::parse("22:00-06:00").unwrap()],
+            daily_budget_bytes: Some(1000),
+            ..ReplayPolicyConfig::default()
+        });
+        assert_eq!(
+            policy.pause_reason(10, at(12, 0)),
+            Some(PauseReason::OutsideWindow)
+        );
+        assert_eq!(
+            policy.next_window_start(at(12, 0)).map(|t| t.time()),
+            NaiveTime::from_hms_opt(22, 0, 0)
+        );
+        assert_eq!(policy.pause_reason(10, at(23, 30)), None);
+        assert_eq!(policy.pause_reason(10, at(5, 59)), None);
+
+        policy.record(995, at(23, 30));
+        assert_eq!(
+            policy.pause_reason(10, at(23, 31)),
+            Some(PauseReason::DailyBudget)
+        );
+        assert_eq!(policy.pause_reason(5, at(23, 31)), None);
+
+        assert!(ReplayWindow::parse("06:00-06:00").is_err());
+        assert!(ReplayWindow::parse("6pm").is_err());
+    }
+
+    #[test]
+    fn throttle_backs_off_on_rtt_spikes_and_recovers() {
+        let mut policy = policy(ReplayPolicyConfig {
+            adaptive: true,
+            ..ReplayPolicyConfig::default()
+        });
+        for _ in 0..5 {
+            policy.observe_rtt(Some(40.0));
+        }
+        assert_eq!(policy.rate_scale(), 1.0);
+
+        for _ in 0..4 {
+            policy.observe_rtt(Some(900.0));
+        }
+        assert!(policy.rate_scale() < 0.2);
+        policy.observe_rtt(None);
+        let low = policy.rate_scale();
+        assert!(low >= MIN_RATE_SCALE);
+
+        for _ in 0..30 {
+            policy.observe_rtt(Some(40.0));
+        }
+        assert_eq!(policy.rate_scale(), 1.0);
+    }
+}
@@ -0,0 +1,632 @@
+use crate::config::PriorityClass;
+use crate::spool::TimeQuality;
+use anyhow::{anyhow, Context, Result};
+use crc32c::crc32c;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+pub const SEGMENT_MAGIC: &[u8; 8] = b"FDSPOOL1";
+pub const SEGMENT_HEADER_LEN: usize = 64;
+pub const SAMPLE_RECORD_LEN: usize = 40;
+const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+/// Segment header version for compacted segments. Raw segments stay at version 1.
+const COMPACTED_SEGMENT_VERSION: u32 = 2;
+/// Header offset of the compaction level (u32 LE). 0 means a raw segment of plain sample frames.
+const HEADER_LEVEL_OFFSET: usize = 48;
+/// Uncompressed size at which a compacted block is cut into its own zstd frame.
+const BLOCK_TARGET_BYTES: usize = 256 * 1024;
+const ZSTD_LEVEL: i32 = 3;
+
+/// One spooled sample, or an average standing in for several (`merged_seqs` non-empty).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpoolEntry {
+    pub sensor_idx: u32,
+    pub seq: u64,
+    pub timestamp_ms: i64,
+    pub value: f64,
+    pub quality: i16,
+    pub time_quality: TimeQuality,
+    pub monotonic_ms: u64,
+    pub merged_seqs: Vec<u64>,
+}
+
+impl SpoolEntry {
+    /// Highest seq this entry accounts for.
+    pub fn last_seq(&self) -> u64 {
+        self.merged_seqs.iter().copied().fold(self.seq, u64::max)
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.sensor_idx.to_le_bytes());
+        out.extend_from_slice(&self.seq.to_le_bytes());
+        out.extend_from_slice(&self.timestamp_ms.to_le_bytes());
+        out.extend_from_slice(&self.value.to_le_bytes());
+        out.extend_from_slice(&self.quality.to_le_bytes());
+        out.extend_from_slice(&self.time_quality.as_flag_bits().to_le_bytes());
+        out.extend_from_slice(&self.monotonic_ms.to_le_bytes());
+        if !self.merged_seqs.is_empty() {
+            out.extend_from_slice(&(self.merged_seqs.len() as u32).to_le_bytes());
+            for seq in &self.merged_seqs {
+                out.extend_from_slice(&seq.to_le_bytes());
+            }
+        }
+    }
+
+    /// Decodes a 40-byte sample record, or a record followed by its merged seqs.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < SAMPLE_RECORD_LEN {
+            return None;
+        }
+        let mut entry = SpoolEntry {
+            sensor_idx: u32::from_le_bytes(buf[0..4].try_into().ok()?),
+            seq: u64::from_le_bytes(buf[4..12].try_into().ok()?),
+            timestamp_ms: i64::from_le_bytes(buf[12..20].try_into().ok()?),
+            value: f64::from_le_bytes(buf[20..28].try_into().ok()?),
+            quality: i16::from_le_bytes(buf[28..30].try_into().ok()?),
+            time_quality: TimeQuality::from_flag_bits(u16::from_le_bytes(
+                buf[30..32].try_into().ok()?,
+            )),
+            monotonic_ms: u64::from_le_bytes(buf[32..40].try_into().ok()?),
+            merged_seqs: Vec::new(),
+        };
+        if buf.len() == SAMPLE_RECORD_LEN {
+            return Some(entry);
+        }
+        let rest = &buf[SAMPLE_RECORD_LEN..];
+        if rest.len() < 4 {
+            return None;
+        }
+        let count = u32::from_le_bytes(rest[0..4].try_into().ok()?) as usize;
+        let seqs = &rest[4..];
+        if seqs.len() != count.checked_mul(8)? {
+            return None;
+        }
+        entry.merged_seqs = seqs
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Some(entry)
+    }
+}
+
+fn read_next_frame_payload(file: &mut fs::File) -> Result<Option<Vec<u8>>> {
+    let mut header = [0u8; 8];
+    match file.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if len == 0 || len > MAX_FRAME_LEN {
+        return Ok(None);
+    }
+
+    let mut payload = vec![0u8; len];
+    match file.read_exact(&mut payload) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    if crc32c(&payload) != crc {
+        return Ok(None);
+    }
+
+    Ok(Some(payload))
+}
+
+fn write_frame(file: &mut fs::File, payload: &[u8]) -> Result<u64> {
+    let len = payload.len() as u32;
+    file.write_all(&len.to_le_bytes())?;
+    file.write_all(&crc32c(payload).to_le_bytes())?;
+    file.write_all(payload)?;
+    Ok(8 + payload.len() as u64)
+}
+
+fn encode_block(entries: &[SpoolEntry]) -> Result<Vec<u8>> {
+    let mut raw = Vec::new();
+    let mut entry_buf = Vec::with_capacity(SAMPLE_RECORD_LEN);
+    for entry in entries {
+        entry_buf.clear();
+        entry.encode(&mut entry_buf);
+        raw.extend_from_slice(&(entry_buf.len() as u32).to_le_bytes());
+        raw.extend_from_slice(&entry_buf);
+    }
+    zstd::bulk::compress(&raw, ZSTD_LEVEL).context("zstd compress block")
+}
+
+fn decode_block(payload: &[u8]) -> Option<Vec<SpoolEntry>> {
+    let raw = zstd::stream::decode_all(payload).ok()?;
+    let mut out = Vec::new();
+    let mut pos = 0usize;
+    while pos < raw.len() {
+        let len = u32::from_le_bytes(raw.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        pos += 4;
+        out.push(SpoolEntry::decode(raw.get(pos..pos + len)?)?);
+        pos += len;
+    }
+    Some(out)
+}
+
+/// Sequential reader over raw and compacted segments.
+#[derive(Debug)]
+pub struct SegmentReader {
+    file: fs::File,
+    level: u32,
+    buffered: VecDeque<SpoolEntry>,
+}
+
+impl SegmentReader {
+    /// Returns `None` for a segment that does not have a complete header yet.
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        let mut file = fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+        if file.metadata().map(|m| m.len()).unwrap_or(0) < SEGMENT_HEADER_LEN as u64 {
+            return Ok(None);
+        }
+        let mut header = [0u8; SEGMENT_HEADER_LEN];
+        file.read_exact(&mut header)?;
+        Ok(Some(Self {
+            file,
+            level: compaction_level(&header),
+            buffered: VecDeque::new(),
+        }))
+    }
+
+    /// Next entry, or `None` at the end of the readable data (EOF or a corrupt frame).
+    pub fn next_entry(&mut self) -> Result<Option<SpoolEntry>> {
+        loop {
+            if let Some(entry) = self.buffered.pop_front() {
+                return Ok(Some(entry));
+            }
+            let Some(payload) = read_next_frame_payload(&mut self.file)? else {
+                return Ok(None);
+            };
+            if self.level == 0 {
+                if payload.len() != SAMPLE_RECORD_LEN {
+                    return Ok(None);
+                }
+                return Ok(SpoolEntry::decode(&payload));
+            }
+            let Some(entries) = decode_block(&payload) else {
+                return Ok(None);
+            };
+            self.buffered.extend(entries);
+        }
+    }
+}
+
+fn compaction_level(header: &[u8]) -> u32 {
+    if header.len() < SEGMENT_HEADER_LEN || &header[0..8] != SEGMENT_MAGIC {
+        return 0;
+    }
+    u32::from_le_bytes(
+        header[HEADER_LEVEL_OFFSET..HEADER_LEVEL_OFFSET + 4]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+pub fn read_compaction_level(path: &Path) -> Result<u32> {
+    let mut file = fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut header = [0u8; SEGMENT_HEADER_LEN];
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(compaction_level(&header)),
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(0),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Fixed fields of a segment header.
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentHeader {
+    pub version: u32,
+    pub stream_id: Uuid,
+    pub created_wall_ms: i64,
+    pub start_seq: u64,
+    pub compaction_level: u32,
+}
+
+impl SegmentHeader {
+    fn parse(header: &[u8]) -> Option<Self> {
+        if header.len() < SEGMENT_HEADER_LEN || &header[0..8] != SEGMENT_MAGIC {
+            return None;
+        }
+        Some(Self {
+            version: u32::from_le_bytes(header[8..12].try_into().ok()?),
+            stream_id: Uuid::from_slice(&header[16..32]).ok()?,
+            created_wall_ms: i64::from_le_bytes(header[32..40].try_into().ok()?),
+            start_seq: u64::from_le_bytes(header[40..48].try_into().ok()?),
+            compaction_level: compaction_level(header),
+        })
+    }
+}
+
+/// Frame-by-frame account of one segment file.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SegmentScan {
+    pub header: Option<SegmentHeader>,
+    pub bytes: u64,
+    pub frames: u64,
+    /// Stored entries; an average counts once.
+    pub entries: u64,
+    /// Samples accounted for, including those merged into averages.
+    pub samples: u64,
+    pub crc_failures: u64,
+    /// Frames with a valid CRC whose payload does not decode.
+    pub undecodable_frames: u64,
+    /// Bytes after the last readable frame (a torn write, or a frame length that cannot be right).
+    pub trailing_bytes: u64,
+    pub first_seq: Option<u64>,
+    pub last_seq: Option<u64>,
+    pub min_timestamp_ms: Option<i64>,
+    pub max_timestamp_ms: Option<i64>,
+}
+
+/// Reads every frame of a segment, skipping over frames that fail their CRC (unlike
+/// [`SegmentReader`], which stops at the first bad frame). `on_entry` sees each decoded entry.
+pub fn scan_segment(path: &Path, mut on_entry: impl FnMut(&SpoolEntry)) -> Result<SegmentScan> {
+    let file = fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let total = file.metadata()?.len();
+    let mut scan = SegmentScan {
+        bytes: total,
+        ..Default::default()
+    };
+    let mut reader = BufReader::new(file);
+    let mut header = [0u8; SEGMENT_HEADER_LEN];
+    if total < SEGMENT_HEADER_LEN as u64 {
+        scan.trailing_bytes = total;
+        return Ok(scan);
+    }
+    reader.read_exact(&mut header)?;
+    scan.header = SegmentHeader::parse(&header);
+    let Some(level) = scan.header.as_ref().map(|header| header.compaction_level) else {
+        scan.trailing_bytes = total - SEGMENT_HEADER_LEN as u64;
+        return Ok(scan);
+    };
+
+    let mut pos = SEGMENT_HEADER_LEN as u64;
+    while pos < total {
+        let remaining = total - pos;
+        let mut frame_header = [0u8; 8];
+        if remaining < 8 {
+            scan.trailing_bytes = remaining;
+            break;
+        }
+        reader.read_exact(&mut frame_header)?;
+        let len = u32::from_le_bytes(frame_header[0..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(frame_header[4..8].try_into().unwrap());
+        if len == 0 || len > MAX_FRAME_LEN || len as u64 > remaining - 8 {
+            scan.trailing_bytes = remaining;
+            break;
+        }
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload)?;
+        pos += 8 + len as u64;
+        scan.frames += 1;
+        if crc32c(&payload) != crc {
+            scan.crc_failures += 1;
+            continue;
+        }
+
+        let entries = if level == 0 {
+            (payload.len() == SAMPLE_RECORD_LEN)
+                .then(|| SpoolEntry::decode(&payload))
+                .flatten()
+                .map(|entry| vec![entry])
+        } else {
+            decode_block(&payload)
+        };
+        let Some(entries) = entries else {
+            scan.undecodable_frames += 1;
+            continue;
+        };
+        for entry in &entries {
+            scan.entries += 1;
+            scan.samples += 1 + entry.merged_seqs.len() as u64;
+            scan.first_seq = Some(scan.first_seq.map_or(entry.seq, |seq| seq.min(entry.seq)));
+            scan.last_seq = Some(
+                scan.last_seq
+                    .map_or(entry.last_seq(), |seq| seq.max(entry.last_seq())),
+            );
+            scan.min_timestamp_ms = Some(
+                scan.min_timestamp_ms
+                    .map_or(entry.timestamp_ms, |ts| ts.min(entry.timestamp_ms)),
+            );
+            scan.max_timestamp_ms = Some(
+                scan.max_timestamp_ms
+                    .map_or(entry.timestamp_ms, |ts| ts.max(entry.timestamp_ms)),
+            );
+            on_entry(entry);
+        }
+    }
+    Ok(scan)
+}
+
+/// Downsampling applied at each compaction level.
+#[derive(Debug, Clone, Copy)]
+pub struct CompactionPlan {
+    pub level: u32,
+    /// Bucket width for `low` sensors.
+    pub low_bucket_ms: i64,
+    /// Bucket width for `normal` sensors; `None` keeps them raw.
+    pub normal_bucket_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompactionOutcome {
+    pub old_bytes: u64,
+    pub new_bytes: u64,
+    pub entries_in: u64,
+    pub entries_out: u64,
+    /// Samples folded into an average (not counting the sample that carries it).
+    pub merged_samples: u64,
+}
+
+/// Sensor index, time quality bits, quality code and bucket start (ms).
+type BucketKey = (u32, u16, i16, i64);
+
+/// Rewrites a closed segment as zstd-compressed blocks, averaging `low` (and, at level 2, `normal`)
+/// sensors into fixed buckets. `high` sensors are always kept raw. The file keeps its name and
+/// modification time so seq-range and age bookkeeping are unaffected.
+pub fn compact_segment(
+    path: &Path,
+    plan: CompactionPlan,
+    class_for: impl Fn(u32) -> PriorityClass,
+) -> Result<CompactionOutcome> {
+    let meta = fs::metadata(path).with_context(|| format!("stat {}", path.display()))?;
+    let mut header = vec![0u8; SEGMENT_HEADER_LEN];
+    {
+        let mut file = fs::File::open(path)?;
+        file.read_exact(&mut header)
+            .context("read segment header")?;
+    }
+    if &header[0..8] != SEGMENT_MAGIC {
+        return Err(anyhow!("not a spool segment"));
+    }
+    if compaction_level(&header) >= plan.level {
+        return Err(anyhow!("segment already compacted"));
+    }
+
+    let mut reader = SegmentReader::open(path)?.ok_or_else(|| anyhow!("segment too small"))?;
+    let mut outcome = CompactionOutcome {
+        old_bytes: meta.len(),
+        ..Default::default()
+    };
+
+    // Entries that stay as-is keep their position; averages are keyed per bucket and emitted at
+    // the position of their first sample.
+    let mut kept: BTreeMap<u64, SpoolEntry> = BTreeMap::new();
+    let mut buckets: HashMap<BucketKey, (u64, f64, u64)> = HashMap::new();
+    while let Some(entry) = reader.next_entry()? {
+        outcome.entries_in += 1 + entry.merged_seqs.len() as u64;
+        let bucket_ms = match class_for(entry.sensor_idx) {
+            PriorityClass::Low => Some(plan.low_bucket_ms),
+            PriorityClass::Normal => plan.normal_bucket_ms,
+            PriorityClass::High => None,
+        };
+        let Some(bucket_ms) = bucket_ms.filter(|ms| *ms > 0) else {
+            kept.insert(entry.seq, entry);
+            continue;
+        };
+        let bucket = entry.timestamp_ms.div_euclid(bucket_ms) * bucket_ms;
+        let key = (
+            entry.sensor_idx,
+            entry.time_quality.as_flag_bits(),
+            entry.quality,
+            bucket,
+        );
+        let weight = 1 + entry.merged_seqs.len() as u64;
+        match buckets.get_mut(&key) {
+            Some((first_seq, sum, count)) => {
+                *sum += entry.value * weight as f64;
+                *count += weight;
+                let first = kept.get_mut(first_seq).expect("bucket head kept");
+                first.merged_seqs.push(entry.seq);
+                first.merged_seqs.extend(entry.merged_seqs);
+            }
+            None => {
+                buckets.insert(key, (entry.seq, entry.value * weight as f64, weight));
+                let offset = entry.timestamp_ms.saturating_sub(bucket).max(0) as u64;
+                let mut head = entry;
+                head.monotonic_ms = head.monotonic_ms.saturating_sub(offset);
+                head.timestamp_ms = bucket;
+                kept.insert(head.seq, head);
+            }
+        }
+    }
+    for (first_seq, sum, count) in buckets.into_values() {
+        if let Some(head) = kept.get_mut(&first_seq) {
+            head.value = sum / count as f64;
+            head.merged_seqs.sort_unstable();
+            head.merged_seqs.dedup();
+        }
+    }
+
+    let tmp = path.with_extension("seg.tmp");
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&tmp)
+        .with_context(|| format!("create {}", tmp.display()))?;
+    header[8..12].copy_from_slice(&COMPACTED_SEGMENT_VERSION.to_le_bytes());
+    header[HEADER_LEVEL_OFFSET..HEADER_LEVEL_OFFSET + 4].copy_from_slice(&plan.level.to_le_bytes());
+    file.write_all(&header)?;
+    let mut written = SEGMENT_HEADER_LEN as u64;
+
+    let mut block: Vec<SpoolEntry> = Vec::new();
+    let mut block_bytes = 0usize;
+    for entry in kept.into_values() {
+        outcome.entries_out += 1;
+        outcome.merged_samples += entry.merged_seqs.len() as u64;
+        block_bytes += 4 + SAMPLE_RECORD_LEN + 4 + entry.merged_seqs.len() * 8;
+        block.push(entry);
+        if block_bytes >= BLOCK_TARGET_BYTES {
+            written += write_frame(&mut file, &encode_block(&block)?)?;
+            block.clear();
+            block_bytes = 0;
+        }
+    }
+    if !block.is_empty() {
+        written += write_frame(&mut file, &encode_block(&block)?)?;
+    }
+    file.sync_data().ok();
+    if let Ok(modified) = meta.modified() {
+        file.set_modified(modified).ok();
+    }
+    drop(file);
+    fs::rename(&tmp, path)
+        .with_context(|| format!("rename {} -> {}", tmp.display(), path.display()))?;
+
+    outcome.new_bytes = written;
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_raw_segment(path: &Path, entries: &[SpoolEntry]) {
+        let mut file = fs::File::create(path).unwrap();
+        let mut header = vec![0u8; SEGMENT_HEADER_LEN];
+        header[0..8].copy_from_slice(SEGMENT_MAGIC);
+        header[8..12].copy_from_slice(&1u32.to_le_bytes());
+        file.write_all(&header).unwrap();
+        for entry in entries {
+            let mut payload = Vec::new();
+            entry.encode(&mut payload);
+            write_frame(&mut file, &payload).unwrap();
+        }
+    }
+
+    fn entry(sensor_idx: u32, seq: u64, timestamp_ms: i64, value: f64) -> SpoolEntry {
+        SpoolEntry {
+            sensor_idx,
+            seq,
+            timestamp_ms,
+            value,
+            quality: 0,
+            time_quality: TimeQuality::Good,
+            monotonic_ms: timestamp_ms as u64,
+            merged_seqs: Vec::new(),
+        }
+    }
+
+    fn read_all(path: &Path) -> Vec<SpoolEntry> {
+        let mut reader = SegmentReader::open(path).unwrap().unwrap();
+        let mut out = Vec::new();
+        while let Some(entry) = reader.next_entry().unwrap() {
+            out.push(entry);
+        }
+        out
+    }
+
+    #[test]
+    fn compaction_averages_low_priority_sensors_and_keeps_others_raw() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("seg-test-1-8.seg");
+        // Sensor 1 is low priority (10s samples), sensor 2 is high priority.
+        let entries = vec![
+            entry(1, 1, 60_000, 1.0),
+            entry(2, 2, 60_000, 100.0),
+            entry(1, 3, 70_000, 2.0),
+            entry(2, 4, 70_000, 101.0),
+            entry(1, 5, 80_000, 3.0),
+            entry(1, 6, 120_000, 10.0),
+            entry(1, 7, 130_000, 20.0),
+            entry(2, 8, 130_000, 102.0),
+        ];
+        write_raw_segment(&path, &entries);
+
+        let plan = CompactionPlan {
+            level: 1,
+            low_bucket_ms: 60_000,
+            normal_bucket_ms: None,
+        };
+        let class_for = |idx: u32| {
+            if idx == 1 {
+                PriorityClass::Low
+            } else {
+                PriorityClass::High
+            }
+        };
+        let outcome = compact_segment(&path, plan, class_for).unwrap();
+        assert_eq!(outcome.entries_in, 8);
+        assert_eq!(outcome.entries_out, 5);
+        assert_eq!(outcome.merged_samples, 3);
+        assert_eq!(read_compaction_level(&path).unwrap(), 1);
+
+        let out = read_all(&path);
+        let seqs: Vec<u64> = out.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![1, 2, 4, 6, 8]);
+        assert_eq!(out[0].merged_seqs, vec![3, 5]);
+        assert_eq!(out[0].value, 2.0);
+        assert_eq!(out[0].last_seq(), 5);
+        assert_eq!(out[3].timestamp_ms, 120_000);
+        assert_eq!(out[3].merged_seqs, vec![7]);
+        assert_eq!(out[3].value, 15.0);
+        assert!(out[1].merged_seqs.is_empty());
+
+        // Already at this level: nothing to do.
+        assert!(compact_segment(&path, plan, class_for).is_err());
+
+        // Level 2 folds normal sensors too and keeps merged seqs of earlier averages.
+        let plan = CompactionPlan {
+            level: 2,
+            low_bucket_ms: 60_000,
+            normal_bucket_ms: Some(600_000),
+        };
+        compact_segment(&path, plan, |idx| {
+            if idx == 1 {
+                PriorityClass::Low
+            } else {
+                PriorityClass::Normal
+            }
+        })
+        .unwrap();
+        let out = read_all(&path);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0].seq, 1);
+        assert_eq!(out[0].merged_seqs, vec![3, 5]);
+        let sensor1_late = out
+            .iter()
+            .find(|e| e.sensor_idx == 1 && e.seq == 6)
+            .unwrap();
+        assert_eq!(sensor1_late.merged_seqs, vec![7]);
+        let sensor2 = out.iter().find(|e| e.sensor_idx == 2).unwrap();
+        assert_eq!(sensor2.seq, 2);
+        assert_eq!(sensor2.merged_seqs, vec![4, 8]);
+        assert!((sensor2.value - 101.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn scan_skips_frames_that_fail_crc() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("seg.seg");
+        let entries: Vec<SpoolEntry> = (1..=3).map(|seq| entry(1, seq, seq as i64, 1.0)).collect();
+        write_raw_segment(&path, &entries);
+
+        // Flip a value byte in the second frame's payload.
+        let mut raw = fs::read(&path).unwrap();
+        let second_payload = SEGMENT_HEADER_LEN + (8 + SAMPLE_RECORD_LEN) + 8;
+        raw[second_payload + 20] ^= 0xff;
+        raw.extend_from_slice(&[1, 2, 3]);
+        fs::write(&path, raw).unwrap();
+
+        let mut seen = Vec::new();
+        let scan = scan_segment(&path, |entry| seen.push(entry.seq)).unwrap();
+        assert_eq!(seen, vec![1, 3]);
+        assert_eq!(scan.frames, 3);
+        assert_eq!(scan.crc_failures, 1);
+        assert_eq!(scan.trailing_bytes, 3);
+        assert_eq!((scan.first_seq, scan.last_seq), (Some(1), Some(3)));
+    }
+}
@@ -1,9 +1,15 @@
-use crate::config::Config;
+use crate::clock::{self, AppliedRepair, ClockAnchors, TimeRepair};
+use crate::config::{Config, PriorityClass};
+use crate::remote_config::{ForwarderSettings, Tuning};
+use crate::replay_policy::ReplayPolicyStatus;
+use crate::segment::{
+    self, CompactionPlan, SegmentReader, SAMPLE_RECORD_LEN, SEGMENT_HEADER_LEN, SEGMENT_MAGIC,
+};
 use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, Utc};
 use crc32c::crc32c;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs;
 use std::io::{Read, Seek, SeekFrom, Write};
@@ -12,12 +18,9 @@ use std::time::Instant;
 use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
-const SEGMENT_MAGIC: &[u8; 8] = b"FDSPOOL1";
 const SEGMENT_VERSION: u32 = 1;
-const SEGMENT_HEADER_LEN: usize = 64;
-const SAMPLE_RECORD_LEN: usize = 40;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TimeQuality {
     Unknown = 0,
@@ -26,9 +29,31 @@ pub enum TimeQuality {
 }
 
 impl TimeQuality {
-    fn as_flag_bits(self) -> u16 {
+    pub fn as_flag_bits(self) -> u16 {
         self as u16
     }
+
+    pub fn from_flag_bits(bits: u16) -> Self {
+        match bits {
+            1 => TimeQuality::Good,
+            2 => TimeQuality::Unsynced,
+            _ => TimeQuality::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LossKind {
+    /// The seqs are gone; the controller may ACK past them.
+    #[default]
+    Dropped,
+    /// Some samples in the range were folded into averages. Nothing is missing from the seq
+    /// stream (averages carry the seqs they replace), so this never permits skipping an ACK.
+    Downsampled,
+    /// Another upstream ACKed the range and it was truncated before this upstream got it. Only
+    /// ever sent to the lagging upstream; never stored in `state.json`.
+    Released,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +61,8 @@ pub struct LossRange {
     pub start_seq: u64,
     pub end_seq: u64,
     pub dropped_at: String,
+    #[serde(default)]
+    pub kind: LossKind,
 }
 
 #[derive(Debug, Clone)]
@@ -44,13 +71,33 @@ pub struct LossEvent {
     pub range: LossRange,
 }
 
+/// `state.json` in the spool directory.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct SpoolStateDisk {
-    stream_id: String,
-    next_seq: u64,
-    acked_seq: u64,
-    open_segment_start_seq: Option<u64>,
-    losses: Vec<LossRange>,
+pub struct SpoolStateDisk {
+    pub stream_id: String,
+    pub next_seq: u64,
+    /// Truncation point: the lowest ACK among upstreams in `upstream_acks`.
+    pub acked_seq: u64,
+    pub open_segment_start_seq: Option<u64>,
+    pub losses: Vec<LossRange>,
+    /// Highest seq each upstream (`host:port`) has ACKed on this stream.
+    #[serde(default)]
+    pub upstream_acks: BTreeMap<String, u64>,
+}
+
+impl SpoolStateDisk {
+    pub fn load(spool_dir: &Path) -> Result<Self> {
+        let path = spool_dir.join("state.json");
+        let raw = fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+        serde_json::from_str(&raw).context("parse state.json")
+    }
+
+    pub fn store(&self, state_path: &Path) -> Result<()> {
+        let tmp = state_path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?).context("write state tmp")?;
+        fs::rename(&tmp, state_path).context("rename state")?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -63,6 +110,33 @@ pub struct PublishSample {
     pub stream_id: Uuid,
     pub time_quality: TimeQuality,
     pub monotonic_ms: u64,
+    pub repair: Option<AppliedRepair>,
+    /// Seqs folded into this sample when its segment was downsampled.
+    pub merged_seqs: Vec<u64>,
+}
+
+impl PublishSample {
+    pub fn last_seq(&self) -> u64 {
+        self.merged_seqs.iter().copied().fold(self.seq, u64::max)
+    }
+
+    /// Applies a timestamp repair decision; `None` means the sample must be held for now.
+    pub fn with_time_repair(mut self, repair: TimeRepair) -> Option<Self> {
+        match repair {
+            TimeRepair::Unchanged => {}
+            TimeRepair::Rebased { timestamp_ms } => {
+                self.repair = Some(AppliedRepair::Rebased {
+                    reported_timestamp_ms: self.timestamp_ms,
+                });
+                self.timestamp_ms = timestamp_ms;
+            }
+            TimeRepair::Pending => return None,
+            TimeRepair::Uncorrectable { reason, boot_id } => {
+                self.repair = Some(AppliedRepair::Uncorrectable { reason, boot_id });
+            }
+        }
+        Some(self)
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -80,10 +154,34 @@ pub struct SpoolStatus {
     pub backlog_samples: u64,
     pub replay_msgs_per_sec: u32,
     pub replay_bytes_per_sec: u32,
+    pub max_spool_age_seconds: Option<u64>,
+    pub segment_roll_bytes: u64,
+    pub segment_roll_seconds: u64,
+    /// Version of the controller-pushed forwarder config in effect, if any.
+    pub config_version: Option<String>,
     pub estimated_drain_seconds: Option<u64>,
     pub losses_pending: usize,
     pub losses: Vec<LossRange>,
     pub oldest_unacked_timestamp_ms: Option<i64>,
+    pub clock_synced: bool,
+    pub active_upstream: Option<String>,
+    pub upstream_acks: BTreeMap<String, u64>,
+    /// Replay windows, budget use and throttling as last reported by the MQTT loop.
+    pub replay_policy: Option<ReplayPolicyStatus>,
+}
+
+impl SpoolStatus {
+    pub fn tuning(&self) -> Tuning {
+        Tuning {
+            max_spool_bytes: self.max_spool_bytes,
+            keep_free_bytes: self.keep_free_bytes,
+            max_spool_age_seconds: self.max_spool_age_seconds,
+            replay_msgs_per_sec: self.replay_msgs_per_sec,
+            replay_bytes_per_sec: self.replay_bytes_per_sec,
+            segment_roll_bytes: self.segment_roll_bytes,
+            segment_roll_seconds: self.segment_roll_seconds,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -98,12 +196,24 @@ pub enum SpoolCommand {
         respond_to: oneshot::Sender<Result<AppendResult>>,
     },
     UpdateAck {
+        upstream: String,
         stream_id: Uuid,
         acked_seq: u64,
     },
+    SetActiveUpstream {
+        upstream: String,
+    },
+    SetReplayPolicy {
+        status: ReplayPolicyStatus,
+    },
     GetStatus {
         respond_to: oneshot::Sender<SpoolStatus>,
     },
+    ApplySettings {
+        version: String,
+        settings: ForwarderSettings,
+        respond_to: oneshot::Sender<Result<Tuning>>,
+    },
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -137,8 +247,22 @@ impl SpoolHandle {
         rx.await.context("spool thread dropped response")?
     }
 
-    pub fn update_ack(&self, stream_id: Uuid, acked_seq: u64) {
-        let _ = self.tx.send(SpoolCommand::UpdateAck { stream_id, acked_seq });
+    pub fn update_ack(&self, upstream: &str, stream_id: Uuid, acked_seq: u64) {
+        let _ = self.tx.send(SpoolCommand::UpdateAck {
+            upstream: upstream.to_string(),
+            stream_id,
+            acked_seq,
+        });
+    }
+
+    pub fn set_active_upstream(&self, upstream: &str) {
+        let _ = self.tx.send(SpoolCommand::SetActiveUpstream {
+            upstream: upstream.to_string(),
+        });
+    }
+
+    pub fn set_replay_policy(&self, status: ReplayPolicyStatus) {
+        let _ = self.tx.send(SpoolCommand::SetReplayPolicy { status });
     }
 
     pub async fn status(&self) -> Result<SpoolStatus> {
@@ -148,6 +272,23 @@ impl SpoolHandle {
             .map_err(|_| anyhow!("spool thread stopped"))?;
         Ok(rx.await.context("spool thread dropped response")?)
     }
+
+    /// Validates `settings` against the environment values and applies them to the running spool.
+    pub async fn apply_settings(
+        &self,
+        version: String,
+        settings: ForwarderSettings,
+    ) -> Result<Tuning> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(SpoolCommand::ApplySettings {
+                version,
+                settings,
+                respond_to: tx,
+            })
+            .map_err(|_| anyhow!("spool thread stopped"))?;
+        rx.await.context("spool thread dropped response")?
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -206,16 +347,23 @@ struct SegmentWriter {
 
 struct SpoolRuntime {
     config: Config,
+    /// Knob values from the environment; controller-pushed settings are overlaid on these.
+    base_tuning: Tuning,
+    config_version: Option<String>,
     state_path: PathBuf,
     sensor_map_path: PathBuf,
     stream_id: Uuid,
     next_seq: u64,
     acked_seq: u64,
+    upstream_acks: BTreeMap<String, u64>,
+    active_upstream: Option<String>,
+    replay_policy: Option<ReplayPolicyStatus>,
     losses: Vec<LossRange>,
     sensor_map: SensorMap,
     spool_bytes: u64,
     segment: SegmentWriter,
     last_sync_at: Instant,
+    anchors: ClockAnchors,
     publish_tx: mpsc::Sender<PublishSample>,
     loss_tx: mpsc::Sender<LossEvent>,
 }
@@ -228,6 +376,7 @@ fn run_spool_thread(
 ) -> Result<()> {
     fs::create_dir_all(&config.spool_dir)
         .with_context(|| format!("failed to create {}", config.spool_dir.display()))?;
+    let _lock = lock_spool_dir(&config.spool_dir)?;
 
     // If the user did not override max_spool_bytes, apply the default policy using filesystem size.
     if env::var("NODE_FORWARDER_MAX_SPOOL_BYTES").is_err() {
@@ -236,6 +385,8 @@ fn run_spool_thread(
         }
     }
 
+    let base_tuning = Tuning::from_config(&config);
+
     let state_path = config.spool_dir.join("state.json");
     let sensor_map_path = config.spool_dir.join("sensor_map.json");
 
@@ -244,8 +395,23 @@ fn run_spool_thread(
         SensorMap::empty()
     });
 
-    let (stream_id, next_seq, acked_seq, open_start_seq, losses) =
-        load_or_init_state(&state_path)?;
+    let (stream_id, state) = load_or_init_state(&state_path)?;
+    let SpoolStateDisk {
+        next_seq,
+        acked_seq,
+        open_segment_start_seq: open_start_seq,
+        losses,
+        mut upstream_acks,
+        ..
+    } = state;
+    // A broker removed from the config must not hold back truncation.
+    upstream_acks.retain(|name, _| {
+        let known = config.upstreams.iter().any(|upstream| &upstream.name == name);
+        if !known {
+            tracing::info!(upstream=%name, "forgetting ACK state of removed upstream");
+        }
+        known
+    });
 
     let (segment, initial_spool_bytes) = open_or_create_segment(
         &config,
@@ -254,18 +420,32 @@ fn run_spool_thread(
         open_start_seq,
     )?;
 
+    let anchors = ClockAnchors::open(
+        &config.spool_dir,
+        clock::current_boot_id(),
+        next_seq,
+        clock::monotonic_ms(),
+        Utc::now().timestamp_millis(),
+    )?;
+
     let mut runtime = SpoolRuntime {
         config,
+        base_tuning,
+        config_version: None,
         state_path,
         sensor_map_path,
         stream_id,
         next_seq,
         acked_seq,
+        upstream_acks,
+        active_upstream: None,
+        replay_policy: None,
         losses,
         sensor_map,
         spool_bytes: initial_spool_bytes,
         segment,
         last_sync_at: Instant::now(),
+        anchors,
         publish_tx,
         loss_tx,
     };
@@ -289,26 +469,60 @@ fn run_spool_thread(
                 let res = runtime.append_samples(samples);
                 let _ = respond_to.send(res);
             }
-            SpoolCommand::UpdateAck { stream_id, acked_seq } => {
-                if stream_id == runtime.stream_id && acked_seq > runtime.acked_seq {
-                    runtime.acked_seq = acked_seq;
-                    if let Err(err) = runtime.delete_acked_segments() {
-                        tracing::warn!(error=%err, "failed to delete acked segments");
-                    }
-                    runtime.prune_losses();
-                    let _ = runtime.persist_state();
+            SpoolCommand::UpdateAck {
+                upstream,
+                stream_id,
+                acked_seq,
+            } => {
+                if stream_id == runtime.stream_id {
+                    runtime.update_ack(upstream, acked_seq);
                 }
             }
+            SpoolCommand::SetActiveUpstream { upstream } => {
+                runtime.active_upstream = Some(upstream);
+            }
+            SpoolCommand::SetReplayPolicy { status } => {
+                runtime.replay_policy = Some(status);
+            }
             SpoolCommand::GetStatus { respond_to } => {
                 let status = runtime.status();
                 let _ = respond_to.send(status);
             }
+            SpoolCommand::ApplySettings {
+                version,
+                settings,
+                respond_to,
+            } => {
+                let res = runtime.apply_settings(version, &settings);
+                let _ = respond_to.send(res);
+            }
         }
     }
 
     Ok(())
 }
 
+/// Takes an exclusive lock on the spool directory for as long as the returned file stays open, so
+/// two forwarders (or a forwarder and `node-forwarder spool reset-ack`) never share a spool.
+pub fn lock_spool_dir(spool_dir: &Path) -> Result<fs::File> {
+    use std::os::unix::io::AsRawFd;
+    let path = spool_dir.join("spool.lock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("open {}", path.display()))?;
+    let rc = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if rc != 0 {
+        return Err(anyhow!(
+            "spool {} is in use by another node-forwarder process",
+            spool_dir.display()
+        ));
+    }
+    Ok(file)
+}
+
 fn compute_default_spool_budget(spool_dir: &Path, keep_free_bytes: u64) -> Result<u64> {
     let (total, free) = statvfs_bytes(spool_dir)?;
     let five_percent = total / 20;
@@ -352,7 +566,7 @@ fn statvfs_bytes(spool_dir: &Path) -> Result<(u64, u64)> {
     Ok((total, free))
 }
 
-fn load_sensor_map(path: &Path) -> Result<SensorMap> {
+pub fn load_sensor_map(path: &Path) -> Result<SensorMap> {
     if !path.exists() {
         return Ok(SensorMap::empty());
     }
@@ -375,43 +589,26 @@ fn persist_sensor_map(path: &Path, map: &SensorMap) -> Result<()> {
     Ok(())
 }
 
-fn load_or_init_state(
-    state_path: &Path,
-) -> Result<(Uuid, u64, u64, Option<u64>, Vec<LossRange>)> {
+fn load_or_init_state(state_path: &Path) -> Result<(Uuid, SpoolStateDisk)> {
     if state_path.exists() {
         let raw = fs::read_to_string(state_path).context("read state")?;
-        let parsed: SpoolStateDisk = serde_json::from_str(&raw).context("parse state")?;
+        let mut parsed: SpoolStateDisk = serde_json::from_str(&raw).context("parse state")?;
         let stream_id = Uuid::parse_str(&parsed.stream_id).context("invalid stream_id")?;
-        return Ok((
-            stream_id,
-            parsed.next_seq.max(1),
-            parsed.acked_seq,
-            parsed.open_segment_start_seq,
-            parsed.losses,
-        ));
+        parsed.next_seq = parsed.next_seq.max(1);
+        return Ok((stream_id, parsed));
     }
-    Ok((Uuid::new_v4(), 1, 0, None, Vec::new()))
-}
-
-fn persist_state(
-    state_path: &Path,
-    stream_id: Uuid,
-    next_seq: u64,
-    acked_seq: u64,
-    open_segment_start_seq: Option<u64>,
-    losses: &[LossRange],
-) -> Result<()> {
-    let tmp = state_path.with_extension("json.tmp");
-    let disk = SpoolStateDisk {
-        stream_id: stream_id.to_string(),
-        next_seq,
-        acked_seq,
-        open_segment_start_seq,
-        losses: losses.to_vec(),
-    };
-    fs::write(&tmp, serde_json::to_string_pretty(&disk)?).context("write state tmp")?;
-    fs::rename(&tmp, state_path).context("rename state")?;
-    Ok(())
+    let stream_id = Uuid::new_v4();
+    Ok((
+        stream_id,
+        SpoolStateDisk {
+            stream_id: stream_id.to_string(),
+            next_seq: 1,
+            acked_seq: 0,
+            open_segment_start_seq: None,
+            losses: Vec::new(),
+            upstream_acks: BTreeMap::new(),
+        },
+    ))
 }
 
 fn open_or_create_segment(
@@ -596,6 +793,22 @@ impl SpoolRuntime {
             });
         }
 
+        let clock_synced = clock::system_clock_synced();
+        if clock_synced == Some(true) && !self.anchors.current_synced() {
+            let mono_ms = clock::monotonic_ms();
+            if self
+                .anchors
+                .record_sync(self.next_seq, mono_ms, Utc::now().timestamp_millis())?
+            {
+                tracing::info!(seq = self.next_seq, "system clock synced; recorded clock anchor");
+            }
+        }
+        let default_time_quality = match clock_synced {
+            Some(true) => TimeQuality::Good,
+            Some(false) => TimeQuality::Unsynced,
+            None => TimeQuality::Unknown,
+        };
+
         let mut published = Vec::with_capacity(samples.len());
         for sample in samples {
             let sensor_id = sample.sensor_id.trim();
@@ -610,8 +823,8 @@ impl SpoolRuntime {
             let seq = self.next_seq;
             self.next_seq = self.next_seq.saturating_add(1);
 
-            let time_quality = sample.time_quality.unwrap_or(TimeQuality::Unknown);
-            let mono_ms = monotonic_ms();
+            let time_quality = sample.time_quality.unwrap_or(default_time_quality);
+            let mono_ms = clock::monotonic_ms();
 
             let payload = encode_sample_record(
                 sensor_idx,
@@ -638,6 +851,8 @@ impl SpoolRuntime {
                 stream_id: self.stream_id,
                 time_quality,
                 monotonic_ms: mono_ms,
+                repair: None,
+                merged_seqs: Vec::new(),
             });
 
             if self.segment.created_at.elapsed() >= self.config.segment_roll_duration
@@ -653,9 +868,22 @@ impl SpoolRuntime {
             }
         }
 
-        // Best-effort publish of live samples (bounded by channel capacity).
+        // Best-effort publish of live samples (bounded by channel capacity). Samples that still
+        // need a clock sync stay in the spool for replay.
+        let now_mono_ms = clock::monotonic_ms();
+        let max_hold_ms = self.config.unsynced_hold.as_millis() as u64;
         for item in &published {
-            if self.publish_tx.try_send(item.clone()).is_err() {
+            let repair = self.anchors.repair(
+                item.seq,
+                item.monotonic_ms,
+                item.time_quality,
+                now_mono_ms,
+                max_hold_ms,
+            );
+            let Some(item) = item.clone().with_time_repair(repair) else {
+                continue;
+            };
+            if self.publish_tx.try_send(item).is_err() {
                 break;
             }
         }
@@ -710,6 +938,15 @@ impl SpoolRuntime {
         Ok(())
     }
 
+    fn apply_settings(&mut self, version: String, settings: &ForwarderSettings) -> Result<Tuning> {
+        let tuning = self.base_tuning.with_settings(settings)?;
+        tuning.apply_to(&mut self.config);
+        self.config_version = Some(version);
+        // A smaller budget or a new age limit takes effect now rather than at the next append.
+        self.enforce_caps()?;
+        Ok(tuning)
+    }
+
     fn enforce_caps(&mut self) -> Result<()> {
         let max_bytes = self.config.max_spool_bytes;
         let free_bytes = compute_free_bytes(&self.config.spool_dir).ok();
@@ -721,15 +958,35 @@ impl SpoolRuntime {
             return Ok(());
         }
 
+        let over_budget = |runtime: &Self| {
+            runtime.spool_bytes > max_bytes
+                || compute_free_bytes(&runtime.config.spool_dir)
+                    .map(|free| free < runtime.config.keep_free_bytes)
+                    .unwrap_or(false)
+        };
+
+        // Degrade before dropping: compact the oldest closed segments, first averaging `low`
+        // sensors, then `normal` ones. Whole segments are only dropped once that is not enough.
+        if over_budget(self) {
+            let mut segments = list_closed_segments(&self.config.spool_dir, self.stream_id)?;
+            segments.sort_by_key(|seg| seg.start_seq);
+            'levels: for level in 1..=2 {
+                for seg in &segments {
+                    if !over_budget(self) {
+                        break 'levels;
+                    }
+                    if seg.end_seq > self.acked_seq {
+                        self.compact_closed_segment(seg, level);
+                    }
+                }
+            }
+        }
+
         let mut segments = list_closed_segments(&self.config.spool_dir, self.stream_id)?;
         segments.sort_by_key(|seg| seg.start_seq);
 
         let mut now = Utc::now();
-        while self.spool_bytes > max_bytes
-            || compute_free_bytes(&self.config.spool_dir)
-                .map(|free| free < self.config.keep_free_bytes)
-                .unwrap_or(false)
-        {
+        while over_budget(self) {
             let Some(seg) = segments.first().cloned() else {
                 break;
             };
@@ -750,42 +1007,123 @@ impl SpoolRuntime {
         Ok(())
     }
 
+    fn compact_closed_segment(&mut self, seg: &ClosedSegment, level: u32) {
+        match segment::read_compaction_level(&seg.path) {
+            Ok(current) if current < level => {}
+            Ok(_) => return,
+            Err(err) => {
+                tracing::debug!(error=%err, path=%seg.path.display(), "failed to read segment header");
+                return;
+            }
+        }
+
+        let plan = CompactionPlan {
+            level,
+            low_bucket_ms: self.config.downsample_low.as_millis() as i64,
+            normal_bucket_ms: (level >= 2).then_some(self.config.downsample_normal.as_millis() as i64),
+        };
+        let priorities = &self.config.sensor_priorities;
+        let by_index = &self.sensor_map.by_index;
+        let class_for = |idx: u32| {
+            by_index
+                .get(&idx)
+                .map(|sensor_id| priorities.class_for(sensor_id))
+                .unwrap_or(PriorityClass::Normal)
+        };
+        let outcome = match segment::compact_segment(&seg.path, plan, class_for) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                tracing::warn!(error=%err, path=%seg.path.display(), "failed to compact spool segment");
+                return;
+            }
+        };
+
+        self.spool_bytes = self
+            .spool_bytes
+            .saturating_sub(outcome.old_bytes)
+            .saturating_add(outcome.new_bytes);
+        tracing::info!(
+            start_seq = seg.start_seq,
+            end_seq = seg.end_seq,
+            level,
+            old_bytes = outcome.old_bytes,
+            new_bytes = outcome.new_bytes,
+            merged_samples = outcome.merged_samples,
+            "compacted spool segment"
+        );
+        if outcome.merged_samples > 0 {
+            self.record_loss(seg.start_seq, seg.end_seq, LossKind::Downsampled, Utc::now());
+        }
+    }
+
     fn drop_segment(&mut self, seg: ClosedSegment, now: DateTime<Utc>) -> Result<()> {
         let size = seg.size_bytes;
         fs::remove_file(&seg.path).ok();
         self.spool_bytes = self.spool_bytes.saturating_sub(size);
 
         if seg.end_seq > self.acked_seq {
-            let loss = LossRange {
-                start_seq: seg.start_seq,
-                end_seq: seg.end_seq,
-                dropped_at: now.to_rfc3339(),
-            };
-            self.losses.push(loss);
-            if let Some(last) = self.losses.last().cloned() {
-                let _ = self.loss_tx.try_send(LossEvent {
-                    stream_id: self.stream_id,
-                    range: last,
-                });
-            }
+            // A dropped range supersedes any downsampled record for the same segment.
+            self.losses.retain(|loss| {
+                !(loss.kind == LossKind::Downsampled
+                    && loss.start_seq == seg.start_seq
+                    && loss.end_seq == seg.end_seq)
+            });
+            self.record_loss(seg.start_seq, seg.end_seq, LossKind::Dropped, now);
         }
         Ok(())
     }
 
+    fn record_loss(&mut self, start_seq: u64, end_seq: u64, kind: LossKind, now: DateTime<Utc>) {
+        let loss = LossRange {
+            start_seq,
+            end_seq,
+            dropped_at: now.to_rfc3339(),
+            kind,
+        };
+        self.losses.push(loss.clone());
+        let _ = self.loss_tx.try_send(LossEvent {
+            stream_id: self.stream_id,
+            range: loss,
+        });
+    }
+
     fn prune_losses(&mut self) {
         // Once the controller ACK has advanced beyond a loss range, it must have accepted the skip.
         self.losses.retain(|loss| loss.end_seq > self.acked_seq);
     }
 
     fn persist_state(&self) -> Result<()> {
-        persist_state(
-            &self.state_path,
-            self.stream_id,
-            self.next_seq,
-            self.acked_seq,
-            Some(self.segment.start_seq),
-            &self.losses,
-        )
+        SpoolStateDisk {
+            stream_id: self.stream_id.to_string(),
+            next_seq: self.next_seq,
+            acked_seq: self.acked_seq,
+            open_segment_start_seq: Some(self.segment.start_seq),
+            losses: self.losses.clone(),
+            upstream_acks: self.upstream_acks.clone(),
+        }
+        .store(&self.state_path)
+    }
+
+    /// Records an upstream's ACK. The spool only truncates up to the lowest ACK among the
+    /// upstreams it has heard from, so a standby that fell behind can still replay.
+    fn update_ack(&mut self, upstream: String, acked_seq: u64) {
+        let entry = self.upstream_acks.entry(upstream).or_insert(0);
+        if acked_seq <= *entry {
+            return;
+        }
+        *entry = acked_seq;
+        let floor = self.upstream_acks.values().copied().min().unwrap_or(acked_seq);
+        if floor > self.acked_seq {
+            self.acked_seq = floor;
+            if let Err(err) = self.delete_acked_segments() {
+                tracing::warn!(error=%err, "failed to delete acked segments");
+            }
+            self.prune_losses();
+            if let Err(err) = self.anchors.prune(floor) {
+                tracing::warn!(error=%err, "failed to prune clock anchors");
+            }
+        }
+        let _ = self.persist_state();
     }
 
     fn status(&self) -> SpoolStatus {
@@ -833,10 +1171,18 @@ impl SpoolRuntime {
             backlog_samples,
             replay_msgs_per_sec: self.config.replay_msgs_per_sec,
             replay_bytes_per_sec: self.config.replay_bytes_per_sec,
+            max_spool_age_seconds: self.config.max_spool_age.map(|age| age.as_secs()),
+            segment_roll_bytes: self.config.segment_roll_bytes,
+            segment_roll_seconds: self.config.segment_roll_duration.as_secs(),
+            config_version: self.config_version.clone(),
             estimated_drain_seconds,
             losses_pending: self.losses.len(),
             losses,
             oldest_unacked_timestamp_ms,
+            clock_synced: self.anchors.current_synced(),
+            active_upstream: self.active_upstream.clone(),
+            upstream_acks: self.upstream_acks.clone(),
+            replay_policy: self.replay_policy.clone(),
         }
     }
 }
@@ -917,44 +1263,6 @@ fn encode_sample_record(
     buf
 }
 
-#[derive(Debug, Clone)]
-struct SampleRecord {
-    seq: u64,
-    timestamp_ms: i64,
-}
-
-fn decode_sample_record(buf: &[u8]) -> Option<SampleRecord> {
-    if buf.len() != SAMPLE_RECORD_LEN {
-        return None;
-    }
-    let seq = u64::from_le_bytes(buf[4..12].try_into().ok()?);
-    let timestamp_ms = i64::from_le_bytes(buf[12..20].try_into().ok()?);
-    Some(SampleRecord {
-        seq,
-        timestamp_ms,
-    })
-}
-
-fn monotonic_ms() -> u64 {
-    #[cfg(target_os = "linux")]
-    unsafe {
-        let mut ts = libc::timespec {
-            tv_sec: 0,
-            tv_nsec: 0,
-        };
-        if libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) != 0 {
-            return 0;
-        }
-        let secs = ts.tv_sec.max(0) as u64;
-        let nanos = ts.tv_nsec.max(0) as u64;
-        secs.saturating_mul(1000) + nanos / 1_000_000
-    }
-    #[cfg(not(target_os = "linux"))]
-    {
-        0
-    }
-}
-
 fn find_oldest_unacked_timestamp_ms(
     spool_dir: &Path,
     stream_id: Uuid,
@@ -977,23 +1285,12 @@ fn find_oldest_unacked_timestamp_ms(
     paths.sort_by_key(|(start, _)| *start);
 
     for (_start, path) in paths {
-        let mut file = match fs::File::open(&path) {
-            Ok(file) => file,
-            Err(_) => continue,
-        };
-        if file.metadata().map(|m| m.len()).unwrap_or(0) < SEGMENT_HEADER_LEN as u64 {
+        let Ok(Some(mut reader)) = SegmentReader::open(&path) else {
             continue;
-        }
-        file.seek(SeekFrom::Start(SEGMENT_HEADER_LEN as u64))?;
-        loop {
-            let Some(payload) = read_next_frame_payload(&mut file)? else {
-                break;
-            };
-            let Some(record) = decode_sample_record(&payload) else {
-                break;
-            };
-            if record.seq > acked_seq {
-                return Ok(Some(record.timestamp_ms));
+        };
+        while let Some(entry) = reader.next_entry()? {
+            if entry.last_seq() > acked_seq {
+                return Ok(Some(entry.timestamp_ms));
             }
         }
     }
@@ -1001,34 +1298,6 @@ fn find_oldest_unacked_timestamp_ms(
     Ok(None)
 }
 
-fn read_next_frame_payload(file: &mut fs::File) -> Result<Option<Vec<u8>>> {
-    let mut header = [0u8; 8];
-    match file.read_exact(&mut header) {
-        Ok(()) => {}
-        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
-        Err(err) => return Err(err.into()),
-    }
-
-    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
-    let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
-    if len == 0 || len > 1024 * 1024 {
-        return Ok(None);
-    }
-
-    let mut payload = vec![0u8; len];
-    match file.read_exact(&mut payload) {
-        Ok(()) => {}
-        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
-        Err(err) => return Err(err.into()),
-    }
-
-    if crc32c(&payload) != crc {
-        return Ok(None);
-    }
-
-    Ok(Some(payload))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1037,13 +1306,17 @@ mod tests {
     fn test_config(spool_dir: &Path) -> Config {
         Config {
             node_id: "test-node".to_string(),
-            mqtt_host: "127.0.0.1".to_string(),
-            mqtt_port: 1883,
-            mqtt_username: None,
-            mqtt_password: None,
+            upstreams: Vec::new(),
+            failover_after: 3,
+            upstream_probe_interval: std::time::Duration::from_secs(15),
+            failback_after: std::time::Duration::from_secs(120),
             mqtt_topic_prefix: "iot".to_string(),
             mqtt_client_id: "node-forwarder-test".to_string(),
             http_bind: "127.0.0.1:0".to_string(),
+            sensor_id_map_path: None,
+            influx_udp_bind: None,
+            statsd_bind: None,
+            ingest_socket_path: None,
             spool_dir: spool_dir.to_path_buf(),
             segment_roll_duration: std::time::Duration::from_secs(3600),
             segment_roll_bytes: 128 * 1024 * 1024,
@@ -1053,6 +1326,15 @@ mod tests {
             max_spool_age: None,
             replay_msgs_per_sec: 2000,
             replay_bytes_per_sec: 10 * 1024 * 1024,
+            replay_policy: Default::default(),
+            unsynced_hold: std::time::Duration::from_secs(86_400),
+            sensor_priorities: Default::default(),
+            downsample_low: std::time::Duration::from_secs(60),
+            downsample_normal: std::time::Duration::from_secs(300),
+            edge_offline_after: std::time::Duration::from_secs(60),
+            output_command_url: "http://127.0.0.1:9000/v1/local/outputs/{output_id}/command"
+                .to_string(),
+            output_command_token: None,
         }
     }
 
@@ -1113,17 +1395,23 @@ mod tests {
         fs::write(&seg2, vec![0u8; 1024]).unwrap();
 
         let mut runtime = SpoolRuntime {
+            base_tuning: Tuning::from_config(&config),
+            config_version: None,
             config,
             state_path: dir.path().join("state.json"),
             sensor_map_path: dir.path().join("sensor_map.json"),
             stream_id,
             next_seq: 21,
             acked_seq: 10,
+            upstream_acks: BTreeMap::new(),
+            active_upstream: None,
+            replay_policy: None,
             losses: Vec::new(),
             sensor_map: SensorMap::empty(),
             spool_bytes: 2048,
             segment,
             last_sync_at: Instant::now(),
+            anchors: ClockAnchors::load(dir.path(), "test-boot").unwrap(),
             publish_tx: mpsc::channel(1).0,
             loss_tx: mpsc::channel(1).0,
         };
@@ -1134,6 +1422,61 @@ mod tests {
         assert!(seg2.exists());
     }
 
+    #[test]
+    fn update_ack_truncates_only_to_the_lowest_upstream_ack() {
+        let dir = TempDir::new().unwrap();
+        let config = test_config(dir.path());
+
+        fs::create_dir_all(&config.spool_dir).unwrap();
+        let stream_id = Uuid::new_v4();
+
+        let (segment, _) = create_new_segment(&config, stream_id, 21).unwrap();
+
+        let seg1 = segment_closed_path(&config.spool_dir, stream_id, 1, 10);
+        fs::write(&seg1, vec![0u8; 1024]).unwrap();
+        let seg2 = segment_closed_path(&config.spool_dir, stream_id, 11, 20);
+        fs::write(&seg2, vec![0u8; 1024]).unwrap();
+
+        let mut runtime = SpoolRuntime {
+            base_tuning: Tuning::from_config(&config),
+            config_version: None,
+            config,
+            state_path: dir.path().join("state.json"),
+            sensor_map_path: dir.path().join("sensor_map.json"),
+            stream_id,
+            next_seq: 21,
+            acked_seq: 0,
+            upstream_acks: BTreeMap::new(),
+            active_upstream: None,
+            replay_policy: None,
+            losses: Vec::new(),
+            sensor_map: SensorMap::empty(),
+            spool_bytes: 2048,
+            segment,
+            last_sync_at: Instant::now(),
+            anchors: ClockAnchors::load(dir.path(), "test-boot").unwrap(),
+            publish_tx: mpsc::channel(1).0,
+            loss_tx: mpsc::channel(1).0,
+        };
+
+        runtime.update_ack("primary:1883".to_string(), 5);
+        runtime.update_ack("standby:1883".to_string(), 5);
+        runtime.update_ack("primary:1883".to_string(), 20);
+        assert_eq!(runtime.acked_seq, 5);
+        assert!(seg1.exists());
+
+        runtime.update_ack("standby:1883".to_string(), 10);
+        runtime.update_ack("primary:1883".to_string(), 15);
+        assert_eq!(runtime.acked_seq, 10);
+        assert_eq!(runtime.upstream_acks["primary:1883"], 20);
+        assert!(!seg1.exists());
+        assert!(seg2.exists());
+
+        let state = SpoolStateDisk::load(dir.path()).unwrap();
+        assert_eq!(state.acked_seq, 10);
+        assert_eq!(state.upstream_acks.len(), 2);
+    }
+
     #[test]
     fn enforce_caps_drops_oldest_and_records_loss() {
         let dir = TempDir::new().unwrap();
@@ -1150,17 +1493,23 @@ mod tests {
         fs::write(&seg2, vec![0u8; 1000]).unwrap();
 
         let mut runtime = SpoolRuntime {
+            base_tuning: Tuning::from_config(&config),
+            config_version: None,
             config,
             state_path: dir.path().join("state.json"),
             sensor_map_path: dir.path().join("sensor_map.json"),
             stream_id,
             next_seq: 21,
             acked_seq: 0,
+            upstream_acks: BTreeMap::new(),
+            active_upstream: None,
+            replay_policy: None,
             losses: Vec::new(),
             sensor_map: SensorMap::empty(),
             spool_bytes: 2000,
             segment,
             last_sync_at: Instant::now(),
+            anchors: ClockAnchors::load(dir.path(), "test-boot").unwrap(),
             publish_tx: mpsc::channel(1).0,
             loss_tx: mpsc::channel(1).0,
         };
@@ -1170,4 +1519,70 @@ mod tests {
         assert!(!seg1.exists());
         assert!(runtime.losses.iter().any(|loss| loss.start_seq == 1 && loss.end_seq == 10));
     }
+
+    #[test]
+    fn enforce_caps_downsamples_low_priority_before_dropping() {
+        let dir = TempDir::new().unwrap();
+        let mut config = test_config(dir.path());
+        config.sensor_priorities.low = vec!["soil-*".to_string()];
+
+        fs::create_dir_all(&config.spool_dir).unwrap();
+        let stream_id = Uuid::new_v4();
+        let (segment, _) = create_new_segment(&config, stream_id, 1).unwrap();
+
+        // 600 one-second samples for a low-priority sensor: ten one-minute buckets once compacted.
+        let seg1 = segment_closed_path(&config.spool_dir, stream_id, 1, 600);
+        let mut file = fs::File::create(&seg1).unwrap();
+        write_segment_header(&mut file, stream_id, 1).unwrap();
+        for seq in 1..=600u64 {
+            let payload = encode_sample_record(1, seq, seq as i64 * 1000, seq as f64, 0, TimeQuality::Good, seq);
+            file.write_all(&(payload.len() as u32).to_le_bytes()).unwrap();
+            file.write_all(&crc32c(&payload).to_le_bytes()).unwrap();
+            file.write_all(&payload).unwrap();
+        }
+        drop(file);
+        let raw_bytes = fs::metadata(&seg1).unwrap().len();
+        config.max_spool_bytes = raw_bytes / 2;
+
+        let mut sensor_map = SensorMap::empty();
+        sensor_map.get_or_insert("soil-moisture-1");
+        let mut runtime = SpoolRuntime {
+            base_tuning: Tuning::from_config(&config),
+            config_version: None,
+            config,
+            state_path: dir.path().join("state.json"),
+            sensor_map_path: dir.path().join("sensor_map.json"),
+            stream_id,
+            next_seq: 601,
+            acked_seq: 0,
+            upstream_acks: BTreeMap::new(),
+            active_upstream: None,
+            replay_policy: None,
+            losses: Vec::new(),
+            sensor_map,
+            spool_bytes: raw_bytes,
+            segment,
+            last_sync_at: Instant::now(),
+            anchors: ClockAnchors::load(dir.path(), "test-boot").unwrap(),
+            publish_tx: mpsc::channel(1).0,
+            loss_tx: mpsc::channel(1).0,
+        };
+
+        runtime.enforce_caps().unwrap();
+
+        assert!(seg1.exists());
+        assert!(runtime.spool_bytes <= runtime.config.max_spool_bytes);
+        assert_eq!(runtime.losses.len(), 1);
+        assert_eq!(runtime.losses[0].kind, LossKind::Downsampled);
+
+        let mut reader = SegmentReader::open(&seg1).unwrap().unwrap();
+        let mut entries = Vec::new();
+        while let Some(entry) = reader.next_entry().unwrap() {
+            entries.push(entry);
+        }
+        assert!(entries.len() <= 11);
+        let covered: usize = entries.iter().map(|entry| entry.merged_seqs.len() + 1).sum();
+        assert_eq!(covered, 600);
+        assert_eq!(entries.last().unwrap().last_seq(), 600);
+    }
 }
@@ -0,0 +1,703 @@
+//! `node-forwarder spool ...`: offline inspection and repair of a spool directory.
+
+use crate::config::Config;
+use crate::segment::{self, SegmentScan, SpoolEntry};
+use crate::spool::{self, LossKind, LossRange, SpoolStateDisk};
+use anyhow::{anyhow, Context, Result};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub const BUNDLE_FORMAT: &str = "node-forwarder-spool-bundle";
+pub const BUNDLE_VERSION: u32 = 1;
+
+const USAGE: &str = "\
+usage: node-forwarder spool <command> [--dir SPOOL_DIR] [options]
+
+commands:
+  inspect [--json]                      state, sensor map and per-segment summary
+  dump [--format csv|ndjson] [--sensor ID] [--from-seq N] [--to-seq N]
+                                        decode samples to stdout
+  verify                                full CRC scan; exits non-zero on damage
+  export --out FILE [--include-acked] [--node-id ID]
+                                        write a bundle the controller can import offline
+  reset-ack [--to-seq N]                rewind the ACK after the controller lost its state
+                                        (node-forwarder must be stopped)
+
+SPOOL_DIR defaults to NODE_FORWARDER_SPOOL_DIR.";
+
+pub fn run(args: &[String]) -> Result<()> {
+    let Some((command, rest)) = args.split_first() else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    let opts = Options::parse(rest)?;
+    match command.as_str() {
+        "inspect" => inspect(&opts),
+        "dump" => dump(&opts),
+        "verify" => verify(&opts),
+        "export" => export(&opts),
+        "reset-ack" => reset_ack(&opts),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        other => Err(anyhow!("unknown spool command {other:?}\n\n{USAGE}")),
+    }
+}
+
+struct Options {
+    values: HashMap<String, String>,
+    flags: Vec<String>,
+}
+
+impl Options {
+    const FLAGS: [&'static str; 2] = ["json", "include-acked"];
+
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut values = HashMap::new();
+        let mut flags = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(key) = arg.strip_prefix("--") else {
+                return Err(anyhow!("unexpected argument {arg:?}"));
+            };
+            if let Some((key, value)) = key.split_once('=') {
+                values.insert(key.to_string(), value.to_string());
+            } else if Self::FLAGS.contains(&key) {
+                flags.push(key.to_string());
+            } else {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| anyhow!("--{key} needs a value"))?;
+                values.insert(key.to_string(), value.clone());
+            }
+        }
+        Ok(Self { values, flags })
+    }
+
+    fn value(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.flags.iter().any(|flag| flag == key)
+    }
+
+    fn seq(&self, key: &str) -> Result<Option<u64>> {
+        self.value(key)
+            .map(|raw| raw.parse().with_context(|| format!("invalid --{key}")))
+            .transpose()
+    }
+
+    fn spool_dir(&self) -> Result<PathBuf> {
+        match self.value("dir") {
+            Some(dir) => Ok(PathBuf::from(dir)),
+            None => Ok(Config::from_env()?.spool_dir),
+        }
+    }
+}
+
+/// A segment file found in the spool directory, identified by its name.
+#[derive(Debug, Clone, Serialize)]
+struct SegmentFile {
+    #[serde(skip)]
+    path: PathBuf,
+    name: String,
+    stream_id: Uuid,
+    start_seq: u64,
+    /// `None` for the open segment.
+    end_seq: Option<u64>,
+}
+
+impl SegmentFile {
+    fn is_open(&self) -> bool {
+        self.end_seq.is_none()
+    }
+}
+
+/// Parses `seg-<uuid>-<start>-<end>.seg` and `seg-<uuid>-<start>.open`.
+fn parse_segment_name(name: &str) -> Option<(Uuid, u64, Option<u64>)> {
+    let rest = name.strip_prefix("seg-")?;
+    let stream_id = Uuid::parse_str(rest.get(..36)?).ok()?;
+    let rest = rest.get(36..)?.strip_prefix('-')?;
+    if let Some(range) = rest.strip_suffix(".seg") {
+        let (start, end) = range.split_once('-')?;
+        return Some((stream_id, start.parse().ok()?, Some(end.parse().ok()?)));
+    }
+    let start = rest.strip_suffix(".open")?;
+    Some((stream_id, start.parse().ok()?, None))
+}
+
+fn list_segment_files(spool_dir: &Path) -> Result<Vec<SegmentFile>> {
+    let mut out = Vec::new();
+    for entry in fs::read_dir(spool_dir).with_context(|| format!("read {}", spool_dir.display()))? {
+        let path = entry?.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|v| v.to_str())
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        let Some((stream_id, start_seq, end_seq)) = parse_segment_name(&name) else {
+            continue;
+        };
+        out.push(SegmentFile {
+            path,
+            name,
+            stream_id,
+            start_seq,
+            end_seq,
+        });
+    }
+    out.sort_by(|a, b| {
+        a.stream_id
+            .cmp(&b.stream_id)
+            .then(a.start_seq.cmp(&b.start_seq))
+    });
+    Ok(out)
+}
+
+fn load_state(spool_dir: &Path) -> Result<(SpoolStateDisk, Uuid)> {
+    let state = SpoolStateDisk::load(spool_dir)?;
+    let stream_id =
+        Uuid::parse_str(state.stream_id.trim()).context("invalid stream_id in state.json")?;
+    Ok((state, stream_id))
+}
+
+fn load_sensor_names(spool_dir: &Path) -> BTreeMap<u32, String> {
+    spool::load_sensor_map(&spool_dir.join("sensor_map.json"))
+        .map(|map| map.by_index.into_iter().collect())
+        .unwrap_or_default()
+}
+
+fn format_ts(timestamp_ms: i64) -> String {
+    Utc.timestamp_millis_opt(timestamp_ms)
+        .single()
+        .map(|ts| ts.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_else(|| timestamp_ms.to_string())
+}
+
+fn time_quality_str(entry: &SpoolEntry) -> &'static str {
+    match entry.time_quality {
+        spool::TimeQuality::Good => "good",
+        spool::TimeQuality::Unsynced => "unsynced",
+        spool::TimeQuality::Unknown => "unknown",
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SegmentReport {
+    #[serde(flatten)]
+    file: SegmentFile,
+    open: bool,
+    #[serde(flatten)]
+    scan: SegmentScan,
+}
+
+fn scan_all(spool_dir: &Path) -> Result<Vec<SegmentReport>> {
+    list_segment_files(spool_dir)?
+        .into_iter()
+        .map(|file| {
+            let scan = segment::scan_segment(&file.path, |_| {})?;
+            Ok(SegmentReport {
+                open: file.is_open(),
+                file,
+                scan,
+            })
+        })
+        .collect()
+}
+
+fn inspect(opts: &Options) -> Result<()> {
+    let spool_dir = opts.spool_dir()?;
+    let state = SpoolStateDisk::load(&spool_dir).ok();
+    let sensors = load_sensor_names(&spool_dir);
+    let segments = scan_all(&spool_dir)?;
+
+    if opts.flag("json") {
+        let report = json!({
+            "spool_dir": spool_dir,
+            "state": state,
+            "sensors": sensors,
+            "segments": segments,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("spool dir: {}", spool_dir.display());
+    match &state {
+        Some(state) => {
+            println!(
+                "stream {}  next_seq {}  acked_seq {}  backlog {}",
+                state.stream_id,
+                state.next_seq,
+                state.acked_seq,
+                state.next_seq.saturating_sub(state.acked_seq + 1)
+            );
+            for (upstream, acked_seq) in &state.upstream_acks {
+                println!("  upstream {upstream}  acked_seq {acked_seq}");
+            }
+            for loss in &state.losses {
+                println!(
+                    "  loss {:?} seq {}..={} at {}",
+                    loss.kind, loss.start_seq, loss.end_seq, loss.dropped_at
+                );
+            }
+        }
+        None => println!("state.json: missing or unreadable"),
+    }
+
+    println!("sensors ({}):", sensors.len());
+    for (idx, sensor_id) in &sensors {
+        println!("  {idx:>5}  {sensor_id}");
+    }
+
+    println!("segments ({}):", segments.len());
+    for seg in &segments {
+        let scan = &seg.scan;
+        let end = seg
+            .file
+            .end_seq
+            .map(|end| end.to_string())
+            .unwrap_or_else(|| "open".to_string());
+        let level = scan
+            .header
+            .as_ref()
+            .map(|header| header.compaction_level.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let span = match (scan.min_timestamp_ms, scan.max_timestamp_ms) {
+            (Some(min), Some(max)) => format!("{} .. {}", format_ts(min), format_ts(max)),
+            _ => "-".to_string(),
+        };
+        println!(
+            "  {}  seq {}..{}  {} bytes  level {}  {} samples  {} crc failures  {} trailing bytes  {}",
+            seg.file.name,
+            seg.file.start_seq,
+            end,
+            scan.bytes,
+            level,
+            scan.samples,
+            scan.crc_failures,
+            scan.trailing_bytes,
+            span
+        );
+    }
+    Ok(())
+}
+
+fn verify(opts: &Options) -> Result<()> {
+    let spool_dir = opts.spool_dir()?;
+    let current_stream = load_state(&spool_dir).ok().map(|(_, stream_id)| stream_id);
+    let segments = scan_all(&spool_dir)?;
+
+    let mut errors = 0usize;
+    let mut samples = 0u64;
+    for seg in &segments {
+        let scan = &seg.scan;
+        let name = &seg.file.name;
+        samples += scan.samples;
+        let mut problems = Vec::new();
+        match &scan.header {
+            None => problems.push("missing or invalid segment header".to_string()),
+            Some(header) => {
+                if header.stream_id != seg.file.stream_id {
+                    problems.push(format!(
+                        "header stream {} does not match file name",
+                        header.stream_id
+                    ));
+                }
+                if header.start_seq != seg.file.start_seq {
+                    problems.push(format!(
+                        "header start_seq {} does not match file name",
+                        header.start_seq
+                    ));
+                }
+            }
+        }
+        if scan.crc_failures > 0 {
+            problems.push(format!("{} frames fail CRC", scan.crc_failures));
+        }
+        if scan.undecodable_frames > 0 {
+            problems.push(format!("{} frames do not decode", scan.undecodable_frames));
+        }
+        if scan.trailing_bytes > 0 {
+            if seg.open {
+                println!(
+                    "note: {name}: {} bytes of torn tail (truncated when node-forwarder starts)",
+                    scan.trailing_bytes
+                );
+            } else {
+                problems.push(format!("{} unreadable trailing bytes", scan.trailing_bytes));
+            }
+        }
+        if scan.first_seq.is_some_and(|seq| seq < seg.file.start_seq)
+            || matches!((scan.last_seq, seg.file.end_seq), (Some(last), Some(end)) if last > end)
+        {
+            problems.push("holds seqs outside the range in its file name".to_string());
+        }
+        if current_stream.is_some_and(|stream_id| stream_id != seg.file.stream_id) {
+            println!("note: {name}: belongs to a previous stream");
+        }
+
+        errors += problems.len();
+        for problem in problems {
+            println!("error: {name}: {problem}");
+        }
+    }
+
+    if errors > 0 {
+        return Err(anyhow!("{errors} problems in {} segments", segments.len()));
+    }
+    println!("ok: {} segments, {samples} samples", segments.len());
+    Ok(())
+}
+
+fn dump(opts: &Options) -> Result<()> {
+    let spool_dir = opts.spool_dir()?;
+    let ndjson = match opts.value("format").unwrap_or("csv") {
+        "csv" => false,
+        "ndjson" => true,
+        other => return Err(anyhow!("unknown --format {other:?} (csv or ndjson)")),
+    };
+    let stream_id = match opts.value("stream") {
+        Some(raw) => Uuid::parse_str(raw).context("invalid --stream")?,
+        None => load_state(&spool_dir)?.1,
+    };
+    let sensor_filter = opts.value("sensor");
+    let from_seq = opts.seq("from-seq")?.unwrap_or(0);
+    let to_seq = opts.seq("to-seq")?.unwrap_or(u64::MAX);
+    let sensors = load_sensor_names(&spool_dir);
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if !ndjson {
+        writeln!(
+            out,
+            "seq,sensor_id,timestamp,timestamp_ms,value,quality,time_quality,mono_ms,merged_seqs"
+        )?;
+    }
+    let mut write_err: Option<io::Error> = None;
+    for file in list_segment_files(&spool_dir)? {
+        if file.stream_id != stream_id
+            || file.end_seq.is_some_and(|end| end < from_seq)
+            || file.start_seq > to_seq
+        {
+            continue;
+        }
+        segment::scan_segment(&file.path, |entry| {
+            if write_err.is_some() || entry.last_seq() < from_seq || entry.seq > to_seq {
+                return;
+            }
+            let sensor_id = sensors
+                .get(&entry.sensor_idx)
+                .cloned()
+                .unwrap_or_else(|| format!("unknown-{}", entry.sensor_idx));
+            if sensor_filter.is_some_and(|filter| filter != sensor_id) {
+                return;
+            }
+            let res = if ndjson {
+                let line = json!({
+                    "seq": entry.seq,
+                    "sensor_id": sensor_id,
+                    "timestamp": format_ts(entry.timestamp_ms),
+                    "timestamp_ms": entry.timestamp_ms,
+                    "value": entry.value,
+                    "quality": entry.quality,
+                    "time_quality": time_quality_str(entry),
+                    "mono_ms": entry.monotonic_ms,
+                    "merged_seqs": entry.merged_seqs,
+                });
+                writeln!(out, "{line}")
+            } else {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{},{}",
+                    entry.seq,
+                    csv_field(&sensor_id),
+                    format_ts(entry.timestamp_ms),
+                    entry.timestamp_ms,
+                    entry.value,
+                    entry.quality,
+                    time_quality_str(entry),
+                    entry.monotonic_ms,
+                    entry.merged_seqs.len()
+                )
+            };
+            if let Err(err) = res {
+                write_err = Some(err);
+            }
+        })?;
+        if let Some(err) = write_err.take() {
+            // A closed pipe (`| head`) is not an error worth reporting.
+            if err.kind() == io::ErrorKind::BrokenPipe {
+                return Ok(());
+            }
+            return Err(err.into());
+        }
+    }
+    out.flush().or_else(|err| match err.kind() {
+        io::ErrorKind::BrokenPipe => Ok(()),
+        _ => Err(err),
+    })?;
+    Ok(())
+}
+
+fn csv_field(raw: &str) -> String {
+    if raw.contains([',', '"', '\n']) {
+        format!("\"{}\"", raw.replace('"', "\"\""))
+    } else {
+        raw.to_string()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BundleManifest {
+    format: &'static str,
+    version: u32,
+    node_id: String,
+    stream_id: Uuid,
+    exported_at: String,
+    acked_seq: u64,
+    next_seq: u64,
+    include_acked: bool,
+    segments: Vec<BundleSegment>,
+    losses: Vec<LossRange>,
+}
+
+#[derive(Debug, Serialize)]
+struct BundleSegment {
+    /// Path inside the bundle.
+    path: String,
+    start_seq: u64,
+    first_seq: Option<u64>,
+    last_seq: Option<u64>,
+    bytes: u64,
+    samples: u64,
+    compaction_level: u32,
+    crc_failures: u64,
+}
+
+fn export(opts: &Options) -> Result<()> {
+    let spool_dir = opts.spool_dir()?;
+    let out_path = PathBuf::from(
+        opts.value("out")
+            .ok_or_else(|| anyhow!("--out is required"))?,
+    );
+    let include_acked = opts.flag("include-acked");
+    let node_id = match opts.value("node-id") {
+        Some(node_id) => node_id.to_string(),
+        None => Config::from_env()?.node_id,
+    };
+    let (state, stream_id) = load_state(&spool_dir)?;
+
+    let mut files = Vec::new();
+    let mut manifest_segments = Vec::new();
+    for file in list_segment_files(&spool_dir)? {
+        if file.stream_id != stream_id {
+            continue;
+        }
+        if !include_acked && file.end_seq.is_some_and(|end| end <= state.acked_seq) {
+            continue;
+        }
+        let scan = segment::scan_segment(&file.path, |_| {})?;
+        // Snapshot the length now: the open segment may still be growing under a running forwarder.
+        manifest_segments.push(BundleSegment {
+            path: format!("segments/{}", file.name),
+            start_seq: file.start_seq,
+            first_seq: scan.first_seq,
+            last_seq: scan.last_seq,
+            bytes: scan.bytes,
+            samples: scan.samples,
+            compaction_level: scan
+                .header
+                .as_ref()
+                .map(|h| h.compaction_level)
+                .unwrap_or(0),
+            crc_failures: scan.crc_failures,
+        });
+        files.push((file, scan.bytes));
+    }
+
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        version: BUNDLE_VERSION,
+        node_id,
+        stream_id,
+        exported_at: Utc::now().to_rfc3339(),
+        acked_seq: state.acked_seq,
+        next_seq: state.next_seq,
+        include_acked,
+        segments: manifest_segments,
+        losses: state.losses.clone(),
+    };
+
+    let tmp_path = out_path.with_extension("tmp");
+    let out =
+        fs::File::create(&tmp_path).with_context(|| format!("create {}", tmp_path.display()))?;
+    let mut builder = tar::Builder::new(BufWriter::new(out));
+    append_bytes(
+        &mut builder,
+        "manifest.json",
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    for name in ["state.json", "sensor_map.json", "clock_anchors.json"] {
+        let path = spool_dir.join(name);
+        if path.exists() {
+            let raw = fs::read(&path).with_context(|| format!("read {}", path.display()))?;
+            append_bytes(&mut builder, name, &raw)?;
+        }
+    }
+    let mut samples = 0u64;
+    for ((file, len), entry) in files.iter().zip(&manifest.segments) {
+        let source =
+            fs::File::open(&file.path).with_context(|| format!("open {}", file.path.display()))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(*len);
+        header.set_mode(0o644);
+        header.set_mtime(Utc::now().timestamp().max(0) as u64);
+        builder
+            .append_data(&mut header, &entry.path, source.take(*len))
+            .with_context(|| format!("add {}", file.name))?;
+        samples += entry.samples;
+    }
+    builder.into_inner()?.flush()?;
+    fs::rename(&tmp_path, &out_path)
+        .with_context(|| format!("rename to {}", out_path.display()))?;
+
+    println!(
+        "wrote {}: stream {}, {} segments, {} samples (acked_seq {})",
+        out_path.display(),
+        stream_id,
+        manifest.segments.len(),
+        samples,
+        state.acked_seq
+    );
+    Ok(())
+}
+
+fn append_bytes<W: Write>(builder: &mut tar::Builder<W>, path: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    builder
+        .append_data(&mut header, path, data)
+        .with_context(|| format!("add {path}"))?;
+    Ok(())
+}
+
+fn reset_ack(opts: &Options) -> Result<()> {
+    let spool_dir = opts.spool_dir()?;
+    let _lock = spool::lock_spool_dir(&spool_dir)
+        .context("stop node-forwarder before resetting the ACK")?;
+    let (mut state, stream_id) = load_state(&spool_dir)?;
+
+    // Everything still on disk is replayed; the controller is told it may skip the rest.
+    let oldest_start = list_segment_files(&spool_dir)?
+        .into_iter()
+        .filter(|file| file.stream_id == stream_id)
+        .map(|file| file.start_seq)
+        .min()
+        .unwrap_or(state.next_seq);
+    let default_seq = oldest_start.saturating_sub(1).min(state.acked_seq);
+    let to_seq = opts.seq("to-seq")?.unwrap_or(default_seq);
+    if to_seq > state.acked_seq {
+        return Err(anyhow!(
+            "--to-seq {to_seq} is past acked_seq {}; reset-ack only rewinds",
+            state.acked_seq
+        ));
+    }
+
+    let previous = state.acked_seq;
+    state.acked_seq = to_seq;
+    for acked_seq in state.upstream_acks.values_mut() {
+        *acked_seq = (*acked_seq).min(to_seq);
+    }
+    state.losses.retain(|loss| loss.end_seq > to_seq);
+    if to_seq > 0 {
+        state.losses.insert(
+            0,
+            LossRange {
+                start_seq: 1,
+                end_seq: to_seq,
+                dropped_at: Utc::now().to_rfc3339(),
+                kind: LossKind::Dropped,
+            },
+        );
+    }
+    state.store(&spool_dir.join("state.json"))?;
+
+    println!("acked_seq {previous} -> {to_seq} for stream {stream_id}");
+    if to_seq > 0 {
+        println!("seqs 1..={to_seq} are reported as lost so the controller can ACK past them");
+    }
+    println!(
+        "{} samples will replay once node-forwarder starts",
+        state.next_seq.saturating_sub(to_seq + 1)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn parses_segment_names() {
+        let stream_id = Uuid::new_v4();
+        assert_eq!(
+            parse_segment_name(&format!("seg-{stream_id}-11-20.seg")),
+            Some((stream_id, 11, Some(20)))
+        );
+        assert_eq!(
+            parse_segment_name(&format!("seg-{stream_id}-21.open")),
+            Some((stream_id, 21, None))
+        );
+        assert_eq!(
+            parse_segment_name(&format!("seg-{stream_id}-21.seg.tmp")),
+            None
+        );
+    }
+
+    #[test]
+    fn reset_ack_rewinds_to_oldest_segment_and_reports_the_gap() {
+        let dir = TempDir::new().unwrap();
+        let stream_id = Uuid::new_v4();
+        SpoolStateDisk {
+            stream_id: stream_id.to_string(),
+            next_seq: 31,
+            acked_seq: 25,
+            open_segment_start_seq: Some(21),
+            losses: Vec::new(),
+            upstream_acks: BTreeMap::from([
+                ("controller:1883".to_string(), 30),
+                ("standby:1883".to_string(), 25),
+            ]),
+        }
+        .store(&dir.path().join("state.json"))
+        .unwrap();
+        fs::write(dir.path().join(format!("seg-{stream_id}-11-20.seg")), b"").unwrap();
+        fs::write(dir.path().join(format!("seg-{stream_id}-21.open")), b"").unwrap();
+
+        let args = vec!["--dir".to_string(), dir.path().display().to_string()];
+        reset_ack(&Options::parse(&args).unwrap()).unwrap();
+
+        let state = SpoolStateDisk::load(dir.path()).unwrap();
+        assert_eq!(state.acked_seq, 10);
+        assert!(state.upstream_acks.values().all(|acked_seq| *acked_seq == 10));
+        assert_eq!(state.losses.len(), 1);
+        assert_eq!(
+            (state.losses[0].start_seq, state.losses[0].end_seq),
+            (1, 10)
+        );
+    }
+}
@@ -5,7 +5,7 @@ use rumqttc::{AsyncClient, Event, MqttOptions, QoS};
 use serde_json::json;
 use sqlx::PgPool;
 use sqlx::Row;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::time::sleep;
@@ -17,6 +17,8 @@ pub enum AckCommand {
         node_mqtt_id: String,
         stream_id: Uuid,
         seqs: Vec<u64>,
+        /// Sample timestamps by seq, used to place loss ranges in time.
+        sample_times: Vec<(u64, DateTime<Utc>)>,
     },
     LossRange {
         node_mqtt_id: String,
@@ -25,6 +27,7 @@ pub enum AckCommand {
         end_seq: u64,
         dropped_at: Option<DateTime<Utc>>,
         reason: Option<String>,
+        kind: Option<String>,
     },
 }
 
@@ -34,6 +37,13 @@ struct LossRange {
     end_seq: u64,
 }
 
+/// A recorded loss event still waiting for the first sample after its range.
+#[derive(Debug, Clone)]
+struct OpenLoss {
+    id: i64,
+    end_seq: u64,
+}
+
 #[derive(Debug)]
 struct NodeAckState {
     stream_id: Uuid,
@@ -42,8 +52,15 @@ struct NodeAckState {
     loss_ranges: Vec<LossRange>,
     dirty: bool,
     last_published_acked_seq: u64,
+    /// Timestamps of `acked_seq` and the pending seqs above it.
+    seq_times: BTreeMap<u64, DateTime<Utc>>,
+    open_losses: Vec<OpenLoss>,
 }
 
+/// How often ACK state is re-read from the database, to pick up ACKs written by the controller
+/// (a spool bundle import) rather than by this process.
+const DB_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
 pub fn channel() -> (mpsc::UnboundedSender<AckCommand>, mpsc::UnboundedReceiver<AckCommand>) {
     mpsc::unbounded_channel()
 }
@@ -74,12 +91,20 @@ pub async fn run_ack_manager(
 
         let mut ticker = tokio::time::interval(Duration::from_secs(1));
         ticker.tick().await;
+        let mut refresh = tokio::time::interval(DB_REFRESH_INTERVAL);
+        refresh.tick().await;
 
         loop {
             tokio::select! {
                 _ = ticker.tick() => {
                     publish_acks(&client, &config, &pool, &mut state).await;
                 }
+                _ = refresh.tick() => {
+                    match load_state(&pool).await {
+                        Ok(fresh) => merge_db_state(&mut state, fresh),
+                        Err(err) => tracing::debug!(error=%err, "failed to refresh ack state"),
+                    }
+                }
                 cmd = rx.recv() => {
                     let Some(cmd) = cmd else { return Ok(()); };
                     if let Err(err) = apply_command(&pool, &mut state, cmd).await {
@@ -129,6 +154,8 @@ async fn load_state(pool: &PgPool) -> Result<HashMap<String, NodeAckState>> {
                 loss_ranges: Vec::new(),
                 dirty: false,
                 last_published_acked_seq: 0,
+                seq_times: BTreeMap::new(),
+                open_losses: Vec::new(),
             },
         );
     }
@@ -160,12 +187,40 @@ async fn load_state(pool: &PgPool) -> Result<HashMap<String, NodeAckState>> {
         }
     }
 
+    let open_rows = sqlx::query(
+        r#"
+        SELECT id, node_mqtt_id, stream_id, end_seq
+        FROM node_forwarder_loss_events
+        WHERE time_end IS NULL
+          AND received_at > NOW() - INTERVAL '30 days'
+        ORDER BY id
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("load open node_forwarder_loss_events")?;
+
+    for row in open_rows {
+        let node_mqtt_id: String = row.try_get("node_mqtt_id")?;
+        let stream_id: Uuid = row.try_get("stream_id")?;
+        let id: i64 = row.try_get("id")?;
+        let end_seq: i64 = row.try_get("end_seq")?;
+        if let Some(state) = out.get_mut(&node_mqtt_id) {
+            if state.stream_id == stream_id {
+                state.open_losses.push(OpenLoss {
+                    id,
+                    end_seq: end_seq.max(0) as u64,
+                });
+            }
+        }
+    }
+
     Ok(out)
 }
 
 async fn apply_command(pool: &PgPool, state: &mut HashMap<String, NodeAckState>, cmd: AckCommand) -> Result<()> {
     match cmd {
-        AckCommand::Committed { node_mqtt_id, stream_id, seqs } => {
+        AckCommand::Committed { node_mqtt_id, stream_id, seqs, sample_times } => {
             if node_mqtt_id.trim().is_empty() || seqs.is_empty() {
                 return Ok(());
             }
@@ -176,6 +231,8 @@ async fn apply_command(pool: &PgPool, state: &mut HashMap<String, NodeAckState>,
                 loss_ranges: Vec::new(),
                 dirty: false,
                 last_published_acked_seq: 0,
+                seq_times: BTreeMap::new(),
+                open_losses: Vec::new(),
             });
 
             if entry.stream_id != stream_id {
@@ -184,6 +241,8 @@ async fn apply_command(pool: &PgPool, state: &mut HashMap<String, NodeAckState>,
                 entry.acked_seq = 0;
                 entry.pending.clear();
                 entry.loss_ranges.clear();
+                entry.seq_times.clear();
+                entry.open_losses.clear();
                 entry.dirty = true;
             }
 
@@ -192,8 +251,18 @@ async fn apply_command(pool: &PgPool, state: &mut HashMap<String, NodeAckState>,
                     entry.pending.insert(seq);
                 }
             }
+            for (seq, timestamp) in sample_times {
+                if seq >= entry.acked_seq {
+                    entry.seq_times.insert(seq, timestamp);
+                }
+            }
 
-            if advance_acked_seq(entry) {
+            let advanced = advance_acked_seq(entry);
+            for (id, time_end) in resolve_open_losses(entry) {
+                update_loss_event_times(pool, id, None, Some(time_end)).await?;
+            }
+            prune_seq_times(entry);
+            if advanced {
                 persist_ack_state(pool, &node_mqtt_id, entry.stream_id, entry.acked_seq).await?;
                 entry.dirty = true;
             }
@@ -205,6 +274,7 @@ async fn apply_command(pool: &PgPool, state: &mut HashMap<String, NodeAckState>,
             end_seq,
             dropped_at,
             reason,
+            kind,
         } => {
             if node_mqtt_id.trim().is_empty() || start_seq == 0 || end_seq < start_seq {
                 return Ok(());
@@ -216,6 +286,8 @@ async fn apply_command(pool: &PgPool, state: &mut HashMap<String, NodeAckState>,
                 loss_ranges: Vec::new(),
                 dirty: false,
                 last_published_acked_seq: 0,
+                seq_times: BTreeMap::new(),
+                open_losses: Vec::new(),
             });
             if entry.stream_id != stream_id {
                 reset_node_state(pool, &node_mqtt_id, stream_id).await?;
@@ -223,9 +295,40 @@ async fn apply_command(pool: &PgPool, state: &mut HashMap<String, NodeAckState>,
                 entry.acked_seq = 0;
                 entry.pending.clear();
                 entry.loss_ranges.clear();
+                entry.seq_times.clear();
+                entry.open_losses.clear();
                 entry.dirty = true;
             }
 
+            // node-forwarder republishes pending ranges on every connect; only the first copy
+            // becomes an event.
+            let recorded = record_loss_event(
+                pool,
+                &node_mqtt_id,
+                stream_id,
+                start_seq,
+                end_seq,
+                dropped_at,
+                reason.as_deref(),
+                kind.as_deref().unwrap_or("dropped"),
+            )
+            .await?;
+            if let Some(id) = recorded {
+                let time_start = entry
+                    .seq_times
+                    .range(..start_seq)
+                    .next_back()
+                    .map(|(_, ts)| *ts);
+                entry.open_losses.push(OpenLoss { id, end_seq });
+                let time_end = resolve_open_losses(entry)
+                    .into_iter()
+                    .find(|(resolved, _)| *resolved == id)
+                    .map(|(_, ts)| ts);
+                if time_start.is_some() || time_end.is_some() {
+                    update_loss_event_times(pool, id, time_start, time_end).await?;
+                }
+            }
+
             persist_loss_range(pool, &node_mqtt_id, stream_id, start_seq, end_seq, dropped_at, reason).await?;
             entry.loss_ranges.push(LossRange { start_seq, end_seq });
             normalize_loss_ranges(&mut entry.loss_ranges);
@@ -234,11 +337,70 @@ async fn apply_command(pool: &PgPool, state: &mut HashMap<String, NodeAckState>,
                 persist_ack_state(pool, &node_mqtt_id, entry.stream_id, entry.acked_seq).await?;
                 entry.dirty = true;
             }
+            prune_seq_times(entry);
         }
     }
     Ok(())
 }
 
+/// Closes open losses whose following sample is known: the sample right after the range, or,
+/// once ACK has moved past the range, the first timestamped sample after it.
+fn resolve_open_losses(entry: &mut NodeAckState) -> Vec<(i64, DateTime<Utc>)> {
+    let mut resolved = Vec::new();
+    let seq_times = &entry.seq_times;
+    let acked_seq = entry.acked_seq;
+    entry.open_losses.retain(|loss| {
+        let next = loss.end_seq.saturating_add(1);
+        let found = seq_times
+            .range(next..)
+            .next()
+            .filter(|(seq, _)| **seq == next || acked_seq >= next);
+        match found {
+            Some((_, ts)) => {
+                resolved.push((loss.id, *ts));
+                false
+            }
+            None => true,
+        }
+    });
+    resolved
+}
+
+/// Keeps timestamps only for `acked_seq` and above; the ACKed sample is the one a new loss
+/// range starting right after it is measured from.
+fn prune_seq_times(entry: &mut NodeAckState) {
+    let keep = entry.seq_times.split_off(&entry.acked_seq);
+    entry.seq_times = keep;
+}
+
+/// Folds ACK state read back from the database into memory. The database wins when it is for a
+/// different stream or a higher `acked_seq`; anything adopted is re-published to the node.
+fn merge_db_state(state: &mut HashMap<String, NodeAckState>, fresh: HashMap<String, NodeAckState>) {
+    for (node_mqtt_id, mut db) in fresh {
+        let Some(entry) = state.get_mut(&node_mqtt_id) else {
+            db.dirty = true;
+            state.insert(node_mqtt_id, db);
+            continue;
+        };
+        if entry.stream_id != db.stream_id {
+            tracing::info!(node=%node_mqtt_id, stream_id=%db.stream_id, acked_seq=db.acked_seq, "adopting ack state from database");
+            db.dirty = true;
+            *entry = db;
+            continue;
+        }
+        if db.acked_seq > entry.acked_seq {
+            tracing::info!(node=%node_mqtt_id, acked_seq=db.acked_seq, "adopting higher acked_seq from database");
+            entry.acked_seq = db.acked_seq;
+            entry.pending.retain(|seq| *seq > db.acked_seq);
+            entry.loss_ranges.extend(db.loss_ranges);
+            normalize_loss_ranges(&mut entry.loss_ranges);
+            advance_acked_seq(entry);
+            prune_seq_times(entry);
+            entry.dirty = true;
+        }
+    }
+}
+
 fn normalize_loss_ranges(ranges: &mut Vec<LossRange>) {
     if ranges.len() <= 1 {
         return;
@@ -404,6 +566,64 @@ async fn persist_loss_range(
     Ok(())
 }
 
+/// Inserts the loss into the history table; `None` when it was already recorded.
+#[allow(clippy::too_many_arguments)]
+async fn record_loss_event(
+    pool: &PgPool,
+    node_mqtt_id: &str,
+    stream_id: Uuid,
+    start_seq: u64,
+    end_seq: u64,
+    dropped_at: Option<DateTime<Utc>>,
+    reason: Option<&str>,
+    kind: &str,
+) -> Result<Option<i64>> {
+    let id: Option<i64> = sqlx::query_scalar(
+        r#"
+        INSERT INTO node_forwarder_loss_events (
+            node_mqtt_id, stream_id, start_seq, end_seq, kind, reason, dropped_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (node_mqtt_id, stream_id, start_seq, end_seq) DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(node_mqtt_id)
+    .bind(stream_id)
+    .bind(start_seq as i64)
+    .bind(end_seq as i64)
+    .bind(kind)
+    .bind(reason)
+    .bind(dropped_at)
+    .fetch_optional(pool)
+    .await
+    .context("record loss event")?;
+    Ok(id)
+}
+
+async fn update_loss_event_times(
+    pool: &PgPool,
+    id: i64,
+    time_start: Option<DateTime<Utc>>,
+    time_end: Option<DateTime<Utc>>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE node_forwarder_loss_events
+        SET time_start = COALESCE(time_start, $2),
+            time_end = COALESCE(time_end, $3)
+        WHERE id = $1
+        "#
+    )
+    .bind(id)
+    .bind(time_start)
+    .bind(time_end)
+    .execute(pool)
+    .await
+    .context("update loss event times")?;
+    Ok(())
+}
+
 async fn prune_loss_ranges(pool: &PgPool, node_mqtt_id: &str, stream_id: Uuid, acked_seq: u64) -> Result<()> {
     sqlx::query(
         r#"
@@ -436,12 +656,77 @@ mod tests {
             loss_ranges: vec![LossRange { start_seq: 3, end_seq: 5 }],
             dirty: false,
             last_published_acked_seq: 0,
+            seq_times: BTreeMap::new(),
+            open_losses: Vec::new(),
         };
         state.pending.extend([1, 2, 6, 7]);
         assert!(advance_acked_seq(&mut state));
         assert_eq!(state.acked_seq, 7);
     }
 
+    #[test]
+    fn adopts_higher_or_foreign_ack_state_from_db() {
+        let stream_a = Uuid::new_v4();
+        let stream_b = Uuid::new_v4();
+        let node_state = |stream_id, acked_seq, pending: &[u64]| NodeAckState {
+            stream_id,
+            acked_seq,
+            pending: pending.iter().copied().collect(),
+            loss_ranges: Vec::new(),
+            dirty: false,
+            last_published_acked_seq: acked_seq,
+            seq_times: BTreeMap::new(),
+            open_losses: Vec::new(),
+        };
+        let mut state = HashMap::from([
+            ("pi-1".to_string(), node_state(stream_a, 10, &[12, 21, 22])),
+            ("pi-2".to_string(), node_state(stream_a, 50, &[])),
+            ("pi-3".to_string(), node_state(stream_a, 5, &[])),
+        ]);
+        let fresh = HashMap::from([
+            ("pi-1".to_string(), node_state(stream_a, 20, &[])),
+            ("pi-2".to_string(), node_state(stream_a, 40, &[])),
+            ("pi-3".to_string(), node_state(stream_b, 7, &[])),
+            ("pi-4".to_string(), node_state(stream_b, 3, &[])),
+        ]);
+        merge_db_state(&mut state, fresh);
+
+        let pi1 = &state["pi-1"];
+        assert_eq!(pi1.acked_seq, 22);
+        assert!(pi1.pending.is_empty());
+        assert!(pi1.dirty);
+        assert_eq!(state["pi-2"].acked_seq, 50);
+        assert!(!state["pi-2"].dirty);
+        assert_eq!(state["pi-3"].stream_id, stream_b);
+        assert_eq!(state["pi-3"].acked_seq, 7);
+        assert!(state["pi-4"].dirty);
+    }
+
+    #[test]
+    fn resolves_loss_end_from_the_next_sample() {
+        let ts = |secs: i64| DateTime::<Utc>::from_timestamp(secs, 0).unwrap();
+        let mut state = NodeAckState {
+            stream_id: Uuid::new_v4(),
+            acked_seq: 2,
+            pending: BTreeSet::new(),
+            loss_ranges: Vec::new(),
+            dirty: false,
+            last_published_acked_seq: 0,
+            seq_times: BTreeMap::from([(2, ts(100)), (9, ts(900))]),
+            open_losses: vec![OpenLoss { id: 1, end_seq: 5 }],
+        };
+        // seq 6 has not arrived; a later live sample must not close the range early.
+        assert!(resolve_open_losses(&mut state).is_empty());
+
+        state.seq_times.insert(6, ts(600));
+        assert_eq!(resolve_open_losses(&mut state), vec![(1, ts(600))]);
+        assert!(state.open_losses.is_empty());
+
+        state.acked_seq = 9;
+        prune_seq_times(&mut state);
+        assert_eq!(state.seq_times.keys().copied().collect::<Vec<_>>(), vec![9]);
+    }
+
     #[test]
     fn normalizes_loss_ranges() {
         let mut ranges = vec![
@@ -44,6 +44,7 @@ impl IngestService {
             seq: None,
             stream_id: None,
             backfill: false,
+            merged_seqs: Vec::new(),
         })
     }
 
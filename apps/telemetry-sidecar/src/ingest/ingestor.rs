@@ -3,7 +3,7 @@ use super::types::{Sample, SensorMeta};
 use super::{TelemetryIngestor, COV_TOLERANCE, STATUS_OFFLINE, STATUS_ONLINE};
 use crate::pipeline::IngestStats;
 use crate::predictive_feed::{PredictiveFeed, PredictiveFeedItem};
-use crate::telemetry::MetricRow;
+use crate::telemetry::{EdgeAlarmEvent, HeldSample, MetricRow};
 use anyhow::Result;
 use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use serde_json::json;
@@ -120,10 +120,13 @@ impl TelemetryIngestor {
                 metric.stream_id,
                 metric.seq,
             ) {
+                let mut seqs = vec![seq];
+                seqs.extend(&metric.merged_seqs);
                 let _ = tx.send(crate::ack::AckCommand::Committed {
                     node_mqtt_id,
                     stream_id,
-                    seqs: vec![seq],
+                    seqs,
+                    sample_times: vec![(seq, metric.timestamp)],
                 });
             }
         }
@@ -140,6 +143,7 @@ impl TelemetryIngestor {
                     seq: metric.seq,
                     stream_id: metric.stream_id,
                     backfill: metric.backfill,
+                    merged_seqs: metric.merged_seqs.clone(),
                 })
                 .await?;
             self.enqueue_predictive_feed(&meta.sensor_id, &row);
@@ -167,6 +171,7 @@ impl TelemetryIngestor {
         end_seq: u64,
         dropped_at: Option<DateTime<Utc>>,
         reason: Option<String>,
+        kind: Option<String>,
     ) {
         let Some(tx) = self.ack_tx.as_ref() else {
             return;
@@ -182,9 +187,87 @@ impl TelemetryIngestor {
             end_seq,
             dropped_at,
             reason,
+            kind,
         });
     }
 
+    /// Parks a sample with an untrustworthy timestamp in `metric_time_holding` and ACKs its seq so
+    /// node-forwarder can truncate past it.
+    pub async fn hold_sample(&self, held: HeldSample) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO metric_time_holding (
+                node_mqtt_id, sensor_id, reported_ts, value, quality, stream_id, seq, mono_ms, boot_id, reason
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(&held.node_mqtt_id)
+        .bind(&held.sensor_id)
+        .bind(held.reported_timestamp)
+        .bind(held.value)
+        .bind(held.quality)
+        .bind(held.stream_id)
+        .bind(held.seq.map(|seq| seq as i64))
+        .bind(held.mono_ms.map(|mono| mono as i64))
+        .bind(held.boot_id.as_deref())
+        .bind(&held.reason)
+        .execute(&self.pool)
+        .await?;
+
+        if let (Some(tx), Some(stream_id), Some(seq)) =
+            (self.ack_tx.as_ref(), held.stream_id, held.seq)
+        {
+            let mut seqs = vec![seq];
+            seqs.extend(&held.merged_seqs);
+            // The reported timestamp is the untrustworthy part, so it cannot place a loss range.
+            let _ = tx.send(crate::ack::AckCommand::Committed {
+                node_mqtt_id: held.node_mqtt_id,
+                stream_id,
+                seqs,
+                sample_times: Vec::new(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Parks an alarm transition a node decided while the controller was unreachable. The alarm
+    /// engine reconciles it with `alarm_events` on its next tick.
+    pub async fn record_edge_alarm(&self, event: EdgeAlarmEvent) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO edge_alarm_events (
+                node_mqtt_id, stream_id, seq, event_sensor_id, rule_id, transition, observed_value, occurred_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(&event.node_mqtt_id)
+        .bind(event.stream_id)
+        .bind(event.seq.map(|seq| seq as i64))
+        .bind(&event.event_sensor_id)
+        .bind(event.rule_id)
+        .bind(&event.transition)
+        .bind(event.observed_value)
+        .bind(event.occurred_at)
+        .execute(&self.pool)
+        .await?;
+
+        if let (Some(tx), Some(stream_id), Some(seq)) =
+            (self.ack_tx.as_ref(), event.stream_id, event.seq)
+        {
+            let _ = tx.send(crate::ack::AckCommand::Committed {
+                node_mqtt_id: event.node_mqtt_id,
+                stream_id,
+                seqs: vec![seq],
+                sample_times: vec![(seq, event.occurred_at)],
+            });
+        }
+        Ok(())
+    }
+
     async fn resolve_node_uuid(
         &self,
         node_identifier: &str,
@@ -671,6 +754,7 @@ impl TelemetryIngestor {
                     seq: None,
                     stream_id: None,
                     backfill: false,
+                    merged_seqs: Vec::new(),
                 })
                 .await?;
         }
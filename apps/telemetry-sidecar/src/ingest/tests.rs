@@ -186,6 +186,7 @@ async fn test_sidecar_ingest_offline_alarm() -> Result<()> {
             seq: None,
             stream_id: None,
             backfill: false,
+            merged_seqs: Vec::new(),
         })
         .await?;
     ingestor.flush().await?;
@@ -1,6 +1,6 @@
 use crate::config::Config;
 use crate::ingest::TelemetryIngestor;
-use crate::telemetry::parse_mqtt_payload;
+use crate::telemetry::{parse_mqtt_payload, TelemetryMessage};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
@@ -109,6 +109,18 @@ pub async fn run_listener(config: Config, ingestor: TelemetryIngestor) -> Result
                         let parts: Vec<&str> = publish.topic.split('/').collect();
                         if parts.len() == 3 {
                             if let Some(loss) = parse_loss_payload(&mut payload) {
+                                if loss.downsampled {
+                                    // Averages carry the seqs they replace, so nothing may be
+                                    // skipped; the range is only informational.
+                                    tracing::info!(
+                                        node = parts[1],
+                                        stream_id = %loss.stream_id,
+                                        start_seq = loss.start_seq,
+                                        end_seq = loss.end_seq,
+                                        "node-forwarder downsampled spooled telemetry"
+                                    );
+                                    continue;
+                                }
                                 ingestor.handle_loss_range(
                                     parts[1],
                                     loss.stream_id,
@@ -116,6 +128,7 @@ pub async fn run_listener(config: Config, ingestor: TelemetryIngestor) -> Result
                                     loss.end_seq,
                                     loss.dropped_at,
                                     loss.reason,
+                                    loss.kind,
                                 );
                             }
                         }
@@ -127,11 +140,21 @@ pub async fn run_listener(config: Config, ingestor: TelemetryIngestor) -> Result
                         &publish.topic,
                         &mut payload,
                     ) {
-                        Ok(Some(metric)) => {
+                        Ok(Some(TelemetryMessage::Metric(metric))) => {
                             if let Err(err) = ingestor.ingest_metric(metric).await {
                                 tracing::warn!(error=%err, "failed to ingest MQTT metric");
                             }
                         }
+                        Ok(Some(TelemetryMessage::Held(held))) => {
+                            if let Err(err) = ingestor.hold_sample(held).await {
+                                tracing::warn!(error=%err, "failed to hold uncorrectable sample");
+                            }
+                        }
+                        Ok(Some(TelemetryMessage::EdgeAlarm(event))) => {
+                            if let Err(err) = ingestor.record_edge_alarm(event).await {
+                                tracing::warn!(error=%err, "failed to record edge alarm event");
+                            }
+                        }
                         Ok(None) => {}
                         Err(err) => {
                             tracing::warn!(error=%err, topic=%publish.topic, "failed to decode MQTT payload")
@@ -158,6 +181,8 @@ struct ParsedLossPayload {
     end_seq: u64,
     dropped_at: Option<DateTime<Utc>>,
     reason: Option<String>,
+    kind: Option<String>,
+    downsampled: bool,
 }
 
 fn parse_loss_payload(payload: &mut [u8]) -> Option<ParsedLossPayload> {
@@ -175,6 +200,8 @@ fn parse_loss_payload(payload: &mut [u8]) -> Option<ParsedLossPayload> {
         dropped_at: Option<&'a str>,
         #[serde(default)]
         reason: Option<&'a str>,
+        #[serde(default)]
+        kind: Option<&'a str>,
     }
 
     let parsed: WireLoss = serde_json::from_slice(payload).ok()?;
@@ -194,6 +221,12 @@ fn parse_loss_payload(payload: &mut [u8]) -> Option<ParsedLossPayload> {
         end_seq: parsed.end_seq,
         dropped_at,
         reason,
+        kind: parsed
+            .kind
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string()),
+        downsampled: parsed.kind.map(str::trim) == Some("downsampled"),
     })
 }
 
@@ -439,10 +472,16 @@ fn parse_status_payload(payload: &mut [u8]) -> Option<ParsedNodeStatus> {
                                             .map(str::trim)
                                             .filter(|value| !value.is_empty())
                                             .map(|value| value.to_string());
+                                        let kind = obj
+                                            .get("kind")
+                                            .and_then(|value| value.as_str())
+                                            .unwrap_or("dropped")
+                                            .to_string();
                                         out.push(json!({
                                             "start_seq": start_seq,
                                             "end_seq": end_seq,
                                             "dropped_at": dropped_at,
+                                            "kind": kind,
                                         }));
                                     }
                                     Some(out)
@@ -1,7 +1,7 @@
 use crate::ack::AckCommand;
 use crate::telemetry::MetricRow;
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sqlx::{postgres::PgPoolOptions, PgPool, Postgres, QueryBuilder};
 use std::sync::{
     atomic::{AtomicBool, AtomicU64, Ordering},
@@ -192,7 +192,8 @@ async fn flush(
             }
 
             if let Some(ack_tx) = ack_tx {
-                let mut grouped: std::collections::HashMap<(String, uuid::Uuid), std::collections::BTreeSet<u64>> =
+                type Committed = (std::collections::BTreeSet<u64>, Vec<(u64, DateTime<Utc>)>);
+                let mut grouped: std::collections::HashMap<(String, uuid::Uuid), Committed> =
                     std::collections::HashMap::new();
                 for metric in &items {
                     let (Some(node_mqtt_id), Some(stream_id), Some(seq)) =
@@ -200,17 +201,20 @@ async fn flush(
                     else {
                         continue;
                     };
-                    grouped
+                    let (seqs, sample_times) = grouped
                         .entry((node_mqtt_id.clone(), stream_id))
-                        .or_default()
-                        .insert(seq);
+                        .or_default();
+                    seqs.insert(seq);
+                    seqs.extend(&metric.merged_seqs);
+                    sample_times.push((seq, metric.timestamp));
                 }
-                for ((node_mqtt_id, stream_id), seqs) in grouped {
+                for ((node_mqtt_id, stream_id), (seqs, sample_times)) in grouped {
                     let seqs: Vec<u64> = seqs.into_iter().collect();
                     let _ = ack_tx.send(AckCommand::Committed {
                         node_mqtt_id,
                         stream_id,
                         seqs,
+                        sample_times,
                     });
                 }
             }
@@ -3,6 +3,14 @@ use chrono::{DateTime, TimeZone, Utc};
 use serde::Deserialize;
 use uuid::Uuid;
 
+/// Bit set on `quality` when node-forwarder re-based the timestamp of a sample captured while
+/// the node clock was unsynced. Lower bits keep the sensor's own quality code.
+pub const QUALITY_TIME_CORRECTED: i32 = 0x40;
+
+/// Bit set on `quality` when the value is an average written by node-forwarder spool compaction
+/// (the spool was under disk pressure). `merged_seqs` lists the seqs it replaced.
+pub const QUALITY_DOWNSAMPLED: i32 = 0x80;
+
 #[derive(Debug, Clone)]
 pub struct MetricRow {
     pub sensor_id: String,
@@ -13,6 +21,8 @@ pub struct MetricRow {
     pub seq: Option<u64>,
     pub stream_id: Option<Uuid>,
     pub backfill: bool,
+    /// Further seqs covered by this row (a downsampled average); committed together with `seq`.
+    pub merged_seqs: Vec<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,6 +38,63 @@ struct BorrowedTelemetry<'a> {
     stream_id: Option<&'a str>,
     #[serde(default)]
     backfill: Option<bool>,
+    #[serde(default, borrow)]
+    time_quality: Option<&'a str>,
+    #[serde(default)]
+    mono_ms: Option<u64>,
+    #[serde(default, borrow)]
+    time_repair_error: Option<&'a str>,
+    #[serde(default, borrow)]
+    boot_id: Option<&'a str>,
+    #[serde(default)]
+    downsampled: Option<bool>,
+    #[serde(default)]
+    merged_seqs: Option<Vec<u64>>,
+    #[serde(default, borrow)]
+    edge_transition: Option<&'a str>,
+}
+
+/// A sample node-forwarder could not re-base (the node clock never synced in its boot session).
+/// It is kept out of `metrics` and parked in `metric_time_holding` for review.
+#[derive(Debug, Clone)]
+pub struct HeldSample {
+    pub node_mqtt_id: String,
+    pub sensor_id: String,
+    pub reported_timestamp: DateTime<Utc>,
+    pub value: f64,
+    pub quality: i32,
+    pub seq: Option<u64>,
+    pub stream_id: Option<Uuid>,
+    pub mono_ms: Option<u64>,
+    pub boot_id: Option<String>,
+    pub reason: String,
+    pub merged_seqs: Vec<u64>,
+}
+
+/// Sensor id prefix node-forwarder uses for alarm transitions it decided on its own while the
+/// controller was unreachable: `edge-alarm.<rule_id>.<target hash>`.
+pub const EDGE_ALARM_SENSOR_PREFIX: &str = "edge-alarm.";
+
+/// An alarm transition evaluated on a node. Parked in `edge_alarm_events` until the alarm engine
+/// reconciles it with `alarm_events`.
+#[derive(Debug, Clone)]
+pub struct EdgeAlarmEvent {
+    pub node_mqtt_id: String,
+    pub event_sensor_id: String,
+    pub rule_id: i64,
+    /// `fired` or `resolved`.
+    pub transition: String,
+    pub observed_value: f64,
+    pub occurred_at: DateTime<Utc>,
+    pub seq: Option<u64>,
+    pub stream_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TelemetryMessage {
+    Metric(MetricRow),
+    Held(HeldSample),
+    EdgeAlarm(EdgeAlarmEvent),
 }
 
 #[derive(Debug, Deserialize)]
@@ -62,7 +129,7 @@ pub fn parse_mqtt_payload(
     topic_prefix: &str,
     topic: &str,
     payload: &mut [u8],
-) -> Result<Option<MetricRow>> {
+) -> Result<Option<TelemetryMessage>> {
     let parts: Vec<&str> = topic.split('/').collect();
     if parts.len() != 4 || parts[0] != topic_prefix || parts[3] != "telemetry" {
         return Ok(None);
@@ -77,14 +144,66 @@ pub fn parse_mqtt_payload(
         .map(|t| t.to_datetime())
         .unwrap_or_else(Utc::now);
 
-    let quality = telemetry.quality.unwrap_or(0);
+    let mut quality = telemetry.quality.unwrap_or(0);
     let seq = telemetry.seq;
     let stream_id = telemetry
         .stream_id
         .and_then(|raw| Uuid::parse_str(raw.trim()).ok());
     let backfill = telemetry.backfill.unwrap_or(false);
+    let merged_seqs = telemetry.merged_seqs.unwrap_or_default();
+    if telemetry.downsampled.unwrap_or(false) {
+        quality |= QUALITY_DOWNSAMPLED;
+    }
+
+    if let Some(transition) = telemetry.edge_transition.map(str::trim) {
+        let rule_id = sensor_id
+            .strip_prefix(EDGE_ALARM_SENSOR_PREFIX)
+            .and_then(|rest| rest.split('.').next())
+            .and_then(|raw| raw.parse::<i64>().ok());
+        if let (Some(rule_id), "fired" | "resolved") = (rule_id, transition) {
+            return Ok(Some(TelemetryMessage::EdgeAlarm(EdgeAlarmEvent {
+                node_mqtt_id: parts[1].to_string(),
+                event_sensor_id: sensor_id,
+                rule_id,
+                transition: transition.to_string(),
+                observed_value: telemetry.value,
+                occurred_at: timestamp,
+                seq,
+                stream_id,
+            })));
+        }
+    }
+
+    match telemetry.time_quality.map(str::trim) {
+        Some("uncorrectable") => {
+            let reason = telemetry
+                .time_repair_error
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .unwrap_or("uncorrectable");
+            return Ok(Some(TelemetryMessage::Held(HeldSample {
+                node_mqtt_id: parts[1].to_string(),
+                sensor_id,
+                reported_timestamp: timestamp,
+                value: telemetry.value,
+                quality,
+                seq,
+                stream_id,
+                mono_ms: telemetry.mono_ms,
+                boot_id: telemetry
+                    .boot_id
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.to_string()),
+                reason: reason.to_string(),
+                merged_seqs,
+            })));
+        }
+        Some("corrected") => quality |= QUALITY_TIME_CORRECTED,
+        _ => {}
+    }
 
-    Ok(Some(MetricRow {
+    Ok(Some(TelemetryMessage::Metric(MetricRow {
         sensor_id,
         timestamp,
         value: telemetry.value,
@@ -93,5 +212,92 @@ pub fn parse_mqtt_payload(
         seq,
         stream_id,
         backfill,
-    }))
+        merged_seqs,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrected_samples_are_flagged_and_uncorrectable_samples_are_held() {
+        let mut corrected = br#"{"timestamp":1700000000000,"value":1.5,"quality":2,"seq":7,
+            "stream_id":"7b1f3c1e-8f5e-4a52-9a53-0f4a8f0f6b11","backfill":true,
+            "time_quality":"corrected","reported_timestamp":1000,"mono_ms":15000}"#
+            .to_vec();
+        let Some(TelemetryMessage::Metric(metric)) =
+            parse_mqtt_payload("iot", "iot/pi-1/temp-1/telemetry", &mut corrected).unwrap()
+        else {
+            panic!("expected metric");
+        };
+        assert_eq!(metric.quality, 2 | QUALITY_TIME_CORRECTED);
+        assert_eq!(metric.timestamp.timestamp_millis(), 1_700_000_000_000);
+
+        let mut uncorrectable = br#"{"timestamp":1000,"value":1.5,"seq":8,
+            "stream_id":"7b1f3c1e-8f5e-4a52-9a53-0f4a8f0f6b11","backfill":true,
+            "time_quality":"uncorrectable","time_repair_error":"never_synced",
+            "boot_id":"boot-a","mono_ms":16000}"#
+            .to_vec();
+        let Some(TelemetryMessage::Held(held)) =
+            parse_mqtt_payload("iot", "iot/pi-1/temp-1/telemetry", &mut uncorrectable).unwrap()
+        else {
+            panic!("expected held sample");
+        };
+        assert_eq!(held.node_mqtt_id, "pi-1");
+        assert_eq!(held.reason, "never_synced");
+        assert_eq!(held.boot_id.as_deref(), Some("boot-a"));
+        assert_eq!(held.seq, Some(8));
+        assert_eq!(held.reported_timestamp.timestamp_millis(), 1000);
+    }
+
+    #[test]
+    fn downsampled_samples_carry_their_merged_seqs() {
+        let mut payload = br#"{"timestamp":1700000000000,"value":3.25,"quality":0,"seq":10,
+            "stream_id":"7b1f3c1e-8f5e-4a52-9a53-0f4a8f0f6b11","backfill":true,
+            "time_quality":"good","downsampled":true,"samples":3,"merged_seqs":[11,12]}"#
+            .to_vec();
+        let Some(TelemetryMessage::Metric(metric)) =
+            parse_mqtt_payload("iot", "iot/pi-1/soil-1/telemetry", &mut payload).unwrap()
+        else {
+            panic!("expected metric");
+        };
+        assert_eq!(metric.quality, QUALITY_DOWNSAMPLED);
+        assert_eq!(metric.seq, Some(10));
+        assert_eq!(metric.merged_seqs, vec![11, 12]);
+    }
+
+    #[test]
+    fn edge_alarm_transitions_are_split_out_of_metrics() {
+        let mut payload = br#"{"timestamp":1700000000000,"value":82.5,"quality":1,"seq":21,
+            "stream_id":"7b1f3c1e-8f5e-4a52-9a53-0f4a8f0f6b11","backfill":true,
+            "time_quality":"good","edge_transition":"fired"}"#
+            .to_vec();
+        let Some(TelemetryMessage::EdgeAlarm(event)) = parse_mqtt_payload(
+            "iot",
+            "iot/pi-1/edge-alarm.42.0a1b2c3d4e5f/telemetry",
+            &mut payload,
+        )
+        .unwrap() else {
+            panic!("expected edge alarm event");
+        };
+        assert_eq!(event.node_mqtt_id, "pi-1");
+        assert_eq!(event.rule_id, 42);
+        assert_eq!(event.transition, "fired");
+        assert_eq!(event.observed_value, 82.5);
+        assert_eq!(event.seq, Some(21));
+        assert_eq!(event.occurred_at.timestamp_millis(), 1_700_000_000_000);
+
+        let mut unknown =
+            br#"{"timestamp":1700000000000,"value":1.0,"edge_transition":"paused"}"#.to_vec();
+        assert!(matches!(
+            parse_mqtt_payload(
+                "iot",
+                "iot/pi-1/edge-alarm.42.0a1b2c3d4e5f/telemetry",
+                &mut unknown
+            )
+            .unwrap(),
+            Some(TelemetryMessage::Metric(_))
+        ));
+    }
 }
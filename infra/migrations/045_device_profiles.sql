@@ -0,0 +1,21 @@
+-- User-defined external device profiles.
+--
+-- Rows are merged with the built-in catalog (shared/device_profiles/catalog.json) at
+-- runtime so new meter models can be added without rebuilding the controller. Built-in
+-- (vendor_id, model_id) pairs are rejected by the API rather than overridden here.
+
+CREATE TABLE IF NOT EXISTS device_profiles (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  vendor_id TEXT NOT NULL,
+  vendor_name TEXT NOT NULL,
+  model_id TEXT NOT NULL,
+  model_name TEXT NOT NULL,
+  since_year INTEGER,
+  protocols JSONB NOT NULL DEFAULT '[]'::jsonb,
+  -- Array of DevicePoint objects validated against shared/device_profiles/device_point.schema.json.
+  points JSONB NOT NULL DEFAULT '[]'::jsonb,
+  created_by TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  UNIQUE (vendor_id, model_id)
+);
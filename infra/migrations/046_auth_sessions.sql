@@ -0,0 +1,38 @@
+-- Persistent login sessions.
+--
+-- Only SHA-256 hashes of the access and refresh tokens are stored. Each refresh
+-- rotates both tokens; the previous refresh hash is kept so a replayed (stolen)
+-- refresh token can be detected and the whole session revoked.
+
+CREATE TABLE IF NOT EXISTS auth_sessions (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  source TEXT NOT NULL,
+  token_hash TEXT NOT NULL,
+  refresh_token_hash TEXT NOT NULL,
+  previous_refresh_token_hash TEXT,
+  client_ip TEXT,
+  user_agent TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  last_used_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  expires_at TIMESTAMPTZ NOT NULL,
+  refresh_expires_at TIMESTAMPTZ NOT NULL,
+  revoked_at TIMESTAMPTZ,
+  revoked_reason TEXT
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS auth_sessions_token_hash_uq
+ON auth_sessions(token_hash);
+
+CREATE UNIQUE INDEX IF NOT EXISTS auth_sessions_refresh_token_hash_uq
+ON auth_sessions(refresh_token_hash);
+
+CREATE INDEX IF NOT EXISTS auth_sessions_previous_refresh_token_hash_idx
+ON auth_sessions(previous_refresh_token_hash)
+WHERE previous_refresh_token_hash IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS auth_sessions_user_id_idx
+ON auth_sessions(user_id);
+
+CREATE INDEX IF NOT EXISTS auth_sessions_refresh_expires_at_idx
+ON auth_sessions(refresh_expires_at);
@@ -0,0 +1,21 @@
+-- TOTP (RFC 6238) second factor for local logins.
+--
+-- `totp_pending_secret` holds a secret between enrollment and the first confirmed
+-- code; it only moves to `totp_secret` once the user proves their authenticator
+-- works. Recovery codes are stored as pbkdf2 hashes (same format as passwords) and
+-- removed from the array when used. `totp_last_step` blocks replay of a code within
+-- its validity window.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_secret TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_pending_secret TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_enabled_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_last_step BIGINT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_recovery_codes JSONB NOT NULL DEFAULT '[]'::jsonb;
+
+-- Per-role login policy. Users of a role with `require_totp` who have not enrolled
+-- yet can still sign in, but get no capabilities until they finish enrollment.
+CREATE TABLE IF NOT EXISTS auth_role_policies (
+  role TEXT PRIMARY KEY,
+  require_totp BOOLEAN NOT NULL DEFAULT FALSE,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,14 @@
+-- OpenID Connect single sign-on.
+--
+-- Users signed in through the identity provider are linked by (issuer, subject);
+-- the email match is only used the first time. `password_login_disabled` turns off
+-- /api/auth/login for a user so they can only sign in through SSO. Users created
+-- just-in-time by SSO have no password and start with it set.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS oidc_issuer TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS oidc_subject TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS password_login_disabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_oidc_identity_idx
+  ON users (oidc_issuer, oidc_subject)
+  WHERE oidc_subject IS NOT NULL;
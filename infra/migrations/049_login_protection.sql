@@ -0,0 +1,36 @@
+-- Brute-force protection for password logins, plus a security event trail.
+--
+-- One counter row per account (lower-cased email, whether or not the user exists)
+-- and per client IP. Failures within the reset window back off exponentially and
+-- eventually lock the key until `locked_until`; a successful login clears the
+-- account row, an admin unlock clears it too.
+
+CREATE TABLE IF NOT EXISTS login_attempt_counters (
+  scope TEXT NOT NULL CHECK (scope IN ('account', 'ip')),
+  key TEXT NOT NULL,
+  failures INTEGER NOT NULL DEFAULT 0,
+  first_failure_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  last_failure_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  locked_until TIMESTAMPTZ,
+  PRIMARY KEY (scope, key)
+);
+
+CREATE INDEX IF NOT EXISTS login_attempt_counters_last_failure_idx
+  ON login_attempt_counters (last_failure_at);
+
+CREATE TABLE IF NOT EXISTS security_events (
+  id BIGSERIAL PRIMARY KEY,
+  occurred_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  event_type TEXT NOT NULL,
+  user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+  email TEXT,
+  client_ip TEXT,
+  user_agent TEXT,
+  actor_id TEXT,
+  actor_email TEXT,
+  details JSONB NOT NULL DEFAULT '{}'::jsonb
+);
+
+CREATE INDEX IF NOT EXISTS security_events_occurred_at_idx ON security_events (occurred_at DESC);
+CREATE INDEX IF NOT EXISTS security_events_user_idx ON security_events (user_id, occurred_at DESC);
+CREATE INDEX IF NOT EXISTS security_events_type_idx ON security_events (event_type, occurred_at DESC);
@@ -0,0 +1,53 @@
+-- Resource-scoped capability grants.
+--
+-- A grant binds one capability of a user or API token to a node, a node group or
+-- a map layer. Once a principal has any grant for a capability, that capability
+-- only applies to the nodes its grants cover; capabilities without grants keep
+-- their global meaning.
+
+CREATE TABLE IF NOT EXISTS node_groups (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  name TEXT NOT NULL UNIQUE,
+  description TEXT NOT NULL DEFAULT '',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS node_group_members (
+  group_id UUID NOT NULL REFERENCES node_groups(id) ON DELETE CASCADE,
+  node_id UUID NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+  PRIMARY KEY (group_id, node_id)
+);
+
+CREATE INDEX IF NOT EXISTS node_group_members_node_idx
+  ON node_group_members (node_id);
+
+-- Nodes a map layer (e.g. a field or greenhouse overlay) stands for.
+CREATE TABLE IF NOT EXISTS map_layer_nodes (
+  layer_id BIGINT NOT NULL REFERENCES map_layers(id) ON DELETE CASCADE,
+  node_id UUID NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+  PRIMARY KEY (layer_id, node_id)
+);
+
+CREATE INDEX IF NOT EXISTS map_layer_nodes_node_idx
+  ON map_layer_nodes (node_id);
+
+CREATE TABLE IF NOT EXISTS capability_grants (
+  id BIGSERIAL PRIMARY KEY,
+  user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+  api_token_id UUID REFERENCES api_tokens(id) ON DELETE CASCADE,
+  capability TEXT NOT NULL,
+  resource_type TEXT NOT NULL CHECK (resource_type IN ('node', 'node_group', 'map_layer')),
+  resource_id TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  created_by TEXT,
+  CHECK ((user_id IS NULL) <> (api_token_id IS NULL))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS capability_grants_user_uniq
+  ON capability_grants (user_id, capability, resource_type, resource_id)
+  WHERE user_id IS NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS capability_grants_token_uniq
+  ON capability_grants (api_token_id, capability, resource_type, resource_id)
+  WHERE api_token_id IS NOT NULL;
@@ -0,0 +1,69 @@
+-- Offsite controller backups.
+--
+-- A backup target is a place full-controller snapshots are pushed to: an
+-- S3-compatible bucket, an SFTP server or a mounted path. Snapshots are encrypted
+-- on the controller before upload; the key never lives in this database (it comes
+-- from CORE_BACKUP_ENCRYPTION_PASSPHRASE / CORE_BACKUP_ENCRYPTION_KEY_FILE).
+
+CREATE TABLE IF NOT EXISTS backup_targets (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  name TEXT NOT NULL UNIQUE,
+  kind TEXT NOT NULL CHECK (kind IN ('s3', 'sftp', 'path')),
+  -- Endpoint, bucket, host, prefix and similar non-secret settings.
+  config JSONB NOT NULL DEFAULT '{}'::jsonb,
+  -- Access keys and passwords; never returned by the API.
+  secrets JSONB NOT NULL DEFAULT '{}'::jsonb,
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  interval_hours INTEGER NOT NULL DEFAULT 24 CHECK (interval_hours > 0),
+  include_map_packs BOOLEAN NOT NULL DEFAULT FALSE,
+  keep_daily INTEGER NOT NULL DEFAULT 7 CHECK (keep_daily >= 0),
+  keep_weekly INTEGER NOT NULL DEFAULT 4 CHECK (keep_weekly >= 0),
+  keep_monthly INTEGER NOT NULL DEFAULT 12 CHECK (keep_monthly >= 0),
+  last_run_at TIMESTAMPTZ,
+  last_status TEXT,
+  last_error TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS offsite_backups (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  target_id UUID NOT NULL REFERENCES backup_targets(id) ON DELETE CASCADE,
+  object_key TEXT NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('running', 'ok', 'error', 'pruned')),
+  trigger TEXT NOT NULL DEFAULT 'schedule',
+  -- Digest of the plaintext archive, checked after decryption on verify/restore.
+  archive_sha256 TEXT,
+  -- Digest and size of the encrypted object as stored on the target.
+  object_sha256 TEXT,
+  size_bytes BIGINT,
+  components JSONB NOT NULL DEFAULT '[]'::jsonb,
+  message TEXT,
+  verified_at TIMESTAMPTZ,
+  verify_status TEXT,
+  verify_message TEXT,
+  started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  finished_at TIMESTAMPTZ,
+  pruned_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS offsite_backups_target_idx
+  ON offsite_backups (target_id, started_at DESC);
+
+-- Controller restores from an offsite snapshot, processed by the restore worker.
+CREATE TABLE IF NOT EXISTS offsite_restore_jobs (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  offsite_backup_id UUID NOT NULL REFERENCES offsite_backups(id) ON DELETE CASCADE,
+  components JSONB NOT NULL DEFAULT '[]'::jsonb,
+  status TEXT NOT NULL CHECK (status IN ('queued', 'running', 'ok', 'error')),
+  message TEXT,
+  actor_user_id UUID,
+  actor_email TEXT,
+  started_at TIMESTAMPTZ,
+  finished_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS offsite_restore_jobs_status_idx
+  ON offsite_restore_jobs (status, created_at);
@@ -0,0 +1,21 @@
+-- Backup verification runs.
+--
+-- A run exports the controller database, restores it into a scratch database,
+-- applies the latest node backup bundles there and records integrity checks.
+-- Failures raise the "Backup verification failed" alarm (origin backup_verify).
+
+CREATE TABLE IF NOT EXISTS backup_verification_runs (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  trigger TEXT NOT NULL DEFAULT 'schedule',
+  -- pass: every check passed; fail: a check failed; error: the run itself broke.
+  status TEXT NOT NULL CHECK (status IN ('running', 'pass', 'fail', 'error')),
+  dump_bytes BIGINT,
+  -- [{"name": ..., "status": "pass"|"fail"|"skip", "detail": ...}]
+  checks JSONB NOT NULL DEFAULT '[]'::jsonb,
+  summary TEXT,
+  started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS backup_verification_runs_started_idx
+  ON backup_verification_runs (started_at DESC);
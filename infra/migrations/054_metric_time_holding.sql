@@ -0,0 +1,26 @@
+-- Samples node-forwarder could not re-base onto a synced clock.
+--
+-- Nodes without an RTC can spool samples stamped with a bogus wall clock (1970, or days off)
+-- after a power cut. node-forwarder re-bases them using the first clock sync of the same boot
+-- session; samples from a boot that never synced land here instead of in metrics.
+
+CREATE TABLE IF NOT EXISTS metric_time_holding (
+  id BIGSERIAL PRIMARY KEY,
+  node_mqtt_id TEXT NOT NULL,
+  sensor_id TEXT NOT NULL,
+  -- Wall-clock timestamp as reported by the unsynced node.
+  reported_ts TIMESTAMPTZ NOT NULL,
+  value DOUBLE PRECISION NOT NULL,
+  quality INTEGER NOT NULL DEFAULT 0,
+  stream_id UUID,
+  seq BIGINT,
+  mono_ms BIGINT,
+  boot_id TEXT,
+  -- never_synced | hold_expired | no_boot_anchor
+  reason TEXT NOT NULL,
+  received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  UNIQUE (node_mqtt_id, stream_id, seq)
+);
+
+CREATE INDEX IF NOT EXISTS metric_time_holding_sensor_idx
+  ON metric_time_holding (sensor_id, received_at DESC);
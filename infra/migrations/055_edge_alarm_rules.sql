@@ -0,0 +1,37 @@
+-- Alarm rules evaluated on nodes while the controller is unreachable.
+--
+-- The controller compiles the supported subset of each opted-in rule into a per-node bundle that
+-- node-forwarder evaluates against local samples. Transitions the node decided on its own come back
+-- through the spool and are parked in edge_alarm_events until the alarm engine reconciles them with
+-- alarm_events and incidents.
+
+CREATE TABLE IF NOT EXISTS alarm_rule_edge (
+  rule_id BIGINT PRIMARY KEY REFERENCES alarm_rules(id) ON DELETE CASCADE,
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  -- {"output_id": "...", "state": "..."} applied on the node when the rule fires / clears.
+  fire_action JSONB,
+  clear_action JSONB,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS edge_alarm_events (
+  id BIGSERIAL PRIMARY KEY,
+  node_mqtt_id TEXT NOT NULL,
+  stream_id UUID,
+  seq BIGINT,
+  -- edge-alarm.<rule_id>.<target hash>
+  event_sensor_id TEXT NOT NULL,
+  rule_id BIGINT NOT NULL,
+  transition TEXT NOT NULL CHECK (transition IN ('fired', 'resolved')),
+  observed_value DOUBLE PRECISION,
+  occurred_at TIMESTAMPTZ NOT NULL,
+  received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  reconciled_at TIMESTAMPTZ,
+  -- applied | duplicate | superseded | unknown_target
+  outcome TEXT,
+  UNIQUE (node_mqtt_id, stream_id, seq)
+);
+
+CREATE INDEX IF NOT EXISTS edge_alarm_events_pending_idx
+  ON edge_alarm_events (occurred_at)
+  WHERE reconciled_at IS NULL;
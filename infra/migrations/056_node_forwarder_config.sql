@@ -0,0 +1,25 @@
+-- node-forwarder settings managed from the controller.
+--
+-- The controller publishes one document per node, retained, on iot/<node>/forwarder-config.
+-- node-forwarder validates and applies it without a restart and reports the outcome, retained, on
+-- iot/<node>/forwarder-config/state. Comparing the two shows drift.
+
+CREATE TABLE IF NOT EXISTS node_forwarder_config (
+  node_id UUID PRIMARY KEY REFERENCES nodes(id) ON DELETE CASCADE,
+  -- Overrides for node-forwarder knobs; absent keys keep the node's environment value.
+  settings JSONB NOT NULL DEFAULT '{}'::jsonb,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_by TEXT
+);
+
+-- Latest report from each forwarder, keyed by the id it publishes under.
+CREATE TABLE IF NOT EXISTS node_forwarder_config_state (
+  node_mqtt_id TEXT PRIMARY KEY,
+  applied_version TEXT,
+  applied_at TIMESTAMPTZ,
+  rejected_version TEXT,
+  error TEXT,
+  -- Values the forwarder is running with after overlaying the applied document.
+  effective JSONB,
+  reported_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,34 @@
+-- Data lost by node-forwarder, one row per reported loss range.
+--
+-- telemetry-sidecar records each range the first time a forwarder reports it (forwarders republish
+-- open ranges, so the seq range is the dedupe key) and fills time_start/time_end from the samples on
+-- either side of the gap. core-server turns each row into a chart annotation and, when enabled,
+-- raises a per-node alarm.
+
+CREATE TABLE IF NOT EXISTS node_forwarder_loss_events (
+  id BIGSERIAL PRIMARY KEY,
+  node_mqtt_id TEXT NOT NULL,
+  stream_id UUID NOT NULL,
+  start_seq BIGINT NOT NULL,
+  end_seq BIGINT NOT NULL,
+  -- What the forwarder did with the data: dropped, expired, corrupt, ...
+  kind TEXT NOT NULL DEFAULT 'dropped',
+  reason TEXT,
+  dropped_at TIMESTAMPTZ,
+  -- Timestamp of the last sample before the gap and the first sample after it.
+  time_start TIMESTAMPTZ,
+  time_end TIMESTAMPTZ,
+  received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  -- Set once core-server has written the chart annotation, even if an operator later deletes it.
+  annotation_id UUID,
+  annotated_at TIMESTAMPTZ,
+  alarm_notified_at TIMESTAMPTZ,
+  UNIQUE (node_mqtt_id, stream_id, start_seq, end_seq)
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_forwarder_loss_events_node
+  ON node_forwarder_loss_events (node_mqtt_id, received_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_node_forwarder_loss_events_unannotated
+  ON node_forwarder_loss_events (received_at)
+  WHERE annotated_at IS NULL;
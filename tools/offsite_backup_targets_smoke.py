@@ -0,0 +1,153 @@
+#!/usr/bin/env python3
+"""Offsite backup smoke against local MinIO and SFTP stand-ins.
+
+Starts `minio/minio` and `atmoz/sftp` containers, registers them as backup
+targets on a running core-server, takes a snapshot on each, waits for it and
+runs an integrity verification. The controller must have
+CORE_BACKUP_ENCRYPTION_PASSPHRASE (or _KEY_FILE) set.
+
+    python3 tools/offsite_backup_targets_smoke.py --api-base http://127.0.0.1:8000 --token $TOKEN
+"""
+from __future__ import annotations
+
+import argparse
+import base64
+import hashlib
+import json
+import subprocess
+import sys
+import time
+import urllib.error
+import urllib.request
+
+MINIO_NAME = "farm-offsite-smoke-minio"
+SFTP_NAME = "farm-offsite-smoke-sftp"
+MINIO_PORT = 19000
+SFTP_PORT = 12222
+
+
+def api(base: str, token: str, method: str, path: str, body: object | None = None) -> object:
+    data = json.dumps(body).encode() if body is not None else None
+    req = urllib.request.Request(f"{base}{path}", data=data, method=method)
+    req.add_header("Authorization", f"Bearer {token}")
+    if data is not None:
+        req.add_header("Content-Type", "application/json")
+    try:
+        with urllib.request.urlopen(req, timeout=60) as resp:
+            raw = resp.read()
+    except urllib.error.HTTPError as exc:
+        raise SystemExit(f"{method} {path} -> {exc.code}: {exc.read().decode(errors='replace')}")
+    return json.loads(raw) if raw else None
+
+
+def docker(*args: str) -> str:
+    return subprocess.run(["docker", *args], check=True, capture_output=True, text=True).stdout
+
+
+def start_stand_ins() -> None:
+    for name in (MINIO_NAME, SFTP_NAME):
+        subprocess.run(["docker", "rm", "-f", name], capture_output=True)
+    docker(
+        "run", "-d", "--name", MINIO_NAME, "-p", f"{MINIO_PORT}:9000",
+        "-e", "MINIO_ROOT_USER=farmsmoke", "-e", "MINIO_ROOT_PASSWORD=farmsmoke123",
+        "--entrypoint", "sh", "minio/minio",
+        "-c", "mkdir -p /data/farm-backups && minio server /data",
+    )
+    docker("run", "-d", "--name", SFTP_NAME, "-p", f"{SFTP_PORT}:22", "atmoz/sftp", "farm:farm:::upload")
+    time.sleep(5)
+
+
+def sftp_host_key_sha256() -> str:
+    # ssh-keyscan prints "host type base64key"; the fingerprint is sha256 of the key blob.
+    out = subprocess.run(
+        ["ssh-keyscan", "-t", "ed25519", "-p", str(SFTP_PORT), "127.0.0.1"],
+        check=True, capture_output=True, text=True,
+    ).stdout
+    blob = base64.b64decode(out.split()[2])
+    return "SHA256:" + base64.b64encode(hashlib.sha256(blob).digest()).decode().rstrip("=")
+
+
+def wait_for_snapshot(base: str, token: str, target_id: str, snapshot_id: str) -> dict:
+    for _ in range(120):
+        for snap in api(base, token, "GET", f"/api/backups/targets/{target_id}/snapshots"):
+            if snap["id"] == snapshot_id and snap["status"] != "running":
+                return snap
+        time.sleep(2)
+    raise SystemExit(f"snapshot {snapshot_id} did not finish")
+
+
+def wait_for_verify(base: str, token: str, target_id: str, snapshot_id: str) -> dict:
+    for _ in range(120):
+        for snap in api(base, token, "GET", f"/api/backups/targets/{target_id}/snapshots"):
+            if snap["id"] == snapshot_id and snap.get("verify_status"):
+                return snap
+        time.sleep(2)
+    raise SystemExit(f"snapshot {snapshot_id} was not verified")
+
+
+def exercise(base: str, token: str, payload: dict) -> None:
+    target = api(base, token, "POST", "/api/backups/targets", payload)
+    try:
+        check = api(base, token, "POST", f"/api/backups/targets/{target['id']}/test")
+        if not check["ok"]:
+            raise SystemExit(f"{payload['name']}: target check failed: {check['message']}")
+        started = api(base, token, "POST", f"/api/backups/targets/{target['id']}/run")
+        snap = wait_for_snapshot(base, token, target["id"], started["id"])
+        if snap["status"] != "ok":
+            raise SystemExit(f"{payload['name']}: snapshot failed: {snap.get('message')}")
+        api(base, token, "POST", f"/api/backups/offsite/{snap['id']}/verify")
+        verified = wait_for_verify(base, token, target["id"], snap["id"])
+        if verified["verify_status"] != "ok":
+            raise SystemExit(f"{payload['name']}: verify failed: {verified.get('verify_message')}")
+        print(f"{payload['name']}: ok ({snap['size_bytes']} bytes, {', '.join(snap['components'])})")
+    finally:
+        api(base, token, "DELETE", f"/api/backups/targets/{target['id']}")
+
+
+def main() -> int:
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--api-base", default="http://127.0.0.1:8000")
+    parser.add_argument("--token", required=True)
+    parser.add_argument("--keep-containers", action="store_true")
+    args = parser.parse_args()
+
+    status = api(args.api_base, args.token, "GET", "/api/backups/targets")
+    if not status["encryption_configured"]:
+        print("core-server has no backup encryption key configured", file=sys.stderr)
+        return 2
+
+    start_stand_ins()
+    try:
+        exercise(args.api_base, args.token, {
+            "name": "smoke-minio",
+            "kind": "s3",
+            "config": {
+                "endpoint": f"http://127.0.0.1:{MINIO_PORT}",
+                "bucket": "farm-backups",
+                "prefix": "controller",
+                "force_path_style": True,
+            },
+            "secrets": {"access_key_id": "farmsmoke", "secret_access_key": "farmsmoke123"},
+            "keep_daily": 2, "keep_weekly": 1, "keep_monthly": 1,
+        })
+        exercise(args.api_base, args.token, {
+            "name": "smoke-sftp",
+            "kind": "sftp",
+            "config": {
+                "host": "127.0.0.1",
+                "port": SFTP_PORT,
+                "username": "farm",
+                "path": "/upload",
+                "host_key_sha256": sftp_host_key_sha256(),
+            },
+            "secrets": {"password": "farm"},
+        })
+    finally:
+        if not args.keep_containers:
+            for name in (MINIO_NAME, SFTP_NAME):
+                subprocess.run(["docker", "rm", "-f", name], capture_output=True)
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())